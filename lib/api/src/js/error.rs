@@ -192,7 +192,7 @@ pub enum InstantiationError {
 
     /// The module was compiled with a CPU feature that is not available on
     /// the current host.
-    #[cfg_attr(feature = "std", error("missing required CPU features: {0:?}"))]
+    #[cfg_attr(feature = "std", error("missing required CPU features: {0}"))]
     CpuFeature(String),
 
     /// Import from a different [`Store`].
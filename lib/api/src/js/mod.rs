@@ -77,7 +77,7 @@ pub mod vm {
     pub use crate::js::export::VMMemory;
 }
 
-pub use wasmer_types::is_wasm;
+pub use wasmer_types::{attach_metadata, is_wasm, strip_custom_sections};
 pub use wasmer_types::{
     Bytes, ExportIndex, GlobalInit, LocalFunctionIndex, Pages, ValueType, WASM_MAX_PAGES,
     WASM_MIN_PAGES, WASM_PAGE_SIZE,
@@ -96,6 +96,28 @@ impl<T, M: MemorySize> WasmPtr<T, M> {
         self.offset.into() == 0
     }
 
+    /// Checks whether the address is a multiple of the alignment of `T`.
+    #[inline]
+    pub fn is_aligned(self) -> bool {
+        self.offset.into() % mem::align_of::<T>() as u64 == 0
+    }
+
+    /// Returns this pointer if the address is a multiple of the alignment
+    /// of `T`, or an error otherwise.
+    ///
+    /// Accesses through a `WasmPtr` don't need to be aligned, but guest
+    /// code hands out aligned pointers to well-formed values, so this can
+    /// reject the others before reading them:
+    /// `ptr.aligned()?.read(&view)`.
+    #[inline]
+    pub fn aligned(self) -> Result<Self, MemoryAccessError> {
+        if self.is_aligned() {
+            Ok(self)
+        } else {
+            Err(MemoryAccessError::Unaligned)
+        }
+    }
+
     /// Calculates an offset from the current pointer address. The argument is
     /// in units of `T`.
     ///
@@ -217,6 +239,36 @@ impl<M: MemorySize> WasmPtr<u8, M> {
         let vec = self.read_until(view, |&byte| byte == 0)?;
         Ok(String::from_utf8(vec)?)
     }
+
+    /// Writes `string` at the `WasmPtr`, without a terminating null byte.
+    ///
+    /// Returns an error if the string doesn't fit in the memory.
+    #[inline]
+    pub fn write_utf8_string(
+        self,
+        view: &MemoryView,
+        string: &str,
+    ) -> Result<(), MemoryAccessError> {
+        let len =
+            M::Offset::try_from(string.len() as u64).map_err(|_| MemoryAccessError::Overflow)?;
+        self.slice(view, len)?.write_slice(string.as_bytes())
+    }
+
+    /// Writes `string` at the `WasmPtr`, followed by a null byte.
+    ///
+    /// Returns an error if the string doesn't fit in the memory. A string
+    /// containing a null byte is read back only up to it.
+    #[inline]
+    pub fn write_utf8_string_with_nul(
+        self,
+        view: &MemoryView,
+        string: &str,
+    ) -> Result<(), MemoryAccessError> {
+        let len =
+            M::Offset::try_from(string.len() as u64).map_err(|_| MemoryAccessError::Overflow)?;
+        self.write_utf8_string(view, string)?;
+        self.add_offset(len)?.write(view, 0)
+    }
 }
 
 unsafe impl<T: ValueType, M: MemorySize> FromToNativeWasmType for WasmPtr<T, M>
@@ -87,7 +87,8 @@
 //!   is possible to serialize it in a file for example, and later execute
 //!   it with Wasmer with headless mode turned on. Headless Wasmer has no
 //!   compiler, which makes it more portable and faster to load. It's
-//!   ideal for constrainted environments.
+//!   ideal for constrainted environments. Build `wasmer` with the
+//!   `headless` feature alone to leave every compiler out of the binary.
 //!   
 //! * **Cross-compilation** — Most compilers support cross-compilation. It
 //!   means it possible to pre-compile a WebAssembly module targetting a
@@ -307,10 +308,22 @@
 #![cfg_attr(feature = "wat", doc = "(enabled),")]
 #![cfg_attr(not(feature = "wat"), doc = "(disabled),")]
 //!   enables `wasmer` to parse the WebAssembly text format,
-//! - `compilation`
+//! - `compiler`
 #![cfg_attr(feature = "compiler", doc = "(enabled),")]
 #![cfg_attr(not(feature = "compiler"), doc = "(disabled),")]
-//!   enables compilation with the wasmer engine.
+//!   enables compilation with the wasmer engine, and is enabled by each
+//!   of the compilers above,
+//! - `headless`
+#![cfg_attr(feature = "headless", doc = "(enabled),")]
+#![cfg_attr(not(feature = "headless"), doc = "(disabled),")]
+//!   enables the runtime alone, with no compiler linked in: modules
+//!   compiled ahead of time are loaded with [`Module::deserialize`] and
+//!   run by the headless engine of [`Store::default`],
+//! - `tracing`
+#![cfg_attr(feature = "tracing", doc = "(enabled),")]
+#![cfg_attr(not(feature = "tracing"), doc = "(disabled),")]
+//!   emits [`tracing`](https://docs.rs/tracing) spans for validation,
+//!   compilation, instantiation and calls, and events for traps.
 //!
 //! The features that set defaults come in sets that are mutually exclusive.
 //!
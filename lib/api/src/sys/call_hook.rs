@@ -0,0 +1,88 @@
+use crate::sys::RuntimeError;
+use wasmer_compiler::FRAME_INFO;
+use wasmer_vm::{VMCallerCheckedAnyfunc, VMContext};
+
+/// A transition between the host and WebAssembly, reported to the hook
+/// set with [`Store::set_call_hook`](crate::Store::set_call_hook).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CallHook {
+    /// The host is calling a function of the store, from
+    /// [`Function::call`](crate::Function::call) or a
+    /// [`TypedFunction`](crate::TypedFunction).
+    CallingWasm,
+    /// A call started with [`CallHook::CallingWasm`] returned, or trapped.
+    ReturningFromWasm,
+    /// WebAssembly is calling a host function.
+    CallingHost,
+    /// A host function called by WebAssembly returned.
+    ReturningFromHost,
+}
+
+impl CallHook {
+    /// Whether execution enters WebAssembly with this transition.
+    pub fn entering_wasm(self) -> bool {
+        matches!(self, Self::CallingWasm | Self::ReturningFromHost)
+    }
+
+    /// Whether execution leaves WebAssembly with this transition.
+    pub fn exiting_wasm(self) -> bool {
+        !self.entering_wasm()
+    }
+}
+
+/// Identifies an instance while it is alive, see [`Instance::id`].
+///
+/// The id of a dropped instance may be reused by a later one.
+///
+/// [`Instance::id`]: crate::Instance::id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct InstanceId(usize);
+
+impl InstanceId {
+    pub(crate) fn from_vmctx(vmctx: *const VMContext) -> Self {
+        Self(vmctx as usize)
+    }
+}
+
+/// The function a [`CallHook`] transition is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CallHookInfo {
+    /// The index of the function in the function index space of its
+    /// module, or `None` for host functions.
+    pub func_index: Option<u32>,
+    /// The instance defining the function, or `None` for host functions.
+    pub instance: Option<InstanceId>,
+}
+
+impl CallHookInfo {
+    /// The information of a host function.
+    pub(crate) fn host() -> Self {
+        Self {
+            func_index: None,
+            instance: None,
+        }
+    }
+
+    /// The information of the function called through `anyfunc`, looked
+    /// up in the frame information of the compiled modules.
+    pub(crate) fn of(anyfunc: &VMCallerCheckedAnyfunc) -> Self {
+        let frame = FRAME_INFO
+            .read()
+            .unwrap()
+            .lookup_frame_info(anyfunc.func_ptr as usize);
+        match frame {
+            Some(frame) => Self {
+                func_index: Some(frame.func_index()),
+                instance: Some(InstanceId::from_vmctx(unsafe { anyfunc.vmctx.vmctx })),
+            },
+            None => Self::host(),
+        }
+    }
+}
+
+/// The hook called on every [`CallHook`] transition of a store.
+///
+/// An error returned on [`CallHook::CallingWasm`] fails the call before
+/// it enters WebAssembly, any other error traps the running WebAssembly
+/// code, or replaces the results of the call that is returning.
+pub type CallHookFn = dyn FnMut(CallHook, &CallHookInfo) -> Result<(), RuntimeError> + Send;
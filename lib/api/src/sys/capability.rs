@@ -0,0 +1,155 @@
+//! Capability tokens guarding host imports.
+//!
+//! A [`Capability`] is a shared token that can be revoked, or set to
+//! expire, while instances are running. Once the functions of an import
+//! namespace are guarded by a capability with
+//! [`Imports::guard_namespace`], calls the guest makes to them after the
+//! capability is gone are denied, either by trapping or by returning
+//! fixed values, which lets a host cut some privileges of a tenant off
+//! without tearing its instance down.
+//!
+//! [`Imports::guard_namespace`]: crate::Imports::guard_namespace
+
+use crate::sys::Value;
+use crate::sys::{AsStoreMut, Function, FunctionEnv, FunctionEnvMut, RuntimeError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// A revocable token granting access to guarded imports.
+///
+/// Clones share the same state: revoking one revokes all of them.
+#[derive(Debug, Clone)]
+pub struct Capability {
+    inner: Arc<CapabilityInner>,
+}
+
+#[derive(Debug)]
+struct CapabilityInner {
+    name: String,
+    revoked: AtomicBool,
+    expires_at: Mutex<Option<Instant>>,
+}
+
+impl Capability {
+    /// Creates a valid capability, which doesn't expire.
+    ///
+    /// The name is only used in the errors of denied calls.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            inner: Arc::new(CapabilityInner {
+                name: name.into(),
+                revoked: AtomicBool::new(false),
+                expires_at: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// The name of the capability.
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// Revokes the capability, for good.
+    pub fn revoke(&self) {
+        self.inner.revoked.store(true, Ordering::SeqCst);
+    }
+
+    /// Makes the capability expire once `ttl` has elapsed, replacing any
+    /// expiry set before.
+    pub fn expire_after(&self, ttl: Duration) {
+        self.expire_at(Instant::now() + ttl);
+    }
+
+    /// Makes the capability expire at `deadline`, replacing any expiry set
+    /// before.
+    pub fn expire_at(&self, deadline: Instant) {
+        *self.inner.expires_at.lock().unwrap() = Some(deadline);
+    }
+
+    /// Checks whether the capability still grants access.
+    pub fn check(&self) -> Result<(), CapabilityError> {
+        if self.inner.revoked.load(Ordering::SeqCst) {
+            return Err(CapabilityError::Revoked(self.inner.name.clone()));
+        }
+        match *self.inner.expires_at.lock().unwrap() {
+            Some(deadline) if Instant::now() >= deadline => {
+                Err(CapabilityError::Expired(self.inner.name.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns whether the capability still grants access.
+    pub fn is_valid(&self) -> bool {
+        self.check().is_ok()
+    }
+}
+
+/// The reason a call to a guarded import was denied.
+///
+/// Calls that trap do so with this error, which can be retrieved with
+/// [`RuntimeError::downcast`].
+///
+/// [`RuntimeError::downcast`]: crate::RuntimeError::downcast
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CapabilityError {
+    /// The capability was revoked.
+    #[error("the `{0}` capability has been revoked")]
+    Revoked(String),
+
+    /// The capability expired.
+    #[error("the `{0}` capability has expired")]
+    Expired(String),
+}
+
+/// What guarded imports do when they are called without a valid
+/// capability.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Denial {
+    /// Trap with a [`CapabilityError`].
+    Trap,
+    /// Return these values to the guest, for instance an error code.
+    ///
+    /// Functions whose results don't have the types of the values trap
+    /// instead.
+    Return(Vec<Value>),
+}
+
+impl Default for Denial {
+    fn default() -> Self {
+        Self::Trap
+    }
+}
+
+/// Wraps `function` into a host function that only calls it while
+/// `capability` is valid.
+pub(crate) fn guard(
+    store: &mut impl AsStoreMut,
+    function: Function,
+    capability: Capability,
+    denial: Denial,
+) -> Function {
+    let ty = function.ty(store);
+    let results = ty.results().to_vec();
+    let env = FunctionEnv::new(&mut store.as_store_mut(), ());
+    Function::new_with_env(
+        store,
+        &env,
+        ty,
+        move |mut env: FunctionEnvMut<()>, args: &[Value]| {
+            if let Err(error) = capability.check() {
+                return match &denial {
+                    Denial::Return(values)
+                        if values.iter().map(Value::ty).eq(results.iter().copied()) =>
+                    {
+                        Ok(values.clone())
+                    }
+                    _ => Err(RuntimeError::user(Box::new(error))),
+                };
+            }
+            function.call(&mut env, args).map(Vec::from)
+        },
+    )
+}
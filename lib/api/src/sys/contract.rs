@@ -0,0 +1,290 @@
+//! ABI contracts checked against the exports of new instances.
+//!
+//! An [`AbiContract`] lists the exports a host expects from the modules
+//! it runs, such as plugins. Once registered with
+//! [`Imports::require_contract`], it is verified before every
+//! instantiation with these imports, so a module that doesn't implement
+//! the ABI of the host is rejected with the list of its differences
+//! before any of its code runs, instead of trapping the first time the
+//! host calls it.
+//!
+//! [`Imports::require_contract`]: crate::Imports::require_contract
+
+use crate::sys::{AsStoreRef, Exports, Extern, Module};
+use std::collections::HashMap;
+use std::fmt;
+use thiserror::Error;
+use wasmer_types::{ExternType, FunctionType, GlobalType, MemoryType, Pages, TableType, Type};
+
+/// A set of exports a module must provide.
+///
+/// # Usage
+/// ```
+/// # use wasmer::{AbiContract, FunctionType, Pages, Type};
+/// let contract = AbiContract::new("host ABI v3")
+///     .function("alloc", FunctionType::new(vec![Type::I32], vec![Type::I32]))
+///     .function("handle", FunctionType::new(vec![Type::I32, Type::I32], vec![]))
+///     .memory("memory", Pages(1));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct AbiContract {
+    name: String,
+    exports: Vec<(String, ExpectedExport)>,
+}
+
+/// An export expected by an [`AbiContract`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpectedExport {
+    /// A function with exactly this type.
+    Function(FunctionType),
+    /// A global with exactly this type.
+    Global(GlobalType),
+    /// A memory with at least this many pages.
+    Memory(Pages),
+    /// A table holding these elements, with at least this many of them.
+    Table(Type, u32),
+}
+
+impl AbiContract {
+    /// Creates a contract without any export.
+    ///
+    /// The name, typically the name and version of the ABI, is used in
+    /// the errors of the modules that don't match the contract.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            exports: Vec::new(),
+        }
+    }
+
+    /// The name of the contract.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The exports the contract expects, in the order they were added.
+    pub fn exports(&self) -> impl Iterator<Item = (&str, &ExpectedExport)> {
+        self.exports
+            .iter()
+            .map(|(name, expected)| (name.as_str(), expected))
+    }
+
+    /// Expects an export, replacing any expectation set before for the
+    /// same name.
+    pub fn export(mut self, name: impl Into<String>, expected: ExpectedExport) -> Self {
+        let name = name.into();
+        match self.exports.iter_mut().find(|(other, _)| *other == name) {
+            Some((_, previous)) => *previous = expected,
+            None => self.exports.push((name, expected)),
+        }
+        self
+    }
+
+    /// Expects a function export with the type `ty`.
+    pub fn function(self, name: impl Into<String>, ty: FunctionType) -> Self {
+        self.export(name, ExpectedExport::Function(ty))
+    }
+
+    /// Expects a global export with the type `ty`.
+    pub fn global(self, name: impl Into<String>, ty: GlobalType) -> Self {
+        self.export(name, ExpectedExport::Global(ty))
+    }
+
+    /// Expects a memory export with at least `minimum` pages.
+    pub fn memory(self, name: impl Into<String>, minimum: Pages) -> Self {
+        self.export(name, ExpectedExport::Memory(minimum))
+    }
+
+    /// Expects a table export of `ty` elements, with at least `minimum`
+    /// of them.
+    pub fn table(self, name: impl Into<String>, ty: Type, minimum: u32) -> Self {
+        self.export(name, ExpectedExport::Table(ty, minimum))
+    }
+
+    /// Verifies the exports of a module against the contract before it
+    /// is instantiated, returning all of their differences if they don't
+    /// match it.
+    ///
+    /// Memories and tables are checked with the size they are declared
+    /// with, which is the size they have once instantiated.
+    pub fn verify_module(&self, module: &Module) -> Result<(), ContractViolation> {
+        let exports = module
+            .exports()
+            .map(|export| (export.name().to_string(), export.ty().clone()))
+            .collect::<HashMap<_, _>>();
+        self.verify_types(|name| exports.get(name).cloned())
+    }
+
+    /// Verifies the exports of an instance against the contract,
+    /// returning all of their differences if they don't match it.
+    ///
+    /// Memories and tables are checked with their current size.
+    pub fn verify(
+        &self,
+        store: &impl AsStoreRef,
+        exports: &Exports,
+    ) -> Result<(), ContractViolation> {
+        self.verify_types(|name| {
+            exports
+                .get_extern(name)
+                .map(|found| current_type(store, found))
+        })
+    }
+
+    fn verify_types(
+        &self,
+        find: impl Fn(&str) -> Option<ExternType>,
+    ) -> Result<(), ContractViolation> {
+        let mismatches = self
+            .exports
+            .iter()
+            .filter_map(|(name, expected)| {
+                let found = match find(name) {
+                    Some(found) => found,
+                    None => {
+                        return Some(ExportMismatch::Missing {
+                            name: name.clone(),
+                            expected: expected.clone(),
+                        })
+                    }
+                };
+                verify_export(name, expected, &found)
+            })
+            .collect::<Vec<_>>();
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(ContractViolation {
+                contract: self.name.clone(),
+                mismatches,
+            })
+        }
+    }
+}
+
+/// The type of an export of an instance, with the current size of its
+/// memory or table.
+fn current_type(store: &impl AsStoreRef, found: &Extern) -> ExternType {
+    match found {
+        Extern::Memory(memory) => ExternType::Memory(MemoryType {
+            minimum: memory.view(store).size(),
+            ..memory.ty(store)
+        }),
+        Extern::Table(table) => ExternType::Table(TableType {
+            minimum: table.size(store),
+            ..table.ty(store)
+        }),
+        _ => found.ty(store),
+    }
+}
+
+fn verify_export(
+    name: &str,
+    expected: &ExpectedExport,
+    found: &ExternType,
+) -> Option<ExportMismatch> {
+    let mismatch = |found: String| {
+        Some(ExportMismatch::Type {
+            name: name.to_string(),
+            expected: expected.clone(),
+            found,
+        })
+    };
+    match (expected, found) {
+        (ExpectedExport::Function(ty), ExternType::Function(found)) => {
+            if found == ty {
+                None
+            } else {
+                mismatch(format!("function {}", found))
+            }
+        }
+        (ExpectedExport::Global(ty), ExternType::Global(found)) => {
+            if found == ty {
+                None
+            } else {
+                mismatch(format!("global {}", found))
+            }
+        }
+        (ExpectedExport::Memory(minimum), ExternType::Memory(found)) => {
+            if found.minimum >= *minimum {
+                None
+            } else {
+                mismatch(format!("memory of {} pages", found.minimum.0))
+            }
+        }
+        (ExpectedExport::Table(ty, minimum), ExternType::Table(found)) => {
+            if found.ty == *ty && found.minimum >= *minimum {
+                None
+            } else {
+                mismatch(format!("table of {} {} elements", found.minimum, found.ty))
+            }
+        }
+        (_, found) => mismatch(format!("{} export", kind(found))),
+    }
+}
+
+fn kind(extern_type: &ExternType) -> &'static str {
+    match extern_type {
+        ExternType::Function(_) => "function",
+        ExternType::Global(_) => "global",
+        ExternType::Memory(_) => "memory",
+        ExternType::Table(_) => "table",
+    }
+}
+
+impl fmt::Display for ExpectedExport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Function(ty) => write!(f, "function {}", ty),
+            Self::Global(ty) => write!(f, "global {}", ty),
+            Self::Memory(minimum) => write!(f, "memory of at least {} pages", minimum.0),
+            Self::Table(ty, minimum) => {
+                write!(f, "table of at least {} {} elements", minimum, ty)
+            }
+        }
+    }
+}
+
+/// A difference between the exports of an instance and an
+/// [`AbiContract`].
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ExportMismatch {
+    /// The export is missing.
+    #[error("missing export `{name}`, expected a {expected}")]
+    Missing {
+        /// The name of the export.
+        name: String,
+        /// What the contract expects.
+        expected: ExpectedExport,
+    },
+
+    /// The export doesn't have the expected kind or type.
+    #[error("export `{name}` is a {found}, expected a {expected}")]
+    Type {
+        /// The name of the export.
+        name: String,
+        /// What the contract expects.
+        expected: ExpectedExport,
+        /// A description of the actual export.
+        found: String,
+    },
+}
+
+/// The error of an instance that doesn't match an [`AbiContract`].
+#[derive(Error, Debug, Clone, PartialEq)]
+pub struct ContractViolation {
+    /// The name of the contract.
+    pub contract: String,
+    /// All the differences with the contract.
+    pub mismatches: Vec<ExportMismatch>,
+}
+
+impl fmt::Display for ContractViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the module doesn't match {}:", self.contract)?;
+        for mismatch in &self.mismatches {
+            write!(f, "\n  - {}", mismatch)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+//! Breakpoints and single-stepping in interpreted instances.
+//!
+//! Once [`Instance::set_debugger`] attached a handler to an instance whose
+//! module runs in the [`Interpreter`](crate::Interpreter), the execution
+//! of its functions stops on the breakpoints set with
+//! [`Instance::set_breakpoint`], and before every instruction when
+//! stepping. The handler is called on each stop with a [`DebugFrame`]
+//! holding the locals and operands of the stopped function, and tells
+//! how to resume with a [`DebugAction`].
+//!
+//! [`Instance::set_debugger`]: crate::Instance::set_debugger
+//! [`Instance::set_breakpoint`]: crate::Instance::set_breakpoint
+
+use crate::sys::store::{StoreInner, StoreMut};
+use crate::sys::Value;
+use thiserror::Error;
+use wasmer_compiler::FRAME_INFO;
+use wasmer_vm::{DebugAction, DebugStop, InstanceDebugger, StopReason};
+
+/// A function of an instance stopped by its debugger.
+#[derive(Debug, Clone)]
+pub struct DebugFrame {
+    /// Why the function stopped.
+    pub reason: StopReason,
+    /// The index of the function in the function index space of its
+    /// module.
+    pub func_index: u32,
+    /// The offset of the operator about to run from the start of the
+    /// function body, as used by [`Instance::set_breakpoint`].
+    ///
+    /// [`Instance::set_breakpoint`]: crate::Instance::set_breakpoint
+    pub offset: u32,
+    /// The parameters of the function, followed by its locals.
+    pub locals: Vec<Value>,
+    /// The operand stack of the function, from the bottom up, as untyped
+    /// 64-bit slots.
+    pub operands: Vec<u64>,
+}
+
+/// An error setting a breakpoint or stepping.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum DebugError {
+    /// The instance has no debugger.
+    #[error("the instance has no debugger")]
+    NoDebugger,
+    /// The function isn't an interpreted function of the instance, or
+    /// the offset is past its end.
+    #[error("no interpreted instruction at offset {offset} of function {func_index}")]
+    UnknownLocation {
+        /// The index of the function.
+        func_index: u32,
+        /// The offset in the function body.
+        offset: u32,
+    },
+}
+
+/// The store the handler of a debugger runs in.
+struct RawStore(*mut StoreInner);
+
+// The handler only runs on the thread the store is used on, while the
+// instance is running.
+unsafe impl Send for RawStore {}
+
+/// Wraps `handler` into the debugger of an instance of the store `raw`.
+pub(crate) fn instance_debugger<F>(raw: *mut StoreInner, mut handler: F) -> InstanceDebugger
+where
+    F: FnMut(&mut StoreMut<'_>, &DebugFrame) -> DebugAction + Send + 'static,
+{
+    let raw = RawStore(raw);
+    InstanceDebugger::new(Box::new(move |stop: &DebugStop<'_>| {
+        let mut store = unsafe { StoreMut::from_raw(raw.0) };
+        let frame = DebugFrame::new(&mut store, stop);
+        handler(&mut store, &frame)
+    }))
+}
+
+impl DebugFrame {
+    fn new(store: &mut StoreMut<'_>, stop: &DebugStop<'_>) -> Self {
+        let frame = FRAME_INFO
+            .read()
+            .unwrap()
+            .lookup_frame_info(stop.address())
+            .expect("interpreted functions have frame information");
+        let num_locals = stop.local_types().count() as u32;
+        let locals = (0..num_locals)
+            .map(|index| {
+                let (ty, raw) = stop.local(index).unwrap();
+                unsafe { Value::from_raw(store, ty, raw) }
+            })
+            .collect();
+        Self {
+            reason: stop.reason(),
+            func_index: frame.func_index(),
+            offset: frame.func_offset() as u32,
+            locals,
+            operands: stop.operands().to_vec(),
+        }
+    }
+}
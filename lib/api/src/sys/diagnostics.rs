@@ -0,0 +1,384 @@
+//! Runtime state summaries for live debugging.
+//!
+//! Once enabled with [`enable`], every [`Store`] created afterwards
+//! registers itself in a process-wide registry, which keeps track of the
+//! instances it holds, the calls currently running in it and the last traps
+//! it raised. [`report`] renders that state as text.
+//!
+//! Long-running embedders can start a [`DiagnosticServer`], which serves
+//! the report over a local socket so that `wasmer attach <pid>` can print
+//! it without stopping the process.
+//!
+//! [`Store`]: crate::Store
+
+use crate::sys::RuntimeError;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use wasmer_compiler::FRAME_INFO;
+use wasmer_types::WASM_PAGE_SIZE;
+use wasmer_vm::VMMemoryDefinition;
+
+/// How many traps are remembered per store.
+const RECENT_TRAPS: usize = 16;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static NEXT_STORE_ID: AtomicUsize = AtomicUsize::new(1);
+static NEXT_CALL_ID: AtomicUsize = AtomicUsize::new(1);
+
+lazy_static::lazy_static! {
+    static ref REGISTRY: Mutex<Vec<Arc<StoreDiagnostics>>> = Mutex::new(Vec::new());
+}
+
+/// Starts tracking the stores created from now on.
+///
+/// Stores created before this call are not tracked.
+pub fn enable() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Returns whether stores are being tracked.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Renders the state of every tracked store: its tags, its instances and
+/// their memory usage, the calls currently running, by the function they
+/// entered the guest through, and the recent traps.
+///
+/// The memory usage is the one of the last call into the store to start
+/// or return, as the memories can only be read by the thread running it.
+pub fn report() -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "wasmer {} (pid {})",
+        env!("CARGO_PKG_VERSION"),
+        std::process::id()
+    );
+    if !enabled() {
+        let _ = writeln!(out, "diagnostics are not enabled in this process");
+        return out;
+    }
+
+    let registry = REGISTRY.lock().unwrap();
+    let _ = writeln!(out, "{} live store(s)", registry.len());
+    for store in registry.iter() {
+        store.write_report(&mut out);
+    }
+    out
+}
+
+/// The path of the socket a [`DiagnosticServer`] listens on in the process
+/// `pid`.
+pub fn socket_path(pid: u32) -> PathBuf {
+    std::env::temp_dir().join(format!("wasmer-{}.sock", pid))
+}
+
+/// The diagnostic state of a store.
+pub(crate) struct StoreDiagnostics {
+    id: usize,
+    created: Instant,
+    state: Mutex<StoreState>,
+}
+
+#[derive(Default)]
+struct StoreState {
+    tags: BTreeMap<String, String>,
+    instances: Vec<InstanceState>,
+    active_calls: Vec<ActiveCall>,
+    recent_traps: VecDeque<RecordedTrap>,
+}
+
+struct InstanceState {
+    module_name: String,
+    /// Only read by the thread using the store, see
+    /// [`StoreDiagnostics::snapshot_memories`].
+    memories: Vec<NonNull<VMMemoryDefinition>>,
+    /// The size of the memories when they were last read.
+    memory_bytes: usize,
+}
+
+struct ActiveCall {
+    id: usize,
+    thread: String,
+    /// The function the call entered the guest through.
+    entry_ptr: usize,
+    since: Instant,
+}
+
+struct RecordedTrap {
+    when: SystemTime,
+    error: String,
+}
+
+// The memory definitions are only read by the thread using the store they
+// belong to, other threads only read the sizes snapshotted then.
+unsafe impl Send for StoreDiagnostics {}
+unsafe impl Sync for StoreDiagnostics {}
+
+impl StoreDiagnostics {
+    /// Registers a new store, if diagnostics are enabled.
+    pub(crate) fn register() -> Option<Arc<Self>> {
+        if !enabled() {
+            return None;
+        }
+        let diagnostics = Arc::new(Self {
+            id: NEXT_STORE_ID.fetch_add(1, Ordering::Relaxed),
+            created: Instant::now(),
+            state: Mutex::new(StoreState::default()),
+        });
+        REGISTRY.lock().unwrap().push(diagnostics.clone());
+        Some(diagnostics)
+    }
+
+    /// Removes the store from the registry. Must be called before the
+    /// store's instances are dropped.
+    pub(crate) fn unregister(&self) {
+        REGISTRY
+            .lock()
+            .unwrap()
+            .retain(|store| !std::ptr::eq(&**store, self));
+    }
+
+    pub(crate) fn set_tags(&self, tags: &BTreeMap<String, String>) {
+        self.state.lock().unwrap().tags = tags.clone();
+    }
+
+    pub(crate) fn record_instance(
+        &self,
+        module_name: Option<&str>,
+        memories: Vec<NonNull<VMMemoryDefinition>>,
+    ) {
+        let mut instance = InstanceState {
+            module_name: module_name.unwrap_or("<unnamed>").to_string(),
+            memories,
+            memory_bytes: 0,
+        };
+        unsafe { instance.snapshot_memories() };
+        self.state.lock().unwrap().instances.push(instance);
+    }
+
+    /// Records the current size of the memories of the instances.
+    ///
+    /// # Safety
+    ///
+    /// Must be called by the thread using the store, while none of its
+    /// memories is being grown.
+    unsafe fn snapshot_memories(&self) {
+        for instance in &mut self.state.lock().unwrap().instances {
+            instance.snapshot_memories();
+        }
+    }
+
+    pub(crate) fn record_trap(&self, error: &RuntimeError) {
+        let mut state = self.state.lock().unwrap();
+        if state.recent_traps.len() == RECENT_TRAPS {
+            state.recent_traps.pop_front();
+        }
+        state.recent_traps.push_back(RecordedTrap {
+            when: SystemTime::now(),
+            error: error.to_string(),
+        });
+    }
+
+    /// Tracks a call entering the guest through the function at
+    /// `entry_ptr` until the returned guard is dropped.
+    ///
+    /// Must be called by the thread using the store, before the call.
+    pub(crate) fn enter_call(self: &Arc<Self>, entry_ptr: usize) -> CallGuard {
+        // The calls are made with the store borrowed mutably, so the
+        // memories can't be grown meanwhile
+        unsafe { self.snapshot_memories() };
+        let id = NEXT_CALL_ID.fetch_add(1, Ordering::Relaxed);
+        let thread = std::thread::current();
+        let thread = match thread.name() {
+            Some(name) => name.to_string(),
+            None => format!("{:?}", thread.id()),
+        };
+        self.state.lock().unwrap().active_calls.push(ActiveCall {
+            id,
+            thread,
+            entry_ptr,
+            since: Instant::now(),
+        });
+        CallGuard {
+            diagnostics: self.clone(),
+            id,
+        }
+    }
+
+    fn write_report(&self, out: &mut String) {
+        let state = self.state.lock().unwrap();
+        let _ = writeln!(
+            out,
+            "\nstore #{} (alive for {})",
+            self.id,
+            format_duration(self.created.elapsed())
+        );
+        if !state.tags.is_empty() {
+            let tags = state
+                .tags
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<_>>();
+            let _ = writeln!(out, "  tags: {}", tags.join(", "));
+        }
+
+        let _ = writeln!(out, "  instances: {}", state.instances.len());
+        for instance in &state.instances {
+            let bytes = instance.memory_bytes;
+            let _ = writeln!(
+                out,
+                "    {}: {} memor{}, {} bytes ({} pages)",
+                instance.module_name,
+                instance.memories.len(),
+                if instance.memories.len() == 1 {
+                    "y"
+                } else {
+                    "ies"
+                },
+                bytes,
+                bytes / WASM_PAGE_SIZE
+            );
+        }
+
+        let _ = writeln!(out, "  active calls: {}", state.active_calls.len());
+        if !state.active_calls.is_empty() {
+            let frame_info = FRAME_INFO.read().unwrap();
+            for call in &state.active_calls {
+                let function = match frame_info.lookup_frame_info(call.entry_ptr) {
+                    Some(frame) => match frame.function_name() {
+                        Some(name) => format!("{}::{}", frame.module_name(), name),
+                        None => format!("{}::<func {}>", frame.module_name(), frame.func_index()),
+                    },
+                    None => "<host function>".to_string(),
+                };
+                let _ = writeln!(
+                    out,
+                    "    entered through {} on thread {}, running for {}",
+                    function,
+                    call.thread,
+                    format_duration(call.since.elapsed())
+                );
+            }
+        }
+
+        let _ = writeln!(out, "  recent traps: {}", state.recent_traps.len());
+        for trap in &state.recent_traps {
+            let ago = trap.when.elapsed().unwrap_or_default();
+            let _ = writeln!(out, "    {} ago: {}", format_duration(ago), trap.error);
+        }
+    }
+}
+
+impl InstanceState {
+    /// # Safety
+    ///
+    /// See [`StoreDiagnostics::snapshot_memories`].
+    unsafe fn snapshot_memories(&mut self) {
+        self.memory_bytes = self
+            .memories
+            .iter()
+            .map(|memory| memory.as_ref().current_length)
+            .sum();
+    }
+}
+
+/// Tracks a running call, see [`StoreDiagnostics::enter_call`].
+pub(crate) struct CallGuard {
+    diagnostics: Arc<StoreDiagnostics>,
+    id: usize,
+}
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        // The call returned on the thread using the store
+        unsafe { self.diagnostics.snapshot_memories() };
+        self.diagnostics
+            .state
+            .lock()
+            .unwrap()
+            .active_calls
+            .retain(|call| call.id != self.id);
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    if duration.as_secs() > 0 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+/// Serves the diagnostics [`report`] of this process on the socket at
+/// [`socket_path`], for `wasmer attach` to read.
+///
+/// Starting a server enables diagnostics. The server stops and removes its
+/// socket when dropped.
+#[cfg(unix)]
+pub struct DiagnosticServer {
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+#[cfg(unix)]
+impl DiagnosticServer {
+    /// Enables diagnostics and starts serving reports.
+    pub fn start() -> std::io::Result<Self> {
+        use std::io::Write;
+        use std::os::unix::net::UnixListener;
+
+        enable();
+        let path = socket_path(std::process::id());
+        // A previous process with the same pid may have left its socket.
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = stop.clone();
+            std::thread::Builder::new()
+                .name("wasmer-diagnostics".to_string())
+                .spawn(move || {
+                    for stream in listener.incoming() {
+                        if stop.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        if let Ok(mut stream) = stream {
+                            let _ = stream.write_all(report().as_bytes());
+                        }
+                    }
+                })?
+        };
+        Ok(Self {
+            path,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// The path of the socket the server listens on.
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+}
+
+#[cfg(unix)]
+impl Drop for DiagnosticServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // Wake the accept loop up so it sees the stop flag.
+        if std::os::unix::net::UnixStream::connect(&self.path).is_ok() {
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
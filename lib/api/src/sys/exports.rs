@@ -1,7 +1,7 @@
 use super::store::AsStoreRef;
 use crate::sys::externals::{Extern, Function, Global, Memory, Table};
 use crate::sys::native::TypedFunction;
-use crate::sys::WasmTypeList;
+use crate::sys::{ExportType, WasmTypeList};
 use indexmap::IndexMap;
 use std::fmt;
 use std::iter::{ExactSizeIterator, FromIterator};
@@ -62,7 +62,31 @@ pub enum ExportError {
 /// Exports is a special kind of map that allows easily unwrapping
 /// the types of instances.
 ///
-/// TODO: add examples of using exports
+/// The exports keep the order in which the module declares them.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::{imports, Instance, Module, Store};
+/// # let mut store = Store::default();
+/// # let module = Module::new(&store, r#"(module
+/// #   (func (export "add") (param i32 i32) (result i32)
+/// #     (i32.add (local.get 0) (local.get 1)))
+/// #   (memory (export "memory") 1))"#).unwrap();
+/// # let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+/// #
+/// // Discover what a module provides.
+/// for export in instance.exports.types(&store) {
+///     println!("{}: {:?}", export.name(), export.ty());
+/// }
+/// let functions = instance
+///     .exports
+///     .iter()
+///     .functions()
+///     .map(|(name, _)| name.as_str())
+///     .collect::<Vec<_>>();
+/// assert_eq!(functions, ["add"]);
+/// ```
 #[derive(Clone, Default)]
 pub struct Exports {
     map: IndexMap<String, Extern>,
@@ -202,6 +226,16 @@ impl Exports {
             iter: self.map.iter(),
         }
     }
+
+    /// Get an iterator over the names and types of the exports.
+    pub fn types<'a>(
+        &'a self,
+        store: &'a impl AsStoreRef,
+    ) -> impl Iterator<Item = ExportType> + ExactSizeIterator + 'a {
+        self.map
+            .iter()
+            .map(move |(name, export)| ExportType::new(name, export.ty(store)))
+    }
 }
 
 impl fmt::Debug for Exports {
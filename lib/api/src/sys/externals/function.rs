@@ -1,3 +1,4 @@
+use crate::sys::call_hook::{CallHook, CallHookInfo};
 use crate::sys::exports::{ExportError, Exportable};
 use crate::sys::externals::Extern;
 use crate::sys::store::{AsStoreMut, AsStoreRef, StoreInner, StoreMut};
@@ -11,12 +12,15 @@ pub use inner::{FromToNativeWasmType, HostFunction, WasmTypeList, WithEnv, Witho
 use std::cell::UnsafeCell;
 use std::cmp::max;
 use std::ffi::c_void;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 use wasmer_types::RawValue;
 use wasmer_vm::{
-    on_host_stack, raise_user_trap, resume_panic, wasmer_call_trampoline, InternalStoreHandle,
-    MaybeInstanceOwned, StoreHandle, VMCallerCheckedAnyfunc, VMContext, VMDynamicFunctionContext,
-    VMExtern, VMFuncRef, VMFunction, VMFunctionBody, VMFunctionContext, VMFunctionKind,
-    VMTrampoline,
+    block_on_future, on_host_stack, raise_user_trap, resume_panic, wasmer_call_trampoline,
+    AsyncCall, InternalStoreHandle, MaybeInstanceOwned, StoreHandle, VMCallerCheckedAnyfunc,
+    VMContext, VMDynamicFunctionContext, VMExtern, VMFuncRef, VMFunction, VMFunctionBody,
+    VMFunctionContext, VMFunctionKind, VMTrampoline,
 };
 
 /// A WebAssembly `function` instance.
@@ -44,9 +48,36 @@ pub struct Function {
 impl Function {
     /// Creates a new host `Function` (dynamic) with the provided signature.
     ///
+    /// The signature only needs to be known at runtime, which lets
+    /// bindings and proxies create functions for arbitrary imports. The
+    /// arguments are checked against it before `func` is called, and so
+    /// are the values it returns. Dynamic functions can't be stored in
+    /// tables.
+    ///
     /// If you know the signature of the host function at compile time,
     /// consider using [`Function::new_typed`] for less runtime overhead.
-    #[cfg(feature = "compiler")]
+    ///
+    /// # Examples
+    ///
+    /// Stubbing all the function imports of a module:
+    ///
+    /// ```
+    /// # use wasmer::{ExternType, Function, Imports, Module, Store, Value};
+    /// # let mut store = Store::default();
+    /// # let module = Module::new(&store, "(module (import \"env\" \"f\" (func (param i32) (result i64))))").unwrap();
+    /// #
+    /// let mut imports = Imports::new();
+    /// for import in module.imports() {
+    ///     if let ExternType::Function(ty) = import.ty() {
+    ///         let results = ty.results().to_vec();
+    ///         let stub = Function::new(&mut store, ty, move |args| {
+    ///             println!("called with {:?}", args);
+    ///             Ok(results.iter().map(|ty| Value::default_for(*ty)).collect())
+    ///         });
+    ///         imports.define(import.module(), import.name(), stub);
+    ///     }
+    /// }
+    /// ```
     pub fn new<FT, F>(store: &mut impl AsStoreMut, ty: FT, func: F) -> Self
     where
         FT: Into<FunctionType>,
@@ -59,7 +90,6 @@ impl Function {
         Self::new_with_env(store, &env, ty, wrapped_func)
     }
 
-    #[cfg(feature = "compiler")]
     /// Creates a new host `Function` (dynamic) with the provided signature.
     ///
     /// If you know the signature of the host function at compile time,
@@ -121,34 +151,122 @@ impl Function {
                 for (i, ty) in func_ty.params().iter().enumerate() {
                     args.push(Value::from_raw(&mut store, *ty, *values_vec.add(i)));
                 }
+                store.call_hook(CallHook::CallingHost, CallHookInfo::host)?;
                 let store_mut = StoreMut::from_raw(raw_store as *mut StoreInner);
                 let env = FunctionEnvMut {
                     store_mut,
                     func_env: func_env.clone(),
                 };
-                let returns = func(env, &args)?;
-
-                // We need to dynamically check that the returns
-                // match the expected types, as well as expected length.
-                let return_types = returns.iter().map(|ret| ret.ty());
-                if return_types.ne(func_ty.results().iter().copied()) {
-                    return Err(RuntimeError::new(format!(
-                        "Dynamic function returned wrong signature. Expected {:?} but got {:?}",
-                        func_ty.results(),
-                        returns.iter().map(|ret| ret.ty())
-                    )));
+                let returns = func(env, &args);
+                store.call_hook(CallHook::ReturningFromHost, CallHookInfo::host)?;
+                write_dynamic_results(&store, &func_ty, &returns?, values_vec)
+            }
+        };
+        let ctx = DynamicFunction { func: wrapper };
+        let func_body_ptr = ctx.func_body_ptr();
+        let call_trampoline = ctx.call_trampoline_address();
+        Self::from_dynamic_context(store, function_type, ctx, func_body_ptr, call_trampoline)
+    }
+
+    /// Creates a new asynchronous host `Function` (dynamic) with the
+    /// provided signature.
+    ///
+    /// `func` returns a future resolving to the results of the call. Wasm
+    /// code calling the function from [`Function::call_async`] is
+    /// suspended while the future is pending, leaving the thread to the
+    /// executor. Calling it from [`Function::call`] traps instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmer::{Function, FunctionType, Type, Store, Value};
+    /// # let mut store = Store::default();
+    /// #
+    /// let signature = FunctionType::new(vec![Type::I32], vec![Type::I32]);
+    ///
+    /// let f = Function::new_async(&mut store, &signature, |args| {
+    ///     let n = args[0].unwrap_i32();
+    ///     async move {
+    ///         // Wait for some I/O here
+    ///         Ok(vec![Value::I32(n * 2)])
+    ///     }
+    /// });
+    /// ```
+    pub fn new_async<FT, F, Fut>(store: &mut impl AsStoreMut, ty: FT, func: F) -> Self
+    where
+        FT: Into<FunctionType>,
+        F: Fn(&[Value]) -> Fut + 'static + Send + Sync,
+        Fut: Future<Output = Result<Vec<Value>, RuntimeError>> + 'static,
+    {
+        let env = FunctionEnv::new(&mut store.as_store_mut(), ());
+        let wrapped_func = move |_env: FunctionEnvMut<()>, args: &[Value]| func(args);
+        Self::new_async_with_env(store, &env, ty, wrapped_func)
+    }
+
+    /// Creates a new asynchronous host `Function` (dynamic) with the
+    /// provided signature and environment.
+    ///
+    /// See [`Function::new_async`]. The environment can only be used while
+    /// creating the future, which can't borrow it.
+    pub fn new_async_with_env<FT, F, Fut, T: Send + 'static>(
+        store: &mut impl AsStoreMut,
+        env: &FunctionEnv<T>,
+        ty: FT,
+        func: F,
+    ) -> Self
+    where
+        FT: Into<FunctionType>,
+        F: Fn(FunctionEnvMut<T>, &[Value]) -> Fut + 'static + Send + Sync,
+        Fut: Future<Output = Result<Vec<Value>, RuntimeError>> + 'static,
+    {
+        let function_type = ty.into();
+        let func_ty = function_type.clone();
+        let func_env = env.clone();
+        let raw_store = store.as_store_mut().as_raw() as *mut u8;
+        let wrapper = move |values_vec: *mut RawValue| -> HostFuture {
+            unsafe {
+                let mut store = StoreMut::from_raw(raw_store as *mut StoreInner);
+                let mut args = Vec::with_capacity(func_ty.params().len());
+                for (i, ty) in func_ty.params().iter().enumerate() {
+                    args.push(Value::from_raw(&mut store, *ty, *values_vec.add(i)));
                 }
-                for (i, ret) in returns.iter().enumerate() {
-                    *values_vec.add(i) = ret.as_raw(&store);
+                if let Err(error) = store.call_hook(CallHook::CallingHost, CallHookInfo::host) {
+                    return Box::pin(async move { Err(error) });
                 }
+                let store_mut = StoreMut::from_raw(raw_store as *mut StoreInner);
+                let env = FunctionEnvMut {
+                    store_mut,
+                    func_env: func_env.clone(),
+                };
+                let returns = func(env, &args);
+                let func_ty = func_ty.clone();
+                Box::pin(async move {
+                    let returns = returns.await;
+                    let mut store = StoreMut::from_raw(raw_store as *mut StoreInner);
+                    store.call_hook(CallHook::ReturningFromHost, CallHookInfo::host)?;
+                    write_dynamic_results(&store, &func_ty, &returns?, values_vec)
+                })
             }
-            Ok(())
         };
-        let mut host_data = Box::new(VMDynamicFunctionContext {
-            address: std::ptr::null(),
-            ctx: DynamicFunction { func: wrapper },
+        let ctx = AsyncDynamicFunction { func: wrapper };
+        let func_body_ptr = ctx.func_body_ptr();
+        let call_trampoline = ctx.call_trampoline_address();
+        Self::from_dynamic_context(store, function_type, ctx, func_body_ptr, call_trampoline)
+    }
+
+    /// Creates a host `Function` calling the wrapper of a dynamic
+    /// function, which keeps `ctx` as its state.
+    fn from_dynamic_context<C: 'static>(
+        store: &mut impl AsStoreMut,
+        function_type: FunctionType,
+        ctx: C,
+        func_body_ptr: *const VMFunctionBody,
+        call_trampoline: VMTrampoline,
+    ) -> Self {
+        let host_data = Box::new(VMDynamicFunctionContext {
+            address: func_body_ptr,
+            ctx,
         });
-        host_data.address = host_data.ctx.func_body_ptr();
 
         // We don't yet have the address with the Wasm ABI signature.
         // The engine linker will replace the address with one pointing to a
@@ -161,7 +279,6 @@ impl Function {
         let vmctx = VMFunctionContext {
             host_env: host_data.as_ref() as *const _ as *mut c_void,
         };
-        let call_trampoline = host_data.ctx.call_trampoline_address();
         let anyfunc = VMCallerCheckedAnyfunc {
             func_ptr,
             type_index,
@@ -180,7 +297,6 @@ impl Function {
         }
     }
 
-    #[cfg(feature = "compiler")]
     #[deprecated(
         since = "3.0.0",
         note = "new_native() has been renamed to new_typed()."
@@ -195,7 +311,6 @@ impl Function {
         Self::new_typed(store, func)
     }
 
-    #[cfg(feature = "compiler")]
     /// Creates a new host `Function` from a native function.
     pub fn new_typed<F, Args, Rets>(store: &mut impl AsStoreMut, func: F) -> Self
     where
@@ -239,7 +354,6 @@ impl Function {
         }
     }
 
-    #[cfg(feature = "compiler")]
     #[deprecated(
         since = "3.0.0",
         note = "new_native_with_env() has been renamed to new_typed_with_env()."
@@ -258,7 +372,6 @@ impl Function {
         Self::new_typed_with_env(store, env, func)
     }
 
-    #[cfg(feature = "compiler")]
     /// Creates a new host `Function` with an environment from a typed function.
     ///
     /// The function signature is automatically retrieved using the
@@ -349,7 +462,6 @@ impl Function {
             .clone()
     }
 
-    #[cfg(feature = "compiler")]
     fn call_wasm(
         &self,
         store: &mut impl AsStoreMut,
@@ -357,6 +469,53 @@ impl Function {
         params: &[Value],
         results: &mut [Value],
     ) -> Result<(), RuntimeError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("call", params = params.len()).entered();
+        let (signature, mut values_vec) = self.call_values(store, params, results)?;
+
+        // Call the trampoline.
+        let vm_function = self.handle.get(store.as_store_ref().objects());
+        let anyfunc = unsafe { *vm_function.anyfunc.as_ptr().as_ref() };
+        store
+            .as_store_mut()
+            .call_hook(CallHook::CallingWasm, || CallHookInfo::of(&anyfunc))?;
+        let _call = store
+            .as_store_ref()
+            .diagnostics()
+            .map(|diagnostics| diagnostics.enter_call(anyfunc.func_ptr as usize));
+        let result = unsafe {
+            wasmer_call_trampoline(
+                store.as_store_ref().signal_handler(),
+                anyfunc.vmctx,
+                trampoline,
+                anyfunc.func_ptr,
+                values_vec.as_mut_ptr() as *mut u8,
+                store.as_store_ref().engine().stack_size(),
+            )
+        };
+        let returned = store
+            .as_store_mut()
+            .call_hook(CallHook::ReturningFromWasm, || CallHookInfo::of(&anyfunc));
+        if let Err(error) = result {
+            return Err(store
+                .as_store_ref()
+                .call_error(RuntimeError::from_trap(error)));
+        }
+        returned?;
+
+        Self::load_results(store, &signature, &values_vec, results);
+        Ok(())
+    }
+
+    /// Checks the arguments and the number of results of a call against
+    /// the signature of the function, returning the signature and the
+    /// values the trampoline is called with.
+    fn call_values(
+        &self,
+        store: &mut impl AsStoreMut,
+        params: &[Value],
+        results: &[Value],
+    ) -> Result<(FunctionType, Vec<RawValue>), RuntimeError> {
         let format_types_for_error_message = |items: &[Value]| {
             items
                 .iter()
@@ -401,28 +560,21 @@ impl Function {
             *slot = arg.as_raw(store);
         }
 
-        // Call the trampoline.
-        let vm_function = self.handle.get(store.as_store_ref().objects());
-        if let Err(error) = unsafe {
-            wasmer_call_trampoline(
-                store.as_store_ref().signal_handler(),
-                vm_function.anyfunc.as_ptr().as_ref().vmctx,
-                trampoline,
-                vm_function.anyfunc.as_ptr().as_ref().func_ptr,
-                values_vec.as_mut_ptr() as *mut u8,
-            )
-        } {
-            return Err(RuntimeError::from_trap(error));
-        }
+        Ok((signature, values_vec))
+    }
 
-        // Load the return values out of `values_vec`.
+    /// Loads the return values of a call out of `values_vec`.
+    fn load_results(
+        store: &mut impl AsStoreMut,
+        signature: &FunctionType,
+        values_vec: &[RawValue],
+        results: &mut [Value],
+    ) {
         for (index, &value_type) in signature.results().iter().enumerate() {
             unsafe {
                 results[index] = Value::from_raw(store, value_type, values_vec[index]);
             }
         }
-
-        Ok(())
     }
 
     /// Returns the number of parameters that this function takes.
@@ -467,7 +619,6 @@ impl Function {
         self.ty(store).results().len()
     }
 
-    #[cfg(feature = "compiler")]
     /// Call the `Function` function.
     ///
     /// Depending on where the Function is defined, it will call it.
@@ -517,6 +668,83 @@ impl Function {
         Ok(results.into_boxed_slice())
     }
 
+    /// Call the `Function` function asynchronously.
+    ///
+    /// This is the same as [`Function::call`], except that the host
+    /// functions created with [`Function::new_async`] can be awaited: while
+    /// their future is pending, the Wasm code is suspended on its own stack
+    /// and the returned future is pending too.
+    ///
+    /// # The future isn't `Send`
+    ///
+    /// The suspended Wasm code can't move to another thread, as its stack
+    /// holds state tied to the thread it started on. The returned future
+    /// must therefore be polled by a single thread: it can't be given to
+    /// `tokio::spawn`, but it can be given to `tokio::task::spawn_local`
+    /// in a `LocalSet`, or be awaited by a task that is.
+    ///
+    /// # Cancellation
+    ///
+    /// Dropping the returned future before it completes cancels the call:
+    /// the future of the host function the call is suspended in is
+    /// dropped, and the Wasm code doesn't run any further, as if it
+    /// trapped. The memories, tables and globals keep the changes it made
+    /// until then.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let run = instance.exports.get_function("run")?;
+    /// let results = run.call_async(&mut store, &[Value::I32(42)]).await?;
+    /// ```
+    pub async fn call_async(
+        &self,
+        store: &mut impl AsStoreMut,
+        params: &[Value],
+    ) -> Result<Box<[Value]>, RuntimeError> {
+        let mut results = vec![Value::null(); self.result_arity(store)];
+        let (signature, mut values_vec) = self.call_values(store, params, &results)?;
+
+        let vm_function = self.handle.get(store.as_store_ref().objects());
+        let anyfunc = unsafe { *vm_function.anyfunc.as_ptr().as_ref() };
+        store
+            .as_store_mut()
+            .call_hook(CallHook::CallingWasm, || CallHookInfo::of(&anyfunc))?;
+        let _call = store
+            .as_store_ref()
+            .diagnostics()
+            .map(|diagnostics| diagnostics.enter_call(anyfunc.func_ptr as usize));
+        let call = unsafe {
+            AsyncCall::new(
+                store.as_store_ref().signal_handler(),
+                anyfunc.vmctx,
+                anyfunc.call_trampoline,
+                anyfunc.func_ptr,
+                values_vec.as_mut_ptr() as *mut u8,
+                store.as_store_ref().engine().stack_size(),
+            )
+        };
+        let result = (PollAsyncCall { call }).await;
+        let returned = store
+            .as_store_mut()
+            .call_hook(CallHook::ReturningFromWasm, || CallHookInfo::of(&anyfunc));
+        if let Err(error) = result {
+            return Err(store
+                .as_store_ref()
+                .call_error(RuntimeError::from_trap(error)));
+        }
+        returned?;
+
+        Self::load_results(store, &signature, &values_vec, &mut results);
+        Ok(results.into_boxed_slice())
+    }
+
+    /// Whether the function was created with [`Function::new`], which
+    /// can't be used as a funcref.
+    pub(crate) fn is_dynamic(&self, store: &impl AsStoreRef) -> bool {
+        self.handle.get(store.as_store_ref().objects()).kind == VMFunctionKind::Dynamic
+    }
+
     pub(crate) fn vm_funcref(&self, store: &impl AsStoreRef) -> VMFuncRef {
         let vm_function = self.handle.get(store.as_store_ref().objects());
         if vm_function.kind == VMFunctionKind::Dynamic {
@@ -525,7 +753,6 @@ impl Function {
         VMFuncRef(vm_function.anyfunc.as_ptr())
     }
 
-    #[cfg(feature = "compiler")]
     pub(crate) unsafe fn from_vm_funcref(store: &mut impl AsStoreMut, funcref: VMFuncRef) -> Self {
         let signature = store
             .as_store_ref()
@@ -547,7 +774,6 @@ impl Function {
 
     /// Transform this WebAssembly function into a native function.
     /// See [`TypedFunction`] to learn more.
-    #[cfg(feature = "compiler")]
     #[deprecated(since = "3.0.0", note = "native() has been renamed to typed().")]
     pub fn native<Args, Rets>(
         &self,
@@ -759,6 +985,99 @@ where
     }
 }
 
+/// The future of a host function created with [`Function::new_async`],
+/// writing its results into the values of the call.
+type HostFuture = Pin<Box<dyn Future<Output = Result<(), RuntimeError>>>>;
+
+/// Host state for an asynchronous dynamic function.
+pub(crate) struct AsyncDynamicFunction<F> {
+    func: F,
+}
+
+impl<F> AsyncDynamicFunction<F>
+where
+    F: Fn(*mut RawValue) -> HostFuture + 'static,
+{
+    // The future is created on the host stack like any host function, but
+    // it's polled from the Wasm stack, which is the one suspended while it
+    // is pending.
+    unsafe extern "C" fn func_wrapper(
+        this: &mut VMDynamicFunctionContext<Self>,
+        values_vec: *mut RawValue,
+    ) {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let future =
+            on_host_stack(|| panic::catch_unwind(AssertUnwindSafe(|| (this.ctx.func)(values_vec))));
+        let future = match future {
+            Ok(future) => future,
+            Err(panic) => resume_panic(panic),
+        };
+
+        match block_on_future(future) {
+            Some(Ok(())) => {}
+            Some(Err(trap)) => raise_user_trap(Box::new(trap)),
+            None => raise_user_trap(Box::new(RuntimeError::new(
+                "async host functions can only be called by `Function::call_async`",
+            ))),
+        }
+    }
+
+    fn func_body_ptr(&self) -> *const VMFunctionBody {
+        Self::func_wrapper as *const VMFunctionBody
+    }
+
+    fn call_trampoline_address(&self) -> VMTrampoline {
+        Self::call_trampoline
+    }
+
+    unsafe extern "C" fn call_trampoline(
+        vmctx: *mut VMContext,
+        _body: *const VMFunctionBody,
+        args: *mut RawValue,
+    ) {
+        let dynamic_function = &mut *(vmctx as *mut VMDynamicFunctionContext<Self>);
+        Self::func_wrapper(dynamic_function, args);
+    }
+}
+
+/// The future of a call made by [`Function::call_async`].
+struct PollAsyncCall {
+    call: AsyncCall,
+}
+
+impl Future for PollAsyncCall {
+    type Output = Result<(), wasmer_vm::Trap>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        unsafe { self.call.poll(cx) }
+    }
+}
+
+/// Checks that the values returned by a dynamic function match its
+/// signature, and writes them into `values_vec`.
+unsafe fn write_dynamic_results(
+    store: &StoreMut,
+    func_ty: &FunctionType,
+    returns: &[Value],
+    values_vec: *mut RawValue,
+) -> Result<(), RuntimeError> {
+    // We need to dynamically check that the returns
+    // match the expected types, as well as expected length.
+    let return_types = returns.iter().map(|ret| ret.ty());
+    if return_types.ne(func_ty.results().iter().copied()) {
+        return Err(RuntimeError::new(format!(
+            "Dynamic function returned wrong signature. Expected {:?} but got {:?}",
+            func_ty.results(),
+            returns.iter().map(|ret| ret.ty())
+        )));
+    }
+    for (i, ret) in returns.iter().enumerate() {
+        *values_vec.add(i) = ret.as_raw(store);
+    }
+    Ok(())
+}
+
 /// This private inner module contains the low-level implementation
 /// for `Function` and its siblings.
 mod inner {
@@ -772,7 +1091,8 @@ mod inner {
     use wasmer_types::{NativeWasmType, RawValue, Type};
     use wasmer_vm::{raise_user_trap, resume_panic, VMFunctionBody};
 
-    use crate::sys::NativeWasmTypeInto;
+    use crate::sys::call_hook::{CallHook, CallHookInfo};
+    use crate::sys::{NativeWasmTypeInto, RuntimeError};
     use crate::{AsStoreMut, AsStoreRef, ExternRef, Function, FunctionEnv, StoreMut};
 
     /// A trait to convert a Rust value to a `WasmNativeType` value,
@@ -890,7 +1210,6 @@ mod inner {
         }
     }
 
-    #[cfg(feature = "compiler")]
     unsafe impl FromToNativeWasmType for Option<Function> {
         type Native = Self;
 
@@ -1283,24 +1602,28 @@ mod inner {
                         let mut store = StoreMut::from_raw(env.raw_store as *mut _);
                         let result = on_host_stack(|| {
                             // println!("func wrapper1");
-                            panic::catch_unwind(AssertUnwindSafe(|| {
+                            panic::catch_unwind(AssertUnwindSafe(|| -> Result<_, RuntimeError> {
                                 $(
                                     let $x = FromToNativeWasmType::from_native(NativeWasmTypeInto::from_abi(&mut store, $x));
                                 )*
                                 // println!("func wrapper2 {:p}", *env.raw_env);
+                                store.call_hook(CallHook::CallingHost, CallHookInfo::host)?;
                                 let store_mut = StoreMut::from_raw(env.raw_store as *mut _);
                                 let f_env = FunctionEnvMut {
                                     store_mut,
                                     func_env: env.env.clone(),
                                 };
                                 // println!("func wrapper3");
-                                (env.func)(f_env, $($x),* ).into_result()
+                                let result = (env.func)(f_env, $($x),* ).into_result();
+                                store.call_hook(CallHook::ReturningFromHost, CallHookInfo::host)?;
+                                Ok(result)
                             }))
                         });
 
                         match result {
-                            Ok(Ok(result)) => return result.into_c_struct(&mut store),
-                            Ok(Err(trap)) => raise_user_trap(Box::new(trap)),
+                            Ok(Ok(Ok(result))) => return result.into_c_struct(&mut store),
+                            Ok(Ok(Err(trap))) => raise_user_trap(Box::new(trap)),
+                            Ok(Err(hook_error)) => raise_user_trap(Box::new(hook_error)),
                             Err(panic) => resume_panic(panic) ,
                         }
                     }
@@ -1367,17 +1690,21 @@ mod inner {
                         let mut store = StoreMut::from_raw(env.raw_store as *mut _);
                         let result = on_host_stack(|| {
                             // println!("func wrapper1");
-                            panic::catch_unwind(AssertUnwindSafe(|| {
+                            panic::catch_unwind(AssertUnwindSafe(|| -> Result<_, RuntimeError> {
                                 $(
                                     let $x = FromToNativeWasmType::from_native(NativeWasmTypeInto::from_abi(&mut store, $x));
                                 )*
-                                (env.func)($($x),* ).into_result()
+                                store.call_hook(CallHook::CallingHost, CallHookInfo::host)?;
+                                let result = (env.func)($($x),* ).into_result();
+                                store.call_hook(CallHook::ReturningFromHost, CallHookInfo::host)?;
+                                Ok(result)
                             }))
                         });
 
                         match result {
-                            Ok(Ok(result)) => return result.into_c_struct(&mut store),
-                            Ok(Err(trap)) => raise_user_trap(Box::new(trap)),
+                            Ok(Ok(Ok(result))) => return result.into_c_struct(&mut store),
+                            Ok(Ok(Err(trap))) => raise_user_trap(Box::new(trap)),
+                            Ok(Err(hook_error)) => raise_user_trap(Box::new(hook_error)),
                             Err(panic) => resume_panic(panic) ,
                         }
                     }
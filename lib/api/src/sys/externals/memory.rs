@@ -8,10 +8,14 @@ use std::marker::PhantomData;
 use std::mem;
 use std::mem::MaybeUninit;
 use std::slice;
+use std::sync::Arc;
 #[cfg(feature = "tracing")]
 use tracing::warn;
 use wasmer_types::Pages;
-use wasmer_vm::{InternalStoreHandle, LinearMemory, MemoryError, StoreHandle, VMExtern, VMMemory};
+use wasmer_vm::{
+    InternalStoreHandle, LinearMemory, MemoryError, MemoryGrowObserver, StoreHandle, VMExtern,
+    VMMemory,
+};
 
 use super::MemoryView;
 
@@ -35,7 +39,6 @@ pub struct Memory {
 }
 
 impl Memory {
-    #[cfg(feature = "compiler")]
     /// Creates a new host `Memory` from the provided [`MemoryType`].
     ///
     /// This function will construct the `Memory` using the store
@@ -51,9 +54,19 @@ impl Memory {
     /// ```
     pub fn new(store: &mut impl AsStoreMut, ty: MemoryType) -> Result<Self, MemoryError> {
         let mut store = store.as_store_mut();
+        if let Some(limiter) = store.objects_mut().limiter_mut() {
+            if !limiter.memory_creating(&ty) {
+                return Err(MemoryError::Generic(
+                    "the resource limiter of the store denied the creation of the memory".into(),
+                ));
+            }
+        }
         let tunables = store.tunables();
         let style = tunables.memory_style(&ty);
-        let memory = tunables.create_host_memory(&ty, &style)?;
+        let mut memory = tunables.create_host_memory(&ty, &style)?;
+        if let Some(observer) = tunables.memory_grow_observer(&ty) {
+            memory.set_grow_observer(observer)?;
+        }
 
         Ok(Self {
             handle: StoreHandle::new(store.objects_mut(), memory),
@@ -108,7 +121,8 @@ impl Memory {
     /// # Errors
     ///
     /// Returns an error if memory can't be grown by the specified amount
-    /// of pages.
+    /// of pages, or if the [`ResourceLimiter`][crate::ResourceLimiter] of
+    /// the store denies it.
     ///
     /// ```should_panic
     /// # use wasmer::{Memory, MemoryType, Pages, Store, Type, Value, WASM_MAX_PAGES};
@@ -129,7 +143,34 @@ impl Memory {
     where
         IntoPages: Into<Pages>,
     {
-        self.handle.get_mut(store.objects_mut()).grow(delta.into())
+        let objects = store.objects_mut();
+        assert_eq!(
+            self.handle.store_id(),
+            objects.id(),
+            "object used with the wrong context"
+        );
+        objects.grow_memory(self.handle.internal_handle(), delta.into())
+    }
+
+    /// Tells `observer` about the growth of this memory, be it requested
+    /// by the host or by `memory.grow`, replacing the observer set by the
+    /// [`Tunables`][crate::Tunables] if any.
+    ///
+    /// The observer can veto the growth, and is told when the memory may
+    /// have moved.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the memory can't be observed, which is the case
+    /// of custom memories not supporting it.
+    pub fn set_grow_observer(
+        &self,
+        store: &mut impl AsStoreMut,
+        observer: Arc<dyn MemoryGrowObserver>,
+    ) -> Result<(), MemoryError> {
+        self.handle
+            .get_mut(store.objects_mut())
+            .set_grow_observer(observer)
     }
 
     pub(crate) fn from_vm_extern(
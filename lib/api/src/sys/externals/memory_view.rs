@@ -2,10 +2,12 @@ use crate::sys::store::AsStoreRef;
 use crate::MemoryAccessError;
 use std::convert::TryInto;
 use std::marker::PhantomData;
-use std::mem::MaybeUninit;
+use std::mem::{self, MaybeUninit};
 use std::slice;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
 use wasmer_types::Pages;
-use wasmer_vm::LinearMemory;
+use wasmer_vm::{memory_wait, LinearMemory, WaitResult};
 
 use super::memory::MemoryBuffer;
 use super::Memory;
@@ -159,4 +161,125 @@ impl<'a> MemoryView<'a> {
         self.write(offset, &buf)?;
         Ok(())
     }
+
+    /// Atomically reads the `u32` at the given offset, like `i32.atomic.load`.
+    ///
+    /// The offset must be a multiple of 4, otherwise a `MemoryAccessError`
+    /// is returned.
+    pub fn atomic_load_u32(&self, offset: u64) -> Result<u32, MemoryAccessError> {
+        Ok(self.atomic::<AtomicU32>(offset)?.load(Ordering::SeqCst))
+    }
+
+    /// Atomically reads the `u64` at the given offset, like `i64.atomic.load`.
+    ///
+    /// The offset must be a multiple of 8, otherwise a `MemoryAccessError`
+    /// is returned.
+    pub fn atomic_load_u64(&self, offset: u64) -> Result<u64, MemoryAccessError> {
+        Ok(self.atomic::<AtomicU64>(offset)?.load(Ordering::SeqCst))
+    }
+
+    /// Atomically writes the `u32` at the given offset, like
+    /// `i32.atomic.store`.
+    pub fn atomic_store_u32(&self, offset: u64, val: u32) -> Result<(), MemoryAccessError> {
+        self.atomic::<AtomicU32>(offset)?
+            .store(val, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Atomically writes the `u64` at the given offset, like
+    /// `i64.atomic.store`.
+    pub fn atomic_store_u64(&self, offset: u64, val: u64) -> Result<(), MemoryAccessError> {
+        self.atomic::<AtomicU64>(offset)?
+            .store(val, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Atomically replaces the `u32` at the given offset by `new` if it is
+    /// `current`, like `i32.atomic.rmw.cmpxchg`, returning the value it had.
+    pub fn atomic_compare_exchange_u32(
+        &self,
+        offset: u64,
+        current: u32,
+        new: u32,
+    ) -> Result<u32, MemoryAccessError> {
+        let atomic = self.atomic::<AtomicU32>(offset)?;
+        Ok(
+            match atomic.compare_exchange(current, new, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(previous) | Err(previous) => previous,
+            },
+        )
+    }
+
+    /// Atomically replaces the `u64` at the given offset by `new` if it is
+    /// `current`, like `i64.atomic.rmw.cmpxchg`, returning the value it had.
+    pub fn atomic_compare_exchange_u64(
+        &self,
+        offset: u64,
+        current: u64,
+        new: u64,
+    ) -> Result<u64, MemoryAccessError> {
+        let atomic = self.atomic::<AtomicU64>(offset)?;
+        Ok(
+            match atomic.compare_exchange(current, new, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(previous) | Err(previous) => previous,
+            },
+        )
+    }
+
+    /// Blocks the current thread until the `u32` at the given offset is
+    /// notified or `timeout` elapses, if its value is `expected`, like
+    /// `memory.atomic.wait32`.
+    ///
+    /// The waiters of a shared memory are woken by [`MemoryView::atomic_notify`]
+    /// from any view of it.
+    pub fn atomic_wait32(
+        &self,
+        offset: u64,
+        expected: u32,
+        timeout: Option<Duration>,
+    ) -> Result<WaitResult, MemoryAccessError> {
+        let atomic = self.atomic::<AtomicU32>(offset)?;
+        Ok(memory_wait::wait32(atomic, expected, timeout))
+    }
+
+    /// Blocks the current thread until the `u64` at the given offset is
+    /// notified or `timeout` elapses, if its value is `expected`, like
+    /// `memory.atomic.wait64`.
+    pub fn atomic_wait64(
+        &self,
+        offset: u64,
+        expected: u64,
+        timeout: Option<Duration>,
+    ) -> Result<WaitResult, MemoryAccessError> {
+        let atomic = self.atomic::<AtomicU64>(offset)?;
+        Ok(memory_wait::wait64(atomic, expected, timeout))
+    }
+
+    /// Wakes up to `count` of the threads waiting on the given offset,
+    /// like `memory.atomic.notify`, returning how many were woken.
+    pub fn atomic_notify(&self, offset: u64, count: u32) -> Result<u32, MemoryAccessError> {
+        let atomic = self.atomic::<AtomicU32>(offset)?;
+        Ok(memory_wait::notify(
+            atomic as *const AtomicU32 as *const u8,
+            count,
+        ))
+    }
+
+    /// The atomic at the given offset, which must be in bounds and a
+    /// multiple of its size, as Wasm atomics are.
+    fn atomic<A>(&self, offset: u64) -> Result<&'a A, MemoryAccessError> {
+        let size = mem::size_of::<A>() as u64;
+        let end = offset
+            .checked_add(size)
+            .ok_or(MemoryAccessError::Overflow)?;
+        if end > self.buffer.len as u64 {
+            return Err(MemoryAccessError::HeapOutOfBounds);
+        }
+        // The base of the memory is page-aligned, so an aligned offset is
+        // an aligned address.
+        if offset % size != 0 {
+            return Err(MemoryAccessError::Unaligned);
+        }
+        Ok(unsafe { &*(self.buffer.base.add(offset as usize) as *const A) })
+    }
 }
@@ -3,7 +3,7 @@ use crate::sys::externals::Extern;
 use crate::sys::store::{AsStoreMut, AsStoreRef};
 use crate::sys::RuntimeError;
 use crate::sys::TableType;
-use crate::{ExternRef, Function, Value};
+use crate::{ExternRef, Function, Type, Value};
 use wasmer_vm::{InternalStoreHandle, StoreHandle, TableElement, VMExtern, VMTable};
 
 /// A WebAssembly `table` instance.
@@ -30,15 +30,28 @@ fn set_table_item(
 
 fn value_to_table_element(
     store: &mut impl AsStoreMut,
+    ty: Type,
     val: Value,
 ) -> Result<wasmer_vm::TableElement, RuntimeError> {
     if !val.is_from_store(store) {
         return Err(RuntimeError::new("cannot pass Value across contexts"));
     }
+    if val.ty() != ty {
+        return Err(RuntimeError::new(format!(
+            "cannot store a {} in a table of {}",
+            val.ty(),
+            ty
+        )));
+    }
     Ok(match val {
         Value::ExternRef(extern_ref) => {
             wasmer_vm::TableElement::ExternRef(extern_ref.map(|e| e.vm_externref()))
         }
+        Value::FuncRef(Some(ref f)) if f.is_dynamic(store) => {
+            return Err(RuntimeError::new(
+                "cannot store a dynamic function in a table",
+            ))
+        }
         Value::FuncRef(func_ref) => {
             wasmer_vm::TableElement::FuncRef(func_ref.map(|f| f.vm_funcref(store)))
         }
@@ -46,7 +59,6 @@ fn value_to_table_element(
     })
 }
 
-#[cfg(feature = "compiler")]
 fn value_from_table_element(store: &mut impl AsStoreMut, item: wasmer_vm::TableElement) -> Value {
     match item {
         wasmer_vm::TableElement::FuncRef(funcref) => {
@@ -59,7 +71,6 @@ fn value_from_table_element(store: &mut impl AsStoreMut, item: wasmer_vm::TableE
 }
 
 impl Table {
-    #[cfg(feature = "compiler")]
     /// Creates a new `Table` with the provided [`TableType`] definition.
     ///
     /// All the elements in the table will be set to the `init` value.
@@ -71,8 +82,15 @@ impl Table {
         ty: TableType,
         init: Value,
     ) -> Result<Self, RuntimeError> {
-        let item = value_to_table_element(&mut store, init)?;
+        let item = value_to_table_element(&mut store, ty.ty, init)?;
         let mut store = store.as_store_mut();
+        if let Some(limiter) = store.objects_mut().limiter_mut() {
+            if !limiter.table_creating(&ty) {
+                return Err(RuntimeError::new(
+                    "the resource limiter of the store denied the creation of the table",
+                ));
+            }
+        }
         let tunables = store.tunables();
         let style = tunables.table_style(&ty);
         let mut table = tunables
@@ -94,7 +112,6 @@ impl Table {
         *self.handle.get(store.as_store_ref().objects()).ty()
     }
 
-    #[cfg(feature = "compiler")]
     /// Retrieves an element of the table at the provided `index`.
     pub fn get(&self, store: &mut impl AsStoreMut, index: u32) -> Option<Value> {
         let item = self.handle.get(store.as_store_ref().objects()).get(index)?;
@@ -102,13 +119,21 @@ impl Table {
     }
 
     /// Sets an element `val` in the Table at the provided `index`.
+    ///
+    /// Host functions can be stored in the tables of instances, to be
+    /// called with `call_indirect` like the functions of the module.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `index` is out of bounds, or if `val` isn't
+    /// a reference of the type of the elements of the table.
     pub fn set(
         &self,
         store: &mut impl AsStoreMut,
         index: u32,
         val: Value,
     ) -> Result<(), RuntimeError> {
-        let item = value_to_table_element(store, val)?;
+        let item = value_to_table_element(store, self.ty(store).ty, val)?;
         set_table_item(self.handle.get_mut(store.objects_mut()), index, item)
     }
 
@@ -125,17 +150,24 @@ impl Table {
     ///
     /// # Errors
     ///
-    /// Returns an error if the `delta` is out of bounds for the table.
+    /// Returns an error if the `delta` is out of bounds for the table, if
+    /// the [`ResourceLimiter`][crate::ResourceLimiter] of the store denies
+    /// it, or if `init` isn't a reference of the type of its elements.
     pub fn grow(
         &self,
         store: &mut impl AsStoreMut,
         delta: u32,
         init: Value,
     ) -> Result<u32, RuntimeError> {
-        let item = value_to_table_element(store, init)?;
-        self.handle
-            .get_mut(store.objects_mut())
-            .grow(delta, item)
+        let item = value_to_table_element(store, self.ty(store).ty, init)?;
+        let objects = store.objects_mut();
+        assert_eq!(
+            self.handle.store_id(),
+            objects.id(),
+            "object used with the wrong context"
+        );
+        objects
+            .grow_table(self.handle.internal_handle(), delta, item)
             .ok_or_else(|| RuntimeError::new(format!("failed to grow table by `{}`", delta)))
     }
 
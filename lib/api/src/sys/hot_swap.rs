@@ -0,0 +1,173 @@
+//! Replacing the module of a running instance while keeping its state.
+//!
+//! [`Instance::hot_swap`] instantiates a new version of the module of an
+//! instance, then carries the contents of the memories and the values of
+//! the mutable globals the instance defines over to the new instance. The
+//! state can only be carried over if the new module defines the same
+//! memories and globals, at the same indices and with compatible types:
+//! [`Instance::check_hot_swap`] lists every [`HotSwapMismatch`] that
+//! prevents it.
+//!
+//! [`Instance::hot_swap`]: crate::Instance::hot_swap
+//! [`Instance::check_hot_swap`]: crate::Instance::check_hot_swap
+
+use crate::sys::{InstanceImageError, InstantiationError};
+use std::fmt;
+use thiserror::Error;
+use wasmer_types::entity::EntityRef;
+use wasmer_types::{GlobalType, LocalGlobalIndex, LocalMemoryIndex, ModuleInfo, Mutability, Pages};
+
+/// A difference between the state of an instance and a new module that
+/// prevents [`Instance::hot_swap`](crate::Instance::hot_swap).
+///
+/// The indices are the ones of the memories and globals in the module of
+/// the instance.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum HotSwapMismatch {
+    /// The new module doesn't define a memory the instance defines.
+    #[error("memory {index} isn't defined by the new module")]
+    MissingMemory {
+        /// The index of the memory.
+        index: u32,
+    },
+
+    /// The memory is larger than the new module allows.
+    #[error(
+        "memory {index} has {pages} pages, more than the maximum of {maximum} of the new module"
+    )]
+    MemoryTooLarge {
+        /// The index of the memory.
+        index: u32,
+        /// The current size of the memory.
+        pages: u32,
+        /// The maximum size of the memory in the new module.
+        maximum: u32,
+    },
+
+    /// The memory is shared in one module but not in the other.
+    #[error("memory {index} is shared in only one of the modules")]
+    MemorySharing {
+        /// The index of the memory.
+        index: u32,
+        /// Whether the memory of the instance is shared.
+        shared: bool,
+    },
+
+    /// The new module doesn't define a mutable global the instance
+    /// defines.
+    #[error("global {index} isn't defined by the new module")]
+    MissingGlobal {
+        /// The index of the global.
+        index: u32,
+    },
+
+    /// The global has another type in the new module.
+    #[error("global {index} is {old}, but {new} in the new module")]
+    GlobalType {
+        /// The index of the global.
+        index: u32,
+        /// The type of the global of the instance.
+        old: GlobalType,
+        /// The type of the global in the new module.
+        new: GlobalType,
+    },
+}
+
+/// The mismatches between the state of an instance and a new module.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub struct HotSwapIncompatibility {
+    /// All the mismatches.
+    pub mismatches: Vec<HotSwapMismatch>,
+}
+
+impl fmt::Display for HotSwapIncompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "the new module can't take over the state of the instance:"
+        )?;
+        for mismatch in &self.mismatches {
+            write!(f, "\n  - {}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+/// An error while hot swapping the module of an instance.
+#[derive(Error, Debug)]
+pub enum HotSwapError {
+    /// The new module can't take over the state of the instance.
+    #[error(transparent)]
+    Incompatible(HotSwapIncompatibility),
+
+    /// The new module failed to instantiate.
+    #[error(transparent)]
+    Instantiation(InstantiationError),
+
+    /// The state of the instance couldn't be carried over.
+    #[error(transparent)]
+    State(InstanceImageError),
+}
+
+/// Lists the mismatches between the memories and mutable globals defined
+/// by `old`, the memories having `pages` pages, and the ones defined by
+/// `new`.
+pub(crate) fn mismatches(
+    old: &ModuleInfo,
+    new: &ModuleInfo,
+    pages: impl Fn(LocalMemoryIndex) -> Pages,
+) -> Vec<HotSwapMismatch> {
+    let mut mismatches = Vec::new();
+
+    for local in (0..old.memories.len() - old.num_imported_memories).map(LocalMemoryIndex::new) {
+        let index = old.memory_index(local);
+        let old_ty = &old.memories[index];
+        let new_ty = match new.memories.get(new.memory_index(local)) {
+            Some(ty) => ty,
+            None => {
+                mismatches.push(HotSwapMismatch::MissingMemory {
+                    index: index.as_u32(),
+                });
+                continue;
+            }
+        };
+        let size = pages(local);
+        match new_ty.maximum {
+            Some(maximum) if maximum < size => mismatches.push(HotSwapMismatch::MemoryTooLarge {
+                index: index.as_u32(),
+                pages: size.0,
+                maximum: maximum.0,
+            }),
+            _ => {}
+        }
+        if old_ty.shared != new_ty.shared {
+            mismatches.push(HotSwapMismatch::MemorySharing {
+                index: index.as_u32(),
+                shared: old_ty.shared,
+            });
+        }
+    }
+
+    for local in (0..old.globals.len() - old.num_imported_globals).map(LocalGlobalIndex::new) {
+        let index = old.global_index(local);
+        let old_ty = old.globals[index];
+        // The constant globals are part of the code rather than of the
+        // state, and come from the new module.
+        if old_ty.mutability != Mutability::Var {
+            continue;
+        }
+        match new.globals.get(new.global_index(local)) {
+            Some(new_ty) if *new_ty != old_ty => mismatches.push(HotSwapMismatch::GlobalType {
+                index: index.as_u32(),
+                old: old_ty,
+                new: *new_ty,
+            }),
+            Some(_) => {}
+            None => mismatches.push(HotSwapMismatch::MissingGlobal {
+                index: index.as_u32(),
+            }),
+        }
+    }
+
+    mismatches
+}
@@ -1,11 +1,17 @@
 //! The import module contains the implementation data structures and helper functions used to
 //! manipulate and access a wasm module's imports including memories, tables, globals, and
 //! functions.
-use crate::{Exports, Extern, Module};
+use crate::sys::capability::{self, Capability, Denial};
+use crate::{
+    AbiContract, AsStoreMut, ExportError, Exports, Extern, Function, FunctionEnv, Instance, Module,
+    RuntimeError, StoreMut,
+};
+use std::any::Any;
 use std::collections::HashMap;
 use std::fmt;
+use std::sync::Arc;
 use wasmer_compiler::LinkError;
-use wasmer_types::ImportError;
+use wasmer_types::{ExternType, ImportError};
 
 /// All of the import data used when instantiating.
 ///
@@ -37,6 +43,26 @@ use wasmer_types::ImportError;
 #[derive(Clone, Default)]
 pub struct Imports {
     map: HashMap<(String, String), Extern>,
+    contracts: Vec<AbiContract>,
+    env_initializers: Vec<EnvInitializer>,
+    allow_missing: bool,
+}
+
+/// Initializes a host environment with the exports of a new instance,
+/// see [`Imports::initialize_env`].
+#[derive(Clone)]
+pub(crate) struct EnvInitializer {
+    init: Arc<dyn Fn(&mut StoreMut, &Instance) -> Result<(), ExportError> + Send + Sync>,
+}
+
+impl EnvInitializer {
+    pub(crate) fn initialize(
+        &self,
+        store: &mut StoreMut,
+        instance: &Instance,
+    ) -> Result<(), ExportError> {
+        (self.init)(store, instance)
+    }
 }
 
 impl Imports {
@@ -111,6 +137,176 @@ impl Imports {
             .insert((ns.to_string(), name.to_string()), val.into());
     }
 
+    /// Guards the functions of the namespace `ns` with `capability`.
+    ///
+    /// Once the capability is revoked or expired, calls to these functions
+    /// are denied as described by `denial`, including from instances that
+    /// were created before. Other externs of the namespace are left as
+    /// they are. The guarded functions are dynamic host functions, which
+    /// can't be stored in tables.
+    ///
+    /// # Usage
+    /// ```no_run
+    /// # use wasmer::{Function, Store};
+    /// # let mut store: Store = Default::default();
+    /// use wasmer::{imports, Capability, Denial, Value};
+    /// fn connect(_fd: i32) -> i32 {
+    ///     0
+    /// }
+    /// let mut import_object = imports! {
+    ///     "net" => { "connect" => Function::new_typed(&mut store, connect) },
+    /// };
+    /// let network = Capability::new("network");
+    /// import_object.guard_namespace(
+    ///     &mut store,
+    ///     "net",
+    ///     &network,
+    ///     Denial::Return(vec![Value::I32(-1)]),
+    /// );
+    /// // ... later on, `connect` returns -1 to the guest.
+    /// network.revoke();
+    /// ```
+    pub fn guard_namespace(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        ns: &str,
+        capability: &Capability,
+        denial: Denial,
+    ) {
+        for ((namespace, _), extern_) in self.map.iter_mut() {
+            if namespace != ns {
+                continue;
+            }
+            if let Extern::Function(function) = extern_ {
+                *function =
+                    capability::guard(store, function.clone(), capability.clone(), denial.clone());
+            }
+        }
+    }
+
+    /// Requires the instances created with these imports to fulfill
+    /// `contract`.
+    ///
+    /// The exports of every module are verified against the contract
+    /// before it is instantiated, so that none of its code runs, and the
+    /// instantiation fails with
+    /// [`InstantiationError::Contract`](crate::InstantiationError::Contract)
+    /// if they don't match it.
+    ///
+    /// # Usage
+    /// ```no_run
+    /// # use wasmer::{FunctionType, Type};
+    /// use wasmer::{AbiContract, Imports};
+    /// let mut import_object = Imports::new();
+    /// import_object.require_contract(
+    ///     AbiContract::new("host ABI v3")
+    ///         .function("handle", FunctionType::new(vec![Type::I32], vec![Type::I32])),
+    /// );
+    /// ```
+    pub fn require_contract(&mut self, contract: AbiContract) {
+        self.contracts.push(contract);
+    }
+
+    /// The contracts the instances created with these imports must
+    /// fulfill.
+    pub fn contracts(&self) -> &[AbiContract] {
+        &self.contracts
+    }
+
+    /// Fills the function imports these imports don't define with stubs,
+    /// instead of failing the instantiation.
+    ///
+    /// A stub traps with the name of the missing import when it is called,
+    /// so modules importing functions they don't use can still run. The
+    /// missing memories, tables and globals still fail the instantiation.
+    ///
+    /// # Usage
+    /// ```no_run
+    /// use wasmer::Imports;
+    /// let mut import_object = Imports::new();
+    /// import_object.allow_missing();
+    /// ```
+    pub fn allow_missing(&mut self) {
+        self.allow_missing = true;
+    }
+
+    /// Whether the missing function imports are filled with stubs, see
+    /// [`Imports::allow_missing`].
+    pub fn allows_missing(&self) -> bool {
+        self.allow_missing
+    }
+
+    /// A stub trapping when called for the missing import `module.name`
+    /// of type `ty`, if it is a function and missing imports are allowed.
+    pub(crate) fn stub(
+        &self,
+        store: &mut impl AsStoreMut,
+        module: &str,
+        name: &str,
+        ty: &ExternType,
+    ) -> Option<Extern> {
+        let ty = match ty {
+            ExternType::Function(ty) if self.allow_missing => ty,
+            _ => return None,
+        };
+        let message = format!("called the missing import `{}`.`{}`", module, name);
+        Some(Extern::Function(Function::new(store, ty, move |_args| {
+            Err(RuntimeError::new(message.clone()))
+        })))
+    }
+
+    /// Initializes the environment `env` of host functions with every
+    /// instance created with these imports.
+    ///
+    /// `init` is called once the exports of a new instance are available,
+    /// before its start function runs, so that the host functions can use
+    /// its memory or call its exported functions from their first call.
+    /// If it fails, so does the instantiation, with
+    /// [`InstantiationError::HostEnvInitialization`](crate::InstantiationError::HostEnvInitialization).
+    ///
+    /// # Usage
+    /// ```no_run
+    /// # use wasmer::{Function, Store};
+    /// # let mut store: Store = Default::default();
+    /// use wasmer::{imports, FunctionEnv, FunctionEnvMut, Memory};
+    /// #[derive(Default)]
+    /// struct Env {
+    ///     memory: Option<Memory>,
+    /// }
+    /// fn print(env: FunctionEnvMut<Env>, ptr: u32, len: u32) {
+    ///     let memory = env.data().memory.as_ref().unwrap();
+    ///     let mut bytes = vec![0; len as usize];
+    ///     memory.view(&env).read(ptr as u64, &mut bytes).unwrap();
+    ///     println!("{}", String::from_utf8_lossy(&bytes));
+    /// }
+    /// let env = FunctionEnv::new(&mut store, Env::default());
+    /// let mut import_object = imports! {
+    ///     "env" => { "print" => Function::new_typed_with_env(&mut store, &env, print) },
+    /// };
+    /// import_object.initialize_env(&env, |env, instance| {
+    ///     env.memory = Some(instance.exports.get_memory("memory")?.clone());
+    ///     Ok(())
+    /// });
+    /// ```
+    pub fn initialize_env<T, F>(&mut self, env: &FunctionEnv<T>, init: F)
+    where
+        T: Any + Send + 'static,
+        F: Fn(&mut T, &Instance) -> Result<(), ExportError> + Send + Sync + 'static,
+    {
+        // Only the handle is kept, so that `T` doesn't need to be `Sync`.
+        let handle = env.handle.clone();
+        self.env_initializers.push(EnvInitializer {
+            init: Arc::new(move |store: &mut StoreMut, instance: &Instance| {
+                let env = FunctionEnv::<T>::from_handle(handle.clone());
+                init(env.as_mut(store), instance)
+            }),
+        });
+    }
+
+    pub(crate) fn env_initializers(&self) -> &[EnvInitializer] {
+        &self.env_initializers
+    }
+
     /// Returns the contents of a namespace as an `Exports`.
     ///
     /// Returns `None` if the namespace doesn't exist.
@@ -128,6 +324,30 @@ impl Imports {
         }
     }
 
+    /// Resolve the imports of `module` like [`Imports::imports_for_module`],
+    /// stubbing the missing functions if [`Imports::allow_missing`] was
+    /// called.
+    pub(crate) fn externs_for_module(
+        &self,
+        store: &mut impl AsStoreMut,
+        module: &Module,
+    ) -> Result<Vec<Extern>, LinkError> {
+        module
+            .imports()
+            .map(|import| {
+                self.get_export(import.module(), import.name())
+                    .or_else(|| self.stub(store, import.module(), import.name(), import.ty()))
+                    .ok_or_else(|| {
+                        LinkError::Import(
+                            import.module().to_string(),
+                            import.name().to_string(),
+                            ImportError::UnknownImport(import.ty().clone()),
+                        )
+                    })
+            })
+            .collect()
+    }
+
     /// Resolve and return a vector of imports in the order they are defined in the `module`'s source code.
     ///
     /// This means the returned `Vec<Extern>` might be a subset of the imports contained in `self`.
@@ -196,6 +416,9 @@ impl fmt::Debug for Imports {
 
         f.debug_struct("Imports")
             .field("map", &SecretMap::new(self.map.len()))
+            .field("contracts", &self.contracts)
+            .field("env_initializers", &self.env_initializers.len())
+            .field("allow_missing", &self.allow_missing)
             .finish()
     }
 }
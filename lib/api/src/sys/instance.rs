@@ -1,13 +1,26 @@
+use crate::sys::call_hook::InstanceId;
+use crate::sys::debugger::{instance_debugger, DebugError, DebugFrame};
 use crate::sys::exports::Exports;
-use crate::sys::externals::Extern;
+use crate::sys::externals::{Extern, Global, Memory};
+use crate::sys::hot_swap::{self, HotSwapError, HotSwapIncompatibility};
 use crate::sys::imports::Imports;
 use crate::sys::module::Module;
-use crate::sys::{LinkError, RuntimeError};
+use crate::sys::resolver::Resolver;
+use crate::sys::{ContractViolation, ExportError, LinkError, RuntimeError, Value};
 use std::fmt;
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use wasmer_vm::{InstanceHandle, StoreHandle};
+use wasmer_types::entity::EntityRef;
+use wasmer_types::{
+    ExportIndex, FunctionIndex, GlobalIndex, ImportError, LocalGlobalIndex, LocalMemoryIndex,
+    LocalTableIndex, MemoryIndex, Mutability, Pages, WASM_PAGE_SIZE,
+};
+use wasmer_vm::{
+    DebugAction, InstanceDebugger, InstanceHandle, InterruptHandle, MemoryError, StoreHandle,
+    StoreId, TableElement, VMExtern, VMExternRef,
+};
 
-use super::store::AsStoreMut;
+use super::store::{AsStoreMut, AsStoreRef, StoreMut};
 
 /// A WebAssembly Instance is a stateful, executable
 /// instance of a WebAssembly [`Module`].
@@ -23,6 +36,8 @@ pub struct Instance {
     module: Module,
     /// The exports for an instance.
     pub exports: Exports,
+    instantiation_time: Duration,
+    interrupt: InterruptHandle,
 }
 
 #[cfg(test)]
@@ -33,10 +48,20 @@ mod send_test {
         true
     }
 
+    fn is_sync<T: Sync>() -> bool {
+        true
+    }
+
     #[test]
     fn instance_is_send() {
         assert!(is_send::<Instance>());
     }
+
+    #[test]
+    fn module_is_send_and_sync() {
+        assert!(is_send::<Module>());
+        assert!(is_sync::<Module>());
+    }
 }
 
 /// An error while instantiating a module.
@@ -59,13 +84,68 @@ pub enum InstantiationError {
 
     /// The module was compiled with a CPU feature that is not available on
     /// the current host.
-    #[error("missing required CPU features: {0:?}")]
+    #[error("missing required CPU features: {0}")]
     CpuFeature(String),
 
+    /// The module was compiled for another architecture or operating
+    /// system than the ones of the current host.
+    #[error("module compiled for `{0}`, which can't run on the host")]
+    DifferentArchOS(String),
+
     /// Import from a different Store.
     /// This error occurs when an import from a different store is used.
     #[error("cannot mix imports from different stores")]
     DifferentStores,
+
+    /// Module compiled by another engine.
+    /// This error occurs when a module is instantiated in a store whose
+    /// engine isn't the one the module was compiled with, or a clone of it.
+    #[error("cannot instantiate a module in a store of another engine")]
+    DifferentEngines,
+
+    /// The module was quarantined by the engine.
+    /// This error occurs when the instances of the module failed too
+    /// often, see [`QuarantinePolicy`](crate::QuarantinePolicy).
+    #[error("{0}")]
+    Quarantined(String),
+
+    /// The resource limiter of the store denied the creation of the
+    /// instance, or of one of its memories or tables, see
+    /// [`Store::set_limiter`](crate::Store::set_limiter).
+    #[error("the resource limiter of the store denied the instantiation")]
+    ResourceLimitExceeded,
+
+    /// The exports of the module don't match a contract the imports
+    /// require, see [`Imports::require_contract`](crate::Imports::require_contract).
+    #[error(transparent)]
+    Contract(ContractViolation),
+
+    /// A host environment failed to initialize with the exports of the
+    /// instance, see [`Imports::initialize_env`](crate::Imports::initialize_env).
+    #[error("failed to initialize a host environment: {0}")]
+    HostEnvInitialization(ExportError),
+}
+
+/// What runs once an instance is created by
+/// [`Instance::new_with_initialization`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Initialization {
+    /// Run the start function of the module, as [`Instance::new`] does.
+    Start,
+    /// Skip the start function, for it to be called later with
+    /// [`Instance::start`] or [`Instance::initialize`], e.g. once the host
+    /// environments are set up.
+    Deferred,
+    /// Run the start function, then the `_initialize` export of the
+    /// module if it has one, as WASI reactors expect before any of their
+    /// other exports are called.
+    Reactor,
+}
+
+impl Default for Initialization {
+    fn default() -> Self {
+        Self::Start
+    }
 }
 
 impl From<wasmer_compiler::InstantiationError> for InstantiationError {
@@ -74,12 +154,12 @@ impl From<wasmer_compiler::InstantiationError> for InstantiationError {
             wasmer_compiler::InstantiationError::Link(e) => Self::Link(e),
             wasmer_compiler::InstantiationError::Start(e) => Self::Start(e),
             wasmer_compiler::InstantiationError::CpuFeature(e) => Self::CpuFeature(e),
+            wasmer_compiler::InstantiationError::DifferentArchOS(e) => Self::DifferentArchOS(e),
         }
     }
 }
 
 impl Instance {
-    #[cfg(feature = "compiler")]
     /// Creates a new `Instance` from a WebAssembly [`Module`] and a
     /// set of imports using [`Imports`] or the [`imports`] macro helper.
     ///
@@ -110,15 +190,59 @@ impl Instance {
     /// Those are, as defined by the spec:
     ///  * Link errors that happen when plugging the imports into the instance
     ///  * Runtime errors that happen when running the module `start` function.
+    ///
+    /// The instance also fails if the exports of the module don't match
+    /// the contracts required by the `imports`, which is checked before
+    /// the module is instantiated, or if one of the host environments
+    /// initialized by the `imports` fails to initialize.
     pub fn new(
         store: &mut impl AsStoreMut,
         module: &Module,
         imports: &Imports,
     ) -> Result<Self, InstantiationError> {
-        let imports = imports
-            .imports_for_module(module)
+        Self::new_with_initialization(store, module, imports, Initialization::Start)
+    }
+
+    /// Creates a new `Instance` like [`Instance::new`], running what
+    /// `initialization` asks for once it is created.
+    ///
+    /// ```
+    /// # use wasmer::{imports, Initialization, Instance, Module, Store};
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut store = Store::default();
+    /// let module = Module::new(&store, "(module (func $f) (start $f))")?;
+    /// let instance = Instance::new_with_initialization(
+    ///     &mut store,
+    ///     &module,
+    ///     &imports! {},
+    ///     Initialization::Deferred,
+    /// )?;
+    /// instance.start(&mut store)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// The function can return the [`InstantiationError`]s of
+    /// [`Instance::new`], with a [`InstantiationError::Start`] error if
+    /// the `_initialize` export of a reactor traps.
+    pub fn new_with_initialization(
+        store: &mut impl AsStoreMut,
+        module: &Module,
+        imports: &Imports,
+        initialization: Initialization,
+    ) -> Result<Self, InstantiationError> {
+        let start = Instant::now();
+        for contract in imports.contracts() {
+            contract
+                .verify_module(module)
+                .map_err(InstantiationError::Contract)?;
+        }
+        let externs = imports
+            .externs_for_module(store, module)
             .map_err(InstantiationError::Link)?;
-        let mut handle = module.instantiate(store, &imports)?;
+        let mut handle = module.instantiate(store, &externs)?;
         let exports = module
             .exports()
             .map(|export| {
@@ -128,17 +252,36 @@ impl Instance {
                 (name, extern_)
             })
             .collect::<Exports>();
+        let interrupt = handle.interrupt_handle();
 
-        let instance = Self {
+        let mut instance = Self {
             _handle: StoreHandle::new(store.objects_mut(), handle),
             module: module.clone(),
             exports,
+            instantiation_time: Duration::ZERO,
+            interrupt,
         };
 
+        // The host environments are initialized before the start function
+        // runs, as it may call the host functions using them.
+        for initializer in imports.env_initializers() {
+            initializer
+                .initialize(&mut store.as_store_mut(), &instance)
+                .map_err(InstantiationError::HostEnvInitialization)?;
+        }
+        module.finish_instantiation(
+            store,
+            &instance._handle,
+            initialization != Initialization::Deferred,
+        )?;
+        instance
+            .initialize(store, initialization)
+            .map_err(InstantiationError::Start)?;
+
+        instance.instantiation_time = start.elapsed();
         Ok(instance)
     }
 
-    #[cfg(feature = "compiler")]
     /// Creates a new `Instance` from a WebAssembly [`Module`] and a
     /// vector of imports.
     ///
@@ -154,6 +297,51 @@ impl Instance {
         module: &Module,
         externs: &[Extern],
     ) -> Result<Self, InstantiationError> {
+        Self::from_externs(store, module, externs)
+    }
+
+    /// Creates a new `Instance` from a WebAssembly [`Module`], asking
+    /// `resolver` for each of its imports.
+    ///
+    /// ## Errors
+    ///
+    /// The function can return [`InstantiationError`]s, like
+    /// [`Instance::new`], with a link error for the first import the
+    /// resolver doesn't provide.
+    pub fn new_with_resolver(
+        store: &mut impl AsStoreMut,
+        module: &Module,
+        resolver: &impl Resolver,
+    ) -> Result<Self, InstantiationError> {
+        let externs = module
+            .imports()
+            .map(|import| {
+                resolver
+                    .resolve(
+                        &mut store.as_store_mut(),
+                        import.module(),
+                        import.name(),
+                        import.ty(),
+                    )
+                    .ok_or_else(|| {
+                        LinkError::Import(
+                            import.module().to_string(),
+                            import.name().to_string(),
+                            ImportError::UnknownImport(import.ty().clone()),
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(InstantiationError::Link)?;
+        Self::from_externs(store, module, &externs)
+    }
+
+    fn from_externs(
+        store: &mut impl AsStoreMut,
+        module: &Module,
+        externs: &[Extern],
+    ) -> Result<Self, InstantiationError> {
+        let start = Instant::now();
         let imports = externs.to_vec();
         let mut handle = module.instantiate(store, &imports)?;
         let exports = module
@@ -165,13 +353,18 @@ impl Instance {
                 (name, extern_)
             })
             .collect::<Exports>();
+        let interrupt = handle.interrupt_handle();
 
-        let instance = Self {
+        let mut instance = Self {
             _handle: StoreHandle::new(store.objects_mut(), handle),
             module: module.clone(),
             exports,
+            instantiation_time: Duration::ZERO,
+            interrupt,
         };
+        module.finish_instantiation(store, &instance._handle, true)?;
 
+        instance.instantiation_time = start.elapsed();
         Ok(instance)
     }
 
@@ -179,6 +372,548 @@ impl Instance {
     pub fn module(&self) -> &Module {
         &self.module
     }
+
+    /// Runs the start function of the module, if it has one that didn't
+    /// run yet because the instance was created with
+    /// [`Initialization::Deferred`].
+    ///
+    /// The start function runs at most once: calling this again, or on an
+    /// instance that already ran it, does nothing.
+    pub fn start(&self, store: &mut impl AsStoreMut) -> Result<(), RuntimeError> {
+        self.module.start(store, &self._handle)
+    }
+
+    /// Runs what `initialization` asks for on an instance created with
+    /// [`Initialization::Deferred`], e.g. once the host environments
+    /// using its exports are set up.
+    pub fn initialize(
+        &self,
+        store: &mut impl AsStoreMut,
+        initialization: Initialization,
+    ) -> Result<(), RuntimeError> {
+        if initialization == Initialization::Deferred {
+            return Ok(());
+        }
+        self.start(store)?;
+        if initialization == Initialization::Reactor {
+            if let Ok(initialize) = self.exports.get_function("_initialize") {
+                initialize.call(store, &[])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the id identifying the instance in the
+    /// [`CallHookInfo`](crate::CallHookInfo) of its functions.
+    pub fn id(&self, store: &impl AsStoreRef) -> InstanceId {
+        InstanceId::from_vmctx(self._handle.get(store.as_store_ref().objects()).vmctx_ptr())
+    }
+
+    /// Returns a handle interrupting the Wasm code of the instance, which
+    /// can be moved to another thread.
+    ///
+    /// Once [`InterruptHandle::interrupt`] is called, the running code of
+    /// the instance traps with [`TrapCode::Interrupt`] when it enters a
+    /// function or starts a new loop iteration, and so do its following
+    /// calls until the handle is [reset](InterruptHandle::reset).
+    ///
+    /// [`TrapCode::Interrupt`]: wasmer_types::TrapCode::Interrupt
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    /// Attaches a debugger to the instance, replacing the previous one
+    /// and its breakpoints.
+    ///
+    /// The debugger only stops the functions run by the
+    /// [`Interpreter`](crate::Interpreter). `handler` is called on every
+    /// stop, and can call back into the store, which it receives, and
+    /// change the breakpoints. The instance doesn't stop while it runs.
+    pub fn set_debugger<F>(&self, store: &mut impl AsStoreMut, handler: F)
+    where
+        F: FnMut(&mut StoreMut<'_>, &DebugFrame) -> DebugAction + Send + 'static,
+    {
+        let debugger = instance_debugger(store.as_store_mut().as_raw(), handler);
+        self._handle
+            .get_mut(store.objects_mut())
+            .set_debugger(Some(debugger));
+    }
+
+    /// Detaches the debugger of the instance, if any.
+    pub fn remove_debugger(&self, store: &mut impl AsStoreMut) {
+        self._handle.get_mut(store.objects_mut()).set_debugger(None);
+    }
+
+    /// Stops the function `func_index` before the operator `offset`
+    /// bytes into its body, or the first one after it.
+    pub fn set_breakpoint(
+        &self,
+        store: &mut impl AsStoreMut,
+        func_index: u32,
+        offset: u32,
+    ) -> Result<(), DebugError> {
+        let (debugger, address) = self.breakpoint_location(store, func_index, offset)?;
+        debugger.add_breakpoint(address);
+        Ok(())
+    }
+
+    /// Removes the breakpoint set with [`Instance::set_breakpoint`],
+    /// returning whether there was one.
+    pub fn clear_breakpoint(
+        &self,
+        store: &mut impl AsStoreMut,
+        func_index: u32,
+        offset: u32,
+    ) -> Result<bool, DebugError> {
+        let (debugger, address) = self.breakpoint_location(store, func_index, offset)?;
+        Ok(debugger.remove_breakpoint(address))
+    }
+
+    /// Stops before the next instruction of the instance that runs.
+    pub fn step(&self, store: &mut impl AsStoreMut) -> Result<(), DebugError> {
+        let handle = self._handle.get_mut(store.objects_mut());
+        handle.debugger_mut().ok_or(DebugError::NoDebugger)?.step();
+        Ok(())
+    }
+
+    fn breakpoint_location<'a>(
+        &self,
+        store: &'a mut impl AsStoreMut,
+        func_index: u32,
+        offset: u32,
+    ) -> Result<(&'a mut InstanceDebugger, usize), DebugError> {
+        let handle = self._handle.get_mut(store.objects_mut());
+        let address = handle
+            .instruction_address(FunctionIndex::from_u32(func_index), offset)
+            .ok_or(DebugError::UnknownLocation { func_index, offset })?;
+        let debugger = handle.debugger_mut().ok_or(DebugError::NoDebugger)?;
+        Ok((debugger, address))
+    }
+
+    /// Measures the resources the instance uses.
+    ///
+    /// Only the memories and tables defined by the module are counted, as
+    /// the imported ones belong to the host.
+    pub fn metrics(&self, store: &mut impl AsStoreMut) -> InstanceMetrics {
+        let info = self.module.info();
+        let memory_bytes = self
+            .local_memories()
+            .map(|(_, index)| self.memory(store, index).view(store).data_size())
+            .sum();
+        let handle = self._handle.get_mut(store.objects_mut());
+        let table_elements = (0..info.tables.len() - info.num_imported_tables)
+            .map(|local| u64::from(handle.get_local_table(LocalTableIndex::new(local)).size()))
+            .sum();
+        InstanceMetrics {
+            instantiation_time: self.instantiation_time,
+            memory_bytes,
+            table_elements,
+        }
+    }
+
+    /// Captures the current state of the instance in an [`InstanceImage`].
+    ///
+    /// This lets an embedder run expensive initialization code once and
+    /// then [`restore`](Self::restore) fresh instances of the same module
+    /// from the image instead of initializing each of them.
+    ///
+    /// ```
+    /// # use wasmer::{imports, Store, Module, Instance, TypedFunction};
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut store = Store::default();
+    /// let module = Module::new(&store, r#"
+    ///   (module
+    ///     (global $counter (mut i32) (i32.const 0))
+    ///     (func (export "bump") (result i32)
+    ///       (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+    ///       (global.get $counter)))
+    /// "#)?;
+    /// let warm = Instance::new(&mut store, &module, &imports! {})?;
+    /// let bump: TypedFunction<(), i32> = warm.exports.get_typed_function(&mut store, "bump")?;
+    /// bump.call(&mut store)?;
+    /// let image = warm.snapshot(&mut store)?;
+    ///
+    /// let fresh = Instance::new(&mut store, &module, &imports! {})?;
+    /// fresh.restore(&mut store, &image)?;
+    /// let bump: TypedFunction<(), i32> = fresh.exports.get_typed_function(&mut store, "bump")?;
+    /// assert_eq!(bump.call(&mut store)?, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn snapshot(
+        &self,
+        store: &mut impl AsStoreMut,
+    ) -> Result<InstanceImage, InstanceImageError> {
+        let info = self.module.info();
+        let mut image = self.capture_state(store)?;
+        let handle = self._handle.get_mut(store.objects_mut());
+        let func_indices = handle.func_indices();
+        for local in (0..info.tables.len() - info.num_imported_tables).map(LocalTableIndex::new) {
+            let size = handle.get_local_table(local).size();
+            let elements = (0..size)
+                .map(|i| match handle.table_get(local, i) {
+                    Some(TableElement::FuncRef(None)) | None => {
+                        Ok(ImageTableElement::FuncRef(None))
+                    }
+                    Some(TableElement::FuncRef(Some(func_ref))) => func_indices
+                        .get(&func_ref)
+                        .map(|&index| ImageTableElement::FuncRef(Some(index)))
+                        .ok_or(InstanceImageError::ForeignFunction {
+                            table: info.table_index(local).index() as u32,
+                            index: i,
+                        }),
+                    Some(TableElement::ExternRef(extern_ref)) => {
+                        Ok(ImageTableElement::ExternRef(extern_ref))
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            image.tables.push((local, elements));
+        }
+
+        Ok(image)
+    }
+
+    /// Captures the memories and the mutable globals of the instance in an
+    /// image without tables.
+    fn capture_state(
+        &self,
+        store: &mut impl AsStoreMut,
+    ) -> Result<InstanceImage, InstanceImageError> {
+        let mut image = InstanceImage {
+            module: self.module.clone(),
+            store_id: store.objects_mut().id(),
+            memories: Vec::new(),
+            globals: Vec::new(),
+            tables: Vec::new(),
+        };
+
+        for (local, index) in self.local_memories() {
+            let memory = self.memory(store, index);
+            let view = memory.view(store);
+            let mut data = vec![0; view.data_size() as usize];
+            // Reading the whole view can't be out of bounds.
+            view.read(0, &mut data).unwrap();
+            image.memories.push((local, data));
+        }
+
+        for (local, index) in self.local_globals() {
+            let global = self.global(store, index);
+            if global.ty(store).mutability != Mutability::Var {
+                continue;
+            }
+            match global.get(store) {
+                value @ Value::I32(_)
+                | value @ Value::I64(_)
+                | value @ Value::F32(_)
+                | value @ Value::F64(_)
+                | value @ Value::V128(_) => image.globals.push((local, value)),
+                _ => return Err(InstanceImageError::UnsupportedGlobal(index.index() as u32)),
+            }
+        }
+
+        Ok(image)
+    }
+
+    /// Checks whether `new_module` can take over the state of the instance
+    /// in [`hot_swap`](Self::hot_swap), listing all the mismatches if it
+    /// can't.
+    pub fn check_hot_swap(
+        &self,
+        store: &mut impl AsStoreMut,
+        new_module: &Module,
+    ) -> Result<(), HotSwapIncompatibility> {
+        let pages = self
+            .local_memories()
+            .map(|(_, index)| self.memory(store, index).view(store).size())
+            .collect::<Vec<_>>();
+        let mismatches = hot_swap::mismatches(self.module.info(), new_module.info(), |local| {
+            pages[local.index()]
+        });
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(HotSwapIncompatibility { mismatches })
+        }
+    }
+
+    /// Replaces the module of the instance with `new_module`, a new
+    /// version of it, keeping the state of the instance.
+    ///
+    /// `new_module` is instantiated with `imports`, then takes over the
+    /// contents of the memories and the values of the mutable globals the
+    /// instance defines, which [`check_hot_swap`](Self::check_hot_swap)
+    /// must find compatible. Its start function doesn't run, as the state
+    /// is already initialized, and its tables, which point to its own
+    /// functions, come from its element segments.
+    ///
+    /// The exports of the instance are replaced with the ones of the new
+    /// module. The clones of the instance and the exports taken before the
+    /// swap keep running the previous module, on its copy of the state.
+    ///
+    /// ```
+    /// # use wasmer::{imports, Instance, Module, Store, TypedFunction};
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut store = Store::default();
+    /// let counter = |step| {
+    ///     format!(r#"(module
+    ///       (global $count (mut i32) (i32.const 0))
+    ///       (func (export "bump") (result i32)
+    ///         (global.set $count (i32.add (global.get $count) (i32.const {})))
+    ///         (global.get $count)))"#, step)
+    /// };
+    /// let mut instance = Instance::new(&mut store, &Module::new(&store, counter(1))?, &imports! {})?;
+    /// let bump: TypedFunction<(), i32> = instance.exports.get_typed_function(&mut store, "bump")?;
+    /// assert_eq!(bump.call(&mut store)?, 1);
+    ///
+    /// instance.hot_swap(&mut store, &Module::new(&store, counter(10))?, &imports! {})?;
+    /// let bump: TypedFunction<(), i32> = instance.exports.get_typed_function(&mut store, "bump")?;
+    /// assert_eq!(bump.call(&mut store)?, 11);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn hot_swap(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        new_module: &Module,
+        imports: &Imports,
+    ) -> Result<(), HotSwapError> {
+        self.check_hot_swap(store, new_module)
+            .map_err(HotSwapError::Incompatible)?;
+        let mut image = self.capture_state(store).map_err(HotSwapError::State)?;
+        let instance =
+            Self::new_with_initialization(store, new_module, imports, Initialization::Deferred)
+                .map_err(HotSwapError::Instantiation)?;
+        image.module = new_module.clone();
+        instance
+            .restore(store, &image)
+            .map_err(HotSwapError::State)?;
+        *self = instance;
+        Ok(())
+    }
+
+    /// Resets the state of the instance to a previously captured
+    /// [`InstanceImage`].
+    ///
+    /// The image must have been taken from an instance of the same
+    /// [`Module`]. Memories and tables are grown to the size they had in
+    /// the image if needed; memory beyond the size of the image is zeroed.
+    pub fn restore(
+        &self,
+        store: &mut impl AsStoreMut,
+        image: &InstanceImage,
+    ) -> Result<(), InstanceImageError> {
+        if !std::ptr::eq(self.module.info(), image.module.info()) {
+            return Err(InstanceImageError::ModuleMismatch);
+        }
+        let has_extern_refs = image.tables.iter().any(|(_, elements)| {
+            elements
+                .iter()
+                .any(|e| matches!(e, ImageTableElement::ExternRef(Some(_))))
+        });
+        if has_extern_refs && store.objects_mut().id() != image.store_id {
+            return Err(InstanceImageError::DifferentStores);
+        }
+        let info = self.module.info();
+
+        for (local, data) in &image.memories {
+            let memory = self.memory(store, info.memory_index(*local));
+            let current = memory.view(store).data_size() as usize;
+            if current < data.len() {
+                let delta = (data.len() - current) / WASM_PAGE_SIZE;
+                memory.grow(store, Pages(delta as u32))?;
+            }
+            let view = memory.view(store);
+            // The memory is at least as large as the image at this point.
+            view.write(0, data).unwrap();
+            let tail = view.data_size() as usize - data.len();
+            if tail > 0 {
+                view.write(data.len() as u64, &vec![0; tail]).unwrap();
+            }
+        }
+
+        for (local, value) in &image.globals {
+            let global = self.global(store, info.global_index(*local));
+            global
+                .set(store, value.clone())
+                .map_err(InstanceImageError::Global)?;
+        }
+
+        let handle = self._handle.get_mut(store.objects_mut());
+        for (local, elements) in &image.tables {
+            let table_error = || InstanceImageError::Table(info.table_index(*local).index() as u32);
+            let size = handle.get_local_table(*local).size();
+            if size < elements.len() as u32 {
+                handle
+                    .table_grow(
+                        *local,
+                        elements.len() as u32 - size,
+                        TableElement::FuncRef(None),
+                    )
+                    .ok_or_else(table_error)?;
+            }
+            for (i, element) in elements.iter().enumerate() {
+                let element = match element {
+                    ImageTableElement::FuncRef(index) => {
+                        TableElement::FuncRef(index.and_then(|index| handle.func_ref(index)))
+                    }
+                    ImageTableElement::ExternRef(extern_ref) => {
+                        TableElement::ExternRef(*extern_ref)
+                    }
+                };
+                handle
+                    .table_set(*local, i as u32, element)
+                    .map_err(|_| table_error())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn local_memories(&self) -> impl Iterator<Item = (LocalMemoryIndex, MemoryIndex)> + '_ {
+        let info = self.module.info();
+        (0..info.memories.len() - info.num_imported_memories)
+            .map(LocalMemoryIndex::new)
+            .map(move |local| (local, info.memory_index(local)))
+    }
+
+    fn local_globals(&self) -> impl Iterator<Item = (LocalGlobalIndex, GlobalIndex)> + '_ {
+        let info = self.module.info();
+        (0..info.globals.len() - info.num_imported_globals)
+            .map(LocalGlobalIndex::new)
+            .map(move |local| (local, info.global_index(local)))
+    }
+
+    fn memory(&self, store: &mut impl AsStoreMut, index: MemoryIndex) -> Memory {
+        let handle = self._handle.get_mut(store.objects_mut());
+        match handle.lookup_by_declaration(ExportIndex::Memory(index)) {
+            VMExtern::Memory(memory) => Memory::from_vm_extern(store, memory),
+            _ => unreachable!(),
+        }
+    }
+
+    fn global(&self, store: &mut impl AsStoreMut, index: GlobalIndex) -> Global {
+        let handle = self._handle.get_mut(store.objects_mut());
+        match handle.lookup_by_declaration(ExportIndex::Global(index)) {
+            VMExtern::Global(global) => Global::from_vm_extern(store, global),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// The resources used by an [`Instance`], as measured by
+/// [`Instance::metrics`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstanceMetrics {
+    /// The time it took to instantiate the module, including running its
+    /// start function.
+    pub instantiation_time: Duration,
+    /// The current size in bytes of the linear memories of the instance.
+    pub memory_bytes: u64,
+    /// The current number of elements of the tables of the instance.
+    pub table_elements: u64,
+}
+
+/// A snapshot of the state of an [`Instance`], taken with
+/// [`Instance::snapshot`] and applied with [`Instance::restore`].
+///
+/// An image holds the memories, mutable globals and tables defined by the
+/// instance's module; imported memories, globals and tables belong to the
+/// host and are not captured. Function references in tables are stored as
+/// function indices, so the image can be restored into any instance of the
+/// same module. External references can only be restored in the store the
+/// image was taken from.
+#[derive(Clone)]
+pub struct InstanceImage {
+    module: Module,
+    store_id: StoreId,
+    memories: Vec<(LocalMemoryIndex, Vec<u8>)>,
+    globals: Vec<(LocalGlobalIndex, Value)>,
+    tables: Vec<(LocalTableIndex, Vec<ImageTableElement>)>,
+}
+
+#[derive(Clone)]
+enum ImageTableElement {
+    FuncRef(Option<FunctionIndex>),
+    ExternRef(Option<VMExternRef>),
+}
+
+impl InstanceImage {
+    /// Gets the [`Module`] the image was taken from.
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    /// Gets the contents of the memories captured in the image, with
+    /// their index in the module.
+    pub fn memories(&self) -> impl Iterator<Item = (u32, &[u8])> + '_ {
+        let info = self.module.info();
+        self.memories
+            .iter()
+            .map(move |(local, data)| (info.memory_index(*local).as_u32(), data.as_slice()))
+    }
+
+    /// Gets the values of the globals captured in the image, with their
+    /// index in the module.
+    pub fn globals(&self) -> impl Iterator<Item = (u32, &Value)> + '_ {
+        let info = self.module.info();
+        self.globals
+            .iter()
+            .map(move |(local, value)| (info.global_index(*local).as_u32(), value))
+    }
+}
+
+impl fmt::Debug for InstanceImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("InstanceImage")
+            .field(
+                "memory_bytes",
+                &self
+                    .memories
+                    .iter()
+                    .map(|(_, data)| data.len())
+                    .sum::<usize>(),
+            )
+            .field("globals", &self.globals.len())
+            .field("tables", &self.tables.len())
+            .finish()
+    }
+}
+
+/// An error while taking or restoring an [`InstanceImage`].
+#[derive(Error, Debug)]
+pub enum InstanceImageError {
+    /// The image was taken from an instance of a different module.
+    #[error("the image was taken from an instance of a different module")]
+    ModuleMismatch,
+
+    /// The image holds external references from a different store.
+    #[error("the image holds external references from a different store")]
+    DifferentStores,
+
+    /// A mutable global holds a reference, which can't be captured.
+    #[error("global {0} holds a reference, which can't be captured in an image")]
+    UnsupportedGlobal(u32),
+
+    /// A table holds a function that doesn't belong to the instance.
+    #[error("element {index} of table {table} refers to a function outside of the instance")]
+    ForeignFunction {
+        /// The index of the table.
+        table: u32,
+        /// The index of the element in the table.
+        index: u32,
+    },
+
+    /// A memory couldn't be grown to the size of the image.
+    #[error(transparent)]
+    Memory(#[from] MemoryError),
+
+    /// A global couldn't be restored.
+    #[error(transparent)]
+    Global(RuntimeError),
+
+    /// A table couldn't be restored to the contents of the image.
+    #[error("table {0} can't be restored from the image")]
+    Table(u32),
 }
 
 impl fmt::Debug for Instance {
@@ -28,6 +28,9 @@ pub enum MemoryAccessError {
     /// String is not valid UTF-8.
     #[error("string is not valid utf-8")]
     NonUtf8String,
+    /// Address isn't a multiple of the alignment of the accessed type.
+    #[error("unaligned memory access")]
+    Unaligned,
 }
 
 impl From<MemoryAccessError> for RuntimeError {
@@ -1,18 +1,33 @@
+mod call_hook;
+mod capability;
+mod contract;
+mod debugger;
+pub mod diagnostics;
 mod exports;
 mod extern_ref;
 mod externals;
 mod function_env;
+mod hot_swap;
 mod imports;
 mod instance;
 mod mem_access;
 mod module;
 mod native;
+#[cfg(feature = "native-imports")]
+mod native_library;
 mod native_type;
 mod ptr;
+mod resolver;
+#[cfg(feature = "signing")]
+mod signature;
 mod store;
 mod tunables;
 mod value;
 
+pub use crate::sys::call_hook::{CallHook, CallHookFn, CallHookInfo, InstanceId};
+pub use crate::sys::capability::{Capability, CapabilityError, Denial};
+pub use crate::sys::contract::{AbiContract, ContractViolation, ExpectedExport, ExportMismatch};
+pub use crate::sys::debugger::{DebugError, DebugFrame};
 pub use crate::sys::exports::{ExportError, Exportable, Exports, ExportsIterator};
 pub use crate::sys::extern_ref::ExternRef;
 pub use crate::sys::externals::{
@@ -20,12 +35,21 @@ pub use crate::sys::externals::{
     WasmTypeList,
 };
 pub use crate::sys::function_env::{FunctionEnv, FunctionEnvMut};
+pub use crate::sys::hot_swap::{HotSwapError, HotSwapIncompatibility, HotSwapMismatch};
 pub use crate::sys::imports::Imports;
-pub use crate::sys::instance::{Instance, InstantiationError};
+pub use crate::sys::instance::{
+    Initialization, Instance, InstanceImage, InstanceImageError, InstanceMetrics,
+    InstantiationError,
+};
 pub use crate::sys::mem_access::{MemoryAccessError, WasmRef, WasmSlice, WasmSliceIter};
-pub use crate::sys::module::Module;
+pub use crate::sys::module::{IoCompileError, Module};
 pub use crate::sys::native::TypedFunction;
+#[cfg(feature = "native-imports")]
+pub use crate::sys::native_library::{NativeLibrary, NativeLibraryError};
 pub use crate::sys::native_type::NativeWasmTypeInto;
+pub use crate::sys::resolver::{Resolver, ResolverChain};
+#[cfg(feature = "signing")]
+pub use crate::sys::signature::{SignatureError, SigningKey, VerifyingKey};
 pub use crate::sys::store::{AsStoreMut, AsStoreRef, StoreMut, StoreRef};
 
 pub use crate::sys::ptr::{Memory32, Memory64, MemorySize, WasmPtr, WasmPtr64};
@@ -37,22 +61,28 @@ pub use target_lexicon::{Architecture, CallingConvention, OperatingSystem, Tripl
 pub use wasmer_compiler::{
     wasmparser, CompilerConfig, FunctionMiddleware, MiddlewareReaderState, ModuleMiddleware,
 };
-pub use wasmer_compiler::{Features, FrameInfo, LinkError, RuntimeError, Tunables};
+pub use wasmer_compiler::{
+    Features, FrameInfo, LinkError, ModuleMetrics, RuntimeError, RuntimeStackTrace, Tunables,
+};
 pub use wasmer_derive::ValueType;
-pub use wasmer_types::is_wasm;
+pub use wasmer_types::{attach_metadata, is_wasm, strip_custom_sections};
 pub use wasmer_types::{
-    CpuFeature, ExportType, ExternType, FunctionType, GlobalType, ImportType, MemoryType,
-    Mutability, TableType, Target, Type,
+    CpuBaseline, CpuFeature, ExportType, ExternType, FunctionType, GlobalType, ImportType,
+    MemoryType, Mutability, TableType, Target, Type,
 };
 
 pub use wasmer_types::{
     Bytes, CompileError, DeserializeError, ExportIndex, GlobalInit, LocalFunctionIndex,
-    MiddlewareError, Pages, ParseCpuFeatureError, SerializeError, ValueType, WasmError, WasmResult,
-    WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
+    MiddlewareError, Pages, ParseCpuFeatureError, SerializeError, TrapCode, ValueType, WasmError,
+    WasmResult, WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
 };
 
 // TODO: should those be moved into wasmer::vm as well?
-pub use wasmer_vm::{raise_user_trap, MemoryError};
+pub use wasmer_vm::{
+    handle_wasm_trap, is_wasm_trap, raise_user_trap, DebugAction, InstancePool, InterruptHandle,
+    MemoryError, MemoryGrowObserver, PoolingConfig, ResourceLimiter, StopReason, TrapHandlers,
+    WaitResult,
+};
 pub mod vm {
     //! The `vm` module re-exports wasmer-vm types.
 
@@ -74,9 +104,11 @@ pub use wasmer_compiler_cranelift::{Cranelift, CraneliftOptLevel};
 #[cfg(feature = "llvm")]
 pub use wasmer_compiler_llvm::{LLVMOptLevel, LLVM};
 
-pub use wasmer_compiler::Engine;
-#[cfg(feature = "compiler")]
+#[cfg(feature = "interpreter")]
+pub use wasmer_compiler_interpreter::Interpreter;
+
 pub use wasmer_compiler::{Artifact, EngineBuilder};
+pub use wasmer_compiler::{Engine, QuarantinePolicy};
 
 /// Version number of this crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -1,8 +1,13 @@
 use crate::sys::InstantiationError;
+use crate::sys::RuntimeError;
+#[cfg(feature = "signing")]
+use crate::sys::{signature, SignatureError, SigningKey, VerifyingKey};
 use crate::AsStoreMut;
 use crate::AsStoreRef;
+use crate::Engine;
 use bytes::Bytes;
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::io;
 use std::path::Path;
@@ -10,13 +15,16 @@ use std::sync::Arc;
 use thiserror::Error;
 use wasmer_compiler::Artifact;
 use wasmer_compiler::ArtifactCreate;
+use wasmer_compiler::ModuleMetrics;
+#[cfg(feature = "compiler")]
+use wasmer_types::strip_custom_sections;
 #[cfg(feature = "wat")]
 use wasmer_types::WasmError;
 use wasmer_types::{
     CompileError, DeserializeError, ExportsIterator, ImportsIterator, ModuleInfo, SerializeError,
 };
 use wasmer_types::{ExportType, ImportType};
-use wasmer_vm::InstanceHandle;
+use wasmer_vm::{InstanceHandle, StoreHandle};
 
 #[derive(Error, Debug)]
 pub enum IoCompileError {
@@ -54,6 +62,10 @@ pub struct Module {
     // ownership of the code and its metadata.
     artifact: Arc<Artifact>,
     module_info: Arc<ModuleInfo>,
+    // The engine the module was compiled or deserialized with, which owns
+    // its code and its signatures. The module can be instantiated in any
+    // store of this engine, from any thread.
+    engine: Engine,
 }
 
 pub trait IntoBytes {
@@ -169,6 +181,45 @@ impl Module {
         Self::from_binary(store, bytes.as_ref())
     }
 
+    #[cfg(feature = "compiler")]
+    /// Creates a new WebAssembly Module like [`Module::new`], dropping the
+    /// custom sections whose name `keep` doesn't accept first.
+    ///
+    /// The custom sections (the name section, DWARF, ...) are otherwise
+    /// kept in the module and in its serialized form, so stripping them
+    /// keeps caches and distributed artifacts small when they aren't
+    /// needed. Without the name section, traces only show function
+    /// indices.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let mut store = Store::default();
+    /// // A module with a `version` custom section holding "1.2.0".
+    /// let bytes = b"\0asm\x01\0\0\0\0\x0d\x07version1.2.0";
+    /// let module = Module::new_stripped(&store, &bytes[..], |_| false)?;
+    /// assert_eq!(module.custom_sections("version").next(), None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_stripped(
+        store: &impl AsStoreRef,
+        bytes: impl AsRef<[u8]>,
+        keep: impl Fn(&str) -> bool,
+    ) -> Result<Self, CompileError> {
+        #[cfg(feature = "wat")]
+        let bytes = wat::parse_bytes(bytes.as_ref()).map_err(|e| {
+            CompileError::Wasm(WasmError::Generic(format!(
+                "Error when converting wat: {}",
+                e
+            )))
+        })?;
+        let binary = strip_custom_sections(bytes.as_ref(), keep)?;
+        Self::from_binary(store, &binary)
+    }
+
     #[cfg(feature = "compiler")]
     /// Creates a new WebAssembly module from a file path.
     pub fn from_file(
@@ -213,6 +264,37 @@ impl Module {
         Ok(module)
     }
 
+    #[cfg(feature = "compiler")]
+    /// Creates a new WebAssembly module from a binary read from `reader`,
+    /// a network stream for example.
+    ///
+    /// The sections and function bodies of the module are validated as
+    /// they arrive, so an invalid module is rejected without waiting for
+    /// the rest of it. The module is compiled once it is complete.
+    ///
+    /// Like [`Module::from_binary`], this function is not compatible with
+    /// the WebAssembly text format.
+    pub fn from_reader(
+        store: &impl AsStoreRef,
+        mut reader: impl io::Read,
+    ) -> Result<Self, IoCompileError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("from_reader").entered();
+        let mut validator = store.as_store_ref().engine().streaming_validator();
+        let mut chunk = vec![0; 64 * 1024];
+        loop {
+            let len = match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            validator.push(&chunk[..len])?;
+        }
+        let binary = validator.finish()?;
+        Ok(Self::compile(store, &binary)?)
+    }
+
     #[cfg(feature = "compiler")]
     /// Validates a new WebAssembly Module given the configuration
     /// in the Store.
@@ -221,16 +303,20 @@ impl Module {
     /// WebAssembly features in the Store Engine to assure deterministic
     /// validation of the Module.
     pub fn validate(store: &impl AsStoreRef, binary: &[u8]) -> Result<(), CompileError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("validate", bytes = binary.len()).entered();
         store.as_store_ref().engine().validate(binary)
     }
 
     #[cfg(feature = "compiler")]
     fn compile(store: &impl AsStoreRef, binary: &[u8]) -> Result<Self, CompileError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("compile", bytes = binary.len()).entered();
         let artifact = store
             .as_store_ref()
             .engine()
             .compile(binary, store.as_store_ref().tunables())?;
-        Ok(Self::from_artifact(artifact))
+        Ok(Self::from_artifact(store.as_store_ref().engine(), artifact))
     }
 
     /// Serializes a module into a binary representation that the `Engine`
@@ -269,7 +355,41 @@ impl Module {
         self.artifact.serialize_to_file(path.as_ref())
     }
 
-    #[cfg(feature = "compiler")]
+    /// Serializes a module into a binary signed with `key`, that
+    /// [`Module::deserialize_checked`] loads when the public key of `key`
+    /// is trusted.
+    ///
+    /// # Usage
+    ///
+    /// ```ignore
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let mut store = Store::default();
+    /// # let module = Module::from_file(&store, "path/to/foo.wasm")?;
+    /// let key = SigningKey::from_pem(&std::fs::read_to_string("key.pem")?)?;
+    /// let serialized = module.serialize_signed(&key)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "signing")]
+    pub fn serialize_signed(&self, key: &SigningKey) -> Result<Bytes, SerializeError> {
+        Ok(key.sign(self.artifact.serialize()?).into())
+    }
+
+    /// Blocks until the functions of the module have been recompiled by
+    /// the optimized tier of the engine, if it has one.
+    ///
+    /// Instances keep running while functions are recompiled, and switch
+    /// to the optimized code as it gets ready: waiting is only useful to
+    /// benchmark, or to test, the optimized code.
+    ///
+    /// Returns the first error of the functions that failed to compile,
+    /// which keep running in the baseline tier. The error is only
+    /// returned by the first call.
+    pub fn wait_for_optimized_tier(&self) -> Result<(), CompileError> {
+        self.artifact.wait_for_optimized_tier()
+    }
+
     /// Deserializes a serialized Module binary into a `Module`.
     /// > Note: the module has to be serialized before with the `serialize` method.
     ///
@@ -299,10 +419,9 @@ impl Module {
     ) -> Result<Self, DeserializeError> {
         let bytes = bytes.into_bytes();
         let artifact = store.as_store_ref().engine().deserialize(&bytes)?;
-        Ok(Self::from_artifact(artifact))
+        Ok(Self::from_artifact(store.as_store_ref().engine(), artifact))
     }
 
-    #[cfg(feature = "compiler")]
     /// Deserializes a a serialized Module located in a `Path` into a `Module`.
     /// > Note: the module has to be serialized before with the `serialize` method.
     ///
@@ -328,54 +447,194 @@ impl Module {
             .as_store_ref()
             .engine()
             .deserialize_from_file(path.as_ref())?;
-        Ok(Self::from_artifact(artifact))
+        Ok(Self::from_artifact(store.as_store_ref().engine(), artifact))
+    }
+
+    /// Deserializes a Module binary signed by one of `trusted_keys` into
+    /// a `Module`.
+    /// > Note: the module has to be serialized before with the `serialize_signed` method.
+    ///
+    /// Unlike [`Module::deserialize`], this function is safe: the bytes are
+    /// only deserialized once their signature shows they are unchanged
+    /// since one of the trusted keys signed them, which makes the owners
+    /// of these keys responsible for what is loaded.
+    ///
+    /// # Usage
+    ///
+    /// ```ignore
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let mut store = Store::default();
+    /// let key = VerifyingKey::from_pem(&std::fs::read_to_string("key.pub.pem")?)?;
+    /// let module = Module::deserialize_checked(&store, serialized_data, &[key])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "signing")]
+    pub fn deserialize_checked(
+        store: &impl AsStoreRef,
+        bytes: impl IntoBytes,
+        trusted_keys: &[VerifyingKey],
+    ) -> Result<Self, SignatureError> {
+        let bytes = bytes.into_bytes();
+        let len = signature::verify(&bytes, trusted_keys)?;
+        Ok(unsafe { Self::deserialize(store, bytes.slice(..len))? })
     }
 
-    fn from_artifact(artifact: Arc<Artifact>) -> Self {
+    fn from_artifact(engine: &Engine, artifact: Arc<Artifact>) -> Self {
         Self {
             module_info: Arc::new(artifact.create_module_info()),
             artifact,
+            engine: engine.clone(),
         }
     }
 
-    #[cfg(feature = "compiler")]
     pub(crate) fn instantiate(
         &self,
         store: &mut impl AsStoreMut,
         imports: &[crate::Extern],
     ) -> Result<InstanceHandle, InstantiationError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("instantiate", module = self.name()).entered();
         // Ensure all imports come from the same context.
         for import in imports {
             if !import.is_from_store(store) {
                 return Err(InstantiationError::DifferentStores);
             }
         }
+        let engine = store.as_store_ref().engine().clone();
+        if !self.engine.is_same(&engine) {
+            return Err(InstantiationError::DifferentEngines);
+        }
+        if let Some(reason) = engine.quarantined(&self.module_info) {
+            return Err(InstantiationError::Quarantined(reason));
+        }
+        if let Some(limiter) = store.objects_mut().limiter_mut() {
+            let info = &self.module_info;
+            let allowed = limiter.instance_creating()
+                && info
+                    .memories
+                    .values()
+                    .skip(info.num_imported_memories)
+                    .all(|ty| limiter.memory_creating(ty))
+                && info
+                    .tables
+                    .values()
+                    .skip(info.num_imported_tables)
+                    .all(|ty| limiter.table_creating(ty));
+            if !allowed {
+                return Err(InstantiationError::ResourceLimitExceeded);
+            }
+        }
         let mut store_mut = store.as_store_mut();
         let (tunables, objects) = store_mut.tunables_and_objects_mut();
         unsafe {
-            let mut instance_handle = self.artifact.instantiate(
-                tunables,
-                &imports
-                    .iter()
-                    .map(crate::Extern::to_vm_extern)
-                    .collect::<Vec<_>>(),
-                objects,
-            )?;
-
-            // After the instance handle is created, we need to initialize
-            // the data, call the start function and so. However, if any
-            // of this steps traps, we still need to keep the instance alive
-            // as some of the Instance elements may have placed in other
-            // instance tables.
-            self.artifact.finish_instantiation(
-                store.as_store_ref().signal_handler(),
-                &mut instance_handle,
-            )?;
+            let mut instance_handle = self
+                .artifact
+                .instantiate_in_pool(
+                    engine.instance_pool(),
+                    tunables,
+                    &imports
+                        .iter()
+                        .map(crate::Extern::to_vm_extern)
+                        .collect::<Vec<_>>(),
+                    objects,
+                )
+                .map_err(|err| self.report_instantiation_error(&engine, err))?;
+            if let Some(diagnostics) = store.as_store_ref().diagnostics() {
+                diagnostics
+                    .record_instance(self.name(), instance_handle.local_memory_definitions());
+            }
 
             Ok(instance_handle)
         }
     }
 
+    /// Initializes the data of an instance created by
+    /// [`instantiate`](Self::instantiate) and calls its start function,
+    /// unless `invoke_start` is false.
+    ///
+    /// The instance must already be owned by the store: if any of these
+    /// steps traps, we still need to keep it alive as some of its elements
+    /// may have been placed in other instance tables.
+    pub(crate) fn finish_instantiation(
+        &self,
+        store: &mut impl AsStoreMut,
+        handle: &StoreHandle<InstanceHandle>,
+        invoke_start: bool,
+    ) -> Result<(), InstantiationError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("start", module = self.name()).entered();
+        let engine = store.as_store_ref().engine().clone();
+        let signal_handler = store.as_store_ref().signal_handler();
+        let result = unsafe {
+            self.artifact.finish_instantiation(
+                signal_handler,
+                engine.stack_size(),
+                handle.get_mut(store.objects_mut()),
+                invoke_start,
+            )
+        };
+        result.map_err(|err| {
+            if let (Some(diagnostics), wasmer_compiler::InstantiationError::Start(trap)) =
+                (store.as_store_ref().diagnostics(), &err)
+            {
+                diagnostics.record_trap(trap);
+            }
+            self.report_instantiation_error(&engine, err)
+        })
+    }
+
+    /// Calls the start function of an instance whose instantiation was
+    /// finished without it, if it wasn't called yet.
+    ///
+    /// A trap is reported like one of a start function called by
+    /// [`finish_instantiation`](Self::finish_instantiation).
+    pub(crate) fn start(
+        &self,
+        store: &mut impl AsStoreMut,
+        handle: &StoreHandle<InstanceHandle>,
+    ) -> Result<(), RuntimeError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("start", module = self.name()).entered();
+        let engine = store.as_store_ref().engine().clone();
+        let signal_handler = store.as_store_ref().signal_handler();
+        let result = unsafe {
+            handle
+                .get_mut(store.objects_mut())
+                .invoke_start_function(signal_handler, engine.stack_size())
+        };
+        result.map_err(|trap| {
+            let trap = RuntimeError::from_trap(trap);
+            if let Some(diagnostics) = store.as_store_ref().diagnostics() {
+                diagnostics.record_trap(&trap);
+            }
+            engine.report_trap(&trap);
+            trap
+        })
+    }
+
+    /// Reports a failed instantiation to the engine, so that modules
+    /// that keep failing get quarantined.
+    fn report_instantiation_error(
+        &self,
+        engine: &Engine,
+        error: wasmer_compiler::InstantiationError,
+    ) -> wasmer_compiler::InstantiationError {
+        match &error {
+            wasmer_compiler::InstantiationError::Link(crate::LinkError::Resource(cause)) => {
+                engine.report_resource_exhaustion(&self.module_info, cause)
+            }
+            wasmer_compiler::InstantiationError::Start(trap) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("runtime error in start function: {}", trap.message());
+                engine.report_trap(trap)
+            }
+            _ => {}
+        }
+        error
+    }
+
     /// Returns the name of the current module.
     ///
     /// This name is normally set in the WebAssembly bytecode by some
@@ -487,10 +746,64 @@ impl Module {
     /// Following the WebAssembly spec, one name can have multiple
     /// custom sections. That's why an iterator (rather than one element)
     /// is returned.
-    pub fn custom_sections<'a>(&'a self, name: &'a str) -> impl Iterator<Item = Box<[u8]>> + 'a {
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{Module, Store};
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// // A module with a `version` custom section holding "1.2.0".
+    /// let bytes = b"\0asm\x01\0\0\0\0\x0d\x07version1.2.0";
+    /// let module = Module::new(&store, &bytes[..])?;
+    ///
+    /// let version = module.custom_sections("version").next();
+    /// assert_eq!(version, Some(&b"1.2.0"[..]));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn custom_sections<'a>(&'a self, name: &str) -> impl Iterator<Item = &'a [u8]> + 'a {
         self.module_info.custom_sections(name)
     }
 
+    /// Returns the key/value metadata attached to the module with
+    /// [`attach_metadata`](crate::attach_metadata), such as its version
+    /// or build id.
+    ///
+    /// The metadata is kept when the module is serialized, and reported
+    /// with the frames of the module in traps and profiles.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::collections::BTreeMap;
+    /// # use wasmer::{attach_metadata, Module, Store};
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// let mut metadata = BTreeMap::new();
+    /// metadata.insert("name".to_string(), "plugin".to_string());
+    /// metadata.insert("version".to_string(), "1.2.0".to_string());
+    /// let bytes = attach_metadata(b"\0asm\x01\0\0\0", &metadata)?;
+    /// let module = Module::new(&store, bytes)?;
+    ///
+    /// assert_eq!(module.name(), Some("plugin"));
+    /// assert_eq!(module.metadata()["version"], "1.2.0");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.module_info.metadata
+    }
+
+    /// Returns how long the module took to compile, and the size of the
+    /// machine code generated for it.
+    ///
+    /// The metrics of lazily compiled modules grow as their functions
+    /// are compiled on their first call.
+    pub fn metrics(&self) -> ModuleMetrics {
+        self.artifact.metrics()
+    }
+
     /// The ABI of the ModuleInfo is very unstable, we refactor it very often.
     /// This function is public because in some cases it can be useful to get some
     /// extra information from the module.
@@ -9,6 +9,7 @@
 //! ```
 use std::marker::PhantomData;
 
+use crate::sys::call_hook::{CallHook, CallHookInfo};
 use crate::sys::{
     AsStoreMut, FromToNativeWasmType, Function, NativeWasmTypeInto, RuntimeError, WasmTypeList,
 };
@@ -87,15 +88,28 @@ macro_rules! impl_native_traits {
                     }
                     rets_list.as_mut()
                 };
-                unsafe {
+                store
+                    .as_store_mut()
+                    .call_hook(CallHook::CallingWasm, || CallHookInfo::of(&anyfunc))?;
+                let _call = store
+                    .as_store_ref()
+                    .diagnostics()
+                    .map(|diagnostics| diagnostics.enter_call(anyfunc.func_ptr as usize));
+                let result = unsafe {
                     wasmer_vm::wasmer_call_trampoline(
                         store.as_store_ref().signal_handler(),
                         anyfunc.vmctx,
                         anyfunc.call_trampoline,
                         anyfunc.func_ptr,
                         args_rets.as_mut_ptr() as *mut u8,
+                        store.as_store_ref().engine().stack_size(),
                     )
-                }?;
+                };
+                let returned = store
+                    .as_store_mut()
+                    .call_hook(CallHook::ReturningFromWasm, || CallHookInfo::of(&anyfunc));
+                result.map_err(|trap| store.as_store_ref().call_error(RuntimeError::from_trap(trap)))?;
+                returned?;
                 let num_rets = rets_list.len();
                 if !using_rets_array && num_rets > 0 {
                     let src_pointer = params_list.as_ptr();
@@ -0,0 +1,208 @@
+//! Host dynamic libraries satisfying function imports.
+//!
+//! A [`NativeLibrary`] loads a shared library of the host and wraps its
+//! symbols as [`Function`]s of a declared signature, which lets a native
+//! plugin be ported to Wasm one function at a time. Nothing about the
+//! symbol is checked against the signature, and the native code runs with
+//! all the privileges of the host, outside of the sandbox: this is opt-in,
+//! behind the `native-imports` feature, and the loading and wrapping are
+//! `unsafe`.
+//!
+//! The symbols are called with the C calling convention through libffi,
+//! as functions of exactly the declared signature, which can have any
+//! number of `i32`, `i64`, `f32` and `f64` parameters, and at most one
+//! result of those types.
+
+use crate::sys::{AsStoreMut, Function, FunctionType, RuntimeError, Type, Value};
+use libffi::middle as ffi;
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// An error loading a native library or wrapping one of its symbols.
+#[derive(Debug, Error)]
+pub enum NativeLibraryError {
+    /// The library couldn't be loaded.
+    #[error("failed to load the native library `{}`: {source}", path.display())]
+    Load {
+        /// The path of the library.
+        path: PathBuf,
+        /// The error of the dynamic loader.
+        source: libloading::Error,
+    },
+    /// The library doesn't define the symbol.
+    #[error("the native library `{}` has no symbol `{symbol}`", path.display())]
+    MissingSymbol {
+        /// The path of the library.
+        path: PathBuf,
+        /// The name of the symbol.
+        symbol: String,
+    },
+    /// The signature can't be called natively.
+    #[error("can't call the native symbol `{symbol}` as {ty}: {reason}")]
+    UnsupportedSignature {
+        /// The name of the symbol.
+        symbol: String,
+        /// The declared signature.
+        ty: FunctionType,
+        /// Why the signature isn't supported.
+        reason: &'static str,
+    },
+}
+
+/// A shared library of the host, whose symbols can be imported as
+/// functions.
+///
+/// The library stays loaded as long as this, or any of the functions
+/// wrapping its symbols, is alive.
+#[derive(Debug, Clone)]
+pub struct NativeLibrary {
+    path: PathBuf,
+    library: Arc<libloading::Library>,
+}
+
+impl NativeLibrary {
+    /// Loads the shared library at `path`.
+    ///
+    /// # Safety
+    ///
+    /// Loading a library runs its initialization routines, which can do
+    /// anything the host can.
+    pub unsafe fn open(path: impl AsRef<Path>) -> Result<Self, NativeLibraryError> {
+        let path = path.as_ref().to_path_buf();
+        let library =
+            libloading::Library::new(&path).map_err(|source| NativeLibraryError::Load {
+                path: path.clone(),
+                source,
+            })?;
+        Ok(Self {
+            path,
+            library: Arc::new(library),
+        })
+    }
+
+    /// The path the library was loaded from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Wraps the function `symbol` of the library as a [`Function`] of
+    /// type `ty`, calling it with the C calling convention.
+    ///
+    /// # Usage
+    /// ```no_run
+    /// # use wasmer::{FunctionType, Imports, NativeLibrary, Store, Type};
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let mut store = Store::default();
+    /// let libm = unsafe { NativeLibrary::open("libm.so.6")? };
+    /// let cos = unsafe {
+    ///     libm.function(&mut store, "cos", FunctionType::new(vec![Type::F64], vec![Type::F64]))?
+    /// };
+    /// let mut imports = Imports::new();
+    /// imports.define("env", "cos", cos);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// `symbol` must be a function taking and returning the C types
+    /// matching `ty` (`int32_t`, `int64_t`, `float` and `double`), which
+    /// is safe to call with any arguments the guest passes.
+    pub unsafe fn function(
+        &self,
+        store: &mut impl AsStoreMut,
+        symbol: &str,
+        ty: FunctionType,
+    ) -> Result<Function, NativeLibraryError> {
+        let unsupported = |reason| NativeLibraryError::UnsupportedSignature {
+            symbol: symbol.to_string(),
+            ty: ty.clone(),
+            reason,
+        };
+        if !ty.params().iter().copied().all(is_number) {
+            return Err(unsupported(
+                "only numbers can be passed to native functions",
+            ));
+        }
+        let result = match ty.results() {
+            [] => None,
+            [result] if is_number(*result) => Some(*result),
+            [_] => return Err(unsupported("native functions can only return numbers")),
+            _ => return Err(unsupported("native functions return at most one result")),
+        };
+
+        let address = self
+            .library
+            .get::<*const ()>(symbol.as_bytes())
+            .map_err(|_| NativeLibraryError::MissingSymbol {
+                path: self.path.clone(),
+                symbol: symbol.to_string(),
+            })?;
+        let address = *address as usize;
+        let params = ty.params().to_vec();
+        let library = self.library.clone();
+        Ok(Function::new(
+            store,
+            ty,
+            move |args| -> Result<Vec<Value>, RuntimeError> {
+                // Keeps the library loaded while the function is alive
+                let _ = &library;
+                Ok(call(address, &params, result, args).into_iter().collect())
+            },
+        ))
+    }
+}
+
+fn is_number(ty: Type) -> bool {
+    matches!(ty, Type::I32 | Type::I64 | Type::F32 | Type::F64)
+}
+
+/// The libffi type of a number.
+fn ffi_type(ty: Type) -> ffi::Type {
+    match ty {
+        Type::I32 => ffi::Type::i32(),
+        Type::I64 => ffi::Type::i64(),
+        Type::F32 => ffi::Type::f32(),
+        Type::F64 => ffi::Type::f64(),
+        // Checked by `NativeLibrary::function`
+        _ => unreachable!(),
+    }
+}
+
+/// Calls the native function at `address`, taking `params` and returning
+/// `result`, with `args`.
+fn call(address: usize, params: &[Type], result: Option<Type>, args: &[Value]) -> Option<Value> {
+    let cif = ffi::Cif::new(
+        params.iter().copied().map(ffi_type),
+        result.map_or_else(ffi::Type::void, ffi_type),
+    );
+    let args = args
+        .iter()
+        .map(|arg| match arg {
+            Value::I32(v) => ffi::Arg::new(v),
+            Value::I64(v) => ffi::Arg::new(v),
+            Value::F32(v) => ffi::Arg::new(v),
+            Value::F64(v) => ffi::Arg::new(v),
+            // Checked against the type of the function when called
+            _ => unreachable!(),
+        })
+        .collect::<Vec<_>>();
+    let code = ffi::CodePtr::from_ptr(address as *const c_void);
+    unsafe {
+        match result {
+            // libffi widens the integer results narrower than a register
+            Some(Type::I32) => Some(Value::I32(
+                cif.call::<libffi::raw::ffi_arg>(code, &args) as i32
+            )),
+            Some(Type::I64) => Some(Value::I64(cif.call(code, &args))),
+            Some(Type::F32) => Some(Value::F32(cif.call(code, &args))),
+            Some(Type::F64) => Some(Value::F64(cif.call(code, &args))),
+            _ => {
+                cif.call::<()>(code, &args);
+                None
+            }
+        }
+    }
+}
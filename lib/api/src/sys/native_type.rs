@@ -180,7 +180,6 @@ impl NativeWasmType for Function {
     type Abi = usize;
 }
 
-#[cfg(feature = "compiler")]
 impl NativeWasmTypeInto for Option<Function> {
     #[inline]
     unsafe fn from_abi(store: &mut impl AsStoreMut, abi: Self::Abi) -> Self {
@@ -0,0 +1,123 @@
+//! Resolvers looking the imports of a module up while it is instantiated.
+//!
+//! [`Imports`] needs every import to be defined before instantiating a
+//! module. A [`Resolver`] is asked for each import of the module by
+//! [`Instance::new_with_resolver`] instead, with the type the module
+//! expects, so it can create the import on the fly, answer for any
+//! field of a namespace, or defer to other resolvers with
+//! [`Resolver::chain`].
+//!
+//! [`Instance::new_with_resolver`]: crate::Instance::new_with_resolver
+
+use crate::sys::{Extern, Imports, StoreMut};
+use wasmer_types::ExternType;
+
+/// Resolves the imports of a module.
+///
+/// It is implemented by [`Imports`], and by the closures taking the same
+/// arguments as [`Resolver::resolve`].
+///
+/// # Usage
+/// ```
+/// # use wasmer::{imports, Extern, ExternType, Function, Instance, Module, Resolver, Store, StoreMut, Value};
+/// # fn main() -> anyhow::Result<()> {
+/// let mut store = Store::default();
+/// let module = Module::new(
+///     &store,
+///     r#"(module
+///          (import "env" "answer" (func (result i32)))
+///          (import "log" "anything" (func (param i32))))"#,
+/// )?;
+///
+/// let answer = Function::new_typed(&mut store, || 42);
+/// let imports = imports! { "env" => { "answer" => answer } };
+/// // Every function of the `log` namespace does nothing.
+/// let log = |store: &mut StoreMut, module: &str, _field: &str, ty: &ExternType| {
+///     match (module, ty) {
+///         ("log", ExternType::Function(ty)) => Some(Extern::Function(Function::new(
+///             store,
+///             ty,
+///             |_args: &[Value]| Ok(vec![]),
+///         ))),
+///         _ => None,
+///     }
+/// };
+///
+/// let instance = Instance::new_with_resolver(&mut store, &module, &imports.chain(log))?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait Resolver {
+    /// Resolves the import `field` of the namespace `module`, of type
+    /// `ty`, or returns `None` if it isn't provided.
+    ///
+    /// An extern of another type than `ty` fails the instantiation.
+    fn resolve(
+        &self,
+        store: &mut StoreMut,
+        module: &str,
+        field: &str,
+        ty: &ExternType,
+    ) -> Option<Extern>;
+
+    /// Resolves the imports with this resolver, or else with `fallback`.
+    fn chain<R: Resolver>(self, fallback: R) -> ResolverChain<Self, R>
+    where
+        Self: Sized,
+    {
+        ResolverChain {
+            first: self,
+            fallback,
+        }
+    }
+}
+
+impl Resolver for Imports {
+    fn resolve(
+        &self,
+        store: &mut StoreMut,
+        module: &str,
+        field: &str,
+        ty: &ExternType,
+    ) -> Option<Extern> {
+        self.get_export(module, field)
+            .or_else(|| self.stub(store, module, field, ty))
+    }
+}
+
+impl<F> Resolver for F
+where
+    F: Fn(&mut StoreMut, &str, &str, &ExternType) -> Option<Extern>,
+{
+    fn resolve(
+        &self,
+        store: &mut StoreMut,
+        module: &str,
+        field: &str,
+        ty: &ExternType,
+    ) -> Option<Extern> {
+        self(store, module, field, ty)
+    }
+}
+
+/// Two resolvers, the second one resolving the imports the first one
+/// doesn't, see [`Resolver::chain`].
+#[derive(Debug, Clone)]
+pub struct ResolverChain<A, B> {
+    first: A,
+    fallback: B,
+}
+
+impl<A: Resolver, B: Resolver> Resolver for ResolverChain<A, B> {
+    fn resolve(
+        &self,
+        store: &mut StoreMut,
+        module: &str,
+        field: &str,
+        ty: &ExternType,
+    ) -> Option<Extern> {
+        self.first
+            .resolve(store, module, field, ty)
+            .or_else(|| self.fallback.resolve(store, module, field, ty))
+    }
+}
@@ -0,0 +1,158 @@
+//! Signing serialized modules, and checking the signatures before loading
+//! them.
+//!
+//! Deserializing a module maps the machine code of the serialized bytes
+//! into executable memory, so precompiled artifacts can only be loaded
+//! from a source that is trusted not to tamper with them.
+//! [`Module::serialize_signed`] appends an Ed25519 signature of the
+//! serialized module to its bytes, and [`Module::deserialize_checked`]
+//! only loads bytes signed by one of the trusted keys. The signed bytes
+//! are still a serialized module for [`Module::deserialize`].
+//!
+//! [`Module::serialize_signed`]: crate::Module::serialize_signed
+//! [`Module::deserialize_checked`]: crate::Module::deserialize_checked
+//! [`Module::deserialize`]: crate::Module::deserialize
+
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use thiserror::Error;
+use wasmer_types::DeserializeError;
+
+/// The length of an Ed25519 signature.
+const SIGNATURE_LEN: usize = 64;
+
+/// Ends the signed bytes, after the signature.
+const SIGNATURE_MAGIC: [u8; 8] = *b"wasmsig1";
+
+/// The DER encoding of the `SubjectPublicKeyInfo` of an Ed25519 public
+/// key, up to the key itself.
+const SPKI_PREFIX: [u8; 12] = [
+    0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+];
+
+/// An error reading a key, or checking the signature of a module.
+#[derive(Error, Debug)]
+pub enum SignatureError {
+    /// The key isn't an Ed25519 key in one of the supported encodings.
+    #[error("invalid key: {0}")]
+    InvalidKey(String),
+
+    /// The bytes have no signature.
+    #[error("the module isn't signed")]
+    Unsigned,
+
+    /// The signature isn't the one of any of the trusted keys for the
+    /// bytes, which were signed by another key or modified afterwards.
+    #[error("the module isn't signed by a trusted key")]
+    Untrusted,
+
+    /// The signature is trusted, but the module couldn't be deserialized.
+    #[error(transparent)]
+    Deserialize(#[from] DeserializeError),
+}
+
+/// An Ed25519 private key, signing serialized modules.
+#[derive(Debug)]
+pub struct SigningKey(Ed25519KeyPair);
+
+impl SigningKey {
+    /// Reads a key from its PKCS#8 DER encoding.
+    pub fn from_pkcs8(der: &[u8]) -> Result<Self, SignatureError> {
+        Ed25519KeyPair::from_pkcs8_maybe_unchecked(der)
+            .map(Self)
+            .map_err(|e| SignatureError::InvalidKey(e.to_string()))
+    }
+
+    /// Reads a key from a `PRIVATE KEY` PEM block, as written by
+    /// `openssl genpkey -algorithm ed25519`.
+    pub fn from_pem(pem: &str) -> Result<Self, SignatureError> {
+        Self::from_pkcs8(&pem_contents(pem, "PRIVATE KEY")?)
+    }
+
+    /// The public key checking the signatures of this key.
+    pub fn verifying_key(&self) -> VerifyingKey {
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(self.0.public_key().as_ref());
+        VerifyingKey(bytes)
+    }
+
+    /// Appends the signature of `bytes` to them.
+    pub(crate) fn sign(&self, mut bytes: Vec<u8>) -> Vec<u8> {
+        let signature = self.0.sign(&bytes);
+        bytes.extend_from_slice(signature.as_ref());
+        bytes.extend_from_slice(&SIGNATURE_MAGIC);
+        bytes
+    }
+}
+
+/// An Ed25519 public key, checking the signatures of serialized modules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VerifyingKey([u8; 32]);
+
+impl VerifyingKey {
+    /// A key from its 32 bytes.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Reads a key from the DER encoding of its `SubjectPublicKeyInfo`.
+    pub fn from_spki(der: &[u8]) -> Result<Self, SignatureError> {
+        if der.len() != SPKI_PREFIX.len() + 32 || der[..SPKI_PREFIX.len()] != SPKI_PREFIX {
+            return Err(SignatureError::InvalidKey(
+                "not an Ed25519 public key".to_string(),
+            ));
+        }
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(&der[SPKI_PREFIX.len()..]);
+        Ok(Self(bytes))
+    }
+
+    /// Reads a key from a `PUBLIC KEY` PEM block, as written by
+    /// `openssl pkey -pubout`.
+    pub fn from_pem(pem: &str) -> Result<Self, SignatureError> {
+        Self::from_spki(&pem_contents(pem, "PUBLIC KEY")?)
+    }
+
+    /// The 32 bytes of the key.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Checks that `signature` is the detached Ed25519 signature of
+    /// `bytes` by this key, as `openssl pkeyutl -sign -rawin` writes it.
+    pub fn verify(&self, bytes: &[u8], signature: &[u8]) -> Result<(), SignatureError> {
+        UnparsedPublicKey::new(&ED25519, &self.0)
+            .verify(bytes, signature)
+            .map_err(|_| SignatureError::Untrusted)
+    }
+}
+
+/// Checks that `bytes` were signed by one of `trusted_keys`, and returns
+/// the length of the signed bytes.
+pub(crate) fn verify(bytes: &[u8], trusted_keys: &[VerifyingKey]) -> Result<usize, SignatureError> {
+    let len = bytes
+        .len()
+        .checked_sub(SIGNATURE_LEN + SIGNATURE_MAGIC.len())
+        .filter(|&len| bytes[len + SIGNATURE_LEN..] == SIGNATURE_MAGIC)
+        .ok_or(SignatureError::Unsigned)?;
+    let (signed, signature) = (&bytes[..len], &bytes[len..len + SIGNATURE_LEN]);
+    if trusted_keys.iter().any(|key| {
+        UnparsedPublicKey::new(&ED25519, &key.0)
+            .verify(signed, signature)
+            .is_ok()
+    }) {
+        Ok(len)
+    } else {
+        Err(SignatureError::Untrusted)
+    }
+}
+
+/// Decodes the first `label` block of `pem`.
+fn pem_contents(pem: &str, label: &str) -> Result<Vec<u8>, SignatureError> {
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+    let missing = || SignatureError::InvalidKey(format!("no `{}` PEM block", label));
+    let start = pem.find(&begin).ok_or_else(missing)? + begin.len();
+    let len = pem[start..].find(&end).ok_or_else(missing)?;
+    let encoded: String = pem[start..start + len].split_whitespace().collect();
+    base64::decode(encoded).map_err(|e| SignatureError::InvalidKey(e.to_string()))
+}
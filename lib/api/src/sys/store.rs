@@ -1,9 +1,12 @@
+use crate::sys::call_hook::{CallHook, CallHookFn, CallHookInfo};
+use crate::sys::diagnostics::StoreDiagnostics;
 use crate::sys::tunables::BaseTunables;
+use crate::sys::RuntimeError;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::sync::{Arc, RwLock};
-#[cfg(feature = "compiler")]
 use wasmer_compiler::{Engine, EngineBuilder, Tunables};
-use wasmer_vm::{init_traps, TrapHandler, TrapHandlerFn};
+use wasmer_vm::{init_traps, ResourceLimiter, TrapHandler, TrapHandlerFn};
 
 use wasmer_vm::StoreObjects;
 
@@ -12,11 +15,28 @@ use wasmer_vm::StoreObjects;
 /// wrap the actual context in a box.
 pub(crate) struct StoreInner {
     pub(crate) objects: StoreObjects,
-    #[cfg(feature = "compiler")]
     pub(crate) engine: Engine,
-    #[cfg(feature = "compiler")]
     pub(crate) tunables: Box<dyn Tunables + Send + Sync>,
     pub(crate) trap_handler: Option<Box<TrapHandlerFn<'static>>>,
+    pub(crate) tags: BTreeMap<String, String>,
+    pub(crate) diagnostics: Option<Arc<StoreDiagnostics>>,
+    pub(crate) call_hook: Option<Box<CallHookFn>>,
+}
+
+impl StoreInner {
+    fn tags_changed(&self) {
+        if let Some(diagnostics) = &self.diagnostics {
+            diagnostics.set_tags(&self.tags);
+        }
+    }
+}
+
+impl Drop for StoreInner {
+    fn drop(&mut self) {
+        if let Some(diagnostics) = &self.diagnostics {
+            diagnostics.unregister();
+        }
+    }
 }
 
 /// The store represents all global state that can be manipulated by
@@ -31,13 +51,11 @@ pub(crate) struct StoreInner {
 /// Spec: <https://webassembly.github.io/spec/core/exec/runtime.html#store>
 pub struct Store {
     pub(crate) inner: Box<StoreInner>,
-    #[cfg(feature = "compiler")]
     engine: Engine,
     trap_handler: Arc<RwLock<Option<Box<TrapHandlerFn<'static>>>>>,
 }
 
 impl Store {
-    #[cfg(feature = "compiler")]
     /// Creates a new `Store` with a specific [`Engine`].
     pub fn new(engine: impl Into<Engine>) -> Self {
         let engine = engine.into();
@@ -45,7 +63,6 @@ impl Store {
         Self::new_with_tunables(engine, BaseTunables::for_target(&target))
     }
 
-    #[cfg(feature = "compiler")]
     #[deprecated(
         since = "3.0.0",
         note = "Store::new_with_engine has been deprecated in favor of Store::new"
@@ -60,7 +77,52 @@ impl Store {
         self.inner.trap_handler = handler;
     }
 
-    #[cfg(feature = "compiler")]
+    /// Sets the limiter enforcing quotas on the objects of this store,
+    /// replacing the previous one if any.
+    ///
+    /// It is consulted before instances, memories and tables are created
+    /// in this store, and before its memories and tables grow, be it
+    /// requested by the host or by `memory.grow` and `table.grow`.
+    pub fn set_limiter(&mut self, limiter: Option<Box<dyn ResourceLimiter>>) {
+        self.inner.objects.set_limiter(limiter);
+    }
+
+    /// Sets the hook called whenever execution moves between the host
+    /// and the WebAssembly code of this store, replacing the previous one
+    /// if any.
+    ///
+    /// The hook sees host calls into the store's functions and the calls
+    /// of its instances to host functions, along with the index of the
+    /// WebAssembly function and the id of its instance. It can be used to
+    /// measure the time spent on each side, or to refuse reentrant calls.
+    pub fn set_call_hook(&mut self, hook: Option<Box<CallHookFn>>) {
+        self.inner.call_hook = hook;
+    }
+
+    /// Attaches a key/value tag to this store, replacing any previous
+    /// value for `key`.
+    ///
+    /// Tags identify the tenant, request or component an instance runs
+    /// on behalf of. They are attached to every [`RuntimeError`] raised
+    /// while calling into the store's instances, and are reported with
+    /// the `tracing` events emitted on traps.
+    pub fn set_tag(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.inner.tags.insert(key.into(), value.into());
+        self.inner.tags_changed();
+    }
+
+    /// Removes a tag from this store, returning its previous value.
+    pub fn remove_tag(&mut self, key: &str) -> Option<String> {
+        let value = self.inner.tags.remove(key);
+        self.inner.tags_changed();
+        value
+    }
+
+    /// Returns the tags attached to this store.
+    pub fn tags(&self) -> &BTreeMap<String, String> {
+        &self.inner.tags
+    }
+
     /// Creates a new `Store` with a specific [`Engine`] and [`Tunables`].
     pub fn new_with_tunables(
         engine: impl Into<Engine>,
@@ -70,7 +132,9 @@ impl Store {
 
         // Make sure the signal handlers are installed.
         // This is required for handling traps.
-        init_traps();
+        if !engine.scopes_trap_handlers() {
+            init_traps();
+        }
 
         Self {
             inner: Box::new(StoreInner {
@@ -78,25 +142,25 @@ impl Store {
                 engine: engine.cloned(),
                 tunables: Box::new(tunables),
                 trap_handler: None,
+                tags: BTreeMap::new(),
+                diagnostics: StoreDiagnostics::register(),
+                call_hook: None,
             }),
             engine: engine.cloned(),
             trap_handler: Arc::new(RwLock::new(None)),
         }
     }
 
-    #[cfg(feature = "compiler")]
     /// Returns the [`Tunables`].
     pub fn tunables(&self) -> &dyn Tunables {
         self.inner.tunables.as_ref()
     }
 
-    #[cfg(feature = "compiler")]
     /// Returns the [`Engine`].
     pub fn engine(&self) -> &Engine {
         &self.engine
     }
 
-    #[cfg(feature = "compiler")]
     /// Checks whether two stores are identical. A store is considered
     /// equal to another store if both have the same engine. The
     /// tunables are excluded from the logic.
@@ -105,7 +169,6 @@ impl Store {
     }
 }
 
-#[cfg(feature = "compiler")]
 impl PartialEq for Store {
     fn eq(&self, other: &Self) -> bool {
         Self::same(self, other)
@@ -133,8 +196,8 @@ unsafe impl TrapHandler for Store {
 unsafe impl Send for Store {}
 unsafe impl Sync for Store {}
 
-// We only implement default if we have assigned a default compiler and engine
-#[cfg(feature = "compiler")]
+// The default store compiles with the first compiler enabled, or is headless
+// if there is none, only running modules compiled ahead of time.
 impl Default for Store {
     fn default() -> Self {
         // We store them on a function that returns to make
@@ -159,21 +222,13 @@ impl Default for Store {
         #[allow(unreachable_code, unused_mut)]
         fn get_engine() -> Engine {
             cfg_if::cfg_if! {
-                if #[cfg(feature = "compiler")] {
-
-            cfg_if::cfg_if! {
-                    if #[cfg(any(feature = "cranelift", feature = "llvm", feature = "singlepass"))]
-                    {
+                if #[cfg(any(feature = "cranelift", feature = "llvm", feature = "singlepass"))] {
                     let config = get_config();
                     EngineBuilder::new(Box::new(config) as Box<dyn wasmer_compiler::CompilerConfig>)
                         .engine()
-                    } else {
+                } else {
                     EngineBuilder::headless()
                         .engine()
-                    }
-            }
-                } else {
-                    compile_error!("No default engine chosen")
                 }
             }
         }
@@ -216,19 +271,16 @@ impl<'a> StoreRef<'a> {
         &self.inner.objects
     }
 
-    #[cfg(feature = "compiler")]
     /// Returns the [`Tunables`].
     pub fn tunables(&self) -> &dyn Tunables {
         self.inner.tunables.as_ref()
     }
 
-    #[cfg(feature = "compiler")]
     /// Returns the [`Engine`].
     pub fn engine(&self) -> &Engine {
         &self.inner.engine
     }
 
-    #[cfg(feature = "compiler")]
     /// Checks whether two stores are identical. A store is considered
     /// equal to another store if both have the same engine. The
     /// tunables are excluded from the logic.
@@ -236,6 +288,32 @@ impl<'a> StoreRef<'a> {
         a.inner.engine.id() == b.inner.engine.id()
     }
 
+    /// Returns the tags attached to the store.
+    pub fn tags(&self) -> &'a BTreeMap<String, String> {
+        &self.inner.tags
+    }
+
+    /// Returns the diagnostic state of the store, if diagnostics are
+    /// enabled.
+    pub(crate) fn diagnostics(&self) -> Option<&'a Arc<StoreDiagnostics>> {
+        self.inner.diagnostics.as_ref()
+    }
+
+    /// Handles an error raised while calling into the store: reports it
+    /// to the engine's quarantine accounting and attaches the store's tags.
+    pub(crate) fn call_error(&self, error: RuntimeError) -> RuntimeError {
+        self.inner.engine.report_trap(&error);
+        if let Some(diagnostics) = &self.inner.diagnostics {
+            diagnostics.record_trap(&error);
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(tags = ?self.inner.tags, "runtime error: {}", error.message());
+        if self.inner.tags.is_empty() {
+            return error;
+        }
+        error.with_tags(self.inner.tags.iter().map(|(k, v)| (k.clone(), v.clone())))
+    }
+
     /// The signal handler
     #[inline]
     pub fn signal_handler(&self) -> Option<*const TrapHandlerFn<'static>> {
@@ -252,19 +330,29 @@ pub struct StoreMut<'a> {
 }
 
 impl<'a> StoreMut<'a> {
+    /// Calls the hook of the store, if any, on `hook`. The information
+    /// on the function is only computed when there is a hook.
+    pub(crate) fn call_hook(
+        &mut self,
+        hook: CallHook,
+        info: impl FnOnce() -> CallHookInfo,
+    ) -> Result<(), RuntimeError> {
+        match self.inner.call_hook.as_mut() {
+            Some(call_hook) => call_hook(hook, &info()),
+            None => Ok(()),
+        }
+    }
+
     /// Returns the [`Tunables`].
-    #[cfg(feature = "compiler")]
     pub fn tunables(&self) -> &dyn Tunables {
         self.inner.tunables.as_ref()
     }
 
     /// Returns the [`Engine`].
-    #[cfg(feature = "compiler")]
     pub fn engine(&self) -> &Engine {
         &self.inner.engine
     }
 
-    #[cfg(feature = "compiler")]
     /// Checks whether two stores are identical. A store is considered
     /// equal to another store if both have the same engine. The
     /// tunables are excluded from the logic.
@@ -272,7 +360,17 @@ impl<'a> StoreMut<'a> {
         a.inner.engine.id() == b.inner.engine.id()
     }
 
-    #[cfg(feature = "compiler")]
+    /// Returns the tags attached to the store.
+    pub fn tags(&self) -> &BTreeMap<String, String> {
+        &self.inner.tags
+    }
+
+    /// Attaches a key/value tag to the store. See [`Store::set_tag`].
+    pub fn set_tag(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.inner.tags.insert(key.into(), value.into());
+        self.inner.tags_changed();
+    }
+
     pub(crate) fn tunables_and_objects_mut(&mut self) -> (&dyn Tunables, &mut StoreObjects) {
         (self.inner.tunables.as_ref(), &mut self.inner.objects)
     }
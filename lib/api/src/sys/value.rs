@@ -77,6 +77,20 @@ impl Value {
         Self::ExternRef(None)
     }
 
+    /// Returns the default value of `ty`, which is zero for numbers and
+    /// null for references, like the locals of a function.
+    pub fn default_for(ty: Type) -> Self {
+        match ty {
+            Type::I32 => Self::I32(0),
+            Type::I64 => Self::I64(0),
+            Type::F32 => Self::F32(0.0),
+            Type::F64 => Self::F64(0.0),
+            Type::V128 => Self::V128(0),
+            Type::ExternRef => Self::ExternRef(None),
+            Type::FuncRef => Self::FuncRef(None),
+        }
+    }
+
     /// Returns the corresponding [`Type`] for this `Value`.
     pub fn ty(&self) -> Type {
         match self {
@@ -106,7 +120,6 @@ impl Value {
         }
     }
 
-    #[cfg(feature = "compiler")]
     /// Converts a `RawValue` to a `Value`.
     ///
     /// # Safety
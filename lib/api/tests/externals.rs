@@ -123,7 +123,57 @@ fn table_get() -> Result<(), String> {
 
 #[universal_test]
 fn table_set() -> Result<(), String> {
-    // Table set not yet tested
+    // Tables are not yet fully supported in Wasm
+    #[cfg(feature = "sys")]
+    {
+        let mut store = Store::default();
+        let table_type = TableType {
+            ty: Type::FuncRef,
+            minimum: 2,
+            maximum: None,
+        };
+        let table = Table::new(&mut store, table_type, Value::FuncRef(None))
+            .map_err(|e| format!("{e:?}"))?;
+        let f = Function::new_typed(&mut store, |num: i32| num + 1);
+        table
+            .set(&mut store, 1, Value::FuncRef(Some(f.clone())))
+            .map_err(|e| format!("{e:?}"))?;
+        match table.get(&mut store, 1) {
+            Some(Value::FuncRef(Some(elem))) => assert_eq!(elem, f),
+            elem => panic!("unexpected element {:?}", elem),
+        }
+        assert!(matches!(
+            table.get(&mut store, 0),
+            Some(Value::FuncRef(None))
+        ));
+        assert!(table.get(&mut store, 2).is_none());
+
+        // Out of bounds, and of the wrong type.
+        assert!(table.set(&mut store, 2, Value::FuncRef(Some(f))).is_err());
+        assert!(table.set(&mut store, 0, Value::ExternRef(None)).is_err());
+        assert!(table.grow(&mut store, 1, Value::I32(0)).is_err());
+        assert_eq!(table.size(&store), 2);
+
+        // Dynamic functions can't be used as funcrefs.
+        let dynamic = Function::new(
+            &mut store,
+            FunctionType::new(vec![Type::I32], vec![Type::I32]),
+            |args| Ok(args.to_vec()),
+        );
+        let err = table
+            .set(&mut store, 0, Value::FuncRef(Some(dynamic.clone())))
+            .unwrap_err();
+        assert_eq!(err.message(), "cannot store a dynamic function in a table");
+        assert!(table
+            .grow(&mut store, 1, Value::FuncRef(Some(dynamic)))
+            .is_err());
+        assert!(matches!(
+            table.get(&mut store, 0),
+            Some(Value::FuncRef(None))
+        ));
+        assert_eq!(table.size(&store), 2);
+    }
+
     Ok(())
 }
 
@@ -206,6 +256,172 @@ fn memory_grow() -> Result<(), String> {
     Ok(())
 }
 
+#[universal_test]
+fn memory_typed_pointers() -> Result<(), String> {
+    let mut store = Store::default();
+    let memory = Memory::new(&mut store, MemoryType::new(Pages(1), None, false))
+        .map_err(|e| format!("{e:?}"))?;
+    let view = memory.view(&store);
+
+    let ptr: WasmPtr<u8> = WasmPtr::new(16);
+    ptr.write_utf8_string_with_nul(&view, "hello")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        ptr.read_utf8_string_with_nul(&view)
+            .map_err(|e| format!("{e:?}"))?,
+        "hello"
+    );
+    assert_eq!(
+        ptr.read_utf8_string(&view, 4)
+            .map_err(|e| format!("{e:?}"))?,
+        "hell"
+    );
+    ptr.write(&view, 0xff).map_err(|e| format!("{e:?}"))?;
+    assert!(matches!(
+        ptr.read_utf8_string(&view, 5),
+        Err(MemoryAccessError::NonUtf8String)
+    ));
+
+    // The last bytes of the memory can't hold the string.
+    let end: WasmPtr<u8> = WasmPtr::new(WASM_PAGE_SIZE as u32 - 2);
+    assert!(matches!(
+        end.write_utf8_string(&view, "hello"),
+        Err(MemoryAccessError::HeapOutOfBounds)
+    ));
+
+    let aligned: WasmPtr<u32> = WasmPtr::new(8);
+    assert!(aligned.is_aligned());
+    aligned
+        .aligned()
+        .and_then(|ptr| ptr.write(&view, 42))
+        .map_err(|e| format!("{e:?}"))?;
+    assert!(matches!(
+        WasmPtr::<u32>::new(10).aligned(),
+        Err(MemoryAccessError::Unaligned)
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn memory_grow_observer() -> Result<(), String> {
+    use std::sync::{Arc, Mutex};
+
+    /// Lets the memories grow up to a number of pages, and logs the growths.
+    #[derive(Debug)]
+    struct Budget {
+        pages: u32,
+        grown: Mutex<Vec<(Pages, Pages)>>,
+    }
+
+    impl MemoryGrowObserver for Budget {
+        fn will_grow(&self, _current: Pages, requested: Pages) -> bool {
+            requested.0 <= self.pages
+        }
+
+        fn did_grow(&self, previous: Pages, current: Pages, _base: *mut u8) {
+            self.grown.lock().unwrap().push((previous, current));
+        }
+    }
+
+    let mut store = Store::default();
+    let budget = Arc::new(Budget {
+        pages: 3,
+        grown: Mutex::new(vec![]),
+    });
+    let memory = Memory::new(&mut store, MemoryType::new(Pages(1), None, false))
+        .map_err(|e| format!("{e:?}"))?;
+    memory
+        .set_grow_observer(&mut store, budget.clone())
+        .map_err(|e| format!("{e:?}"))?;
+
+    assert_eq!(memory.grow(&mut store, 1), Ok(Pages(1)));
+    assert!(matches!(
+        memory.grow(&mut store, 2),
+        Err(MemoryError::CouldNotGrow { .. })
+    ));
+    assert_eq!(memory.view(&store).size(), Pages(2));
+
+    // The guest sees a vetoed growth as a failed one.
+    let module = Module::new(
+        &store,
+        r#"(module
+             (import "env" "memory" (memory 1))
+             (func (export "grow") (param i32) (result i32)
+               (memory.grow (local.get 0))))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let imports = imports! { "env" => { "memory" => memory.clone() } };
+    let instance = Instance::new(&mut store, &module, &imports).map_err(|e| format!("{e:?}"))?;
+    let grow: TypedFunction<i32, i32> = instance
+        .exports
+        .get_typed_function(&mut store, "grow")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(grow.call(&mut store, 2).map_err(|e| format!("{e:?}"))?, -1);
+    assert_eq!(grow.call(&mut store, 1).map_err(|e| format!("{e:?}"))?, 2);
+
+    assert_eq!(
+        *budget.grown.lock().unwrap(),
+        vec![(Pages(1), Pages(2)), (Pages(2), Pages(3))]
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn memory_atomics() -> Result<(), String> {
+    use std::time::Duration;
+
+    let mut store = Store::default();
+    let memory = Memory::new(&mut store, MemoryType::new(Pages(1), Some(Pages(1)), true))
+        .map_err(|e| format!("{e:?}"))?;
+    let view = memory.view(&store);
+
+    view.atomic_store_u32(8, 7).map_err(|e| format!("{e:?}"))?;
+    assert_eq!(view.atomic_load_u32(8).map_err(|e| format!("{e:?}"))?, 7);
+    assert_eq!(
+        view.atomic_compare_exchange_u32(8, 7, 9)
+            .map_err(|e| format!("{e:?}"))?,
+        7
+    );
+    assert_eq!(
+        view.atomic_compare_exchange_u32(8, 7, 11)
+            .map_err(|e| format!("{e:?}"))?,
+        9
+    );
+    view.atomic_store_u64(16, u64::MAX)
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        view.atomic_load_u64(16).map_err(|e| format!("{e:?}"))?,
+        u64::MAX
+    );
+
+    assert!(matches!(
+        view.atomic_load_u32(10),
+        Err(MemoryAccessError::Unaligned)
+    ));
+    assert!(matches!(
+        view.atomic_load_u64(WASM_PAGE_SIZE as u64),
+        Err(MemoryAccessError::HeapOutOfBounds)
+    ));
+
+    assert_eq!(
+        view.atomic_wait32(8, 0, None)
+            .map_err(|e| format!("{e:?}"))?,
+        WaitResult::NotEqual
+    );
+    assert_eq!(
+        view.atomic_wait32(8, 9, Some(Duration::from_millis(10)))
+            .map_err(|e| format!("{e:?}"))?,
+        WaitResult::TimedOut
+    );
+    assert_eq!(view.atomic_notify(8, 1).map_err(|e| format!("{e:?}"))?, 0);
+
+    Ok(())
+}
+
 #[universal_test]
 fn function_new() -> Result<(), String> {
     let mut store = Store::default();
@@ -406,3 +622,96 @@ fn function_new_dynamic_env() -> Result<(), String> {
 
     Ok(())
 }
+
+#[cfg(all(feature = "native-imports", target_os = "linux"))]
+#[test]
+fn native_library_functions() -> Result<(), String> {
+    let mut store = Store::default();
+    let libm = unsafe { NativeLibrary::open("libm.so.6").map_err(|e| e.to_string())? };
+    let libc = unsafe { NativeLibrary::open("libc.so.6").map_err(|e| e.to_string())? };
+
+    let abs = unsafe {
+        libc.function(
+            &mut store,
+            "abs",
+            FunctionType::new(vec![Type::I32], vec![Type::I32]),
+        )
+    }
+    .map_err(|e| e.to_string())?;
+    assert_eq!(
+        abs.call(&mut store, &[Value::I32(-7)]).unwrap().to_vec(),
+        vec![Value::I32(7)]
+    );
+
+    // The kinds of the parameters are interleaved
+    let ldexp = unsafe {
+        libm.function(
+            &mut store,
+            "ldexp",
+            FunctionType::new(vec![Type::F64, Type::I32], vec![Type::F64]),
+        )
+    }
+    .map_err(|e| e.to_string())?;
+    assert_eq!(
+        ldexp
+            .call(&mut store, &[Value::F64(1.5), Value::I32(3)])
+            .unwrap()
+            .to_vec(),
+        vec![Value::F64(12.0)]
+    );
+    let ldexpf = unsafe {
+        libm.function(
+            &mut store,
+            "ldexpf",
+            FunctionType::new(vec![Type::F32, Type::I32], vec![Type::F32]),
+        )
+    }
+    .map_err(|e| e.to_string())?;
+    assert_eq!(
+        ldexpf
+            .call(&mut store, &[Value::F32(-0.75), Value::I32(2)])
+            .unwrap()
+            .to_vec(),
+        vec![Value::F32(-3.0)]
+    );
+    // Several parameters of the same kind
+    let fma = unsafe {
+        libm.function(
+            &mut store,
+            "fma",
+            FunctionType::new(vec![Type::F64; 3], vec![Type::F64]),
+        )
+    }
+    .map_err(|e| e.to_string())?;
+    assert_eq!(
+        fma.call(
+            &mut store,
+            &[Value::F64(2.0), Value::F64(3.0), Value::F64(0.5)]
+        )
+        .unwrap()
+        .to_vec(),
+        vec![Value::F64(6.5)]
+    );
+
+    assert!(matches!(
+        unsafe {
+            libm.function(
+                &mut store,
+                "cos",
+                FunctionType::new(vec![Type::V128], vec![Type::F64]),
+            )
+        },
+        Err(NativeLibraryError::UnsupportedSignature { .. })
+    ));
+    assert!(matches!(
+        unsafe {
+            libm.function(
+                &mut store,
+                "not_a_symbol",
+                FunctionType::new(vec![], vec![]),
+            )
+        },
+        Err(NativeLibraryError::MissingSymbol { .. })
+    ));
+    Ok(())
+}
@@ -78,3 +78,2154 @@ fn unit_native_function_env() -> Result<(), String> {
 
     Ok(())
 }
+
+#[cfg(feature = "sys")]
+#[test]
+fn store_tags_are_attached_to_traps() -> Result<(), String> {
+    let mut store = Store::default();
+    store.set_tag("tenant", "acme");
+    store.set_tag("request", "42");
+    let module = Module::new(
+        &store,
+        "
+(module
+  (func $boom (export \"boom\") (result i32)
+    unreachable))
+",
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+
+    let boom = instance
+        .exports
+        .get_function("boom")
+        .map_err(|e| format!("{e:?}"))?;
+    let err = boom.call(&mut store, &[]).unwrap_err();
+    assert_eq!(
+        err.tags(),
+        &[
+            ("request".to_string(), "42".to_string()),
+            ("tenant".to_string(), "acme".to_string())
+        ]
+    );
+    assert!(err
+        .to_string()
+        .starts_with("RuntimeError: unreachable [request=42, tenant=acme]"));
+
+    let boom: TypedFunction<(), i32> = boom.typed(&mut store).map_err(|e| format!("{e:?}"))?;
+    assert_eq!(store.remove_tag("request").as_deref(), Some("42"));
+    let err = boom.call(&mut store).unwrap_err();
+    assert_eq!(err.tags(), &[("tenant".to_string(), "acme".to_string())]);
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn diagnostics_report_live_stores() -> Result<(), String> {
+    diagnostics::enable();
+    let mut store = Store::default();
+    store.set_tag("tenant", "diagnosed");
+    let module = Module::new(
+        &store,
+        "
+(module $diagnosed
+  (import \"env\" \"report\" (func $report))
+  (memory (export \"memory\") 2)
+  (func (export \"run\") (call $report))
+  (func (export \"boom\") unreachable))
+",
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let env = FunctionEnv::new(&mut store, String::new());
+    let report =
+        Function::new_typed_with_env(&mut store, &env, |mut env: FunctionEnvMut<String>| {
+            *env.data_mut() = diagnostics::report();
+        });
+    let instance = Instance::new(
+        &mut store,
+        &module,
+        &imports! { "env" => { "report" => report } },
+    )
+    .map_err(|e| format!("{e:?}"))?;
+
+    let run: TypedFunction<(), ()> = instance
+        .exports
+        .get_typed_function(&store, "run")
+        .map_err(|e| format!("{e:?}"))?;
+    run.call(&mut store).map_err(|e| format!("{e:?}"))?;
+    let during_call = env.as_ref(&store).clone();
+    let ours = during_call
+        .split("\nstore #")
+        .find(|store| store.contains("tags: tenant=diagnosed"))
+        .ok_or_else(|| during_call.clone())?;
+    assert!(ours.contains("diagnosed: 1 memory, 131072 bytes (2 pages)"));
+    assert!(ours.contains("active calls: 1"));
+    assert!(ours.contains("entered through diagnosed::<func 1> on thread"));
+
+    let boom = instance
+        .exports
+        .get_function("boom")
+        .map_err(|e| format!("{e:?}"))?;
+    boom.call(&mut store, &[]).unwrap_err();
+    let after_trap = diagnostics::report();
+    let ours = after_trap
+        .split("\nstore #")
+        .find(|store| store.contains("tags: tenant=diagnosed"))
+        .ok_or_else(|| after_trap.clone())?;
+    assert!(ours.contains("active calls: 0"));
+    assert!(ours.contains("recent traps: 1"));
+    assert!(ours.contains("RuntimeError: unreachable\n    at <unnamed> (diagnosed[2]:"));
+
+    drop(store);
+    assert!(!diagnostics::report().contains("tags: tenant=diagnosed"));
+
+    Ok(())
+}
+
+#[cfg(all(feature = "sys", unix))]
+#[test]
+fn diagnostic_server_serves_reports() -> Result<(), String> {
+    use std::io::Read;
+    use std::os::unix::net::UnixStream;
+
+    let server = diagnostics::DiagnosticServer::start().map_err(|e| e.to_string())?;
+    assert!(diagnostics::enabled());
+    assert_eq!(
+        server.path(),
+        diagnostics::socket_path(std::process::id()).as_path()
+    );
+
+    let mut report = String::new();
+    UnixStream::connect(server.path())
+        .and_then(|mut stream| stream.read_to_string(&mut report))
+        .map_err(|e| e.to_string())?;
+    assert!(report.starts_with(&format!(
+        "wasmer {} (pid {})",
+        env!("CARGO_PKG_VERSION"),
+        std::process::id()
+    )));
+
+    let path = server.path().to_path_buf();
+    drop(server);
+    assert!(!path.exists());
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn pooled_instances_recycle_their_slots() -> Result<(), String> {
+    let wat = r#"
+(module
+  (memory (export "mem") 1)
+  (table 1 funcref)
+  (func (export "poke")
+    (i32.store (i32.const 0) (i32.const 42)))
+  (func (export "peek") (result i32)
+    (i32.load (i32.const 0))))
+"#;
+    let bytes = Module::new(&Store::default(), wat)
+        .map_err(|e| format!("{e:?}"))?
+        .serialize()
+        .map_err(|e| format!("{e:?}"))?;
+    let pool = InstancePool::new(PoolingConfig {
+        max_instances: 2,
+        max_memories: 2,
+        max_tables: 2,
+        table_elements: 16,
+        ..Default::default()
+    })?;
+    let engine = EngineBuilder::headless()
+        .set_instance_pool(Some(pool.clone()))
+        .engine();
+    let module = unsafe { Module::deserialize(&Store::new(&engine), bytes) }
+        .map_err(|e| format!("{e:?}"))?;
+
+    for _ in 0..3 {
+        let mut store = Store::new(&engine);
+        let instance =
+            Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+        assert_eq!(pool.available_instances(), 1);
+        assert_eq!(pool.available_memories(), 1);
+        assert_eq!(pool.available_tables(), 1);
+
+        let peek: TypedFunction<(), i32> = instance
+            .exports
+            .get_typed_function(&mut store, "peek")
+            .map_err(|e| format!("{e:?}"))?;
+        let poke: TypedFunction<(), ()> = instance
+            .exports
+            .get_typed_function(&mut store, "poke")
+            .map_err(|e| format!("{e:?}"))?;
+        // Recycled memories are zeroed.
+        assert_eq!(peek.call(&mut store).map_err(|e| format!("{e:?}"))?, 0);
+        poke.call(&mut store).map_err(|e| format!("{e:?}"))?;
+        assert_eq!(peek.call(&mut store).map_err(|e| format!("{e:?}"))?, 42);
+
+        drop(store);
+        assert_eq!(pool.available_instances(), 2);
+        assert_eq!(pool.available_memories(), 2);
+        assert_eq!(pool.available_tables(), 2);
+    }
+
+    let mut store = Store::new(&engine);
+    Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    match Instance::new(&mut store, &module, &imports! {}) {
+        Err(InstantiationError::Link(LinkError::Resource(message))) => {
+            assert!(message.contains("no instance slot left"), "{}", message)
+        }
+        other => panic!("unexpected instantiation result: {:?}", other.map(|_| ())),
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn deserialized_modules_initialize_memories_from_images() -> Result<(), String> {
+    let wat = r#"
+(module
+  (memory (export "mem") 2)
+  (data (i32.const 8) "\2a\00\00\00")
+  (data (i32.const 0x1fffc) "\07\00\00\00")
+  (func (export "poke") (param i32 i32)
+    (i32.store (local.get 0) (local.get 1)))
+  (func (export "peek") (param i32) (result i32)
+    (i32.load (local.get 0))))
+"#;
+    let store = Store::default();
+    let bytes = Module::new(&store, wat)
+        .map_err(|e| format!("{e:?}"))?
+        .serialize()
+        .map_err(|e| format!("{e:?}"))?;
+    let module = unsafe { Module::deserialize(&store, bytes) }.map_err(|e| format!("{e:?}"))?;
+
+    let mut store = Store::new(store.engine());
+    let mut accessors = Vec::new();
+    for _ in 0..2 {
+        let instance =
+            Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+        let peek: TypedFunction<i32, i32> = instance
+            .exports
+            .get_typed_function(&mut store, "peek")
+            .map_err(|e| format!("{e:?}"))?;
+        let poke: TypedFunction<(i32, i32), ()> = instance
+            .exports
+            .get_typed_function(&mut store, "poke")
+            .map_err(|e| format!("{e:?}"))?;
+        accessors.push((peek, poke));
+    }
+
+    let (peek, poke) = &accessors[0];
+    assert_eq!(peek.call(&mut store, 8).map_err(|e| format!("{e:?}"))?, 42);
+    assert_eq!(
+        peek.call(&mut store, 0x1fffc)
+            .map_err(|e| format!("{e:?}"))?,
+        7
+    );
+    poke.call(&mut store, 8, 1).map_err(|e| format!("{e:?}"))?;
+    assert_eq!(peek.call(&mut store, 8).map_err(|e| format!("{e:?}"))?, 1);
+
+    // Writes stay private to the instance that makes them.
+    let (peek, _) = &accessors[1];
+    assert_eq!(peek.call(&mut store, 8).map_err(|e| format!("{e:?}"))?, 42);
+    assert_eq!(peek.call(&mut store, 0).map_err(|e| format!("{e:?}"))?, 0);
+
+    // Data segments that don't fit still trap on instantiation.
+    let bytes = Module::new(
+        &store,
+        r#"(module (memory 1) (data (i32.const 0xfffe) "\01\02\03"))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?
+    .serialize()
+    .map_err(|e| format!("{e:?}"))?;
+    let module = unsafe { Module::deserialize(&store, bytes) }.map_err(|e| format!("{e:?}"))?;
+    assert!(matches!(
+        Instance::new(&mut store, &module, &imports! {}),
+        Err(InstantiationError::Start(_))
+    ));
+
+    Ok(())
+}
+
+#[cfg(all(feature = "sys", feature = "cranelift", target_arch = "x86_64", unix))]
+#[test]
+fn lazily_compiled_functions_run_on_first_call() -> Result<(), String> {
+    let wat = r#"
+(module
+  (func $fac (export "fac") (param i64) (result i64)
+    (if (result i64) (i64.eqz (local.get 0))
+      (then (i64.const 1))
+      (else
+        (i64.mul (local.get 0)
+          (call $fac (i64.sub (local.get 0) (i64.const 1)))))))
+  (func (export "fac_plus") (param i64 f64) (result f64)
+    (f64.add (f64.convert_i64_u (call $fac (local.get 0))) (local.get 1)))
+  (func (export "trap")
+    unreachable))
+"#;
+    let engine = EngineBuilder::new(Cranelift::default())
+        .set_lazy_compilation(true)
+        .engine();
+    assert!(engine.lazy_compilation());
+    let mut store = Store::new(&engine);
+    let module = Module::new(&store, wat).map_err(|e| format!("{e:?}"))?;
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+
+    let fac_plus: TypedFunction<(i64, f64), f64> = instance
+        .exports
+        .get_typed_function(&mut store, "fac_plus")
+        .map_err(|e| format!("{e:?}"))?;
+    let fac: TypedFunction<i64, i64> = instance
+        .exports
+        .get_typed_function(&mut store, "fac")
+        .map_err(|e| format!("{e:?}"))?;
+    // The first call compiles both functions, later ones go straight to
+    // the compiled code.
+    assert_eq!(
+        fac_plus
+            .call(&mut store, 5, 0.5)
+            .map_err(|e| format!("{e:?}"))?,
+        120.5
+    );
+    assert_eq!(fac.call(&mut store, 6).map_err(|e| format!("{e:?}"))?, 720);
+
+    let trap: TypedFunction<(), ()> = instance
+        .exports
+        .get_typed_function(&mut store, "trap")
+        .map_err(|e| format!("{e:?}"))?;
+    // Traps in lazily compiled functions are symbolicated as usual.
+    let error = trap.call(&mut store).unwrap_err();
+    assert_eq!(error.trace().len(), 1);
+    assert_eq!(error.trace()[0].func_index(), 2);
+
+    // Serialized lazy modules hold all of their functions.
+    let bytes = module.serialize().map_err(|e| format!("{e:?}"))?;
+    let module = unsafe { Module::deserialize(&store, bytes) }.map_err(|e| format!("{e:?}"))?;
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let fac: TypedFunction<i64, i64> = instance
+        .exports
+        .get_typed_function(&mut store, "fac")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(fac.call(&mut store, 4).map_err(|e| format!("{e:?}"))?, 24);
+
+    Ok(())
+}
+
+#[cfg(all(feature = "sys", feature = "cranelift"))]
+#[test]
+fn modules_compile_on_the_engine_threads() -> Result<(), String> {
+    let engine = EngineBuilder::new(Cranelift::default())
+        .set_compile_jobs(Some(2))
+        .engine();
+    assert_eq!(engine.compile_jobs(), Some(2));
+    let mut store = Store::new(&engine);
+    let module = Module::new(
+        &store,
+        r#"
+(module
+  (func $double (param i32) (result i32)
+    (i32.add (local.get 0) (local.get 0)))
+  (func (export "quadruple") (param i32) (result i32)
+    (call $double (call $double (local.get 0)))))
+"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let quadruple: TypedFunction<i32, i32> = instance
+        .exports
+        .get_typed_function(&mut store, "quadruple")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        quadruple
+            .call(&mut store, 3)
+            .map_err(|e| format!("{e:?}"))?,
+        12
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn guarded_imports_are_denied_once_their_capability_is_gone() -> Result<(), String> {
+    use std::time::Duration;
+
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"
+(module
+  (import "net" "send" (func $send (param i32) (result i32)))
+  (func (export "send") (param i32) (result i32)
+    (call $send (local.get 0))))
+"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    fn send_bytes(len: i32) -> i32 {
+        len
+    }
+
+    let network = Capability::new("network");
+    let mut import_object = imports! {
+        "net" => { "send" => Function::new_typed(&mut store, send_bytes) },
+    };
+    import_object.guard_namespace(&mut store, "net", &network, Denial::Trap);
+    let instance =
+        Instance::new(&mut store, &module, &import_object).map_err(|e| format!("{e:?}"))?;
+    let send: TypedFunction<i32, i32> = instance
+        .exports
+        .get_typed_function(&mut store, "send")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(send.call(&mut store, 5).map_err(|e| format!("{e:?}"))?, 5);
+
+    network.revoke();
+    let error = send.call(&mut store, 5).unwrap_err();
+    assert_eq!(
+        error
+            .downcast::<CapabilityError>()
+            .map_err(|e| format!("{e:?}"))?,
+        CapabilityError::Revoked("network".to_string())
+    );
+
+    let lease = Capability::new("lease");
+    let mut import_object = imports! {
+        "net" => { "send" => Function::new_typed(&mut store, send_bytes) },
+    };
+    import_object.guard_namespace(
+        &mut store,
+        "net",
+        &lease,
+        Denial::Return(vec![Value::I32(-1)]),
+    );
+    let instance =
+        Instance::new(&mut store, &module, &import_object).map_err(|e| format!("{e:?}"))?;
+    let send: TypedFunction<i32, i32> = instance
+        .exports
+        .get_typed_function(&mut store, "send")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(send.call(&mut store, 5).map_err(|e| format!("{e:?}"))?, 5);
+    lease.expire_after(Duration::from_secs(0));
+    assert!(!lease.is_valid());
+    assert_eq!(send.call(&mut store, 5).map_err(|e| format!("{e:?}"))?, -1);
+
+    Ok(())
+}
+
+#[cfg(all(
+    feature = "sys",
+    feature = "singlepass",
+    feature = "cranelift",
+    target_arch = "x86_64",
+    unix
+))]
+#[test]
+fn functions_are_hot_swapped_to_the_optimized_tier() -> Result<(), String> {
+    let wat = r#"
+(module
+  (func $fac (export "fac") (param i64) (result i64)
+    (if (result i64) (i64.eqz (local.get 0))
+      (then (i64.const 1))
+      (else
+        (i64.mul (local.get 0)
+          (call $fac (i64.sub (local.get 0) (i64.const 1)))))))
+  (func (export "fac_plus") (param i64) (result i64)
+    (i64.add (call $fac (local.get 0)) (i64.const 1))))
+"#;
+    let engine = EngineBuilder::new(Singlepass::default())
+        .set_optimized_tier(Some(Box::new(Cranelift::default())))
+        .engine();
+    assert!(engine.optimized_tier().is_some());
+    let mut store = Store::new(&engine);
+    let module = Module::new(&store, wat).map_err(|e| format!("{e:?}"))?;
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+
+    let fac: TypedFunction<i64, i64> = instance
+        .exports
+        .get_typed_function(&mut store, "fac")
+        .map_err(|e| format!("{e:?}"))?;
+    let fac_plus: TypedFunction<i64, i64> = instance
+        .exports
+        .get_typed_function(&mut store, "fac_plus")
+        .map_err(|e| format!("{e:?}"))?;
+    // Calls made before and while the functions are recompiled, and after
+    // all of them have been swapped, give the same results.
+    assert_eq!(fac.call(&mut store, 5).map_err(|e| format!("{e:?}"))?, 120);
+    assert_eq!(
+        fac_plus.call(&mut store, 5).map_err(|e| format!("{e:?}"))?,
+        121
+    );
+    module
+        .wait_for_optimized_tier()
+        .map_err(|e| format!("{e:?}"))?;
+    // Both functions run the code of the optimized tier from then on.
+    assert_eq!(module.metrics().optimized_functions, 2);
+    assert_eq!(fac.call(&mut store, 6).map_err(|e| format!("{e:?}"))?, 720);
+    assert_eq!(
+        fac_plus.call(&mut store, 6).map_err(|e| format!("{e:?}"))?,
+        721
+    );
+    // Waiting again returns straight away.
+    module
+        .wait_for_optimized_tier()
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(module.metrics().optimized_functions, 2);
+
+    Ok(())
+}
+
+#[cfg(all(feature = "sys", feature = "interpreter"))]
+#[test]
+fn modules_run_in_the_interpreter() -> Result<(), String> {
+    let wat = r#"
+(module
+  (import "env" "double" (func $double (param i32) (result i32)))
+  (type $unary (func (param i64) (result i64)))
+  (memory (export "memory") 1)
+  (table 2 funcref)
+  (elem (i32.const 0) $fac $double)
+  (func $fac (export "fac") (param i64) (result i64)
+    (local i64)
+    (local.set 1 (i64.const 1))
+    (block
+      (loop
+        (br_if 1 (i64.eqz (local.get 0)))
+        (local.set 1 (i64.mul (local.get 1) (local.get 0)))
+        (local.set 0 (i64.sub (local.get 0) (i64.const 1)))
+        (br 0)))
+    (local.get 1))
+  (func (export "call_indirect") (param i64 i32) (result i64)
+    (call_indirect (type $unary) (local.get 0) (local.get 1)))
+  (func (export "store_double") (param i32 i32) (result i32)
+    (i32.store (local.get 0) (call $double (local.get 1)))
+    (i32.load (local.get 0)))
+  (func (export "div") (param i32 i32) (result i32)
+    (i32.div_s (local.get 0) (local.get 1))))
+"#;
+    let mut store = Store::new(Interpreter::default());
+    let module = Module::new(&store, wat).map_err(|e| format!("{e:?}"))?;
+    let imports = imports! {
+        "env" => {
+            "double" => Function::new_typed(&mut store, |x: i32| x * 2),
+        },
+    };
+    let instance = Instance::new(&mut store, &module, &imports).map_err(|e| format!("{e:?}"))?;
+
+    let fac: TypedFunction<i64, i64> = instance
+        .exports
+        .get_typed_function(&mut store, "fac")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(fac.call(&mut store, 5).map_err(|e| format!("{e:?}"))?, 120);
+    let call_indirect: TypedFunction<(i64, i32), i64> = instance
+        .exports
+        .get_typed_function(&mut store, "call_indirect")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        call_indirect
+            .call(&mut store, 6, 0)
+            .map_err(|e| format!("{e:?}"))?,
+        720
+    );
+    let err = call_indirect.call(&mut store, 6, 1).unwrap_err();
+    assert_eq!(err.message(), "indirect call type mismatch");
+
+    // Host functions are called from interpreted code, and memory accesses
+    // are bounds checked.
+    let store_double: TypedFunction<(i32, i32), i32> = instance
+        .exports
+        .get_typed_function(&mut store, "store_double")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        store_double
+            .call(&mut store, 16, 21)
+            .map_err(|e| format!("{e:?}"))?,
+        42
+    );
+    let memory = instance
+        .exports
+        .get_memory("memory")
+        .map_err(|e| format!("{e:?}"))?;
+    let mut bytes = [0; 4];
+    memory
+        .view(&store)
+        .read(16, &mut bytes)
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(i32::from_le_bytes(bytes), 42);
+    let err = store_double.call(&mut store, 65534, 1).unwrap_err();
+    assert_eq!(err.message(), "out of bounds memory access");
+
+    let div: TypedFunction<(i32, i32), i32> = instance
+        .exports
+        .get_typed_function(&mut store, "div")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        div.call(&mut store, 7, -2).map_err(|e| format!("{e:?}"))?,
+        -3
+    );
+    let err = div.call(&mut store, 7, 0).unwrap_err();
+    assert_eq!(err.message(), "integer divide by zero");
+
+    // There is no machine code to serialize.
+    assert!(module.serialize().is_err());
+
+    Ok(())
+}
+
+#[cfg(all(feature = "sys", feature = "interpreter"))]
+#[test]
+fn interpreted_instances_stop_on_breakpoints_and_steps() -> Result<(), String> {
+    use std::sync::{Arc, Mutex};
+
+    let mut store = Store::new(Interpreter::default());
+    let module = Module::new(
+        &store,
+        r#"(module (func (export "add") (param i32 i32) (result i32) (local i32)
+             (local.set 2 (i32.add (local.get 0) (local.get 1)))
+             (local.get 2)))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let add: TypedFunction<(i32, i32), i32> = instance
+        .exports
+        .get_typed_function(&mut store, "add")
+        .map_err(|e| format!("{e:?}"))?;
+
+    assert_eq!(instance.step(&mut store), Err(DebugError::NoDebugger));
+
+    // Step from the start of the function until the sum is stored.
+    let stops = Arc::new(Mutex::new(Vec::new()));
+    let recorded = stops.clone();
+    instance.set_debugger(
+        &mut store,
+        move |_: &mut StoreMut<'_>, frame: &DebugFrame| -> DebugAction {
+            let locals: Vec<Option<i32>> = frame.locals.iter().map(Value::i32).collect();
+            let done = locals[2] == Some(7);
+            recorded
+                .lock()
+                .unwrap()
+                .push((frame.reason, frame.func_index, frame.offset, locals));
+            if done {
+                DebugAction::Continue
+            } else {
+                DebugAction::Step
+            }
+        },
+    );
+    instance
+        .set_breakpoint(&mut store, 0, 0)
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        instance.set_breakpoint(&mut store, 1, 0),
+        Err(DebugError::UnknownLocation {
+            func_index: 1,
+            offset: 0
+        })
+    );
+    assert_eq!(add.call(&mut store, 3, 4).map_err(|e| format!("{e:?}"))?, 7);
+
+    let frames = std::mem::take(&mut *stops.lock().unwrap());
+    assert!(frames.len() > 1);
+    let (reason, func_index, _, locals) = &frames[0];
+    assert_eq!(*reason, StopReason::Breakpoint);
+    assert_eq!(*func_index, 0);
+    assert_eq!(locals, &[Some(3), Some(4), Some(0)]);
+    for window in frames.windows(2) {
+        assert_eq!(window[1].0, StopReason::Step);
+        assert!(window[1].2 >= window[0].2);
+    }
+    assert_eq!(frames.last().unwrap().3, [Some(3), Some(4), Some(7)]);
+
+    // Without breakpoints, the function runs straight through.
+    assert_eq!(instance.clear_breakpoint(&mut store, 0, 0), Ok(true));
+    assert_eq!(add.call(&mut store, 1, 2).map_err(|e| format!("{e:?}"))?, 3);
+    assert!(stops.lock().unwrap().is_empty());
+    instance.remove_debugger(&mut store);
+
+    Ok(())
+}
+
+#[cfg(all(
+    feature = "sys",
+    feature = "cranelift",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+#[test]
+fn modules_are_cross_compiled_for_other_targets() -> Result<(), String> {
+    let (triple, features) = if cfg!(target_arch = "x86_64") {
+        ("aarch64-unknown-linux-gnu", CpuFeature::set())
+    } else {
+        (
+            "x86_64-unknown-linux-gnu",
+            CpuFeature::set() | CpuFeature::SSE2,
+        )
+    };
+    let target = Target::new(triple.parse().map_err(|e| format!("{e:?}"))?, features);
+    assert!(!target.is_native());
+    let engine = EngineBuilder::new(Cranelift::default())
+        .set_target(Some(target))
+        .engine();
+    let mut store = Store::new(&engine);
+    let module = Module::new(
+        &store,
+        r#"(module (func (export "add") (param i32 i32) (result i32)
+             (i32.add (local.get 0) (local.get 1))))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+
+    // The code is compiled and serialized, but never linked on this host.
+    let bytes = module.serialize().map_err(|e| format!("{e:?}"))?;
+    assert!(matches!(
+        Instance::new(&mut store, &module, &imports! {}),
+        Err(InstantiationError::DifferentArchOS(_))
+    ));
+    assert!(matches!(
+        unsafe { Module::deserialize(&Store::default(), bytes) },
+        Err(DeserializeError::Incompatible(_))
+    ));
+
+    Ok(())
+}
+
+#[cfg(all(feature = "sys", feature = "llvm", not(target_arch = "riscv64")))]
+#[test]
+fn modules_are_cross_compiled_for_riscv64() -> Result<(), String> {
+    let triple = "riscv64gc-unknown-linux-gnu";
+    let target = Target::new(
+        triple.parse().map_err(|e| format!("{e:?}"))?,
+        CpuFeature::set(),
+    );
+    let engine = EngineBuilder::new(LLVM::default())
+        .set_target(Some(target))
+        .engine();
+    let store = Store::new(&engine);
+    // Direct calls, libcalls, constant pools and multi-value results all
+    // need their own relocations or ABI lowering on RISC-V.
+    let module = Module::new(
+        &store,
+        r#"(module
+             (func $floor (param f32 f64) (result f32 f64)
+               (f32.floor (local.get 0))
+               (f64.mul (f64.floor (local.get 1)) (f64.const 0.1)))
+             (func (export "floor") (param f32 f64) (result f32 f64)
+               (call $floor (local.get 0) (local.get 1)))
+             (func (export "swap") (param i32 f32) (result f32 i32)
+               (local.get 1) (local.get 0)))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+
+    assert!(!module.serialize().map_err(|e| format!("{e:?}"))?.is_empty());
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn instances_are_verified_against_abi_contracts() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+             (memory (export "memory") 1)
+             (global (export "version") i32 (i32.const 3))
+             (func (export "alloc") (param i32) (result i32) (local.get 0)))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+
+    let contract = AbiContract::new("host ABI v3")
+        .function("alloc", FunctionType::new(vec![Type::I32], vec![Type::I32]))
+        .global("version", GlobalType::new(Type::I32, Mutability::Const))
+        .memory("memory", Pages(1));
+    let mut imports = imports! {};
+    imports.require_contract(contract.clone());
+    Instance::new(&mut store, &module, &imports).map_err(|e| format!("{e:?}"))?;
+
+    // Every difference is reported at once.
+    let mut imports = imports! {};
+    imports.require_contract(
+        contract
+            .function("alloc", FunctionType::new(vec![Type::I64], vec![Type::I64]))
+            .function("handle", FunctionType::new(vec![], vec![]))
+            .function("memory", FunctionType::new(vec![], vec![]))
+            .memory("heap", Pages(2)),
+    );
+    let violation = match Instance::new(&mut store, &module, &imports) {
+        Err(InstantiationError::Contract(violation)) => violation,
+        other => return Err(format!("unexpected result: {other:?}")),
+    };
+    assert_eq!(violation.contract, "host ABI v3");
+    assert_eq!(violation.mismatches.len(), 4);
+    assert_eq!(
+        violation.mismatches[0].to_string(),
+        "export `alloc` is a function [I32] -> [I32], expected a function [I64] -> [I64]"
+    );
+    assert!(matches!(
+        &violation.mismatches[1],
+        ExportMismatch::Type { name, found, .. } if name == "memory" && found == "memory export"
+    ));
+    assert!(matches!(
+        &violation.mismatches[2],
+        ExportMismatch::Missing { name, .. } if name == "handle"
+    ));
+    assert!(violation
+        .to_string()
+        .starts_with("the module doesn't match host ABI v3:\n  - "));
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn abi_contracts_are_verified_before_instantiation() -> Result<(), String> {
+    let mut store = Store::default();
+    // The start function would trap if it ran.
+    let module = Module::new(
+        &store,
+        r#"(module
+             (memory (export "memory") 1)
+             (func $start unreachable)
+             (start $start))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+
+    let mut imports = imports! {};
+    imports.require_contract(AbiContract::new("host ABI v3").memory("memory", Pages(2)));
+    match Instance::new(&mut store, &module, &imports) {
+        Err(InstantiationError::Contract(violation)) => assert_eq!(
+            violation.mismatches[0].to_string(),
+            "export `memory` is a memory of 1 pages, expected a memory of at least 2 pages"
+        ),
+        other => return Err(format!("unexpected result: {other:?}")),
+    }
+
+    Ok(())
+}
+
+#[cfg(all(feature = "sys", feature = "interpreter"))]
+#[test]
+fn interpreter_traps_have_a_wasm_backtrace() -> Result<(), String> {
+    let wat = r#"
+(module
+  (func $outer (export "outer") (param i32) (result i32)
+    (call $divide (i32.const 1) (local.get 0)))
+  (func $divide (param i32 i32) (result i32)
+    (i32.div_s (local.get 0) (local.get 1))))
+"#;
+    let mut store = Store::new(Interpreter::default());
+    let module = Module::new(&store, wat).map_err(|e| format!("{e:?}"))?;
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let outer: TypedFunction<i32, i32> = instance
+        .exports
+        .get_typed_function(&mut store, "outer")
+        .map_err(|e| format!("{e:?}"))?;
+
+    let err = outer.call(&mut store, 0).unwrap_err();
+    assert_eq!(err.message(), "integer divide by zero");
+    let trace = err.trace();
+    assert_eq!(trace.len(), 2);
+    assert_eq!(trace[0].function_name(), Some("divide"));
+    assert_eq!(trace[0].func_index(), 1);
+    assert_eq!(trace[1].function_name(), Some("outer"));
+    assert_eq!(trace[1].func_index(), 0);
+    // The frames point at the `i32.div_s` and at the `call`, after the
+    // operands of both.
+    assert!(trace[0].func_offset() > 2);
+    assert!(trace[1].func_offset() > 2);
+    assert!(trace[0].module_offset() > trace[1].module_offset());
+    assert!(err.to_string().contains("at divide"));
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn dynamic_functions_proxy_imports_known_at_runtime() -> Result<(), String> {
+    use std::sync::{Arc, Mutex};
+
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+             (import "env" "add" (func $add (param i32 i64) (result i64)))
+             (import "env" "scale" (func $scale (param f64) (result f64 i32)))
+             (func (export "run") (result i64)
+               (drop (call $scale (f64.const 1.5)))
+               (drop)
+               (call $add (i32.const 1) (i64.const 2))))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+
+    // Record every call and return default values, without knowing the
+    // imports at compile time.
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let mut imports = Imports::new();
+    for import in module.imports() {
+        if let ExternType::Function(ty) = import.ty() {
+            let name = import.name().to_string();
+            let results = ty.results().to_vec();
+            let calls = calls.clone();
+            let proxy = Function::new(&mut store, ty, move |args| {
+                calls.lock().unwrap().push((name.clone(), args.to_vec()));
+                Ok(results.iter().map(|ty| Value::default_for(*ty)).collect())
+            });
+            imports.define(import.module(), import.name(), proxy);
+        }
+    }
+
+    let instance = Instance::new(&mut store, &module, &imports).map_err(|e| format!("{e:?}"))?;
+    let run = instance
+        .exports
+        .get_function("run")
+        .map_err(|e| format!("{e:?}"))?;
+    let results = run.call(&mut store, &[]).map_err(|e| format!("{e:?}"))?;
+    assert_eq!(results.to_vec(), vec![Value::I64(0)]);
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec![
+            ("scale".to_string(), vec![Value::F64(1.5)]),
+            ("add".to_string(), vec![Value::I32(1), Value::I64(2)]),
+        ]
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn host_envs_are_initialized_before_the_start_function() -> Result<(), String> {
+    #[derive(Default)]
+    struct Env {
+        memory: Option<Memory>,
+        seen: Vec<u8>,
+    }
+
+    fn record(mut env: FunctionEnvMut<Env>, ptr: u32, len: u32) {
+        let memory = env.data().memory.clone().expect("memory initialized");
+        let mut bytes = vec![0; len as usize];
+        memory.view(&env).read(ptr as u64, &mut bytes).unwrap();
+        env.data_mut().seen.extend(bytes);
+    }
+
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+             (import "env" "record" (func $record (param i32 i32)))
+             (memory (export "memory") 1)
+             (data (i32.const 8) "hello")
+             (func $start (call $record (i32.const 8) (i32.const 5)))
+             (start $start))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let env = FunctionEnv::new(&mut store, Env::default());
+    let mut imports = imports! {
+        "env" => { "record" => Function::new_typed_with_env(&mut store, &env, record) },
+    };
+    imports.initialize_env(&env, |env: &mut Env, instance| {
+        env.memory = Some(instance.exports.get_memory("memory")?.clone());
+        Ok(())
+    });
+    Instance::new(&mut store, &module, &imports).map_err(|e| format!("{e:?}"))?;
+    assert_eq!(env.as_ref(&store).seen, b"hello");
+
+    // A failed initialization fails the instantiation.
+    let mut imports = imports! {
+        "env" => { "record" => Function::new_typed_with_env(&mut store, &env, record) },
+    };
+    imports.initialize_env(&env, |env: &mut Env, instance| {
+        env.memory = Some(instance.exports.get_memory("heap")?.clone());
+        Ok(())
+    });
+    assert!(matches!(
+        Instance::new(&mut store, &module, &imports),
+        Err(InstantiationError::HostEnvInitialization(ExportError::Missing(name))) if name == "heap"
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn start_functions_can_be_deferred_and_reactors_initialized() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+             (global $started (export "started") (mut i32) (i32.const 0))
+             (global $initialized (export "initialized") (mut i32) (i32.const 0))
+             (func $start
+               (global.set $started (i32.add (global.get $started) (i32.const 1))))
+             (func (export "_initialize")
+               (global.set $initialized (i32.add (global.get $initialized) (i32.const 1))))
+             (start $start))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let counters = |store: &mut Store, instance: &Instance| {
+        let exports = &instance.exports;
+        (
+            exports.get_global("started").unwrap().get(store),
+            exports.get_global("initialized").unwrap().get(store),
+        )
+    };
+
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        counters(&mut store, &instance),
+        (Value::I32(1), Value::I32(0))
+    );
+
+    // A deferred start function runs once, when asked to.
+    let instance = Instance::new_with_initialization(
+        &mut store,
+        &module,
+        &imports! {},
+        Initialization::Deferred,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        counters(&mut store, &instance),
+        (Value::I32(0), Value::I32(0))
+    );
+    instance.start(&mut store).map_err(|e| format!("{e:?}"))?;
+    instance.start(&mut store).map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        counters(&mut store, &instance),
+        (Value::I32(1), Value::I32(0))
+    );
+
+    let instance = Instance::new_with_initialization(
+        &mut store,
+        &module,
+        &imports! {},
+        Initialization::Reactor,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        counters(&mut store, &instance),
+        (Value::I32(1), Value::I32(1))
+    );
+
+    // A start function that already ran doesn't run again.
+    instance.start(&mut store).map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        counters(&mut store, &instance),
+        (Value::I32(1), Value::I32(1))
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn async_host_functions_suspend_call_async() -> Result<(), String> {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    /// A future pending once before completing.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    struct CountWakes(AtomicUsize);
+
+    impl Wake for CountWakes {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+             (import "host" "double" (func $double (param i32) (result i32)))
+             (func (export "run") (param i32) (result i32)
+               (i32.add (call $double (local.get 0)) (i32.const 1))))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let double = Function::new_async(
+        &mut store,
+        FunctionType::new(vec![Type::I32], vec![Type::I32]),
+        |args| {
+            let n = args[0].unwrap_i32();
+            async move {
+                YieldOnce(false).await;
+                Ok(vec![Value::I32(n * 2)])
+            }
+        },
+    );
+    let imports = imports! { "host" => { "double" => double } };
+    let instance = Instance::new(&mut store, &module, &imports).map_err(|e| format!("{e:?}"))?;
+    let run = instance
+        .exports
+        .get_function("run")
+        .map_err(|e| format!("{e:?}"))?;
+
+    let wakes = Arc::new(CountWakes(AtomicUsize::new(0)));
+    let waker = Waker::from(wakes.clone());
+    let mut cx = Context::from_waker(&waker);
+    let params = [Value::I32(20)];
+    let mut call = Box::pin(run.call_async(&mut store, &params));
+    let mut polls = 0;
+    let results = loop {
+        polls += 1;
+        if let Poll::Ready(results) = call.as_mut().poll(&mut cx) {
+            break results.map_err(|e| format!("{e:?}"))?;
+        }
+    };
+    drop(call);
+    assert_eq!(results.to_vec(), vec![Value::I32(41)]);
+    assert_eq!(polls, 2);
+    assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+
+    // Synchronous calls can't wait for the host function.
+    let error = run.call(&mut store, &params).unwrap_err();
+    assert!(error.message().contains("Function::call_async"));
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn dropping_call_async_drops_the_host_future() -> Result<(), String> {
+    use std::future::Future;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    /// Records that the future owning it was dropped.
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+             (import "host" "wait" (func $wait))
+             (global $calls (export "calls") (mut i32) (i32.const 0))
+             (func (export "run")
+               (global.set $calls (i32.add (global.get $calls) (i32.const 1)))
+               (call $wait)
+               (global.set $calls (i32.const 100))))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let dropped = Arc::new(AtomicBool::new(false));
+    let flag = dropped.clone();
+    let wait = Function::new_async(&mut store, FunctionType::new(vec![], vec![]), move |_| {
+        let flag = DropFlag(flag.clone());
+        async move {
+            let _flag = flag;
+            std::future::pending::<()>().await;
+            Ok(vec![])
+        }
+    });
+    let imports = imports! { "host" => { "wait" => wait } };
+    let instance = Instance::new(&mut store, &module, &imports).map_err(|e| format!("{e:?}"))?;
+    let run = instance
+        .exports
+        .get_function("run")
+        .map_err(|e| format!("{e:?}"))?;
+    let calls = instance
+        .exports
+        .get_global("calls")
+        .map_err(|e| format!("{e:?}"))?;
+
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let mut cx = Context::from_waker(&waker);
+    for call_count in 1..=2 {
+        let mut call = Box::pin(run.call_async(&mut store, &[]));
+        assert!(call.as_mut().poll(&mut cx).is_pending());
+        assert!(!dropped.load(Ordering::SeqCst));
+        drop(call);
+        assert!(dropped.swap(false, Ordering::SeqCst));
+        // The Wasm code didn't run past the host function
+        assert_eq!(calls.get(&store), Value::I32(call_count));
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn modules_are_quarantined_after_repeated_traps() -> Result<(), String> {
+    let mut store = Store::default();
+    store.engine().set_quarantine_policy(QuarantinePolicy {
+        max_traps: Some(2),
+        ..Default::default()
+    });
+    let wat = "
+(module $crashy
+  (func $boom (export \"boom\")
+    unreachable))
+";
+    let module = Module::new(&store, wat).map_err(|e| format!("{e:?}"))?;
+    let healthy = Module::new(&store, "(module)").map_err(|e| format!("{e:?}"))?;
+
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let boom = instance
+        .exports
+        .get_function("boom")
+        .map_err(|e| format!("{e:?}"))?;
+    boom.call(&mut store, &[]).unwrap_err();
+    assert!(store.engine().quarantined(module.info()).is_none());
+    boom.call(&mut store, &[]).unwrap_err();
+
+    match Instance::new(&mut store, &module, &imports! {}) {
+        Err(InstantiationError::Quarantined(reason)) => assert_eq!(
+            reason,
+            "module `crashy` is quarantined: trapped 2 times (last failure: unreachable)"
+        ),
+        other => panic!("unexpected instantiation result: {:?}", other.map(|_| ())),
+    }
+    Instance::new(&mut store, &healthy, &imports! {}).map_err(|e| format!("{e:?}"))?;
+
+    // Compiling or deserializing the same module again doesn't escape
+    // the quarantine.
+    let recompiled = Module::new(&store, wat).map_err(|e| format!("{e:?}"))?;
+    assert!(store.engine().quarantined(recompiled.info()).is_some());
+    let serialized = module.serialize().map_err(|e| format!("{e:?}"))?;
+    let deserialized =
+        unsafe { Module::deserialize(&store, serialized) }.map_err(|e| format!("{e:?}"))?;
+    assert!(store.engine().quarantined(deserialized.info()).is_some());
+
+    store.engine().release_module(module.info());
+    Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn instance_snapshot_and_restore() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        "
+(module
+  (type $ret_i32 (func (result i32)))
+  (memory (export \"memory\") 1)
+  (global $initialized (mut i32) (i32.const 0))
+  (table 2 funcref)
+  (func $one (type $ret_i32) (i32.const 1))
+  (func $two (type $ret_i32) (i32.const 2))
+  (elem (i32.const 0) $one)
+  (elem declare func $two)
+  (func (export \"init\")
+    (memory.grow (i32.const 1))
+    drop
+    (i32.store (i32.const 70000) (i32.const 42))
+    (global.set $initialized (i32.const 1))
+    (table.set (i32.const 1) (ref.func $two)))
+  (func (export \"state\") (result i32)
+    (i32.add
+      (i32.add (i32.load (i32.const 70000)) (global.get $initialized))
+      (call_indirect (type $ret_i32) (i32.const 1)))))
+",
+    )
+    .map_err(|e| format!("{e:?}"))?;
+
+    let warm = Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let init: TypedFunction<(), ()> = warm
+        .exports
+        .get_typed_function(&mut store, "init")
+        .map_err(|e| format!("{e:?}"))?;
+    init.call(&mut store).map_err(|e| format!("{e:?}"))?;
+    let image = warm.snapshot(&mut store).map_err(|e| format!("{e:?}"))?;
+
+    let fresh = Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    fresh
+        .restore(&mut store, &image)
+        .map_err(|e| format!("{e:?}"))?;
+    let state: TypedFunction<(), i32> = fresh
+        .exports
+        .get_typed_function(&mut store, "state")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(state.call(&mut store).map_err(|e| format!("{e:?}"))?, 45);
+    let memory = fresh
+        .exports
+        .get_memory("memory")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(memory.view(&store).size(), Pages(2));
+
+    let other = Module::new(&store, "(module)").map_err(|e| format!("{e:?}"))?;
+    let other = Instance::new(&mut store, &other, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    assert!(matches!(
+        other.restore(&mut store, &image),
+        Err(InstanceImageError::ModuleMismatch)
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn hot_swapped_modules_keep_the_state_of_the_instance() -> Result<(), String> {
+    let mut store = Store::default();
+    let v1 = Module::new(
+        &store,
+        r#"(module
+             (memory (export "memory") 1)
+             (global $calls (mut i32) (i32.const 0))
+             (func (export "push") (param i32)
+               (global.set $calls (i32.add (global.get $calls) (i32.const 1)))
+               (i32.store (i32.mul (global.get $calls) (i32.const 4)) (local.get 0)))
+             (func (export "sum") (result i32)
+               (i32.add (i32.load (i32.const 4)) (i32.load (i32.const 8)))))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let v2 = Module::new(
+        &store,
+        r#"(module
+             (memory (export "memory") 1 4)
+             (global $calls (mut i32) (i32.const 0))
+             (func (export "sum") (result i32)
+               (i32.mul (i32.add (i32.load (i32.const 4)) (i32.load (i32.const 8)))
+                        (global.get $calls))))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+
+    let mut instance =
+        Instance::new(&mut store, &v1, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let push: TypedFunction<i32, ()> = instance
+        .exports
+        .get_typed_function(&mut store, "push")
+        .map_err(|e| format!("{e:?}"))?;
+    push.call(&mut store, 3).map_err(|e| format!("{e:?}"))?;
+    push.call(&mut store, 4).map_err(|e| format!("{e:?}"))?;
+
+    instance
+        .hot_swap(&mut store, &v2, &imports! {})
+        .map_err(|e| format!("{e:?}"))?;
+    assert!(instance.exports.get_function("push").is_err());
+    let sum: TypedFunction<(), i32> = instance
+        .exports
+        .get_typed_function(&mut store, "sum")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(sum.call(&mut store).map_err(|e| format!("{e:?}"))?, 14);
+
+    // The state doesn't fit into a module with a smaller memory and
+    // another global.
+    let v3 = Module::new(
+        &store,
+        r#"(module
+             (memory 0 0)
+             (global $calls (mut i64) (i64.const 0)))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let incompatibility = instance
+        .check_hot_swap(&mut store, &v3)
+        .expect_err("incompatible module");
+    assert_eq!(
+        incompatibility.mismatches,
+        [
+            HotSwapMismatch::MemoryTooLarge {
+                index: 0,
+                pages: 1,
+                maximum: 0
+            },
+            HotSwapMismatch::GlobalType {
+                index: 0,
+                old: GlobalType::new(Type::I32, Mutability::Var),
+                new: GlobalType::new(Type::I64, Mutability::Var),
+            },
+        ]
+    );
+    assert!(matches!(
+        instance.hot_swap(&mut store, &v3, &imports! {}),
+        Err(HotSwapError::Incompatible(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn module_and_instance_metrics() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        "
+(module
+  (memory (export \"memory\") 1)
+  (table 3 funcref)
+  (func (export \"grow\") (result i32)
+    (memory.grow (i32.const 1))))
+",
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let metrics = module.metrics();
+    assert!(metrics.compile_time.is_some());
+    assert_eq!(metrics.optimized_compile_time, None);
+    assert_eq!(metrics.optimized_functions, 0);
+    assert!(metrics.code_size > 0);
+
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let metrics = instance.metrics(&mut store);
+    assert_eq!(metrics.memory_bytes, WASM_PAGE_SIZE as u64);
+    assert_eq!(metrics.table_elements, 3);
+
+    let grow: TypedFunction<(), i32> = instance
+        .exports
+        .get_typed_function(&mut store, "grow")
+        .map_err(|e| format!("{e:?}"))?;
+    grow.call(&mut store).map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        instance.metrics(&mut store).memory_bytes,
+        2 * WASM_PAGE_SIZE as u64
+    );
+
+    Ok(())
+}
+
+#[cfg(all(feature = "sys", feature = "cranelift"))]
+#[test]
+fn wasm_stack_size_is_configurable() -> Result<(), String> {
+    let wat = r#"
+(module
+  (func $depth (export "depth") (param i32) (result i32)
+    (if (result i32) (i32.eqz (local.get 0))
+      (then (i32.const 0))
+      (else
+        (i32.add (i32.const 1)
+          (call $depth (i32.sub (local.get 0) (i32.const 1))))))))
+"#;
+    let depth_with_stack = |stack_size: usize| -> Result<Result<i32, RuntimeError>, String> {
+        let engine = EngineBuilder::new(Cranelift::default())
+            .set_stack_size(Some(stack_size))
+            .engine();
+        assert_eq!(engine.stack_size(), stack_size);
+        let mut store = Store::new(&engine);
+        let module = Module::new(&store, wat).map_err(|e| format!("{e:?}"))?;
+        let instance =
+            Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+        let depth: TypedFunction<i32, i32> = instance
+            .exports
+            .get_typed_function(&mut store, "depth")
+            .map_err(|e| format!("{e:?}"))?;
+        Ok(depth.call(&mut store, 100_000))
+    };
+
+    let error = depth_with_stack(64 * 1024)?.unwrap_err();
+    assert_eq!(error.to_trap(), Some(wasmer_types::TrapCode::StackOverflow));
+    assert_eq!(
+        depth_with_stack(64 * 1024 * 1024)?.map_err(|e| format!("{e:?}"))?,
+        100_000
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn interrupt_handle_stops_running_guest() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"
+(module
+  (func (export "spin")
+    (loop $again
+      (br $again)))
+  (func (export "answer") (result i32)
+    (i32.const 42)))
+"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let spin: TypedFunction<(), ()> = instance
+        .exports
+        .get_typed_function(&mut store, "spin")
+        .map_err(|e| format!("{e:?}"))?;
+    let answer: TypedFunction<(), i32> = instance
+        .exports
+        .get_typed_function(&mut store, "answer")
+        .map_err(|e| format!("{e:?}"))?;
+
+    let handle = instance.interrupt_handle();
+    let interrupter = {
+        let handle = handle.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            handle.interrupt();
+        })
+    };
+    let error = spin.call(&mut store).unwrap_err();
+    interrupter.join().unwrap();
+    assert_eq!(error.to_trap(), Some(wasmer_types::TrapCode::Interrupt));
+
+    // The calls keep trapping until the handle is reset
+    assert!(handle.is_interrupted());
+    let error = answer.call(&mut store).unwrap_err();
+    assert_eq!(error.to_trap(), Some(wasmer_types::TrapCode::Interrupt));
+    handle.reset();
+    assert_eq!(answer.call(&mut store).map_err(|e| format!("{e:?}"))?, 42);
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn resolvers_provide_imports_during_instantiation() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+             (import "env" "base" (global $base i32))
+             (import "math" "double" (func $double (param i32) (result i32)))
+             (import "math" "triple" (func $triple (param i32) (result i32)))
+             (func (export "run") (result i32)
+               (call $triple (call $double (global.get $base)))))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+
+    let imports = imports! {
+        "env" => { "base" => Global::new(&mut store, Value::I32(7)) },
+    };
+    // Multiplies by the factor named by the field, for any field of `math`.
+    let math = |store: &mut StoreMut, module: &str, field: &str, ty: &ExternType| {
+        let factor = match (module, field) {
+            ("math", "double") => 2,
+            ("math", "triple") => 3,
+            _ => return None,
+        };
+        match ty {
+            ExternType::Function(ty) => Some(Extern::Function(Function::new(
+                store,
+                ty,
+                move |args: &[Value]| Ok(vec![Value::I32(args[0].unwrap_i32() * factor)]),
+            ))),
+            _ => None,
+        }
+    };
+
+    let instance = Instance::new_with_resolver(&mut store, &module, &imports.clone().chain(math))
+        .map_err(|e| format!("{e:?}"))?;
+    let run = instance
+        .exports
+        .get_typed_function::<(), i32>(&store, "run")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(run.call(&mut store).map_err(|e| format!("{e:?}"))?, 42);
+
+    // Without the fallback, `math` is missing.
+    match Instance::new_with_resolver(&mut store, &module, &imports) {
+        Err(InstantiationError::Link(LinkError::Import(module, field, _))) => {
+            assert_eq!((module.as_str(), field.as_str()), ("math", "double"));
+        }
+        _ => return Err("expected a link error".to_string()),
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn missing_imports_are_stubbed_when_allowed() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+             (import "env" "used" (func $used (result i32)))
+             (import "env" "unused" (func $unused (param i64) (result f32)))
+             (func (export "run") (result i32) (call $used))
+             (func (export "fail") (result f32) (call $unused (i64.const 1))))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let mut imports = imports! {
+        "env" => { "used" => Function::new_typed(&mut store, || 7) },
+    };
+    assert!(matches!(
+        Instance::new(&mut store, &module, &imports),
+        Err(InstantiationError::Link(_))
+    ));
+
+    imports.allow_missing();
+    let instance = Instance::new(&mut store, &module, &imports).map_err(|e| format!("{e:?}"))?;
+    let run = instance
+        .exports
+        .get_typed_function::<(), i32>(&store, "run")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(run.call(&mut store).map_err(|e| format!("{e:?}"))?, 7);
+    let fail = instance
+        .exports
+        .get_typed_function::<(), f32>(&store, "fail")
+        .map_err(|e| format!("{e:?}"))?;
+    let error = fail.call(&mut store).unwrap_err();
+    assert_eq!(error.message(), "called the missing import `env`.`unused`");
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn host_memories_are_shared_between_instances() -> Result<(), String> {
+    let mut store = Store::default();
+    let memory =
+        Memory::new(&mut store, MemoryType::new(1, None, false)).map_err(|e| format!("{e:?}"))?;
+    let imports = imports! {
+        "env" => { "memory" => memory.clone() },
+    };
+    let writer = Module::new(
+        &store,
+        r#"(module
+             (import "env" "memory" (memory $memory 1))
+             (export "memory" (memory $memory))
+             (func (export "write") (param i32 i32)
+               (i32.store8 (local.get 0) (local.get 1))))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let reader = Module::new(
+        &store,
+        r#"(module
+             (import "env" "memory" (memory 1))
+             (func (export "read") (param i32) (result i32)
+               (i32.load8_u (local.get 0))))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let writer = Instance::new(&mut store, &writer, &imports).map_err(|e| format!("{e:?}"))?;
+    let reader = Instance::new(&mut store, &reader, &imports).map_err(|e| format!("{e:?}"))?;
+
+    // The memory the writer exports is the one of the host.
+    let exported = writer
+        .exports
+        .get_memory("memory")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(exported, &memory);
+
+    let write = writer
+        .exports
+        .get_typed_function::<(i32, i32), ()>(&store, "write")
+        .map_err(|e| format!("{e:?}"))?;
+    let read = reader
+        .exports
+        .get_typed_function::<i32, i32>(&store, "read")
+        .map_err(|e| format!("{e:?}"))?;
+    write
+        .call(&mut store, 100, 42)
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        read.call(&mut store, 100).map_err(|e| format!("{e:?}"))?,
+        42
+    );
+    memory
+        .view(&store)
+        .write_u8(200, 7)
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(read.call(&mut store, 200).map_err(|e| format!("{e:?}"))?, 7);
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn host_functions_can_be_stored_in_guest_tables() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+             (type $unary (func (param i32) (result i32)))
+             (table (export "table") 1 funcref)
+             (elem (i32.const 0) $double)
+             (func $double (type $unary) (i32.mul (local.get 0) (i32.const 2)))
+             (func (export "call") (param i32 i32) (result i32)
+               (call_indirect (type $unary) (local.get 1) (local.get 0))))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let table = instance
+        .exports
+        .get_table("table")
+        .map_err(|e| format!("{e:?}"))?
+        .clone();
+    let call: TypedFunction<(i32, i32), i32> = instance
+        .exports
+        .get_typed_function(&mut store, "call")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        call.call(&mut store, 0, 21).map_err(|e| format!("{e:?}"))?,
+        42
+    );
+
+    // Patch the guest function with a host one, and add another slot.
+    let increment = Function::new_typed(&mut store, |x: i32| x + 1);
+    table
+        .set(&mut store, 0, Value::FuncRef(Some(increment.clone())))
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        call.call(&mut store, 0, 21).map_err(|e| format!("{e:?}"))?,
+        22
+    );
+    assert_eq!(
+        table
+            .grow(&mut store, 1, Value::FuncRef(Some(increment)))
+            .map_err(|e| format!("{e:?}"))?,
+        1
+    );
+    assert_eq!(table.size(&store), 2);
+    assert_eq!(
+        call.call(&mut store, 1, 1).map_err(|e| format!("{e:?}"))?,
+        2
+    );
+
+    // Functions of another type trap when called with the wrong one.
+    let nullary = Function::new_typed(&mut store, || 0);
+    table
+        .set(&mut store, 1, Value::FuncRef(Some(nullary)))
+        .map_err(|e| format!("{e:?}"))?;
+    assert!(call.call(&mut store, 1, 1).is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn hosts_read_and_update_guest_globals() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+             (import "env" "epoch" (global $epoch (mut i64)))
+             (global $debug (export "debug") (mut i32) (i32.const 0))
+             (global (export "version") i32 (i32.const 3))
+             (func (export "tick") (result i32)
+               (global.set $epoch (i64.add (global.get $epoch) (i64.const 1)))
+               (global.get $debug)))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let epoch = Global::new_mut(&mut store, Value::I64(10));
+    let imports = imports! { "env" => { "epoch" => epoch.clone() } };
+    let instance = Instance::new(&mut store, &module, &imports).map_err(|e| format!("{e:?}"))?;
+    let debug = instance
+        .exports
+        .get_global("debug")
+        .map_err(|e| format!("{e:?}"))?
+        .clone();
+    let version = instance
+        .exports
+        .get_global("version")
+        .map_err(|e| format!("{e:?}"))?
+        .clone();
+    let tick: TypedFunction<(), i32> = instance
+        .exports
+        .get_typed_function(&mut store, "tick")
+        .map_err(|e| format!("{e:?}"))?;
+
+    assert_eq!(tick.call(&mut store).map_err(|e| format!("{e:?}"))?, 0);
+    debug
+        .set(&mut store, Value::I32(1))
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(tick.call(&mut store).map_err(|e| format!("{e:?}"))?, 1);
+    assert_eq!(epoch.get(&mut store), Value::I64(12));
+
+    assert_eq!(version.get(&mut store), Value::I32(3));
+    assert!(version.set(&mut store, Value::I32(4)).is_err());
+    assert!(debug.set(&mut store, Value::I64(0)).is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn exports_can_be_enumerated_with_their_types() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+             (func (export "run") (param i64) (result i32) (i32.const 0))
+             (memory (export "memory") 1)
+             (global (export "version") i32 (i32.const 1))
+             (func (export "init")))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+
+    let types = instance.exports.types(&store).collect::<Vec<_>>();
+    assert_eq!(types, module.exports().collect::<Vec<_>>());
+    assert_eq!(
+        types.iter().map(|export| export.name()).collect::<Vec<_>>(),
+        ["run", "memory", "version", "init"]
+    );
+    assert_eq!(
+        types[0].ty(),
+        &ExternType::Function(FunctionType::new([Type::I64], [Type::I32]))
+    );
+
+    let functions = instance
+        .exports
+        .iter()
+        .functions()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(functions, ["run", "init"]);
+    assert_eq!(instance.exports.iter().memories().count(), 1);
+    assert_eq!(instance.exports.iter().tables().count(), 0);
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn modules_are_shared_by_the_stores_of_an_engine() -> Result<(), String> {
+    let store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+             (global $count (mut i32) (i32.const 0))
+             (func (export "incr") (param i32) (result i32)
+               (global.set $count (i32.add (global.get $count) (local.get 0)))
+               (global.get $count)))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let engine = store.engine().clone();
+
+    // Every thread instantiates the module compiled once in a store of its
+    // own, whose state is isolated from the others.
+    let threads = (1..=4)
+        .map(|step| {
+            let module = module.clone();
+            let engine = engine.clone();
+            std::thread::spawn(move || -> Result<i32, String> {
+                let mut store = Store::new(engine);
+                let instance = Instance::new(&mut store, &module, &imports! {})
+                    .map_err(|e| format!("{e:?}"))?;
+                let incr: TypedFunction<i32, i32> = instance
+                    .exports
+                    .get_typed_function(&mut store, "incr")
+                    .map_err(|e| format!("{e:?}"))?;
+                for _ in 0..9 {
+                    incr.call(&mut store, step).map_err(|e| format!("{e:?}"))?;
+                }
+                incr.call(&mut store, step).map_err(|e| format!("{e:?}"))
+            })
+        })
+        .collect::<Vec<_>>();
+    for (step, thread) in (1..=4).zip(threads) {
+        assert_eq!(thread.join().unwrap()?, 10 * step);
+    }
+
+    // The code of a module belongs to its engine.
+    let mut other = Store::default();
+    assert!(matches!(
+        Instance::new(&mut other, &module, &imports! {}),
+        Err(InstantiationError::DifferentEngines)
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn resource_limiters_enforce_quotas() -> Result<(), String> {
+    /// Allows 4 pages of memory and a single instance.
+    struct Quota {
+        instances: u32,
+    }
+
+    impl ResourceLimiter for Quota {
+        fn instance_creating(&mut self) -> bool {
+            self.instances += 1;
+            self.instances <= 1
+        }
+
+        fn memory_growing(
+            &mut self,
+            _current: Pages,
+            desired: Pages,
+            _maximum: Option<Pages>,
+        ) -> bool {
+            desired <= Pages(4)
+        }
+
+        fn table_growing(&mut self, _current: u32, desired: u32, _maximum: Option<u32>) -> bool {
+            desired <= 10
+        }
+    }
+
+    let mut store = Store::default();
+    store.set_limiter(Some(Box::new(Quota { instances: 0 })));
+    let module = Module::new(
+        &store,
+        r#"(module
+             (memory (export "memory") 1)
+             (table (export "table") 1 funcref)
+             (func (export "grow") (param i32) (result i32)
+               (memory.grow (local.get 0))))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+
+    // The guest and the host share the quota of the memory.
+    let grow: TypedFunction<i32, i32> = instance
+        .exports
+        .get_typed_function(&mut store, "grow")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(grow.call(&mut store, 2).map_err(|e| format!("{e:?}"))?, 1);
+    assert_eq!(grow.call(&mut store, 2).map_err(|e| format!("{e:?}"))?, -1);
+    let memory = instance
+        .exports
+        .get_memory("memory")
+        .map_err(|e| format!("{e:?}"))?;
+    assert!(matches!(
+        memory.grow(&mut store, 2),
+        Err(MemoryError::CouldNotGrow { .. })
+    ));
+    assert_eq!(memory.grow(&mut store, 1), Ok(Pages(3)));
+
+    let table = instance
+        .exports
+        .get_table("table")
+        .map_err(|e| format!("{e:?}"))?;
+    assert!(table.grow(&mut store, 10, Value::FuncRef(None)).is_err());
+    assert_eq!(
+        table
+            .grow(&mut store, 9, Value::FuncRef(None))
+            .map_err(|e| format!("{e:?}"))?,
+        1
+    );
+
+    assert!(matches!(
+        Instance::new(&mut store, &module, &imports! {}),
+        Err(InstantiationError::ResourceLimitExceeded)
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn headless_stores_run_precompiled_modules() -> Result<(), String> {
+    let bytes = Module::new(
+        &Store::default(),
+        r#"(module
+             (func (export "double") (param i32) (result i32)
+               (i32.mul (local.get 0) (i32.const 2))))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?
+    .serialize()
+    .map_err(|e| format!("{e:?}"))?;
+
+    let mut store = Store::new(EngineBuilder::headless());
+    assert!(Module::new(&store, "(module)").is_err());
+    let module = unsafe { Module::deserialize(&store, bytes) }.map_err(|e| format!("{e:?}"))?;
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let double: TypedFunction<i32, i32> = instance
+        .exports
+        .get_typed_function(&mut store, "double")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        double.call(&mut store, 21).map_err(|e| format!("{e:?}"))?,
+        42
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn traps_carry_their_code_and_offset() -> Result<(), String> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+             (func (export "div") (param i32 i32) (result i32)
+               (i32.div_s (local.get 0) (local.get 1)))
+             (func (export "abort")
+               unreachable))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+
+    let div: TypedFunction<(i32, i32), i32> = instance
+        .exports
+        .get_typed_function(&mut store, "div")
+        .map_err(|e| format!("{e:?}"))?;
+    let err = div.call(&mut store, 1, 0).unwrap_err();
+    assert_eq!(err.trap_code(), Some(TrapCode::IntegerDivisionByZero));
+    assert!(err.trap_offset().is_some());
+
+    let abort: TypedFunction<(), ()> = instance
+        .exports
+        .get_typed_function(&mut store, "abort")
+        .map_err(|e| format!("{e:?}"))?;
+    let err = abort.call(&mut store).unwrap_err();
+    assert_eq!(err.trap_code(), Some(TrapCode::UnreachableCodeReached));
+    assert_ne!(
+        err.trap_offset(),
+        div.call(&mut store, 1, 0).unwrap_err().trap_offset()
+    );
+
+    assert_eq!(RuntimeError::new("host error").trap_code(), None);
+
+    Ok(())
+}
+
+/// Calls `outer`, which calls `inner`, which calls a host function
+/// capturing the stack trace, and returns the function indices of the
+/// captured frames.
+#[cfg(feature = "sys")]
+fn capture_stack_trace_from_a_host_function(mut store: Store) -> Result<Vec<u32>, String> {
+    let module = Module::new(
+        &store,
+        r#"(module
+             (import "env" "capture" (func $capture))
+             (func $inner
+               (call $capture))
+             (func (export "outer")
+               (call $inner)))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let env = FunctionEnv::new(&mut store, Vec::new());
+    let capture =
+        Function::new_typed_with_env(&mut store, &env, |mut env: FunctionEnvMut<Vec<u32>>| {
+            let trace = RuntimeStackTrace::capture();
+            *env.data_mut() = trace.frames().iter().map(FrameInfo::func_index).collect();
+        });
+    let imports = imports! {
+        "env" => {
+            "capture" => capture,
+        },
+    };
+    let instance = Instance::new(&mut store, &module, &imports).map_err(|e| format!("{e:?}"))?;
+    let outer: TypedFunction<(), ()> = instance
+        .exports
+        .get_typed_function(&store, "outer")
+        .map_err(|e| format!("{e:?}"))?;
+    outer.call(&mut store).map_err(|e| format!("{e:?}"))?;
+
+    // Outside of WebAssembly, there is nothing to capture.
+    assert!(RuntimeStackTrace::capture().is_empty());
+
+    Ok(env.as_ref(&store).clone())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn host_functions_capture_the_stack_trace_of_their_callers() -> Result<(), String> {
+    // The import comes first in the function index space.
+    assert_eq!(
+        capture_stack_trace_from_a_host_function(Store::default())?,
+        vec![1, 2]
+    );
+    Ok(())
+}
+
+#[cfg(all(feature = "sys", feature = "interpreter"))]
+#[test]
+fn host_functions_capture_the_stack_trace_of_interpreted_callers() -> Result<(), String> {
+    assert_eq!(
+        capture_stack_trace_from_a_host_function(Store::new(Interpreter::default()))?,
+        vec![1, 2]
+    );
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn call_hooks_see_host_and_wasm_transitions() -> Result<(), String> {
+    use std::sync::{Arc, Mutex};
+
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+             (import "env" "host" (func $host (param i32) (result i32)))
+             (func (export "run") (param i32) (result i32)
+               (call $host (local.get 0))))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let host = Function::new_typed(&mut store, |x: i32| x + 1);
+    let imports = imports! {
+        "env" => {
+            "host" => host,
+        },
+    };
+    let instance = Instance::new(&mut store, &module, &imports).map_err(|e| format!("{e:?}"))?;
+    let run: TypedFunction<i32, i32> = instance
+        .exports
+        .get_typed_function(&store, "run")
+        .map_err(|e| format!("{e:?}"))?;
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    store.set_call_hook(Some(Box::new(
+        move |hook: CallHook, info: &CallHookInfo| -> Result<(), RuntimeError> {
+            recorded.lock().unwrap().push((hook, *info));
+            Ok(())
+        },
+    )));
+    assert_eq!(run.call(&mut store, 41).map_err(|e| format!("{e:?}"))?, 42);
+
+    let run_info = CallHookInfo {
+        // The import comes first in the function index space.
+        func_index: Some(1),
+        instance: Some(instance.id(&store)),
+    };
+    let host_info = CallHookInfo {
+        func_index: None,
+        instance: None,
+    };
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            (CallHook::CallingWasm, run_info),
+            (CallHook::CallingHost, host_info),
+            (CallHook::ReturningFromHost, host_info),
+            (CallHook::ReturningFromWasm, run_info),
+        ]
+    );
+
+    // A hook can refuse calls before they enter the instance.
+    store.set_call_hook(Some(Box::new(|hook: CallHook, _: &CallHookInfo| {
+        if hook == CallHook::CallingWasm {
+            return Err(RuntimeError::new("reentrant call"));
+        }
+        Ok(())
+    })));
+    let error = run.call(&mut store, 1).unwrap_err();
+    assert_eq!(error.message(), "reentrant call");
+
+    store.set_call_hook(None);
+    assert_eq!(run.call(&mut store, 1).map_err(|e| format!("{e:?}"))?, 2);
+
+    Ok(())
+}
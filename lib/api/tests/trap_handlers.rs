@@ -0,0 +1,155 @@
+//! The trap handlers of wasmer next to the signal handlers of an embedder.
+//!
+//! Signal handlers are global to the process, so the scenarios run one
+//! after the other from a single test.
+#![cfg(all(feature = "sys", feature = "cranelift", target_os = "linux"))]
+
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use wasmer::*;
+
+/// The signals the handler of the embedder got.
+static EMBEDDER_SIGNALS: AtomicUsize = AtomicUsize::new(0);
+/// The signals the handler of the embedder got from Wasm code.
+static EMBEDDER_WASM_TRAPS: AtomicUsize = AtomicUsize::new(0);
+
+/// The `SIGSEGV` handler of the embedder, which counts the signals it
+/// gets and leaves the Wasm traps to wasmer.
+unsafe extern "C" fn embedder_handler(
+    signum: libc::c_int,
+    siginfo: *mut libc::siginfo_t,
+    context: *mut libc::c_void,
+) {
+    EMBEDDER_SIGNALS.fetch_add(1, SeqCst);
+    if is_wasm_trap(context) {
+        EMBEDDER_WASM_TRAPS.fetch_add(1, SeqCst);
+    }
+    handle_wasm_trap(signum, siginfo, context);
+}
+
+/// Installs the handler of the embedder, returning the one it replaced.
+unsafe fn install_embedder_handler() -> libc::sigaction {
+    EMBEDDER_SIGNALS.store(0, SeqCst);
+    EMBEDDER_WASM_TRAPS.store(0, SeqCst);
+    let mut handler: libc::sigaction = mem::zeroed();
+    handler.sa_flags = libc::SA_SIGINFO | libc::SA_NODEFER | libc::SA_ONSTACK;
+    handler.sa_sigaction = embedder_handler as usize;
+    libc::sigemptyset(&mut handler.sa_mask);
+    let mut previous: libc::sigaction = mem::zeroed();
+    assert_eq!(libc::sigaction(libc::SIGSEGV, &handler, &mut previous), 0);
+    previous
+}
+
+unsafe fn restore_handler(previous: &libc::sigaction) {
+    assert_eq!(libc::sigaction(libc::SIGSEGV, previous, ptr::null_mut()), 0);
+}
+
+fn current_handler() -> usize {
+    unsafe {
+        let mut current: libc::sigaction = mem::zeroed();
+        assert_eq!(libc::sigaction(libc::SIGSEGV, ptr::null(), &mut current), 0);
+        current.sa_sigaction
+    }
+}
+
+fn scoped_engine() -> Engine {
+    EngineBuilder::new(Cranelift::default())
+        .set_scoped_trap_handlers(true)
+        .engine()
+}
+
+/// Runs Wasm code accessing its memory out of bounds, which raises a
+/// `SIGSEGV`.
+fn access_out_of_bounds(engine: &Engine) -> Option<TrapCode> {
+    let mut store = Store::new(engine);
+    let module = Module::new(
+        &store,
+        r#"(module
+             (memory 1)
+             (func (export "load") (result i32)
+               (i32.load (i32.const 0x20000))))"#,
+    )
+    .unwrap();
+    let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+    let load: TypedFunction<(), i32> = instance.exports.get_typed_function(&store, "load").unwrap();
+    load.call(&mut store).unwrap_err().to_trap()
+}
+
+/// The handlers are installed over the one of the embedder while an
+/// engine scoping them lives, and removed once all its clones are gone.
+fn scoped_install_and_uninstall() {
+    let previous = unsafe { install_embedder_handler() };
+
+    let engine = scoped_engine();
+    assert!(engine.scopes_trap_handlers());
+    assert_ne!(current_handler(), embedder_handler as usize);
+    assert_eq!(
+        access_out_of_bounds(&engine),
+        Some(TrapCode::HeapAccessOutOfBounds)
+    );
+    // Wasm traps don't reach the handler of the embedder.
+    assert_eq!(EMBEDDER_SIGNALS.load(SeqCst), 0);
+
+    let clone = engine.clone();
+    drop(engine);
+    assert_ne!(current_handler(), embedder_handler as usize);
+    drop(clone);
+    assert_eq!(current_handler(), embedder_handler as usize);
+
+    unsafe { restore_handler(&previous) };
+}
+
+/// The signals that aren't Wasm traps go on to the handler of the
+/// embedder installed before.
+fn chaining_to_the_previous_handler() {
+    let previous = unsafe { install_embedder_handler() };
+
+    let engine = scoped_engine();
+    unsafe { libc::raise(libc::SIGSEGV) };
+    assert_eq!(EMBEDDER_SIGNALS.load(SeqCst), 1);
+    assert_eq!(EMBEDDER_WASM_TRAPS.load(SeqCst), 0);
+    assert_eq!(
+        access_out_of_bounds(&engine),
+        Some(TrapCode::HeapAccessOutOfBounds)
+    );
+    assert_eq!(EMBEDDER_SIGNALS.load(SeqCst), 1);
+    drop(engine);
+
+    unsafe { restore_handler(&previous) };
+}
+
+/// A handler installed over the ones of wasmer tells the Wasm traps
+/// apart with `is_wasm_trap`, and has wasmer handle them.
+fn embedder_handler_running_first() {
+    let engine = scoped_engine();
+    let previous = unsafe { install_embedder_handler() };
+
+    assert_eq!(
+        access_out_of_bounds(&engine),
+        Some(TrapCode::HeapAccessOutOfBounds)
+    );
+    assert_eq!(EMBEDDER_SIGNALS.load(SeqCst), 1);
+    assert_eq!(EMBEDDER_WASM_TRAPS.load(SeqCst), 1);
+    unsafe { libc::raise(libc::SIGSEGV) };
+    assert_eq!(EMBEDDER_SIGNALS.load(SeqCst), 2);
+    assert_eq!(EMBEDDER_WASM_TRAPS.load(SeqCst), 1);
+
+    // The handlers of wasmer can't be removed from under the one of the
+    // embedder, which may forward signals to them.
+    drop(engine);
+    assert_eq!(current_handler(), embedder_handler as usize);
+    unsafe { restore_handler(&previous) };
+    assert_eq!(current_handler(), previous.sa_sigaction);
+
+    // Once on top again, they are removed with the last user.
+    drop(TrapHandlers::install());
+    assert_ne!(current_handler(), previous.sa_sigaction);
+}
+
+#[test]
+fn trap_handlers_coexist_with_the_embedder() {
+    scoped_install_and_uninstall();
+    chaining_to_the_previous_handler();
+    embedder_handler_running_first();
+}
@@ -80,11 +80,47 @@ pub unsafe extern "C" fn wasm_instance_new(
             return None;
         }
 
+        Err(e @ InstantiationError::DifferentArchOS(_)) => {
+            crate::error::update_last_error(e);
+
+            return None;
+        }
+
         Err(e @ InstantiationError::DifferentStores) => {
             crate::error::update_last_error(e);
 
             return None;
         }
+
+        Err(e @ InstantiationError::DifferentEngines) => {
+            crate::error::update_last_error(e);
+
+            return None;
+        }
+
+        Err(e @ InstantiationError::Quarantined(_)) => {
+            crate::error::update_last_error(e);
+
+            return None;
+        }
+
+        Err(e @ InstantiationError::ResourceLimitExceeded) => {
+            crate::error::update_last_error(e);
+
+            return None;
+        }
+
+        Err(e @ InstantiationError::Contract(_)) => {
+            crate::error::update_last_error(e);
+
+            return None;
+        }
+
+        Err(e @ InstantiationError::HostEnvInitialization(_)) => {
+            crate::error::update_last_error(e);
+
+            return None;
+        }
     };
 
     Some(Box::new(wasm_instance_t {
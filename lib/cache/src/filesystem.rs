@@ -1,9 +1,10 @@
 #![cfg_attr(not(feature = "filesystem"), allow(unused))]
 use crate::cache::Cache;
 use crate::hash::Hash;
-use std::fs::{create_dir_all, File};
-use std::io::{self, Write};
-use std::path::PathBuf;
+use filetime::FileTime;
+use std::fs::{self, create_dir_all};
+use std::io;
+use std::path::{Path, PathBuf};
 use wasmer::{DeserializeError, Module, SerializeError, Store};
 
 /// Representation of a directory that contains compiled wasm artifacts.
@@ -11,6 +12,9 @@ use wasmer::{DeserializeError, Module, SerializeError, Store};
 /// The `FileSystemCache` type implements the [`Cache`] trait, which allows it to be used
 /// generically when some sort of cache is required.
 ///
+/// The cache is unbounded by default. With [`FileSystemCache::set_max_size`],
+/// the least recently used modules are evicted when it grows over a budget.
+///
 /// # Usage
 ///
 /// ```
@@ -34,6 +38,7 @@ use wasmer::{DeserializeError, Module, SerializeError, Store};
 pub struct FileSystemCache {
     path: PathBuf,
     ext: Option<String>,
+    max_size: Option<u64>,
 }
 
 #[cfg(feature = "filesystem")]
@@ -45,7 +50,11 @@ impl FileSystemCache {
             let metadata = path.metadata()?;
             if metadata.is_dir() {
                 if !metadata.permissions().readonly() {
-                    Ok(Self { path, ext: None })
+                    Ok(Self {
+                        path,
+                        ext: None,
+                        max_size: None,
+                    })
                 } else {
                     // This directory is readonly.
                     Err(io::Error::new(
@@ -72,7 +81,11 @@ impl FileSystemCache {
                     format!("failed to create cache directory: {}", path.display()),
                 ))
             } else {
-                Ok(Self { path, ext: None })
+                Ok(Self {
+                    path,
+                    ext: None,
+                    max_size: None,
+                })
             }
         }
     }
@@ -84,6 +97,76 @@ impl FileSystemCache {
     pub fn set_cache_extension(&mut self, ext: Option<impl ToString>) {
         self.ext = ext.map(|ext| ext.to_string());
     }
+
+    /// Bound the total size of the cached modules to `max_size` bytes, or
+    /// remove the bound with `None`.
+    ///
+    /// The modification time of the cached files records when they were
+    /// last loaded or stored. Storing a module evicts the least recently
+    /// used ones until the cache fits in the budget again, considering only
+    /// the files with the extension of this cache.
+    pub fn set_max_size(&mut self, max_size: Option<u64>) {
+        self.max_size = max_size;
+    }
+
+    /// The path of the file caching the module with the given key.
+    pub(crate) fn path_of(&self, key: Hash) -> PathBuf {
+        let filename = if let Some(ref ext) = self.ext {
+            format!("{}.{}", key.to_string(), ext)
+        } else {
+            key.to_string()
+        };
+        self.path.join(filename)
+    }
+
+    /// Writes the serialized module with the given key, evicting other
+    /// modules if the cache gets over its budget.
+    pub(crate) fn write(&self, key: Hash, bytes: &[u8]) -> io::Result<()> {
+        let path = self.path_of(key);
+        fs::write(&path, bytes)?;
+        match self.max_size {
+            Some(max_size) => self.evict(max_size, &path),
+            None => Ok(()),
+        }
+    }
+
+    /// Evicts the least recently used modules, other than `keep`, until the
+    /// cache fits in `max_size`.
+    fn evict(&self, max_size: u64, keep: &Path) -> io::Result<()> {
+        let mut total = 0;
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.path)? {
+            let entry = entry?;
+            let path = entry.path();
+            let is_cached = match (&self.ext, path.extension()) {
+                (Some(ext), Some(found)) => found == ext.as_str(),
+                (None, None) => true,
+                _ => false,
+            };
+            let metadata = entry.metadata()?;
+            if !is_cached || !metadata.is_file() {
+                continue;
+            }
+            total += metadata.len();
+            if path != keep {
+                files.push((metadata.modified()?, metadata.len(), path));
+            }
+        }
+        files.sort_by_key(|(modified, _, _)| *modified);
+        for (_, len, path) in files {
+            if total <= max_size {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                // Another process evicted it first
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            total -= len;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(feature = "filesystem")]
@@ -92,27 +175,60 @@ impl Cache for FileSystemCache {
     type SerializeError = SerializeError;
 
     unsafe fn load(&self, store: &Store, key: Hash) -> Result<Module, Self::DeserializeError> {
-        let filename = if let Some(ref ext) = self.ext {
-            format!("{}.{}", key.to_string(), ext)
-        } else {
-            key.to_string()
-        };
-        let path = self.path.join(filename);
-        Module::deserialize_from_file(store, path)
+        let path = self.path_of(key);
+        let module = Module::deserialize_from_file(store, &path)?;
+        // Mark the module as recently used, failing to do so only makes it
+        // evicted sooner
+        let _ = filetime::set_file_mtime(&path, FileTime::now());
+        Ok(module)
     }
 
     fn store(&mut self, key: Hash, module: &Module) -> Result<(), Self::SerializeError> {
-        let filename = if let Some(ref ext) = self.ext {
-            format!("{}.{}", key.to_string(), ext)
-        } else {
-            key.to_string()
-        };
-        let path = self.path.join(filename);
-        let mut file = File::create(path)?;
-
         let buffer = module.serialize()?;
-        file.write_all(&buffer)?;
+        self.write(key, &buffer)?;
 
         Ok(())
     }
 }
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+    use tempfile::TempDir;
+    use wasmer_compiler_singlepass::Singlepass;
+
+    fn set_age(cache: &FileSystemCache, key: Hash, seconds: u64) {
+        let time = SystemTime::now() - Duration::from_secs(seconds);
+        filetime::set_file_mtime(cache.path_of(key), FileTime::from_system_time(time)).unwrap();
+    }
+
+    #[test]
+    fn least_recently_used_modules_are_evicted() {
+        let tmp_dir = TempDir::new().unwrap();
+        let store = Store::new(Singlepass::default());
+        let module = Module::new(&store, b"\0asm\x01\0\0\0").unwrap();
+        let size = module.serialize().unwrap().len() as u64;
+        let mut cache = FileSystemCache::new(tmp_dir.path()).unwrap();
+        cache.set_cache_extension(Some("wasmu"));
+        cache.set_max_size(Some(size * 2));
+        let keys = [[1; 32], [2; 32], [3; 32]].map(Hash::new);
+
+        cache.store(keys[0], &module).unwrap();
+        cache.store(keys[1], &module).unwrap();
+        set_age(&cache, keys[0], 20);
+        set_age(&cache, keys[1], 10);
+        // Files of other caches in the directory are left alone
+        fs::write(tmp_dir.path().join("unrelated"), vec![0; size as usize]).unwrap();
+
+        // Loading the oldest module makes the other one the least recently
+        // used
+        unsafe { cache.load(&store, keys[0]) }.unwrap();
+        cache.store(keys[2], &module).unwrap();
+
+        assert!(cache.path_of(keys[0]).exists());
+        assert!(!cache.path_of(keys[1]).exists());
+        assert!(cache.path_of(keys[2]).exists());
+        assert!(tmp_dir.path().join("unrelated").exists());
+    }
+}
@@ -1,29 +1,152 @@
 use crate::DeserializeError;
+use memmap2::Mmap;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
 use std::str::FromStr;
 use std::string::ToString;
+use wasmer::Engine;
+
+/// Inputs from this size on are hashed with BLAKE3 on multiple threads.
+const BLAKE3_PARALLEL_THRESHOLD: usize = 128 * 1024;
+
+/// The algorithm used to compute a [`Hash`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    /// BLAKE3, the default.
+    Blake3,
+    /// SHA-256.
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// The prefix of the hexadecimal representation of hashes computed
+    /// with this algorithm.
+    ///
+    /// BLAKE3 hashes aren't prefixed, so that keys generated before other
+    /// algorithms were supported stay valid.
+    fn prefix(self) -> &'static str {
+        match self {
+            Self::Blake3 => "",
+            Self::Sha256 => "sha256-",
+        }
+    }
+}
+
+impl Default for HashAlgorithm {
+    fn default() -> Self {
+        Self::Blake3
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Blake3 => write!(f, "blake3"),
+            Self::Sha256 => write!(f, "sha256"),
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blake3" => Ok(Self::Blake3),
+            "sha256" => Ok(Self::Sha256),
+            _ => Err(format!(
+                "unknown hash algorithm `{}` (expected `blake3` or `sha256`)",
+                s
+            )),
+        }
+    }
+}
 
 /// A hash used as a key when loading and storing modules in a
 /// [`Cache`].
+///
+/// Hashes computed with different algorithms never compare equal, and
+/// have distinct string representations, so they can address contents
+/// side by side.
+///
+/// [`Cache`]: crate::Cache
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-// Hash is made up of a 32 byte array
-pub struct Hash([u8; 32]);
+pub struct Hash {
+    algorithm: HashAlgorithm,
+    // Hash is made up of a 32 byte array
+    bytes: [u8; 32],
+}
 
 impl Hash {
-    /// Creates a new instance from 32 raw bytes.
+    /// Creates a new instance from 32 raw bytes of a BLAKE3 hash.
     /// Does not perform any hashing. In order to create a hash from data,
     /// use `Hash::generate`.
     pub fn new(bytes: [u8; 32]) -> Self {
-        Self(bytes)
+        Self::with_algorithm(HashAlgorithm::Blake3, bytes)
+    }
+
+    /// Creates a new instance from 32 raw bytes of a hash computed with
+    /// `algorithm`.
+    pub fn with_algorithm(algorithm: HashAlgorithm, bytes: [u8; 32]) -> Self {
+        Self { algorithm, bytes }
     }
 
     /// Creates a new hash from a slice of bytes.
     pub fn generate(bytes: &[u8]) -> Self {
-        let hash = blake3::hash(bytes);
-        Self::new(hash.into())
+        Self::generate_with(HashAlgorithm::default(), bytes)
+    }
+
+    /// Creates a new hash from a slice of bytes, using `algorithm`.
+    pub fn generate_with(algorithm: HashAlgorithm, bytes: &[u8]) -> Self {
+        let mut hasher = Hasher::new(algorithm);
+        hasher.update(bytes);
+        hasher.finalize()
+    }
+
+    /// Creates a new hash from the contents of the file at `path`, using
+    /// `algorithm`.
+    ///
+    /// Regular files are memory-mapped rather than read, which saves
+    /// copying large modules around. Other files, such as FIFOs, are read.
+    pub fn generate_from_file(
+        algorithm: HashAlgorithm,
+        path: impl AsRef<Path>,
+    ) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        if file.metadata()?.is_file() {
+            let contents = unsafe { Mmap::map(&file)? };
+            Ok(Self::generate_with(algorithm, &contents))
+        } else {
+            let mut hasher = Hasher::new(algorithm);
+            io::copy(&mut file, &mut hasher)?;
+            Ok(hasher.finalize())
+        }
+    }
+
+    /// Derives the key of the artifact `engine` compiles from the module
+    /// with this hash.
+    ///
+    /// The key changes with the [`Engine::deterministic_id`] of the
+    /// engine, so that the artifacts of engines compiling differently,
+    /// which must not be loaded by one another, don't share keys.
+    pub fn for_engine(self, engine: &Engine) -> Self {
+        let mut hasher = Hasher::new(self.algorithm);
+        hasher
+            .update(&self.bytes)
+            .update(engine.deterministic_id().as_bytes());
+        hasher.finalize()
+    }
+
+    /// The algorithm the hash was computed with.
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
     }
 
     pub(crate) fn to_array(self) -> [u8; 32] {
-        self.0
+        self.bytes
     }
 }
 
@@ -31,7 +154,11 @@ impl ToString for Hash {
     /// Create the hexadecimal representation of the
     /// stored hash.
     fn to_string(&self) -> String {
-        hex::encode(&self.to_array())
+        format!(
+            "{}{}",
+            self.algorithm.prefix(),
+            hex::encode(&self.to_array())
+        )
     }
 }
 
@@ -39,6 +166,10 @@ impl FromStr for Hash {
     type Err = DeserializeError;
     /// Create hash from hexadecimal representation
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algorithm, s) = match s.strip_prefix(HashAlgorithm::Sha256.prefix()) {
+            Some(s) => (HashAlgorithm::Sha256, s),
+            None => (HashAlgorithm::Blake3, s),
+        };
         let bytes = hex::decode(s).map_err(|e| {
             DeserializeError::Generic(format!(
                 "Could not decode prehashed key as hexadecimal: {}",
@@ -51,9 +182,83 @@ impl FromStr for Hash {
             ));
         }
         use std::convert::TryInto;
-        Ok(Self(bytes[0..32].try_into().map_err(|e| {
-            DeserializeError::Generic(format!("Could not get first 32 bytes: {}", e))
-        })?))
+        Ok(Self::with_algorithm(
+            algorithm,
+            bytes[0..32].try_into().map_err(|e| {
+                DeserializeError::Generic(format!("Could not get first 32 bytes: {}", e))
+            })?,
+        ))
+    }
+}
+
+/// Computes a [`Hash`] from data fed incrementally, for instance while
+/// streaming a module from the network.
+///
+/// ```
+/// use std::io::Write;
+/// use wasmer_cache::{Hash, HashAlgorithm, Hasher};
+///
+/// let mut hasher = Hasher::new(HashAlgorithm::Sha256);
+/// hasher.update(b"\0asm");
+/// hasher.write_all(&[1, 0, 0, 0]).unwrap();
+/// assert_eq!(
+///     hasher.finalize(),
+///     Hash::generate_with(HashAlgorithm::Sha256, b"\0asm\x01\0\0\0")
+/// );
+/// ```
+#[derive(Clone)]
+pub struct Hasher(HasherState);
+
+#[derive(Clone)]
+enum HasherState {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    /// Creates a hasher computing a hash with `algorithm`.
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        Self(match algorithm {
+            HashAlgorithm::Blake3 => HasherState::Blake3(Box::new(blake3::Hasher::new())),
+            HashAlgorithm::Sha256 => HasherState::Sha256(Sha256::new()),
+        })
+    }
+
+    /// Feeds more data to the hasher.
+    pub fn update(&mut self, bytes: &[u8]) -> &mut Self {
+        match &mut self.0 {
+            HasherState::Blake3(hasher) if bytes.len() >= BLAKE3_PARALLEL_THRESHOLD => {
+                hasher.update_rayon(bytes);
+            }
+            HasherState::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+            HasherState::Sha256(hasher) => hasher.update(bytes),
+        }
+        self
+    }
+
+    /// Returns the hash of all the data fed to the hasher.
+    pub fn finalize(self) -> Hash {
+        match self.0 {
+            HasherState::Blake3(hasher) => {
+                Hash::with_algorithm(HashAlgorithm::Blake3, hasher.finalize().into())
+            }
+            HasherState::Sha256(hasher) => {
+                Hash::with_algorithm(HashAlgorithm::Sha256, hasher.finalize().into())
+            }
+        }
+    }
+}
+
+impl io::Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
     }
 }
 
@@ -71,4 +276,96 @@ mod tests {
         let hash = Hash::new(original);
         assert_eq!(hash.to_array(), original);
     }
+
+    #[test]
+    fn hashes_round_trip_through_strings() {
+        for algorithm in [HashAlgorithm::Blake3, HashAlgorithm::Sha256] {
+            let hash = Hash::generate_with(algorithm, b"(module)");
+            assert_eq!(Hash::from_str(&hash.to_string()).unwrap(), hash);
+        }
+        assert_eq!(
+            Hash::generate_with(HashAlgorithm::Sha256, b"").to_string(),
+            "sha256-e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_ne!(
+            Hash::generate_with(HashAlgorithm::Blake3, b"").to_array(),
+            Hash::generate_with(HashAlgorithm::Sha256, b"").to_array()
+        );
+    }
+
+    #[test]
+    fn engine_keys_depend_on_the_compiler_options() {
+        use wasmer::EngineBuilder;
+        use wasmer_compiler_singlepass::Singlepass;
+
+        let hash = Hash::generate(b"(module)");
+        let singlepass = EngineBuilder::new(Singlepass::default()).engine();
+        let mut config = Singlepass::default();
+        config.canonicalize_nans(false);
+        let singlepass_with_nans = EngineBuilder::new(config).engine();
+
+        assert_eq!(hash.for_engine(&singlepass), hash.for_engine(&singlepass));
+        assert_ne!(hash.for_engine(&singlepass), hash);
+        assert_ne!(
+            hash.for_engine(&singlepass),
+            hash.for_engine(&singlepass_with_nans)
+        );
+        assert_ne!(
+            hash.for_engine(&singlepass),
+            hash.for_engine(&EngineBuilder::headless().engine())
+        );
+        assert_eq!(
+            hash.for_engine(&singlepass).algorithm(),
+            HashAlgorithm::Blake3
+        );
+    }
+
+    #[test]
+    fn engine_keys_depend_on_the_middlewares() {
+        use std::sync::Arc;
+        use wasmer::wasmparser::Operator;
+        use wasmer::{CompilerConfig, EngineBuilder};
+        use wasmer_compiler_singlepass::Singlepass;
+        use wasmer_middlewares::{CallDepthLimit, Metering};
+
+        fn with_limits(points: u64, depth: u32) -> Engine {
+            let mut config = Singlepass::default();
+            config.push_middleware(Arc::new(Metering::new(points, |_: &Operator| 1)));
+            config.push_middleware(Arc::new(CallDepthLimit::new(depth)));
+            EngineBuilder::new(config).engine()
+        }
+
+        let hash = Hash::generate(b"(module)");
+        let singlepass = EngineBuilder::new(Singlepass::default()).engine();
+        assert_ne!(
+            hash.for_engine(&singlepass),
+            hash.for_engine(&with_limits(10, 10))
+        );
+        assert_eq!(
+            hash.for_engine(&with_limits(10, 10)),
+            hash.for_engine(&with_limits(10, 10))
+        );
+        assert_ne!(
+            hash.for_engine(&with_limits(10, 10)),
+            hash.for_engine(&with_limits(20, 10))
+        );
+        assert_ne!(
+            hash.for_engine(&with_limits(10, 10)),
+            hash.for_engine(&with_limits(10, 20))
+        );
+    }
+
+    #[test]
+    fn streamed_hashes_match_one_shot_hashes() {
+        let data = (0..BLAKE3_PARALLEL_THRESHOLD * 3)
+            .map(|i| i as u8)
+            .collect::<Vec<_>>();
+        for algorithm in [HashAlgorithm::Blake3, HashAlgorithm::Sha256] {
+            let mut hasher = Hasher::new(algorithm);
+            for chunk in data.chunks(BLAKE3_PARALLEL_THRESHOLD + 7) {
+                hasher.update(chunk);
+            }
+            assert_eq!(hasher.finalize(), Hash::generate_with(algorithm, &data));
+        }
+    }
 }
@@ -21,11 +21,15 @@
 mod cache;
 mod filesystem;
 mod hash;
+#[cfg(feature = "http")]
+mod remote;
 
 pub use crate::cache::Cache;
 #[cfg(feature = "filesystem")]
 pub use crate::filesystem::FileSystemCache;
-pub use crate::hash::Hash;
+pub use crate::hash::{Hash, HashAlgorithm, Hasher};
+#[cfg(feature = "http")]
+pub use crate::remote::RemoteCache;
 
 // We re-export those for convinience of users
 pub use wasmer::{DeserializeError, SerializeError};
@@ -1,7 +1,7 @@
 //! Generate a header file for the static object file produced.
 
 use super::{generate_c, CStatement, CType};
-use wasmer_types::ModuleInfo;
+use wasmer_types::{ExportIndex, ModuleInfo};
 use wasmer_types::{Symbol, SymbolRegistry};
 
 /// Helper functions to simplify the usage of the static artifact.
@@ -290,9 +290,161 @@ pub fn generate_header_file(
         value: HELPER_FUNCTIONS.to_string(),
     });
 
+    c_statements.push(CStatement::LiteralConstant {
+        value: generate_entry_points(module_info),
+    });
+
     c_statements.push(CStatement::LiteralConstant {
         value: "\n#ifdef __cplusplus\n}\n#endif\n\n".to_string(),
     });
 
     generate_c(&c_statements)
 }
+
+/// Helper function to get the exported functions of an instance by the
+/// indices declared for them.
+const ENTRY_POINT_HELPERS: &str = r#"
+static inline wasm_func_t* wasmer_object_export_func(const wasm_extern_vec_t* exports, size_t index) {
+        if (index >= exports->size) {
+                return NULL;
+        }
+        return wasm_extern_as_func(exports->data[index]);
+}
+"#;
+
+/// Generate the declarations of the entry points of the module: the index
+/// of each exported function in the exports of its instances, as returned
+/// by `wasm_instance_exports`.
+///
+/// The names of the exports are turned into C identifiers, so
+/// `WASMER_EXPORT_<NAME>` is declared for the function exported as `name`.
+pub fn generate_entry_points(module_info: &ModuleInfo) -> String {
+    let mut c_statements = vec![CStatement::LiteralConstant {
+        value: r#"
+// Entry points of the module: the functions it exports, by their index in the
+// exports of its instances. Use them with `wasm_instance_exports` and
+// `wasmer_object_export_func` to call into the module.
+"#
+        .to_string(),
+    }];
+    // The identifiers the exports map to, which the suffixed ones of
+    // clashing names must stay clear of.
+    let reserved = module_info
+        .exports
+        .iter()
+        .filter(|(_, export)| matches!(export, ExportIndex::Function(_)))
+        .map(|(name, _)| format!("WASMER_EXPORT_{}", c_identifier(name)))
+        .collect::<Vec<_>>();
+    let mut declared = Vec::new();
+    for (index, (name, export)) in module_info.exports.iter().enumerate() {
+        let func_index = match export {
+            ExportIndex::Function(func_index) => *func_index,
+            _ => continue,
+        };
+        let mut define = format!("WASMER_EXPORT_{}", c_identifier(name));
+        if declared.contains(&define) {
+            // Different export names can map to the same identifier
+            let base = define;
+            let mut suffix = index;
+            loop {
+                define = format!("{}_{}", base, suffix);
+                if !declared.contains(&define) && !reserved.contains(&define) {
+                    break;
+                }
+                suffix += 1;
+            }
+        }
+        let func_type = &module_info.signatures[module_info.functions[func_index]];
+        c_statements.push(CStatement::LiteralConstant {
+            value: format!(
+                "\n// `{}`: {}\n#define {} {}\n",
+                name.replace('\n', " "),
+                func_type,
+                define,
+                index
+            ),
+        });
+        declared.push(define);
+    }
+    c_statements.push(CStatement::LiteralConstant {
+        value: ENTRY_POINT_HELPERS.to_string(),
+    });
+
+    generate_c(&c_statements)
+}
+
+/// Turns an export name into an upper case C identifier.
+fn c_identifier(name: &str) -> String {
+    let mut ident = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if ident.is_empty() {
+        ident.push('_');
+    }
+    ident
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::{FunctionType, MemoryIndex, Type};
+
+    #[test]
+    fn generate_entry_points_declares_exported_functions() {
+        let mut module_info = ModuleInfo::new();
+        let sig_index = module_info.signatures.push(FunctionType::new(
+            vec![Type::I32, Type::I32],
+            vec![Type::I32],
+        ));
+        let add = module_info.functions.push(sig_index);
+        let noop = module_info.functions.push(sig_index);
+        module_info.exports.insert(
+            "memory".to_string(),
+            ExportIndex::Memory(MemoryIndex::new(0)),
+        );
+        module_info
+            .exports
+            .insert("add".to_string(), ExportIndex::Function(add));
+        module_info
+            .exports
+            .insert("no-op".to_string(), ExportIndex::Function(noop));
+        module_info
+            .exports
+            .insert("no_op".to_string(), ExportIndex::Function(noop));
+
+        let header = generate_entry_points(&module_info);
+        assert!(!header.contains("WASMER_EXPORT_MEMORY"));
+        assert!(header.contains("// `add`: [I32, I32] -> [I32]\n#define WASMER_EXPORT_ADD 1\n"));
+        assert!(header.contains("#define WASMER_EXPORT_NO_OP 2\n"));
+        assert!(header.contains("#define WASMER_EXPORT_NO_OP_3 3\n"));
+        assert!(header.contains("wasmer_object_export_func"));
+    }
+
+    #[test]
+    fn generate_entry_points_keeps_suffixed_identifiers_unique() {
+        let mut module_info = ModuleInfo::new();
+        let sig_index = module_info
+            .signatures
+            .push(FunctionType::new(vec![], vec![]));
+        let func = module_info.functions.push(sig_index);
+        for name in ["foo", "foo!", "foo_1", "foo?"] {
+            module_info
+                .exports
+                .insert(name.to_string(), ExportIndex::Function(func));
+        }
+
+        let header = generate_entry_points(&module_info);
+        assert!(header.contains("#define WASMER_EXPORT_FOO 0\n"));
+        assert!(header.contains("#define WASMER_EXPORT_FOO_2 1\n"));
+        assert!(header.contains("#define WASMER_EXPORT_FOO_1 2\n"));
+        assert!(header.contains("#define WASMER_EXPORT_FOO_3 3\n"));
+    }
+}
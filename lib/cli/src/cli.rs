@@ -1,5 +1,7 @@
 //! The logic for the Wasmer CLI tool.
 
+#[cfg(unix)]
+use crate::commands::Attach;
 #[cfg(target_os = "linux")]
 use crate::commands::Binfmt;
 #[cfg(feature = "compiler")]
@@ -8,13 +10,17 @@ use crate::commands::Compile;
 use crate::commands::CreateExe;
 #[cfg(feature = "static-artifact-create")]
 use crate::commands::CreateObj;
+#[cfg(all(feature = "interpreter", feature = "wasi"))]
+use crate::commands::Debug;
+#[cfg(feature = "wasi")]
+use crate::commands::Serve;
 #[cfg(feature = "wast")]
 use crate::commands::Wast;
-use crate::commands::{Cache, Config, Inspect, Run, SelfUpdate, Validate};
+use crate::commands::{Cache, Config, GenCompletions, Inspect, Run, SelfUpdate, Strip, Validate};
 use crate::error::PrettyError;
 use anyhow::Result;
 
-use clap::{ErrorKind, Parser};
+use clap::{CommandFactory, ErrorKind, Parser};
 
 #[derive(Parser)]
 #[cfg_attr(
@@ -126,14 +132,47 @@ enum WasmerCLIOptions {
     #[clap(name = "config")]
     Config(Config),
 
+    /// Print the completions of the wasmer commands for a shell
+    ///
+    /// Example usage:
+    ///
+    /// ```text
+    /// $ wasmer gen-completions bash > /etc/bash_completion.d/wasmer
+    /// $ wasmer gen-completions zsh > "${fpath[1]}/_wasmer"
+    /// $ wasmer gen-completions fish > ~/.config/fish/completions/wasmer.fish
+    /// ```
+    #[clap(name = "gen-completions", verbatim_doc_comment)]
+    GenCompletions(GenCompletions),
+
     /// Update wasmer to the latest version
     #[clap(name = "self-update")]
     SelfUpdate(SelfUpdate),
 
+    /// Serve HTTP requests with a WASI program, run as a CGI script
+    #[cfg(feature = "wasi")]
+    #[clap(name = "serve")]
+    Serve(Serve),
+
+    /// Debug a WASI program from an editor, with the Debug Adapter
+    /// Protocol
+    #[cfg(all(feature = "interpreter", feature = "wasi"))]
+    #[clap(name = "debug")]
+    Debug(Debug),
+
     /// Inspect a WebAssembly file
     #[clap(name = "inspect")]
     Inspect(Inspect),
 
+    /// Strip the custom sections (names, DWARF, ...) of a WebAssembly file
+    #[clap(name = "strip")]
+    Strip(Strip),
+
+    /// Print the live runtime state of a process started with
+    /// `wasmer run --diagnostics`
+    #[cfg(unix)]
+    #[clap(name = "attach")]
+    Attach(Attach),
+
     /// Run spec testsuite
     #[cfg(feature = "wast")]
     #[clap(name = "wast")]
@@ -159,7 +198,15 @@ impl WasmerCLIOptions {
             #[cfg(feature = "static-artifact-create")]
             Self::CreateObj(create_obj) => create_obj.execute(),
             Self::Config(config) => config.execute(),
+            Self::GenCompletions(gen_completions) => gen_completions.execute(&mut Self::command()),
+            #[cfg(feature = "wasi")]
+            Self::Serve(serve) => serve.execute(),
+            #[cfg(all(feature = "interpreter", feature = "wasi"))]
+            Self::Debug(debug) => debug.execute(),
             Self::Inspect(inspect) => inspect.execute(),
+            Self::Strip(strip) => strip.execute(),
+            #[cfg(unix)]
+            Self::Attach(attach) => attach.execute(),
             #[cfg(feature = "wast")]
             Self::Wast(wast) => wast.execute(),
             #[cfg(target_os = "linux")]
@@ -188,8 +235,9 @@ pub fn wasmer_main() {
         WasmerCLIOptions::Run(Run::from_binfmt_args())
     } else {
         match command.unwrap_or(&"".to_string()).as_ref() {
-            "cache" | "compile" | "config" | "create-exe" | "help" | "inspect" | "run"
-            | "self-update" | "validate" | "wast" | "binfmt" => WasmerCLIOptions::parse(),
+            "attach" | "cache" | "compile" | "config" | "create-exe" | "debug"
+            | "gen-completions" | "help" | "inspect" | "run" | "self-update" | "serve"
+            | "strip" | "validate" | "wast" | "binfmt" => WasmerCLIOptions::parse(),
             _ => {
                 WasmerCLIOptions::try_parse_from(args.iter()).unwrap_or_else(|e| {
                     match e.kind() {
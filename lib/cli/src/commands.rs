@@ -1,4 +1,6 @@
 //! The commands available in the Wasmer binary.
+#[cfg(unix)]
+mod attach;
 #[cfg(target_os = "linux")]
 mod binfmt;
 mod cache;
@@ -9,13 +11,21 @@ mod config;
 mod create_exe;
 #[cfg(feature = "static-artifact-create")]
 mod create_obj;
+#[cfg(all(feature = "interpreter", feature = "wasi"))]
+mod debug;
+mod gen_completions;
 mod inspect;
 mod run;
 mod self_update;
+#[cfg(feature = "wasi")]
+mod serve;
+mod strip;
 mod validate;
 #[cfg(feature = "wast")]
 mod wast;
 
+#[cfg(unix)]
+pub use attach::*;
 #[cfg(target_os = "linux")]
 pub use binfmt::*;
 #[cfg(feature = "compiler")]
@@ -24,9 +34,16 @@ pub use compile::*;
 pub use create_exe::*;
 #[cfg(feature = "static-artifact-create")]
 pub use create_obj::*;
+#[cfg(all(feature = "interpreter", feature = "wasi"))]
+pub use debug::*;
+#[cfg(feature = "wasi")]
+pub use serve::*;
 #[cfg(feature = "wast")]
 pub use wast::*;
-pub use {cache::*, config::*, inspect::*, run::*, self_update::*, validate::*};
+pub use {
+    cache::*, config::*, gen_completions::*, inspect::*, run::*, self_update::*, strip::*,
+    validate::*,
+};
 
 /// The kind of object format to emit.
 #[derive(Debug, Copy, Clone, clap::Parser)]
@@ -0,0 +1,33 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::io::Read;
+use std::os::unix::net::UnixStream;
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer attach` subcommand
+pub struct Attach {
+    /// Process id of the Wasmer process to inspect
+    #[clap(name = "PID")]
+    pid: u32,
+}
+
+impl Attach {
+    /// Runs logic for the `attach` subcommand
+    pub fn execute(&self) -> Result<()> {
+        let path = wasmer::diagnostics::socket_path(self.pid);
+        let mut stream = UnixStream::connect(&path).with_context(|| {
+            format!(
+                "no diagnostic server is running in process {} (expected a socket at `{}`); \
+                 start it with `wasmer run --diagnostics` or `wasmer::diagnostics::DiagnosticServer::start()`",
+                self.pid,
+                path.display()
+            )
+        })?;
+        let mut report = String::new();
+        stream
+            .read_to_string(&mut report)
+            .with_context(|| format!("failed to read the report of process {}", self.pid))?;
+        print!("{}", report);
+        Ok(())
+    }
+}
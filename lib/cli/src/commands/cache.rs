@@ -1,7 +1,13 @@
 use crate::common::get_cache_dir;
-use anyhow::{Context, Result};
+use crate::VERSION;
+use anyhow::{bail, Context, Result};
+use bytesize::ByteSize;
 use clap::Parser;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
 
 #[derive(Debug, Parser)]
 /// The options for the `wasmer cache` subcommand
@@ -13,6 +19,37 @@ pub enum Cache {
     /// Display the location of the cache
     #[clap(name = "dir")]
     Dir,
+
+    /// List the cached modules, from the least to the most recently used
+    #[clap(name = "list")]
+    List,
+
+    /// Remove the cached modules that are too old or over a size budget
+    #[clap(name = "prune")]
+    Prune(Prune),
+}
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer cache prune` subcommand
+pub struct Prune {
+    /// Remove the modules not used for this many days
+    #[clap(long = "older-than", name = "DAYS")]
+    older_than: Option<u64>,
+
+    /// Remove the least recently used modules until the cache fits in this
+    /// size (e.g. `500MB`)
+    #[clap(long = "max-size", name = "SIZE")]
+    max_size: Option<ByteSize>,
+}
+
+/// A module in the cache.
+#[derive(Debug)]
+struct CacheEntry {
+    /// The compiler the module was compiled with.
+    compiler: String,
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
 }
 
 impl Cache {
@@ -25,6 +62,12 @@ impl Cache {
             Cache::Dir => {
                 self.dir()?;
             }
+            Cache::List => {
+                self.list().context("failed to list wasmer cache.")?;
+            }
+            Cache::Prune(prune) => {
+                prune.execute().context("failed to prune wasmer cache.")?;
+            }
         }
         Ok(())
     }
@@ -41,4 +84,163 @@ impl Cache {
         println!("{}", get_cache_dir().to_string_lossy());
         Ok(())
     }
+    fn list(&self) -> Result<()> {
+        let entries = read_entries(&get_cache_dir())?;
+        let now = SystemTime::now();
+        for entry in &entries {
+            let name = entry.path.file_name().unwrap_or_default();
+            println!(
+                "{:<12} {:>10} {:>5}d  {}",
+                entry.compiler,
+                ByteSize(entry.size).to_string(),
+                age(now, entry).as_secs() / SECONDS_PER_DAY,
+                name.to_string_lossy()
+            );
+        }
+        let total: u64 = entries.iter().map(|entry| entry.size).sum();
+        eprintln!(
+            "{} cached modules, {} in total (wasmer {}).",
+            entries.len(),
+            ByteSize(total),
+            VERSION
+        );
+        Ok(())
+    }
+}
+
+impl Prune {
+    fn execute(&self) -> Result<()> {
+        if self.older_than.is_none() && self.max_size.is_none() {
+            bail!("nothing to prune, pass `--older-than` and/or `--max-size`");
+        }
+        let entries = read_entries(&get_cache_dir())?;
+        let pruned = self.select(&entries, SystemTime::now());
+        let mut freed = 0;
+        for entry in &pruned {
+            fs::remove_file(&entry.path)
+                .with_context(|| format!("failed to remove `{}`", entry.path.display()))?;
+            freed += entry.size;
+        }
+        eprintln!(
+            "Pruned {} cached modules, freeing {}.",
+            pruned.len(),
+            ByteSize(freed)
+        );
+        Ok(())
+    }
+
+    /// Selects the entries to remove, given all of them from the oldest
+    /// to the newest.
+    fn select<'a>(&self, entries: &'a [CacheEntry], now: SystemTime) -> Vec<&'a CacheEntry> {
+        let max_age = self
+            .older_than
+            .map(|days| Duration::from_secs(days.saturating_mul(SECONDS_PER_DAY)));
+        let (mut pruned, kept): (Vec<_>, Vec<_>) = entries
+            .iter()
+            .partition(|entry| max_age.map_or(false, |max_age| age(now, entry) > max_age));
+        if let Some(max_size) = self.max_size {
+            let mut size: u64 = kept.iter().map(|entry| entry.size).sum();
+            for entry in kept {
+                if size <= max_size.as_u64() {
+                    break;
+                }
+                size -= entry.size;
+                pruned.push(entry);
+            }
+        }
+        pruned
+    }
+}
+
+fn age(now: SystemTime, entry: &CacheEntry) -> Duration {
+    now.duration_since(entry.modified).unwrap_or_default()
+}
+
+/// Reads the modules cached in `cache_dir`, from the oldest to the newest.
+///
+/// The modules are stored in a directory per compiler.
+fn read_entries(cache_dir: &Path) -> Result<Vec<CacheEntry>> {
+    let mut entries = Vec::new();
+    if !cache_dir.exists() {
+        return Ok(entries);
+    }
+    for compiler_dir in fs::read_dir(cache_dir)? {
+        let compiler_dir = compiler_dir?;
+        if !compiler_dir.file_type()?.is_dir() {
+            continue;
+        }
+        let compiler = compiler_dir.file_name().to_string_lossy().into_owned();
+        for file in fs::read_dir(compiler_dir.path())? {
+            let file = file?;
+            let metadata = file.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            entries.push(CacheEntry {
+                compiler: compiler.clone(),
+                path: file.path(),
+                size: metadata.len(),
+                modified: metadata.modified()?,
+            });
+        }
+    }
+    entries.sort_by_key(|entry| entry.modified);
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, size: u64, days: u64, now: SystemTime) -> CacheEntry {
+        CacheEntry {
+            compiler: "cranelift".to_string(),
+            path: PathBuf::from(name),
+            size,
+            modified: now - Duration::from_secs(days * SECONDS_PER_DAY),
+        }
+    }
+
+    fn selected(prune: &Prune, entries: &[CacheEntry], now: SystemTime) -> Vec<String> {
+        prune
+            .select(entries, now)
+            .iter()
+            .map(|entry| entry.path.display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn prune_selects_old_entries_then_oldest_over_budget() {
+        let now = SystemTime::now();
+        let entries = vec![
+            entry("a", 400, 30, now),
+            entry("b", 300, 10, now),
+            entry("c", 200, 5, now),
+            entry("d", 100, 1, now),
+        ];
+
+        let by_age = Prune {
+            older_than: Some(7),
+            max_size: None,
+        };
+        assert_eq!(selected(&by_age, &entries, now), vec!["a", "b"]);
+
+        let by_size = Prune {
+            older_than: None,
+            max_size: Some(ByteSize(350)),
+        };
+        assert_eq!(selected(&by_size, &entries, now), vec!["a", "b"]);
+
+        let both = Prune {
+            older_than: Some(20),
+            max_size: Some(ByteSize(250)),
+        };
+        assert_eq!(selected(&both, &entries, now), vec!["a", "b", "c"]);
+
+        let nothing = Prune {
+            older_than: Some(60),
+            max_size: Some(ByteSize(1000)),
+        };
+        assert!(selected(&nothing, &entries, now).is_empty());
+    }
 }
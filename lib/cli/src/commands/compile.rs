@@ -2,7 +2,7 @@ use crate::store::StoreOptions;
 use crate::warning;
 use anyhow::{Context, Result};
 use clap::Parser;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use wasmer::*;
 
 #[derive(Debug, Parser)]
@@ -16,15 +16,23 @@ pub struct Compile {
     #[clap(name = "OUTPUT PATH", short = 'o', parse(from_os_str))]
     output: PathBuf,
 
-    /// Compilation Target triple
-    #[clap(long = "target")]
-    target_triple: Option<Triple>,
+    /// Sign the compiled module with the Ed25519 private key of this PEM
+    /// file, for `wasmer run --require-signature` to check
+    #[clap(long = "sign", name = "KEY", parse(from_os_str))]
+    sign: Option<PathBuf>,
+
+    /// Attach metadata to the compiled module, such as `name=plugin`,
+    /// `version=1.2.0` or `build-id=...`, reported in its traps and
+    /// profiles. Can be repeated
+    #[clap(
+        long = "metadata",
+        name = "KEY=VALUE",
+        parse(try_from_str = parse_metadata_entry)
+    )]
+    metadata: Vec<(String, String)>,
 
     #[clap(flatten)]
     store: StoreOptions,
-
-    #[clap(short = 'm')]
-    cpu_features: Vec<CpuFeature>,
 }
 
 impl Compile {
@@ -35,23 +43,7 @@ impl Compile {
     }
 
     fn inner_execute(&self) -> Result<()> {
-        let target = self
-            .target_triple
-            .as_ref()
-            .map(|target_triple| {
-                let mut features = self
-                    .cpu_features
-                    .clone()
-                    .into_iter()
-                    .fold(CpuFeature::set(), |a, b| a | b);
-                // Cranelift requires SSE2, so we have this "hack" for now to facilitate
-                // usage
-                if target_triple.architecture == Architecture::X86_64 {
-                    features |= CpuFeature::SSE2;
-                }
-                Target::new(target_triple.clone(), features)
-            })
-            .unwrap_or_default();
+        let target = self.store.get_target()?;
         let (store, compiler_type) = self.store.get_store_for_target(target.clone())?;
         let output_filename = self
             .output
@@ -73,8 +65,19 @@ impl Compile {
         println!("Compiler: {}", compiler_type.to_string());
         println!("Target: {}", target.triple());
 
-        let module = Module::from_file(&store, &self.path)?;
-        module.serialize_to_file(&self.output)?;
+        let module = if self.metadata.is_empty() {
+            Module::from_file(&store, &self.path)?
+        } else {
+            let contents = std::fs::read(&self.path)?;
+            #[cfg(feature = "wat")]
+            let contents = wat2wasm(&contents)?.into_owned();
+            let metadata = self.metadata.iter().cloned().collect();
+            Module::new(&store, attach_metadata(&contents, &metadata)?)?
+        };
+        match &self.sign {
+            Some(key) => write_signed(&module, key, &self.output)?,
+            None => module.serialize_to_file(&self.output)?,
+        }
         eprintln!(
             "✔ File compiled successfully to `{}`.",
             self.output.display(),
@@ -83,3 +86,30 @@ impl Compile {
         Ok(())
     }
 }
+
+fn parse_metadata_entry(entry: &str) -> Result<(String, String)> {
+    match entry.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => bail!(
+            "metadata must be of the form `KEY=VALUE`; found `{}`",
+            entry
+        ),
+    }
+}
+
+/// Writes `module` to `output`, signed with the private key of the PEM
+/// file `key`.
+#[cfg(feature = "signing")]
+fn write_signed(module: &Module, key: &Path, output: &Path) -> Result<()> {
+    let pem = std::fs::read_to_string(key)
+        .with_context(|| format!("failed to read the signing key `{}`", key.display()))?;
+    let key = SigningKey::from_pem(&pem)
+        .with_context(|| format!("invalid signing key `{}`", key.display()))?;
+    std::fs::write(output, module.serialize_signed(&key)?)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "signing"))]
+fn write_signed(_module: &Module, _key: &Path, _output: &Path) -> Result<()> {
+    bail!("wasmer was built without the `signing` feature")
+}
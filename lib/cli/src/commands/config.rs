@@ -1,3 +1,5 @@
+use crate::common::get_cache_dir;
+use crate::store::CompilerType;
 use crate::VERSION;
 use anyhow::{Context, Result};
 use clap::Parser;
@@ -31,6 +33,18 @@ pub struct Config {
     #[clap(long, conflicts_with = "pkg-config")]
     cflags: bool,
 
+    /// Directory caching the compiled modules.
+    #[clap(long, conflicts_with = "pkg-config")]
+    cache_dir: bool,
+
+    /// Features Wasmer was built with, separated by spaces.
+    #[clap(long, conflicts_with = "pkg-config")]
+    features: bool,
+
+    /// Compilers Wasmer was built with, separated by spaces.
+    #[clap(long, conflicts_with = "pkg-config")]
+    compilers: bool,
+
     /// It outputs the necessary details for compiling
     /// and linking a program to Wasmer, using the `pkg-config` format.
     #[clap(long)]
@@ -44,6 +58,34 @@ impl Config {
             .context("failed to retrieve the wasmer config".to_string())
     }
     fn inner_execute(&self) -> Result<()> {
+        // Only the paths of the installation need `WASMER_DIR`
+        if self.pkg_config
+            || self.prefix
+            || self.bindir
+            || self.includedir
+            || self.libdir
+            || self.libs
+            || self.cflags
+        {
+            self.print_paths()?;
+        }
+        if self.cache_dir {
+            println!("{}", get_cache_dir().display());
+        }
+        if self.features {
+            println!("{}", enabled_features().join(" "));
+        }
+        if self.compilers {
+            let compilers = CompilerType::enabled()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            println!("{}", compilers.join(" "));
+        }
+        Ok(())
+    }
+
+    fn print_paths(&self) -> Result<()> {
         let key = "WASMER_DIR";
         let wasmer_dir = env::var(key)
             .or_else(|e| {
@@ -100,3 +142,51 @@ impl Config {
         Ok(())
     }
 }
+
+/// The optional features the CLI was built with.
+fn enabled_features() -> Vec<&'static str> {
+    vec![
+        #[cfg(feature = "cache")]
+        "cache",
+        #[cfg(feature = "remote-cache")]
+        "remote-cache",
+        #[cfg(feature = "compiler")]
+        "compiler",
+        #[cfg(feature = "singlepass")]
+        "singlepass",
+        #[cfg(feature = "cranelift")]
+        "cranelift",
+        #[cfg(feature = "llvm")]
+        "llvm",
+        #[cfg(feature = "interpreter")]
+        "interpreter",
+        #[cfg(feature = "debug-info")]
+        "debug-info",
+        #[cfg(feature = "wasmer-artifact-create")]
+        "wasmer-artifact-create",
+        #[cfg(feature = "static-artifact-create")]
+        "static-artifact-create",
+        #[cfg(feature = "wasmer-artifact-load")]
+        "wasmer-artifact-load",
+        #[cfg(feature = "static-artifact-load")]
+        "static-artifact-load",
+        #[cfg(feature = "headless")]
+        "headless",
+        #[cfg(feature = "wasi")]
+        "wasi",
+        #[cfg(feature = "experimental-io-devices")]
+        "experimental-io-devices",
+        #[cfg(feature = "emscripten")]
+        "emscripten",
+        #[cfg(feature = "wat")]
+        "wat",
+        #[cfg(feature = "wast")]
+        "wast",
+        #[cfg(feature = "http")]
+        "http",
+        #[cfg(feature = "debug")]
+        "debug",
+        #[cfg(feature = "enable-serde")]
+        "enable-serde",
+    ]
+}
@@ -53,20 +53,6 @@ pub struct CreateExe {
     #[clap(name = "OUTPUT PATH", short = 'o', parse(from_os_str))]
     output: PathBuf,
 
-    /// Compilation Target triple
-    ///
-    /// Accepted target triple values must follow the
-    /// ['target_lexicon'](https://crates.io/crates/target-lexicon) crate format.
-    ///
-    /// The recommended targets we try to support are:
-    ///
-    /// - "x86_64-linux-gnu"
-    /// - "aarch64-linux-gnu"
-    /// - "x86_64-apple-darwin"
-    /// - "arm64-apple-darwin"
-    #[clap(long = "target")]
-    target_triple: Option<Triple>,
-
     // Cross-compile with `zig`
     /// Cross-compilation library path.
     #[clap(long = "library-path")]
@@ -96,9 +82,6 @@ pub struct CreateExe {
     #[clap(name = "HEADER", long = "header", verbatim_doc_comment)]
     header: Option<PathBuf>,
 
-    #[clap(short = 'm')]
-    cpu_features: Vec<CpuFeature>,
-
     /// Additional libraries to link against.
     /// This is useful for fixing linker errors that may occur on some systems.
     #[clap(short = 'l')]
@@ -119,7 +102,8 @@ impl CreateExe {
         /* Making library_path, tarball zig_binary_path flags require that target_triple flag
          * is set cannot be encoded with structopt, so we have to perform cli flag validation
          * manually here */
-        let cross_compile: Option<CrossCompile> = if self.target_triple.is_none()
+        let target_triple = self.compiler.target_triple();
+        let cross_compile: Option<CrossCompile> = if target_triple.is_none()
             && (self.library_path.is_some()
                 || self.tarball.is_some()
                 || self.zig_binary_path.is_some())
@@ -127,7 +111,7 @@ impl CreateExe {
             return Err(anyhow!(
                 "To cross-compile an executable, you must specify a target triple with --target"
             ));
-        } else if self.target_triple.is_some() {
+        } else if target_triple.is_some() {
             Some(CrossCompile {
                 library_path: self.library_path.clone(),
                 zig_binary_path: self.zig_binary_path.clone(),
@@ -137,29 +121,13 @@ impl CreateExe {
             None
         };
 
-        let target = self
-            .target_triple
-            .as_ref()
-            .map(|target_triple| {
-                let mut features = self
-                    .cpu_features
-                    .clone()
-                    .into_iter()
-                    .fold(CpuFeature::set(), |a, b| a | b);
-                // Cranelift requires SSE2, so we have this "hack" for now to facilitate
-                // usage
-                if target_triple.architecture == Architecture::X86_64 {
-                    features |= CpuFeature::SSE2;
-                }
-                Target::new(target_triple.clone(), features)
-            })
-            .unwrap_or_default();
+        let target = self.compiler.get_target()?;
 
         env::set_current_dir(&working_dir)?;
 
         let cross_compilation: Option<CrossCompileSetup> = if let Some(mut cross_subc) =
             cross_compile.or_else(|| {
-                if self.target_triple.is_some() {
+                if target_triple.is_some() {
                     Some(CrossCompile {
                         library_path: None,
                         tarball: None,
@@ -175,7 +143,7 @@ impl CreateExe {
                 ));
             }
 
-            let target = if let Some(target_triple) = self.target_triple.clone() {
+            let target = if let Some(target_triple) = target_triple.cloned() {
                 target_triple
             } else {
                 return Err(anyhow!(
@@ -210,9 +178,8 @@ impl CreateExe {
                 v
             } else {
                 {
-                    let libwasmer_path = if self
-                        .target_triple
-                        .clone()
+                    let libwasmer_path = if target_triple
+                        .cloned()
                         .unwrap_or(Triple::host())
                         .operating_system
                         == wasmer_types::OperatingSystem::Windows
@@ -221,9 +188,8 @@ impl CreateExe {
                     } else {
                         "lib/libwasmer.a"
                     };
-                    let libwasmer_headless_path = if self
-                        .target_triple
-                        .clone()
+                    let libwasmer_headless_path = if target_triple
+                        .cloned()
                         .unwrap_or(Triple::host())
                         .operating_system
                         == wasmer_types::OperatingSystem::Windows
@@ -342,14 +308,14 @@ impl CreateExe {
                         &c_src_path,
                         &c_src_obj,
                         static_defs_header_path,
-                        self.target_triple.clone(),
+                        self.compiler.target_triple().cloned(),
                     )
                     .context("Failed to compile C source code")?;
                     LinkCode {
                         object_paths: vec![c_src_obj, wasm_object_path],
                         output_path,
                         additional_libraries: self.libraries.clone(),
-                        target: self.target_triple.clone(),
+                        target: self.compiler.target_triple().cloned(),
                         ..Default::default()
                     }
                     .run()
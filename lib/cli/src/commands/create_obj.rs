@@ -34,20 +34,6 @@ pub struct CreateObj {
     )]
     header_output: Option<PathBuf>,
 
-    /// Compilation Target triple
-    ///
-    /// Accepted target triple values must follow the
-    /// ['target_lexicon'](https://crates.io/crates/target-lexicon) crate format.
-    ///
-    /// The recommended targets we try to support are:
-    ///
-    /// - "x86_64-linux-gnu"
-    /// - "aarch64-linux-gnu"
-    /// - "x86_64-apple-darwin"
-    /// - "arm64-apple-darwin"
-    #[clap(long = "target")]
-    target_triple: Option<Triple>,
-
     /// Object format options
     ///
     /// This flag accepts two options: `symbols` or `serialized`.
@@ -56,9 +42,6 @@ pub struct CreateObj {
     #[clap(name = "OBJECT_FORMAT", long = "object-format", verbatim_doc_comment)]
     object_format: Option<ObjectFormat>,
 
-    #[clap(short = 'm', multiple = true, number_of_values = 1)]
-    cpu_features: Vec<CpuFeature>,
-
     #[clap(flatten)]
     compiler: CompilerOptions,
 }
@@ -66,23 +49,7 @@ pub struct CreateObj {
 impl CreateObj {
     /// Runs logic for the `create-obj` subcommand
     pub fn execute(&self) -> Result<()> {
-        let target = self
-            .target_triple
-            .as_ref()
-            .map(|target_triple| {
-                let mut features = self
-                    .cpu_features
-                    .clone()
-                    .into_iter()
-                    .fold(CpuFeature::set(), |a, b| a | b);
-                // Cranelift requires SSE2, so we have this "hack" for now to facilitate
-                // usage
-                if target_triple.architecture == Architecture::X86_64 {
-                    features |= CpuFeature::SSE2;
-                }
-                Target::new(target_triple.clone(), features)
-            })
-            .unwrap_or_default();
+        let target = self.compiler.get_target()?;
         let (store, compiler_type) = self.compiler.get_store_for_target(target.clone())?;
         let object_format = self.object_format.unwrap_or(ObjectFormat::Symbols);
 
@@ -115,6 +82,9 @@ impl CreateObj {
                 writer.flush()?;
                 let mut writer = BufWriter::new(File::create(&header_output_path)?);
                 writer.write_all(WASMER_SERIALIZED_HEADER)?;
+                let entry_points =
+                    crate::c_gen::staticlib_header::generate_entry_points(module.info());
+                writer.write_all(entry_points.as_bytes())?;
                 writer.flush()?;
             }
             ObjectFormat::Symbols => {
@@ -157,6 +127,12 @@ impl CreateObj {
 	#include "{}"
 	
 	wasm_module_t *module = wasmer_object_module_new(store, "my_module_name");
+
+The functions exported by the module are declared in the header as `WASMER_EXPORT_<NAME>` indices into the exports of its instances:
+
+	wasm_extern_vec_t exports;
+	wasm_instance_exports(instance, &exports);
+	wasm_func_t *func = wasmer_object_export_func(&exports, WASMER_EXPORT_MY_FUNCTION);
             "#,
             header_output.display(),
         );
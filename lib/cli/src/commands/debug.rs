@@ -0,0 +1,784 @@
+//! The `wasmer debug` subcommand, a [Debug Adapter Protocol] server
+//! debugging a WASI program.
+//!
+//! The program runs in the interpreter, under the debugger of its
+//! instance. An editor talks to the server over the standard streams, or
+//! over TCP with `--listen`, and sets breakpoints at the lines of the
+//! sources the module was compiled from, which its DWARF maps to its
+//! code. At a stop, the locals and the operand stack of the stopped
+//! function can be inspected, and stepping runs until the next line.
+//!
+//! As the standard streams may carry the protocol, the standard input of
+//! the program is empty, and what it writes is sent to the editor as
+//! `output` events.
+//!
+//! [Debug Adapter Protocol]: https://microsoft.github.io/debug-adapter-protocol/
+use crate::utils::{parse_envvar, parse_mapdir};
+use anyhow::{Context, Result};
+use clap::Parser;
+use serde::Deserialize;
+use serde_json::{json, Value as Json};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use wasmer::wasmparser::{Parser as WasmParser, Payload};
+use wasmer::{
+    AsStoreMut, DebugAction, DebugFrame, EngineBuilder, Instance, Module, StopReason, Store,
+};
+use wasmer_middlewares::line_table::LineTable;
+use wasmer_wasi::{is_wasi_module, Pipe, WasiError, WasiState};
+
+/// The id of the only thread of the program.
+const THREAD_ID: u64 = 1;
+
+/// The `variablesReference` of the locals of the stopped function.
+const LOCALS_REFERENCE: u64 = 1;
+
+/// The `variablesReference` of its operand stack.
+const OPERANDS_REFERENCE: u64 = 2;
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer debug` subcommand
+pub struct Debug {
+    /// The WASI program to debug
+    #[clap(name = "FILE", parse(from_os_str))]
+    path: PathBuf,
+
+    /// Wait for an editor on this address, instead of talking to it over
+    /// the standard streams
+    #[clap(long = "listen")]
+    listen: Option<SocketAddr>,
+
+    /// WASI pre-opened directory
+    #[clap(long = "dir", name = "DIR")]
+    pre_opened_directories: Vec<PathBuf>,
+
+    /// Map a host directory to a different location for the Wasm module
+    #[clap(
+        long = "mapdir",
+        name = "GUEST_DIR:HOST_DIR",
+        parse(try_from_str = parse_mapdir),
+    )]
+    mapped_dirs: Vec<(String, PathBuf)>,
+
+    /// Pass custom environment variables
+    #[clap(
+        long = "env",
+        name = "KEY=VALUE",
+        parse(try_from_str = parse_envvar),
+    )]
+    env_vars: Vec<(String, String)>,
+
+    /// Application arguments
+    #[clap(value_name = "ARGS")]
+    args: Vec<String>,
+}
+
+impl Debug {
+    /// Runs logic for the `debug` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .context(format!("failed to debug `{}`", self.path.display()))
+    }
+
+    fn inner_execute(&self) -> Result<()> {
+        let wasm = std::fs::read(&self.path)?;
+        #[cfg(feature = "wat")]
+        let wasm = wasmer::wat2wasm(&wasm)?.into_owned();
+        // Only interpreted functions stop on breakpoints
+        let mut store = Store::new(EngineBuilder::new(
+            wasmer_compiler_interpreter::Interpreter::new(),
+        ));
+        let module = Module::new(&store, &wasm)?;
+        if !is_wasi_module(&module) {
+            bail!("only WASI programs can be debugged");
+        }
+        let sources = Sources::new(&wasm, &module)?;
+
+        let connection = match self.listen {
+            Some(address) => {
+                let listener = TcpListener::bind(address)
+                    .with_context(|| format!("failed to listen on {}", address))?;
+                eprintln!("Waiting for an editor on {}", listener.local_addr()?);
+                let (stream, _) = listener.accept()?;
+                Connection::new(stream.try_clone()?, stream)
+            }
+            None => Connection::new(io::stdin(), io::stdout()),
+        };
+
+        let program_name = self
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let stdout = Pipe::new();
+        let stderr = Pipe::new();
+        let wasi_env = WasiState::new(&program_name)
+            .args(&self.args)
+            .envs(self.env_vars.clone())
+            .preopen_dirs(self.pre_opened_directories.clone())?
+            .map_dirs(self.mapped_dirs.clone())?
+            .stdin(Box::new(Pipe::new()))
+            .stdout(Box::new(stdout.clone()))
+            .stderr(Box::new(stderr.clone()))
+            .finalize(&mut store)?;
+        let imports = wasi_env.import_object(&mut store, &module)?;
+        let instance = Instance::new(&mut store, &module, &imports)?;
+        let memory = instance.exports.get_memory("memory")?;
+        wasi_env.data_mut(&mut store).set_memory(memory.clone());
+
+        let session = Arc::new(Mutex::new(Session {
+            connection,
+            sources,
+            instance: instance.clone(),
+            stdout,
+            stderr,
+            stop: None,
+            step_from: None,
+            breakpoints: HashMap::new(),
+            disconnected: false,
+        }));
+        let stopped = session.clone();
+        instance.set_debugger(&mut store, move |store, frame| {
+            stopped.lock().unwrap().stop(store, frame)
+        });
+
+        if !session.lock().unwrap().configure(&mut store)? {
+            return Ok(());
+        }
+        let start = instance.exports.get_function("_start")?;
+        let result = match start.call(&mut store, &[]) {
+            Ok(_) => Ok(0),
+            Err(err) => match err.downcast::<WasiError>() {
+                Ok(WasiError::Exit(code)) => Ok(code),
+                Ok(err) => Err(err.to_string()),
+                Err(err) => Err(err.to_string()),
+            },
+        };
+        let mut session = session.lock().unwrap();
+        if session.disconnected {
+            return Ok(());
+        }
+        session.finish(&mut store, result)?;
+        Ok(())
+    }
+}
+
+/// Maps the code of the module to the sources it was compiled from,
+/// through its DWARF.
+struct Sources {
+    /// The line programs of the module, if it has DWARF.
+    lines: Option<LineTable>,
+    /// The offset of the code section in the module.
+    code_section_offset: usize,
+    /// The range of the body of each local function in the module.
+    bodies: Vec<Range<usize>>,
+    num_imported_functions: u32,
+    function_names: HashMap<u32, String>,
+}
+
+impl Sources {
+    fn new(wasm: &[u8], module: &Module) -> Result<Self> {
+        let mut code_section_offset = 0;
+        let mut bodies = Vec::new();
+        let mut debug_sections = HashMap::new();
+        for payload in WasmParser::new(0).parse_all(wasm) {
+            match payload? {
+                Payload::CodeSectionStart { range, .. } => code_section_offset = range.start,
+                Payload::CodeSectionEntry(body) => {
+                    let reader = body.get_binary_reader();
+                    let start = reader.original_position();
+                    bodies.push(start..start + reader.bytes_remaining());
+                }
+                Payload::CustomSection { name, data, .. } if name.starts_with(".debug_") => {
+                    debug_sections.insert(name.to_string(), data.to_vec());
+                }
+                _ => {}
+            }
+        }
+        let info = module.info();
+        Ok(Self {
+            lines: LineTable::new(&debug_sections),
+            code_section_offset,
+            bodies,
+            num_imported_functions: info.num_imported_functions as u32,
+            function_names: info
+                .function_names
+                .iter()
+                .map(|(index, name)| (index.as_u32(), name.clone()))
+                .collect(),
+        })
+    }
+
+    /// The file and line of the operator `offset` bytes into the body of
+    /// the function `func_index`.
+    fn location(&self, func_index: u32, offset: u32) -> Option<(String, u64)> {
+        let local_index = func_index.checked_sub(self.num_imported_functions)?;
+        let body = self.bodies.get(local_index as usize)?;
+        let address = body.start + offset as usize - self.code_section_offset;
+        self.lines.as_ref()?.find(address as u64)
+    }
+
+    /// The functions and offsets where the code of the first line at or
+    /// after `line` of the file `path` starts, with that line.
+    fn breakpoints(&self, path: &str, line: u64) -> Option<(u64, Vec<(u32, u32)>)> {
+        let (line, addresses) = self.lines.as_ref()?.lines(path, line)?;
+        let locations = addresses
+            .into_iter()
+            .filter_map(|address| {
+                let offset = self.code_section_offset + address as usize;
+                let local_index = self
+                    .bodies
+                    .partition_point(|body| body.start <= offset)
+                    .checked_sub(1)?;
+                let body = &self.bodies[local_index];
+                if offset >= body.end {
+                    return None;
+                }
+                Some((
+                    self.num_imported_functions + local_index as u32,
+                    (offset - body.start) as u32,
+                ))
+            })
+            .collect();
+        Some((line, locations))
+    }
+
+    fn function_name(&self, func_index: u32) -> String {
+        self.function_names
+            .get(&func_index)
+            .cloned()
+            .unwrap_or_else(|| format!("<function {}>", func_index))
+    }
+}
+
+/// A stop of the program, as shown to the editor.
+struct Stop {
+    frame: DebugFrame,
+    location: Option<(String, u64)>,
+}
+
+/// What the program does after a request.
+enum Next {
+    /// It stays as it is.
+    Stay,
+    /// It resumes from a stop.
+    Resume(DebugAction),
+    /// It starts, once the editor has set its breakpoints.
+    Start,
+    /// It is killed, as the editor is gone.
+    Disconnect,
+}
+
+/// The state shared by the program and its stops.
+struct Session {
+    connection: Connection,
+    sources: Sources,
+    instance: Instance,
+    stdout: Pipe,
+    stderr: Pipe,
+    /// Where the program is stopped, if it is.
+    stop: Option<Stop>,
+    /// The line a step started from, which it runs until it leaves.
+    step_from: Option<(String, u64)>,
+    /// The functions and offsets of the breakpoints set in each source.
+    breakpoints: HashMap<String, Vec<(u32, u32)>>,
+    /// Whether the editor is gone.
+    disconnected: bool,
+}
+
+impl Session {
+    /// Handles the requests configuring the program, returning whether
+    /// it has to start.
+    fn configure(&mut self, store: &mut impl AsStoreMut) -> Result<bool> {
+        loop {
+            let request = match self.connection.read_request()? {
+                Some(request) => request,
+                None => return Ok(false),
+            };
+            match self.handle(store, &request)? {
+                Next::Start => return Ok(true),
+                Next::Disconnect => return Ok(false),
+                Next::Stay | Next::Resume(_) => {}
+            }
+        }
+    }
+
+    /// Called by the debugger of the instance on every stop.
+    fn stop(&mut self, store: &mut impl AsStoreMut, frame: &DebugFrame) -> DebugAction {
+        if self.disconnected {
+            return DebugAction::Continue;
+        }
+        let location = self.sources.location(frame.func_index, frame.offset);
+        // A step runs until it reaches another line, through the code
+        // without one
+        if frame.reason == StopReason::Step
+            && self.step_from.is_some()
+            && (location.is_none() || location == self.step_from)
+        {
+            return DebugAction::Step;
+        }
+        self.step_from = None;
+        self.stop = Some(Stop {
+            frame: frame.clone(),
+            location,
+        });
+        let action = match self.wait(store, frame.reason) {
+            Ok(Some(action)) => action,
+            Ok(None) => {
+                self.disconnect(store);
+                DebugAction::Continue
+            }
+            Err(e) => {
+                warning!("lost the editor: {:#}", e);
+                self.disconnect(store);
+                DebugAction::Continue
+            }
+        };
+        self.stop = None;
+        action
+    }
+
+    /// Tells the editor about a stop, then handles its requests until it
+    /// resumes the program, or returns `None` once it's gone.
+    fn wait(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        reason: StopReason,
+    ) -> Result<Option<DebugAction>> {
+        self.send_output()?;
+        let reason = match reason {
+            StopReason::Breakpoint => "breakpoint",
+            StopReason::Step => "step",
+        };
+        self.connection.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        )?;
+        loop {
+            let request = match self.connection.read_request()? {
+                Some(request) => request,
+                None => return Ok(None),
+            };
+            match self.handle(store, &request)? {
+                Next::Resume(action) => return Ok(Some(action)),
+                Next::Disconnect => return Ok(None),
+                Next::Stay | Next::Start => {}
+            }
+        }
+    }
+
+    /// Kills the program once the editor is gone.
+    fn disconnect(&mut self, store: &mut impl AsStoreMut) {
+        self.disconnected = true;
+        self.instance.remove_debugger(store);
+        self.instance.interrupt_handle().interrupt();
+    }
+
+    /// Tells the editor the program ended, with its exit code or the
+    /// error it failed with, then handles its requests until it's gone.
+    fn finish(&mut self, store: &mut impl AsStoreMut, result: Result<u32, String>) -> Result<()> {
+        self.send_output()?;
+        let exit_code = match result {
+            Ok(code) => code,
+            Err(message) => {
+                self.connection.event(
+                    "output",
+                    json!({ "category": "stderr", "output": format!("{}\n", message) }),
+                )?;
+                1
+            }
+        };
+        self.connection
+            .event("exited", json!({ "exitCode": exit_code }))?;
+        self.connection.event("terminated", json!({}))?;
+        while let Some(request) = self.connection.read_request()? {
+            if let Next::Disconnect = self.handle(store, &request)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends what the program wrote since the last time.
+    fn send_output(&mut self) -> io::Result<()> {
+        for (category, pipe) in [("stdout", &self.stdout), ("stderr", &self.stderr)] {
+            let output = pipe.take_string_lossy();
+            if !output.is_empty() {
+                self.connection
+                    .event("output", json!({ "category": category, "output": output }))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, store: &mut impl AsStoreMut, request: &Request) -> Result<Next> {
+        let next = match request.command.as_str() {
+            "initialize" => {
+                self.connection
+                    .respond(request, json!({ "supportsConfigurationDoneRequest": true }))?;
+                // The program is ready for its breakpoints right away
+                self.connection.event("initialized", json!({}))?;
+                Next::Stay
+            }
+            "launch" | "attach" | "setExceptionBreakpoints" => {
+                self.connection.respond(request, json!({}))?;
+                Next::Stay
+            }
+            "configurationDone" => {
+                self.connection.respond(request, json!({}))?;
+                Next::Start
+            }
+            "setBreakpoints" => {
+                match serde_json::from_value(request.arguments.clone()) {
+                    Ok(arguments) => {
+                        let breakpoints = self.set_breakpoints(store, arguments);
+                        self.connection
+                            .respond(request, json!({ "breakpoints": breakpoints }))?;
+                    }
+                    Err(e) => self.connection.respond_error(request, e.to_string())?,
+                }
+                Next::Stay
+            }
+            "threads" => {
+                self.connection.respond(
+                    request,
+                    json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+                )?;
+                Next::Stay
+            }
+            "stackTrace" => {
+                let frames: Vec<Json> = self
+                    .stop
+                    .iter()
+                    .map(|stop| self.stack_frame(stop))
+                    .collect();
+                self.connection.respond(
+                    request,
+                    json!({ "stackFrames": frames, "totalFrames": frames.len() }),
+                )?;
+                Next::Stay
+            }
+            "scopes" => {
+                let scopes = if self.stop.is_some() {
+                    json!([
+                        { "name": "Locals", "variablesReference": LOCALS_REFERENCE, "expensive": false },
+                        { "name": "Operands", "variablesReference": OPERANDS_REFERENCE, "expensive": false },
+                    ])
+                } else {
+                    json!([])
+                };
+                self.connection
+                    .respond(request, json!({ "scopes": scopes }))?;
+                Next::Stay
+            }
+            "variables" => {
+                let reference = request.arguments["variablesReference"].as_u64();
+                let variables = self.variables(reference.unwrap_or(0));
+                self.connection
+                    .respond(request, json!({ "variables": variables }))?;
+                Next::Stay
+            }
+            "continue" | "next" | "stepIn" if self.stop.is_none() => {
+                self.connection
+                    .respond_error(request, "the program isn't stopped".to_string())?;
+                Next::Stay
+            }
+            "continue" => {
+                self.connection
+                    .respond(request, json!({ "allThreadsContinued": true }))?;
+                Next::Resume(DebugAction::Continue)
+            }
+            // Calls are stepped into, as the debugger steps instructions
+            "next" | "stepIn" => {
+                self.step_from = self.stop.as_ref().and_then(|stop| stop.location.clone());
+                self.connection.respond(request, json!({}))?;
+                Next::Resume(DebugAction::Step)
+            }
+            "disconnect" | "terminate" => {
+                self.connection.respond(request, json!({}))?;
+                Next::Disconnect
+            }
+            command => {
+                self.connection
+                    .respond_error(request, format!("`{}` isn't supported", command))?;
+                Next::Stay
+            }
+        };
+        Ok(next)
+    }
+
+    /// Replaces the breakpoints of a source, returning how they were set.
+    fn set_breakpoints(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        arguments: SetBreakpointsArguments,
+    ) -> Vec<Json> {
+        let path = arguments.source.path.unwrap_or_default();
+        for (func_index, offset) in self.breakpoints.remove(&path).unwrap_or_default() {
+            let _ = self.instance.clear_breakpoint(store, func_index, offset);
+        }
+        let mut set = Vec::new();
+        let mut breakpoints = Vec::new();
+        for breakpoint in arguments.breakpoints {
+            let (line, locations) = self
+                .sources
+                .breakpoints(&path, breakpoint.line)
+                .unwrap_or((breakpoint.line, Vec::new()));
+            let mut verified = false;
+            for (func_index, offset) in locations {
+                if self
+                    .instance
+                    .set_breakpoint(store, func_index, offset)
+                    .is_ok()
+                {
+                    set.push((func_index, offset));
+                    verified = true;
+                }
+            }
+            breakpoints.push(if verified {
+                json!({ "verified": true, "line": line })
+            } else {
+                json!({ "verified": false, "line": line, "message": "no code at this line" })
+            });
+        }
+        self.breakpoints.insert(path, set);
+        breakpoints
+    }
+
+    fn stack_frame(&self, stop: &Stop) -> Json {
+        let frame = &stop.frame;
+        let mut stack_frame = json!({
+            "id": 1,
+            "name": self.sources.function_name(frame.func_index),
+            "line": 0,
+            "column": 0,
+            "instructionPointerReference": format!("{}+{:#x}", frame.func_index, frame.offset),
+        });
+        if let Some((path, line)) = &stop.location {
+            let name = Path::new(path)
+                .file_name()
+                .map_or_else(|| path.clone(), |name| name.to_string_lossy().into_owned());
+            stack_frame["source"] = json!({ "name": name, "path": path });
+            stack_frame["line"] = json!(line);
+            stack_frame["column"] = json!(1);
+        }
+        stack_frame
+    }
+
+    fn variables(&self, reference: u64) -> Vec<Json> {
+        let frame = match &self.stop {
+            Some(stop) => &stop.frame,
+            None => return Vec::new(),
+        };
+        match reference {
+            LOCALS_REFERENCE => frame
+                .locals
+                .iter()
+                .enumerate()
+                .map(|(index, value)| {
+                    json!({
+                        "name": format!("local{}", index),
+                        "value": value.to_string(),
+                        "type": value.ty().to_string(),
+                        "variablesReference": 0,
+                    })
+                })
+                .collect(),
+            OPERANDS_REFERENCE => frame
+                .operands
+                .iter()
+                .enumerate()
+                .map(|(index, operand)| {
+                    json!({
+                        "name": format!("[{}]", index),
+                        "value": format!("{:#x}", operand),
+                        "variablesReference": 0,
+                    })
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// A request of the editor.
+#[derive(Debug, Deserialize)]
+struct Request {
+    seq: u64,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    command: String,
+    #[serde(default)]
+    arguments: Json,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetBreakpointsArguments {
+    source: Source,
+    #[serde(default)]
+    breakpoints: Vec<SourceBreakpoint>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Source {
+    path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SourceBreakpoint {
+    line: u64,
+}
+
+/// The connection to the editor: messages made of a `Content-Length`
+/// header, an empty line, then a JSON body.
+struct Connection {
+    reader: Box<dyn BufRead + Send>,
+    writer: Box<dyn Write + Send>,
+    seq: u64,
+}
+
+impl Connection {
+    fn new(reader: impl Read + Send + 'static, writer: impl Write + Send + 'static) -> Self {
+        Self {
+            reader: Box::new(BufReader::new(reader)),
+            writer: Box::new(writer),
+            seq: 0,
+        }
+    }
+
+    /// Reads the next request, skipping the other messages, or returns
+    /// `None` once the editor closed the connection.
+    fn read_request(&mut self) -> Result<Option<Request>> {
+        loop {
+            let mut length = None;
+            loop {
+                let mut line = String::new();
+                if self.reader.read_line(&mut line)? == 0 {
+                    return Ok(None);
+                }
+                let line = line.trim_end();
+                if line.is_empty() {
+                    if length.is_some() {
+                        break;
+                    }
+                    continue;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.trim().eq_ignore_ascii_case("Content-Length") {
+                        length = Some(
+                            value
+                                .trim()
+                                .parse::<usize>()
+                                .context("invalid `Content-Length`")?,
+                        );
+                    }
+                }
+            }
+            let mut body = vec![0; length.unwrap()];
+            self.reader.read_exact(&mut body)?;
+            let request: Request =
+                serde_json::from_slice(&body).context("invalid protocol message")?;
+            if request.kind == "request" {
+                return Ok(Some(request));
+            }
+        }
+    }
+
+    fn send(&mut self, mut message: Json) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        let body = message.to_string();
+        write!(
+            self.writer,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+        self.writer.flush()
+    }
+
+    fn respond(&mut self, request: &Request, body: Json) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request.seq,
+            "success": true,
+            "command": request.command,
+            "body": body,
+        }))
+    }
+
+    fn respond_error(&mut self, request: &Request, message: String) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request.seq,
+            "success": false,
+            "command": request.command,
+            "message": message,
+        }))
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A writer whose output can be read after the connection owns it.
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn connections_frame_messages() {
+        let input = concat!(
+            "Content-Length: 58\r\n\r\n",
+            r#"{"seq":1,"type":"response","request_seq":1,"success":true}"#,
+            "Content-Length: 77\r\n\r\n",
+            r#"{"seq":2,"type":"request","command":"threads","arguments":{"filter":"all"}}  "#,
+        );
+        let output = Output::default();
+        let mut connection = Connection::new(input.as_bytes(), output.clone());
+
+        // Responses of the editor are skipped
+        let request = connection.read_request().unwrap().unwrap();
+        assert_eq!(request.seq, 2);
+        assert_eq!(request.command, "threads");
+        assert_eq!(request.arguments["filter"], "all");
+        assert!(connection.read_request().unwrap().is_none());
+
+        connection.respond(&request, json!({})).unwrap();
+        connection.event("terminated", json!({})).unwrap();
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let messages: Vec<Json> = output
+            .split("Content-Length: ")
+            .skip(1)
+            .map(|message| {
+                let (length, body) = message.split_once("\r\n\r\n").unwrap();
+                assert_eq!(length.parse::<usize>().unwrap(), body.len());
+                serde_json::from_str(body).unwrap()
+            })
+            .collect();
+        assert_eq!(messages[0]["seq"], 1);
+        assert_eq!(messages[0]["request_seq"], 2);
+        assert_eq!(messages[0]["success"], true);
+        assert_eq!(messages[1]["seq"], 2);
+        assert_eq!(messages[1]["event"], "terminated");
+    }
+}
@@ -0,0 +1,23 @@
+use anyhow::Result;
+use clap::{Command, Parser};
+use clap_complete::Shell;
+use std::io;
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer gen-completions` subcommand
+pub struct GenCompletions {
+    /// The shell to print the completions for: `bash`, `zsh`, `fish`,
+    /// `powershell` or `elvish`
+    #[clap(name = "SHELL")]
+    shell: Shell,
+}
+
+impl GenCompletions {
+    /// Runs logic for the `gen-completions` subcommand, printing the
+    /// completions of `command` and its subcommands
+    pub fn execute(&self, command: &mut Command) -> Result<()> {
+        let name = command.get_name().to_string();
+        clap_complete::generate(self.shell, command, name, &mut io::stdout());
+        Ok(())
+    }
+}
@@ -30,10 +30,28 @@ impl Inspect {
         let module = Module::new(&store, module_contents)?;
         println!("Type: {}", if !iswasm { "wat" } else { "wasm" });
         println!("Size: {}", ByteSize(module_len as _));
+        if let Some(name) = module.name() {
+            println!("Name: {}", name);
+        }
+        if !module.metadata().is_empty() {
+            println!("Metadata:");
+            for (key, value) in module.metadata() {
+                println!("  {}: {}", key, value);
+            }
+        }
         println!("Imports:");
         println!("  Functions:");
         for f in module.imports().functions() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+            println!(
+                "    \"{}\".\"{}\": {}{}",
+                f.module(),
+                f.name(),
+                f.ty(),
+                internal_name(
+                    module.info().imported_function_name(f.module(), f.name()),
+                    f.name()
+                )
+            );
         }
         println!("  Memories:");
         for f in module.imports().memories() {
@@ -50,7 +68,12 @@ impl Inspect {
         println!("Exports:");
         println!("  Functions:");
         for f in module.exports().functions() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+            println!(
+                "    \"{}\": {}{}",
+                f.name(),
+                f.ty(),
+                internal_name(module.info().exported_function_name(f.name()), f.name())
+            );
         }
         println!("  Memories:");
         for f in module.exports().memories() {
@@ -67,3 +90,12 @@ impl Inspect {
         Ok(())
     }
 }
+
+/// The name the name section gives to a function imported or exported as
+/// `field`, to print after it if it's another one.
+fn internal_name(name: Option<&str>, field: &str) -> String {
+    match name {
+        Some(name) if name != field => format!(" (`{}` in the name section)", name),
+        _ => String::new(),
+    }
+}
@@ -1,41 +1,172 @@
 use crate::common::get_cache_dir;
+use crate::error::PrettyError;
 #[cfg(feature = "debug")]
 use crate::logging;
 use crate::store::{CompilerType, StoreOptions};
 use crate::suggestions::suggest_function_exports;
 use crate::warning;
 use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{self, Read};
 use std::path::PathBuf;
 use std::str::FromStr;
+#[cfg(feature = "compiler")]
+use std::sync::Arc;
 use wasmer::FunctionEnv;
 use wasmer::*;
+#[cfg(feature = "remote-cache")]
+use wasmer_cache::RemoteCache;
 #[cfg(feature = "cache")]
-use wasmer_cache::{Cache, FileSystemCache, Hash};
-use wasmer_types::Type as ValueType;
+use wasmer_cache::{Cache, FileSystemCache, Hash, HashAlgorithm};
 
 use clap::Parser;
 
+#[cfg(feature = "compiler")]
+mod compare;
+mod coredump;
+#[cfg(feature = "compiler")]
+mod coverage;
+mod imports;
+mod invoke;
+#[cfg(feature = "wasi")]
+mod journal;
+#[cfg(feature = "compiler")]
+mod limits;
+mod link;
+#[cfg(feature = "http")]
+mod package;
+#[cfg(unix)]
+mod profile;
+mod repl;
+#[cfg(feature = "compiler")]
+mod stats;
+mod timeout;
 #[cfg(feature = "wasi")]
 mod wasi;
+mod watch;
 
+#[cfg(feature = "compiler")]
+use compare::Outcome;
+use coredump::CoredumpOptions;
+#[cfg(feature = "compiler")]
+use coverage::CoverageOptions;
+use imports::ImportsConfig;
+use invoke::{Invocation, Invocations, OutputFormat};
+#[cfg(feature = "compiler")]
+use limits::LimitOptions;
+use link::LinkOptions;
+#[cfg(feature = "http")]
+use package::{Package, PackageSpecifier};
+#[cfg(unix)]
+use profile::ProfileOptions;
+#[cfg(feature = "compiler")]
+use stats::StatsOptions;
+use std::time::Duration;
+pub(crate) use timeout::{parse_duration, Watchdog};
 #[cfg(feature = "wasi")]
 use wasi::Wasi;
+use watch::Watcher;
 
 #[derive(Debug, Parser, Clone, Default)]
 /// The options for the `wasmer run` subcommand
+#[clap(allow_negative_numbers = true)]
 pub struct Run {
     /// Disable the cache
     #[cfg(feature = "cache")]
     #[clap(long = "disable-cache")]
     disable_cache: bool,
 
-    /// File to run
+    /// File to run, `-` to read the module from stdin, or package of the
+    /// registry to run its entrypoint command with `--package` (e.g.
+    /// `namespace/name@1.2.0`)
     #[clap(name = "FILE", parse(from_os_str))]
     path: PathBuf,
 
-    /// Invoke a specified function
-    #[clap(long = "invoke", short = 'i')]
-    invoke: Option<String>,
+    /// Run the package of the registry named by FILE
+    #[cfg(feature = "http")]
+    #[clap(long = "package")]
+    package: bool,
+
+    #[clap(flatten)]
+    invoke: Invocations,
+
+    /// How the results of the invoked functions are printed: `text`, or
+    /// `json` for a JSON array
+    #[clap(long = "output", name = "FORMAT", default_value = "text")]
+    output: OutputFormat,
+
+    /// Instantiate the module once, then read `function args...` lines
+    /// from stdin and invoke them, keeping the state of the instance
+    /// between calls
+    #[clap(long = "interactive", conflicts_with = "invoke")]
+    interactive: bool,
+
+    /// Run this function as the command of the module, instead of
+    /// `_start`
+    #[clap(
+        long = "entrypoint",
+        name = "FUNCTION",
+        conflicts_with_all = &["invoke", "interactive"]
+    )]
+    entrypoint: Option<String>,
+
+    /// Don't run the start function of the module when instantiating it,
+    /// nor the `_initialize` function of reactors
+    #[clap(long = "no-start")]
+    no_start: bool,
+
+    /// Invoke the function with each of these compilers (e.g.
+    /// `singlepass,cranelift`), and fail if their results or traps differ
+    #[cfg(feature = "compiler")]
+    #[clap(
+        long = "compare",
+        name = "COMPILERS",
+        use_value_delimiter = true,
+        requires = "invoke"
+    )]
+    compare: Vec<CompilerType>,
+
+    /// Interrupt the guest and exit with code 124 if the entrypoint or the
+    /// invoked functions don't finish within this time (e.g. `500ms`, `5s`
+    /// or `2m`)
+    #[clap(long = "timeout", name = "DURATION", parse(try_from_str = parse_duration))]
+    timeout: Option<Duration>,
+
+    /// Run the module again, recompiled, whenever its file changes
+    #[clap(long = "watch", conflicts_with = "interactive")]
+    watch: bool,
+
+    /// With `--watch`, also run the module again when the files of its
+    /// preopened and mapped directories change
+    #[cfg(feature = "wasi")]
+    #[clap(long = "watch-dirs", requires = "watch")]
+    watch_dirs: bool,
+
+    /// A JSON file describing additional imports to provide to the module
+    #[clap(long = "imports", name = "IMPORTS_CONFIG", parse(from_os_str))]
+    imports_config: Option<PathBuf>,
+
+    /// Fill the function imports nothing provides with stubs trapping when
+    /// called, instead of failing to instantiate the module
+    #[clap(long = "stub-missing-imports")]
+    stub_missing_imports: bool,
+
+    /// Allow the `native` imports of `--imports` to load this host shared
+    /// library. Its functions run outside of the sandbox, with all the
+    /// privileges of wasmer. Can be repeated
+    #[clap(long = "allow-native", name = "LIBRARY", parse(from_os_str))]
+    allow_native: Vec<PathBuf>,
+
+    /// Only load a precompiled module if it was signed by `wasmer compile
+    /// --sign` with the private key of this Ed25519 public key, read from
+    /// a PEM file. Can be repeated to trust several keys
+    #[clap(long = "require-signature", name = "PUBLIC_KEY", parse(from_os_str))]
+    require_signature: Vec<PathBuf>,
+
+    #[clap(flatten)]
+    link: LinkOptions,
 
     /// The command name is a string that will override the first argument passed
     /// to the wasm program. This is used in wapm to provide nicer output in
@@ -50,9 +181,26 @@ pub struct Run {
     #[clap(long = "cache-key", hide = true)]
     cache_key: Option<String>,
 
+    /// The algorithm the module is hashed with to look it up in the cache
+    /// (`blake3` or `sha256`)
+    #[cfg(feature = "cache")]
+    #[clap(long = "cache-hash-algorithm", default_value = "blake3")]
+    cache_hash_algorithm: HashAlgorithm,
+
+    /// The size the cache of compiled modules is bounded to (e.g. `2GB`),
+    /// evicting the least recently used ones. Defaults to the
+    /// `WASMER_CACHE_MAX_SIZE` environment variable, or no bound
+    #[cfg(feature = "cache")]
+    #[clap(long = "cache-max-size", name = "SIZE")]
+    cache_max_size: Option<bytesize::ByteSize>,
+
     #[clap(flatten)]
     store: StoreOptions,
 
+    #[cfg(feature = "compiler")]
+    #[clap(flatten)]
+    limits: LimitOptions,
+
     // TODO: refactor WASI structure to allow shared options with Emscripten
     #[cfg(feature = "wasi")]
     #[clap(flatten)]
@@ -63,6 +211,27 @@ pub struct Run {
     #[clap(long = "enable-io-devices")]
     enable_experimental_io_devices: bool,
 
+    /// Serve the runtime state of this process to `wasmer attach <pid>`
+    /// while it runs
+    #[cfg(unix)]
+    #[clap(long = "diagnostics")]
+    diagnostics: bool,
+
+    #[cfg(unix)]
+    #[clap(flatten)]
+    profile: ProfileOptions,
+
+    #[cfg(feature = "compiler")]
+    #[clap(flatten)]
+    coverage: CoverageOptions,
+
+    #[clap(flatten)]
+    coredump: CoredumpOptions,
+
+    #[cfg(feature = "compiler")]
+    #[clap(flatten)]
+    stats: StatsOptions,
+
     /// Enable debug output
     #[cfg(feature = "debug")]
     #[clap(long = "debug", short = 'd')]
@@ -84,6 +253,33 @@ impl Run {
         if self.debug {
             logging::set_up_logging(self.verbose).unwrap();
         }
+        if self.interactive && self.reads_stdin() {
+            bail!("`--interactive` reads its commands from stdin, so the module can't be read from it");
+        }
+        #[cfg(feature = "wasi")]
+        self.check_hardening()?;
+        #[cfg(unix)]
+        let _diagnostics = if self.diagnostics {
+            Some(
+                wasmer::diagnostics::DiagnosticServer::start()
+                    .context("failed to start the diagnostic server")?,
+            )
+        } else {
+            None
+        };
+        #[cfg(feature = "http")]
+        if let Some(specifier) = self.package_specifier()? {
+            let package = Package::fetch(&specifier)
+                .with_context(|| format!("failed to fetch the package `{}`", specifier))?;
+            return self.with_package(package).execute();
+        }
+        if self.watch {
+            return self.run_watched();
+        }
+        self.run_once()
+    }
+
+    fn run_once(&self) -> Result<()> {
         self.inner_execute().with_context(|| {
             format!(
                 "failed to run `{}`{}",
@@ -97,28 +293,114 @@ impl Run {
         })
     }
 
-    fn inner_module_run(&self, mut store: Store, instance: Instance) -> Result<()> {
-        // If this module exports an _initialize function, run that first.
-        if let Ok(initialize) = instance.exports.get_function("_initialize") {
-            initialize
-                .call(&mut store, &[])
-                .with_context(|| "failed to run _initialize function")?;
+    /// Runs the module, then runs it again whenever the watched files
+    /// change, until the process is killed. The failures of a run are
+    /// printed, as the next change may fix them.
+    fn run_watched(&self) -> Result<()> {
+        if self.reads_stdin() {
+            bail!("`--watch` needs a module file to watch, not stdin");
+        }
+        let mut paths = vec![self.path.clone()];
+        #[cfg(feature = "wasi")]
+        if self.watch_dirs {
+            paths.extend(self.wasi.directories().cloned());
+        }
+        let mut watcher = Watcher::new(paths);
+        // The prehashed key is the one of the initial contents, which the
+        // runs after a change must not load from the cache
+        #[cfg(feature = "cache")]
+        let rerun = Self {
+            cache_key: None,
+            ..self.clone()
+        };
+        #[cfg(not(feature = "cache"))]
+        let rerun = self.clone();
+        let mut run = self;
+        loop {
+            if let Err(error) = run.run_once() {
+                PrettyError::print(error);
+            }
+            eprintln!("{}", "Waiting for changes...".bold());
+            watcher.wait();
+            run = &rerun;
+        }
+    }
+
+    /// Runs the instance, which `interrupt` stops if it doesn't finish
+    /// within `--timeout`.
+    fn inner_module_run(
+        &self,
+        mut store: Store,
+        instance: Instance,
+        interrupt: impl FnOnce() + Send + 'static,
+    ) -> Result<()> {
+        if self.interactive {
+            let result = repl::run(&mut store, &instance, self.output);
+            #[cfg(feature = "compiler")]
+            self.coverage
+                .finish(&mut store, &instance, &self.module_name())?;
+            return result;
         }
 
-        // Do we want to invoke a function?
-        if let Some(ref invoke) = self.invoke {
-            let result = self.invoke_function(&mut store, &instance, invoke, &self.args)?;
-            println!(
-                "{}",
-                result
-                    .iter()
-                    .map(|val| val.to_string())
-                    .collect::<Vec<String>>()
-                    .join(" ")
-            );
+        match self.timeout {
+            Some(timeout) => {
+                let watchdog = Watchdog::start(timeout, interrupt)?;
+                watchdog.check(self.run_instance(store, instance))
+            }
+            None => self.run_instance(store, instance),
+        }
+    }
+
+    fn run_instance(&self, mut store: Store, instance: Instance) -> Result<()> {
+        // Do we want to invoke functions?
+        if !self.invoke.is_empty() {
+            // The calls share the instance, and stop at the first failure
+            let result: Result<()> = self.invoke.calls().iter().try_for_each(|call| {
+                #[cfg(feature = "compiler")]
+                let meter = self.stats.start(&mut store, &instance);
+                let result = self.invoke_function(&mut store, &instance, call);
+                #[cfg(feature = "compiler")]
+                self.stats
+                    .finish(meter, &mut store, &instance, &call.function);
+                println!("{}", invoke::format_results(&result?, self.output));
+                Ok(())
+            });
+            if let Some(error) = result
+                .as_ref()
+                .err()
+                .and_then(|e| e.downcast_ref::<RuntimeError>())
+            {
+                self.coredump
+                    .write(&mut store, &instance, &self.module_name(), error)?;
+            }
+            #[cfg(feature = "compiler")]
+            self.coverage
+                .finish(&mut store, &instance, &self.module_name())?;
+            result?;
         } else {
-            let start: Function = self.try_find_function(&instance, "_start", &[])?;
+            if self.entrypoint.is_none() && self.is_reactor(instance.module()) {
+                bail!("the module is a reactor, exporting `_initialize` but no `_start`: call one of its functions with `--invoke`");
+            }
+            let entrypoint = self.entrypoint();
+            let start: Function = self.try_find_function(&instance, entrypoint, &[])?;
+            #[cfg(unix)]
+            let profiler = self.profile.start()?;
+            #[cfg(feature = "compiler")]
+            let meter = self.stats.start(&mut store, &instance);
             let result = start.call(&mut store, &[]);
+            #[cfg(feature = "compiler")]
+            self.stats.finish(meter, &mut store, &instance, entrypoint);
+            if let Err(error) = &result {
+                self.coredump
+                    .write(&mut store, &instance, &self.module_name(), error)?;
+            }
+            #[cfg(unix)]
+            if let Some(profiler) = profiler {
+                self.profile.finish(profiler)?;
+            }
+            #[cfg(feature = "compiler")]
+            self.coverage
+                .finish(&mut store, &instance, &self.module_name())?;
             #[cfg(feature = "wasi")]
             self.wasi.handle_result(result)?;
             #[cfg(not(feature = "wasi"))]
@@ -129,7 +411,17 @@ impl Run {
     }
 
     fn inner_execute(&self) -> Result<()> {
-        let (mut store, module) = self.get_store_module()?;
+        #[cfg(feature = "compiler")]
+        if !self.compare.is_empty() {
+            return match self.invoke.calls() {
+                [call] => self.compare_compilers(call),
+                _ => bail!("`--compare` invokes a single function"),
+            };
+        }
+        let (store, module) = self.get_store_module()?;
+        #[cfg(feature = "compiler")]
+        let store = self.limits.apply(store)?;
+        let mut store = store;
         #[cfg(feature = "emscripten")]
         {
             use wasmer_emscripten::{
@@ -170,19 +462,43 @@ impl Run {
                         self.path.to_str().unwrap()
                     },
                     self.args.iter().map(|arg| arg.as_str()).collect(),
-                    self.invoke.clone(),
+                    match self.invoke.calls() {
+                        [] => None,
+                        [call] => Some(call.function.clone()),
+                        _ => bail!("Emscripten modules can only invoke a single function"),
+                    },
                 )?;
                 return Ok(());
             }
         }
 
+        let imports_config = match &self.imports_config {
+            Some(path) => ImportsConfig::from_path(path)?,
+            None => ImportsConfig::default(),
+        };
+        let mut extra_imports = imports_config.to_imports(&mut store, &self.allow_native)?;
+        if self.stub_missing_imports {
+            extra_imports.allow_missing();
+        }
+        self.link.link(&mut store, &mut extra_imports)?;
+
         // If WASI is enabled, try to execute it with it
         #[cfg(feature = "wasi")]
         let ret = {
             use std::collections::BTreeSet;
             use wasmer_wasi::WasiVersion;
 
-            let wasi_versions = Wasi::get_versions(&module);
+            // The other imports are provided, linked or stubbed, so they don't
+            // prevent running the module with WASI
+            let wasi_versions = if imports_config.wasi
+                || self.stub_missing_imports
+                || !self.link.is_empty()
+                || self.wasi.allows_extra_imports()
+            {
+                Wasi::get_versions_lenient(&module)
+            } else {
+                Wasi::get_versions(&module)
+            };
             match wasi_versions {
                 Some(wasi_versions) if !wasi_versions.is_empty() => {
                     if wasi_versions.len() >= 2 {
@@ -205,28 +521,42 @@ impl Run {
                     let program_name = self
                         .command_name
                         .clone()
-                        .or_else(|| {
-                            self.path
-                                .file_name()
-                                .map(|f| f.to_string_lossy().to_string())
-                        })
-                        .unwrap_or_default();
-                    let (_ctx, instance) = self
+                        .unwrap_or_else(|| self.module_name());
+                    let (ctx, instance) = self
                         .wasi
-                        .instantiate(&mut store, &module, program_name, self.args.clone())
+                        .instantiate(
+                            &mut store,
+                            &module,
+                            program_name,
+                            self.args.clone(),
+                            &extra_imports,
+                            self.initialization(&module),
+                        )
                         .with_context(|| "failed to instantiate WASI module")?;
-                    self.inner_module_run(store, instance)
+                    // Blocking WASI calls are interrupted too
+                    let interrupt = instance.interrupt_handle();
+                    let wasi_interrupt = ctx.as_ref(&store).interrupt_handle();
+                    self.inner_module_run(store, instance, move || {
+                        interrupt.interrupt();
+                        wasi_interrupt.interrupt();
+                    })
                 }
                 // not WASI
                 _ => {
-                    let instance = Instance::new(&mut store, &module, &imports! {})?;
-                    self.inner_module_run(store, instance)
+                    let instance = Instance::new_with_initialization(
+                        &mut store,
+                        &module,
+                        &extra_imports,
+                        self.initialization(&module),
+                    )?;
+                    let interrupt = instance.interrupt_handle();
+                    self.inner_module_run(store, instance, move || interrupt.interrupt())
                 }
             }
         };
         #[cfg(not(feature = "wasi"))]
         let ret = {
-            let instance = Instance::new(&module, &imports! {})?;
+            let instance = Instance::new(&module, &extra_imports)?;
 
             // If this module exports an _initialize function, run that first.
             if let Ok(initialize) = instance.exports.get_function("_initialize") {
@@ -235,17 +565,19 @@ impl Run {
                     .with_context(|| "failed to run _initialize function")?;
             }
 
-            // Do we want to invoke a function?
-            if let Some(ref invoke) = self.invoke {
-                let result = self.invoke_function(&instance, invoke, &self.args)?;
-                println!(
-                    "{}",
-                    result
-                        .iter()
-                        .map(|val| val.to_string())
-                        .collect::<Vec<String>>()
-                        .join(" ")
-                );
+            // Do we want to invoke functions?
+            if !self.invoke.is_empty() {
+                for call in self.invoke.calls() {
+                    let result = self.invoke_function(&instance, call)?;
+                    println!(
+                        "{}",
+                        result
+                            .iter()
+                            .map(|val| val.to_string())
+                            .collect::<Vec<String>>()
+                            .join(" ")
+                    );
+                }
             } else {
                 let start: Function = self.try_find_function(&instance, "_start", &[])?;
                 let result = start.call(&[]);
@@ -259,23 +591,170 @@ impl Run {
         ret
     }
 
+    /// Invokes the function with each of the compilers of `--compare`,
+    /// in a fresh store and instance every time.
+    #[cfg(feature = "compiler")]
+    fn compare_compilers(&self, call: &Invocation) -> Result<()> {
+        if self.compare.len() < 2 {
+            bail!("`--compare` needs at least two compilers");
+        }
+        let bytes = if self.reads_stdin() {
+            let mut bytes = Vec::new();
+            io::stdin()
+                .lock()
+                .read_to_end(&mut bytes)
+                .context("failed to read the module from stdin")?;
+            bytes
+        } else {
+            std::fs::read(&self.path)?
+        };
+        let outcomes = self
+            .compare
+            .iter()
+            .map(|compiler| {
+                self.invoke_with_compiler(compiler, &bytes, call)
+                    .unwrap_or_else(|e| Outcome::of_error(compiler.clone(), e))
+            })
+            .collect::<Vec<_>>();
+        compare::report(&outcomes)
+    }
+
+    #[cfg(feature = "compiler")]
+    fn invoke_with_compiler(
+        &self,
+        compiler: &CompilerType,
+        bytes: &[u8],
+        call: &Invocation,
+    ) -> Result<Outcome> {
+        let (store, _) = self.store.with_compiler(compiler).get_store()?;
+        let mut store = self.limits.apply(store)?;
+        let module = Module::new(&store, bytes)?;
+        let imports_config = match &self.imports_config {
+            Some(path) => ImportsConfig::from_path(path)?,
+            None => ImportsConfig::default(),
+        };
+        let mut extra_imports = imports_config.to_imports(&mut store, &self.allow_native)?;
+        if self.stub_missing_imports {
+            extra_imports.allow_missing();
+        }
+        self.link.link(&mut store, &mut extra_imports)?;
+        #[cfg(feature = "wasi")]
+        let instance = if Wasi::has_wasi_imports(&module) {
+            self.wasi
+                .instantiate(
+                    &mut store,
+                    &module,
+                    self.module_name(),
+                    self.args.clone(),
+                    &extra_imports,
+                    self.initialization(&module),
+                )?
+                .1
+        } else {
+            Instance::new_with_initialization(
+                &mut store,
+                &module,
+                &extra_imports,
+                self.initialization(&module),
+            )?
+        };
+        #[cfg(not(feature = "wasi"))]
+        let instance = Instance::new_with_initialization(
+            &mut store,
+            &module,
+            &extra_imports,
+            self.initialization(&module),
+        )?;
+        let func = self.try_find_function(&instance, &call.function, &call.args)?;
+        let func_ty = func.ty(&store);
+        let params =
+            invoke::parse_args(&mut store, &instance, &call.function, &func_ty, &call.args)?;
+        let result = func.call(&mut store, &params);
+        Ok(Outcome::of_call(compiler.clone(), result, self.output))
+    }
+
     fn get_store_module(&self) -> Result<(Store, Module)> {
-        let contents = std::fs::read(self.path.clone())?;
-        if wasmer_compiler::Artifact::is_deserializable(&contents) {
+        // Regular files are mapped rather than read, so that hashing them
+        // for the cache doesn't need a copy. Anything else, like stdin or a
+        // FIFO such as `<(...)`, can neither be mapped nor opened twice, so
+        // it is read.
+        let file = if self.reads_stdin() {
+            None
+        } else {
+            Some(File::open(&self.path)?)
+        };
+        let mut read = Vec::new();
+        let mapped = match file {
+            Some(file) if file.metadata()?.is_file() => Some(unsafe { Mmap::map(&file)? }),
+            Some(mut file) => {
+                file.read_to_end(&mut read)
+                    .with_context(|| format!("failed to read {}", self.path.display()))?;
+                None
+            }
+            None => {
+                io::stdin()
+                    .lock()
+                    .read_to_end(&mut read)
+                    .context("failed to read the module from stdin")?;
+                None
+            }
+        };
+        let contents: &[u8] = mapped.as_deref().unwrap_or(&read);
+        if wasmer_compiler::Artifact::is_deserializable(contents) {
+            #[cfg(feature = "compiler")]
+            if self.coverage.is_enabled() {
+                bail!("`--coverage` needs a module to compile, not a precompiled one");
+            }
+            #[cfg(feature = "compiler")]
+            if self.limits.instruments() {
+                bail!("`--max-call-depth` needs a module to compile, not a precompiled one");
+            }
             let engine = wasmer_compiler::EngineBuilder::headless();
+            #[cfg(feature = "compiler")]
+            let engine = engine
+                .set_perf(self.store.perf())
+                .set_stack_size(self.store.stack_size());
             let store = Store::new(engine);
-            let module = unsafe { Module::deserialize_from_file(&store, &self.path)? };
+            let module = if !self.require_signature.is_empty() {
+                self.deserialize_signed(&store, contents)?
+            } else if mapped.is_some() {
+                unsafe { Module::deserialize_from_file(&store, &self.path)? }
+            } else {
+                unsafe { Module::deserialize(&store, contents)? }
+            };
             return Ok((store, module));
         }
+        #[cfg(feature = "compiler")]
+        let (store, compiler_type) = {
+            let mut middlewares: Vec<Arc<dyn ModuleMiddleware>> = vec![];
+            if let Some(coverage) = self.coverage.middleware(contents)? {
+                middlewares.push(coverage);
+            }
+            if let Some(call_depth_limit) = self.limits.middleware() {
+                middlewares.push(call_depth_limit);
+            }
+            if let Some(instruction_counter) = self.stats.middleware() {
+                middlewares.push(instruction_counter);
+            }
+            self.store.get_store_with_middlewares(middlewares)?
+        };
+        #[cfg(not(feature = "compiler"))]
         let (store, compiler_type) = self.store.get_store()?;
+        // Modules loaded from the cache aren't registered with debuggers,
+        // nor instrumented for coverage, call depth limits or instruction
+        // counts
         #[cfg(feature = "cache")]
-        let module_result: Result<Module> = if !self.disable_cache && contents.len() > 0x1000 {
-            self.get_module_from_cache(&store, &contents, &compiler_type)
+        let module_result: Result<Module> = if !self.disable_cache
+            && !self.store.debug_info()
+            && !self.instrumented()
+            && contents.len() > 0x1000
+        {
+            self.get_module_from_cache(&store, contents, &compiler_type)
         } else {
             Module::new(&store, contents).map_err(|e| e.into())
         };
         #[cfg(not(feature = "cache"))]
-        let module_result = Module::new(&store, &contents);
+        let module_result = Module::new(&store, contents);
 
         let mut module = module_result.with_context(|| {
             format!(
@@ -284,11 +763,39 @@ impl Run {
             )
         })?;
         // We set the name outside the cache, to make sure we dont cache the name
-        module.set_name(&self.path.file_name().unwrap_or_default().to_string_lossy());
+        module.set_name(&self.module_name());
 
         Ok((store, module))
     }
 
+    /// Deserializes the precompiled module `contents`, if it is signed by
+    /// one of the keys of `--require-signature`.
+    #[cfg(feature = "signing")]
+    fn deserialize_signed(&self, store: &Store, contents: &[u8]) -> Result<Module> {
+        Module::deserialize_checked(store, contents, &self.trusted_keys()?)
+            .context("refusing to load the precompiled module")
+    }
+
+    /// The public keys of `--require-signature`.
+    #[cfg(feature = "signing")]
+    fn trusted_keys(&self) -> Result<Vec<VerifyingKey>> {
+        self.require_signature
+            .iter()
+            .map(|path| {
+                let pem = std::fs::read_to_string(path).with_context(|| {
+                    format!("failed to read the public key `{}`", path.display())
+                })?;
+                VerifyingKey::from_pem(&pem)
+                    .with_context(|| format!("invalid public key `{}`", path.display()))
+            })
+            .collect()
+    }
+
+    #[cfg(not(feature = "signing"))]
+    fn deserialize_signed(&self, _store: &Store, _contents: &[u8]) -> Result<Module> {
+        bail!("wasmer was built without the `signing` feature")
+    }
+
     #[cfg(feature = "cache")]
     fn get_module_from_cache(
         &self,
@@ -300,14 +807,21 @@ impl Run {
         // and the file length is greater than 4KB.
         // For files smaller than 4KB caching is not worth,
         // as it takes space and the speedup is minimal.
-        let mut cache = self.get_cache(compiler_type)?;
+        let cache = self.get_cache(compiler_type)?;
+        #[cfg(feature = "remote-cache")]
+        let mut cache = self.remote_cache(cache, store, compiler_type)?;
+        #[cfg(not(feature = "remote-cache"))]
+        let mut cache = cache;
         // Try to get the hash from the provided `--cache-key`, otherwise
-        // generate one from the provided file `.wasm` contents.
+        // generate one from the provided file `.wasm` contents. The key is
+        // derived from it and the engine settings, so that modules compiled
+        // with other compilers, flags or CPU features are never loaded.
         let hash = self
             .cache_key
             .as_ref()
             .and_then(|key| Hash::from_str(key).ok())
-            .unwrap_or_else(|| Hash::generate(contents));
+            .unwrap_or_else(|| Hash::generate_with(self.cache_hash_algorithm, contents))
+            .for_engine(store.engine());
         match unsafe { cache.load(store, hash) } {
             Ok(module) => Ok(module),
             Err(e) => {
@@ -316,12 +830,15 @@ impl Run {
                         // Do not notify on IO errors
                     }
                     err => {
-                        warning!("cached module is corrupted: {}", err);
+                        warning!("failed to load the cached module: {}", err);
                     }
                 }
                 let module = Module::new(store, contents)?;
-                // Store the compiled Module in cache
-                cache.store(hash, &module)?;
+                // Store the compiled Module in cache, the module can run
+                // even if that fails
+                if let Err(err) = cache.store(hash, &module) {
+                    warning!("failed to cache the module: {}", err);
+                }
                 Ok(module)
             }
         }
@@ -336,9 +853,186 @@ impl Run {
 
         let extension = "wasmu";
         cache.set_cache_extension(Some(extension));
+        let max_size = match self.cache_max_size {
+            Some(max_size) => Some(max_size),
+            None => std::env::var("WASMER_CACHE_MAX_SIZE")
+                .ok()
+                .map(|max_size| max_size.parse::<bytesize::ByteSize>())
+                .transpose()
+                .map_err(|e| anyhow!("invalid `WASMER_CACHE_MAX_SIZE`: {}", e))?,
+        };
+        cache.set_max_size(max_size.map(|max_size| max_size.as_u64()));
         Ok(cache)
     }
 
+    /// Puts the remote cache in front of the filesystem cache `local`, if
+    /// the `WASMER_REMOTE_CACHE` environment variable is set to the
+    /// `https://` URL of a server.
+    ///
+    /// Modules are shared with the other machines using it, with the
+    /// filesystem cache as a local copy. Downloaded modules are only
+    /// loaded when signed by one of the keys of `--require-signature`, and
+    /// compiled modules are only uploaded when signed with the private key
+    /// `WASMER_REMOTE_CACHE_SIGNING_KEY` points to. A bearer token can be
+    /// given to the server with `WASMER_REMOTE_CACHE_TOKEN`.
+    #[cfg(feature = "remote-cache")]
+    fn remote_cache(
+        &self,
+        local: FileSystemCache,
+        store: &Store,
+        compiler_type: &CompilerType,
+    ) -> Result<Box<dyn Cache<SerializeError = SerializeError, DeserializeError = DeserializeError>>>
+    {
+        let url = match std::env::var("WASMER_REMOTE_CACHE") {
+            Ok(url) => url,
+            Err(_) => return Ok(Box::new(local)),
+        };
+        if self.require_signature.is_empty() {
+            warning!(
+                "ignoring `WASMER_REMOTE_CACHE`: the keys downloaded modules must be signed with \
+                 are given with `--require-signature`"
+            );
+            return Ok(Box::new(local));
+        }
+        // Artifacts can only be shared between machines producing the
+        // same ones
+        let target = store.engine().target();
+        let fingerprint = format!(
+            "{}/{}/{}-{:x}",
+            crate::VERSION,
+            compiler_type.to_string(),
+            target.triple(),
+            target.cpu_features().as_u64()
+        );
+        let mut cache = RemoteCache::new(local, url, fingerprint, self.trusted_keys()?)
+            .context("invalid `WASMER_REMOTE_CACHE`")?;
+        if let Ok(path) = std::env::var("WASMER_REMOTE_CACHE_SIGNING_KEY") {
+            let pem = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read the private key `{}`", path))?;
+            let key = SigningKey::from_pem(&pem)
+                .with_context(|| format!("invalid private key `{}`", path))?;
+            cache.set_signing_key(key);
+        }
+        if let Ok(token) = std::env::var("WASMER_REMOTE_CACHE_TOKEN") {
+            cache.set_header("Authorization", format!("Bearer {}", token));
+        }
+        Ok(Box::new(cache))
+    }
+
+    /// Fails if `--harden` is combined with options writing files once the
+    /// module ran, which the process can't create anymore then, or with
+    /// `--allow-http`, whose requests need to resolve hosts by reading the
+    /// configuration files of the resolver.
+    #[cfg(feature = "wasi")]
+    fn check_hardening(&self) -> Result<()> {
+        if !self.wasi.is_hardened() {
+            return Ok(());
+        }
+        #[cfg(unix)]
+        if self.profile.is_enabled() {
+            bail!("`--profile` can't be combined with `--harden`");
+        }
+        #[cfg(feature = "compiler")]
+        if self.coverage.is_enabled() {
+            bail!("`--coverage` can't be combined with `--harden`");
+        }
+        if self.coredump.is_enabled() {
+            bail!("`--coredump-on-trap` can't be combined with `--harden`");
+        }
+        #[cfg(feature = "wasi-http")]
+        if self.wasi.allows_http() {
+            bail!("`--allow-http` can't be combined with `--harden`");
+        }
+        Ok(())
+    }
+
+    /// The package of the registry to run, with `--package`.
+    #[cfg(feature = "http")]
+    fn package_specifier(&self) -> Result<Option<PackageSpecifier>> {
+        if !self.package {
+            return Ok(None);
+        }
+        self.path
+            .to_str()
+            .and_then(|path| path.parse().ok())
+            .map(Some)
+            .ok_or_else(|| {
+                anyhow!(
+                    "`{}` isn't a package, like `namespace/name@1.2.0`",
+                    self.path.display()
+                )
+            })
+    }
+
+    /// The options to run the entrypoint command of `package`, with its
+    /// directories mounted.
+    #[cfg(feature = "http")]
+    fn with_package(&self, package: Package) -> Self {
+        let mut run = self.clone();
+        run.path = package.module;
+        run.package = false;
+        run.command_name = run.command_name.or(Some(package.command));
+        #[cfg(feature = "wasi")]
+        run.wasi.add_mapped_dirs(package.mapped_dirs);
+        run
+    }
+
+    /// The function run as the command of the module.
+    fn entrypoint(&self) -> &str {
+        self.entrypoint.as_deref().unwrap_or("_start")
+    }
+
+    /// Whether `module` is a WASI reactor, exporting `_initialize` but no
+    /// entrypoint to run it as a command.
+    fn is_reactor(&self, module: &Module) -> bool {
+        let exports_function = |name: &str| {
+            module
+                .exports()
+                .functions()
+                .any(|export| export.name() == name)
+        };
+        exports_function("_initialize") && !exports_function(self.entrypoint())
+    }
+
+    /// How the instances of `module` are initialized: reactors get their
+    /// `_initialize` function called before anything else runs.
+    fn initialization(&self, module: &Module) -> Initialization {
+        if self.no_start {
+            Initialization::Deferred
+        } else if self.is_reactor(module) {
+            Initialization::Reactor
+        } else {
+            Initialization::Start
+        }
+    }
+
+    /// Whether the module is read from stdin, when the path to run is `-`.
+    fn reads_stdin(&self) -> bool {
+        self.path.as_os_str() == "-"
+    }
+
+    /// The name of the module, from its file name.
+    fn module_name(&self) -> String {
+        if self.reads_stdin() {
+            return "stdin".to_string();
+        }
+        self.path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[cfg(all(feature = "cache", feature = "compiler"))]
+    fn instrumented(&self) -> bool {
+        self.coverage.is_enabled() || self.limits.instruments() || self.stats.instruments()
+    }
+
+    #[cfg(all(feature = "cache", not(feature = "compiler")))]
+    fn instrumented(&self) -> bool {
+        false
+    }
+
     fn try_find_function(
         &self,
         instance: &Instance,
@@ -392,53 +1086,17 @@ impl Run {
         &self,
         ctx: &mut impl AsStoreMut,
         instance: &Instance,
-        invoke: &str,
-        args: &[String],
+        call: &Invocation,
     ) -> Result<Box<[Value]>> {
-        let func: Function = self.try_find_function(instance, invoke, args)?;
+        let func: Function = self.try_find_function(instance, &call.function, &call.args)?;
         let func_ty = func.ty(ctx);
-        let required_arguments = func_ty.params().len();
-        let provided_arguments = args.len();
-        if required_arguments != provided_arguments {
-            bail!(
-                "Function expected {} arguments, but received {}: \"{}\"",
-                required_arguments,
-                provided_arguments,
-                self.args.join(" ")
-            );
-        }
-        let invoke_args = args
-            .iter()
-            .zip(func_ty.params().iter())
-            .map(|(arg, param_type)| match param_type {
-                ValueType::I32 => {
-                    Ok(Value::I32(arg.parse().map_err(|_| {
-                        anyhow!("Can't convert `{}` into a i32", arg)
-                    })?))
-                }
-                ValueType::I64 => {
-                    Ok(Value::I64(arg.parse().map_err(|_| {
-                        anyhow!("Can't convert `{}` into a i64", arg)
-                    })?))
-                }
-                ValueType::F32 => {
-                    Ok(Value::F32(arg.parse().map_err(|_| {
-                        anyhow!("Can't convert `{}` into a f32", arg)
-                    })?))
-                }
-                ValueType::F64 => {
-                    Ok(Value::F64(arg.parse().map_err(|_| {
-                        anyhow!("Can't convert `{}` into a f64", arg)
-                    })?))
-                }
-                _ => Err(anyhow!(
-                    "Don't know how to convert {} into {:?}",
-                    arg,
-                    param_type
-                )),
-            })
-            .collect::<Result<Vec<_>>>()?;
-        Ok(func.call(ctx, &invoke_args)?)
+        let invoke_args = invoke::parse_args(ctx, instance, &call.function, &func_ty, &call.args)?;
+        let result = func.call(ctx, &invoke_args);
+        #[cfg(feature = "wasi")]
+        let result = self.wasi.handle_result(result)?;
+        #[cfg(not(feature = "wasi"))]
+        let result = result?;
+        Ok(result)
     }
 
     /// Create Run instance for arguments/env,
@@ -0,0 +1,95 @@
+//! `wasmer run --compare`, invoking a function with several compilers to
+//! find where they diverge.
+
+use super::invoke::{self, OutputFormat};
+use crate::store::CompilerType;
+use anyhow::{bail, Result};
+use wasmer::{RuntimeError, Value};
+
+/// What invoking the function did with one of the compilers.
+#[derive(Debug)]
+pub struct Outcome {
+    compiler: CompilerType,
+    /// The results, the trap, or the error compiling or instantiating the
+    /// module, as printed.
+    summary: String,
+}
+
+impl Outcome {
+    /// The outcome of a function that ran to completion or trapped.
+    pub fn of_call(
+        compiler: CompilerType,
+        result: Result<Box<[Value]>, RuntimeError>,
+        output: OutputFormat,
+    ) -> Self {
+        let summary = match result {
+            Ok(results) => invoke::format_results(&results, output),
+            Err(e) => {
+                let message = e.message();
+                match e.to_trap() {
+                    Some(code) => format!("trap: {}", code.message()),
+                    None => format!("error: {}", message),
+                }
+            }
+        };
+        Self { compiler, summary }
+    }
+
+    /// The outcome of a module that couldn't be run.
+    pub fn of_error(compiler: CompilerType, error: anyhow::Error) -> Self {
+        Self {
+            compiler,
+            summary: format!("error: {:#}", error),
+        }
+    }
+}
+
+/// Prints the outcome the compilers agree on, or fails listing what each
+/// of them did.
+pub fn report(outcomes: &[Outcome]) -> Result<()> {
+    let first = &outcomes[0];
+    if outcomes
+        .iter()
+        .all(|outcome| outcome.summary == first.summary)
+    {
+        eprintln!("The {} compilers agree.", outcomes.len());
+        println!("{}", first.summary);
+        return Ok(());
+    }
+    let width = outcomes
+        .iter()
+        .map(|outcome| outcome.compiler.to_string().len())
+        .max()
+        .unwrap_or(0);
+    for outcome in outcomes {
+        eprintln!(
+            "{:width$}  {}",
+            outcome.compiler.to_string(),
+            outcome.summary,
+            width = width
+        );
+    }
+    bail!("the compilers diverge")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outcomes() {
+        let results = Outcome::of_call(
+            CompilerType::Cranelift,
+            Ok(vec![Value::I32(1), Value::F64(0.5)].into_boxed_slice()),
+            OutputFormat::Text,
+        );
+        assert_eq!(results.summary, "1 0.5");
+        let error = Outcome::of_call(
+            CompilerType::Singlepass,
+            Err(RuntimeError::new("host function failed")),
+            OutputFormat::Text,
+        );
+        assert_eq!(error.summary, "error: host function failed");
+        assert!(report(&[results, error]).is_err());
+    }
+}
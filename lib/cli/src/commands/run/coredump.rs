@@ -0,0 +1,225 @@
+//! Coredumps of the guest when it traps, behind `wasmer run
+//! --coredump-on-trap`.
+//!
+//! A coredump is a Wasm module following the [tool conventions]: its
+//! memory, data and global sections hold the memories and mutable
+//! globals of the instance, and its `core`, `coremodules`,
+//! `coreinstances` and `corestack` custom sections describe the process
+//! and the call stack of the trap, from the frame that trapped outwards.
+//! The values of locals and of the operand stack aren't recorded.
+//!
+//! [tool conventions]: https://github.com/WebAssembly/tool-conventions/blob/main/Coredump.md
+
+use crate::warning;
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::path::PathBuf;
+use wasmer::{FrameInfo, Instance, InstanceImage, RuntimeError, Store, Value, WASM_PAGE_SIZE};
+
+#[derive(Debug, Parser, Clone, Default)]
+/// Coredump options
+pub struct CoredumpOptions {
+    /// Write a coredump of the memories, globals and call stack of the
+    /// guest to this file when it traps, in the Wasm coredump format
+    #[clap(long = "coredump-on-trap", name = "COREDUMP_FILE", parse(from_os_str))]
+    coredump_on_trap: Option<PathBuf>,
+}
+
+impl CoredumpOptions {
+    /// Whether a coredump was requested.
+    pub fn is_enabled(&self) -> bool {
+        self.coredump_on_trap.is_some()
+    }
+
+    /// Writes a coredump of `instance` to `--coredump-on-trap` if `error`
+    /// is a trap of the guest.
+    pub fn write(
+        &self,
+        store: &mut Store,
+        instance: &Instance,
+        module_name: &str,
+        error: &RuntimeError,
+    ) -> Result<()> {
+        let path = match &self.coredump_on_trap {
+            Some(path) if error.trap_code().is_some() => path,
+            _ => return Ok(()),
+        };
+        let image = match instance.snapshot(store) {
+            Ok(image) => Some(image),
+            Err(e) => {
+                warning!("the coredump won't have the memories and globals: {}", e);
+                None
+            }
+        };
+        let coredump = encode_coredump(module_name, image.as_ref(), error.trace());
+        std::fs::write(path, coredump)
+            .with_context(|| format!("failed to write the coredump to `{}`", path.display()))?;
+        eprintln!("Wrote a coredump of the trap to `{}`.", path.display());
+        Ok(())
+    }
+}
+
+fn encode_coredump(
+    module_name: &str,
+    image: Option<&InstanceImage>,
+    frames: &[FrameInfo],
+) -> Vec<u8> {
+    let memories = image
+        .map(|image| image.memories().map(|(_, data)| data).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let globals = image
+        .map(|image| image.globals().map(|(_, value)| value).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let mut out = b"\0asm\x01\0\0\0".to_vec();
+
+    let mut core = vec![0];
+    encode_name(module_name, &mut core);
+    custom_section("core", &core, &mut out);
+
+    let mut coremodules = Vec::new();
+    encode_u32(1, &mut coremodules);
+    coremodules.push(0);
+    encode_name(module_name, &mut coremodules);
+    custom_section("coremodules", &coremodules, &mut out);
+
+    // The single instance owns all the memories and globals of the coredump
+    let mut coreinstances = Vec::new();
+    encode_u32(1, &mut coreinstances);
+    coreinstances.push(0);
+    encode_u32(0, &mut coreinstances);
+    for count in &[memories.len(), globals.len()] {
+        encode_u32(*count as u32, &mut coreinstances);
+        for index in 0..*count {
+            encode_u32(index as u32, &mut coreinstances);
+        }
+    }
+    custom_section("coreinstances", &coreinstances, &mut out);
+
+    let mut memory_section = Vec::new();
+    encode_u32(memories.len() as u32, &mut memory_section);
+    for data in &memories {
+        // Limits with a minimum and no maximum
+        memory_section.push(0);
+        encode_u32((data.len() / WASM_PAGE_SIZE) as u32, &mut memory_section);
+    }
+    section(5, &memory_section, &mut out);
+
+    let mut global_section = Vec::new();
+    encode_u32(globals.len() as u32, &mut global_section);
+    for value in &globals {
+        encode_global(value, &mut global_section);
+    }
+    section(6, &global_section, &mut out);
+
+    let mut data_section = Vec::new();
+    encode_u32(memories.len() as u32, &mut data_section);
+    for (index, data) in memories.iter().enumerate() {
+        // An active segment at offset 0, `(i32.const 0)`
+        if index == 0 {
+            data_section.push(0);
+        } else {
+            data_section.push(2);
+            encode_u32(index as u32, &mut data_section);
+        }
+        data_section.extend_from_slice(&[0x41, 0, 0x0b]);
+        encode_u32(data.len() as u32, &mut data_section);
+        data_section.extend_from_slice(data);
+    }
+    section(11, &data_section, &mut out);
+
+    let mut corestack = vec![0];
+    encode_name("main", &mut corestack);
+    encode_u32(frames.len() as u32, &mut corestack);
+    for frame in frames {
+        corestack.push(0);
+        encode_u32(0, &mut corestack);
+        encode_u32(frame.func_index(), &mut corestack);
+        encode_u32(frame.func_offset() as u32, &mut corestack);
+        // No locals nor operand stack
+        encode_u32(0, &mut corestack);
+        encode_u32(0, &mut corestack);
+    }
+    custom_section("corestack", &corestack, &mut out);
+
+    out
+}
+
+/// Encodes a mutable global initialized to `value`.
+fn encode_global(value: &Value, out: &mut Vec<u8>) {
+    let (ty, init): (u8, Vec<u8>) = match value {
+        Value::I32(v) => {
+            let mut init = vec![0x41];
+            encode_i64(i64::from(*v), &mut init);
+            (0x7f, init)
+        }
+        Value::I64(v) => {
+            let mut init = vec![0x42];
+            encode_i64(*v, &mut init);
+            (0x7e, init)
+        }
+        Value::F32(v) => {
+            let mut init = vec![0x43];
+            init.extend_from_slice(&v.to_bits().to_le_bytes());
+            (0x7d, init)
+        }
+        Value::F64(v) => {
+            let mut init = vec![0x44];
+            init.extend_from_slice(&v.to_bits().to_le_bytes());
+            (0x7c, init)
+        }
+        Value::V128(v) => {
+            let mut init = vec![0xfd, 0x0c];
+            init.extend_from_slice(&v.to_le_bytes());
+            (0x7b, init)
+        }
+        // Images only hold numeric globals
+        _ => unreachable!(),
+    };
+    out.push(ty);
+    out.push(1);
+    out.extend_from_slice(&init);
+    out.push(0x0b);
+}
+
+fn section(id: u8, content: &[u8], out: &mut Vec<u8>) {
+    out.push(id);
+    encode_u32(content.len() as u32, out);
+    out.extend_from_slice(content);
+}
+
+fn custom_section(name: &str, content: &[u8], out: &mut Vec<u8>) {
+    let mut section_content = Vec::new();
+    encode_name(name, &mut section_content);
+    section_content.extend_from_slice(content);
+    section(0, &section_content, out);
+}
+
+fn encode_name(name: &str, out: &mut Vec<u8>) {
+    encode_u32(name.len() as u32, out);
+    out.extend_from_slice(name.as_bytes());
+}
+
+fn encode_u32(mut value: u32, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn encode_i64(mut value: i64, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
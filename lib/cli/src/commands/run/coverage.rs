@@ -0,0 +1,68 @@
+//! Code coverage of the guest, behind `wasmer run --coverage`.
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use wasmer::{Instance, Store};
+use wasmer_middlewares::Coverage;
+
+#[derive(Debug, Parser, Clone, Default)]
+/// Coverage options
+pub struct CoverageOptions {
+    /// Record which functions and basic blocks of the module run, and
+    /// write them to an LCOV tracefile. Lines are those of the sources of
+    /// the module when it has DWARF, otherwise offsets in the module
+    #[clap(long = "coverage", name = "LCOV_FILE", parse(from_os_str))]
+    coverage: Option<PathBuf>,
+
+    /// The middleware instrumenting the module, once it is created.
+    #[clap(skip)]
+    middleware: Arc<Mutex<Option<Arc<Coverage>>>>,
+}
+
+impl CoverageOptions {
+    /// Whether coverage was requested.
+    pub fn is_enabled(&self) -> bool {
+        self.coverage.is_some()
+    }
+
+    /// Creates the middleware instrumenting `wasm`, if coverage was
+    /// requested.
+    pub fn middleware(&self, wasm: &[u8]) -> Result<Option<Arc<Coverage>>> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+        let middleware = Arc::new(Coverage::new(wasm)?);
+        *self.middleware.lock().unwrap() = Some(middleware.clone());
+        Ok(Some(middleware))
+    }
+
+    /// Writes the coverage of `instance` to `--coverage`, if the module
+    /// was instrumented.
+    pub fn finish(&self, store: &mut Store, instance: &Instance, module_name: &str) -> Result<()> {
+        let (middleware, path) = match (&*self.middleware.lock().unwrap(), &self.coverage) {
+            (Some(middleware), Some(path)) => (middleware.clone(), path),
+            _ => return Ok(()),
+        };
+        let report = middleware.report(store, instance);
+        let file =
+            File::create(path).with_context(|| format!("failed to create `{}`", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        report.write_lcov(module_name, &mut writer)?;
+        writer.flush()?;
+        let functions = report.functions();
+        eprintln!(
+            "Wrote the coverage of {}/{} functions to `{}`.",
+            functions
+                .iter()
+                .filter(|function| function.hits() > 0)
+                .count(),
+            functions.len(),
+            path.display()
+        );
+        Ok(())
+    }
+}
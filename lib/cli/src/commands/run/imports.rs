@@ -0,0 +1,405 @@
+//! Declarative import objects for the `run` command.
+//!
+//! An imports configuration is a JSON document describing which host
+//! capabilities a module gets wired to, so operators can change what a
+//! guest sees without writing an embedder:
+//!
+//! ```json
+//! {
+//!   "wasi": true,
+//!   "imports": [
+//!     { "module": "env", "name": "log", "provider": "stub", "params": ["i32", "i32"] },
+//!     { "module": "env", "name": "max_conns", "provider": "env-var", "type": "i32", "var": "MAX_CONNS", "default": "16" },
+//!     { "module": "env", "name": "debug", "provider": "global", "type": "i32", "value": "0", "mutable": true },
+//!     { "module": "env", "name": "cos", "provider": "native", "library": "libm.so.6", "params": ["f64"], "results": ["f64"] }
+//!   ]
+//! }
+//! ```
+//!
+//! The `native` provider calls a function of a host shared library, which
+//! runs outside of the sandbox: the library must also be allowed with
+//! `--allow-native`.
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use wasmer::{
+    AsStoreMut, Extern, Function, FunctionType, Global, Imports, RuntimeError, Type, Value,
+};
+
+/// The root of an imports configuration file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImportsConfig {
+    /// Always provide the WASI imports, even if the module also imports
+    /// from namespaces that are not WASI.
+    #[serde(default)]
+    pub wasi: bool,
+
+    /// The individual import bindings.
+    #[serde(default)]
+    pub imports: Vec<ImportBinding>,
+}
+
+/// Binds a single `module.name` import to a host provider.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportBinding {
+    /// The import module (namespace), e.g. `env`.
+    pub module: String,
+    /// The import field name.
+    pub name: String,
+    /// What satisfies the import.
+    #[serde(flatten)]
+    pub provider: ImportProvider,
+}
+
+/// The built-in providers an import can be bound to.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "provider", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum ImportProvider {
+    /// A function that does nothing and returns zeroed results.
+    Stub {
+        /// The parameter types of the function.
+        #[serde(default)]
+        params: Vec<ConfigType>,
+        /// The result types of the function.
+        #[serde(default)]
+        results: Vec<ConfigType>,
+    },
+    /// A global initialized from a literal value.
+    Global {
+        /// The type of the global.
+        #[serde(rename = "type")]
+        ty: ConfigType,
+        /// The initial value of the global.
+        value: String,
+        /// Whether the guest may mutate the global.
+        #[serde(default)]
+        mutable: bool,
+    },
+    /// An immutable global initialized from a host environment variable.
+    EnvVar {
+        /// The type of the global.
+        #[serde(rename = "type")]
+        ty: ConfigType,
+        /// The environment variable to read.
+        var: String,
+        /// The value to use if the environment variable is not set.
+        #[serde(default)]
+        default: Option<String>,
+    },
+    /// A function of a host shared library.
+    Native {
+        /// The path of the library, which must be allowed with
+        /// `--allow-native`.
+        library: PathBuf,
+        /// The symbol of the function, defaulting to the import field
+        /// name.
+        #[serde(default)]
+        symbol: Option<String>,
+        /// The parameter types of the function.
+        #[serde(default)]
+        params: Vec<ConfigType>,
+        /// The result types of the function.
+        #[serde(default)]
+        results: Vec<ConfigType>,
+    },
+}
+
+/// The value types that can be spelled in an imports configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigType {
+    /// `i32`
+    I32,
+    /// `i64`
+    I64,
+    /// `f32`
+    F32,
+    /// `f64`
+    F64,
+}
+
+impl From<ConfigType> for Type {
+    fn from(ty: ConfigType) -> Self {
+        match ty {
+            ConfigType::I32 => Self::I32,
+            ConfigType::I64 => Self::I64,
+            ConfigType::F32 => Self::F32,
+            ConfigType::F64 => Self::F64,
+        }
+    }
+}
+
+impl ConfigType {
+    fn zero(self) -> Value {
+        match self {
+            Self::I32 => Value::I32(0),
+            Self::I64 => Value::I64(0),
+            Self::F32 => Value::F32(0.0),
+            Self::F64 => Value::F64(0.0),
+        }
+    }
+
+    fn parse(self, value: &str) -> Result<Value> {
+        let value = value.trim();
+        Ok(match self {
+            Self::I32 => Value::I32(
+                value
+                    .parse()
+                    .with_context(|| format!("can't convert `{}` into a i32", value))?,
+            ),
+            Self::I64 => Value::I64(
+                value
+                    .parse()
+                    .with_context(|| format!("can't convert `{}` into a i64", value))?,
+            ),
+            Self::F32 => Value::F32(
+                value
+                    .parse()
+                    .with_context(|| format!("can't convert `{}` into a f32", value))?,
+            ),
+            Self::F64 => Value::F64(
+                value
+                    .parse()
+                    .with_context(|| format!("can't convert `{}` into a f64", value))?,
+            ),
+        })
+    }
+}
+
+impl ImportsConfig {
+    /// Reads an imports configuration from a JSON file.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let contents = fs::read(path)
+            .with_context(|| format!("failed to read imports config `{}`", path.display()))?;
+        serde_json::from_slice(&contents)
+            .with_context(|| format!("failed to parse imports config `{}`", path.display()))
+    }
+
+    /// Instantiates every binding of the configuration in `store`. Only
+    /// the native libraries of `allow_native` can be loaded.
+    pub fn to_imports(
+        &self,
+        store: &mut impl AsStoreMut,
+        allow_native: &[PathBuf],
+    ) -> Result<Imports> {
+        let mut imports = Imports::new();
+        for binding in &self.imports {
+            let extern_ = binding.to_extern(store, allow_native).with_context(|| {
+                format!(
+                    "invalid binding for import `{}.{}`",
+                    binding.module, binding.name
+                )
+            })?;
+            imports.define(&binding.module, &binding.name, extern_);
+        }
+        Ok(imports)
+    }
+}
+
+impl ImportBinding {
+    fn to_extern(&self, store: &mut impl AsStoreMut, allow_native: &[PathBuf]) -> Result<Extern> {
+        Ok(match &self.provider {
+            ImportProvider::Stub { params, results } => {
+                let ty = function_type(params, results);
+                let results = results.iter().map(|ty| ty.zero()).collect::<Vec<_>>();
+                Function::new(
+                    store,
+                    ty,
+                    move |_args| -> Result<Vec<Value>, RuntimeError> { Ok(results.clone()) },
+                )
+                .into()
+            }
+            ImportProvider::Global { ty, value, mutable } => {
+                let value = ty.parse(value)?;
+                if *mutable {
+                    Global::new_mut(store, value).into()
+                } else {
+                    Global::new(store, value).into()
+                }
+            }
+            ImportProvider::EnvVar { ty, var, default } => {
+                let value = match std::env::var(var) {
+                    Ok(value) => value,
+                    Err(_) => default.clone().ok_or_else(|| {
+                        anyhow!(
+                            "environment variable `{}` is not set and no default was given",
+                            var
+                        )
+                    })?,
+                };
+                Global::new(store, ty.parse(&value)?).into()
+            }
+            ImportProvider::Native {
+                library,
+                symbol,
+                params,
+                results,
+            } => {
+                let path = match allowed_library(library, allow_native)? {
+                    Some(path) => path,
+                    None => bail!(
+                        "the native library `{}` runs outside of the sandbox, pass `--allow-native {}` to allow it",
+                        library.display(),
+                        library.display()
+                    ),
+                };
+                let symbol = symbol.as_deref().unwrap_or(&self.name);
+                native_function(store, &path, symbol, function_type(params, results))?.into()
+            }
+        })
+    }
+}
+
+/// The path to load `library` from, if it is allowed by `allow_native`.
+///
+/// The libraries given by path are compared by their canonical path, and
+/// loaded from it, so that another spelling of an allowed path, or a link
+/// to it, is allowed as well. The bare names of libraries, which the
+/// dynamic loader looks up, must be allowed as they are.
+fn allowed_library(library: &Path, allow_native: &[PathBuf]) -> Result<Option<PathBuf>> {
+    if is_bare_name(library) {
+        let allowed = allow_native.iter().any(|allowed| allowed == library);
+        return Ok(if allowed {
+            Some(library.to_path_buf())
+        } else {
+            None
+        });
+    }
+    let library = library
+        .canonicalize()
+        .with_context(|| format!("failed to find the native library `{}`", library.display()))?;
+    let allowed = allow_native
+        .iter()
+        .filter(|allowed| !is_bare_name(allowed))
+        .filter_map(|allowed| allowed.canonicalize().ok())
+        .any(|allowed| allowed == library);
+    Ok(if allowed { Some(library) } else { None })
+}
+
+/// Whether `path` is the bare file name of a library.
+fn is_bare_name(path: &Path) -> bool {
+    let mut components = path.components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    )
+}
+
+fn function_type(params: &[ConfigType], results: &[ConfigType]) -> FunctionType {
+    FunctionType::new(
+        params.iter().copied().map(Type::from).collect::<Vec<_>>(),
+        results.iter().copied().map(Type::from).collect::<Vec<_>>(),
+    )
+}
+
+#[cfg(feature = "native-imports")]
+fn native_function(
+    store: &mut impl AsStoreMut,
+    library: &Path,
+    symbol: &str,
+    ty: FunctionType,
+) -> Result<Function> {
+    // The library was allowed with `--allow-native`, which is the user
+    // vouching for it and for the signature of its symbol
+    unsafe {
+        let library = wasmer::NativeLibrary::open(library)?;
+        Ok(library.function(store, symbol, ty)?)
+    }
+}
+
+#[cfg(not(feature = "native-imports"))]
+fn native_function(
+    _store: &mut impl AsStoreMut,
+    _library: &Path,
+    _symbol: &str,
+    _ty: FunctionType,
+) -> Result<Function> {
+    bail!("wasmer was built without the `native-imports` feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_imports_config() {
+        let config: ImportsConfig = serde_json::from_str(
+            r#"{
+                "wasi": true,
+                "imports": [
+                    { "module": "env", "name": "log", "provider": "stub", "params": ["i32"] },
+                    { "module": "env", "name": "g", "provider": "global", "type": "i64", "value": "-3", "mutable": true },
+                    { "module": "env", "name": "v", "provider": "env-var", "type": "f32", "var": "V", "default": "1.5" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert!(config.wasi);
+        assert_eq!(config.imports.len(), 3);
+        assert!(matches!(
+            &config.imports[0].provider,
+            ImportProvider::Stub { params, results } if params == &[ConfigType::I32] && results.is_empty()
+        ));
+        assert!(matches!(
+            &config.imports[1].provider,
+            ImportProvider::Global {
+                ty: ConfigType::I64,
+                mutable: true,
+                ..
+            }
+        ));
+        assert!(matches!(
+            &config.imports[2].provider,
+            ImportProvider::EnvVar {
+                ty: ConfigType::F32,
+                default: Some(_),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn native_libraries_must_be_allowed() {
+        let config: ImportsConfig = serde_json::from_str(
+            r#"{ "imports": [ { "module": "env", "name": "cos", "provider": "native", "library": "libm.so.6" } ] }"#,
+        )
+        .unwrap();
+        let mut store = wasmer::Store::default();
+        let err = config.to_imports(&mut store, &[]).unwrap_err();
+        assert!(format!("{:#}", err).contains("--allow-native libm.so.6"));
+    }
+
+    #[test]
+    fn native_libraries_are_allowed_by_canonical_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = dir.path().join("libplugin.so");
+        fs::write(&library, b"not a library").unwrap();
+        let other = dir.path().join("libother.so");
+        fs::write(&other, b"not a library").unwrap();
+
+        let spelled = dir.path().join(".").join("libplugin.so");
+        assert_eq!(
+            allowed_library(&spelled, &[library.clone()]).unwrap(),
+            Some(library.canonicalize().unwrap())
+        );
+        assert_eq!(allowed_library(&other, &[library.clone()]).unwrap(), None);
+        // A bare name is looked up by the dynamic loader, not in the
+        // current directory
+        assert_eq!(
+            allowed_library(Path::new("libplugin.so"), &[library.clone()]).unwrap(),
+            None
+        );
+        assert!(allowed_library(&dir.path().join("missing.so"), &[library]).is_err());
+    }
+
+    #[test]
+    fn reject_unknown_provider() {
+        let err = serde_json::from_str::<ImportsConfig>(
+            r#"{ "imports": [ { "module": "env", "name": "x", "provider": "magic" } ] }"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("magic"));
+    }
+}
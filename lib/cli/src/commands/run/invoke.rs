@@ -0,0 +1,657 @@
+//! `wasmer run --invoke`, converting the command line arguments into the
+//! parameters of the invoked functions and printing their results.
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Arg, ArgMatches, Args, Command, FromArgMatches};
+use std::convert::TryFrom;
+use std::fmt;
+use std::num::IntErrorKind;
+use std::str::FromStr;
+use wasmer::{AsStoreMut, FunctionType, Instance, Type, Value};
+
+/// The functions of `--invoke`, called in turn on the same instance.
+#[derive(Debug, Clone, Default)]
+pub struct Invocations(Vec<Invocation>);
+
+/// A function to invoke, with the arguments following its `--invoke` on
+/// the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    /// The name of the exported function.
+    pub function: String,
+    /// The arguments of the call.
+    pub args: Vec<String>,
+}
+
+impl Invocations {
+    /// The calls, in the order of the command line.
+    pub fn calls(&self) -> &[Invocation] {
+        &self.0
+    }
+
+    /// Whether no function is invoked.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// The arguments of each call are the positional `args` of the command
+// after its `--invoke`, which the derived parser can't tell apart, so they
+// are matched by their positions on the command line.
+impl Args for Invocations {
+    fn augment_args(cmd: Command<'_>) -> Command<'_> {
+        cmd.arg(
+            Arg::new("invoke")
+                .long("invoke")
+                .short('i')
+                .value_name("FUNCTION")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help(
+                    "Invoke a specified function, with the arguments following it. Its \
+                     arguments can be negative or hexadecimal integers, strings when it \
+                     takes a pointer and a length, hexadecimal floats (`0x1.8p-3`), v128 \
+                     lanes (`i32x4:1,2,3,4`), and `null` or exported functions as \
+                     references. Can be repeated to invoke several functions in turn, \
+                     keeping the state of the instance between calls",
+                ),
+        )
+    }
+
+    fn augment_args_for_update(cmd: Command<'_>) -> Command<'_> {
+        Self::augment_args(cmd)
+    }
+}
+
+impl FromArgMatches for Invocations {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        let mut calls = match (matches.indices_of("invoke"), matches.values_of("invoke")) {
+            (Some(indices), Some(functions)) => indices
+                .zip(functions)
+                .map(|(index, function)| {
+                    let call = Invocation {
+                        function: function.to_string(),
+                        args: Vec::new(),
+                    };
+                    (index, call)
+                })
+                .collect::<Vec<_>>(),
+            _ => return Ok(Self::default()),
+        };
+        if let (Some(indices), Some(args)) = (matches.indices_of("args"), matches.values_of("args"))
+        {
+            for (index, arg) in indices.zip(args) {
+                // The arguments before the first `--invoke` belong to it
+                let call = calls.iter().rposition(|(i, _)| *i < index).unwrap_or(0);
+                calls[call].1.args.push(arg.to_string());
+            }
+        }
+        Ok(Self(calls.into_iter().map(|(_, call)| call).collect()))
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+/// How the results of the invoked function are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The results separated by spaces.
+    Text,
+    /// A JSON array of the results.
+    Json,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "unknown output format `{}`, expected `text` or `json`",
+                s
+            )),
+        }
+    }
+}
+
+/// Converts `args` into the parameters of the function exported as `name`,
+/// of type `ty`.
+///
+/// Integers can be negative or hexadecimal (`0x..`), and out of the
+/// signed range up to the unsigned maximum. An argument that isn't an
+/// integer where the function expects two `i32`s is a string: it is
+/// written into the `memory` of the instance, allocated with its
+/// `cabi_realloc` or `malloc` export, and passed as a pointer and a
+/// length. Floats can be written exactly in hexadecimal, v128s as an
+/// integer or by lanes, and references are `null`, or the name of an
+/// exported function for a funcref.
+pub fn parse_args(
+    ctx: &mut impl AsStoreMut,
+    instance: &Instance,
+    name: &str,
+    ty: &FunctionType,
+    args: &[String],
+) -> Result<Vec<Value>> {
+    convert_args(ctx, instance, ty, args).with_context(|| {
+        format!(
+            "invalid arguments for {}",
+            describe_function(instance, name, ty)
+        )
+    })
+}
+
+/// Describes the function exported as `name` with its signature, and with
+/// the name the name section gives it if it's another one.
+pub fn describe_function(instance: &Instance, name: &str, ty: &FunctionType) -> String {
+    match instance.module().info().exported_function_name(name) {
+        Some(internal) if internal != name => {
+            format!("`{}` (`{}` in the name section): {}", name, internal, ty)
+        }
+        _ => format!("`{}`: {}", name, ty),
+    }
+}
+
+fn convert_args(
+    ctx: &mut impl AsStoreMut,
+    instance: &Instance,
+    ty: &FunctionType,
+    args: &[String],
+) -> Result<Vec<Value>> {
+    let params = ty.params();
+    let mut values = Vec::with_capacity(params.len());
+    let mut remaining = args.iter();
+    while values.len() < params.len() {
+        let arg = match remaining.next() {
+            Some(arg) => arg,
+            None => break,
+        };
+        let value = match params[values.len()] {
+            Type::I32 => match parse_integer(arg, 32) {
+                Ok(value) => Value::I32(value as i32),
+                Err(IntegerError::Invalid) if params.get(values.len() + 1) == Some(&Type::I32) => {
+                    let (pointer, length) = write_string(ctx, instance, arg)?;
+                    values.push(Value::I32(pointer));
+                    Value::I32(length)
+                }
+                Err(error) => bail!("Can't convert `{}` into a i32: {}", arg, error),
+            },
+            Type::I64 => Value::I64(
+                parse_integer(arg, 64)
+                    .map_err(|error| anyhow!("Can't convert `{}` into a i64: {}", arg, error))?
+                    as i64,
+            ),
+            Type::F32 => Value::F32(f32::from_bits(
+                parse_float(arg, 32)
+                    .map_err(|error| anyhow!("Can't convert `{}` into a f32: {}", arg, error))?
+                    as u32,
+            )),
+            Type::F64 => {
+                Value::F64(f64::from_bits(parse_float(arg, 64).map_err(|error| {
+                    anyhow!("Can't convert `{}` into a f64: {}", arg, error)
+                })?))
+            }
+            Type::V128 => Value::V128(
+                parse_v128(arg)
+                    .map_err(|error| anyhow!("Can't convert `{}` into a v128: {}", arg, error))?,
+            ),
+            Type::ExternRef => match arg.as_str() {
+                "null" => Value::ExternRef(None),
+                _ => bail!(
+                    "Can't convert `{}` into an externref: only `null` can be passed",
+                    arg
+                ),
+            },
+            Type::FuncRef => match arg.as_str() {
+                "null" => Value::FuncRef(None),
+                name => match instance.exports.get_function(name) {
+                    Ok(function) => Value::FuncRef(Some(function.clone())),
+                    Err(_) => bail!(
+                        "Can't convert `{}` into a funcref: it is neither `null` nor the name of \
+                         an exported function",
+                        arg
+                    ),
+                },
+            },
+        };
+        values.push(value);
+    }
+    if values.len() != params.len() || remaining.next().is_some() {
+        bail!(
+            "expected {} arguments, but received {}: \"{}\"",
+            params.len(),
+            args.len(),
+            args.join(" ")
+        );
+    }
+    Ok(values)
+}
+
+/// Why an argument isn't an integer of the expected size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IntegerError {
+    /// The argument isn't an integer.
+    Invalid,
+    /// The integer doesn't fit in this number of bits.
+    OutOfRange(u32),
+}
+
+impl fmt::Display for IntegerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::Invalid => write!(f, "not a decimal or hexadecimal integer"),
+            Self::OutOfRange(128) => write!(f, "out of the range of 128-bit unsigned integers"),
+            Self::OutOfRange(bits) => write!(
+                f,
+                "out of the range of {}-bit integers, from {} to {} (or 0x{:x} unsigned)",
+                bits,
+                -(1i128 << (bits - 1)),
+                (1i128 << (bits - 1)) - 1,
+                (1u128 << bits) - 1
+            ),
+        }
+    }
+}
+
+/// Parses a decimal or hexadecimal integer fitting in `bits` bits, either
+/// as a signed or as an unsigned number.
+fn parse_integer(arg: &str, bits: u32) -> Result<i128, IntegerError> {
+    let (negative, digits) = match arg.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, arg),
+    };
+    let (radix, numeral) = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => (16, hex),
+        None => (10, digits),
+    };
+    // `from_str_radix` would accept a sign of its own
+    if numeral.starts_with('+') {
+        return Err(IntegerError::Invalid);
+    }
+    let magnitude = u128::from_str_radix(numeral, radix).map_err(|error| match error.kind() {
+        IntErrorKind::PosOverflow => IntegerError::OutOfRange(bits),
+        _ => IntegerError::Invalid,
+    })?;
+    let out_of_range = IntegerError::OutOfRange(bits);
+    if bits == 128 {
+        // Only unsigned values are accepted, as they don't fit in an `i128`
+        return if negative && magnitude != 0 {
+            Err(out_of_range)
+        } else {
+            Ok(magnitude as i128)
+        };
+    }
+    let value = i128::try_from(magnitude).map_err(|_| out_of_range)?;
+    let value = if negative { -value } else { value };
+    if value < -(1 << (bits - 1)) || value >= 1 << bits {
+        return Err(out_of_range);
+    }
+    Ok(value)
+}
+
+/// Parses a float of `bits` bits (32 or 64) into its bit pattern.
+///
+/// Decimal floats are rounded to the nearest float, but can't overflow to
+/// an infinity. The hexadecimal floats of the text format (`0x1.8p-3`)
+/// must be exactly representable, and NaNs can have a payload
+/// (`nan:0x200000`), for every float to be reachable.
+fn parse_float(arg: &str, bits: u32) -> Result<u64, String> {
+    let (mantissa_bits, exponent_bits): (u32, u32) = if bits == 32 { (23, 8) } else { (52, 11) };
+    let (negative, unsigned) = match arg.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, arg.strip_prefix('+').unwrap_or(arg)),
+    };
+    // `parse` would accept a second sign
+    if unsigned.starts_with(|c: char| c == '+' || c == '-') {
+        return Err("not a float".to_string());
+    }
+    let sign = u64::from(negative) << (bits - 1);
+    let infinity = ((1u64 << exponent_bits) - 1) << mantissa_bits;
+
+    if let Some(payload) = unsigned.strip_prefix("nan:0x") {
+        return match u64::from_str_radix(payload, 16) {
+            Ok(payload) if payload != 0 && payload < 1u64 << mantissa_bits => {
+                Ok(sign | infinity | payload)
+            }
+            _ => Err(format!(
+                "the NaN payload must be from 0x1 to 0x{:x}",
+                (1u64 << mantissa_bits) - 1
+            )),
+        };
+    }
+
+    if let Some(hex) = unsigned
+        .strip_prefix("0x")
+        .or_else(|| unsigned.strip_prefix("0X"))
+    {
+        let (mantissa, exponent) =
+            parse_hex_float(hex).ok_or("not a hexadecimal float such as `0x1.8p-3`")?;
+        let bias = (1i64 << (exponent_bits - 1)) - 1;
+        let min_exponent = 1 - bias - i64::from(mantissa_bits);
+        let value = exact_float(mantissa, exponent, mantissa_bits + 1, bias, min_exponent)
+            .ok_or_else(|| format!("not exactly representable as a f{}", bits))?;
+        let magnitude = if bits == 32 {
+            u64::from((value as f32).to_bits())
+        } else {
+            value.to_bits()
+        };
+        return Ok(sign | magnitude);
+    }
+
+    let magnitude = if bits == 32 {
+        unsigned.parse::<f32>().map(|x| u64::from(x.to_bits()))
+    } else {
+        unsigned.parse::<f64>().map(f64::to_bits)
+    }
+    .map_err(|_| "not a decimal or hexadecimal float, `inf` or `nan`".to_string())?;
+    // Only `inf` and `infinity` are infinite
+    let infinite = unsigned.starts_with(|c: char| c == 'i' || c == 'I');
+    if magnitude == infinity && !infinite {
+        return Err(format!("out of the range of f{}", bits));
+    }
+    Ok(sign | magnitude)
+}
+
+/// Parses the digits of a hexadecimal float after its `0x`, into an
+/// integer mantissa and a binary exponent.
+fn parse_hex_float(hex: &str) -> Option<(u128, i64)> {
+    let (digits, exponent) = match hex.find(|c| c == 'p' || c == 'P') {
+        Some(p) => (&hex[..p], hex[p + 1..].parse::<i32>().ok()?),
+        None => (hex, 0),
+    };
+    let (integer, fraction) = match digits.find('.') {
+        Some(dot) => (&digits[..dot], &digits[dot + 1..]),
+        None => (digits, ""),
+    };
+    if integer.is_empty() && fraction.is_empty() {
+        return None;
+    }
+    let mut mantissa = 0u128;
+    for digit in integer.chars().chain(fraction.chars()) {
+        mantissa = mantissa
+            .checked_mul(16)?
+            .checked_add(u128::from(digit.to_digit(16)?))?;
+    }
+    Some((mantissa, i64::from(exponent) - 4 * fraction.len() as i64))
+}
+
+/// The value of `mantissa * 2^exponent`, if a float with `precision` bits
+/// of mantissa represents it exactly, its highest bit being at most at
+/// `max_exponent`, and its lowest at least at `min_exponent`.
+fn exact_float(
+    mut mantissa: u128,
+    mut exponent: i64,
+    precision: u32,
+    max_exponent: i64,
+    min_exponent: i64,
+) -> Option<f64> {
+    if mantissa == 0 {
+        return Some(0.0);
+    }
+    let zeros = mantissa.trailing_zeros();
+    mantissa >>= zeros;
+    exponent += i64::from(zeros);
+    let len = 128 - mantissa.leading_zeros();
+    if len > precision || exponent + i64::from(len) - 1 > max_exponent || exponent < min_exponent {
+        return None;
+    }
+    // Scaled in steps, each exact as the result is representable
+    let mut value = mantissa as f64;
+    while exponent != 0 {
+        let step = exponent.clamp(-1000, 1000);
+        value *= 2f64.powi(step as i32);
+        exponent -= step;
+    }
+    Some(value)
+}
+
+/// Parses a v128 as an integer, or as lanes: `i8x16`, `i16x8`, `i32x4`,
+/// `i64x2`, `f32x4` or `f64x2`, then a colon and the lanes separated by
+/// commas, from the lowest one (`i32x4:1,2,3,4`).
+fn parse_v128(arg: &str) -> Result<u128, String> {
+    let (shape, lanes) = match arg.split_once(':') {
+        Some(split) => split,
+        None => {
+            return parse_integer(arg, 128)
+                .map(|x| x as u128)
+                .map_err(|e| e.to_string())
+        }
+    };
+    let (float, bits) = match shape {
+        "i8x16" => (false, 8),
+        "i16x8" => (false, 16),
+        "i32x4" => (false, 32),
+        "i64x2" => (false, 64),
+        "f32x4" => (true, 32),
+        "f64x2" => (true, 64),
+        _ => return Err(format!("unknown lane shape `{}`", shape)),
+    };
+    let lanes = lanes.split(',').collect::<Vec<_>>();
+    if lanes.len() != (128 / bits) as usize {
+        return Err(format!(
+            "expected {} lanes, but received {}",
+            128 / bits,
+            lanes.len()
+        ));
+    }
+    let mask = u128::MAX >> (128 - bits);
+    lanes
+        .iter()
+        .enumerate()
+        .try_fold(0u128, |value, (i, lane)| {
+            let lane = if float {
+                u128::from(parse_float(lane, bits).map_err(|e| format!("lane `{}`: {}", lane, e))?)
+            } else {
+                parse_integer(lane, bits).map_err(|e| format!("lane `{}`: {}", lane, e))? as u128
+            };
+            Ok(value | (lane & mask) << (i as u32 * bits))
+        })
+}
+
+/// Writes `string` into the memory of the instance, returning its
+/// pointer and length.
+fn write_string(
+    ctx: &mut impl AsStoreMut,
+    instance: &Instance,
+    string: &str,
+) -> Result<(i32, i32)> {
+    let length = i32::try_from(string.len())
+        .map_err(|_| anyhow!("The string `{}` is too long to be passed", string))?;
+    let pointer = if let Ok(realloc) = instance
+        .exports
+        .get_typed_function::<(i32, i32, i32, i32), i32>(ctx, "cabi_realloc")
+    {
+        realloc.call(ctx, 0, 0, 1, length)?
+    } else if let Ok(malloc) = instance
+        .exports
+        .get_typed_function::<i32, i32>(ctx, "malloc")
+    {
+        malloc.call(ctx, length)?
+    } else {
+        bail!(
+            "Can't pass the string `{}`: the module exports no allocator (`cabi_realloc` or `malloc`)",
+            string
+        );
+    };
+    let memory = instance
+        .exports
+        .get_memory("memory")
+        .with_context(|| format!("Can't pass the string `{}`", string))?;
+    memory
+        .view(ctx)
+        .write(pointer as u32 as u64, string.as_bytes())
+        .with_context(|| format!("Can't pass the string `{}`", string))?;
+    Ok((pointer, length))
+}
+
+/// Formats the results of an invoked function.
+pub fn format_results(results: &[Value], format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Text => results
+            .iter()
+            .map(|val| val.to_string())
+            .collect::<Vec<String>>()
+            .join(" "),
+        OutputFormat::Json => {
+            serde_json::Value::Array(results.iter().map(to_json).collect()).to_string()
+        }
+    }
+}
+
+/// Converts a result into JSON, with the floats that JSON can't represent
+/// (`NaN`, `inf` and `-inf`) and the 128-bit vectors as strings.
+fn to_json(value: &Value) -> serde_json::Value {
+    let float = |x: f64, text: String| match serde_json::Number::from_f64(x) {
+        Some(number) => serde_json::Value::Number(number),
+        None => serde_json::Value::String(text),
+    };
+    match value {
+        Value::I32(x) => (*x).into(),
+        Value::I64(x) => (*x).into(),
+        // Printed then parsed back, for `0.1f32` to be `0.1` rather than
+        // `0.10000000149011612`
+        Value::F32(x) => float(x.to_string().parse().unwrap(), x.to_string()),
+        Value::F64(x) => float(*x, x.to_string()),
+        Value::V128(x) => format!("0x{:032x}", x).into(),
+        Value::ExternRef(None) | Value::FuncRef(None) => serde_json::Value::Null,
+        value => value.to_string().into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::Run;
+    use clap::Parser;
+
+    #[test]
+    fn invocations() {
+        let call = |function: &str, args: &[&str]| Invocation {
+            function: function.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        };
+        let run = Run::try_parse_from(&[
+            "run", "mod.wasm", "--invoke", "init", "--invoke", "process", "1", "-2", "-i", "finish",
+        ])
+        .unwrap();
+        assert_eq!(
+            run.invoke.calls(),
+            [
+                call("init", &[]),
+                call("process", &["1", "-2"]),
+                call("finish", &[])
+            ]
+        );
+
+        let run =
+            Run::try_parse_from(&["run", "mod.wasm", "--invoke", "greet", "--", "-x"]).unwrap();
+        assert_eq!(run.invoke.calls(), [call("greet", &["-x"])]);
+
+        let run = Run::try_parse_from(&["run", "mod.wasm", "arg"]).unwrap();
+        assert!(run.invoke.is_empty());
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(parse_integer("42", 32), Ok(42));
+        assert_eq!(parse_integer("-42", 32), Ok(-42));
+        assert_eq!(parse_integer("0x2a", 32), Ok(42));
+        assert_eq!(parse_integer("-0x2A", 32), Ok(-42));
+        assert_eq!(parse_integer("0xffffffff", 32).map(|x| x as i32), Ok(-1));
+        assert_eq!(parse_integer("-2147483648", 32), Ok(-2147483648));
+        assert_eq!(
+            parse_integer("-2147483649", 32),
+            Err(IntegerError::OutOfRange(32))
+        );
+        assert_eq!(
+            parse_integer("0x100000000", 32),
+            Err(IntegerError::OutOfRange(32))
+        );
+        assert_eq!(parse_integer("0x100000000", 64), Ok(0x1_0000_0000));
+        assert_eq!(
+            parse_integer("1000000000000000000000000000000000000000", 64),
+            Err(IntegerError::OutOfRange(64))
+        );
+        assert_eq!(parse_integer("hello", 32), Err(IntegerError::Invalid));
+        assert_eq!(parse_integer("-", 32), Err(IntegerError::Invalid));
+        assert_eq!(parse_integer("0x+1", 32), Err(IntegerError::Invalid));
+        assert_eq!(
+            IntegerError::OutOfRange(8).to_string(),
+            "out of the range of 8-bit integers, from -128 to 127 (or 0xff unsigned)"
+        );
+    }
+
+    #[test]
+    fn floats() {
+        let single = |arg| parse_float(arg, 32).map(|bits| f32::from_bits(bits as u32));
+        let double = |arg| parse_float(arg, 64).map(f64::from_bits);
+        assert_eq!(single("0.1"), Ok(0.1));
+        assert_eq!(double("-2.5e3"), Ok(-2500.0));
+        assert_eq!(double("0x1.8p-3"), Ok(0.1875));
+        assert_eq!(double("-0x10"), Ok(-16.0));
+        assert_eq!(double("0x1p-1074"), Ok(f64::from_bits(1)));
+        assert_eq!(single("0x1.fffffep127"), Ok(f32::MAX));
+        assert!(single("0x1.fffffe8p127").is_err());
+        assert!(single("0x1p128").is_err());
+        assert!(double("0x1p-1075").is_err());
+        assert_eq!(double("-inf"), Ok(f64::NEG_INFINITY));
+        assert!(single("1e39").is_err());
+        assert_eq!(parse_float("nan:0x1", 32), Ok(0x7f80_0001));
+        assert_eq!(parse_float("-nan:0x1", 64), Ok(0xfff0_0000_0000_0001));
+        assert!(parse_float("nan:0x800000", 32).is_err());
+        assert!(double("-nan").unwrap().is_nan());
+        assert!(double("one").is_err());
+    }
+
+    #[test]
+    fn v128s() {
+        assert_eq!(parse_v128("0x0102"), Ok(0x0102));
+        assert_eq!(
+            parse_v128("i32x4:1,2,3,-1"),
+            Ok(0xffffffff_00000003_00000002_00000001)
+        );
+        assert_eq!(parse_v128("i64x2:0,0x1"), Ok(1 << 64));
+        assert_eq!(
+            parse_v128("f32x4:1,0,0,-0"),
+            Ok(0x80000000_00000000_00000000_3f800000)
+        );
+        assert!(parse_v128("i8x16:1,2").is_err());
+        assert!(parse_v128("i8x16:256,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0").is_err());
+        assert!(parse_v128("i128x1:0").is_err());
+        assert!(parse_v128("-1").is_err());
+    }
+
+    #[test]
+    fn json_results() {
+        assert_eq!(
+            format_results(
+                &[
+                    Value::I32(-1),
+                    Value::I64(1 << 40),
+                    Value::F32(0.1),
+                    Value::F64(f64::NAN),
+                    Value::FuncRef(None),
+                ],
+                OutputFormat::Json
+            ),
+            r#"[-1,1099511627776,0.1,"NaN",null]"#
+        );
+        assert_eq!(
+            format_results(&[Value::I32(1), Value::F64(2.5)], OutputFormat::Text),
+            "1 2.5"
+        );
+    }
+}
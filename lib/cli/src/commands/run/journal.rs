@@ -0,0 +1,498 @@
+//! Record and replay of WASI calls for the `run` command.
+//!
+//! With `--record <FILE>` every WASI import called by the guest is logged
+//! to a journal: the arguments, the results, the bytes the call wrote into
+//! linear memory and how the call ended (returned, `proc_exit` or trap).
+//!
+//! With `--replay <FILE>` the WASI imports are not executed at all: each call
+//! is checked against the next journal entry and answered from it, so a run
+//! can be reproduced without the files, environment or clocks of the machine
+//! it was recorded on. Only the guest's writes to stdout and stderr are
+//! echoed to the host, so the output of the original run is visible.
+//!
+//! Recording snapshots linear memory around every call, which makes it
+//! slow for guests with large memories; it is a debugging tool.
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use wasmer::{
+    AsStoreMut, Extern, Function, FunctionEnv, FunctionEnvMut, Imports, Memory, MemoryView,
+    RuntimeError, Value,
+};
+use wasmer_wasi::WasiError;
+
+/// The magic bytes (and format version) every journal starts with.
+const MAGIC: &[u8; 8] = b"WASMERJ\x01";
+
+/// A journal wrapping the WASI imports of one instance.
+pub struct Journal {
+    env: FunctionEnv<JournalEnv>,
+}
+
+struct JournalEnv {
+    mode: Mode,
+    memory: Option<Memory>,
+}
+
+enum Mode {
+    Record(File),
+    Replay {
+        entries: VecDeque<JournalEntry>,
+        position: usize,
+    },
+}
+
+/// A single recorded WASI call.
+#[derive(Debug, Clone, PartialEq)]
+struct JournalEntry {
+    module: String,
+    name: String,
+    args: Vec<Value>,
+    results: Vec<Value>,
+    /// The `(offset, bytes)` ranges of linear memory changed by the call.
+    writes: Vec<(u64, Vec<u8>)>,
+    outcome: Outcome,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Outcome {
+    Returned,
+    Exit(u32),
+    Trap(String),
+}
+
+impl Journal {
+    /// Creates a journal recording into the file at `path`.
+    pub fn record(store: &mut impl AsStoreMut, path: &Path) -> Result<Self> {
+        let mut file = File::create(path)
+            .with_context(|| format!("failed to create journal `{}`", path.display()))?;
+        file.write_all(MAGIC)?;
+        Ok(Self::new(store, Mode::Record(file)))
+    }
+
+    /// Creates a journal replaying the file at `path`.
+    pub fn replay(store: &mut impl AsStoreMut, path: &Path) -> Result<Self> {
+        let contents = fs::read(path)
+            .with_context(|| format!("failed to read journal `{}`", path.display()))?;
+        let entries = decode_journal(&contents)
+            .with_context(|| format!("failed to parse journal `{}`", path.display()))?;
+        Ok(Self::new(
+            store,
+            Mode::Replay {
+                entries,
+                position: 0,
+            },
+        ))
+    }
+
+    fn new(store: &mut impl AsStoreMut, mode: Mode) -> Self {
+        let env = FunctionEnv::new(store, JournalEnv { mode, memory: None });
+        Self { env }
+    }
+
+    /// Wraps every function in `imports` so its calls go through the journal.
+    pub fn wrap_imports(&self, store: &mut impl AsStoreMut, imports: &Imports) -> Imports {
+        let mut wrapped = Imports::new();
+        for ((module, name), extern_) in imports {
+            let extern_ = match extern_ {
+                Extern::Function(inner) => {
+                    Extern::Function(self.wrap_function(store, module.clone(), name.clone(), inner))
+                }
+                other => other,
+            };
+            wrapped.define(&module, &name, extern_);
+        }
+        wrapped
+    }
+
+    /// Sets the memory whose changes are recorded or replayed.
+    pub fn set_memory(&self, store: &mut impl AsStoreMut, memory: Memory) {
+        self.env.as_mut(store).memory = Some(memory);
+    }
+
+    fn wrap_function(
+        &self,
+        store: &mut impl AsStoreMut,
+        module: String,
+        name: String,
+        inner: Function,
+    ) -> Function {
+        let ty = inner.ty(store);
+        Function::new_with_env(
+            store,
+            &self.env,
+            ty,
+            move |mut env: FunctionEnvMut<JournalEnv>, args: &[Value]| {
+                if let Mode::Record(_) = env.data().mode {
+                    record_call(&mut env, &module, &name, &inner, args)
+                } else {
+                    replay_call(&mut env, &module, &name, args)
+                }
+            },
+        )
+    }
+}
+
+fn snapshot(view: &MemoryView) -> Vec<u8> {
+    let mut data = vec![0; view.data_size() as usize];
+    // Reading the whole view can't be out of bounds.
+    view.read(0, &mut data).unwrap();
+    data
+}
+
+fn record_call(
+    env: &mut FunctionEnvMut<JournalEnv>,
+    module: &str,
+    name: &str,
+    inner: &Function,
+    args: &[Value],
+) -> Result<Vec<Value>, RuntimeError> {
+    let memory = env.data().memory.clone();
+    let before = memory.as_ref().map(|memory| snapshot(&memory.view(env)));
+    let result = inner.call(env, args);
+    let writes = match (&memory, before) {
+        (Some(memory), Some(before)) => diff_memory(&before, &snapshot(&memory.view(env))),
+        _ => Vec::new(),
+    };
+
+    let (result, results, outcome) = match result {
+        Ok(results) => {
+            let results = results.into_vec();
+            (Ok(results.clone()), results, Outcome::Returned)
+        }
+        Err(err) => match err.downcast::<WasiError>() {
+            Ok(WasiError::Exit(code)) => (
+                Err(RuntimeError::user(Box::new(WasiError::Exit(code)))),
+                Vec::new(),
+                Outcome::Exit(code),
+            ),
+            Ok(err) => {
+                let message = err.to_string();
+                (
+                    Err(RuntimeError::user(Box::new(err))),
+                    Vec::new(),
+                    Outcome::Trap(message),
+                )
+            }
+            Err(err) => {
+                let message = err.message();
+                (Err(err), Vec::new(), Outcome::Trap(message))
+            }
+        },
+    };
+
+    let entry = JournalEntry {
+        module: module.to_string(),
+        name: name.to_string(),
+        args: args.to_vec(),
+        results,
+        writes,
+        outcome,
+    };
+    let mut bytes = Vec::new();
+    encode_entry(&entry, &mut bytes).map_err(|e| RuntimeError::new(e.to_string()))?;
+    if let Mode::Record(file) = &mut env.data_mut().mode {
+        // Entries are written (unbuffered) as they happen, so the journal is
+        // complete even if the process exits from within the guest.
+        file.write_all(&bytes)
+            .map_err(|e| RuntimeError::new(format!("failed to write journal: {}", e)))?;
+    }
+    result
+}
+
+fn replay_call(
+    env: &mut FunctionEnvMut<JournalEnv>,
+    module: &str,
+    name: &str,
+    args: &[Value],
+) -> Result<Vec<Value>, RuntimeError> {
+    let entry = match &mut env.data_mut().mode {
+        Mode::Replay { entries, position } => {
+            *position += 1;
+            let position = *position;
+            let entry = entries.pop_front().ok_or_else(|| {
+                RuntimeError::new(format!(
+                    "replay journal exhausted at call #{} to `{}.{}`",
+                    position, module, name
+                ))
+            })?;
+            if entry.module != module || entry.name != name || entry.args != args {
+                return Err(RuntimeError::new(format!(
+                    "replay diverged at call #{}: the journal has `{}.{}` with {:?} but the guest called `{}.{}` with {:?}",
+                    position, entry.module, entry.name, entry.args, module, name, args
+                )));
+            }
+            entry
+        }
+        Mode::Record(_) => unreachable!(),
+    };
+
+    if let Some(memory) = env.data().memory.clone() {
+        let view = memory.view(env);
+        for (offset, bytes) in &entry.writes {
+            view.write(*offset, bytes)
+                .map_err(|e| RuntimeError::new(e.to_string()))?;
+        }
+        if name == "fd_write" {
+            echo_fd_write(&view, args);
+        }
+    }
+
+    match entry.outcome {
+        Outcome::Returned => Ok(entry.results),
+        Outcome::Exit(code) => Err(RuntimeError::user(Box::new(WasiError::Exit(code)))),
+        Outcome::Trap(message) => Err(RuntimeError::new(message)),
+    }
+}
+
+/// Echoes the guest's writes to stdout and stderr on the host.
+fn echo_fd_write(view: &MemoryView, args: &[Value]) {
+    let (fd, iovs, iovs_len) = match args {
+        [Value::I32(fd), Value::I32(iovs), Value::I32(iovs_len), ..] => {
+            (*fd, *iovs as u32 as u64, *iovs_len as u32 as u64)
+        }
+        _ => return,
+    };
+    let mut out: Box<dyn Write> = match fd {
+        1 => Box::new(io::stdout()),
+        2 => Box::new(io::stderr()),
+        _ => return,
+    };
+    for i in 0..iovs_len {
+        let mut iov = [0u8; 8];
+        if view.read(iovs + i * 8, &mut iov).is_err() {
+            return;
+        }
+        let buf = u32::from_le_bytes(iov[..4].try_into().unwrap()) as u64;
+        let len = u32::from_le_bytes(iov[4..].try_into().unwrap()) as usize;
+        let mut data = vec![0; len];
+        if view.read(buf, &mut data).is_err() {
+            return;
+        }
+        let _ = out.write_all(&data);
+    }
+    let _ = out.flush();
+}
+
+/// Returns the ranges of `after` that differ from `before`.
+///
+/// Ranges separated by less than a few unchanged bytes are merged, and
+/// memory that grew during the call is reported as written.
+fn diff_memory(before: &[u8], after: &[u8]) -> Vec<(u64, Vec<u8>)> {
+    const GAP: usize = 16;
+    let mut writes: Vec<(u64, Vec<u8>)> = Vec::new();
+    let mut i = 0;
+    while i < after.len() {
+        if before.get(i) == Some(&after[i]) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let mut end = i + 1;
+        let mut unchanged = 0;
+        i += 1;
+        while i < after.len() && unchanged < GAP {
+            if before.get(i) == Some(&after[i]) {
+                unchanged += 1;
+            } else {
+                unchanged = 0;
+                end = i + 1;
+            }
+            i += 1;
+        }
+        writes.push((start as u64, after[start..end].to_vec()));
+    }
+    writes
+}
+
+fn encode_entry(entry: &JournalEntry, out: &mut Vec<u8>) -> Result<()> {
+    encode_str(&entry.module, out);
+    encode_str(&entry.name, out);
+    encode_values(&entry.args, out)?;
+    encode_values(&entry.results, out)?;
+    out.extend_from_slice(&(entry.writes.len() as u32).to_le_bytes());
+    for (offset, bytes) in &entry.writes {
+        out.extend_from_slice(&offset.to_le_bytes());
+        out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        out.extend_from_slice(bytes);
+    }
+    match &entry.outcome {
+        Outcome::Returned => out.push(0),
+        Outcome::Exit(code) => {
+            out.push(1);
+            out.extend_from_slice(&code.to_le_bytes());
+        }
+        Outcome::Trap(message) => {
+            out.push(2);
+            encode_str(message, out);
+        }
+    }
+    Ok(())
+}
+
+fn encode_str(s: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn encode_values(values: &[Value], out: &mut Vec<u8>) -> Result<()> {
+    out.extend_from_slice(&(values.len() as u32).to_le_bytes());
+    for value in values {
+        let (tag, bits) = match value {
+            Value::I32(v) => (0u8, *v as u32 as u64),
+            Value::I64(v) => (1, *v as u64),
+            Value::F32(v) => (2, v.to_bits() as u64),
+            Value::F64(v) => (3, v.to_bits()),
+            other => bail!("can't record a value of type {:?}", other.ty()),
+        };
+        out.push(tag);
+        out.extend_from_slice(&bits.to_le_bytes());
+    }
+    Ok(())
+}
+
+fn decode_journal(bytes: &[u8]) -> Result<VecDeque<JournalEntry>> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        bail!("not a wasmer journal");
+    }
+    let mut reader = Reader {
+        bytes,
+        position: MAGIC.len(),
+    };
+    let mut entries = VecDeque::new();
+    while reader.position < bytes.len() {
+        entries.push_back(reader.entry()?);
+    }
+    Ok(entries)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| anyhow!("unexpected end of journal"))?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8(self.take(len)?.to_vec())?)
+    }
+
+    fn values(&mut self) -> Result<Vec<Value>> {
+        let len = self.u32()?;
+        (0..len)
+            .map(|_| {
+                let tag = self.u8()?;
+                let bits = self.u64()?;
+                Ok(match tag {
+                    0 => Value::I32(bits as u32 as i32),
+                    1 => Value::I64(bits as i64),
+                    2 => Value::F32(f32::from_bits(bits as u32)),
+                    3 => Value::F64(f64::from_bits(bits)),
+                    _ => bail!("invalid value tag {}", tag),
+                })
+            })
+            .collect()
+    }
+
+    fn entry(&mut self) -> Result<JournalEntry> {
+        let module = self.string()?;
+        let name = self.string()?;
+        let args = self.values()?;
+        let results = self.values()?;
+        let writes = (0..self.u32()?)
+            .map(|_| {
+                let offset = self.u64()?;
+                let len = self.u32()? as usize;
+                Ok((offset, self.take(len)?.to_vec()))
+            })
+            .collect::<Result<_>>()?;
+        let outcome = match self.u8()? {
+            0 => Outcome::Returned,
+            1 => Outcome::Exit(self.u32()?),
+            2 => Outcome::Trap(self.string()?),
+            tag => bail!("invalid outcome tag {}", tag),
+        };
+        Ok(JournalEntry {
+            module,
+            name,
+            args,
+            results,
+            writes,
+            outcome,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journal_roundtrip() {
+        let entries = vec![
+            JournalEntry {
+                module: "wasi_snapshot_preview1".to_string(),
+                name: "clock_time_get".to_string(),
+                args: vec![Value::I32(1), Value::I64(1000), Value::I32(64)],
+                results: vec![Value::I32(0)],
+                writes: vec![(64, 1_234_567u64.to_le_bytes().to_vec())],
+                outcome: Outcome::Returned,
+            },
+            JournalEntry {
+                module: "wasi_snapshot_preview1".to_string(),
+                name: "proc_exit".to_string(),
+                args: vec![Value::I32(3)],
+                results: vec![],
+                writes: vec![],
+                outcome: Outcome::Exit(3),
+            },
+        ];
+        let mut bytes = MAGIC.to_vec();
+        for entry in &entries {
+            encode_entry(entry, &mut bytes).unwrap();
+        }
+        assert_eq!(decode_journal(&bytes).unwrap(), entries);
+        assert!(decode_journal(&bytes[..bytes.len() - 1]).is_err());
+        assert!(decode_journal(b"garbage!").is_err());
+    }
+
+    #[test]
+    fn memory_diff() {
+        let before = vec![0u8; 64];
+        let mut after = before.clone();
+        after[2] = 1;
+        after[4] = 2;
+        after[40] = 3;
+        after.extend_from_slice(&[7, 7]);
+        assert_eq!(
+            diff_memory(&before, &after),
+            vec![(2, vec![1, 0, 2]), (40, vec![3]), (64, vec![7, 7])]
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! `wasmer run --max-memory`, `--max-table-elements` and
+//! `--max-call-depth`, bounding the memories, tables and calls of the
+//! guest.
+
+use anyhow::{bail, Result};
+use bytesize::ByteSize;
+use clap::Parser;
+use std::ptr::NonNull;
+use std::sync::Arc;
+use wasmer::vm::{MemoryError, MemoryStyle, TableStyle, VMMemoryDefinition, VMTableDefinition};
+use wasmer::{
+    vm, BaseTunables, MemoryType, Pages, Store, TableType, Tunables, WASM_MAX_PAGES, WASM_PAGE_SIZE,
+};
+use wasmer_middlewares::CallDepthLimit;
+
+#[derive(Debug, Parser, Clone, Default)]
+/// Resource limits of the guest
+pub struct LimitOptions {
+    /// The size each linear memory of the guest can grow to (e.g. `128MiB`),
+    /// rounded down to whole Wasm pages of 64KiB
+    #[clap(long = "max-memory", name = "SIZE")]
+    max_memory: Option<ByteSize>,
+
+    /// The number of elements each table of the guest can grow to
+    #[clap(long = "max-table-elements", name = "ELEMENTS")]
+    max_table_elements: Option<u32>,
+
+    /// The number of calls of Wasm functions the guest can nest, counted
+    /// by instrumenting the module. The size of the stack bounds them
+    /// too, see `--stack-size`
+    #[clap(long = "max-call-depth", name = "CALLS")]
+    max_call_depth: Option<u32>,
+}
+
+impl LimitOptions {
+    /// Whether the module needs to be instrumented to enforce the limits.
+    pub fn instruments(&self) -> bool {
+        self.max_call_depth.is_some()
+    }
+
+    /// Creates the middleware limiting the depth of calls, if
+    /// `--max-call-depth` was given.
+    pub fn middleware(&self) -> Option<Arc<CallDepthLimit>> {
+        self.max_call_depth
+            .map(|max_depth| Arc::new(CallDepthLimit::new(max_depth)))
+    }
+
+    /// Replaces the tunables of `store` by ones enforcing the limits, if
+    /// any were given.
+    pub fn apply(&self, store: Store) -> Result<Store> {
+        if self.max_memory.is_none() && self.max_table_elements.is_none() {
+            return Ok(store);
+        }
+        let max_memory = match self.max_memory {
+            Some(size) => {
+                let pages = (size.as_u64() / WASM_PAGE_SIZE as u64).min(WASM_MAX_PAGES as u64);
+                if pages == 0 {
+                    bail!("`--max-memory` must be at least one Wasm page (64KiB)");
+                }
+                Some(Pages(pages as u32))
+            }
+            None => None,
+        };
+        let engine = store.engine().clone();
+        let tunables = LimitingTunables {
+            base: BaseTunables::for_target(engine.target()),
+            max_memory,
+            max_table_elements: self.max_table_elements,
+        };
+        Ok(Store::new_with_tunables(engine, tunables))
+    }
+}
+
+/// Tunables lowering the maximum of the memories and tables of the guest
+/// to the limits, so that growing them beyond fails as it would at their
+/// declared maximum.
+///
+/// The styles are those of the base tunables for the declared types, so
+/// that the compiled module doesn't depend on the limits and can be
+/// cached.
+struct LimitingTunables {
+    base: BaseTunables,
+    max_memory: Option<Pages>,
+    max_table_elements: Option<u32>,
+}
+
+impl LimitingTunables {
+    fn limit_memory(&self, ty: &MemoryType) -> Result<MemoryType, MemoryError> {
+        let limit = match self.max_memory {
+            Some(limit) => limit,
+            None => return Ok(*ty),
+        };
+        if ty.minimum > limit {
+            return Err(MemoryError::MinimumMemoryTooLarge {
+                min_requested: ty.minimum,
+                max_allowed: limit,
+            });
+        }
+        let mut limited = *ty;
+        limited.maximum = Some(ty.maximum.map_or(limit, |maximum| maximum.min(limit)));
+        Ok(limited)
+    }
+
+    fn limit_table(&self, ty: &TableType) -> Result<TableType, String> {
+        let limit = match self.max_table_elements {
+            Some(limit) => limit,
+            None => return Ok(*ty),
+        };
+        if ty.minimum > limit {
+            return Err(format!(
+                "the table needs at least {} elements, more than `--max-table-elements` ({})",
+                ty.minimum, limit
+            ));
+        }
+        let mut limited = *ty;
+        limited.maximum = Some(ty.maximum.map_or(limit, |maximum| maximum.min(limit)));
+        Ok(limited)
+    }
+}
+
+impl Tunables for LimitingTunables {
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        self.base.memory_style(memory)
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.base.table_style(table)
+    }
+
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<vm::VMMemory, MemoryError> {
+        self.base.create_host_memory(&self.limit_memory(ty)?, style)
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<vm::VMMemory, MemoryError> {
+        self.base
+            .create_vm_memory(&self.limit_memory(ty)?, style, vm_definition_location)
+    }
+
+    fn create_host_table(&self, ty: &TableType, style: &TableStyle) -> Result<vm::VMTable, String> {
+        self.base.create_host_table(&self.limit_table(ty)?, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<vm::VMTable, String> {
+        self.base
+            .create_vm_table(&self.limit_table(ty)?, style, vm_definition_location)
+    }
+}
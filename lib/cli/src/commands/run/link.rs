@@ -0,0 +1,115 @@
+//! `wasmer run --link`, satisfying the imports of the module with the
+//! exports of other modules.
+//!
+//! Every linked module is instantiated before the main one, in the order
+//! of the command line, and its exports are provided under its name: a
+//! module linked with `--link helper.wasm` satisfies the imports of
+//! `helper.*`. A linked module can itself import from the modules linked
+//! before it.
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::path::PathBuf;
+use std::str::FromStr;
+use wasmer::{Imports, Instance, Module, Store};
+
+#[derive(Debug, Parser, Clone, Default)]
+/// Link options
+pub struct LinkOptions {
+    /// Instantiate this module first and satisfy the imports of
+    /// `NAME.*` with its exports. `NAME` defaults to the file name of the
+    /// module, without its extension. Can be repeated
+    #[clap(long = "link", name = "[NAME=]MODULE")]
+    link: Vec<LinkedModule>,
+}
+
+/// A module of `--link`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LinkedModule {
+    /// The import module name its exports are provided under.
+    name: String,
+    path: PathBuf,
+}
+
+impl FromStr for LinkedModule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some((name, path)) = s.split_once('=') {
+            if name.is_empty() {
+                bail!("the name of the linked module `{}` is empty", path);
+            }
+            return Ok(Self {
+                name: name.to_string(),
+                path: PathBuf::from(path),
+            });
+        }
+        let path = PathBuf::from(s);
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| anyhow!("can't name the linked module `{}`, use `NAME={}`", s, s))?
+            .to_string();
+        Ok(Self { name, path })
+    }
+}
+
+impl LinkOptions {
+    /// Whether modules are linked. The main module then imports from
+    /// namespaces that are not WASI.
+    pub fn is_empty(&self) -> bool {
+        self.link.is_empty()
+    }
+
+    /// Instantiates the linked modules with `imports`, and adds their
+    /// exports to it under their names.
+    pub fn link(&self, store: &mut Store, imports: &mut Imports) -> Result<()> {
+        for linked in &self.link {
+            if imports.contains_namespace(&linked.name) {
+                bail!(
+                    "the linked module `{}` is named `{}`, which already provides imports",
+                    linked.path.display(),
+                    linked.name
+                );
+            }
+            let module = Module::from_file(&*store, &linked.path).with_context(|| {
+                format!(
+                    "failed to compile the linked module `{}`",
+                    linked.path.display()
+                )
+            })?;
+            let instance = Instance::new(store, &module, imports).with_context(|| {
+                format!(
+                    "failed to instantiate the linked module `{}`",
+                    linked.path.display()
+                )
+            })?;
+            imports.register_namespace(&linked.name, instance.exports);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_linked_modules() {
+        assert_eq!(
+            "lib/helper.wasm".parse::<LinkedModule>().unwrap(),
+            LinkedModule {
+                name: "helper".to_string(),
+                path: PathBuf::from("lib/helper.wasm"),
+            }
+        );
+        assert_eq!(
+            "env=lib/helper.wat".parse::<LinkedModule>().unwrap(),
+            LinkedModule {
+                name: "env".to_string(),
+                path: PathBuf::from("lib/helper.wat"),
+            }
+        );
+        assert!("=helper.wasm".parse::<LinkedModule>().is_err());
+    }
+}
@@ -0,0 +1,416 @@
+//! `wasmer run namespace/name@version`, running a package of the
+//! registry.
+//!
+//! The package archive is downloaded once into the cache directory,
+//! checked against the SHA-256 checksum given by the registry and
+//! extracted there. Its `wapm.toml` manifest declares the modules of the
+//! package, the commands running them and the directories of the package
+//! to mount in the filesystem of the guest.
+
+use crate::common::get_cache_dir;
+use crate::utils;
+use anyhow::{anyhow, bail, Context, Result};
+use http_req::request::{Method, Request};
+use http_req::uri::Uri;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The registry packages are resolved with, unless overridden by the
+/// `WASMER_REGISTRY` environment variable.
+const DEFAULT_REGISTRY: &str = "https://registry.wapm.io/graphql";
+
+/// The name of the manifest at the root of a package.
+const MANIFEST: &str = "wapm.toml";
+
+/// A package of the registry, like `namespace/name@1.2.0`, or
+/// `namespace/name` for its latest version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageSpecifier {
+    namespace: String,
+    name: String,
+    version: Option<String>,
+}
+
+impl FromStr for PackageSpecifier {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (package, version) = match s.split_once('@') {
+            Some((package, version)) => (package, Some(version)),
+            None => (s, None),
+        };
+        let (namespace, name) = package.split_once('/').ok_or(())?;
+        if !is_identifier(namespace) || !is_identifier(name) {
+            return Err(());
+        }
+        if let Some(version) = version {
+            if !is_identifier(version) {
+                return Err(());
+            }
+        }
+        Ok(Self {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            version: version.map(str::to_string),
+        })
+    }
+}
+
+/// Whether `s` can name a namespace, a package or a version, which are
+/// also used as directory names in the cache.
+fn is_identifier(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+impl fmt::Display for PackageSpecifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.namespace, self.name)?;
+        if let Some(version) = &self.version {
+            write!(f, "@{}", version)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `wapm.toml` manifest of a package.
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    package: ManifestPackage,
+    #[serde(default, rename = "module")]
+    modules: Vec<ManifestModule>,
+    #[serde(default, rename = "command")]
+    commands: Vec<ManifestCommand>,
+    /// The directories of the package to mount, by guest path.
+    #[serde(default)]
+    fs: BTreeMap<String, PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestPackage {
+    /// The command run by default, needed if there are several.
+    entrypoint: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestModule {
+    name: String,
+    source: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestCommand {
+    name: String,
+    module: String,
+}
+
+/// A package extracted in the cache, with the command to run.
+#[derive(Debug)]
+pub struct Package {
+    /// The name of the command, passed as the program name to the guest.
+    pub command: String,
+    /// The module the command runs.
+    pub module: PathBuf,
+    /// The directories of the package to mount, by guest path.
+    pub mapped_dirs: Vec<(String, PathBuf)>,
+}
+
+impl Package {
+    /// Fetches the package from the registry, unless it is cached
+    /// already, and picks its entrypoint command.
+    pub fn fetch(specifier: &PackageSpecifier) -> Result<Self> {
+        let registry = Registry::from_env();
+        let (version, download_url, checksum) = registry.resolve(specifier)?;
+        if !is_identifier(&version) {
+            bail!(
+                "the registry replied with the invalid version `{}`",
+                version
+            );
+        }
+        let mut dir = get_cache_dir();
+        dir.push("packages");
+        dir.push(&specifier.namespace);
+        dir.push(format!("{}@{}", specifier.name, version));
+        if !dir.join(MANIFEST).exists() {
+            eprintln!(
+                "Downloading {}/{}@{}",
+                specifier.namespace, specifier.name, version
+            );
+            download(&download_url, &checksum, &dir)?;
+        }
+        Self::from_dir(&dir)
+    }
+
+    /// Picks the entrypoint command of the package extracted in `dir`.
+    fn from_dir(dir: &Path) -> Result<Self> {
+        let manifest = fs::read_to_string(dir.join(MANIFEST))
+            .with_context(|| format!("failed to read the `{}` of the package", MANIFEST))?;
+        let manifest: Manifest = toml::from_str(&manifest)
+            .with_context(|| format!("invalid `{}` in the package", MANIFEST))?;
+        let command = match (&manifest.package.entrypoint, &manifest.commands[..]) {
+            (Some(entrypoint), commands) => commands
+                .iter()
+                .find(|command| &command.name == entrypoint)
+                .ok_or_else(|| anyhow!("the entrypoint `{}` isn't a command", entrypoint))?,
+            (None, [command]) => command,
+            (None, []) => bail!("the package has no command to run"),
+            (None, commands) => bail!(
+                "the package has several commands ({}) but no entrypoint",
+                commands
+                    .iter()
+                    .map(|command| format!("`{}`", command.name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        let module = manifest
+            .modules
+            .iter()
+            .find(|module| module.name == command.module)
+            .ok_or_else(|| {
+                anyhow!(
+                    "the module `{}` of the command `{}` isn't in the package",
+                    command.module,
+                    command.name
+                )
+            })?;
+        Ok(Self {
+            command: command.name.clone(),
+            module: package_path(dir, &module.source)?,
+            mapped_dirs: manifest
+                .fs
+                .iter()
+                .map(|(guest, host)| Ok((guest.clone(), package_path(dir, host)?)))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+/// The path of the file `path` of the manifest in the package extracted
+/// in `dir`, which must not be outside of it.
+fn package_path(dir: &Path, path: &Path) -> Result<PathBuf> {
+    if !path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        bail!("the path `{}` is outside of the package", path.display());
+    }
+    let joined = dir.join(path);
+    // The archive may also have symbolic links leading out of it
+    if let (Ok(dir), Ok(canonical)) = (dir.canonicalize(), joined.canonicalize()) {
+        if !canonical.starts_with(dir) {
+            bail!("the path `{}` is outside of the package", path.display());
+        }
+    }
+    Ok(joined)
+}
+
+/// The GraphQL API of a registry.
+struct Registry {
+    url: String,
+}
+
+impl Registry {
+    fn from_env() -> Self {
+        Self {
+            url: std::env::var("WASMER_REGISTRY").unwrap_or_else(|_| DEFAULT_REGISTRY.to_string()),
+        }
+    }
+
+    /// Resolves the version of the package, returning it with the URL
+    /// and the SHA-256 checksum of its archive.
+    fn resolve(&self, specifier: &PackageSpecifier) -> Result<(String, String, String)> {
+        let query = serde_json::json!({
+            "query": "query($name: String!, $version: String) {
+                getPackageVersion(name: $name, version: $version) {
+                    version
+                    distribution { downloadUrl sha256 }
+                }
+            }",
+            "variables": {
+                "name": format!("{}/{}", specifier.namespace, specifier.name),
+                "version": specifier.version,
+            },
+        });
+        let body = serde_json::to_vec(&query)?;
+        let uri = Uri::try_from(self.url.as_str())
+            .map_err(|e| anyhow!("invalid registry URL `{}`: {}", self.url, e))?;
+        let mut response = Vec::new();
+        let status = Request::new(&uri)
+            .method(Method::POST)
+            .header("User-Agent", "wasmer")
+            .header("Content-Type", "application/json")
+            .header("Content-Length", &body.len())
+            .timeout(Some(Duration::from_secs(30)))
+            .body(&body)
+            .send(&mut response)
+            .with_context(|| format!("failed to query the registry at `{}`", self.url))?
+            .status_code();
+        if !status.is_success() {
+            bail!(
+                "the registry at `{}` replied with status {}",
+                self.url,
+                status
+            );
+        }
+        let response: serde_json::Value = serde_json::from_slice(&response)
+            .with_context(|| format!("invalid reply of the registry at `{}`", self.url))?;
+        if let Some(message) = response["errors"][0]["message"].as_str() {
+            bail!("the registry at `{}` replied: {}", self.url, message);
+        }
+        let package = &response["data"]["getPackageVersion"];
+        if package.is_null() {
+            bail!("the package `{}` isn't in the registry", specifier);
+        }
+        match (
+            package["version"].as_str(),
+            package["distribution"]["downloadUrl"].as_str(),
+            package["distribution"]["sha256"].as_str(),
+        ) {
+            (Some(version), Some(url), Some(checksum)) => {
+                Ok((version.to_string(), url.to_string(), checksum.to_string()))
+            }
+            _ => bail!("invalid reply of the registry at `{}`", self.url),
+        }
+    }
+}
+
+/// Downloads the archive at `url`, checks it has the SHA-256 `checksum`
+/// and extracts it into `dir`.
+fn download(url: &str, checksum: &str, dir: &Path) -> Result<()> {
+    let archive = utils::download(url)?;
+    utils::verify_sha256(&archive, checksum).context("the package archive is corrupted")?;
+
+    // The archive is extracted next to `dir` then moved, so that a
+    // failed extraction doesn't leave a partial package in the cache
+    let parent = dir.parent().unwrap();
+    fs::create_dir_all(parent)?;
+    let extracted = tempfile::tempdir_in(parent)?;
+    utils::extract_tar_gz(&archive, extracted.path()).context("invalid package archive")?;
+    if !extracted.path().join(MANIFEST).exists() {
+        bail!("the package has no `{}`", MANIFEST);
+    }
+    let extracted = extracted.into_path();
+    if let Err(e) = fs::rename(&extracted, dir) {
+        let _ = fs::remove_dir_all(&extracted);
+        // Another process may have extracted it in the meantime
+        if !dir.join(MANIFEST).exists() {
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_package_specifier() {
+        assert_eq!(
+            "author/pkg@1.2.0".parse(),
+            Ok(PackageSpecifier {
+                namespace: "author".to_string(),
+                name: "pkg".to_string(),
+                version: Some("1.2.0".to_string()),
+            })
+        );
+        assert_eq!(
+            "author/pkg".parse(),
+            Ok(PackageSpecifier {
+                namespace: "author".to_string(),
+                name: "pkg".to_string(),
+                version: None,
+            })
+        );
+        assert_eq!("pkg.wasm".parse::<PackageSpecifier>(), Err(()));
+        assert_eq!("./pkg.wasm".parse::<PackageSpecifier>(), Err(()));
+        assert_eq!("author/pkg@".parse::<PackageSpecifier>(), Err(()));
+        assert_eq!("a/b/c".parse::<PackageSpecifier>(), Err(()));
+        assert_eq!("a/b@../../c".parse::<PackageSpecifier>(), Err(()));
+        assert!(!is_identifier("../1.0.0"));
+    }
+
+    #[test]
+    fn package_paths_stay_in_the_package() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let manifest = |source: &str, fs: &str| {
+            format!(
+                r#"
+[package]
+name = "author/pkg"
+version = "1.2.0"
+
+[[module]]
+name = "pkg"
+source = "{}"
+
+[[command]]
+name = "pkg"
+module = "pkg"
+
+[fs]
+"/data" = "{}"
+"#,
+                source, fs
+            )
+        };
+        for (source, fs) in [
+            ("../pkg.wasm", "assets"),
+            ("/pkg.wasm", "assets"),
+            ("pkg.wasm", "/etc"),
+            ("pkg.wasm", "assets/../.."),
+        ] {
+            fs::write(dir.path().join(MANIFEST), manifest(source, fs))?;
+            assert!(Package::from_dir(dir.path()).is_err(), "{} {}", source, fs);
+        }
+        fs::write(dir.path().join(MANIFEST), manifest("./pkg.wasm", "assets"))?;
+        assert!(Package::from_dir(dir.path()).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn package_entrypoint() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(
+            dir.path().join(MANIFEST),
+            r#"
+[package]
+name = "author/pkg"
+version = "1.2.0"
+entrypoint = "pkg"
+
+[[module]]
+name = "pkg"
+source = "target/pkg.wasm"
+abi = "wasi"
+
+[[command]]
+name = "pkg"
+module = "pkg"
+
+[[command]]
+name = "other"
+module = "pkg"
+
+[fs]
+"/data" = "assets"
+"#,
+        )?;
+        let package = Package::from_dir(dir.path())?;
+        assert_eq!(package.command, "pkg");
+        assert_eq!(package.module, dir.path().join("target/pkg.wasm"));
+        assert_eq!(
+            package.mapped_dirs,
+            vec![("/data".to_string(), dir.path().join("assets"))]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,281 @@
+//! A sampling CPU profiler for the guest, behind `wasmer run --profile cpu`.
+//!
+//! A sampler thread interrupts the thread running the guest with
+//! `SIGPROF` at a fixed frequency. The signal handler only records the
+//! return addresses of the interrupted stack, which is unwound with the
+//! unwind info the compilers generate for the guest functions. The sampler
+//! then resolves them to guest functions, and counts the stacks.
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use wasmer_compiler::{FrameInfo, FRAME_INFO};
+use wasmer_types::METADATA_NAME;
+
+#[derive(Debug, Parser, Clone, Default)]
+/// Profiling options
+pub struct ProfileOptions {
+    /// Profile the guest while it runs (only `cpu` is supported)
+    #[clap(long = "profile", name = "PROFILER", parse(try_from_str = parse_profiler))]
+    profile: Option<Profiler>,
+
+    /// Where to write the profile: a flamegraph if the file ends with
+    /// `.svg`, or else the folded stacks `inferno` and `flamegraph.pl`
+    /// read
+    #[clap(
+        long = "profile-out",
+        name = "PROFILE_OUT",
+        parse(from_os_str),
+        default_value = "profile.svg"
+    )]
+    profile_out: PathBuf,
+
+    /// How many times per second the guest stack is sampled
+    #[clap(long = "profile-frequency", name = "HZ", default_value = "99")]
+    profile_frequency: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Profiler {
+    Cpu,
+}
+
+fn parse_profiler(profiler: &str) -> Result<Profiler> {
+    match profiler {
+        "cpu" => Ok(Profiler::Cpu),
+        _ => bail!("unknown profiler `{}`, expected `cpu`", profiler),
+    }
+}
+
+impl ProfileOptions {
+    /// Whether profiling was requested.
+    pub fn is_enabled(&self) -> bool {
+        self.profile.is_some()
+    }
+
+    /// Starts profiling the current thread, if a profiler was requested.
+    pub fn start(&self) -> Result<Option<CpuProfiler>> {
+        match self.profile {
+            Some(Profiler::Cpu) => {
+                if self.profile_frequency == 0 {
+                    bail!("`--profile-frequency` must be at least 1");
+                }
+                Ok(Some(CpuProfiler::start(self.profile_frequency)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Stops a profiler and writes its profile to `--profile-out`.
+    pub fn finish(&self, profiler: CpuProfiler) -> Result<()> {
+        let stacks = profiler.stop();
+        if stacks.is_empty() {
+            bail!("no samples were taken, the guest ran for too short a time");
+        }
+        let mut lines = stacks
+            .iter()
+            .map(|(stack, count)| format!("{} {}", stack.join(";"), count))
+            .collect::<Vec<_>>();
+        lines.sort();
+
+        let path = &self.profile_out;
+        let file =
+            File::create(path).with_context(|| format!("failed to create `{}`", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        if path
+            .extension()
+            .map_or(false, |extension| extension == "svg")
+        {
+            let mut options = inferno::flamegraph::Options::default();
+            options.title = "wasmer run".to_string();
+            options.count_name = "samples".to_string();
+            inferno::flamegraph::from_lines(
+                &mut options,
+                lines.iter().map(String::as_str),
+                &mut writer,
+            )?;
+        } else {
+            for line in &lines {
+                writeln!(writer, "{}", line)?;
+            }
+        }
+        writer.flush()?;
+        eprintln!(
+            "Wrote a profile of {} samples to `{}`.",
+            stacks.values().sum::<u64>(),
+            path.display()
+        );
+        Ok(())
+    }
+}
+
+/// The deepest stacks recorded, in frames.
+const MAX_DEPTH: usize = 256;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_FRAME: AtomicUsize = AtomicUsize::new(0);
+
+/// The return addresses of the last stack sampled, filled by the signal
+/// handler and read by the sampler once `SAMPLED` is set.
+static FRAMES: [AtomicUsize; MAX_DEPTH] = [NO_FRAME; MAX_DEPTH];
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
+static SAMPLED: AtomicBool = AtomicBool::new(false);
+
+/// Only one thread can be profiled at a time, as the samples go through
+/// the statics above.
+static PROFILING: AtomicBool = AtomicBool::new(false);
+
+/// The stacks sampled so far, from the outermost function, with how many
+/// times they were.
+type Stacks = HashMap<Vec<String>, u64>;
+
+/// A profiler sampling the stack of a thread until it is stopped.
+pub struct CpuProfiler {
+    stop: Arc<AtomicBool>,
+    stacks: Arc<Mutex<Stacks>>,
+    sampler: JoinHandle<()>,
+    previous_action: libc::sigaction,
+}
+
+impl CpuProfiler {
+    /// Starts sampling the current thread `frequency` times per second.
+    fn start(frequency: u32) -> Result<Self> {
+        if PROFILING.swap(true, Ordering::AcqRel) {
+            bail!("another thread is being profiled");
+        }
+        let previous_action = unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_sigprof as usize;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            let mut previous_action: libc::sigaction = std::mem::zeroed();
+            if libc::sigaction(libc::SIGPROF, &action, &mut previous_action) != 0 {
+                PROFILING.store(false, Ordering::Release);
+                bail!(
+                    "failed to install the profiler signal handler: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+            previous_action
+        };
+
+        let target = unsafe { libc::pthread_self() } as usize;
+        let interval = Duration::from_secs(1) / frequency;
+        let stop = Arc::new(AtomicBool::new(false));
+        let stacks = Arc::new(Mutex::new(Stacks::new()));
+        let sampler = {
+            let stop = stop.clone();
+            let stacks = stacks.clone();
+            thread::Builder::new()
+                .name("wasmer-profiler".to_string())
+                .spawn(move || sample(target as libc::pthread_t, interval, &stop, &stacks))?
+        };
+        Ok(Self {
+            stop,
+            stacks,
+            sampler,
+            previous_action,
+        })
+    }
+
+    /// Stops sampling, returning the stacks sampled.
+    fn stop(self) -> Stacks {
+        self.stop.store(true, Ordering::Release);
+        let _ = self.sampler.join();
+        unsafe {
+            libc::sigaction(libc::SIGPROF, &self.previous_action, std::ptr::null_mut());
+        }
+        PROFILING.store(false, Ordering::Release);
+        let mut stacks = self.stacks.lock().unwrap();
+        std::mem::take(&mut *stacks)
+    }
+}
+
+/// Records the return addresses of the interrupted stack.
+extern "C" fn on_sigprof(_signal: libc::c_int) {
+    // The previous sample hasn't been read yet
+    if SAMPLED.load(Ordering::Acquire) {
+        return;
+    }
+    let mut depth = 0;
+    unsafe {
+        backtrace::trace_unsynchronized(|frame| {
+            FRAMES[depth].store(frame.ip() as usize, Ordering::Relaxed);
+            depth += 1;
+            depth < MAX_DEPTH
+        });
+    }
+    DEPTH.store(depth, Ordering::Relaxed);
+    SAMPLED.store(true, Ordering::Release);
+}
+
+fn sample(target: libc::pthread_t, interval: Duration, stop: &AtomicBool, stacks: &Mutex<Stacks>) {
+    while !stop.load(Ordering::Acquire) {
+        thread::sleep(interval);
+        if unsafe { libc::pthread_kill(target, libc::SIGPROF) } != 0 {
+            return;
+        }
+        // Give up on the sample if the thread doesn't handle the signal
+        // in time, for instance if it is blocking it
+        let deadline = Instant::now() + interval.max(Duration::from_millis(10));
+        while !SAMPLED.load(Ordering::Acquire) {
+            if Instant::now() >= deadline {
+                break;
+            }
+            thread::yield_now();
+        }
+        if !SAMPLED.load(Ordering::Acquire) {
+            continue;
+        }
+        let depth = DEPTH.load(Ordering::Relaxed);
+        let pcs = FRAMES[..depth]
+            .iter()
+            .map(|pc| pc.load(Ordering::Relaxed))
+            .collect::<Vec<_>>();
+        SAMPLED.store(false, Ordering::Release);
+
+        // The functions are resolved right away, while their module is
+        // still loaded
+        let mut stack = {
+            let frame_info = FRAME_INFO.read().unwrap();
+            pcs.iter()
+                .filter_map(|pc| frame_info.lookup_frame_info(*pc))
+                .map(|frame| {
+                    let function = match frame.function_name() {
+                        Some(name) => name.to_string(),
+                        None => format!("wasm-function[{}]", frame.func_index()),
+                    };
+                    format!("{}`{}", module_label(&frame), function)
+                })
+                .collect::<Vec<_>>()
+        };
+        if stack.is_empty() {
+            stack.push("[host]".to_string());
+        }
+        stack.reverse();
+        *stacks.lock().unwrap().entry(stack).or_insert(0) += 1;
+    }
+}
+
+/// The name of the module of `frame`, followed by its metadata, for the
+/// profiles of different builds of a module to tell them apart.
+fn module_label(frame: &FrameInfo) -> String {
+    let metadata = frame
+        .module_metadata()
+        .iter()
+        .filter(|(key, _)| key.as_str() != METADATA_NAME)
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>();
+    if metadata.is_empty() {
+        return frame.module_name().to_string();
+    }
+    // `;` separates the frames of the folded stacks
+    format!("{}[{}]", frame.module_name(), metadata.join(",")).replace(';', ",")
+}
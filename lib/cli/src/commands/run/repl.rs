@@ -0,0 +1,155 @@
+//! `wasmer run --interactive`, invoking the exports of one instance line
+//! by line, so that its memories and globals are kept between calls.
+
+use super::invoke::{self, OutputFormat};
+use anyhow::{anyhow, bail, Result};
+use std::io::{self, BufRead, Write};
+use wasmer::{AsStoreMut, Instance};
+
+const HELP: &str = "\
+Commands:
+  <function> [ARGS]...  invoke an exported function
+  :exports              list the exported functions and globals
+  :global <name>        print the value of an exported global
+  :help                 print this help
+  :quit                 exit (or end the input)
+Arguments with spaces can be quoted, e.g. `greet \"Hello, world\"`.";
+
+/// Reads commands from stdin until it ends or `:quit`.
+///
+/// An invocation that fails, including by trapping, is reported and the
+/// session goes on with the same instance.
+pub fn run(ctx: &mut impl AsStoreMut, instance: &Instance, output: OutputFormat) -> Result<()> {
+    let interactive = atty::is(atty::Stream::Stdin);
+    if interactive {
+        eprintln!("Type `:help` for the commands, `:quit` to exit.");
+    }
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        if interactive {
+            eprint!("> ");
+            io::stderr().flush()?;
+        }
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Ok(()),
+        };
+        let words = match split_line(&line) {
+            Ok(words) => words,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                continue;
+            }
+        };
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (command.as_str(), args),
+            None => continue,
+        };
+        let result = match command {
+            ":quit" | ":q" => return Ok(()),
+            ":help" | ":h" => {
+                println!("{}", HELP);
+                Ok(())
+            }
+            ":exports" => {
+                list_exports(ctx, instance);
+                Ok(())
+            }
+            ":global" => print_global(ctx, instance, args),
+            command if command.starts_with(':') => {
+                Err(anyhow!("unknown command `{}`, see `:help`", command))
+            }
+            name => invoke_function(ctx, instance, name, args, output),
+        };
+        if let Err(e) = result {
+            eprintln!("error: {:?}", e);
+        }
+    }
+}
+
+fn invoke_function(
+    ctx: &mut impl AsStoreMut,
+    instance: &Instance,
+    name: &str,
+    args: &[String],
+    output: OutputFormat,
+) -> Result<()> {
+    let func = instance.exports.get_function(name).map_err(|_| {
+        anyhow!(
+            "no exported function `{}`, see `:exports` for the functions",
+            name
+        )
+    })?;
+    let func_ty = func.ty(ctx);
+    let params = invoke::parse_args(ctx, instance, name, &func_ty, args)?;
+    let results = func.call(ctx, &params)?;
+    println!("{}", invoke::format_results(&results, output));
+    Ok(())
+}
+
+fn list_exports(ctx: &mut impl AsStoreMut, instance: &Instance) {
+    for (name, func) in instance.exports.iter().functions() {
+        println!("{}: {}", name, func.ty(ctx));
+    }
+    for (name, global) in instance.exports.iter().globals() {
+        println!("{}: global {}", name, global.ty(ctx));
+    }
+}
+
+fn print_global(ctx: &mut impl AsStoreMut, instance: &Instance, args: &[String]) -> Result<()> {
+    let name = match args {
+        [name] => name,
+        _ => bail!("usage: `:global <name>`"),
+    };
+    let global = instance
+        .exports
+        .get_global(name)
+        .map_err(|_| anyhow!("no exported global `{}`", name))?;
+    println!("{}", global.get(ctx));
+    Ok(())
+}
+
+/// Splits a line into words separated by whitespace, where double quotes
+/// group words and `\` escapes the next character.
+fn split_line(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quoted = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => bail!("nothing to escape at the end of the line"),
+            },
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quoted {
+        bail!("unterminated quote");
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_lines() {
+        assert_eq!(split_line("  add 1   -2 ").unwrap(), vec!["add", "1", "-2"]);
+        assert_eq!(
+            split_line(r#"greet "Hello, world" "" a\"b"#).unwrap(),
+            vec!["greet", "Hello, world", "", "a\"b"]
+        );
+        assert!(split_line("").unwrap().is_empty());
+        assert!(split_line(r#"greet "Hello"#).is_err());
+    }
+}
@@ -0,0 +1,58 @@
+//! `wasmer run --print-stats`, reporting the resources used by the call
+//! into the guest.
+
+use clap::Parser;
+use std::sync::Arc;
+use wasmer::{AsStoreMut, Instance};
+use wasmer_middlewares::{CallMeter, InstructionCounter};
+
+#[derive(Debug, Parser, Clone, Default)]
+/// Call statistics options
+pub struct StatsOptions {
+    /// Print the wall time, CPU time and executed instructions of the
+    /// call into the module to stderr. The instructions are counted by
+    /// instrumenting the module
+    #[clap(long = "print-stats")]
+    print_stats: bool,
+}
+
+impl StatsOptions {
+    /// Whether the module needs to be instrumented to count instructions.
+    pub fn instruments(&self) -> bool {
+        self.print_stats
+    }
+
+    /// Creates the middleware counting instructions, if `--print-stats`
+    /// was given.
+    pub fn middleware(&self) -> Option<Arc<InstructionCounter>> {
+        if self.print_stats {
+            Some(Arc::new(InstructionCounter::new()))
+        } else {
+            None
+        }
+    }
+
+    /// Starts measuring a call into `instance`, if `--print-stats` was
+    /// given.
+    pub fn start(&self, store: &mut impl AsStoreMut, instance: &Instance) -> Option<CallMeter> {
+        if self.print_stats {
+            Some(CallMeter::start(store, instance))
+        } else {
+            None
+        }
+    }
+
+    /// Prints what the call of `function` measured by `meter` used.
+    pub fn finish(
+        &self,
+        meter: Option<CallMeter>,
+        store: &mut impl AsStoreMut,
+        instance: &Instance,
+        function: &str,
+    ) {
+        if let Some(meter) = meter {
+            let stats = meter.finish(store, instance);
+            eprintln!("`{}`: {}", function, stats);
+        }
+    }
+}
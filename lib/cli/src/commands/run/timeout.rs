@@ -0,0 +1,137 @@
+//! `wasmer run --timeout`, bounding how long the guest can run.
+
+use crate::error::GuestTimeout;
+use anyhow::{bail, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Parses a duration such as `500ms`, `5s`, `2m` or `1h`. A number
+/// without a unit is in seconds.
+pub fn parse_duration(duration: &str) -> Result<Duration> {
+    let duration = duration.trim();
+    let split = duration
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(duration.len());
+    let (value, unit) = duration.split_at(split);
+    let value: f64 = match value.parse() {
+        Ok(value) => value,
+        Err(_) => bail!("invalid duration `{}`", duration),
+    };
+    let seconds = match unit.trim() {
+        "ms" => value / 1000.,
+        "" | "s" => value,
+        "m" => value * 60.,
+        "h" => value * 3600.,
+        unit => bail!(
+            "unknown unit `{}` in duration `{}`, expected `ms`, `s`, `m` or `h`",
+            unit,
+            duration
+        ),
+    };
+    if !seconds.is_finite() || seconds <= 0. {
+        bail!("the duration `{}` must be positive", duration);
+    }
+    // `Duration::from_secs_f64` panics past the largest duration
+    if seconds >= u64::MAX as f64 {
+        bail!("the duration `{}` is too long", duration);
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+/// A thread interrupting the guest if it is still running once the
+/// timeout elapses, until it is dropped.
+///
+/// The guest stops at its next function call or loop iteration, or as
+/// soon as it is blocked in a host call that can be interrupted, and
+/// [`check`](Self::check) then turns its error into a [`GuestTimeout`].
+pub struct Watchdog {
+    timeout: Duration,
+    timed_out: Arc<AtomicBool>,
+    _finished: Sender<()>,
+}
+
+impl Watchdog {
+    /// Starts watching the guest, which must finish within `timeout`, or
+    /// else is stopped by calling `interrupt`.
+    pub fn start(timeout: Duration, interrupt: impl FnOnce() + Send + 'static) -> Result<Self> {
+        let (finished, receiver) = mpsc::channel::<()>();
+        let timed_out = Arc::new(AtomicBool::new(false));
+        {
+            let timed_out = timed_out.clone();
+            thread::Builder::new()
+                .name("wasmer-timeout".to_string())
+                .spawn(move || {
+                    // The sender is only ever dropped, when the guest finishes
+                    if let Err(RecvTimeoutError::Timeout) = receiver.recv_timeout(timeout) {
+                        timed_out.store(true, Ordering::SeqCst);
+                        interrupt();
+                    }
+                })?;
+        }
+        Ok(Self {
+            timeout,
+            timed_out,
+            _finished: finished,
+        })
+    }
+
+    /// Whether the guest was interrupted because it timed out.
+    pub fn timed_out(&self) -> bool {
+        self.timed_out.load(Ordering::SeqCst)
+    }
+
+    /// Replaces the error of a guest that was interrupted by a
+    /// [`GuestTimeout`].
+    pub fn check<T>(&self, result: Result<T>) -> Result<T> {
+        match result {
+            Err(_) if self.timed_out() => Err(GuestTimeout(self.timeout).into()),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("5").unwrap(), Duration::from_secs(5));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(parse_duration(" 1h ").unwrap(), Duration::from_secs(3600));
+        assert!(parse_duration("0").is_err());
+        assert!(parse_duration("-1s").is_err());
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("100000000000000000000h").is_err());
+        assert!(parse_duration(&"9".repeat(400)).is_err());
+    }
+
+    #[test]
+    fn watchdog_interrupts_the_guest() {
+        let (interrupted, receiver) = mpsc::channel();
+        let watchdog = Watchdog::start(Duration::from_millis(10), move || {
+            interrupted.send(()).unwrap();
+        })
+        .unwrap();
+        receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(watchdog.timed_out());
+        let error = watchdog
+            .check::<()>(Err(anyhow::anyhow!("interrupted")))
+            .unwrap_err();
+        assert!(error.downcast_ref::<GuestTimeout>().is_some());
+    }
+
+    #[test]
+    fn watchdog_stops_with_the_guest() {
+        let watchdog =
+            Watchdog::start(Duration::from_secs(3600), || panic!("interrupted")).unwrap();
+        assert!(!watchdog.timed_out());
+        assert!(watchdog.check(Ok(())).is_ok());
+    }
+}
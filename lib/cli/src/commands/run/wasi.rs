@@ -1,11 +1,22 @@
-use crate::utils::{parse_envvar, parse_mapdir};
-use anyhow::Result;
+use super::journal::Journal;
+use crate::error::GuestExit;
+use crate::utils::{parse_envvar, parse_mapdir, parse_mount};
+use anyhow::{Context, Result};
 use std::collections::BTreeSet;
-use std::path::PathBuf;
-use wasmer::{AsStoreMut, FunctionEnv, Instance, Module, RuntimeError, Value};
+use std::env;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use wasmer::{
+    AsStoreMut, FunctionEnv, Imports, Initialization, Instance, Module, RuntimeError, Value,
+};
+use wasmer_vfs::host_fs::{DirFileSystem, File as HostFile};
+use wasmer_vfs::mem_fs::FileSystem as MemFileSystem;
+use wasmer_vfs::overlay_fs;
 use wasmer_wasi::{
-    get_wasi_versions, import_object_for_all_wasi_versions, is_wasix_module, WasiEnv, WasiError,
-    WasiState, WasiVersion,
+    get_wasi_versions, is_wasix_module, WasiEnv, WasiError, WasiHardening, WasiState,
+    WasiStateBuilder, WasiVersion,
 };
 
 use clap::Parser;
@@ -25,6 +36,29 @@ pub struct Wasi {
     )]
     mapped_dirs: Vec<(String, PathBuf)>,
 
+    /// Mount the contents of a tar (possibly gzipped) or zip archive as a
+    /// read-only directory of the Wasm module
+    #[clap(
+        long = "mount",
+        name = "ARCHIVE:GUEST_DIR",
+        parse(try_from_str = parse_mount),
+    )]
+    mounts: Vec<(PathBuf, String)>,
+
+    /// Mount the contents of an archive, like `--mount`, as a directory the
+    /// Wasm module can change, without changing the archive
+    #[clap(
+        long = "overlay",
+        name = "OVERLAY_ARCHIVE:GUEST_DIR",
+        parse(try_from_str = parse_mount),
+    )]
+    overlays: Vec<(PathBuf, String)>,
+
+    /// Keep the changes to the `--overlay` directories in this host
+    /// directory, instead of in memory
+    #[clap(long = "overlay-upper", name = "UPPER_DIR")]
+    overlay_upper: Option<PathBuf>,
+
     /// Pass custom environment variables
     #[clap(
         long = "env",
@@ -33,6 +67,43 @@ pub struct Wasi {
     )]
     env_vars: Vec<(String, String)>,
 
+    /// Pass all the environment variables of the host, under the ones of
+    /// `--env`
+    #[clap(long = "env-inherit", conflicts_with = "PATTERN")]
+    env_inherit: bool,
+
+    /// Pass the environment variables of the host with this name, or
+    /// starting with this prefix when it ends with `*` (as in `APP_*`).
+    /// Can be repeated
+    #[clap(long = "env-allow", name = "PATTERN")]
+    env_allow: Vec<String>,
+
+    /// Read the standard input of the module from this file
+    #[clap(long = "stdin", name = "STDIN_FILE", parse(from_os_str))]
+    stdin: Option<PathBuf>,
+
+    /// Write the standard output of the module to this file, such as
+    /// `/dev/null`, rather than to the one of wasmer
+    #[clap(long = "stdout", name = "STDOUT_FILE", parse(from_os_str))]
+    stdout: Option<PathBuf>,
+
+    /// Write the standard error of the module to this file, which can be
+    /// the one of `--stdout`, rather than to the one of wasmer
+    #[clap(long = "stderr", name = "STDERR_FILE", parse(from_os_str))]
+    stderr: Option<PathBuf>,
+
+    /// The number of bytes the module can write to its standard output
+    /// and error together (e.g. `10MiB`). Writes going over it fail as if
+    /// the disk were full
+    #[clap(long = "max-output", name = "MAX_OUTPUT")]
+    max_output: Option<bytesize::ByteSize>,
+
+    /// The number of bytes the module can write to the files of the
+    /// preopened and mapped directories (e.g. `100MiB`). Writes going over
+    /// it fail as if the disk were full
+    #[clap(long = "fs-quota", name = "FS_QUOTA")]
+    fs_quota: Option<bytesize::ByteSize>,
+
     /// Enable experimental IO devices
     #[cfg(feature = "experimental-io-devices")]
     #[cfg_attr(
@@ -41,6 +112,23 @@ pub struct Wasi {
     )]
     enable_experimental_io_devices: bool,
 
+    /// Provide the wasi-crypto imports, doing the cryptography of the
+    /// module with the primitives of the host
+    #[cfg(feature = "wasi-crypto")]
+    #[cfg_attr(feature = "wasi-crypto", clap(long = "enable-crypto"))]
+    enable_crypto: bool,
+
+    /// Allow the module to send HTTP requests to a host, with the
+    /// wasi_experimental_http imports. `*.` allows the subdomains of a
+    /// domain, and the methods can be restricted, as in
+    /// `GET,HEAD=*.example.com`
+    #[cfg(feature = "wasi-http")]
+    #[cfg_attr(
+        feature = "wasi-http",
+        clap(long = "allow-http", name = "[METHODS=]HOST")
+    )]
+    allow_http: Vec<wasmer_wasi_http::HttpRule>,
+
     /// Allow WASI modules to import multiple versions of WASI without a warning.
     #[clap(long = "allow-multiple-wasi-versions")]
     pub allow_multiple_wasi_versions: bool,
@@ -48,10 +136,103 @@ pub struct Wasi {
     /// Require WASI modules to only import 1 version of WASI.
     #[clap(long = "deny-multiple-wasi-versions")]
     pub deny_multiple_wasi_versions: bool,
+
+    /// Record every WASI call made by the module into a journal file
+    #[clap(
+        long = "record",
+        name = "RECORD_JOURNAL",
+        parse(from_os_str),
+        conflicts_with = "REPLAY_JOURNAL"
+    )]
+    record: Option<PathBuf>,
+
+    /// Answer WASI calls from a journal written by `--record` instead of
+    /// executing them on the host
+    #[clap(long = "replay", name = "REPLAY_JOURNAL", parse(from_os_str))]
+    replay: Option<PathBuf>,
+
+    /// Restrict the system calls of wasmer to the ones the capabilities
+    /// given to the module need before running it, as a defense against
+    /// bugs of the runtime (Linux only). Files can't be opened afterwards
+    /// without directories, nor sockets for non-WASIX modules; the filter
+    /// doesn't restrict which files can be opened with directories. Can't
+    /// be combined with `--allow-http`
+    #[clap(long = "harden", conflicts_with = "watch")]
+    harden: bool,
 }
 
 #[allow(dead_code)]
 impl Wasi {
+    /// Maps more host directories into the filesystem of the module.
+    pub fn add_mapped_dirs(&mut self, mapped_dirs: impl IntoIterator<Item = (String, PathBuf)>) {
+        self.mapped_dirs.extend(mapped_dirs);
+    }
+
+    /// Whether `--harden` restricts the system calls of the process.
+    pub fn is_hardened(&self) -> bool {
+        self.harden
+    }
+
+    /// Whether `--allow-http` lets the module send HTTP requests.
+    #[cfg(feature = "wasi-http")]
+    pub fn allows_http(&self) -> bool {
+        !self.allow_http.is_empty()
+    }
+
+    /// The host directories the module can access.
+    pub fn directories(&self) -> impl Iterator<Item = &PathBuf> {
+        self.pre_opened_directories
+            .iter()
+            .chain(self.mapped_dirs.iter().map(|(_, dir)| dir))
+    }
+
+    /// The environment variables of the module: the ones of `host` that
+    /// `--env-inherit` or `--env-allow` let through, and the ones of
+    /// `--env`, which take precedence.
+    fn environment(
+        &self,
+        host: impl Iterator<Item = (OsString, OsString)>,
+    ) -> Vec<(String, String)> {
+        let allowed = |name: &str| {
+            self.env_inherit
+                || self
+                    .env_allow
+                    .iter()
+                    .any(|pattern| match pattern.strip_suffix('*') {
+                        Some(prefix) => name.starts_with(prefix),
+                        None => name == pattern,
+                    })
+        };
+        let mut environment = host
+            // The variables that aren't Unicode are skipped
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+            .filter(|(name, _)| {
+                allowed(name) && !self.env_vars.iter().any(|(explicit, _)| explicit == name)
+            })
+            .collect::<Vec<_>>();
+        environment.sort();
+        environment.extend(self.env_vars.iter().cloned());
+        environment
+    }
+
+    /// Whether the module may import more than WASI, from the namespaces
+    /// of the enabled proposals.
+    pub fn allows_extra_imports(&self) -> bool {
+        #[cfg(feature = "wasi-crypto")]
+        {
+            if self.enable_crypto {
+                return true;
+            }
+        }
+        #[cfg(feature = "wasi-http")]
+        {
+            if !self.allow_http.is_empty() {
+                return true;
+            }
+        }
+        false
+    }
+
     /// Gets the WASI version (if any) for the provided module
     pub fn get_versions(module: &Module) -> Option<BTreeSet<WasiVersion>> {
         // Get the wasi version in strict mode, so no other imports are
@@ -59,6 +240,12 @@ impl Wasi {
         get_wasi_versions(module, true)
     }
 
+    /// Gets the WASI version (if any) for the provided module, allowing it
+    /// to also import from namespaces that are not WASI.
+    pub fn get_versions_lenient(module: &Module) -> Option<BTreeSet<WasiVersion>> {
+        get_wasi_versions(module, false)
+    }
+
     /// Checks if a given module has any WASI imports at all.
     pub fn has_wasi_imports(module: &Module) -> bool {
         // Get the wasi version in non-strict mode, so no other imports
@@ -73,15 +260,41 @@ impl Wasi {
         module: &Module,
         program_name: String,
         args: Vec<String>,
+        extra_imports: &Imports,
+        initialization: Initialization,
     ) -> Result<(FunctionEnv<WasiEnv>, Instance)> {
         let args = args.iter().cloned().map(|arg| arg.into_bytes());
 
         let mut wasi_state_builder = WasiState::new(program_name);
         wasi_state_builder
             .args(args)
-            .envs(self.env_vars.clone())
+            .envs(self.environment(env::vars_os()))
             .preopen_dirs(self.pre_opened_directories.clone())?
             .map_dirs(self.mapped_dirs.clone())?;
+        for (archive, guest_dir) in &self.mounts {
+            wasi_state_builder.mount(guest_dir, Box::new(read_archive(archive)?))?;
+        }
+        for (archive, guest_dir) in &self.overlays {
+            let lower = read_archive(archive)?;
+            let upper: Box<dyn wasmer_vfs::FileSystem> = match &self.overlay_upper {
+                Some(upper_dir) => {
+                    let dir = upper_dir.join(guest_dir.trim_start_matches('/'));
+                    std::fs::create_dir_all(&dir)
+                        .with_context(|| format!("failed to create `{}`", dir.display()))?;
+                    Box::new(DirFileSystem::new(dir))
+                }
+                None => Box::new(MemFileSystem::default()),
+            };
+            let fs = overlay_fs::FileSystem::new(Box::new(lower), upper);
+            wasi_state_builder.mount_writable(guest_dir, Box::new(fs))?;
+        }
+        self.redirect_stdio(&mut wasi_state_builder)?;
+        if let Some(max_output) = self.max_output {
+            wasi_state_builder.max_output(max_output.as_u64());
+        }
+        if let Some(fs_quota) = self.fs_quota {
+            wasi_state_builder.fs_quota(fs_quota.as_u64());
+        }
 
         #[cfg(feature = "experimental-io-devices")]
         {
@@ -96,23 +309,122 @@ impl Wasi {
             is_wasix_module(module),
             std::sync::atomic::Ordering::Release,
         );
-        let import_object = import_object_for_all_wasi_versions(store, &wasi_env.env);
-        let instance = Instance::new(store, module, &import_object)?;
+        let mut import_object = wasi_env.import_object_for_all_wasi_versions(store, module)?;
+        let journal = match (&self.record, &self.replay) {
+            (Some(path), _) => Some(Journal::record(store, path)?),
+            (_, Some(path)) => Some(Journal::replay(store, path)?),
+            (None, None) => None,
+        };
+        if let Some(journal) = &journal {
+            import_object = journal.wrap_imports(store, &import_object);
+        }
+        #[cfg(feature = "wasi-crypto")]
+        let crypto = if self.enable_crypto {
+            let crypto = wasmer_wasi_crypto::WasiCrypto::new(store);
+            import_object.extend(&crypto.import_object(store));
+            Some(crypto)
+        } else {
+            None
+        };
+        #[cfg(feature = "wasi-http")]
+        let http = if !self.allow_http.is_empty() {
+            let mut policy = wasmer_wasi_http::HttpPolicy::default();
+            for rule in &self.allow_http {
+                policy.allow(rule.clone());
+            }
+            let http = wasmer_wasi_http::WasiHttp::new(store, policy);
+            import_object.extend(&http.import_object(store));
+            Some(http)
+        } else {
+            None
+        };
+        import_object.extend(extra_imports);
+        if extra_imports.allows_missing() {
+            import_object.allow_missing();
+        }
+        if self.harden {
+            WasiHardening::for_state(&wasi_env.env.as_ref(store).state)
+                .install()
+                .context("failed to harden the process")?;
+        }
+        // The start function and `_initialize` may call WASI, so they only
+        // run once its memory is set
+        let instance = Instance::new_with_initialization(
+            store,
+            module,
+            &import_object,
+            Initialization::Deferred,
+        )?;
         let memory = instance.exports.get_memory("memory")?;
         wasi_env.data_mut(store).set_memory(memory.clone());
+        if let Some(journal) = &journal {
+            journal.set_memory(store, memory.clone());
+        }
+        #[cfg(feature = "wasi-crypto")]
+        {
+            if let Some(crypto) = &crypto {
+                crypto.initialize(store, &instance)?;
+            }
+        }
+        #[cfg(feature = "wasi-http")]
+        {
+            if let Some(http) = &http {
+                http.initialize(store, &instance)?;
+            }
+        }
+        instance.initialize(store, initialization)?;
         Ok((wasi_env.env, instance))
     }
 
-    /// Helper function for handling the result of a Wasi _start function.
-    pub fn handle_result(&self, result: Result<Box<[Value]>, RuntimeError>) -> Result<()> {
+    /// Redirects the standard streams of the module to the files of
+    /// `--stdin`, `--stdout` and `--stderr`.
+    fn redirect_stdio(&self, builder: &mut WasiStateBuilder) -> Result<()> {
+        let open = |path: &PathBuf, write: bool| -> Result<HostFile> {
+            let file = if write {
+                File::create(host_path(path))
+            } else {
+                File::open(host_path(path))
+            }
+            .with_context(|| format!("failed to open `{}`", path.display()))?;
+            Ok(HostFile::new(file, path.clone(), !write, write, false))
+        };
+        if let Some(path) = &self.stdin {
+            builder.stdin(Box::new(open(path, false)?));
+        }
+        let stdout = self
+            .stdout
+            .as_ref()
+            .map(|path| open(path, true))
+            .transpose()?;
+        if let Some(path) = &self.stderr {
+            let stderr = match &stdout {
+                // Both streams share the position in the file
+                Some(stdout) if self.stdout.as_ref() == Some(path) => {
+                    HostFile::new(stdout.inner.try_clone()?, path.clone(), false, true, false)
+                }
+                _ => open(path, true)?,
+            };
+            builder.stderr(Box::new(stderr));
+        }
+        if let Some(stdout) = stdout {
+            builder.stdout(Box::new(stdout));
+        }
+        Ok(())
+    }
+
+    /// Helper function for handling the result of a Wasi function.
+    ///
+    /// A guest exiting with a nonzero code gives a [`GuestExit`] error.
+    pub fn handle_result(
+        &self,
+        result: Result<Box<[Value]>, RuntimeError>,
+    ) -> Result<Box<[Value]>> {
         match result {
-            Ok(_) => Ok(()),
+            Ok(values) => Ok(values),
             Err(err) => {
                 let err: anyhow::Error = match err.downcast::<WasiError>() {
-                    Ok(WasiError::Exit(exit_code)) => {
-                        // We should exit with the provided exit code
-                        std::process::exit(exit_code as _);
-                    }
+                    Ok(WasiError::Exit(0)) => return Ok(Box::new([])),
+                    Ok(WasiError::Exit(exit_code)) => GuestExit(exit_code as i32).into(),
                     Ok(err) => err.into(),
                     Err(err) => err.into(),
                 };
@@ -122,15 +434,93 @@ impl Wasi {
     }
 
     pub fn for_binfmt_interpreter() -> Result<Self> {
-        use std::env;
         let dir = env::var_os("WASMER_BINFMT_MISC_PREOPEN")
             .map(Into::into)
             .unwrap_or_else(|| PathBuf::from("."));
         Ok(Self {
             deny_multiple_wasi_versions: true,
-            env_vars: env::vars().collect(),
+            env_inherit: true,
             pre_opened_directories: vec![dir],
             ..Self::default()
         })
     }
 }
+
+/// The path of the host file for `path`, where `/dev/null` is the null
+/// device on every platform.
+fn host_path(path: &Path) -> &Path {
+    if cfg!(windows) && path == Path::new("/dev/null") {
+        Path::new("NUL")
+    } else {
+        path
+    }
+}
+
+/// Loads the contents of the archive at `archive` in memory.
+fn read_archive(archive: &Path) -> Result<MemFileSystem> {
+    let file =
+        File::open(archive).with_context(|| format!("failed to open `{}`", archive.display()))?;
+    MemFileSystem::from_archive(BufReader::new(file))
+        .map_err(|e| anyhow!("failed to read the archive `{}`: {}", archive.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn environment() {
+        let host = || {
+            [
+                ("APP_PORT", "80"),
+                ("APP_HOST", "h"),
+                ("HOME", "/root"),
+                ("PATH", "/bin"),
+            ]
+            .iter()
+            .map(|(name, value)| (OsString::from(name), OsString::from(value)))
+        };
+        let vars = |vars: &[(&str, &str)]| {
+            vars.iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let wasi = Wasi {
+            env_vars: vars(&[("APP_PORT", "8080")]),
+            env_allow: vec!["APP_*".to_string(), "HOME".to_string()],
+            ..Wasi::default()
+        };
+        assert_eq!(
+            wasi.environment(host()),
+            vars(&[("APP_HOST", "h"), ("HOME", "/root"), ("APP_PORT", "8080")])
+        );
+
+        let wasi = Wasi {
+            env_inherit: true,
+            ..Wasi::default()
+        };
+        assert_eq!(wasi.environment(host()).len(), 4);
+        assert!(Wasi::default().environment(host()).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "wasi-http")]
+    fn hardening_rejects_http() {
+        use crate::commands::Run;
+        use clap::Parser;
+
+        let run = Run::try_parse_from(&["run", "mod.wasm", "--harden"]).unwrap();
+        assert!(run.check_hardening().is_ok());
+        let run = Run::try_parse_from(&["run", "mod.wasm", "--allow-http", "example.com"]).unwrap();
+        assert!(run.check_hardening().is_ok());
+
+        let run =
+            Run::try_parse_from(&["run", "mod.wasm", "--harden", "--allow-http", "example.com"])
+                .unwrap();
+        let error = run.check_hardening().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`--allow-http` can't be combined with `--harden`"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! `wasmer run --watch`, running the module again whenever it changes.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How often the watched paths are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Polls files and directories, the latter recursively, until they change.
+pub struct Watcher {
+    paths: Vec<PathBuf>,
+    stamps: Vec<Stamp>,
+}
+
+/// The state of a watched path: the latest modification time of the files
+/// under it, and their number, for removals to count as changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    modified: Option<SystemTime>,
+    files: usize,
+}
+
+impl Watcher {
+    /// Starts watching `paths`, in their current state.
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let stamps = stamps(&paths);
+        Self { paths, stamps }
+    }
+
+    /// Blocks until one of the paths changes, and stays unchanged for a
+    /// poll interval, so that the files being written are complete.
+    pub fn wait(&mut self) {
+        let mut changed = false;
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let stamps = stamps(&self.paths);
+            if stamps != self.stamps {
+                self.stamps = stamps;
+                changed = true;
+            } else if changed {
+                return;
+            }
+        }
+    }
+}
+
+fn stamps(paths: &[PathBuf]) -> Vec<Stamp> {
+    paths
+        .iter()
+        .map(|path| {
+            let mut stamp = Stamp {
+                modified: None,
+                files: 0,
+            };
+            add_to_stamp(path, true, &mut stamp);
+            stamp
+        })
+        .collect()
+}
+
+/// Adds `path` to `stamp`, with the files under it if it is a directory.
+/// Only the watched paths themselves are followed when they are symbolic
+/// links, so that links can't make the walk loop.
+fn add_to_stamp(path: &Path, follow_links: bool, stamp: &mut Stamp) {
+    let metadata = if follow_links {
+        fs::metadata(path)
+    } else {
+        fs::symlink_metadata(path)
+    };
+    let metadata = match metadata {
+        Ok(metadata) => metadata,
+        Err(_) => return,
+    };
+    stamp.files += 1;
+    stamp.modified = stamp.modified.max(metadata.modified().ok());
+    if metadata.is_dir() {
+        if let Ok(entries) = fs::read_dir(path) {
+            for entry in entries.flatten() {
+                add_to_stamp(&entry.path(), false, stamp);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directory_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = vec![dir.path().to_path_buf()];
+        let initial = stamps(&paths);
+        assert_eq!(initial[0].files, 1);
+
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub").join("file"), b"data").unwrap();
+        let added = stamps(&paths);
+        assert_eq!(added[0].files, 3);
+        assert_ne!(added, initial);
+
+        fs::remove_file(dir.path().join("sub").join("file")).unwrap();
+        assert_eq!(stamps(&paths)[0].files, 2);
+
+        // A missing path is watched until it appears
+        let missing = vec![dir.path().join("missing.wasm")];
+        assert_eq!(stamps(&missing)[0].files, 0);
+    }
+}
@@ -1,12 +1,58 @@
 //! When wasmer self-update is executed, this is what gets executed
+//!
+//! The latest release of the channel is looked up on GitHub, and its
+//! archive for the platform is downloaded over HTTPS and checked against
+//! its SHA-256 checksum. The checksum comes from GitHub too, so it only
+//! catches corrupted downloads: the archive must also be signed by one of
+//! the keys of `--require-signature`, which GitHub can't forge, unless
+//! `--insecure` is given. The `wasmer` executable of the archive is then
+//! renamed over the current one, so that a failed update leaves the
+//! current executable untouched.
 use anyhow::{Context, Result};
 use clap::Parser;
-#[cfg(not(target_os = "windows"))]
-use std::process::{Command, Stdio};
+use std::path::PathBuf;
+#[cfg(feature = "http")]
+use {
+    crate::utils::{download, extract_tar_gz, verify_sha256},
+    crate::warning,
+    crate::VERSION,
+    anyhow::{anyhow, bail},
+    serde::Deserialize,
+    std::fs,
+    std::path::Path,
+};
+
+/// The GitHub API listing the releases of wasmer, newest first.
+#[cfg(feature = "http")]
+const RELEASES_URL: &str = "https://api.github.com/repos/wasmerio/wasmer/releases";
 
 /// The options for the `wasmer self-update` subcommand
 #[derive(Debug, Parser)]
-pub struct SelfUpdate {}
+pub struct SelfUpdate {
+    /// Update to the latest prerelease, rather than to the latest stable
+    /// release
+    #[clap(long)]
+    prerelease: bool,
+
+    /// Only check whether a newer release is available
+    #[clap(long)]
+    check: bool,
+
+    /// Install the latest release even if it isn't newer
+    #[clap(long, conflicts_with = "check")]
+    force: bool,
+
+    /// Only install a release whose archive is signed, in the
+    /// `<archive>.sig` asset, by the private key of this Ed25519 public
+    /// key, read from a PEM file. Can be repeated to trust several keys
+    #[clap(long = "require-signature", name = "PUBLIC_KEY", parse(from_os_str))]
+    require_signature: Vec<PathBuf>,
+
+    /// Install a release without checking its signature, trusting the
+    /// checksum GitHub publishes for it
+    #[clap(long, conflicts_with = "PUBLIC_KEY")]
+    insecure: bool,
+}
 
 impl SelfUpdate {
     /// Runs logic for the `self-update` subcommand
@@ -14,26 +60,295 @@ impl SelfUpdate {
         self.inner_execute().context("failed to self-update wasmer")
     }
 
-    #[cfg(not(target_os = "windows"))]
+    #[cfg(not(feature = "http"))]
     fn inner_execute(&self) -> Result<()> {
-        println!("Fetching latest installer");
-        let cmd = Command::new("curl")
-            .arg("https://get.wasmer.io")
-            .arg("-sSfL")
-            .stdout(Stdio::piped())
-            .spawn()?;
-
-        let mut process = Command::new("sh")
-            .stdin(cmd.stdout.unwrap())
-            .stdout(Stdio::inherit())
-            .spawn()?;
-
-        process.wait().unwrap();
-        Ok(())
+        anyhow::bail!("wasmer was built without the `http` feature. Use install instructions on the Wasmer homepage: https://wasmer.io");
     }
 
-    #[cfg(target_os = "windows")]
+    #[cfg(feature = "http")]
     fn inner_execute(&self) -> Result<()> {
-        bail!("Self update is not supported on Windows. Use install instructions on the Wasmer homepage: https://wasmer.io");
+        if self.require_signature.is_empty() && !self.insecure && !self.check {
+            bail!(
+                "the release must be signed by a trusted key, pass its public key with \
+                 `--require-signature`, or `--insecure` to skip the signature check"
+            );
+        }
+        let asset = release_asset_name()
+            .ok_or_else(|| anyhow!("there are no releases of wasmer for this platform"))?;
+        let release = Release::latest(self.prerelease)?;
+        let version = release.tag_name.trim_start_matches('v');
+        if !self.force && !is_newer(version, VERSION) {
+            eprintln!("wasmer {} is up to date.", VERSION);
+            return Ok(());
+        }
+        if self.check {
+            eprintln!("wasmer {} is available (installed: {}).", version, VERSION);
+            return Ok(());
+        }
+
+        let archive_url = &release.asset(asset)?.browser_download_url;
+        eprintln!("Downloading wasmer {} from `{}`...", version, archive_url);
+        let archive = download(archive_url)?;
+        verify_checksum(&archive, &release.checksum(asset)?)?;
+        if self.insecure {
+            warning!(
+                "the signature of the release wasn't checked, only a checksum published by \
+                 GitHub as well"
+            );
+        } else {
+            let signature_url = &release
+                .asset(&format!("{}.sig", asset))?
+                .browser_download_url;
+            self.verify_signature(&archive, &download(signature_url)?)?;
+        }
+
+        let current = std::env::current_exe()?.canonicalize()?;
+        install(&archive, &current)?;
+        eprintln!("✔ Updated wasmer from {} to {}.", VERSION, version);
+        Ok(())
+    }
+
+    /// Checks that `signature` is the signature of `archive` by one of the
+    /// keys of `--require-signature`.
+    #[cfg(all(feature = "http", feature = "signing"))]
+    fn verify_signature(&self, archive: &[u8], signature: &[u8]) -> Result<()> {
+        use wasmer::VerifyingKey;
+
+        for path in &self.require_signature {
+            let pem = fs::read_to_string(path)
+                .with_context(|| format!("failed to read the public key `{}`", path.display()))?;
+            let key = VerifyingKey::from_pem(&pem)
+                .with_context(|| format!("invalid public key `{}`", path.display()))?;
+            if key.verify(archive, signature).is_ok() {
+                return Ok(());
+            }
+        }
+        bail!("the release isn't signed by a trusted key")
+    }
+
+    #[cfg(all(feature = "http", not(feature = "signing")))]
+    fn verify_signature(&self, _archive: &[u8], _signature: &[u8]) -> Result<()> {
+        bail!("wasmer was built without the `signing` feature")
+    }
+}
+
+/// A release of wasmer, as listed by the GitHub API.
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    prerelease: bool,
+    assets: Vec<Asset>,
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+    /// The digest GitHub computed for the asset, as `sha256:<hex>`.
+    #[serde(default)]
+    digest: Option<String>,
+}
+
+#[cfg(feature = "http")]
+impl Release {
+    /// Looks up the latest release, or prerelease if `prerelease` is set.
+    fn latest(prerelease: bool) -> Result<Self> {
+        let releases: Vec<Self> = serde_json::from_slice(&download(RELEASES_URL)?)
+            .context("invalid reply of the GitHub API")?;
+        releases
+            .into_iter()
+            .find(|release| prerelease || !release.prerelease)
+            .ok_or_else(|| anyhow!("no release of wasmer was found"))
+    }
+
+    fn asset(&self, name: &str) -> Result<&Asset> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .ok_or_else(|| anyhow!("the release {} has no `{}`", self.tag_name, name))
+    }
+
+    /// The SHA-256 checksum of the asset `name`, from the `<name>.sha256`
+    /// asset if the release has one, or else from the digest GitHub
+    /// reports for it.
+    fn checksum(&self, name: &str) -> Result<String> {
+        if let Ok(checksum) = self.asset(&format!("{}.sha256", name)) {
+            let checksum = download(&checksum.browser_download_url)?;
+            return String::from_utf8(checksum)
+                .map_err(|_| anyhow!("the checksum of the release is malformed"));
+        }
+        self.asset(name)?
+            .digest
+            .as_deref()
+            .and_then(|digest| digest.strip_prefix("sha256:"))
+            .map(str::to_string)
+            .ok_or_else(|| {
+                anyhow!(
+                    "the release {} publishes no checksum of `{}`",
+                    self.tag_name,
+                    name
+                )
+            })
+    }
+}
+
+/// The name of the release archive for the platform wasmer runs on.
+#[cfg(feature = "http")]
+fn release_asset_name() -> Option<&'static str> {
+    if cfg!(all(
+        target_os = "linux",
+        target_arch = "x86_64",
+        target_env = "musl"
+    )) {
+        Some("wasmer-linux-musl-amd64.tar.gz")
+    } else if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+        Some("wasmer-linux-amd64.tar.gz")
+    } else if cfg!(all(target_os = "linux", target_arch = "aarch64")) {
+        Some("wasmer-linux-aarch64.tar.gz")
+    } else if cfg!(all(target_os = "macos", target_arch = "x86_64")) {
+        Some("wasmer-darwin-amd64.tar.gz")
+    } else if cfg!(all(target_os = "macos", target_arch = "aarch64")) {
+        Some("wasmer-darwin-arm64.tar.gz")
+    } else if cfg!(all(target_os = "windows", target_arch = "x86_64")) {
+        Some("wasmer-windows-amd64.tar.gz")
+    } else {
+        None
+    }
+}
+
+/// Whether `version` is newer than `current`. A prerelease is older than
+/// the release it precedes.
+#[cfg(feature = "http")]
+fn is_newer(version: &str, current: &str) -> bool {
+    fn parse(version: &str) -> (Vec<u64>, bool, Vec<(u64, &str)>) {
+        let (release, prerelease) = match version.split_once('-') {
+            Some((release, prerelease)) => (release, Some(prerelease)),
+            None => (version, None),
+        };
+        let numbers = release.split('.').map(|n| n.parse().unwrap_or(0)).collect();
+        // Numeric identifiers (`beta.2`) compare as numbers
+        let identifiers = prerelease
+            .map(|prerelease| {
+                prerelease
+                    .split('.')
+                    .map(|id| id.parse().map_or((0, id), |n| (n, "")))
+                    .collect()
+            })
+            .unwrap_or_default();
+        (numbers, prerelease.is_none(), identifiers)
+    }
+    parse(version) > parse(current)
+}
+
+/// Checks `archive` against `checksum`, its hexadecimal SHA-256 digest,
+/// optionally followed by the name of the archive as `sha256sum` prints it.
+#[cfg(feature = "http")]
+fn verify_checksum(archive: &[u8], checksum: &str) -> Result<()> {
+    verify_sha256(archive, checksum).context("the downloaded release is corrupted")
+}
+
+/// Replaces the executable at `current` with the one of `archive`.
+#[cfg(feature = "http")]
+fn install(archive: &[u8], current: &Path) -> Result<()> {
+    // Extracted next to the current executable, so that the new one can
+    // be renamed over it
+    let dir = current.parent().unwrap();
+    let extracted = tempfile::tempdir_in(dir)
+        .with_context(|| format!("failed to write to `{}`", dir.display()))?;
+    extract_tar_gz(archive, extracted.path()).context("invalid release archive")?;
+    let executable = extracted.path().join("bin").join(if cfg!(windows) {
+        "wasmer.exe"
+    } else {
+        "wasmer"
+    });
+    if !executable.exists() {
+        bail!("the release has no `{}` executable", executable.display());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&executable, fs::Permissions::from_mode(0o755))?;
+    }
+    // A running executable can't be replaced on Windows, but it can be
+    // moved out of the way. The two renames aren't atomic, so the current
+    // executable is moved back if the new one can't take its place.
+    #[cfg(windows)]
+    {
+        let previous = current.with_extension("old.exe");
+        let _ = fs::remove_file(&previous);
+        fs::rename(current, &previous)?;
+        if let Err(error) = fs::rename(&executable, current) {
+            fs::rename(&previous, current).with_context(|| {
+                format!(
+                    "failed to restore `{}` from `{}`",
+                    current.display(),
+                    previous.display()
+                )
+            })?;
+            return Err(error)
+                .with_context(|| format!("failed to replace `{}`", current.display()));
+        }
+    }
+    #[cfg(not(windows))]
+    fs::rename(&executable, current)
+        .with_context(|| format!("failed to replace `{}`", current.display()))?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "http"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_versions() {
+        assert!(is_newer("3.0.0", "3.0.0-beta.2"));
+        assert!(is_newer("3.0.0-beta.10", "3.0.0-beta.2"));
+        assert!(is_newer("3.0.0-rc.1", "3.0.0-beta.2"));
+        assert!(is_newer("3.1.0", "3.0.10"));
+        assert!(!is_newer("3.0.0", "3.0.0"));
+        assert!(!is_newer("2.3.0", "3.0.0-beta.2"));
+    }
+
+    #[test]
+    fn signatures_are_required_by_default() {
+        let update = SelfUpdate::try_parse_from(["self-update"]).unwrap();
+        let error = update.inner_execute().unwrap_err();
+        assert!(error.to_string().contains("--insecure"), "{}", error);
+        assert!(SelfUpdate::try_parse_from(["self-update", "--insecure"]).is_ok());
+        assert!(SelfUpdate::try_parse_from([
+            "self-update",
+            "--insecure",
+            "--require-signature",
+            "key.pem"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn checksums_fall_back_to_the_github_digest() {
+        let release: Release = serde_json::from_str(
+            r#"{
+                "tag_name": "v3.0.0",
+                "prerelease": false,
+                "assets": [
+                    {
+                        "name": "wasmer-linux-amd64.tar.gz",
+                        "browser_download_url": "https://example.com/a",
+                        "digest": "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+                    },
+                    {
+                        "name": "wasmer-darwin-amd64.tar.gz",
+                        "browser_download_url": "https://example.com/b"
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let checksum = release.checksum("wasmer-linux-amd64.tar.gz").unwrap();
+        assert!(verify_checksum(b"hello", &checksum).is_ok());
+        assert!(release.checksum("wasmer-darwin-amd64.tar.gz").is_err());
+        assert!(release.checksum("wasmer-windows-amd64.tar.gz").is_err());
     }
 }
@@ -0,0 +1,609 @@
+//! The `wasmer serve` subcommand, serving HTTP with a WASI program.
+//!
+//! The program is a [WCGI] handler: every request runs a fresh instance of
+//! it, as a CGI script, allocated in a pool with room for an instance per
+//! worker. The request is described by the environment
+//! variables of CGI (`REQUEST_METHOD`, `PATH_INFO`, `QUERY_STRING`,
+//! `HTTP_*`, ...) and its body is the standard input. The program writes
+//! the headers of the response to its standard output, a `Status` header
+//! setting the status code, then an empty line and the body.
+//!
+//! A client has [`READ_TIMEOUT`] to send its whole request, and the program
+//! is interrupted if it doesn't respond within `--timeout`.
+//!
+//! [WCGI]: https://wasmer.io/posts/announcing-wcgi
+use super::run::{parse_duration, Watchdog};
+use crate::error::GuestTimeout;
+use crate::store::StoreOptions;
+use crate::utils::{parse_envvar, parse_mapdir};
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{sync_channel, Receiver, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use wasmer::{Engine, Instance, InstancePool, Module, PoolingConfig, Store};
+use wasmer_wasi::{is_wasi_module, Pipe, WasiError, WasiState};
+
+/// The largest request line and headers accepted.
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+/// How long a client can take to send its whole request.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer serve` subcommand
+pub struct Serve {
+    /// The WASI program handling the requests, as a CGI script
+    #[clap(name = "FILE", parse(from_os_str))]
+    path: PathBuf,
+
+    /// The address to listen on
+    #[clap(long = "listen", default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+
+    /// How many requests are handled at once, by default as many as
+    /// there are CPUs
+    #[clap(long = "workers")]
+    workers: Option<usize>,
+
+    /// How many requests can wait for a worker, the following ones are
+    /// answered with `503 Service Unavailable`
+    #[clap(long = "queue", default_value = "64")]
+    queue: usize,
+
+    /// The largest request body accepted, in bytes
+    #[clap(long = "max-body-size", default_value = "10485760")]
+    max_body_size: usize,
+
+    /// The most bytes the program can write for a response, headers
+    /// included, and to its standard error
+    #[clap(long = "max-response-size", default_value = "10485760")]
+    max_response_size: u64,
+
+    /// How long the program can take to handle a request (e.g. `500ms`,
+    /// `5s` or `2m`) before it is interrupted and `504 Gateway Timeout` is
+    /// sent
+    #[clap(
+        long = "timeout",
+        name = "DURATION",
+        default_value = "30s",
+        parse(try_from_str = parse_duration)
+    )]
+    timeout: Duration,
+
+    /// WASI pre-opened directory
+    #[clap(long = "dir", name = "DIR")]
+    pre_opened_directories: Vec<PathBuf>,
+
+    /// Map a host directory to a different location for the Wasm module
+    #[clap(
+        long = "mapdir",
+        name = "GUEST_DIR:HOST_DIR",
+        parse(try_from_str = parse_mapdir),
+    )]
+    mapped_dirs: Vec<(String, PathBuf)>,
+
+    /// Pass custom environment variables
+    #[clap(
+        long = "env",
+        name = "KEY=VALUE",
+        parse(try_from_str = parse_envvar),
+    )]
+    env_vars: Vec<(String, String)>,
+
+    #[clap(flatten)]
+    store: StoreOptions,
+}
+
+impl Serve {
+    /// Runs logic for the `serve` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .context(format!("failed to serve `{}`", self.path.display()))
+    }
+
+    fn inner_execute(&self) -> Result<()> {
+        let (store, _compiler_type) = self.store.get_store()?;
+        let module = Module::from_file(&store, &self.path)?;
+        if !is_wasi_module(&module) {
+            bail!("only WASI programs can handle requests");
+        }
+        let listener = TcpListener::bind(self.listen)
+            .with_context(|| format!("failed to listen on {}", self.listen))?;
+        let address = listener.local_addr()?;
+        let workers = self
+            .workers
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+            .max(1);
+
+        let handler = Arc::new(Handler {
+            engine: store
+                .engine()
+                .clone()
+                .with_instance_pool(Some(instance_pool(&module, workers)?)),
+            module,
+            program_name: self
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            address,
+            pre_opened_directories: self.pre_opened_directories.clone(),
+            mapped_dirs: self.mapped_dirs.clone(),
+            env_vars: self.env_vars.clone(),
+            max_body_size: self.max_body_size,
+            max_response_size: self.max_response_size,
+            timeout: self.timeout,
+        });
+        let (sender, receiver) = sync_channel::<TcpStream>(self.queue);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..workers {
+            let handler = handler.clone();
+            let receiver = receiver.clone();
+            thread::spawn(move || worker(&handler, &receiver));
+        }
+
+        eprintln!(
+            "Serving `{}` on http://{} with {} workers",
+            self.path.display(),
+            address,
+            workers
+        );
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warning!("failed to accept a connection: {}", e);
+                    continue;
+                }
+            };
+            match sender.try_send(stream) {
+                Ok(()) => {}
+                Err(TrySendError::Full(mut stream)) => {
+                    let _ = Response::error(503).write_to(&mut stream);
+                }
+                Err(TrySendError::Disconnected(_)) => bail!("all the workers stopped"),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A pool with the slots `workers` instances of `module` need at once.
+fn instance_pool(module: &Module, workers: usize) -> Result<InstancePool> {
+    let info = module.info();
+    let memories = info.memories.len() - info.num_imported_memories;
+    let tables = info.tables.len() - info.num_imported_tables;
+    InstancePool::new(PoolingConfig {
+        max_instances: workers,
+        max_memories: workers * memories,
+        max_tables: workers * tables,
+        ..PoolingConfig::default()
+    })
+    .map_err(|e| anyhow!("failed to reserve the instance pool: {}", e))
+}
+
+/// Handles the connections of `receiver`, one at a time.
+fn worker(handler: &Handler, receiver: &Mutex<Receiver<TcpStream>>) {
+    loop {
+        // The lock is released as soon as a connection is received
+        let stream = receiver.lock().unwrap().recv();
+        match stream {
+            Ok(stream) => handler.handle(stream),
+            Err(_) => return,
+        }
+    }
+}
+
+/// Runs the program for the requests, shared by the workers.
+struct Handler {
+    engine: Engine,
+    module: Module,
+    program_name: String,
+    address: SocketAddr,
+    pre_opened_directories: Vec<PathBuf>,
+    mapped_dirs: Vec<(String, PathBuf)>,
+    env_vars: Vec<(String, String)>,
+    max_body_size: usize,
+    max_response_size: u64,
+    timeout: Duration,
+}
+
+impl Handler {
+    fn handle(&self, mut stream: TcpStream) {
+        let peer = stream.peer_addr().ok();
+        let reader = DeadlineReader {
+            stream: &stream,
+            deadline: Instant::now() + READ_TIMEOUT,
+        };
+        let response = match read_request(reader, self.max_body_size) {
+            Ok(request) => self.respond(&request, peer).unwrap_or_else(|e| {
+                warning!("`{} {}` failed: {:#}", request.method, request.target, e);
+                if e.downcast_ref::<GuestTimeout>().is_some() {
+                    Response::error(504)
+                } else {
+                    Response::error(500)
+                }
+            }),
+            Err(status) => Response::error(status),
+        };
+        let _ = response.write_to(&mut stream);
+    }
+
+    /// Runs a fresh instance of the program for `request`, since CGI
+    /// scripts handle a single request.
+    fn respond(&self, request: &Request, peer: Option<SocketAddr>) -> Result<Response> {
+        let mut store = Store::new(self.engine.clone());
+        let stdout = Pipe::new();
+        let wasi_env = WasiState::new(&self.program_name)
+            .envs(self.env_vars.clone())
+            .envs(request.cgi_env(self.address, peer))
+            .preopen_dirs(self.pre_opened_directories.clone())?
+            .map_dirs(self.mapped_dirs.clone())?
+            .stdin(Box::new(Pipe::with_data(request.body.clone())))
+            .stdout(Box::new(stdout.clone()))
+            .max_output(self.max_response_size)
+            .finalize(&mut store)?;
+        let imports = wasi_env.import_object(&mut store, &self.module)?;
+        let instance = Instance::new(&mut store, &self.module, &imports)?;
+        let memory = instance.exports.get_memory("memory")?;
+        wasi_env.data_mut(&mut store).set_memory(memory.clone());
+
+        let start = instance.exports.get_function("_start")?;
+        let interrupt = instance.interrupt_handle();
+        let wasi_interrupt = wasi_env.env.as_ref(&store).interrupt_handle();
+        let watchdog = Watchdog::start(self.timeout, move || {
+            interrupt.interrupt();
+            wasi_interrupt.interrupt();
+        })?;
+        let result = start.call(&mut store, &[]);
+        watchdog.check(match result {
+            Ok(_) => Ok(()),
+            Err(err) => match err.downcast::<WasiError>() {
+                Ok(WasiError::Exit(0)) => Ok(()),
+                Ok(WasiError::Exit(code)) => Err(anyhow!("the handler exited with code {}", code)),
+                Ok(err) => Err(err.into()),
+                Err(err) => Err(err.into()),
+            },
+        })?;
+        parse_cgi_response(&stdout.take())
+    }
+}
+
+/// Reads a stream until a deadline, after which reading fails with
+/// [`io::ErrorKind::TimedOut`].
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self
+            .deadline
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| !remaining.is_zero())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "the request took too long"))?;
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+/// An HTTP request, as read from a client.
+#[derive(Debug)]
+struct Request {
+    method: String,
+    target: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// The CGI environment variables describing the request.
+    fn cgi_env(&self, address: SocketAddr, peer: Option<SocketAddr>) -> Vec<(String, String)> {
+        let (path, query) = self
+            .target
+            .split_once('?')
+            .unwrap_or((self.target.as_str(), ""));
+        let mut env = vec![
+            ("GATEWAY_INTERFACE".to_string(), "CGI/1.1".to_string()),
+            ("SERVER_PROTOCOL".to_string(), "HTTP/1.1".to_string()),
+            (
+                "SERVER_SOFTWARE".to_string(),
+                format!("wasmer/{}", env!("CARGO_PKG_VERSION")),
+            ),
+            ("SERVER_NAME".to_string(), address.ip().to_string()),
+            ("SERVER_PORT".to_string(), address.port().to_string()),
+            ("REQUEST_METHOD".to_string(), self.method.clone()),
+            ("REQUEST_URI".to_string(), self.target.clone()),
+            ("SCRIPT_NAME".to_string(), String::new()),
+            ("PATH_INFO".to_string(), path.to_string()),
+            ("QUERY_STRING".to_string(), query.to_string()),
+        ];
+        if let Some(peer) = peer {
+            env.push(("REMOTE_ADDR".to_string(), peer.ip().to_string()));
+            env.push(("REMOTE_PORT".to_string(), peer.port().to_string()));
+        }
+        if !self.body.is_empty() {
+            env.push(("CONTENT_LENGTH".to_string(), self.body.len().to_string()));
+        }
+        for (name, value) in &self.headers {
+            let name = name.to_ascii_uppercase().replace('-', "_");
+            match name.as_str() {
+                "CONTENT_TYPE" => env.push((name, value.clone())),
+                // Already given, or about the connection to the server
+                "CONTENT_LENGTH" | "CONNECTION" | "PROXY" => {}
+                _ => env.push((format!("HTTP_{}", name), value.clone())),
+            }
+        }
+        env
+    }
+}
+
+/// Reads a request from `stream`, or the status of the error response
+/// to send when it's invalid.
+fn read_request(stream: impl Read, max_body_size: usize) -> Result<Request, u16> {
+    let mut reader = BufReader::new(stream);
+    let mut lines = Vec::new();
+    let mut size = 0;
+    loop {
+        let mut line = Vec::new();
+        let read = reader
+            .by_ref()
+            .take((MAX_HEAD_SIZE - size) as u64)
+            .read_until(b'\n', &mut line)
+            .map_err(read_error)?;
+        size += read;
+        if !line.ends_with(b"\n") {
+            return Err(if size >= MAX_HEAD_SIZE { 431 } else { 400 });
+        }
+        let line = String::from_utf8(line).map_err(|_| 400u16)?;
+        let line = line.trim_end_matches(&['\r', '\n'][..]);
+        if line.is_empty() {
+            // Blank lines before the request line are ignored
+            if lines.is_empty() {
+                continue;
+            }
+            break;
+        }
+        lines.push(line.to_string());
+    }
+
+    let request_line = lines.remove(0);
+    let mut parts = request_line.split(' ');
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version), None) if !method.is_empty() => {
+            (method, target, version)
+        }
+        _ => return Err(400),
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(505);
+    }
+    let headers = lines
+        .iter()
+        .map(|line| {
+            line.split_once(':')
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .ok_or(400u16)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    };
+    if header("Transfer-Encoding").is_some() {
+        return Err(501);
+    }
+    let length = match header("Content-Length") {
+        Some(length) => length.parse::<usize>().map_err(|_| 400u16)?,
+        None => 0,
+    };
+    if length > max_body_size {
+        return Err(413);
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(read_error)?;
+    Ok(Request {
+        method: method.to_string(),
+        target: target.to_string(),
+        headers,
+        body,
+    })
+}
+
+/// The status of the error response to a request that couldn't be read.
+fn read_error(error: io::Error) -> u16 {
+    match error.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => 408,
+        _ => 400,
+    }
+}
+
+/// An HTTP response, as sent to a client.
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn error(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".to_string(), "text/plain".to_string())],
+            body: format!("{} {}\n", status, reason(status)).into_bytes(),
+        }
+    }
+
+    fn write_to(&self, stream: &mut impl Write) -> std::io::Result<()> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason(self.status));
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.body.len()
+        ));
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+/// Parses what a CGI script wrote: headers, an empty line, then the body.
+fn parse_cgi_response(output: &[u8]) -> Result<Response> {
+    let mut status = None;
+    let mut headers = Vec::new();
+    let mut rest = output;
+    loop {
+        let end = rest
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .context("the handler didn't write the headers of the response")?;
+        let line = std::str::from_utf8(&rest[..end])
+            .context("the handler wrote headers that aren't UTF-8")?
+            .trim_end_matches('\r');
+        rest = &rest[end + 1..];
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line
+            .split_once(':')
+            .with_context(|| format!("the handler wrote an invalid header `{}`", line))?;
+        let (name, value) = (name.trim(), value.trim());
+        if name.eq_ignore_ascii_case("Status") {
+            let code = value.split(' ').next().unwrap_or_default();
+            status = Some(
+                code.parse::<u16>()
+                    .ok()
+                    .filter(|code| (100..1000).contains(code))
+                    .with_context(|| format!("the handler wrote an invalid status `{}`", value))?,
+            );
+        } else if !["Content-Length", "Connection", "Transfer-Encoding"]
+            .iter()
+            .any(|header| name.eq_ignore_ascii_case(header))
+        {
+            headers.push((name.to_string(), value.to_string()));
+        }
+    }
+    let redirects = headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("Location"));
+    Ok(Response {
+        status: status.unwrap_or(if redirects { 302 } else { 200 }),
+        headers,
+        body: rest.to_vec(),
+    })
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        505 => "HTTP Version Not Supported",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_requests() {
+        let request = read_request(
+            &b"POST /items?color=red HTTP/1.1\r\nHost: localhost\r\nContent-Type: text/plain\r\nX-Trace-Id: 42\r\nContent-Length: 5\r\n\r\nhello"[..],
+            1024,
+        )
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.body, b"hello");
+        let env = request.cgi_env("127.0.0.1:8080".parse().unwrap(), None);
+        let var = |name: &str| {
+            env.iter()
+                .find(|(var, _)| var == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(var("PATH_INFO"), Some("/items"));
+        assert_eq!(var("QUERY_STRING"), Some("color=red"));
+        assert_eq!(var("CONTENT_TYPE"), Some("text/plain"));
+        assert_eq!(var("CONTENT_LENGTH"), Some("5"));
+        assert_eq!(var("HTTP_X_TRACE_ID"), Some("42"));
+
+        assert_eq!(read_request(&b"GET /\r\n\r\n"[..], 1024).unwrap_err(), 400);
+        assert_eq!(
+            read_request(&b"PUT / HTTP/1.1\r\nContent-Length: 2048\r\n\r\n"[..], 1024).unwrap_err(),
+            413
+        );
+        let huge = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(MAX_HEAD_SIZE));
+        assert_eq!(read_request(huge.as_bytes(), 1024).unwrap_err(), 431);
+    }
+
+    #[test]
+    fn slow_requests_time_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        // The headers never end, but keep coming
+        let sender = thread::spawn(move || {
+            for _ in 0..20 {
+                if client.write_all(b"X-Slow: 1\r\n").is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+        });
+        let started = Instant::now();
+        let reader = DeadlineReader {
+            stream: &server,
+            deadline: started + Duration::from_millis(100),
+        };
+        assert_eq!(read_request(reader, 1024).unwrap_err(), 408);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        drop(server);
+        sender.join().unwrap();
+    }
+
+    #[test]
+    fn parse_cgi_responses() {
+        let response =
+            parse_cgi_response(b"Status: 404 Not Found\nContent-Type: text/plain\n\nmissing")
+                .unwrap();
+        assert_eq!(response.status, 404);
+        assert_eq!(
+            response.headers,
+            vec![("Content-Type".to_string(), "text/plain".to_string())]
+        );
+        assert_eq!(response.body, b"missing");
+
+        let response = parse_cgi_response(b"Location: /elsewhere\r\n\r\n").unwrap();
+        assert_eq!(response.status, 302);
+
+        assert!(parse_cgi_response(b"just a body").is_err());
+    }
+}
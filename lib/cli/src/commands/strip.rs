@@ -0,0 +1,51 @@
+use anyhow::{bail, Context, Result};
+use bytesize::ByteSize;
+use clap::Parser;
+use std::path::PathBuf;
+use wasmer::*;
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer strip` subcommand
+pub struct Strip {
+    /// Input file
+    #[clap(name = "FILE", parse(from_os_str))]
+    path: PathBuf,
+
+    /// Output file, the input file is overwritten if not given
+    #[clap(name = "OUTPUT PATH", short = 'o', parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// The custom sections to keep (e.g. `name`, for the names of the
+    /// functions in traces)
+    #[clap(long = "keep", name = "SECTION")]
+    keep: Vec<String>,
+}
+
+impl Strip {
+    /// Runs logic for the `strip` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .context(format!("failed to strip `{}`", self.path.display()))
+    }
+
+    fn inner_execute(&self) -> Result<()> {
+        let contents = std::fs::read(&self.path)?;
+        if !is_wasm(&contents) {
+            bail!("`wasmer strip` only strips WebAssembly files");
+        }
+        let stripped = strip_custom_sections(&contents, |name| {
+            self.keep.iter().any(|keep| keep.as_str() == name)
+        })?;
+        let output = self.output.as_ref().unwrap_or(&self.path);
+        std::fs::write(output, &stripped)
+            .with_context(|| format!("failed to write `{}`", output.display()))?;
+        let saved = (contents.len() - stripped.len()) as u64;
+        eprintln!(
+            "✔ Stripped {} ({:.1}%) of custom sections, to `{}`.",
+            ByteSize(saved),
+            100. * saved as f64 / contents.len() as f64,
+            output.display()
+        );
+        Ok(())
+    }
+}
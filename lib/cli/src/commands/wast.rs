@@ -1,16 +1,18 @@
 //! Runs a .wast WebAssembly test suites
 use crate::store::StoreOptions;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::Parser;
-use std::path::PathBuf;
+use colored::*;
+use std::fs;
+use std::path::{Path, PathBuf};
 use wasmer_wast::Wast as WastSpectest;
 
 #[derive(Debug, Parser)]
 /// The options for the `wasmer wast` subcommand
 pub struct Wast {
-    /// Wast file to run
-    #[clap(name = "FILE", parse(from_os_str))]
-    path: PathBuf,
+    /// Wast files to run, or directories to run the `.wast` files of
+    #[clap(name = "FILES", parse(from_os_str), required = true)]
+    paths: Vec<PathBuf>,
 
     #[clap(flatten)]
     store: StoreOptions,
@@ -21,17 +23,64 @@ pub struct Wast {
 }
 
 impl Wast {
-    /// Runs logic for the `validate` subcommand
+    /// Runs logic for the `wast` subcommand
     pub fn execute(&self) -> Result<()> {
         self.inner_execute()
-            .context(format!("failed to test the wast `{}`", self.path.display()))
+            .context("failed to test the wast files")
     }
+
     fn inner_execute(&self) -> Result<()> {
-        let (store, _compiler_name) = self.store.get_store()?;
-        let mut wast = WastSpectest::new_with_spectest(store);
-        wast.fail_fast = self.fail_fast;
-        wast.run_file(&self.path).with_context(|| "tests failed")?;
-        eprintln!("Wast tests succeeded for `{}`.", self.path.display());
+        let mut files = Vec::new();
+        for path in &self.paths {
+            collect_wast_files(path, &mut files)
+                .with_context(|| format!("failed to read `{}`", path.display()))?;
+        }
+        if files.is_empty() {
+            bail!("no `.wast` files to run");
+        }
+        let mut failed = 0;
+        for file in &files {
+            // Every script runs in a store of its own, so that the
+            // instances it registers don't leak into the next one
+            let (store, _compiler_name) = self.store.get_store()?;
+            let mut wast = WastSpectest::new_with_spectest(store);
+            wast.fail_fast = self.fail_fast;
+            match wast.run_file(file) {
+                Ok(()) => eprintln!("{} {}", "ok".green(), file.display()),
+                Err(e) => {
+                    failed += 1;
+                    eprintln!("{} {}\n{}", "FAILED".red(), file.display(), e);
+                    if self.fail_fast {
+                        break;
+                    }
+                }
+            }
+        }
+        if failed > 0 {
+            bail!("{} of {} wast files failed", failed, files.len());
+        }
+        eprintln!("Wast tests succeeded for {} files.", files.len());
         Ok(())
     }
 }
+
+/// Adds `path` if it's a file, or the `.wast` files found recursively in
+/// it if it's a directory, in a deterministic order.
+fn collect_wast_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            collect_wast_files(&entry, files)?;
+        } else if entry.extension().map_or(false, |ext| ext == "wast") {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
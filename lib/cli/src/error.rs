@@ -3,6 +3,45 @@
 use anyhow::{Chain, Error};
 use colored::*;
 use std::fmt::{self, Debug, Write};
+use std::time::Duration;
+use wasmer::RuntimeError;
+
+/// The exit code of the CLI when the guest traps, the same as the one of
+/// a process aborted by `SIGABRT` on Unix.
+pub const TRAP_EXIT_CODE: i32 = 134;
+
+/// The exit code of the CLI when it fails for another reason.
+pub const ERROR_EXIT_CODE: i32 = 1;
+
+/// The exit code of the CLI when the guest times out, the same as the one
+/// of the `timeout` command of coreutils.
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// An error telling that the guest asked to exit with the given code
+/// (e.g. with the WASI `proc_exit`), which the CLI exits with.
+#[derive(Debug)]
+pub struct GuestExit(pub i32);
+
+impl fmt::Display for GuestExit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the guest exited with code {}", self.0)
+    }
+}
+
+impl std::error::Error for GuestExit {}
+
+/// An error telling that the guest was interrupted because it didn't
+/// finish within the given time.
+#[derive(Debug)]
+pub struct GuestTimeout(pub Duration);
+
+impl fmt::Display for GuestTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the guest didn't finish within {:?}", self.0)
+    }
+}
+
+impl std::error::Error for GuestTimeout {}
 
 /// A `PrettyError` for printing `anyhow::Error` nicely.
 pub struct PrettyError {
@@ -21,15 +60,36 @@ macro_rules! warning {
 impl PrettyError {
     /// Process a `Result` printing any errors and exiting
     /// the process after
+    ///
+    /// The process exits with the code of a [`GuestExit`] error, without
+    /// printing it, with [`TIMEOUT_EXIT_CODE`] for a [`GuestTimeout`], with
+    /// [`TRAP_EXIT_CODE`] if the guest trapped, and with
+    /// [`ERROR_EXIT_CODE`] otherwise.
     pub fn report<T>(result: Result<T, Error>) -> ! {
         std::process::exit(match result {
             Ok(_t) => 0,
-            Err(error) => {
-                eprintln!("{:?}", PrettyError { error });
-                1
-            }
+            Err(error) => match error.downcast_ref::<GuestExit>() {
+                Some(GuestExit(code)) => *code,
+                None => {
+                    let timed_out = error.downcast_ref::<GuestTimeout>().is_some();
+                    let trapped = error.chain().any(|cause| cause.is::<RuntimeError>());
+                    eprintln!("{:?}", PrettyError { error });
+                    if timed_out {
+                        TIMEOUT_EXIT_CODE
+                    } else if trapped {
+                        TRAP_EXIT_CODE
+                    } else {
+                        ERROR_EXIT_CODE
+                    }
+                }
+            },
         });
     }
+
+    /// Prints an error like [`report`](Self::report), without exiting.
+    pub fn print(error: Error) {
+        eprintln!("{:?}", PrettyError { error });
+    }
 }
 
 impl Debug for PrettyError {
@@ -8,13 +8,16 @@ use crate::common::WasmFeatures;
 use clap::Parser;
 #[allow(unused_imports)]
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::string::ToString;
 #[allow(unused_imports)]
 use std::sync::Arc;
 use wasmer::*;
-#[cfg(feature = "compiler")]
-use wasmer_compiler::CompilerConfig;
 use wasmer_compiler::Engine;
+#[cfg(feature = "compiler")]
+use wasmer_compiler::PerfStrategy;
+#[cfg(feature = "compiler")]
+use wasmer_compiler::{CompilerConfig, ModuleMiddleware};
 
 #[derive(Debug, Clone, Parser, Default)]
 /// The compiler options
@@ -29,17 +32,21 @@ pub struct StoreOptions {
 /// The compiler options
 pub struct CompilerOptions {
     /// Use Singlepass compiler.
-    #[clap(long, conflicts_with_all = &["cranelift", "llvm"])]
+    #[clap(long, conflicts_with_all = &["cranelift", "llvm", "interpreter"])]
     singlepass: bool,
 
     /// Use Cranelift compiler.
-    #[clap(long, conflicts_with_all = &["singlepass", "llvm"])]
+    #[clap(long, conflicts_with_all = &["singlepass", "llvm", "interpreter"])]
     cranelift: bool,
 
     /// Use LLVM compiler.
-    #[clap(long, conflicts_with_all = &["singlepass", "cranelift"])]
+    #[clap(long, conflicts_with_all = &["singlepass", "cranelift", "interpreter"])]
     llvm: bool,
 
+    /// Use the interpreter, which doesn't need memory to be executable.
+    #[clap(long, conflicts_with_all = &["singlepass", "cranelift", "llvm"])]
+    interpreter: bool,
+
     /// Enable compiler internal verification.
     #[clap(long)]
     #[cfg(any(feature = "singlepass", feature = "cranelift", feature = "llvm"))]
@@ -50,10 +57,70 @@ pub struct CompilerOptions {
     #[clap(long, parse(from_os_str))]
     llvm_debug_dir: Option<PathBuf>,
 
+    /// Compile for a CPU feature profile (x86-64, x86-64-v2, x86-64-v3 or
+    /// x86-64-v4) instead of the features detected on this host, so the
+    /// artifacts run on every CPU that meets the profile.
+    #[clap(long = "cpu-baseline")]
+    cpu_baseline: Option<CpuBaseline>,
+
+    /// Compile for another target triple than the host, for instance
+    /// `aarch64-unknown-linux-gnu`, following the
+    /// [`target-lexicon`](https://crates.io/crates/target-lexicon) format.
+    ///
+    /// Code compiled for another target can be serialized or emitted as
+    /// an object, but not run.
+    #[clap(long = "target")]
+    target_triple: Option<Triple>,
+
+    /// CPU features the `--target` supports, as a comma-separated list
+    /// (for instance `sse2,avx2`).
+    #[clap(short = 'm', long = "cpu-features", use_value_delimiter = true)]
+    cpu_features: Vec<CpuFeature>,
+
+    /// Number of threads to compile the functions of a module on
+    /// (defaults to one per CPU).
+    #[clap(long = "compile-jobs")]
+    compile_jobs: Option<usize>,
+
+    /// The size of the stack Wasm code runs on (e.g. `8MiB`, defaults to
+    /// `1MiB`). Deeply recursive modules need a larger one.
+    #[clap(long = "stack-size", name = "STACK_SIZE")]
+    stack_size: Option<bytesize::ByteSize>,
+
+    /// Register the compiled code with native debuggers (GDB, LLDB),
+    /// along with debug info translated from the DWARF of the module.
+    #[cfg(feature = "debug-info")]
+    #[clap(long = "debug-info")]
+    debug_info: bool,
+
+    /// Describe the compiled functions to the Linux `perf` profiler, in
+    /// `/tmp/perf-<pid>.map` (`--perf` or `--perf=map`) or in
+    /// `jit-<pid>.dump` (`--perf=jitdump`).
+    #[clap(
+        long = "perf",
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "map",
+        parse(try_from_str = parse_perf_strategy)
+    )]
+    perf: Option<PerfStrategy>,
+
     #[clap(flatten)]
     features: WasmFeatures,
 }
 
+#[cfg(feature = "compiler")]
+fn parse_perf_strategy(strategy: &str) -> Result<PerfStrategy> {
+    match strategy {
+        "map" => Ok(PerfStrategy::PerfMap),
+        "jitdump" => Ok(PerfStrategy::JitDump),
+        _ => bail!(
+            "unknown perf output `{}`, expected `map` or `jitdump`",
+            strategy
+        ),
+    }
+}
+
 #[cfg(feature = "compiler")]
 impl CompilerOptions {
     fn get_compiler(&self) -> Result<CompilerType> {
@@ -63,6 +130,8 @@ impl CompilerOptions {
             Ok(CompilerType::LLVM)
         } else if self.singlepass {
             Ok(CompilerType::Singlepass)
+        } else if self.interpreter {
+            Ok(CompilerType::Interpreter)
         } else {
             // Auto mode, we choose the best compiler for that platform
             cfg_if::cfg_if! {
@@ -74,6 +143,9 @@ impl CompilerOptions {
                 }
                 else if #[cfg(feature = "llvm")] {
                     Ok(CompilerType::LLVM)
+                }
+                else if #[cfg(feature = "interpreter")] {
+                    Ok(CompilerType::Interpreter)
                 } else {
                     bail!("There are no available compilers for your architecture");
                 }
@@ -101,8 +173,58 @@ impl CompilerOptions {
         Ok(features)
     }
 
+    /// The triple given with `--target`, if any.
+    pub fn target_triple(&self) -> Option<&Triple> {
+        self.target_triple.as_ref()
+    }
+
+    /// Gets the target to compile for: the one given with `--target` and
+    /// `--cpu-features`, or else the host.
+    pub fn get_target(&self) -> Result<Target> {
+        let target_triple = match self.target_triple.as_ref() {
+            Some(target_triple) => target_triple,
+            None if self.cpu_features.is_empty() => return Ok(Target::default()),
+            None => bail!("`--cpu-features` can only be used along with `--target`"),
+        };
+        let mut features = self
+            .cpu_features
+            .iter()
+            .fold(CpuFeature::set(), |a, b| a | *b);
+        // Cranelift requires SSE2, so we have this "hack" for now to facilitate
+        // usage
+        if target_triple.architecture == Architecture::X86_64 {
+            features |= CpuFeature::SSE2;
+        }
+        Ok(Target::new(target_triple.clone(), features))
+    }
+
+    /// Applies the `--cpu-baseline` profile (if any) to a target.
+    ///
+    /// The profile replaces the features detected on the host, and is
+    /// added to the features requested explicitly for other targets.
+    pub fn apply_cpu_baseline(&self, target: Target) -> Result<Target> {
+        let baseline = match self.cpu_baseline {
+            Some(baseline) => baseline,
+            None => return Ok(target),
+        };
+        if target.triple().architecture != baseline.architecture() {
+            bail!(
+                "the `{}` CPU baseline can't be used for the `{}` target",
+                baseline,
+                target.triple()
+            );
+        }
+        let features = if target == Target::default() {
+            baseline.features()
+        } else {
+            *target.cpu_features() | baseline.features()
+        };
+        Ok(Target::new(target.triple().clone(), features))
+    }
+
     /// Gets the Store for a given target.
     pub fn get_store_for_target(&self, target: Target) -> Result<(Store, CompilerType)> {
+        let target = self.apply_cpu_baseline(target)?;
         let (compiler_config, compiler_type) = self.get_compiler_config()?;
         let engine = self.get_engine(target, compiler_config)?;
         let store = Store::new(engine);
@@ -116,14 +238,24 @@ impl CompilerOptions {
         compiler_config: Box<dyn CompilerConfig>,
     ) -> Result<Engine> {
         let features = self.get_features(compiler_config.default_features_for_target(&target))?;
-        let engine: Engine = wasmer_compiler::EngineBuilder::new(compiler_config)
+        let engine = wasmer_compiler::EngineBuilder::new(compiler_config)
             .set_features(Some(features))
             .set_target(Some(target))
-            .engine();
+            .set_compile_jobs(self.compile_jobs)
+            .set_perf(self.perf)
+            .set_stack_size(self.stack_size());
+        #[cfg(feature = "debug-info")]
+        let engine = engine.set_debug_info(self.debug_info);
+        let engine: Engine = engine.engine();
 
         Ok(engine)
     }
 
+    /// The size in bytes of the stack Wasm code runs on, if it was given.
+    fn stack_size(&self) -> Option<usize> {
+        self.stack_size.map(|size| size.as_u64() as usize)
+    }
+
     /// Get the Compiler Config for the current options
     #[allow(unused_variables)]
     pub(crate) fn get_compiler_config(&self) -> Result<(Box<dyn CompilerConfig>, CompilerType)> {
@@ -249,7 +381,14 @@ impl CompilerOptions {
                 }
                 Box::new(config)
             }
-            #[cfg(not(all(feature = "singlepass", feature = "cranelift", feature = "llvm",)))]
+            #[cfg(feature = "interpreter")]
+            CompilerType::Interpreter => Box::new(wasmer_compiler_interpreter::Interpreter::new()),
+            #[cfg(not(all(
+                feature = "singlepass",
+                feature = "cranelift",
+                feature = "llvm",
+                feature = "interpreter",
+            )))]
             compiler => {
                 bail!(
                     "The `{}` compiler is not included in this binary.",
@@ -264,7 +403,7 @@ impl CompilerOptions {
 }
 
 /// The compiler used for the store
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CompilerType {
     /// Singlepass compiler
     Singlepass,
@@ -272,6 +411,8 @@ pub enum CompilerType {
     Cranelift,
     /// LLVM compiler
     LLVM,
+    /// Interpreter
+    Interpreter,
     /// Headless compiler
     Headless,
 }
@@ -286,6 +427,8 @@ impl CompilerType {
             Self::Cranelift,
             #[cfg(feature = "llvm")]
             Self::LLVM,
+            #[cfg(feature = "interpreter")]
+            Self::Interpreter,
         ]
     }
 }
@@ -296,22 +439,114 @@ impl ToString for CompilerType {
             Self::Singlepass => "singlepass".to_string(),
             Self::Cranelift => "cranelift".to_string(),
             Self::LLVM => "llvm".to_string(),
+            Self::Interpreter => "interpreter".to_string(),
             Self::Headless => "headless".to_string(),
         }
     }
 }
 
+impl FromStr for CompilerType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "singlepass" => Ok(Self::Singlepass),
+            "cranelift" => Ok(Self::Cranelift),
+            "llvm" => Ok(Self::LLVM),
+            "interpreter" => Ok(Self::Interpreter),
+            _ => Err(format!(
+                "unknown compiler `{}`, expected `singlepass`, `cranelift`, `llvm` or `interpreter`",
+                s
+            )),
+        }
+    }
+}
+
 #[cfg(all(feature = "compiler"))]
 impl StoreOptions {
     /// Gets the store for the host target, with the compiler name selected
     pub fn get_store(&self) -> Result<(Store, CompilerType)> {
-        let target = Target::default();
-        self.get_store_for_target(target)
+        self.get_store_with_middlewares(vec![])
+    }
+
+    /// Gets the store for the host target, with the compiler name selected
+    /// and `middlewares` pushed onto the middleware chain of the compiler.
+    pub fn get_store_with_middlewares(
+        &self,
+        middlewares: Vec<Arc<dyn ModuleMiddleware>>,
+    ) -> Result<(Store, CompilerType)> {
+        let target = self.compiler.get_target()?;
+        if !target.is_native() {
+            bail!(
+                "code compiled for `{}` can't run on this host, `--target` can only be used to compile modules ahead of time",
+                target.triple()
+            );
+        }
+        let target = self.compiler.apply_cpu_baseline(target)?;
+        // The store runs code on this host, so it can't use features
+        // the host doesn't have.
+        let missing = target.cpu_features().difference(CpuFeature::for_host());
+        if !missing.is_empty() {
+            bail!(
+                "this host's CPU doesn't support the requested CPU baseline (missing {})",
+                CpuFeature::format_set(missing)
+            );
+        }
+        self.get_store_for_target_with_middlewares(target, middlewares)
+    }
+
+    /// Gets the target given with `--target`, or else the host.
+    pub fn get_target(&self) -> Result<Target> {
+        self.compiler.get_target()
+    }
+
+    /// The same options, with `compiler` selected instead of the one given.
+    pub fn with_compiler(&self, compiler: &CompilerType) -> Self {
+        let mut options = self.clone();
+        options.compiler.singlepass = *compiler == CompilerType::Singlepass;
+        options.compiler.cranelift = *compiler == CompilerType::Cranelift;
+        options.compiler.llvm = *compiler == CompilerType::LLVM;
+        options.compiler.interpreter = *compiler == CompilerType::Interpreter;
+        options
+    }
+
+    /// How the compiled functions are described to `perf`, if they are.
+    pub fn perf(&self) -> Option<PerfStrategy> {
+        self.compiler.perf
+    }
+
+    /// The size in bytes of the stack Wasm code runs on, if it was given.
+    pub fn stack_size(&self) -> Option<usize> {
+        self.compiler.stack_size()
+    }
+
+    /// Whether the compiled code is registered with native debuggers.
+    #[cfg(feature = "debug-info")]
+    pub fn debug_info(&self) -> bool {
+        self.compiler.debug_info
+    }
+
+    /// Whether the compiled code is registered with native debuggers.
+    #[cfg(not(feature = "debug-info"))]
+    pub fn debug_info(&self) -> bool {
+        false
     }
 
     /// Gets the store for a given target, with the compiler name selected.
     pub fn get_store_for_target(&self, target: Target) -> Result<(Store, CompilerType)> {
-        let (compiler_config, compiler_type) = self.compiler.get_compiler_config()?;
+        self.get_store_for_target_with_middlewares(target, vec![])
+    }
+
+    fn get_store_for_target_with_middlewares(
+        &self,
+        target: Target,
+        middlewares: Vec<Arc<dyn ModuleMiddleware>>,
+    ) -> Result<(Store, CompilerType)> {
+        let target = self.compiler.apply_cpu_baseline(target)?;
+        let (mut compiler_config, compiler_type) = self.compiler.get_compiler_config()?;
+        for middleware in middlewares {
+            compiler_config.push_middleware(middleware);
+        }
         let engine = self.get_engine_with_compiler(target, compiler_config)?;
         let store = Store::new(engine);
         Ok((store, compiler_type))
@@ -342,4 +577,9 @@ impl StoreOptions {
         let store = Store::new(engine);
         Ok((store, CompilerType::Headless))
     }
+
+    /// Whether the compiled code is registered with native debuggers.
+    pub fn debug_info(&self) -> bool {
+        false
+    }
 }
@@ -2,6 +2,22 @@
 use anyhow::{bail, Result};
 use std::env;
 use std::path::PathBuf;
+#[cfg(feature = "http")]
+use {
+    anyhow::{anyhow, Context},
+    http_req::{request::Request, uri::Uri},
+    sha2::{Digest, Sha256},
+    std::convert::TryFrom,
+    std::fs,
+    std::path::Path,
+    std::process::Command,
+    std::time::Duration,
+    url::Url,
+};
+
+/// How many redirections [`download`] follows.
+#[cfg(feature = "http")]
+const MAX_REDIRECTS: usize = 5;
 
 /// Whether or not Wasmer should print with color
 pub fn wasmer_should_print_color() -> bool {
@@ -64,9 +80,103 @@ pub fn parse_envvar(entry: &str) -> Result<(String, String)> {
     }
 }
 
+/// Parses an archive to mount, as `<archive>:<guest dir>`.
+pub fn parse_mount(entry: &str) -> Result<(PathBuf, String)> {
+    // The path of the archive can have a `:`, as in `C:\`
+    match entry.rsplit_once(':') {
+        Some((archive, guest_dir)) if !archive.is_empty() && !guest_dir.is_empty() => {
+            Ok((PathBuf::from(archive), guest_dir.to_string()))
+        }
+        _ => bail!(
+            "Mounts must be of the form `<archive>:<guest dir>`; found `{}`",
+            entry
+        ),
+    }
+}
+
+/// Downloads `url` over HTTPS, following redirections as long as they
+/// stay on HTTPS.
+#[cfg(feature = "http")]
+pub fn download(url: &str) -> Result<Vec<u8>> {
+    let mut url = Url::parse(url).with_context(|| format!("invalid URL `{}`", url))?;
+    for _ in 0..=MAX_REDIRECTS {
+        if url.scheme() != "https" {
+            bail!("refusing to download `{}` without HTTPS", url);
+        }
+        let uri =
+            Uri::try_from(url.as_str()).map_err(|e| anyhow!("invalid URL `{}`: {}", url, e))?;
+        let mut body = Vec::new();
+        let response = Request::new(&uri)
+            .header("User-Agent", "wasmer")
+            .timeout(Some(Duration::from_secs(60)))
+            .send(&mut body)
+            .with_context(|| format!("failed to download `{}`", url))?;
+        let status = response.status_code();
+        if status.is_redirect() {
+            let location = response
+                .headers()
+                .get("Location")
+                .ok_or_else(|| anyhow!("`{}` redirects nowhere", url))?;
+            url = redirect_target(&url, location)?;
+            continue;
+        }
+        if !status.is_success() {
+            bail!("`GET {}` replied with status {}", url, status);
+        }
+        return Ok(body);
+    }
+    bail!("too many redirections downloading `{}`", url)
+}
+
+/// The URL a redirection from `url` to `location` leads to, as
+/// `location` may be relative to `url`.
+#[cfg(feature = "http")]
+fn redirect_target(url: &Url, location: &str) -> Result<Url> {
+    url.join(location)
+        .with_context(|| format!("`{}` redirects to the invalid URL `{}`", url, location))
+}
+
+/// Checks that `data` has the SHA-256 `checksum`, in hexadecimal and
+/// optionally followed by a file name as `sha256sum` prints it.
+#[cfg(feature = "http")]
+pub fn verify_sha256(data: &[u8], checksum: &str) -> Result<()> {
+    let expected = checksum
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| anyhow!("the checksum is empty"))?;
+    let actual = format!("{:x}", Sha256::digest(data));
+    if !expected.eq_ignore_ascii_case(&actual) {
+        bail!("the checksum is {} instead of {}", actual, expected);
+    }
+    Ok(())
+}
+
+/// Extracts the `.tar.gz` `archive` into the directory `dir`.
+#[cfg(feature = "http")]
+pub fn extract_tar_gz(archive: &[u8], dir: &Path) -> Result<()> {
+    let archive_path = dir.join(".archive.tar.gz");
+    fs::write(&archive_path, archive)?;
+    let output = Command::new("tar")
+        .arg("-xzf")
+        .arg(&archive_path)
+        .arg("-C")
+        .arg(dir)
+        .output()
+        .context("failed to run `tar`");
+    fs::remove_file(&archive_path)?;
+    let output = output?;
+    if !output.status.success() {
+        bail!(
+            "failed to extract the archive: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::parse_envvar;
+    use super::{parse_envvar, parse_mount};
 
     #[test]
     fn test_parse_envvar() {
@@ -89,4 +199,70 @@ mod tests {
             ("A".into(), "B=C=D".into())
         );
     }
+
+    #[test]
+    fn test_parse_mount() {
+        assert_eq!(
+            parse_mount("data.tar.gz:/data").unwrap(),
+            ("data.tar.gz".into(), "/data".into())
+        );
+        assert_eq!(
+            parse_mount(r"C:\assets.zip:/assets").unwrap(),
+            (r"C:\assets.zip".into(), "/assets".into())
+        );
+        assert!(parse_mount("data.tar").is_err());
+        assert!(parse_mount("data.tar:").is_err());
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_redirect_target() {
+        use super::redirect_target;
+        use url::Url;
+
+        let url =
+            Url::parse("https://github.com/wasmerio/wasmer/releases/download/v3.0.0/a.tar.gz?x=/y")
+                .unwrap();
+        let target = |location| redirect_target(&url, location).unwrap().to_string();
+        assert_eq!(
+            target("https://objects.githubusercontent.com/a"),
+            "https://objects.githubusercontent.com/a"
+        );
+        assert_eq!(
+            target("//objects.githubusercontent.com/a"),
+            "https://objects.githubusercontent.com/a"
+        );
+        assert_eq!(
+            target("/mirror/a.tar.gz"),
+            "https://github.com/mirror/a.tar.gz"
+        );
+        assert_eq!(
+            target("b.tar.gz"),
+            "https://github.com/wasmerio/wasmer/releases/download/v3.0.0/b.tar.gz"
+        );
+        assert_eq!(
+            target("http://objects.githubusercontent.com/a"),
+            "http://objects.githubusercontent.com/a"
+        );
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_download_requires_https() {
+        let error = super::download("http://github.com/wasmerio/wasmer").unwrap_err();
+        assert!(error.to_string().contains("without HTTPS"), "{}", error);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_verify_sha256() {
+        use super::verify_sha256;
+
+        let sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert!(verify_sha256(b"hello", sha256).is_ok());
+        assert!(verify_sha256(b"hello", &sha256.to_uppercase()).is_ok());
+        assert!(verify_sha256(b"hello", &format!("{}  wasmer.tar.gz\n", sha256)).is_ok());
+        assert!(verify_sha256(b"hello!", sha256).is_err());
+        assert!(verify_sha256(b"hello", "").is_err());
+    }
 }
@@ -10,14 +10,15 @@ use crate::trampoline::{
 };
 use crate::translator::{
     compiled_function_unwind_info, irlibcall_to_libcall, irreloc_to_relocationkind,
-    signature_to_cranelift_ir, CraneliftUnwindInfo, FuncTranslator,
+    irusertrapcode_to_trapcode, signature_to_cranelift_ir, CraneliftUnwindInfo, FuncTranslator,
 };
 use cranelift_codegen::ir::ExternalName;
+use cranelift_codegen::isa::TargetIsa;
 use cranelift_codegen::print_errors::pretty_error;
 use cranelift_codegen::{ir, MachReloc};
 use cranelift_codegen::{Context, MachTrap};
 #[cfg(feature = "unwind")]
-use gimli::write::{Address, EhFrame, FrameTable};
+use gimli::write::{Address, CieId, EhFrame, FrameTable};
 #[cfg(feature = "rayon")]
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 use std::sync::Arc;
@@ -28,9 +29,9 @@ use wasmer_compiler::{
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
     CallingConvention, Compilation, CompileError, CompileModuleInfo, CompiledFunction,
-    CompiledFunctionFrameInfo, CompiledFunctionUnwindInfo, Dwarf, FunctionBody, FunctionIndex,
-    LocalFunctionIndex, ModuleInfo, Relocation, RelocationTarget, SectionIndex, SignatureIndex,
-    Target, TrapCode, TrapInformation,
+    CompiledFunctionFrameInfo, CompiledFunctionUnwindInfo, CustomSection, Dwarf, FunctionBody,
+    FunctionIndex, LocalFunctionIndex, ModuleInfo, Relocation, RelocationTarget, SectionIndex,
+    SignatureIndex, Target, TrapCode, TrapInformation,
 };
 
 /// A compiler that compiles a WebAssembly module with Cranelift, translating the Wasm to Cranelift IR,
@@ -49,14 +50,240 @@ impl CraneliftCompiler {
     pub fn config(&self) -> &Cranelift {
         &self.config
     }
+
+    /// Compiles a single function, along with its DWARF frame description
+    /// entry if `emit_fde` is set and the ISA produces one.
+    #[allow(clippy::too_many_arguments)]
+    fn compile_function_body(
+        &self,
+        isa: &dyn TargetIsa,
+        compile_info: &CompileModuleInfo,
+        module_translation_state: &ModuleTranslationState,
+        signatures: &PrimaryMap<SignatureIndex, ir::Signature>,
+        func_translator: &mut FuncTranslator,
+        i: LocalFunctionIndex,
+        input: &FunctionBodyData<'_>,
+        emit_fde: bool,
+    ) -> Result<(CompiledFunction, Option<Fde>), CompileError> {
+        let _span = tracing::trace_span!(
+            "compile_function",
+            index = i.as_u32(),
+            bytes = input.data.len()
+        )
+        .entered();
+        let module = &compile_info.module;
+        let func_index = module.func_index(i);
+        let mut context = Context::new();
+        let mut func_env = FuncEnvironment::new(
+            isa.frontend_config(),
+            module,
+            signatures,
+            &compile_info.memory_styles,
+            &compile_info.table_styles,
+        );
+        context.func.name = get_function_name(func_index);
+        context.func.signature = signatures[module.functions[func_index]].clone();
+        // if generate_debug_info {
+        //     context.func.collect_debug_info();
+        // }
+        let mut reader = MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
+        reader.set_middleware_chain(
+            self.config
+                .middlewares
+                .generate_function_middleware_chain(i),
+        );
+
+        func_translator.translate(
+            module_translation_state,
+            &mut reader,
+            &mut context.func,
+            &mut func_env,
+            i,
+        )?;
+
+        let mut code_buf: Vec<u8> = Vec::new();
+        context
+            .compile_and_emit(isa, &mut code_buf)
+            .map_err(|error| CompileError::Codegen(pretty_error(&context.func, error)))?;
+
+        let result = context.mach_compile_result.as_ref().unwrap();
+        let func_relocs = result
+            .buffer
+            .relocs()
+            .iter()
+            .map(|r| mach_reloc_to_reloc(module, r))
+            .collect::<Vec<_>>();
+
+        let traps = result
+            .buffer
+            .traps()
+            .iter()
+            .map(mach_trap_to_trap)
+            .collect::<Vec<_>>();
+
+        let (unwind_info, fde) = match compiled_function_unwind_info(isa, &context)? {
+            #[cfg(feature = "unwind")]
+            CraneliftUnwindInfo::Fde(fde) => {
+                if emit_fde {
+                    let fde = fde.to_fde(Address::Symbol {
+                        // The symbol is the kind of relocation.
+                        // "0" is used for functions
+                        symbol: WriterRelocate::FUNCTION_SYMBOL,
+                        // We use the addend as a way to specify the
+                        // function index
+                        addend: i.index() as _,
+                    });
+                    // The unwind information is inserted into the dwarf section
+                    (Some(CompiledFunctionUnwindInfo::Dwarf), Some(fde))
+                } else {
+                    (None, None)
+                }
+            }
+            #[cfg(feature = "unwind")]
+            other => (other.maybe_into_to_windows_unwind(), None),
+
+            // This is a bit hacky, but necessary since gimli is not
+            // available when the "unwind" feature is disabled.
+            #[cfg(not(feature = "unwind"))]
+            other => {
+                let _ = emit_fde;
+                (other.maybe_into_to_windows_unwind(), None)
+            }
+        };
+
+        let range = reader.range();
+        let address_map = get_function_address_map(&context, range, code_buf.len());
+
+        Ok((
+            CompiledFunction {
+                body: FunctionBody {
+                    body: code_buf,
+                    unwind_info,
+                },
+                relocations: func_relocs,
+                frame_info: CompiledFunctionFrameInfo { address_map, traps },
+            },
+            fde,
+        ))
+    }
+}
+
+/// Creates the frame table holding the DWARF unwind information of the
+/// functions, if the target uses it.
+#[cfg(feature = "unwind")]
+fn dwarf_frametable(isa: &dyn TargetIsa, target: &Target) -> Option<(FrameTable, CieId)> {
+    match target.triple().default_calling_convention() {
+        Ok(CallingConvention::SystemV) => {
+            match isa.create_systemv_cie() {
+                Some(cie) => {
+                    let mut dwarf_frametable = FrameTable::default();
+                    let cie_id = dwarf_frametable.add_cie(cie);
+                    Some((dwarf_frametable, cie_id))
+                }
+                // Even though we are in a SystemV system, Cranelift doesn't support it
+                None => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Writes the `.eh_frame` section describing the given functions.
+#[cfg(feature = "unwind")]
+fn eh_frame_section(
+    target: &Target,
+    mut dwarf_frametable: FrameTable,
+    cie_id: CieId,
+    fdes: impl IntoIterator<Item = Fde>,
+) -> CustomSection {
+    for fde in fdes {
+        dwarf_frametable.add_fde(cie_id, fde);
+    }
+    let mut eh_frame = EhFrame(WriterRelocate::new(target.triple().endianness().ok()));
+    dwarf_frametable.write_eh_frame(&mut eh_frame).unwrap();
+    eh_frame.0.into_section()
 }
 
+/// The DWARF frame description entry of a compiled function.
+#[cfg(feature = "unwind")]
+type Fde = gimli::write::FrameDescriptionEntry;
+#[cfg(not(feature = "unwind"))]
+type Fde = ();
+
 impl Compiler for CraneliftCompiler {
     /// Get the middlewares for this compiler
     fn get_middlewares(&self) -> &[Arc<dyn ModuleMiddleware>] {
         &self.config.middlewares
     }
 
+    fn supports_lazy_compilation(&self) -> bool {
+        true
+    }
+
+    fn deterministic_id(&self) -> String {
+        format!(
+            "cranelift-{}-{:?}{}{}",
+            env!("CARGO_PKG_VERSION"),
+            self.config.opt_level,
+            if self.config.enable_nan_canonicalization {
+                "-nan"
+            } else {
+                ""
+            },
+            if self.config.enable_pic { "-pic" } else { "" },
+        )
+    }
+
+    /// Compile a single function of the module using Cranelift.
+    fn compile_function(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation_state: &ModuleTranslationState,
+        index: LocalFunctionIndex,
+        input: &FunctionBodyData<'_>,
+    ) -> Result<(CompiledFunction, Option<CustomSection>), CompileError> {
+        let isa = self
+            .config()
+            .isa(target)
+            .map_err(|error| CompileError::Codegen(error.to_string()))?;
+        let frontend_config = isa.frontend_config();
+        let signatures = compile_info
+            .module
+            .signatures
+            .iter()
+            .map(|(_sig_index, func_type)| signature_to_cranelift_ir(func_type, frontend_config))
+            .collect::<PrimaryMap<SignatureIndex, ir::Signature>>();
+        #[cfg(feature = "unwind")]
+        let dwarf_frametable = dwarf_frametable(&*isa, target);
+        #[cfg(feature = "unwind")]
+        let emit_fde = dwarf_frametable.is_some();
+        #[cfg(not(feature = "unwind"))]
+        let emit_fde = false;
+
+        let (function, fde) = self.compile_function_body(
+            &*isa,
+            compile_info,
+            module_translation_state,
+            &signatures,
+            &mut FuncTranslator::new(),
+            index,
+            input,
+            emit_fde,
+        )?;
+
+        #[cfg(feature = "unwind")]
+        let eh_frame = dwarf_frametable.map(|(dwarf_frametable, cie_id)| {
+            eh_frame_section(target, dwarf_frametable, cie_id, fde)
+        });
+        #[cfg(not(feature = "unwind"))]
+        let eh_frame = {
+            let _ = fde;
+            None
+        };
+        Ok((function, eh_frame))
+    }
+
     /// Compile the module using Cranelift, producing a compilation result with
     /// associated relocations.
     fn compile_module(
@@ -71,8 +298,6 @@ impl Compiler for CraneliftCompiler {
             .isa(target)
             .map_err(|error| CompileError::Codegen(error.to_string()))?;
         let frontend_config = isa.frontend_config();
-        let memory_styles = &compile_info.memory_styles;
-        let table_styles = &compile_info.table_styles;
         let module = &compile_info.module;
         let signatures = module
             .signatures
@@ -88,22 +313,14 @@ impl Compiler for CraneliftCompiler {
             // FDEs will cause some issues in Linux.
             None
         } else {
-            match target.triple().default_calling_convention() {
-                Ok(CallingConvention::SystemV) => {
-                    match isa.create_systemv_cie() {
-                        Some(cie) => {
-                            let mut dwarf_frametable = FrameTable::default();
-                            let cie_id = dwarf_frametable.add_cie(cie);
-                            Some((dwarf_frametable, cie_id))
-                        }
-                        // Even though we are in a SystemV system, Cranelift doesn't support it
-                        None => None,
-                    }
-                }
-                _ => None,
-            }
+            dwarf_frametable(&*isa, target)
         };
 
+        #[cfg(feature = "unwind")]
+        let emit_fdes = dwarf_frametable.is_some();
+        #[cfg(not(feature = "unwind"))]
+        let emit_fdes = false;
+
         let mut custom_sections = PrimaryMap::new();
 
         #[cfg(not(feature = "rayon"))]
@@ -114,97 +331,16 @@ impl Compiler for CraneliftCompiler {
             .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
             .into_iter()
             .map(|(i, input)| {
-                let func_index = module.func_index(i);
-                let mut context = Context::new();
-                let mut func_env = FuncEnvironment::new(
-                    isa.frontend_config(),
-                    module,
-                    &signatures,
-                    &memory_styles,
-                    &table_styles,
-                );
-                context.func.name = get_function_name(func_index);
-                context.func.signature = signatures[module.functions[func_index]].clone();
-                // if generate_debug_info {
-                //     context.func.collect_debug_info();
-                // }
-                let mut reader =
-                    MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
-                reader.set_middleware_chain(
-                    self.config
-                        .middlewares
-                        .generate_function_middleware_chain(i),
-                );
-
-                func_translator.translate(
+                self.compile_function_body(
+                    &*isa,
+                    compile_info,
                     module_translation_state,
-                    &mut reader,
-                    &mut context.func,
-                    &mut func_env,
+                    &signatures,
+                    &mut func_translator,
                     i,
-                )?;
-
-                let mut code_buf: Vec<u8> = Vec::new();
-                context
-                    .compile_and_emit(&*isa, &mut code_buf)
-                    .map_err(|error| CompileError::Codegen(pretty_error(&context.func, error)))?;
-
-                let result = context.mach_compile_result.as_ref().unwrap();
-                let func_relocs = result
-                    .buffer
-                    .relocs()
-                    .into_iter()
-                    .map(|r| mach_reloc_to_reloc(module, r))
-                    .collect::<Vec<_>>();
-
-                let traps = result
-                    .buffer
-                    .traps()
-                    .into_iter()
-                    .map(mach_trap_to_trap)
-                    .collect::<Vec<_>>();
-
-                let (unwind_info, fde) = match compiled_function_unwind_info(&*isa, &context)? {
-                    #[cfg(feature = "unwind")]
-                    CraneliftUnwindInfo::Fde(fde) => {
-                        if dwarf_frametable.is_some() {
-                            let fde = fde.to_fde(Address::Symbol {
-                                // The symbol is the kind of relocation.
-                                // "0" is used for functions
-                                symbol: WriterRelocate::FUNCTION_SYMBOL,
-                                // We use the addend as a way to specify the
-                                // function index
-                                addend: i.index() as _,
-                            });
-                            // The unwind information is inserted into the dwarf section
-                            (Some(CompiledFunctionUnwindInfo::Dwarf), Some(fde))
-                        } else {
-                            (None, None)
-                        }
-                    }
-                    #[cfg(feature = "unwind")]
-                    other => (other.maybe_into_to_windows_unwind(), None),
-
-                    // This is a bit hacky, but necessary since gimli is not
-                    // available when the "unwind" feature is disabled.
-                    #[cfg(not(feature = "unwind"))]
-                    other => (other.maybe_into_to_windows_unwind(), None::<()>),
-                };
-
-                let range = reader.range();
-                let address_map = get_function_address_map(&context, range, code_buf.len());
-
-                Ok((
-                    CompiledFunction {
-                        body: FunctionBody {
-                            body: code_buf,
-                            unwind_info,
-                        },
-                        relocations: func_relocs,
-                        frame_info: CompiledFunctionFrameInfo { address_map, traps },
-                    },
-                    fde,
-                ))
+                    input,
+                    emit_fdes,
+                )
             })
             .collect::<Result<Vec<_>, CompileError>>()?
             .into_iter()
@@ -215,111 +351,25 @@ impl Compiler for CraneliftCompiler {
             .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
             .par_iter()
             .map_init(FuncTranslator::new, |func_translator, (i, input)| {
-                let func_index = module.func_index(*i);
-                let mut context = Context::new();
-                let mut func_env = FuncEnvironment::new(
-                    isa.frontend_config(),
-                    module,
-                    &signatures,
-                    memory_styles,
-                    table_styles,
-                );
-                context.func.name = get_function_name(func_index);
-                context.func.signature = signatures[module.functions[func_index]].clone();
-                // if generate_debug_info {
-                //     context.func.collect_debug_info();
-                // }
-                let mut reader =
-                    MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
-                reader.set_middleware_chain(
-                    self.config
-                        .middlewares
-                        .generate_function_middleware_chain(*i),
-                );
-
-                func_translator.translate(
+                self.compile_function_body(
+                    &*isa,
+                    compile_info,
                     module_translation_state,
-                    &mut reader,
-                    &mut context.func,
-                    &mut func_env,
+                    &signatures,
+                    func_translator,
                     *i,
-                )?;
-
-                let mut code_buf: Vec<u8> = Vec::new();
-                context
-                    .compile_and_emit(&*isa, &mut code_buf)
-                    .map_err(|error| CompileError::Codegen(pretty_error(&context.func, error)))?;
-
-                let result = context.mach_compile_result.as_ref().unwrap();
-                let func_relocs = result
-                    .buffer
-                    .relocs()
-                    .iter()
-                    .map(|r| mach_reloc_to_reloc(module, r))
-                    .collect::<Vec<_>>();
-
-                let traps = result
-                    .buffer
-                    .traps()
-                    .iter()
-                    .map(mach_trap_to_trap)
-                    .collect::<Vec<_>>();
-
-                let (unwind_info, fde) = match compiled_function_unwind_info(&*isa, &context)? {
-                    #[cfg(feature = "unwind")]
-                    CraneliftUnwindInfo::Fde(fde) => {
-                        if dwarf_frametable.is_some() {
-                            let fde = fde.to_fde(Address::Symbol {
-                                // The symbol is the kind of relocation.
-                                // "0" is used for functions
-                                symbol: WriterRelocate::FUNCTION_SYMBOL,
-                                // We use the addend as a way to specify the
-                                // function index
-                                addend: i.index() as _,
-                            });
-                            // The unwind information is inserted into the dwarf section
-                            (Some(CompiledFunctionUnwindInfo::Dwarf), Some(fde))
-                        } else {
-                            (None, None)
-                        }
-                    }
-                    #[cfg(feature = "unwind")]
-                    other => (other.maybe_into_to_windows_unwind(), None),
-
-                    // This is a bit hacky, but necessary since gimli is not
-                    // available when the "unwind" feature is disabled.
-                    #[cfg(not(feature = "unwind"))]
-                    other => (other.maybe_into_to_windows_unwind(), None::<()>),
-                };
-
-                let range = reader.range();
-                let address_map = get_function_address_map(&context, range, code_buf.len());
-
-                Ok((
-                    CompiledFunction {
-                        body: FunctionBody {
-                            body: code_buf,
-                            unwind_info,
-                        },
-                        relocations: func_relocs,
-                        frame_info: CompiledFunctionFrameInfo { address_map, traps },
-                    },
-                    fde,
-                ))
+                    input,
+                    emit_fdes,
+                )
             })
             .collect::<Result<Vec<_>, CompileError>>()?
             .into_iter()
             .unzip();
 
         #[cfg(feature = "unwind")]
-        let dwarf = if let Some((mut dwarf_frametable, cie_id)) = dwarf_frametable {
-            for fde in fdes.into_iter().flatten() {
-                dwarf_frametable.add_fde(cie_id, fde);
-            }
-            let mut eh_frame = EhFrame(WriterRelocate::new(target.triple().endianness().ok()));
-            dwarf_frametable.write_eh_frame(&mut eh_frame).unwrap();
-
-            let eh_frame_section = eh_frame.0.into_section();
+        let dwarf = if let Some((dwarf_frametable, cie_id)) = dwarf_frametable {
+            let eh_frame_section =
+                eh_frame_section(target, dwarf_frametable, cie_id, fdes.into_iter().flatten());
             custom_sections.push(eh_frame_section);
             Some(Dwarf::new(SectionIndex::new(custom_sections.len() - 1)))
         } else {
@@ -438,9 +488,7 @@ fn translate_ir_trapcode(trap: ir::TrapCode) -> TrapCode {
         ir::TrapCode::IntegerDivisionByZero => TrapCode::IntegerDivisionByZero,
         ir::TrapCode::BadConversionToInteger => TrapCode::BadConversionToInteger,
         ir::TrapCode::UnreachableCodeReached => TrapCode::UnreachableCodeReached,
-        ir::TrapCode::Interrupt => unimplemented!("Interrupts not supported"),
-        ir::TrapCode::User(_user_code) => unimplemented!("User trap code not supported"),
-        // ir::TrapCode::Interrupt => TrapCode::Interrupt,
-        // ir::TrapCode::User(user_code) => TrapCode::User(user_code),
+        ir::TrapCode::Interrupt => TrapCode::Interrupt,
+        ir::TrapCode::User(user_code) => irusertrapcode_to_trapcode(user_code),
     }
 }
@@ -29,10 +29,10 @@ pub enum CraneliftOptLevel {
 /// consumed by `wasmer_engine::Engine::new`.
 #[derive(Debug, Clone)]
 pub struct Cranelift {
-    enable_nan_canonicalization: bool,
+    pub(crate) enable_nan_canonicalization: bool,
     enable_verifier: bool,
-    enable_pic: bool,
-    opt_level: CraneliftOptLevel,
+    pub(crate) enable_pic: bool,
+    pub(crate) opt_level: CraneliftOptLevel,
     /// The middleware chain.
     pub(crate) middlewares: Vec<Arc<dyn ModuleMiddleware>>,
 }
@@ -706,6 +706,26 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
 
         (base, func_addr)
     }
+
+    /// Translates a check of the interrupt flag of the instance, trapping
+    /// with `Interrupt` if it is set.
+    fn translate_interrupt_check(&mut self, mut pos: FuncCursor) {
+        let pointer_type = self.pointer_type();
+        let vmctx = self.vmctx(pos.func);
+        let base = pos.ins().global_value(pointer_type, vmctx);
+
+        let mut mem_flags = ir::MemFlags::trusted();
+        mem_flags.set_readonly();
+
+        // Load the address of the flag, then the flag itself, which is
+        // written by other threads.
+        let interrupt_offset = i32::try_from(self.offsets.vmctx_interrupt()).unwrap();
+        let flag_addr = pos
+            .ins()
+            .load(pointer_type, mem_flags, base, interrupt_offset);
+        let flag = pos.ins().load(I32, ir::MemFlags::trusted(), flag_addr, 0);
+        pos.ins().trapnz(flag, ir::TrapCode::Interrupt);
+    }
 }
 
 impl<'module_environment> TargetEnvironment for FuncEnvironment<'module_environment> {
@@ -1414,6 +1434,16 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         ))
     }
 
+    fn translate_function_entry(&mut self, pos: FuncCursor) -> WasmResult<()> {
+        self.translate_interrupt_check(pos);
+        Ok(())
+    }
+
+    fn translate_loop_header(&mut self, pos: FuncCursor) -> WasmResult<()> {
+        self.translate_interrupt_check(pos);
+        Ok(())
+    }
+
     fn get_global_type(&self, global_index: GlobalIndex) -> Option<WasmerType> {
         Some(self.module.globals.get(global_index)?.ty)
     }
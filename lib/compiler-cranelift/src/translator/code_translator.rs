@@ -104,10 +104,11 @@ use wasmer_types::{
     allow(clippy::unneeded_field_pattern, clippy::cognitive_complexity)
 )]
 /// Translates wasm operators into Cranelift IR instructions. Returns `true` if it inserted
-/// a return.
+/// a return. An `unreachable` traps with `unreachable_trap_code`.
 pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
     module_translation_state: &ModuleTranslationState,
     op: &Operator,
+    unreachable_trap_code: ir::TrapCode,
     builder: &mut FunctionBuilder,
     state: &mut FuncTranslationState,
     environ: &mut FE,
@@ -221,7 +222,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             // We do nothing
         }
         Operator::Unreachable => {
-            builder.ins().trap(ir::TrapCode::UnreachableCodeReached);
+            builder.ins().trap(unreachable_trap_code);
             state.reachable = false;
         }
         /***************************** Control flow blocks **********************************
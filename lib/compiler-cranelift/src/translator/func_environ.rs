@@ -440,6 +440,16 @@ pub trait FuncEnvironment: TargetEnvironment {
         count: ir::Value,
     ) -> WasmResult<ir::Value>;
 
+    /// Emit code at the beginning of every wasm function, after its locals
+    /// are declared.
+    ///
+    /// This can be used to insert explicit interrupt or safepoint checking on
+    /// function entry.
+    fn translate_function_entry(&mut self, _pos: FuncCursor) -> WasmResult<()> {
+        // By default, don't emit anything.
+        Ok(())
+    }
+
     /// Emit code at the beginning of every wasm loop.
     ///
     /// This can be used to insert explicit interrupt or safepoint checking at
@@ -10,7 +10,7 @@
 use super::code_translator::{bitcast_arguments, translate_operator, wasm_param_types};
 use super::func_environ::{FuncEnvironment, ReturnMode};
 use super::func_state::FuncTranslationState;
-use super::translation_utils::get_vmctx_value_label;
+use super::translation_utils::{get_vmctx_value_label, trapcode_to_irtrapcode};
 use cranelift_codegen::entity::EntityRef;
 use cranelift_codegen::ir::{self, Block, InstBuilder, ValueLabel};
 use cranelift_codegen::timing;
@@ -110,6 +110,7 @@ impl FuncTranslator {
         self.state.initialize(&builder.func.signature, exit_block);
 
         parse_local_decls(reader, &mut builder, num_params, environ)?;
+        environ.translate_function_entry(builder.cursor())?;
         parse_function_body(
             module_translation_state,
             reader,
@@ -234,7 +235,15 @@ fn parse_function_body<FE: FuncEnvironment + ?Sized>(
         builder.set_srcloc(cur_srcloc(reader));
         let op = reader.read_operator()?;
         environ.before_translate_operator(&op, builder, state)?;
-        translate_operator(module_translation_state, &op, builder, state, environ)?;
+        let unreachable_trap_code = trapcode_to_irtrapcode(reader.unreachable_trap_code());
+        translate_operator(
+            module_translation_state,
+            &op,
+            unreachable_trap_code,
+            builder,
+            state,
+            environ,
+        )?;
         environ.after_translate_operator(&op, builder, state)?;
     }
 
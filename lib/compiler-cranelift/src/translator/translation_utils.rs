@@ -9,7 +9,7 @@ use cranelift_codegen::isa::TargetFrontendConfig;
 use cranelift_frontend::FunctionBuilder;
 use wasmer_compiler::wasm_unsupported;
 use wasmer_compiler::wasmparser;
-use wasmer_types::{FunctionType, LibCall, RelocationKind, Type, WasmError, WasmResult};
+use wasmer_types::{FunctionType, LibCall, RelocationKind, TrapCode, Type, WasmError, WasmResult};
 
 /// Helper function translate a Function signature into Cranelift Ir
 pub fn signature_to_cranelift_ir(
@@ -75,6 +75,45 @@ pub fn irlibcall_to_libcall(libcall: ir::LibCall) -> LibCall {
     }
 }
 
+/// Transform a runtime TrapCode into a Cranelift TrapCode
+///
+/// The trap codes Cranelift doesn't know about are user trap codes,
+/// numbered after the runtime ones.
+pub fn trapcode_to_irtrapcode(trap_code: TrapCode) -> ir::TrapCode {
+    match trap_code {
+        TrapCode::StackOverflow => ir::TrapCode::StackOverflow,
+        TrapCode::HeapAccessOutOfBounds => ir::TrapCode::HeapOutOfBounds,
+        TrapCode::HeapMisaligned => ir::TrapCode::HeapMisaligned,
+        TrapCode::TableAccessOutOfBounds => ir::TrapCode::TableOutOfBounds,
+        TrapCode::IndirectCallToNull => ir::TrapCode::IndirectCallToNull,
+        TrapCode::BadSignature => ir::TrapCode::BadSignature,
+        TrapCode::IntegerOverflow => ir::TrapCode::IntegerOverflow,
+        TrapCode::IntegerDivisionByZero => ir::TrapCode::IntegerDivisionByZero,
+        TrapCode::BadConversionToInteger => ir::TrapCode::BadConversionToInteger,
+        TrapCode::UnreachableCodeReached => ir::TrapCode::UnreachableCodeReached,
+        TrapCode::Interrupt => ir::TrapCode::Interrupt,
+        TrapCode::OutOfBounds
+        | TrapCode::UnalignedAtomic
+        | TrapCode::OutOfGas
+        | TrapCode::Watchpoint => ir::TrapCode::User(trap_code as u16),
+    }
+}
+
+/// Transform a Cranelift user trap code into the runtime TrapCode it
+/// stands for
+pub fn irusertrapcode_to_trapcode(user_code: u16) -> TrapCode {
+    [
+        TrapCode::OutOfBounds,
+        TrapCode::UnalignedAtomic,
+        TrapCode::OutOfGas,
+        TrapCode::Watchpoint,
+    ]
+    .iter()
+    .copied()
+    .find(|&trap_code| trap_code as u16 == user_code)
+    .unwrap_or_else(|| unimplemented!("User trap code {} not supported", user_code))
+}
+
 /// Transform Cranelift Reloc to compiler Relocation
 pub fn irreloc_to_relocationkind(reloc: Reloc) -> RelocationKind {
     match reloc {
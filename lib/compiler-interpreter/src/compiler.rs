@@ -0,0 +1,68 @@
+//! Support for running modules with the interpreter.
+
+use crate::config::Interpreter;
+use crate::translator::translate_function;
+use std::sync::Arc;
+use wasmer_compiler::{Compiler, FunctionBodyData, ModuleMiddleware, ModuleTranslationState};
+use wasmer_types::entity::PrimaryMap;
+use wasmer_types::{
+    Compilation, CompileError, CompileModuleInfo, InterpretedFunction, LocalFunctionIndex, Target,
+};
+
+/// A compiler translating WebAssembly modules for the interpreter of
+/// the VM.
+pub struct InterpreterCompiler {
+    config: Interpreter,
+}
+
+impl InterpreterCompiler {
+    /// Creates a new interpreter compiler
+    pub fn new(config: Interpreter) -> Self {
+        Self { config }
+    }
+}
+
+impl Compiler for InterpreterCompiler {
+    /// Get the middlewares for this compiler
+    fn get_middlewares(&self) -> &[Arc<dyn ModuleMiddleware>] {
+        &self.config.middlewares
+    }
+
+    fn is_interpreter(&self) -> bool {
+        true
+    }
+
+    fn deterministic_id(&self) -> String {
+        format!("interpreter-{}", env!("CARGO_PKG_VERSION"))
+    }
+
+    /// The interpreter doesn't emit machine code: modules are translated
+    /// with [`Compiler::interpret_module`] instead.
+    fn compile_module(
+        &self,
+        _target: &Target,
+        _compile_info: &CompileModuleInfo,
+        _module_translation: &ModuleTranslationState,
+        _function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) -> Result<Compilation, CompileError> {
+        Err(CompileError::UnsupportedFeature(
+            "compiling modules to machine code with the interpreter".to_string(),
+        ))
+    }
+
+    /// Translate the functions of the module for the interpreter.
+    fn interpret_module(
+        &self,
+        _target: &Target,
+        compile_info: &CompileModuleInfo,
+        _module_translation: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) -> Result<PrimaryMap<LocalFunctionIndex, InterpretedFunction>, CompileError> {
+        function_body_inputs
+            .iter()
+            .map(|(index, input)| {
+                translate_function(&compile_info.module, &self.config.middlewares, index, input)
+            })
+            .collect()
+    }
+}
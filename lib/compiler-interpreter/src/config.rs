@@ -0,0 +1,56 @@
+use crate::compiler::InterpreterCompiler;
+use std::sync::Arc;
+use wasmer_compiler::{Compiler, CompilerConfig, Engine, EngineBuilder, ModuleMiddleware};
+use wasmer_types::{Features, Target};
+
+/// The configuration of the interpreter.
+#[derive(Debug, Clone)]
+pub struct Interpreter {
+    /// The middleware chain.
+    pub(crate) middlewares: Vec<Arc<dyn ModuleMiddleware>>,
+}
+
+impl Interpreter {
+    /// Creates a new configuration object with the default configuration
+    /// specified.
+    pub fn new() -> Self {
+        Self {
+            middlewares: vec![],
+        }
+    }
+}
+
+impl CompilerConfig for Interpreter {
+    fn enable_pic(&mut self) {
+        // Do nothing, since the interpreter doesn't emit any code.
+    }
+
+    /// Transform it into the compiler
+    fn compiler(self: Box<Self>) -> Box<dyn Compiler> {
+        Box::new(InterpreterCompiler::new(*self))
+    }
+
+    /// Gets the default features for this compiler in the given target
+    fn default_features_for_target(&self, _target: &Target) -> Features {
+        let mut features = Features::default();
+        features.simd(false);
+        features
+    }
+
+    /// Pushes a middleware onto the back of the middleware chain.
+    fn push_middleware(&mut self, middleware: Arc<dyn ModuleMiddleware>) {
+        self.middlewares.push(middleware);
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Self::new()
+    }
+}
+
+impl From<Interpreter> for Engine {
+    fn from(config: Interpreter) -> Self {
+        EngineBuilder::new(config).engine()
+    }
+}
@@ -0,0 +1,15 @@
+//! A WebAssembly `Compiler` implementation targeting the interpreter of
+//! the Wasmer VM.
+//!
+//! Instead of emitting machine code, it translates functions into the
+//! instructions of the interpreter, so WebAssembly can run on platforms
+//! where memory can't be made executable, like iOS or locked-down
+//! sandboxes. It is also a reference to test the other compilers
+//! against.
+
+mod compiler;
+mod config;
+mod translator;
+
+pub use crate::compiler::InterpreterCompiler;
+pub use crate::config::Interpreter;
@@ -0,0 +1,663 @@
+//! Translation of WebAssembly function bodies into the instructions of
+//! the interpreter.
+//!
+//! Structured control flow is lowered to branches in a single pass. The
+//! height of the operand stack is tracked along the way, so every branch
+//! knows how many values it keeps and drops; branches forward are patched
+//! once the end of their target is reached.
+
+use std::convert::TryFrom;
+use std::mem;
+use std::sync::Arc;
+use wasmer_compiler::wasmparser::{
+    MemoryImmediate, Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType,
+};
+use wasmer_compiler::{
+    from_binaryreadererror_wasmerror, wptype_to_type, FunctionBinaryReader, FunctionBodyData,
+    MiddlewareBinaryReader, ModuleMiddleware, ModuleMiddlewareChain,
+};
+use wasmer_types::{
+    BranchTarget, CompileError, DataIndex, ElemIndex, FunctionAddressMap, FunctionIndex,
+    GlobalIndex, Instruction, InstructionAddressMap, InterpretedFunction, LocalFunctionIndex,
+    MemArg, MemoryIndex, ModuleInfo, SignatureIndex, SourceLoc, TableIndex, TrapCode,
+};
+
+/// Translates the body of the local function `index` of `module`.
+pub(crate) fn translate_function(
+    module: &ModuleInfo,
+    middlewares: &[Arc<dyn ModuleMiddleware>],
+    index: LocalFunctionIndex,
+    input: &FunctionBodyData<'_>,
+) -> Result<InterpretedFunction, CompileError> {
+    let mut reader = MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
+    reader.set_middleware_chain(middlewares.generate_function_middleware_chain(index));
+
+    let ty = &module.signatures[module.functions[module.func_index(index)]];
+    let mut locals = vec![];
+    for _ in 0..reader.read_local_count()? {
+        let (count, ty) = reader.read_local_decl()?;
+        let ty = wptype_to_type(ty)?;
+        if locals.len() + count as usize > u32::MAX as usize {
+            return Err(CompileError::Codegen("too many locals".to_string()));
+        }
+        locals.resize(locals.len() + count as usize, ty);
+    }
+
+    let mut translator = FunctionTranslator {
+        module,
+        code: vec![],
+        branch_tables: vec![],
+        frames: vec![ControlFrame {
+            kind: FrameKind::Function,
+            height: 0,
+            params: 0,
+            results: ty.results().len() as u32,
+            fixups: vec![],
+        }],
+        height: 0,
+        unreachable_depth: 0,
+    };
+    let mut srclocs = vec![];
+    while !translator.frames.is_empty() {
+        let srcloc = SourceLoc::new(reader.original_position() as u32);
+        let op = reader.read_operator()?;
+        translator.translate(op, reader.unreachable_trap_code())?;
+        srclocs.resize(translator.code.len(), srcloc);
+    }
+    let end_srcloc = SourceLoc::new(reader.original_position() as u32);
+
+    Ok(InterpretedFunction {
+        params: ty.params().into(),
+        results: ty.results().into(),
+        locals: locals.into_boxed_slice(),
+        code: translator.code.into_boxed_slice(),
+        branch_tables: translator
+            .branch_tables
+            .into_iter()
+            .map(Vec::into_boxed_slice)
+            .collect(),
+        address_map: address_map(srclocs, input.module_offset, end_srcloc),
+    })
+}
+
+/// Maps the instructions translated from the operators at `srclocs` back
+/// to them, for the backtraces of traps.
+fn address_map(srclocs: Vec<SourceLoc>, start: usize, end_srcloc: SourceLoc) -> FunctionAddressMap {
+    let size = mem::size_of::<Instruction>();
+    FunctionAddressMap {
+        body_len: srclocs.len() * size,
+        instructions: srclocs
+            .into_iter()
+            .enumerate()
+            .map(|(index, srcloc)| InstructionAddressMap {
+                srcloc,
+                code_offset: index * size,
+                code_len: size,
+            })
+            .collect(),
+        start_srcloc: SourceLoc::new(start as u32),
+        end_srcloc,
+        body_offset: 0,
+    }
+}
+
+/// The kind of a control frame.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    /// The body of the function, whose end returns.
+    Function,
+    /// A `block`.
+    Block,
+    /// A `loop`, whose branches continue at the given instruction.
+    Loop(u32),
+    /// An `if` whose `else` hasn't been reached yet, skipped to by the
+    /// `BrIfEqz` at the given instruction.
+    If(usize),
+    /// The `else` of an `if`.
+    Else,
+}
+
+/// Where the instruction a branch continues at is written.
+enum Fixup {
+    /// In the branch at the given instruction.
+    Code(usize),
+    /// In the given entry of the given branch table.
+    Table(usize, usize),
+}
+
+/// A block, loop or if being translated.
+struct ControlFrame {
+    kind: FrameKind,
+    /// The height of the operand stack below the parameters of the frame.
+    height: u32,
+    params: u32,
+    results: u32,
+    /// The branches to the end of the frame.
+    fixups: Vec<Fixup>,
+}
+
+struct FunctionTranslator<'a> {
+    module: &'a ModuleInfo,
+    code: Vec<Instruction>,
+    branch_tables: Vec<Vec<BranchTarget>>,
+    frames: Vec<ControlFrame>,
+    /// The height of the operand stack, not counting the locals.
+    height: u32,
+    /// The number of frames opened in unreachable code, plus one, or zero
+    /// if the code is reachable.
+    unreachable_depth: u32,
+}
+
+impl<'a> FunctionTranslator<'a> {
+    /// Translates `op`, skipping it if it is unreachable. An `unreachable`
+    /// traps with `unreachable_trap_code`.
+    fn translate(
+        &mut self,
+        op: Operator<'_>,
+        unreachable_trap_code: TrapCode,
+    ) -> Result<(), CompileError> {
+        if self.unreachable_depth > 0 {
+            match op {
+                Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } => {
+                    self.unreachable_depth += 1;
+                }
+                Operator::Else if self.unreachable_depth == 1 => {
+                    self.unreachable_depth = 0;
+                    self.translate_else(false);
+                }
+                Operator::End => {
+                    self.unreachable_depth -= 1;
+                    if self.unreachable_depth == 0 {
+                        self.translate_end();
+                    }
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        let (instruction, pops, pushes) = match op {
+            Operator::Nop
+            | Operator::I32ReinterpretF32
+            | Operator::I64ReinterpretF64
+            | Operator::F32ReinterpretI32
+            | Operator::F64ReinterpretI64 => return Ok(()),
+
+            Operator::Unreachable => {
+                self.code
+                    .push(Instruction::Unreachable(unreachable_trap_code));
+                self.unreachable_depth = 1;
+                return Ok(());
+            }
+            Operator::Block { ty } => {
+                self.push_frame(FrameKind::Block, ty);
+                return Ok(());
+            }
+            Operator::Loop { ty } => {
+                self.push_frame(FrameKind::Loop(self.code.len() as u32), ty);
+                return Ok(());
+            }
+            Operator::If { ty } => {
+                self.height -= 1;
+                let at = self.code.len();
+                self.code.push(Instruction::BrIfEqz(0));
+                self.push_frame(FrameKind::If(at), ty);
+                return Ok(());
+            }
+            Operator::Else => {
+                self.translate_else(true);
+                return Ok(());
+            }
+            Operator::End => {
+                self.translate_end();
+                return Ok(());
+            }
+            Operator::Br { relative_depth } => {
+                self.emit_branch(relative_depth, Instruction::Br);
+                self.unreachable_depth = 1;
+                return Ok(());
+            }
+            Operator::BrIf { relative_depth } => {
+                self.height -= 1;
+                self.emit_branch(relative_depth, Instruction::BrIf);
+                return Ok(());
+            }
+            Operator::BrTable { ref table } => {
+                self.height -= 1;
+                let mut depths = table
+                    .targets()
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(from_binaryreadererror_wasmerror)?;
+                depths.push(table.default());
+                let index = self.branch_tables.len();
+                let mut targets = Vec::with_capacity(depths.len());
+                for (entry, depth) in depths.into_iter().enumerate() {
+                    let (target, frame) = self.branch_target(depth);
+                    self.add_fixup(frame, Fixup::Table(index, entry));
+                    targets.push(target);
+                }
+                self.branch_tables.push(targets);
+                self.code.push(Instruction::BrTable(index as u32));
+                self.unreachable_depth = 1;
+                return Ok(());
+            }
+            Operator::Return => {
+                self.code.push(Instruction::Return);
+                self.unreachable_depth = 1;
+                return Ok(());
+            }
+            Operator::Call { function_index } => {
+                let function = FunctionIndex::from_u32(function_index);
+                let ty = &self.module.signatures[self.module.functions[function]];
+                let (params, results) = (ty.params().len(), ty.results().len());
+                (Instruction::Call(function), params as u32, results as u32)
+            }
+            Operator::CallIndirect {
+                index, table_index, ..
+            } => {
+                let signature = SignatureIndex::from_u32(index);
+                let ty = &self.module.signatures[signature];
+                let (params, results) = (ty.params().len(), ty.results().len());
+                (
+                    Instruction::CallIndirect {
+                        signature,
+                        table: TableIndex::from_u32(table_index),
+                    },
+                    params as u32 + 1,
+                    results as u32,
+                )
+            }
+
+            Operator::Drop => (Instruction::Drop, 1, 0),
+            Operator::Select | Operator::TypedSelect { .. } => (Instruction::Select, 3, 1),
+            Operator::LocalGet { local_index } => (Instruction::LocalGet(local_index), 0, 1),
+            Operator::LocalSet { local_index } => (Instruction::LocalSet(local_index), 1, 0),
+            Operator::LocalTee { local_index } => (Instruction::LocalTee(local_index), 1, 1),
+            Operator::GlobalGet { global_index } => {
+                let global = GlobalIndex::from_u32(global_index);
+                let ty = self.module.globals[global].ty;
+                (Instruction::GlobalGet { global, ty }, 0, 1)
+            }
+            Operator::GlobalSet { global_index } => {
+                let global = GlobalIndex::from_u32(global_index);
+                let ty = self.module.globals[global].ty;
+                (Instruction::GlobalSet { global, ty }, 1, 0)
+            }
+
+            Operator::I32Load { memarg } => (Instruction::I32Load(mem_arg(memarg)?), 1, 1),
+            Operator::I64Load { memarg } => (Instruction::I64Load(mem_arg(memarg)?), 1, 1),
+            Operator::F32Load { memarg } => (Instruction::F32Load(mem_arg(memarg)?), 1, 1),
+            Operator::F64Load { memarg } => (Instruction::F64Load(mem_arg(memarg)?), 1, 1),
+            Operator::I32Load8S { memarg } => (Instruction::I32Load8S(mem_arg(memarg)?), 1, 1),
+            Operator::I32Load8U { memarg } => (Instruction::I32Load8U(mem_arg(memarg)?), 1, 1),
+            Operator::I32Load16S { memarg } => (Instruction::I32Load16S(mem_arg(memarg)?), 1, 1),
+            Operator::I32Load16U { memarg } => (Instruction::I32Load16U(mem_arg(memarg)?), 1, 1),
+            Operator::I64Load8S { memarg } => (Instruction::I64Load8S(mem_arg(memarg)?), 1, 1),
+            Operator::I64Load8U { memarg } => (Instruction::I64Load8U(mem_arg(memarg)?), 1, 1),
+            Operator::I64Load16S { memarg } => (Instruction::I64Load16S(mem_arg(memarg)?), 1, 1),
+            Operator::I64Load16U { memarg } => (Instruction::I64Load16U(mem_arg(memarg)?), 1, 1),
+            Operator::I64Load32S { memarg } => (Instruction::I64Load32S(mem_arg(memarg)?), 1, 1),
+            Operator::I64Load32U { memarg } => (Instruction::I64Load32U(mem_arg(memarg)?), 1, 1),
+            Operator::I32Store { memarg } => (Instruction::I32Store(mem_arg(memarg)?), 2, 0),
+            Operator::I64Store { memarg } => (Instruction::I64Store(mem_arg(memarg)?), 2, 0),
+            Operator::F32Store { memarg } => (Instruction::F32Store(mem_arg(memarg)?), 2, 0),
+            Operator::F64Store { memarg } => (Instruction::F64Store(mem_arg(memarg)?), 2, 0),
+            Operator::I32Store8 { memarg } => (Instruction::I32Store8(mem_arg(memarg)?), 2, 0),
+            Operator::I32Store16 { memarg } => (Instruction::I32Store16(mem_arg(memarg)?), 2, 0),
+            Operator::I64Store8 { memarg } => (Instruction::I64Store8(mem_arg(memarg)?), 2, 0),
+            Operator::I64Store16 { memarg } => (Instruction::I64Store16(mem_arg(memarg)?), 2, 0),
+            Operator::I64Store32 { memarg } => (Instruction::I64Store32(mem_arg(memarg)?), 2, 0),
+
+            Operator::MemorySize { mem, .. } => {
+                (Instruction::MemorySize(MemoryIndex::from_u32(mem)), 0, 1)
+            }
+            Operator::MemoryGrow { mem, .. } => {
+                (Instruction::MemoryGrow(MemoryIndex::from_u32(mem)), 1, 1)
+            }
+            Operator::MemoryInit { segment, mem } => (
+                Instruction::MemoryInit {
+                    memory: MemoryIndex::from_u32(mem),
+                    data: DataIndex::from_u32(segment),
+                },
+                3,
+                0,
+            ),
+            Operator::DataDrop { segment } => {
+                (Instruction::DataDrop(DataIndex::from_u32(segment)), 0, 0)
+            }
+            Operator::MemoryCopy { src, dst } => {
+                if src != dst {
+                    return Err(CompileError::UnsupportedFeature(
+                        "copies between memories in the interpreter".to_string(),
+                    ));
+                }
+                (Instruction::MemoryCopy(MemoryIndex::from_u32(dst)), 3, 0)
+            }
+            Operator::MemoryFill { mem } => {
+                (Instruction::MemoryFill(MemoryIndex::from_u32(mem)), 3, 0)
+            }
+
+            Operator::TableInit { segment, table } => (
+                Instruction::TableInit {
+                    table: TableIndex::from_u32(table),
+                    elem: ElemIndex::from_u32(segment),
+                },
+                3,
+                0,
+            ),
+            Operator::ElemDrop { segment } => {
+                (Instruction::ElemDrop(ElemIndex::from_u32(segment)), 0, 0)
+            }
+            Operator::TableCopy {
+                dst_table,
+                src_table,
+            } => (
+                Instruction::TableCopy {
+                    dst: TableIndex::from_u32(dst_table),
+                    src: TableIndex::from_u32(src_table),
+                },
+                3,
+                0,
+            ),
+            Operator::TableGet { table } => {
+                (Instruction::TableGet(TableIndex::from_u32(table)), 1, 1)
+            }
+            Operator::TableSet { table } => {
+                (Instruction::TableSet(TableIndex::from_u32(table)), 2, 0)
+            }
+            Operator::TableGrow { table } => {
+                (Instruction::TableGrow(TableIndex::from_u32(table)), 2, 1)
+            }
+            Operator::TableSize { table } => {
+                (Instruction::TableSize(TableIndex::from_u32(table)), 0, 1)
+            }
+            Operator::TableFill { table } => {
+                (Instruction::TableFill(TableIndex::from_u32(table)), 3, 0)
+            }
+            Operator::RefNull { .. } => (Instruction::RefNull, 0, 1),
+            Operator::RefIsNull => (Instruction::RefIsNull, 1, 1),
+            Operator::RefFunc { function_index } => (
+                Instruction::RefFunc(FunctionIndex::from_u32(function_index)),
+                0,
+                1,
+            ),
+
+            Operator::I32Const { value } => (Instruction::I32Const(value), 0, 1),
+            Operator::I64Const { value } => (Instruction::I64Const(value), 0, 1),
+            Operator::F32Const { value } => (Instruction::F32Const(value.bits()), 0, 1),
+            Operator::F64Const { value } => (Instruction::F64Const(value.bits()), 0, 1),
+
+            Operator::I32Eqz => (Instruction::I32Eqz, 1, 1),
+            Operator::I32Eq => (Instruction::I32Eq, 2, 1),
+            Operator::I32Ne => (Instruction::I32Ne, 2, 1),
+            Operator::I32LtS => (Instruction::I32LtS, 2, 1),
+            Operator::I32LtU => (Instruction::I32LtU, 2, 1),
+            Operator::I32GtS => (Instruction::I32GtS, 2, 1),
+            Operator::I32GtU => (Instruction::I32GtU, 2, 1),
+            Operator::I32LeS => (Instruction::I32LeS, 2, 1),
+            Operator::I32LeU => (Instruction::I32LeU, 2, 1),
+            Operator::I32GeS => (Instruction::I32GeS, 2, 1),
+            Operator::I32GeU => (Instruction::I32GeU, 2, 1),
+            Operator::I64Eqz => (Instruction::I64Eqz, 1, 1),
+            Operator::I64Eq => (Instruction::I64Eq, 2, 1),
+            Operator::I64Ne => (Instruction::I64Ne, 2, 1),
+            Operator::I64LtS => (Instruction::I64LtS, 2, 1),
+            Operator::I64LtU => (Instruction::I64LtU, 2, 1),
+            Operator::I64GtS => (Instruction::I64GtS, 2, 1),
+            Operator::I64GtU => (Instruction::I64GtU, 2, 1),
+            Operator::I64LeS => (Instruction::I64LeS, 2, 1),
+            Operator::I64LeU => (Instruction::I64LeU, 2, 1),
+            Operator::I64GeS => (Instruction::I64GeS, 2, 1),
+            Operator::I64GeU => (Instruction::I64GeU, 2, 1),
+            Operator::F32Eq => (Instruction::F32Eq, 2, 1),
+            Operator::F32Ne => (Instruction::F32Ne, 2, 1),
+            Operator::F32Lt => (Instruction::F32Lt, 2, 1),
+            Operator::F32Gt => (Instruction::F32Gt, 2, 1),
+            Operator::F32Le => (Instruction::F32Le, 2, 1),
+            Operator::F32Ge => (Instruction::F32Ge, 2, 1),
+            Operator::F64Eq => (Instruction::F64Eq, 2, 1),
+            Operator::F64Ne => (Instruction::F64Ne, 2, 1),
+            Operator::F64Lt => (Instruction::F64Lt, 2, 1),
+            Operator::F64Gt => (Instruction::F64Gt, 2, 1),
+            Operator::F64Le => (Instruction::F64Le, 2, 1),
+            Operator::F64Ge => (Instruction::F64Ge, 2, 1),
+
+            Operator::I32Clz => (Instruction::I32Clz, 1, 1),
+            Operator::I32Ctz => (Instruction::I32Ctz, 1, 1),
+            Operator::I32Popcnt => (Instruction::I32Popcnt, 1, 1),
+            Operator::I32Add => (Instruction::I32Add, 2, 1),
+            Operator::I32Sub => (Instruction::I32Sub, 2, 1),
+            Operator::I32Mul => (Instruction::I32Mul, 2, 1),
+            Operator::I32DivS => (Instruction::I32DivS, 2, 1),
+            Operator::I32DivU => (Instruction::I32DivU, 2, 1),
+            Operator::I32RemS => (Instruction::I32RemS, 2, 1),
+            Operator::I32RemU => (Instruction::I32RemU, 2, 1),
+            Operator::I32And => (Instruction::I32And, 2, 1),
+            Operator::I32Or => (Instruction::I32Or, 2, 1),
+            Operator::I32Xor => (Instruction::I32Xor, 2, 1),
+            Operator::I32Shl => (Instruction::I32Shl, 2, 1),
+            Operator::I32ShrS => (Instruction::I32ShrS, 2, 1),
+            Operator::I32ShrU => (Instruction::I32ShrU, 2, 1),
+            Operator::I32Rotl => (Instruction::I32Rotl, 2, 1),
+            Operator::I32Rotr => (Instruction::I32Rotr, 2, 1),
+            Operator::I64Clz => (Instruction::I64Clz, 1, 1),
+            Operator::I64Ctz => (Instruction::I64Ctz, 1, 1),
+            Operator::I64Popcnt => (Instruction::I64Popcnt, 1, 1),
+            Operator::I64Add => (Instruction::I64Add, 2, 1),
+            Operator::I64Sub => (Instruction::I64Sub, 2, 1),
+            Operator::I64Mul => (Instruction::I64Mul, 2, 1),
+            Operator::I64DivS => (Instruction::I64DivS, 2, 1),
+            Operator::I64DivU => (Instruction::I64DivU, 2, 1),
+            Operator::I64RemS => (Instruction::I64RemS, 2, 1),
+            Operator::I64RemU => (Instruction::I64RemU, 2, 1),
+            Operator::I64And => (Instruction::I64And, 2, 1),
+            Operator::I64Or => (Instruction::I64Or, 2, 1),
+            Operator::I64Xor => (Instruction::I64Xor, 2, 1),
+            Operator::I64Shl => (Instruction::I64Shl, 2, 1),
+            Operator::I64ShrS => (Instruction::I64ShrS, 2, 1),
+            Operator::I64ShrU => (Instruction::I64ShrU, 2, 1),
+            Operator::I64Rotl => (Instruction::I64Rotl, 2, 1),
+            Operator::I64Rotr => (Instruction::I64Rotr, 2, 1),
+
+            Operator::F32Abs => (Instruction::F32Abs, 1, 1),
+            Operator::F32Neg => (Instruction::F32Neg, 1, 1),
+            Operator::F32Ceil => (Instruction::F32Ceil, 1, 1),
+            Operator::F32Floor => (Instruction::F32Floor, 1, 1),
+            Operator::F32Trunc => (Instruction::F32Trunc, 1, 1),
+            Operator::F32Nearest => (Instruction::F32Nearest, 1, 1),
+            Operator::F32Sqrt => (Instruction::F32Sqrt, 1, 1),
+            Operator::F32Add => (Instruction::F32Add, 2, 1),
+            Operator::F32Sub => (Instruction::F32Sub, 2, 1),
+            Operator::F32Mul => (Instruction::F32Mul, 2, 1),
+            Operator::F32Div => (Instruction::F32Div, 2, 1),
+            Operator::F32Min => (Instruction::F32Min, 2, 1),
+            Operator::F32Max => (Instruction::F32Max, 2, 1),
+            Operator::F32Copysign => (Instruction::F32Copysign, 2, 1),
+            Operator::F64Abs => (Instruction::F64Abs, 1, 1),
+            Operator::F64Neg => (Instruction::F64Neg, 1, 1),
+            Operator::F64Ceil => (Instruction::F64Ceil, 1, 1),
+            Operator::F64Floor => (Instruction::F64Floor, 1, 1),
+            Operator::F64Trunc => (Instruction::F64Trunc, 1, 1),
+            Operator::F64Nearest => (Instruction::F64Nearest, 1, 1),
+            Operator::F64Sqrt => (Instruction::F64Sqrt, 1, 1),
+            Operator::F64Add => (Instruction::F64Add, 2, 1),
+            Operator::F64Sub => (Instruction::F64Sub, 2, 1),
+            Operator::F64Mul => (Instruction::F64Mul, 2, 1),
+            Operator::F64Div => (Instruction::F64Div, 2, 1),
+            Operator::F64Min => (Instruction::F64Min, 2, 1),
+            Operator::F64Max => (Instruction::F64Max, 2, 1),
+            Operator::F64Copysign => (Instruction::F64Copysign, 2, 1),
+
+            Operator::I32WrapI64 => (Instruction::I32WrapI64, 1, 1),
+            Operator::I32TruncF32S => (Instruction::I32TruncF32S, 1, 1),
+            Operator::I32TruncF32U => (Instruction::I32TruncF32U, 1, 1),
+            Operator::I32TruncF64S => (Instruction::I32TruncF64S, 1, 1),
+            Operator::I32TruncF64U => (Instruction::I32TruncF64U, 1, 1),
+            Operator::I64ExtendI32S => (Instruction::I64ExtendI32S, 1, 1),
+            Operator::I64ExtendI32U => (Instruction::I64ExtendI32U, 1, 1),
+            Operator::I64TruncF32S => (Instruction::I64TruncF32S, 1, 1),
+            Operator::I64TruncF32U => (Instruction::I64TruncF32U, 1, 1),
+            Operator::I64TruncF64S => (Instruction::I64TruncF64S, 1, 1),
+            Operator::I64TruncF64U => (Instruction::I64TruncF64U, 1, 1),
+            Operator::F32ConvertI32S => (Instruction::F32ConvertI32S, 1, 1),
+            Operator::F32ConvertI32U => (Instruction::F32ConvertI32U, 1, 1),
+            Operator::F32ConvertI64S => (Instruction::F32ConvertI64S, 1, 1),
+            Operator::F32ConvertI64U => (Instruction::F32ConvertI64U, 1, 1),
+            Operator::F32DemoteF64 => (Instruction::F32DemoteF64, 1, 1),
+            Operator::F64ConvertI32S => (Instruction::F64ConvertI32S, 1, 1),
+            Operator::F64ConvertI32U => (Instruction::F64ConvertI32U, 1, 1),
+            Operator::F64ConvertI64S => (Instruction::F64ConvertI64S, 1, 1),
+            Operator::F64ConvertI64U => (Instruction::F64ConvertI64U, 1, 1),
+            Operator::F64PromoteF32 => (Instruction::F64PromoteF32, 1, 1),
+            Operator::I32Extend8S => (Instruction::I32Extend8S, 1, 1),
+            Operator::I32Extend16S => (Instruction::I32Extend16S, 1, 1),
+            Operator::I64Extend8S => (Instruction::I64Extend8S, 1, 1),
+            Operator::I64Extend16S => (Instruction::I64Extend16S, 1, 1),
+            Operator::I64Extend32S => (Instruction::I64Extend32S, 1, 1),
+            Operator::I32TruncSatF32S => (Instruction::I32TruncSatF32S, 1, 1),
+            Operator::I32TruncSatF32U => (Instruction::I32TruncSatF32U, 1, 1),
+            Operator::I32TruncSatF64S => (Instruction::I32TruncSatF64S, 1, 1),
+            Operator::I32TruncSatF64U => (Instruction::I32TruncSatF64U, 1, 1),
+            Operator::I64TruncSatF32S => (Instruction::I64TruncSatF32S, 1, 1),
+            Operator::I64TruncSatF32U => (Instruction::I64TruncSatF32U, 1, 1),
+            Operator::I64TruncSatF64S => (Instruction::I64TruncSatF64S, 1, 1),
+            Operator::I64TruncSatF64U => (Instruction::I64TruncSatF64U, 1, 1),
+
+            op => {
+                return Err(CompileError::UnsupportedFeature(format!(
+                    "{:?} in the interpreter",
+                    op
+                )))
+            }
+        };
+        self.code.push(instruction);
+        self.height = self.height - pops + pushes;
+        Ok(())
+    }
+
+    /// Opens a frame of the given kind and block type.
+    fn push_frame(&mut self, kind: FrameKind, ty: WpTypeOrFuncType) {
+        let (params, results) = match ty {
+            WpTypeOrFuncType::Type(WpType::EmptyBlockType) => (0, 0),
+            WpTypeOrFuncType::Type(_) => (0, 1),
+            WpTypeOrFuncType::FuncType(index) => {
+                let ty = &self.module.signatures[SignatureIndex::from_u32(index)];
+                (ty.params().len() as u32, ty.results().len() as u32)
+            }
+        };
+        self.frames.push(ControlFrame {
+            kind,
+            height: self.height - params,
+            params,
+            results,
+            fixups: vec![],
+        });
+    }
+
+    /// Starts the `else` branch of the `if` frame on top, whose `then`
+    /// branch branches to its end if its own end is `reachable`.
+    fn translate_else(&mut self, reachable: bool) {
+        let frame = self.frames.last_mut().unwrap();
+        if reachable {
+            frame.fixups.push(Fixup::Code(self.code.len()));
+            self.code.push(Instruction::Br(BranchTarget {
+                pc: 0,
+                drop: 0,
+                keep: frame.results,
+            }));
+        }
+        if let FrameKind::If(at) = frame.kind {
+            let pc = self.code.len() as u32;
+            set_pc(
+                &mut self.code,
+                &mut self.branch_tables,
+                &Fixup::Code(at),
+                pc,
+            );
+        }
+        frame.kind = FrameKind::Else;
+        self.height = frame.height + frame.params;
+    }
+
+    /// Closes the frame on top, patching the branches to its end.
+    fn translate_end(&mut self) {
+        let frame = self.frames.pop().unwrap();
+        let end = self.code.len() as u32;
+        if let FrameKind::If(at) = frame.kind {
+            set_pc(
+                &mut self.code,
+                &mut self.branch_tables,
+                &Fixup::Code(at),
+                end,
+            );
+        }
+        for fixup in &frame.fixups {
+            set_pc(&mut self.code, &mut self.branch_tables, fixup, end);
+        }
+        self.height = frame.height + frame.results;
+        if frame.kind == FrameKind::Function {
+            self.code.push(Instruction::Return);
+        }
+    }
+
+    /// Returns the target of a branch to the frame at `relative_depth`,
+    /// along with the index of the frame.
+    fn branch_target(&self, relative_depth: u32) -> (BranchTarget, usize) {
+        let index = self.frames.len() - 1 - relative_depth as usize;
+        let frame = &self.frames[index];
+        let (pc, keep) = match frame.kind {
+            FrameKind::Loop(start) => (start, frame.params),
+            _ => (0, frame.results),
+        };
+        let target = BranchTarget {
+            pc,
+            drop: self.height - frame.height - keep,
+            keep,
+        };
+        (target, index)
+    }
+
+    /// Records where the branch to the end of the frame at `index` is
+    /// written, unless it is a loop, whose branches go backwards.
+    fn add_fixup(&mut self, index: usize, fixup: Fixup) {
+        let frame = &mut self.frames[index];
+        if !matches!(frame.kind, FrameKind::Loop(_)) {
+            frame.fixups.push(fixup);
+        }
+    }
+
+    /// Emits a branch to the frame at `relative_depth`.
+    fn emit_branch(&mut self, relative_depth: u32, branch: fn(BranchTarget) -> Instruction) {
+        let (target, frame) = self.branch_target(relative_depth);
+        self.add_fixup(frame, Fixup::Code(self.code.len()));
+        self.code.push(branch(target));
+    }
+}
+
+/// Makes the branch written at `fixup` continue at `pc`.
+fn set_pc(
+    code: &mut [Instruction],
+    branch_tables: &mut [Vec<BranchTarget>],
+    fixup: &Fixup,
+    pc: u32,
+) {
+    match *fixup {
+        Fixup::Code(at) => match &mut code[at] {
+            Instruction::Br(target) | Instruction::BrIf(target) => target.pc = pc,
+            Instruction::BrIfEqz(target) => *target = pc,
+            _ => unreachable!(),
+        },
+        Fixup::Table(table, entry) => branch_tables[table][entry].pc = pc,
+    }
+}
+
+/// Converts the immediate of a memory access.
+fn mem_arg(memarg: MemoryImmediate) -> Result<MemArg, CompileError> {
+    let offset = u32::try_from(memarg.offset).map_err(|_| {
+        CompileError::UnsupportedFeature("64-bit memory offsets in the interpreter".to_string())
+    })?;
+    Ok(MemArg {
+        memory: MemoryIndex::from_u32(memarg.memory),
+        offset,
+    })
+}
@@ -10,6 +10,7 @@ use inkwell::{
     attributes::{Attribute, AttributeLoc},
     builder::Builder,
     context::Context,
+    module::Module,
     targets::TargetMachine,
     types::FunctionType,
     values::{BasicValueEnum, CallSiteValue, FunctionValue, PointerValue},
@@ -19,19 +20,20 @@ use wasmer_types::FunctionType as FuncSig;
 use wasmer_vm::VMOffsets;
 
 mod aarch64_systemv;
+mod riscv_systemv;
 mod x86_64_systemv;
 
 use aarch64_systemv::Aarch64SystemV;
+use riscv_systemv::RiscvSystemV;
 use x86_64_systemv::X86_64SystemV;
 
 pub fn get_abi(target_machine: &TargetMachine) -> Box<dyn Abi> {
-    if target_machine
-        .get_triple()
-        .as_str()
-        .to_string_lossy()
-        .starts_with("aarch64")
-    {
+    let triple = target_machine.get_triple();
+    let triple = triple.as_str().to_string_lossy();
+    if triple.starts_with("aarch64") {
         Box::new(Aarch64SystemV {})
+    } else if triple.starts_with("riscv64") {
+        Box::new(RiscvSystemV {})
     } else {
         Box::new(X86_64SystemV {})
     }
@@ -84,4 +86,8 @@ pub trait Abi {
         values: &[BasicValueEnum<'ctx>],
         func_type: &FunctionType<'ctx>,
     ) -> Result<BasicValueEnum<'ctx>, CompileError>;
+
+    /// Sets the module flags selecting this ABI, for targets where the
+    /// target machine alone doesn't determine it.
+    fn set_module_flags<'ctx>(&self, _context: &'ctx Context, _module: &Module<'ctx>) {}
 }
@@ -192,6 +192,20 @@ impl Compiler for LLVMCompiler {
         &self.config.middlewares
     }
 
+    fn deterministic_id(&self) -> String {
+        format!(
+            "llvm-{}-{:?}{}{}",
+            env!("CARGO_PKG_VERSION"),
+            self.config.opt_level,
+            if self.config.enable_nan_canonicalization {
+                "-nan"
+            } else {
+                ""
+            },
+            if self.config.is_pic { "-pic" } else { "" },
+        )
+    }
+
     fn experimental_native_compile_module<'data, 'module>(
         &self,
         target: &Target,
@@ -243,6 +257,12 @@ impl Compiler for LLVMCompiler {
                     FuncTranslator::new(target_machine)
                 },
                 |func_translator, (i, input)| {
+                    let _span = tracing::trace_span!(
+                        "compile_function",
+                        index = i.as_u32(),
+                        bytes = input.data.len()
+                    )
+                    .entered();
                     // TODO: remove (to serialize)
                     //let _data = data.lock().unwrap();
                     func_translator.translate(
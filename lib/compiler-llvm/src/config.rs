@@ -7,7 +7,7 @@ pub use inkwell::OptimizationLevel as LLVMOptLevel;
 use itertools::Itertools;
 use std::fmt::Debug;
 use std::sync::Arc;
-use target_lexicon::Architecture;
+use target_lexicon::{Architecture, Riscv64Architecture};
 use wasmer_compiler::{Compiler, CompilerConfig, Engine, EngineBuilder, ModuleMiddleware};
 use wasmer_types::{FunctionType, LocalFunctionIndex, Target, Triple};
 
@@ -42,7 +42,7 @@ pub struct LLVM {
     pub(crate) enable_nan_canonicalization: bool,
     pub(crate) enable_verifier: bool,
     pub(crate) opt_level: LLVMOptLevel,
-    is_pic: bool,
+    pub(crate) is_pic: bool,
     pub(crate) callbacks: Option<Arc<dyn LLVMCallbacks>>,
     /// The middleware chain.
     pub(crate) middlewares: Vec<Arc<dyn ModuleMiddleware>>,
@@ -83,7 +83,7 @@ impl LLVM {
         }
     }
 
-    fn code_model(&self) -> CodeModel {
+    fn code_model(&self, architecture: Architecture) -> CodeModel {
         // We normally use the large code model, but when targeting shared
         // objects, we are required to use PIC. If we use PIC anyways, we lose
         // any benefit from large code model and there's some cost on all
@@ -91,6 +91,11 @@ impl LLVM {
         // at all.
         if self.is_pic {
             CodeModel::Small
+        } else if let Architecture::Riscv64(_) = architecture {
+            // The RISC-V backend has no large code model. Calls to libcalls
+            // go through trampolines, which keeps everything the code
+            // references within the +/-2GiB of the medium one.
+            CodeModel::Medium
         } else {
             CodeModel::Large
         }
@@ -122,8 +127,14 @@ impl LLVM {
         } else {
             target_lexicon::BinaryFormat::Elf
         };
+        // LLVM doesn't know about the RISC-V extensions in the architecture
+        // name, they are enabled as CPU features instead.
+        let architecture = match target.triple().architecture {
+            Architecture::Riscv64(_) => Architecture::Riscv64(Riscv64Architecture::Riscv64),
+            architecture => architecture,
+        };
         let triple = Triple {
-            architecture: target.triple().architecture,
+            architecture,
             vendor: target.triple().vendor.clone(),
             operating_system,
             environment: target.triple().environment,
@@ -156,6 +167,14 @@ impl LLVM {
                 info: true,
                 machine_code: true,
             }),
+            Architecture::Riscv64(_) => InkwellTarget::initialize_riscv(&InitializationConfig {
+                asm_parser: true,
+                asm_printer: true,
+                base: true,
+                disassembler: true,
+                info: true,
+                machine_code: true,
+            }),
             // Architecture::Arm(_) => InkwellTarget::initialize_arm(&InitializationConfig {
             //     asm_parser: true,
             //     asm_printer: true,
@@ -174,17 +193,23 @@ impl LLVM {
             .iter()
             .map(|feature| format!("+{}", feature.to_string()))
             .join(",");
+        // LLVM only enables the base integer instructions of RISC-V by
+        // default, while the `riscv64gc` targets have the `imafdc` ones.
+        let (llvm_cpu, llvm_cpu_features) = match triple.architecture {
+            Architecture::Riscv64(_) => ("generic-rv64", "+m,+a,+c,+d,+f".to_string()),
+            _ => ("generic", llvm_cpu_features),
+        };
 
         let target_triple = self.target_triple(target);
         let llvm_target = InkwellTarget::from_triple(&target_triple).unwrap();
         llvm_target
             .create_target_machine(
                 &target_triple,
-                "generic",
+                llvm_cpu,
                 &llvm_cpu_features,
                 self.opt_level,
                 self.reloc_mode(),
-                self.code_model(),
+                self.code_model(triple.architecture),
             )
             .unwrap()
     }
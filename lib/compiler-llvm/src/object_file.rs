@@ -192,6 +192,26 @@ where
                     object::RelocationKind::Elf(object::elf::R_AARCH64_MOVW_UABS_G3),
                     0,
                 ) => RelocationKind::Arm64Movw3,
+                (
+                    object::Architecture::Riscv64,
+                    object::RelocationKind::Elf(object::elf::R_RISCV_CALL),
+                    0,
+                )
+                | (
+                    object::Architecture::Riscv64,
+                    object::RelocationKind::Elf(object::elf::R_RISCV_CALL_PLT),
+                    0,
+                ) => RelocationKind::RiscvCall,
+                (
+                    object::Architecture::Riscv64,
+                    object::RelocationKind::Elf(object::elf::R_RISCV_PCREL_HI20),
+                    0,
+                ) => RelocationKind::RiscvPCRelHi20,
+                (
+                    object::Architecture::Riscv64,
+                    object::RelocationKind::Elf(object::elf::R_RISCV_PCREL_LO12_I),
+                    0,
+                ) => RelocationKind::RiscvPCRelLo12I,
                 _ => {
                     return Err(CompileError::Codegen(format!(
                         "unknown relocation {:?}",
@@ -199,12 +219,24 @@ where
                     )));
                 }
             };
-            let addend = reloc.addend();
+            let mut addend = reloc.addend();
             let target = match reloc.target() {
                 object::read::RelocationTarget::Symbol(index) => {
                     let symbol = elf.symbol_by_index(index).map_err(map_object_err)?;
                     let symbol_name = symbol.name().map_err(map_object_err)?;
-                    if symbol.kind() == object::SymbolKind::Section {
+                    if kind == RelocationKind::RiscvPCRelLo12I {
+                        // The symbol labels the `auipc` computing the high
+                        // part of the address, in this same section. Keep
+                        // its offset, the target is resolved below.
+                        if symbol.section() != object::SymbolSection::Section(section_index) {
+                            return Err(CompileError::Codegen(format!(
+                                "relocation doesn't target a label of its section {:?}",
+                                reloc
+                            )));
+                        }
+                        addend = symbol.address() as i64;
+                        root_section_reloc_target
+                    } else if symbol.kind() == object::SymbolKind::Section {
                         match symbol.section() {
                             object::SymbolSection::Section(section_index) => {
                                 if section_index == root_section_index {
@@ -282,6 +314,33 @@ where
                     addend,
                 });
         }
+
+        // Make the low parts of the PC-relative addresses target the same
+        // address as their high parts. Adjusting the addend by the distance
+        // to the `auipc` keeps them relative to the `auipc`, as RISC-V
+        // requires, while linking them like any other relocation.
+        if let Some(section_relocations) = relocations.get_mut(&section_index) {
+            let high_parts = section_relocations
+                .iter()
+                .filter(|r| r.kind == RelocationKind::RiscvPCRelHi20)
+                .map(|r| (r.offset, (r.reloc_target, r.addend)))
+                .collect::<HashMap<_, _>>();
+            for r in section_relocations
+                .iter_mut()
+                .filter(|r| r.kind == RelocationKind::RiscvPCRelLo12I)
+            {
+                let high_part_offset = r.addend as u32;
+                let (reloc_target, addend) =
+                    *high_parts.get(&high_part_offset).ok_or_else(|| {
+                        CompileError::Codegen(format!(
+                            "no high part for the PC-relative relocation {:?}",
+                            r
+                        ))
+                    })?;
+                r.reloc_target = reloc_target;
+                r.addend = addend + r.offset as i64 - high_part_offset as i64;
+            }
+        }
     }
 
     let eh_frame_section_indices = eh_frame_section_indices
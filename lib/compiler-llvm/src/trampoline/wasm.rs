@@ -52,6 +52,7 @@ impl FuncTrampoline {
         let target_data = target_machine.get_target_data();
         module.set_triple(&target_triple);
         module.set_data_layout(&target_data.get_data_layout());
+        self.abi.set_module_flags(&self.ctx, &module);
         let intrinsics = Intrinsics::declare(&module, &self.ctx, &target_data);
 
         let (callee_ty, callee_attrs) =
@@ -178,6 +179,7 @@ impl FuncTrampoline {
         let target_triple = target_machine.get_triple();
         module.set_triple(&target_triple);
         module.set_data_layout(&target_data.get_data_layout());
+        self.abi.set_module_flags(&self.ctx, &module);
         let intrinsics = Intrinsics::declare(&module, &self.ctx, &target_data);
 
         let (trampoline_ty, trampoline_attrs) =
@@ -33,7 +33,8 @@ use wasmer_compiler::{
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::{
     CompileError, FunctionIndex, FunctionType, GlobalIndex, LocalFunctionIndex, MemoryIndex,
-    ModuleInfo, RelocationTarget, SignatureIndex, Symbol, SymbolRegistry, TableIndex, Type,
+    ModuleInfo, RelocationTarget, SignatureIndex, Symbol, SymbolRegistry, TableIndex, TrapCode,
+    Type,
 };
 use wasmer_vm::{MemoryStyle, TableStyle, VMOffsets};
 
@@ -87,6 +88,7 @@ impl FuncTranslator {
         let target_data = target_machine.get_target_data();
         module.set_triple(&target_triple);
         module.set_data_layout(&target_data.get_data_layout());
+        self.abi.set_module_flags(&self.ctx, &module);
         let wasm_fn_type = wasm_module
             .signatures
             .get(wasm_module.functions[func_index])
@@ -212,11 +214,12 @@ impl FuncTranslator {
             fcg.ctx.basic(),
             &func_attrs,
         );
+        fcg.trap_if_interrupted();
 
         while fcg.state.has_control_frames() {
             let pos = reader.current_position() as u32;
             let op = reader.read_operator()?;
-            fcg.translate_operator(op, pos)?;
+            fcg.translate_operator(op, pos, reader.unreachable_trap_code())?;
         }
 
         fcg.finalize(wasm_fn_type)?;
@@ -1222,6 +1225,56 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
         self.builder.position_at_end(continue_block);
     }
 
+    fn trap_if_interrupted(&mut self) {
+        let flag_ptr = self.ctx.interrupt_flag(self.intrinsics, self.module);
+        let flag = self
+            .builder
+            .build_load(flag_ptr, "interrupt_flag")
+            .into_int_value();
+        // The flag is set by other threads, so it must be read again on
+        // every check.
+        flag.as_instruction_value()
+            .unwrap()
+            .set_volatile(true)
+            .unwrap();
+        let interrupted =
+            self.builder
+                .build_int_compare(IntPredicate::NE, flag, self.intrinsics.i32_zero, "");
+        let interrupted = self
+            .builder
+            .build_call(
+                self.intrinsics.expect_i1,
+                &[
+                    interrupted.into(),
+                    self.intrinsics.i1_ty.const_int(0, false).into(),
+                ],
+                "",
+            )
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+
+        let continue_block = self
+            .context
+            .append_basic_block(self.function, "not_interrupted_continue_block");
+        let interrupted_block = self
+            .context
+            .append_basic_block(self.function, "interrupted_trap_block");
+        self.builder
+            .build_conditional_branch(interrupted, interrupted_block, continue_block);
+
+        self.builder.position_at_end(interrupted_block);
+        self.builder.build_call(
+            self.intrinsics.throw_trap,
+            &[self.intrinsics.trap_interrupt.into()],
+            "throw",
+        );
+        self.builder.build_unreachable();
+
+        self.builder.position_at_end(continue_block);
+    }
+
     fn finalize(&mut self, wasm_fn_type: &FunctionType) -> Result<(), CompileError> {
         let func_type = self.function.get_type();
 
@@ -1383,7 +1436,12 @@ pub struct LLVMFunctionCodeGenerator<'ctx, 'a> {
 }
 
 impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
-    fn translate_operator(&mut self, op: Operator, _source_loc: u32) -> Result<(), CompileError> {
+    fn translate_operator(
+        &mut self,
+        op: Operator,
+        _source_loc: u32,
+        unreachable_trap_code: TrapCode,
+    ) -> Result<(), CompileError> {
         // TODO: remove this vmctx by moving everything into CtxType. Values
         // computed off vmctx usually benefit from caching.
         let vmctx = &self.ctx.basic().into_pointer_value();
@@ -1520,6 +1578,8 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 }
                 */
 
+                self.trap_if_interrupted();
+
                 self.state.push_loop(loop_body, loop_next, loop_phis, phis);
             }
             Operator::Br { relative_depth } => {
@@ -1872,11 +1932,12 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 }
                 */
 
-                self.builder.build_call(
-                    self.intrinsics.throw_trap,
-                    &[self.intrinsics.trap_unreachable.into()],
-                    "throw",
-                );
+                let trap_code = self
+                    .intrinsics
+                    .i32_ty
+                    .const_int(unreachable_trap_code as _, false);
+                self.builder
+                    .build_call(self.intrinsics.throw_trap, &[trap_code.into()], "throw");
                 self.builder.build_unreachable();
 
                 self.state.reachable = false;
@@ -208,7 +208,6 @@ pub struct Intrinsics<'ctx> {
     pub f64x2_zero: VectorValue<'ctx>,
     pub i32_consts: [IntValue<'ctx>; 16],
 
-    pub trap_unreachable: BasicValueEnum<'ctx>,
     pub trap_call_indirect_null: BasicValueEnum<'ctx>,
     pub trap_call_indirect_sig: BasicValueEnum<'ctx>,
     pub trap_memory_oob: BasicValueEnum<'ctx>,
@@ -217,6 +216,7 @@ pub struct Intrinsics<'ctx> {
     pub trap_bad_conversion_to_integer: BasicValueEnum<'ctx>,
     pub trap_unaligned_atomic: BasicValueEnum<'ctx>,
     pub trap_table_access_oob: BasicValueEnum<'ctx>,
+    pub trap_interrupt: BasicValueEnum<'ctx>,
 
     pub experimental_stackmap: FunctionValue<'ctx>,
 
@@ -753,9 +753,6 @@ impl<'ctx> Intrinsics<'ctx> {
             f64x2_zero,
             i32_consts,
 
-            trap_unreachable: i32_ty
-                .const_int(TrapCode::UnreachableCodeReached as _, false)
-                .as_basic_value_enum(),
             trap_call_indirect_null: i32_ty
                 .const_int(TrapCode::IndirectCallToNull as _, false)
                 .as_basic_value_enum(),
@@ -780,6 +777,9 @@ impl<'ctx> Intrinsics<'ctx> {
             trap_table_access_oob: i32_ty
                 .const_int(TrapCode::TableAccessOutOfBounds as _, false)
                 .as_basic_value_enum(),
+            trap_interrupt: i32_ty
+                .const_int(TrapCode::Interrupt as _, false)
+                .as_basic_value_enum(),
 
             experimental_stackmap: module.add_function(
                 "llvm.experimental.stackmap",
@@ -1098,6 +1098,7 @@ pub struct CtxType<'ctx, 'a> {
     cached_functions: HashMap<FunctionIndex, FunctionCache<'ctx>>,
     cached_memory_grow: HashMap<MemoryIndex, PointerValue<'ctx>>,
     cached_memory_size: HashMap<MemoryIndex, PointerValue<'ctx>>,
+    cached_interrupt_flag: Option<PointerValue<'ctx>>,
 
     offsets: VMOffsets,
 }
@@ -1123,6 +1124,7 @@ impl<'ctx, 'a> CtxType<'ctx, 'a> {
             cached_functions: HashMap::new(),
             cached_memory_grow: HashMap::new(),
             cached_memory_size: HashMap::new(),
+            cached_interrupt_flag: None,
 
             // TODO: pointer width
             offsets: VMOffsets::new(8, wasm_module),
@@ -1383,6 +1385,46 @@ impl<'ctx, 'a> CtxType<'ctx, 'a> {
         })
     }
 
+    /// Returns a pointer to the interrupt flag of the instance.
+    pub fn interrupt_flag(
+        &mut self,
+        intrinsics: &Intrinsics<'ctx>,
+        module: &Module<'ctx>,
+    ) -> PointerValue<'ctx> {
+        let (cached_interrupt_flag, ctx_ptr_value, cache_builder, offsets) = (
+            &mut self.cached_interrupt_flag,
+            self.ctx_ptr_value,
+            &self.cache_builder,
+            &self.offsets,
+        );
+        *cached_interrupt_flag.get_or_insert_with(|| {
+            let byte_offset = intrinsics
+                .i64_ty
+                .const_int(offsets.vmctx_interrupt().into(), false);
+            let flag_ptr_ptr = unsafe {
+                cache_builder.build_gep(ctx_ptr_value, &[byte_offset], "interrupt_flag_ptr")
+            };
+            let flag_ptr_ptr = cache_builder
+                .build_bitcast(
+                    flag_ptr_ptr,
+                    intrinsics.i32_ptr_ty.ptr_type(AddressSpace::Generic),
+                    "",
+                )
+                .into_pointer_value();
+
+            let flag_ptr = cache_builder
+                .build_load(flag_ptr_ptr, "interrupt_flag")
+                .into_pointer_value();
+            tbaa_label(
+                module,
+                intrinsics,
+                "interrupt_flag_ptr".to_string(),
+                flag_ptr.as_instruction_value().unwrap(),
+            );
+            flag_ptr
+        })
+    }
+
     pub fn global(
         &mut self,
         index: GlobalIndex,
@@ -94,6 +94,7 @@ struct SpecialLabelSet {
     table_access_oob: Label,
     indirect_call_null: Label,
     bad_signature: Label,
+    interrupt: Label,
 }
 
 /// Metadata about a floating-point value.
@@ -1083,7 +1084,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
             state_diff_id,
         });
 
-        // TODO: Full preemption by explicit signal checking
+        self.emit_interrupt_check()?;
 
         // We insert set StackOverflow as the default trap that can happen
         // anywhere in the function prologue.
@@ -1097,6 +1098,27 @@ impl<'a, M: Machine> FuncGen<'a, M> {
         Ok(())
     }
 
+    /// Traps with `Interrupt` if the interrupt flag of the instance is set.
+    fn emit_interrupt_check(&mut self) -> Result<(), CodegenError> {
+        let tmp = self.machine.acquire_temp_gpr().unwrap();
+        self.machine.move_location(
+            Size::S64,
+            Location::Memory(
+                self.machine.get_vmctx_reg(),
+                self.vmoffsets.vmctx_interrupt() as i32,
+            ),
+            Location::GPR(tmp),
+        )?;
+        self.machine
+            .move_location(Size::S32, Location::Memory(tmp, 0), Location::GPR(tmp))?;
+        self.machine
+            .location_cmp(Size::S32, Location::Imm32(0), Location::GPR(tmp))?;
+        self.machine
+            .jmp_on_different(self.special_labels.interrupt)?;
+        self.machine.release_gpr(tmp);
+        Ok(())
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         module: &'a ModuleInfo,
@@ -1128,6 +1150,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
             table_access_oob: machine.get_label(),
             indirect_call_null: machine.get_label(),
             bad_signature: machine.get_label(),
+            interrupt: machine.get_label(),
         };
 
         let fsm = FunctionStateMap::new(
@@ -1170,7 +1193,11 @@ impl<'a, M: Machine> FuncGen<'a, M> {
         !self.control_stack.is_empty()
     }
 
-    pub fn feed_operator(&mut self, op: Operator) -> Result<(), CodegenError> {
+    pub fn feed_operator(
+        &mut self,
+        op: Operator,
+        unreachable_trap_code: TrapCode,
+    ) -> Result<(), CodegenError> {
         assert!(self.fp_stack.len() <= self.value_stack.len());
 
         self.state.wasm_inst_offset = self.state.wasm_inst_offset.wrapping_add(1);
@@ -3102,7 +3129,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
                 });
                 self.machine.emit_label(label)?;
 
-                // TODO: Re-enable interrupt signal check without branching
+                self.emit_interrupt_check()?;
             }
             Operator::Nop => {}
             Operator::MemorySize { mem, mem_byte: _ } => {
@@ -3818,8 +3845,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
             }
             Operator::Unreachable => {
                 self.mark_trappable();
-                self.machine
-                    .emit_illegal_op(TrapCode::UnreachableCodeReached)?;
+                self.machine.emit_illegal_op(unreachable_trap_code)?;
                 self.unreachable_depth = 1;
             }
             Operator::Return => {
@@ -5937,6 +5963,9 @@ impl<'a, M: Machine> FuncGen<'a, M> {
         self.machine.emit_label(self.special_labels.bad_signature)?;
         self.machine.emit_illegal_op(TrapCode::BadSignature)?;
 
+        self.machine.emit_label(self.special_labels.interrupt)?;
+        self.machine.emit_illegal_op(TrapCode::Interrupt)?;
+
         // Notify the assembler backend to generate necessary code at end of function.
         self.machine.finalize_function()?;
 
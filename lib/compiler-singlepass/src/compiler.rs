@@ -13,9 +13,10 @@ use crate::machine::{
 use crate::machine_arm64::MachineARM64;
 use crate::machine_x64::MachineX86_64;
 #[cfg(feature = "unwind")]
-use crate::unwind::{create_systemv_cie, UnwindFrame};
+use crate::unwind::create_systemv_cie;
+use crate::unwind::UnwindFrame;
 #[cfg(feature = "unwind")]
-use gimli::write::{EhFrame, FrameTable};
+use gimli::write::{CieId, EhFrame, FrameTable};
 #[cfg(feature = "rayon")]
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 use std::sync::Arc;
@@ -26,9 +27,9 @@ use wasmer_compiler::{
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
     Architecture, CallingConvention, Compilation, CompileError, CompileModuleInfo,
-    CompiledFunction, CpuFeature, Dwarf, FunctionBody, FunctionIndex, FunctionType,
-    LocalFunctionIndex, MemoryIndex, ModuleInfo, OperatingSystem, SectionIndex, TableIndex, Target,
-    TrapCode, TrapInformation, VMOffsets,
+    CompiledFunction, CpuFeature, CustomSection, Dwarf, FunctionBody, FunctionIndex, FunctionType,
+    LocalFunctionIndex, MemoryIndex, MemoryStyle, ModuleInfo, OperatingSystem, SectionIndex,
+    TableIndex, TableStyle, Target, TrapCode, TrapInformation, VMOffsets,
 };
 
 impl From<CodegenError> for CompileError {
@@ -53,6 +54,173 @@ impl SinglepassCompiler {
     fn config(&self) -> &Singlepass {
         &self.config
     }
+
+    /// Compiles a single function, along with its unwind information.
+    #[allow(clippy::too_many_arguments)]
+    fn compile_function_body(
+        &self,
+        target: &Target,
+        module: &ModuleInfo,
+        vmoffsets: &VMOffsets,
+        memory_styles: &PrimaryMap<MemoryIndex, MemoryStyle>,
+        table_styles: &PrimaryMap<TableIndex, TableStyle>,
+        simd_arch: Option<CpuFeature>,
+        calling_convention: CallingConvention,
+        i: LocalFunctionIndex,
+        input: &FunctionBodyData<'_>,
+    ) -> Result<(CompiledFunction, Option<UnwindFrame>), CompileError> {
+        let _span = tracing::trace_span!(
+            "compile_function",
+            index = i.as_u32(),
+            bytes = input.data.len()
+        )
+        .entered();
+        let middleware_chain = self
+            .config
+            .middlewares
+            .generate_function_middleware_chain(i);
+        let mut reader = MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
+        reader.set_middleware_chain(middleware_chain);
+
+        // This local list excludes arguments.
+        let mut locals = vec![];
+        let num_locals = reader.read_local_count()?;
+        for _ in 0..num_locals {
+            let (count, ty) = reader.read_local_decl()?;
+            for _ in 0..count {
+                locals.push(ty);
+            }
+        }
+
+        match target.triple().architecture {
+            Architecture::X86_64 => {
+                let machine = MachineX86_64::new(simd_arch);
+                let mut generator = FuncGen::new(
+                    module,
+                    &self.config,
+                    vmoffsets,
+                    memory_styles,
+                    table_styles,
+                    i,
+                    &locals,
+                    machine,
+                    calling_convention,
+                )
+                .map_err(to_compile_error)?;
+                while generator.has_control_frames() {
+                    generator.set_srcloc(reader.original_position() as u32);
+                    let op = reader.read_operator()?;
+                    generator
+                        .feed_operator(op, reader.unreachable_trap_code())
+                        .map_err(to_compile_error)?;
+                }
+
+                generator.finalize(input).map_err(to_compile_error)
+            }
+            Architecture::Aarch64(_) => {
+                let machine = MachineARM64::new();
+                let mut generator = FuncGen::new(
+                    module,
+                    &self.config,
+                    vmoffsets,
+                    memory_styles,
+                    table_styles,
+                    i,
+                    &locals,
+                    machine,
+                    calling_convention,
+                )
+                .map_err(to_compile_error)?;
+                while generator.has_control_frames() {
+                    generator.set_srcloc(reader.original_position() as u32);
+                    let op = reader.read_operator()?;
+                    generator
+                        .feed_operator(op, reader.unreachable_trap_code())
+                        .map_err(to_compile_error)?;
+                }
+
+                generator.finalize(input).map_err(to_compile_error)
+            }
+            _ => unimplemented!(),
+        }
+    }
+}
+
+/// Checks that Singlepass can compile for `target`, returning the SIMD
+/// extension and the calling convention to generate code with.
+fn codegen_settings(
+    target: &Target,
+) -> Result<(Option<CpuFeature>, CallingConvention), CompileError> {
+    match target.triple().architecture {
+        Architecture::X86_64 => {}
+        Architecture::Aarch64(_) => {}
+        _ => {
+            return Err(CompileError::UnsupportedTarget(
+                target.triple().architecture.to_string(),
+            ))
+        }
+    }
+
+    let simd_arch = match target.triple().architecture {
+        Architecture::X86_64 => {
+            if target.cpu_features().contains(CpuFeature::AVX) {
+                Some(CpuFeature::AVX)
+            } else if target.cpu_features().contains(CpuFeature::SSE42) {
+                Some(CpuFeature::SSE42)
+            } else {
+                return Err(CompileError::UnsupportedTarget(
+                    "x86_64 without AVX or SSE 4.2".to_string(),
+                ));
+            }
+        }
+        _ => None,
+    };
+    let calling_convention = match target.triple().default_calling_convention() {
+        Ok(CallingConvention::WindowsFastcall) => CallingConvention::WindowsFastcall,
+        Ok(CallingConvention::SystemV) => CallingConvention::SystemV,
+        Ok(CallingConvention::AppleAarch64) => CallingConvention::AppleAarch64,
+        _ => {
+            return Err(CompileError::UnsupportedTarget(
+                "Unsupported Calling convention for Singlepass compiler".to_string(),
+            ))
+        }
+    };
+    Ok((simd_arch, calling_convention))
+}
+
+/// Creates the frame table holding the DWARF unwind information of the
+/// functions, if the target uses it.
+#[cfg(feature = "unwind")]
+fn dwarf_frametable(target: &Target) -> Option<(FrameTable, CieId)> {
+    match target.triple().default_calling_convention() {
+        Ok(CallingConvention::SystemV) => match create_systemv_cie(target.triple().architecture) {
+            Some(cie) => {
+                let mut dwarf_frametable = FrameTable::default();
+                let cie_id = dwarf_frametable.add_cie(cie);
+                Some((dwarf_frametable, cie_id))
+            }
+            None => None,
+        },
+        _ => None,
+    }
+}
+
+/// Writes the `.eh_frame` section describing the given functions.
+#[cfg(feature = "unwind")]
+fn eh_frame_section(
+    target: &Target,
+    mut dwarf_frametable: FrameTable,
+    cie_id: CieId,
+    fdes: impl IntoIterator<Item = UnwindFrame>,
+) -> CustomSection {
+    for fde in fdes {
+        match fde {
+            UnwindFrame::SystemV(fde) => dwarf_frametable.add_fde(cie_id, fde),
+        }
+    }
+    let mut eh_frame = EhFrame(WriterRelocate::new(target.triple().endianness().ok()));
+    dwarf_frametable.write_eh_frame(&mut eh_frame).unwrap();
+    eh_frame.0.into_section()
 }
 
 impl Compiler for SinglepassCompiler {
@@ -61,6 +229,57 @@ impl Compiler for SinglepassCompiler {
         &self.config.middlewares
     }
 
+    fn deterministic_id(&self) -> String {
+        format!(
+            "singlepass-{}{}",
+            env!("CARGO_PKG_VERSION"),
+            if self.config.enable_nan_canonicalization {
+                "-nan"
+            } else {
+                ""
+            },
+        )
+    }
+
+    fn supports_lazy_compilation(&self) -> bool {
+        true
+    }
+
+    /// Compile a single function of the module using Singlepass.
+    fn compile_function(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        _module_translation: &ModuleTranslationState,
+        index: LocalFunctionIndex,
+        input: &FunctionBodyData<'_>,
+    ) -> Result<(CompiledFunction, Option<CustomSection>), CompileError> {
+        let (simd_arch, calling_convention) = codegen_settings(target)?;
+        let vmoffsets = VMOffsets::new(8, &compile_info.module);
+        let (function, fde) = self.compile_function_body(
+            target,
+            &compile_info.module,
+            &vmoffsets,
+            &compile_info.memory_styles,
+            &compile_info.table_styles,
+            simd_arch,
+            calling_convention,
+            index,
+            input,
+        )?;
+
+        #[cfg(feature = "unwind")]
+        let eh_frame = dwarf_frametable(target).map(|(dwarf_frametable, cie_id)| {
+            eh_frame_section(target, dwarf_frametable, cie_id, fde)
+        });
+        #[cfg(not(feature = "unwind"))]
+        let eh_frame = {
+            let _ = fde;
+            None
+        };
+        Ok((function, eh_frame))
+    }
+
     /// Compile the module using Singlepass, producing a compilation result with
     /// associated relocations.
     fn compile_module(
@@ -70,40 +289,7 @@ impl Compiler for SinglepassCompiler {
         _module_translation: &ModuleTranslationState,
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
     ) -> Result<Compilation, CompileError> {
-        match target.triple().architecture {
-            Architecture::X86_64 => {}
-            Architecture::Aarch64(_) => {}
-            _ => {
-                return Err(CompileError::UnsupportedTarget(
-                    target.triple().architecture.to_string(),
-                ))
-            }
-        }
-
-        let simd_arch = match target.triple().architecture {
-            Architecture::X86_64 => {
-                if target.cpu_features().contains(CpuFeature::AVX) {
-                    Some(CpuFeature::AVX)
-                } else if target.cpu_features().contains(CpuFeature::SSE42) {
-                    Some(CpuFeature::SSE42)
-                } else {
-                    return Err(CompileError::UnsupportedTarget(
-                        "x86_64 without AVX or SSE 4.2".to_string(),
-                    ));
-                }
-            }
-            _ => None,
-        };
-        let calling_convention = match target.triple().default_calling_convention() {
-            Ok(CallingConvention::WindowsFastcall) => CallingConvention::WindowsFastcall,
-            Ok(CallingConvention::SystemV) => CallingConvention::SystemV,
-            Ok(CallingConvention::AppleAarch64) => CallingConvention::AppleAarch64,
-            _ => {
-                return Err(CompileError::UnsupportedTarget(
-                    "Unsupported Calling convention for Singlepass compiler".to_string(),
-                ))
-            }
-        };
+        let (simd_arch, calling_convention) = codegen_settings(target)?;
 
         // Generate the frametable
         #[cfg(feature = "unwind")]
@@ -113,19 +299,7 @@ impl Compiler for SinglepassCompiler {
             // FDEs will cause some issues in Linux.
             None
         } else {
-            match target.triple().default_calling_convention() {
-                Ok(CallingConvention::SystemV) => {
-                    match create_systemv_cie(target.triple().architecture) {
-                        Some(cie) => {
-                            let mut dwarf_frametable = FrameTable::default();
-                            let cie_id = dwarf_frametable.add_cie(cie);
-                            Some((dwarf_frametable, cie_id))
-                        }
-                        None => None,
-                    }
-                }
-                _ => None,
-            }
+            dwarf_frametable(target)
         };
 
         let memory_styles = &compile_info.memory_styles;
@@ -153,71 +327,17 @@ impl Compiler for SinglepassCompiler {
             .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
             .into_par_iter_if_rayon()
             .map(|(i, input)| {
-                let middleware_chain = self
-                    .config
-                    .middlewares
-                    .generate_function_middleware_chain(i);
-                let mut reader =
-                    MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
-                reader.set_middleware_chain(middleware_chain);
-
-                // This local list excludes arguments.
-                let mut locals = vec![];
-                let num_locals = reader.read_local_count()?;
-                for _ in 0..num_locals {
-                    let (count, ty) = reader.read_local_decl()?;
-                    for _ in 0..count {
-                        locals.push(ty);
-                    }
-                }
-
-                match target.triple().architecture {
-                    Architecture::X86_64 => {
-                        let machine = MachineX86_64::new(simd_arch);
-                        let mut generator = FuncGen::new(
-                            module,
-                            &self.config,
-                            &vmoffsets,
-                            memory_styles,
-                            table_styles,
-                            i,
-                            &locals,
-                            machine,
-                            calling_convention,
-                        )
-                        .map_err(to_compile_error)?;
-                        while generator.has_control_frames() {
-                            generator.set_srcloc(reader.original_position() as u32);
-                            let op = reader.read_operator()?;
-                            generator.feed_operator(op).map_err(to_compile_error)?;
-                        }
-
-                        generator.finalize(input).map_err(to_compile_error)
-                    }
-                    Architecture::Aarch64(_) => {
-                        let machine = MachineARM64::new();
-                        let mut generator = FuncGen::new(
-                            module,
-                            &self.config,
-                            &vmoffsets,
-                            memory_styles,
-                            table_styles,
-                            i,
-                            &locals,
-                            machine,
-                            calling_convention,
-                        )
-                        .map_err(to_compile_error)?;
-                        while generator.has_control_frames() {
-                            generator.set_srcloc(reader.original_position() as u32);
-                            let op = reader.read_operator()?;
-                            generator.feed_operator(op).map_err(to_compile_error)?;
-                        }
-
-                        generator.finalize(input).map_err(to_compile_error)
-                    }
-                    _ => unimplemented!(),
-                }
+                self.compile_function_body(
+                    target,
+                    module,
+                    &vmoffsets,
+                    memory_styles,
+                    table_styles,
+                    simd_arch,
+                    calling_convention,
+                    i,
+                    input,
+                )
             })
             .collect::<Result<Vec<_>, CompileError>>()?
             .into_iter()
@@ -250,16 +370,9 @@ impl Compiler for SinglepassCompiler {
             .collect::<PrimaryMap<FunctionIndex, FunctionBody>>();
 
         #[cfg(feature = "unwind")]
-        let dwarf = if let Some((mut dwarf_frametable, cie_id)) = dwarf_frametable {
-            for fde in fdes.into_iter().flatten() {
-                match fde {
-                    UnwindFrame::SystemV(fde) => dwarf_frametable.add_fde(cie_id, fde),
-                }
-            }
-            let mut eh_frame = EhFrame(WriterRelocate::new(target.triple().endianness().ok()));
-            dwarf_frametable.write_eh_frame(&mut eh_frame).unwrap();
-
-            let eh_frame_section = eh_frame.0.into_section();
+        let dwarf = if let Some((dwarf_frametable, cie_id)) = dwarf_frametable {
+            let eh_frame_section =
+                eh_frame_section(target, dwarf_frametable, cie_id, fdes.into_iter().flatten());
             custom_sections.push(eh_frame_section);
             Some(Dwarf::new(SectionIndex::new(custom_sections.len() - 1)))
         } else {
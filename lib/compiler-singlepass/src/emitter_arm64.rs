@@ -154,6 +154,17 @@ pub trait EmitterARM64 {
     fn emit_strb(&mut self, sz: Size, reg: Location, dst: Location) -> Result<(), CodegenError>;
     fn emit_strh(&mut self, sz: Size, reg: Location, dst: Location) -> Result<(), CodegenError>;
 
+    fn emit_ldar(&mut self, sz: Size, reg: Location, addr: GPR) -> Result<(), CodegenError>;
+    fn emit_stlr(&mut self, sz: Size, reg: Location, addr: GPR) -> Result<(), CodegenError>;
+    fn emit_ldaxr(&mut self, sz: Size, reg: Location, addr: GPR) -> Result<(), CodegenError>;
+    fn emit_stlxr(
+        &mut self,
+        sz: Size,
+        status: Location,
+        reg: Location,
+        addr: GPR,
+    ) -> Result<(), CodegenError>;
+
     fn emit_mov(&mut self, sz: Size, src: Location, dst: Location) -> Result<(), CodegenError>;
 
     fn emit_movn(&mut self, sz: Size, reg: Location, val: u32) -> Result<(), CodegenError>;
@@ -1060,6 +1071,113 @@ impl EmitterARM64 for Assembler {
         Ok(())
     }
 
+    fn emit_ldar(&mut self, sz: Size, reg: Location, addr: GPR) -> Result<(), CodegenError> {
+        let addr = addr.into_index() as u32;
+        match (sz, reg) {
+            (Size::S8, Location::GPR(reg)) => {
+                let reg = reg.into_index() as u32;
+                dynasm!(self ; ldarb W(reg), [X(addr)]);
+            }
+            (Size::S16, Location::GPR(reg)) => {
+                let reg = reg.into_index() as u32;
+                dynasm!(self ; ldarh W(reg), [X(addr)]);
+            }
+            (Size::S32, Location::GPR(reg)) => {
+                let reg = reg.into_index() as u32;
+                dynasm!(self ; ldar W(reg), [X(addr)]);
+            }
+            (Size::S64, Location::GPR(reg)) => {
+                let reg = reg.into_index() as u32;
+                dynasm!(self ; ldar X(reg), [X(addr)]);
+            }
+            _ => codegen_error!("singlepass can't emit LDAR {:?}, {:?}", sz, reg),
+        }
+        Ok(())
+    }
+    fn emit_stlr(&mut self, sz: Size, reg: Location, addr: GPR) -> Result<(), CodegenError> {
+        let addr = addr.into_index() as u32;
+        match (sz, reg) {
+            (Size::S8, Location::GPR(reg)) => {
+                let reg = reg.into_index() as u32;
+                dynasm!(self ; stlrb W(reg), [X(addr)]);
+            }
+            (Size::S16, Location::GPR(reg)) => {
+                let reg = reg.into_index() as u32;
+                dynasm!(self ; stlrh W(reg), [X(addr)]);
+            }
+            (Size::S32, Location::GPR(reg)) => {
+                let reg = reg.into_index() as u32;
+                dynasm!(self ; stlr W(reg), [X(addr)]);
+            }
+            (Size::S64, Location::GPR(reg)) => {
+                let reg = reg.into_index() as u32;
+                dynasm!(self ; stlr X(reg), [X(addr)]);
+            }
+            _ => codegen_error!("singlepass can't emit STLR {:?}, {:?}", sz, reg),
+        }
+        Ok(())
+    }
+    fn emit_ldaxr(&mut self, sz: Size, reg: Location, addr: GPR) -> Result<(), CodegenError> {
+        let addr = addr.into_index() as u32;
+        match (sz, reg) {
+            (Size::S8, Location::GPR(reg)) => {
+                let reg = reg.into_index() as u32;
+                dynasm!(self ; ldaxrb W(reg), [X(addr)]);
+            }
+            (Size::S16, Location::GPR(reg)) => {
+                let reg = reg.into_index() as u32;
+                dynasm!(self ; ldaxrh W(reg), [X(addr)]);
+            }
+            (Size::S32, Location::GPR(reg)) => {
+                let reg = reg.into_index() as u32;
+                dynasm!(self ; ldaxr W(reg), [X(addr)]);
+            }
+            (Size::S64, Location::GPR(reg)) => {
+                let reg = reg.into_index() as u32;
+                dynasm!(self ; ldaxr X(reg), [X(addr)]);
+            }
+            _ => codegen_error!("singlepass can't emit LDAXR {:?}, {:?}", sz, reg),
+        }
+        Ok(())
+    }
+    fn emit_stlxr(
+        &mut self,
+        sz: Size,
+        status: Location,
+        reg: Location,
+        addr: GPR,
+    ) -> Result<(), CodegenError> {
+        let addr = addr.into_index() as u32;
+        match (sz, status, reg) {
+            (Size::S8, Location::GPR(status), Location::GPR(reg)) => {
+                let status = status.into_index() as u32;
+                let reg = reg.into_index() as u32;
+                dynasm!(self ; stlxrb W(status), W(reg), [X(addr)]);
+            }
+            (Size::S16, Location::GPR(status), Location::GPR(reg)) => {
+                let status = status.into_index() as u32;
+                let reg = reg.into_index() as u32;
+                dynasm!(self ; stlxrh W(status), W(reg), [X(addr)]);
+            }
+            (Size::S32, Location::GPR(status), Location::GPR(reg)) => {
+                let status = status.into_index() as u32;
+                let reg = reg.into_index() as u32;
+                dynasm!(self ; stlxr W(status), W(reg), [X(addr)]);
+            }
+            (Size::S64, Location::GPR(status), Location::GPR(reg)) => {
+                let status = status.into_index() as u32;
+                let reg = reg.into_index() as u32;
+                dynasm!(self ; stlxr W(status), X(reg), [X(addr)]);
+            }
+            _ => codegen_error!(
+                "singlepass can't emit STLXR {:?}, {:?}, {:?}",
+                sz,
+                status,
+                reg
+            ),
+        }
+        Ok(())
+    }
     fn emit_mov(&mut self, sz: Size, src: Location, dst: Location) -> Result<(), CodegenError> {
         match (sz, src, dst) {
             (Size::S64, Location::GPR(src), Location::GPR(dst)) => {
@@ -1114,23 +1114,248 @@ impl MachineARM64 {
         Ok(())
     }
 
-    /*fn emit_compare_and_swap<F: FnOnce(&mut Self, GPR, GPR)>(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _ret: Location,
-        _memarg: &MemoryImmediate,
-        _value_size: usize,
-        _memory_sz: Size,
-        _stack_sz: Size,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-        _cb: F,
-    ) {
-        unimplemented!();
-    }*/
+    /// Emits a sequentially consistent load of `access` bytes, zero-extended
+    /// to `sz` in `ret`.
+    #[allow(clippy::too_many_arguments)]
+    fn emit_atomic_load(
+        &mut self,
+        sz: Size,
+        access: Size,
+        addr: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.memory_op(
+            addr,
+            memarg,
+            true,
+            access_bytes(access),
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            |this, addr| {
+                let mut temps = vec![];
+                let dest = this.location_to_reg(sz, ret, &mut temps, ImmType::None, false, None)?;
+                this.assembler.emit_ldar(access, dest, addr)?;
+                if ret != dest {
+                    this.move_location(sz, dest, ret)?;
+                }
+                for r in temps {
+                    this.release_gpr(r);
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Emits a sequentially consistent store of the low `access` bytes of
+    /// `value`.
+    #[allow(clippy::too_many_arguments)]
+    fn emit_atomic_store(
+        &mut self,
+        sz: Size,
+        access: Size,
+        value: Location,
+        memarg: &MemoryImmediate,
+        target_addr: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        let mut temps = vec![];
+        let value = self.location_to_reg(sz, value, &mut temps, ImmType::None, true, None)?;
+        self.memory_op(
+            target_addr,
+            memarg,
+            true,
+            access_bytes(access),
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            |this, addr| this.assembler.emit_stlr(access, value, addr),
+        )?;
+        for r in temps {
+            self.release_gpr(r);
+        }
+        Ok(())
+    }
+
+    /// Emits an atomic read-modify-write of `access` bytes, storing
+    /// `op(old, loc)`, or `loc` itself without `op`, and returning the old
+    /// value zero-extended to `sz` in `ret`.
+    ///
+    /// The value is loaded and stored exclusively, retrying until no other
+    /// thread wrote it in between.
+    #[allow(clippy::too_many_arguments)]
+    fn emit_atomic_rmw(
+        &mut self,
+        op: Option<
+            fn(&mut Assembler, Size, Location, Location, Location) -> Result<(), CodegenError>,
+        >,
+        sz: Size,
+        access: Size,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        let mut temps = vec![];
+        let value = self.location_to_reg(sz, loc, &mut temps, ImmType::None, true, None)?;
+        self.memory_op(
+            target,
+            memarg,
+            true,
+            access_bytes(access),
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            |this, addr| {
+                let old = this.acquire_temp_gpr().ok_or(CodegenError {
+                    message: "singlepass cannot acquire temp gpr".to_string(),
+                })?;
+                let status = this.acquire_temp_gpr().ok_or(CodegenError {
+                    message: "singlepass cannot acquire temp gpr".to_string(),
+                })?;
+                let new = match op {
+                    Some(_) => Some(this.acquire_temp_gpr().ok_or(CodegenError {
+                        message: "singlepass cannot acquire temp gpr".to_string(),
+                    })?),
+                    None => None,
+                };
+
+                let retry = this.assembler.get_label();
+                this.assembler.emit_label(retry)?;
+                this.assembler
+                    .emit_ldaxr(access, Location::GPR(old), addr)?;
+                let stored = match (op, new) {
+                    (Some(op), Some(new)) => {
+                        op(
+                            &mut this.assembler,
+                            sz,
+                            Location::GPR(old),
+                            value,
+                            Location::GPR(new),
+                        )?;
+                        Location::GPR(new)
+                    }
+                    _ => value,
+                };
+                this.assembler
+                    .emit_stlxr(access, Location::GPR(status), stored, addr)?;
+                this.assembler
+                    .emit_cbnz_label(Size::S32, Location::GPR(status), retry)?;
+                this.move_location(sz, Location::GPR(old), ret)?;
+
+                if let Some(new) = new {
+                    this.release_gpr(new);
+                }
+                this.release_gpr(status);
+                this.release_gpr(old);
+                Ok(())
+            },
+        )?;
+        for r in temps {
+            self.release_gpr(r);
+        }
+        Ok(())
+    }
+
+    /// Emits an atomic compare and exchange of `access` bytes, storing
+    /// `new` if the value is `cmp`, and returning the old value
+    /// zero-extended to `sz` in `ret`.
+    #[allow(clippy::too_many_arguments)]
+    fn emit_atomic_cmpxchg(
+        &mut self,
+        sz: Size,
+        access: Size,
+        new: Location,
+        cmp: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        let mut temps = vec![];
+        let new = self.location_to_reg(sz, new, &mut temps, ImmType::None, true, None)?;
+        let cmp = self.location_to_reg(sz, cmp, &mut temps, ImmType::None, true, None)?;
+        // The old value is zero-extended, so it is compared to the expected
+        // value truncated to the size of the access.
+        let expected = if access < sz {
+            let tmp = self.acquire_temp_gpr().ok_or(CodegenError {
+                message: "singlepass cannot acquire temp gpr".to_string(),
+            })?;
+            temps.push(tmp);
+            match access {
+                Size::S8 => self
+                    .assembler
+                    .emit_uxtb(Size::S32, cmp, Location::GPR(tmp))?,
+                Size::S16 => self
+                    .assembler
+                    .emit_uxth(Size::S32, cmp, Location::GPR(tmp))?,
+                _ => self
+                    .assembler
+                    .emit_mov(Size::S32, cmp, Location::GPR(tmp))?,
+            }
+            Location::GPR(tmp)
+        } else {
+            cmp
+        };
+        self.memory_op(
+            target,
+            memarg,
+            true,
+            access_bytes(access),
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            |this, addr| {
+                let old = this.acquire_temp_gpr().ok_or(CodegenError {
+                    message: "singlepass cannot acquire temp gpr".to_string(),
+                })?;
+                let status = this.acquire_temp_gpr().ok_or(CodegenError {
+                    message: "singlepass cannot acquire temp gpr".to_string(),
+                })?;
+
+                let retry = this.assembler.get_label();
+                let done = this.assembler.get_label();
+                this.assembler.emit_label(retry)?;
+                this.assembler
+                    .emit_ldaxr(access, Location::GPR(old), addr)?;
+                this.assembler.emit_cmp(sz, expected, Location::GPR(old))?;
+                this.assembler.emit_bcond_label(Condition::Ne, done)?;
+                this.assembler
+                    .emit_stlxr(access, Location::GPR(status), new, addr)?;
+                this.assembler
+                    .emit_cbnz_label(Size::S32, Location::GPR(status), retry)?;
+                this.assembler.emit_label(done)?;
+                this.move_location(sz, Location::GPR(old), ret)?;
+
+                this.release_gpr(status);
+                this.release_gpr(old);
+                Ok(())
+            },
+        )?;
+        for r in temps {
+            self.release_gpr(r);
+        }
+        Ok(())
+    }
 
     fn offset_is_ok(&self, size: Size, offset: i32) -> bool {
         if offset < 0 {
@@ -1397,6 +1622,16 @@ impl MachineARM64 {
     }
 }
 
+/// The size in bytes of an access of `size`.
+fn access_bytes(size: Size) -> usize {
+    match size {
+        Size::S8 => 1,
+        Size::S16 => 2,
+        Size::S32 => 4,
+        Size::S64 => 8,
+    }
+}
+
 impl Machine for MachineARM64 {
     type GPR = GPR;
     type SIMD = NEON;
@@ -3278,39 +3513,69 @@ impl Machine for MachineARM64 {
     }
     fn i32_atomic_load(
         &mut self,
-        _addr: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        addr: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_load unimplemented");
+        self.emit_atomic_load(
+            Size::S32,
+            Size::S32,
+            addr,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     fn i32_atomic_load_8u(
         &mut self,
-        _addr: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        addr: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_load_8u unimplemented");
+        self.emit_atomic_load(
+            Size::S32,
+            Size::S8,
+            addr,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     fn i32_atomic_load_16u(
         &mut self,
-        _addr: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        addr: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_load_16u unimplemented");
+        self.emit_atomic_load(
+            Size::S32,
+            Size::S16,
+            addr,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     fn i32_save(
         &mut self,
@@ -3380,336 +3645,618 @@ impl Machine for MachineARM64 {
     }
     fn i32_atomic_save(
         &mut self,
-        _value: Location,
-        _memarg: &MemoryImmediate,
-        _target_addr: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        value: Location,
+        memarg: &MemoryImmediate,
+        target_addr: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_save unimplemented");
+        self.emit_atomic_store(
+            Size::S32,
+            Size::S32,
+            value,
+            memarg,
+            target_addr,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     fn i32_atomic_save_8(
         &mut self,
-        _value: Location,
-        _memarg: &MemoryImmediate,
-        _target_addr: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        value: Location,
+        memarg: &MemoryImmediate,
+        target_addr: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_save_8 unimplemented");
+        self.emit_atomic_store(
+            Size::S32,
+            Size::S8,
+            value,
+            memarg,
+            target_addr,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     fn i32_atomic_save_16(
         &mut self,
-        _value: Location,
-        _memarg: &MemoryImmediate,
-        _target_addr: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        value: Location,
+        memarg: &MemoryImmediate,
+        target_addr: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_save_16 unimplemented");
+        self.emit_atomic_store(
+            Size::S32,
+            Size::S16,
+            value,
+            memarg,
+            target_addr,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic Add with i32
     fn i32_atomic_add(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_add unimplemented");
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_add),
+            Size::S32,
+            Size::S32,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic Add with u8
     fn i32_atomic_add_8u(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_add_8u unimplemented");
-    }
-    // i32 atomic Add with u16
-    fn i32_atomic_add_16u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_add_16u unimplemented");
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_add),
+            Size::S32,
+            Size::S8,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    // i32 atomic Add with u16
+    fn i32_atomic_add_16u(
+        &mut self,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_add),
+            Size::S32,
+            Size::S16,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic Sub with i32
     fn i32_atomic_sub(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_sub unimplemented");
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_sub),
+            Size::S32,
+            Size::S32,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic Sub with u8
     fn i32_atomic_sub_8u(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_sub_8u unimplemented");
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_sub),
+            Size::S32,
+            Size::S8,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic Sub with u16
     fn i32_atomic_sub_16u(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_sub_16u unimplemented");
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_sub),
+            Size::S32,
+            Size::S16,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic And with i32
     fn i32_atomic_and(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_and unimplemented");
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_and),
+            Size::S32,
+            Size::S32,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic And with u8
     fn i32_atomic_and_8u(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_and_8u unimplemented");
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_and),
+            Size::S32,
+            Size::S8,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic And with u16
     fn i32_atomic_and_16u(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_and_16u unimplemented");
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_and),
+            Size::S32,
+            Size::S16,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic Or with i32
     fn i32_atomic_or(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_or unimplemented");
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_or),
+            Size::S32,
+            Size::S32,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic Or with u8
     fn i32_atomic_or_8u(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_or_8u unimplemented");
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_or),
+            Size::S32,
+            Size::S8,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic Or with u16
     fn i32_atomic_or_16u(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_or_16u unimplemented");
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_or),
+            Size::S32,
+            Size::S16,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic Xor with i32
     fn i32_atomic_xor(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_xor unimplemented");
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_eor),
+            Size::S32,
+            Size::S32,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic Xor with u8
     fn i32_atomic_xor_8u(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_xor_8u unimplemented");
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_eor),
+            Size::S32,
+            Size::S8,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic Xor with u16
     fn i32_atomic_xor_16u(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_xor_16u unimplemented");
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_eor),
+            Size::S32,
+            Size::S16,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic Exchange with i32
     fn i32_atomic_xchg(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_xchg unimplemented");
+        self.emit_atomic_rmw(
+            None,
+            Size::S32,
+            Size::S32,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic Exchange with u8
     fn i32_atomic_xchg_8u(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_xchg_8u unimplemented");
+        self.emit_atomic_rmw(
+            None,
+            Size::S32,
+            Size::S8,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic Exchange with u16
     fn i32_atomic_xchg_16u(
         &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_xchg_16u unimplemented");
-    }
-    // i32 atomic Exchange with i32
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_rmw(
+            None,
+            Size::S32,
+            Size::S16,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    // i32 atomic Exchange with i32
     fn i32_atomic_cmpxchg(
         &mut self,
-        _new: Location,
-        _cmp: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        new: Location,
+        cmp: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_cmpxchg unimplemented");
+        self.emit_atomic_cmpxchg(
+            Size::S32,
+            Size::S32,
+            new,
+            cmp,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic Exchange with u8
     fn i32_atomic_cmpxchg_8u(
         &mut self,
-        _new: Location,
-        _cmp: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        new: Location,
+        cmp: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_cmpxchg_8u unimplemented");
+        self.emit_atomic_cmpxchg(
+            Size::S32,
+            Size::S8,
+            new,
+            cmp,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
     // i32 atomic Exchange with u16
     fn i32_atomic_cmpxchg_16u(
         &mut self,
-        _new: Location,
-        _cmp: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
+        new: Location,
+        cmp: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i32_atomic_cmpxchg_16u unimplemented");
+        self.emit_atomic_cmpxchg(
+            Size::S32,
+            Size::S16,
+            new,
+            cmp,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
 
     fn emit_call_with_reloc(
@@ -4105,109 +4652,893 @@ impl Machine for MachineARM64 {
         }
         Ok(())
     }
-    fn i64_shl(
+    fn i64_shl(
+        &mut self,
+        loc_a: Location,
+        loc_b: Location,
+        ret: Location,
+    ) -> Result<(), CodegenError> {
+        self.emit_relaxed_binop3(
+            Assembler::emit_lsl,
+            Size::S64,
+            loc_a,
+            loc_b,
+            ret,
+            ImmType::Shift64No0,
+        )
+    }
+    fn i64_shr(
+        &mut self,
+        loc_a: Location,
+        loc_b: Location,
+        ret: Location,
+    ) -> Result<(), CodegenError> {
+        self.emit_relaxed_binop3(
+            Assembler::emit_lsr,
+            Size::S64,
+            loc_a,
+            loc_b,
+            ret,
+            ImmType::Shift64No0,
+        )
+    }
+    fn i64_sar(
+        &mut self,
+        loc_a: Location,
+        loc_b: Location,
+        ret: Location,
+    ) -> Result<(), CodegenError> {
+        self.emit_relaxed_binop3(
+            Assembler::emit_asr,
+            Size::S64,
+            loc_a,
+            loc_b,
+            ret,
+            ImmType::Shift64No0,
+        )
+    }
+    fn i64_rol(
+        &mut self,
+        loc_a: Location,
+        loc_b: Location,
+        ret: Location,
+    ) -> Result<(), CodegenError> {
+        // there is no ROL on ARM64. We use ROR with 64-value instead
+        let mut temps = vec![];
+        let src2 = match loc_b {
+            Location::Imm8(imm) => Location::Imm8(64 - (imm & 63)),
+            Location::Imm32(imm) => Location::Imm8(64 - (imm & 63) as u8),
+            Location::Imm64(imm) => Location::Imm8(64 - (imm & 63) as u8),
+            _ => {
+                let tmp1 = self.location_to_reg(
+                    Size::S64,
+                    Location::Imm32(64),
+                    &mut temps,
+                    ImmType::None,
+                    true,
+                    None,
+                )?;
+                let tmp2 =
+                    self.location_to_reg(Size::S64, loc_b, &mut temps, ImmType::None, true, None)?;
+                self.assembler.emit_sub(Size::S64, tmp1, tmp2, tmp1)?;
+                tmp1
+            }
+        };
+        self.emit_relaxed_binop3(
+            Assembler::emit_ror,
+            Size::S64,
+            loc_a,
+            src2,
+            ret,
+            ImmType::Shift64No0,
+        )?;
+        for r in temps {
+            self.release_gpr(r);
+        }
+        Ok(())
+    }
+    fn i64_ror(
+        &mut self,
+        loc_a: Location,
+        loc_b: Location,
+        ret: Location,
+    ) -> Result<(), CodegenError> {
+        self.emit_relaxed_binop3(
+            Assembler::emit_ror,
+            Size::S64,
+            loc_a,
+            loc_b,
+            ret,
+            ImmType::Shift64No0,
+        )
+    }
+    fn i64_load(
+        &mut self,
+        addr: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.memory_op(
+            addr,
+            memarg,
+            false,
+            8,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            |this, addr| this.emit_relaxed_ldr64(Size::S64, ret, Location::Memory(addr, 0)),
+        )
+    }
+    fn i64_load_8u(
+        &mut self,
+        addr: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.memory_op(
+            addr,
+            memarg,
+            false,
+            1,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            |this, addr| this.emit_relaxed_ldr8(Size::S64, ret, Location::Memory(addr, 0)),
+        )
+    }
+    fn i64_load_8s(
+        &mut self,
+        addr: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.memory_op(
+            addr,
+            memarg,
+            false,
+            1,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            |this, addr| this.emit_relaxed_ldr8s(Size::S64, ret, Location::Memory(addr, 0)),
+        )
+    }
+    fn i64_load_16u(
+        &mut self,
+        addr: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.memory_op(
+            addr,
+            memarg,
+            false,
+            2,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            |this, addr| this.emit_relaxed_ldr16(Size::S64, ret, Location::Memory(addr, 0)),
+        )
+    }
+    fn i64_load_16s(
+        &mut self,
+        addr: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.memory_op(
+            addr,
+            memarg,
+            false,
+            2,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            |this, addr| this.emit_relaxed_ldr16s(Size::S64, ret, Location::Memory(addr, 0)),
+        )
+    }
+    fn i64_load_32u(
+        &mut self,
+        addr: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.memory_op(
+            addr,
+            memarg,
+            false,
+            4,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            |this, addr| this.emit_relaxed_ldr32(Size::S64, ret, Location::Memory(addr, 0)),
+        )
+    }
+    fn i64_load_32s(
+        &mut self,
+        addr: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.memory_op(
+            addr,
+            memarg,
+            false,
+            4,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            |this, addr| this.emit_relaxed_ldr32s(Size::S64, ret, Location::Memory(addr, 0)),
+        )
+    }
+    fn i64_atomic_load(
+        &mut self,
+        addr: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_load(
+            Size::S64,
+            Size::S64,
+            addr,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    fn i64_atomic_load_8u(
+        &mut self,
+        addr: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_load(
+            Size::S64,
+            Size::S8,
+            addr,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    fn i64_atomic_load_16u(
+        &mut self,
+        addr: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_load(
+            Size::S64,
+            Size::S16,
+            addr,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    fn i64_atomic_load_32u(
+        &mut self,
+        addr: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_load(
+            Size::S64,
+            Size::S32,
+            addr,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    fn i64_save(
+        &mut self,
+        target_value: Location,
+        memarg: &MemoryImmediate,
+        target_addr: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.memory_op(
+            target_addr,
+            memarg,
+            false,
+            8,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            |this, addr| this.emit_relaxed_str64(target_value, Location::Memory(addr, 0)),
+        )
+    }
+    fn i64_save_8(
+        &mut self,
+        target_value: Location,
+        memarg: &MemoryImmediate,
+        target_addr: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.memory_op(
+            target_addr,
+            memarg,
+            false,
+            1,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            |this, addr| this.emit_relaxed_str8(target_value, Location::Memory(addr, 0)),
+        )
+    }
+    fn i64_save_16(
+        &mut self,
+        target_value: Location,
+        memarg: &MemoryImmediate,
+        target_addr: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.memory_op(
+            target_addr,
+            memarg,
+            false,
+            2,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            |this, addr| this.emit_relaxed_str16(target_value, Location::Memory(addr, 0)),
+        )
+    }
+    fn i64_save_32(
+        &mut self,
+        target_value: Location,
+        memarg: &MemoryImmediate,
+        target_addr: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.memory_op(
+            target_addr,
+            memarg,
+            false,
+            4,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+            |this, addr| this.emit_relaxed_str32(target_value, Location::Memory(addr, 0)),
+        )
+    }
+    fn i64_atomic_save(
+        &mut self,
+        value: Location,
+        memarg: &MemoryImmediate,
+        target_addr: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_store(
+            Size::S64,
+            Size::S64,
+            value,
+            memarg,
+            target_addr,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    fn i64_atomic_save_8(
+        &mut self,
+        value: Location,
+        memarg: &MemoryImmediate,
+        target_addr: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_store(
+            Size::S64,
+            Size::S8,
+            value,
+            memarg,
+            target_addr,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    fn i64_atomic_save_16(
+        &mut self,
+        value: Location,
+        memarg: &MemoryImmediate,
+        target_addr: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_store(
+            Size::S64,
+            Size::S16,
+            value,
+            memarg,
+            target_addr,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    fn i64_atomic_save_32(
+        &mut self,
+        value: Location,
+        memarg: &MemoryImmediate,
+        target_addr: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_store(
+            Size::S64,
+            Size::S32,
+            value,
+            memarg,
+            target_addr,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    // i64 atomic Add with i64
+    fn i64_atomic_add(
+        &mut self,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_add),
+            Size::S64,
+            Size::S64,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    // i64 atomic Add with u8
+    fn i64_atomic_add_8u(
+        &mut self,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_add),
+            Size::S64,
+            Size::S8,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    // i64 atomic Add with u16
+    fn i64_atomic_add_16u(
+        &mut self,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_add),
+            Size::S64,
+            Size::S16,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    // i64 atomic Add with u32
+    fn i64_atomic_add_32u(
+        &mut self,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_add),
+            Size::S64,
+            Size::S32,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    // i64 atomic Sub with i64
+    fn i64_atomic_sub(
+        &mut self,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_sub),
+            Size::S64,
+            Size::S64,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    // i64 atomic Sub with u8
+    fn i64_atomic_sub_8u(
+        &mut self,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_sub),
+            Size::S64,
+            Size::S8,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    // i64 atomic Sub with u16
+    fn i64_atomic_sub_16u(
+        &mut self,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_sub),
+            Size::S64,
+            Size::S16,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    // i64 atomic Sub with u32
+    fn i64_atomic_sub_32u(
+        &mut self,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_sub),
+            Size::S64,
+            Size::S32,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    // i64 atomic And with i64
+    fn i64_atomic_and(
+        &mut self,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_and),
+            Size::S64,
+            Size::S64,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    // i64 atomic And with u8
+    fn i64_atomic_and_8u(
         &mut self,
-        loc_a: Location,
-        loc_b: Location,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
         ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        self.emit_relaxed_binop3(
-            Assembler::emit_lsl,
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_and),
             Size::S64,
-            loc_a,
-            loc_b,
+            Size::S8,
+            loc,
+            target,
+            memarg,
             ret,
-            ImmType::Shift64No0,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
         )
     }
-    fn i64_shr(
+    // i64 atomic And with u16
+    fn i64_atomic_and_16u(
         &mut self,
-        loc_a: Location,
-        loc_b: Location,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
         ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        self.emit_relaxed_binop3(
-            Assembler::emit_lsr,
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_and),
             Size::S64,
-            loc_a,
-            loc_b,
+            Size::S16,
+            loc,
+            target,
+            memarg,
             ret,
-            ImmType::Shift64No0,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
         )
     }
-    fn i64_sar(
+    // i64 atomic And with u32
+    fn i64_atomic_and_32u(
         &mut self,
-        loc_a: Location,
-        loc_b: Location,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
         ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        self.emit_relaxed_binop3(
-            Assembler::emit_asr,
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_and),
             Size::S64,
-            loc_a,
-            loc_b,
+            Size::S32,
+            loc,
+            target,
+            memarg,
             ret,
-            ImmType::Shift64No0,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
         )
     }
-    fn i64_rol(
+    // i64 atomic Or with i64
+    fn i64_atomic_or(
         &mut self,
-        loc_a: Location,
-        loc_b: Location,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
         ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        // there is no ROL on ARM64. We use ROR with 64-value instead
-        let mut temps = vec![];
-        let src2 = match loc_b {
-            Location::Imm8(imm) => Location::Imm8(64 - (imm & 63)),
-            Location::Imm32(imm) => Location::Imm8(64 - (imm & 63) as u8),
-            Location::Imm64(imm) => Location::Imm8(64 - (imm & 63) as u8),
-            _ => {
-                let tmp1 = self.location_to_reg(
-                    Size::S64,
-                    Location::Imm32(64),
-                    &mut temps,
-                    ImmType::None,
-                    true,
-                    None,
-                )?;
-                let tmp2 =
-                    self.location_to_reg(Size::S64, loc_b, &mut temps, ImmType::None, true, None)?;
-                self.assembler.emit_sub(Size::S64, tmp1, tmp2, tmp1)?;
-                tmp1
-            }
-        };
-        self.emit_relaxed_binop3(
-            Assembler::emit_ror,
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_or),
             Size::S64,
-            loc_a,
-            src2,
+            Size::S64,
+            loc,
+            target,
+            memarg,
             ret,
-            ImmType::Shift64No0,
-        )?;
-        for r in temps {
-            self.release_gpr(r);
-        }
-        Ok(())
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
     }
-    fn i64_ror(
+    // i64 atomic Or with u8
+    fn i64_atomic_or_8u(
         &mut self,
-        loc_a: Location,
-        loc_b: Location,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
         ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        self.emit_relaxed_binop3(
-            Assembler::emit_ror,
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_or),
             Size::S64,
-            loc_a,
-            loc_b,
+            Size::S8,
+            loc,
+            target,
+            memarg,
             ret,
-            ImmType::Shift64No0,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
         )
     }
-    fn i64_load(
+    // i64 atomic Or with u16
+    fn i64_atomic_or_16u(
         &mut self,
-        addr: Location,
+        loc: Location,
+        target: Location,
         memarg: &MemoryImmediate,
         ret: Location,
         need_check: bool,
@@ -4215,21 +5546,25 @@ impl Machine for MachineARM64 {
         offset: i32,
         heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        self.memory_op(
-            addr,
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_or),
+            Size::S64,
+            Size::S16,
+            loc,
+            target,
             memarg,
-            false,
-            8,
+            ret,
             need_check,
             imported_memories,
             offset,
             heap_access_oob,
-            |this, addr| this.emit_relaxed_ldr64(Size::S64, ret, Location::Memory(addr, 0)),
         )
     }
-    fn i64_load_8u(
+    // i64 atomic Or with u32
+    fn i64_atomic_or_32u(
         &mut self,
-        addr: Location,
+        loc: Location,
+        target: Location,
         memarg: &MemoryImmediate,
         ret: Location,
         need_check: bool,
@@ -4237,21 +5572,25 @@ impl Machine for MachineARM64 {
         offset: i32,
         heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        self.memory_op(
-            addr,
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_or),
+            Size::S64,
+            Size::S32,
+            loc,
+            target,
             memarg,
-            false,
-            1,
+            ret,
             need_check,
             imported_memories,
             offset,
             heap_access_oob,
-            |this, addr| this.emit_relaxed_ldr8(Size::S64, ret, Location::Memory(addr, 0)),
         )
     }
-    fn i64_load_8s(
+    // i64 atomic xor with i64
+    fn i64_atomic_xor(
         &mut self,
-        addr: Location,
+        loc: Location,
+        target: Location,
         memarg: &MemoryImmediate,
         ret: Location,
         need_check: bool,
@@ -4259,21 +5598,25 @@ impl Machine for MachineARM64 {
         offset: i32,
         heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        self.memory_op(
-            addr,
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_eor),
+            Size::S64,
+            Size::S64,
+            loc,
+            target,
             memarg,
-            false,
-            1,
+            ret,
             need_check,
             imported_memories,
             offset,
             heap_access_oob,
-            |this, addr| this.emit_relaxed_ldr8s(Size::S64, ret, Location::Memory(addr, 0)),
         )
     }
-    fn i64_load_16u(
+    // i64 atomic xor with u8
+    fn i64_atomic_xor_8u(
         &mut self,
-        addr: Location,
+        loc: Location,
+        target: Location,
         memarg: &MemoryImmediate,
         ret: Location,
         need_check: bool,
@@ -4281,21 +5624,25 @@ impl Machine for MachineARM64 {
         offset: i32,
         heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        self.memory_op(
-            addr,
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_eor),
+            Size::S64,
+            Size::S8,
+            loc,
+            target,
             memarg,
-            false,
-            2,
+            ret,
             need_check,
             imported_memories,
             offset,
             heap_access_oob,
-            |this, addr| this.emit_relaxed_ldr16(Size::S64, ret, Location::Memory(addr, 0)),
         )
     }
-    fn i64_load_16s(
+    // i64 atomic xor with u16
+    fn i64_atomic_xor_16u(
         &mut self,
-        addr: Location,
+        loc: Location,
+        target: Location,
         memarg: &MemoryImmediate,
         ret: Location,
         need_check: bool,
@@ -4303,21 +5650,103 @@ impl Machine for MachineARM64 {
         offset: i32,
         heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        self.memory_op(
-            addr,
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_eor),
+            Size::S64,
+            Size::S16,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    // i64 atomic xor with u32
+    fn i64_atomic_xor_32u(
+        &mut self,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_rmw(
+            Some(Assembler::emit_eor),
+            Size::S64,
+            Size::S32,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    // i64 atomic Exchange with i64
+    fn i64_atomic_xchg(
+        &mut self,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_rmw(
+            None,
+            Size::S64,
+            Size::S64,
+            loc,
+            target,
+            memarg,
+            ret,
+            need_check,
+            imported_memories,
+            offset,
+            heap_access_oob,
+        )
+    }
+    // i64 atomic Exchange with u8
+    fn i64_atomic_xchg_8u(
+        &mut self,
+        loc: Location,
+        target: Location,
+        memarg: &MemoryImmediate,
+        ret: Location,
+        need_check: bool,
+        imported_memories: bool,
+        offset: i32,
+        heap_access_oob: Label,
+    ) -> Result<(), CodegenError> {
+        self.emit_atomic_rmw(
+            None,
+            Size::S64,
+            Size::S8,
+            loc,
+            target,
             memarg,
-            false,
-            2,
+            ret,
             need_check,
             imported_memories,
             offset,
             heap_access_oob,
-            |this, addr| this.emit_relaxed_ldr16s(Size::S64, ret, Location::Memory(addr, 0)),
         )
     }
-    fn i64_load_32u(
+    // i64 atomic Exchange with u16
+    fn i64_atomic_xchg_16u(
         &mut self,
-        addr: Location,
+        loc: Location,
+        target: Location,
         memarg: &MemoryImmediate,
         ret: Location,
         need_check: bool,
@@ -4325,21 +5754,25 @@ impl Machine for MachineARM64 {
         offset: i32,
         heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        self.memory_op(
-            addr,
+        self.emit_atomic_rmw(
+            None,
+            Size::S64,
+            Size::S16,
+            loc,
+            target,
             memarg,
-            false,
-            4,
+            ret,
             need_check,
             imported_memories,
             offset,
             heap_access_oob,
-            |this, addr| this.emit_relaxed_ldr32(Size::S64, ret, Location::Memory(addr, 0)),
         )
     }
-    fn i64_load_32s(
+    // i64 atomic Exchange with u32
+    fn i64_atomic_xchg_32u(
         &mut self,
-        addr: Location,
+        loc: Location,
+        target: Location,
         memarg: &MemoryImmediate,
         ret: Location,
         need_check: bool,
@@ -4347,598 +5780,128 @@ impl Machine for MachineARM64 {
         offset: i32,
         heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        self.memory_op(
-            addr,
+        self.emit_atomic_rmw(
+            None,
+            Size::S64,
+            Size::S32,
+            loc,
+            target,
             memarg,
-            false,
-            4,
+            ret,
             need_check,
             imported_memories,
             offset,
             heap_access_oob,
-            |this, addr| this.emit_relaxed_ldr32s(Size::S64, ret, Location::Memory(addr, 0)),
         )
     }
-    fn i64_atomic_load(
-        &mut self,
-        _addr: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_load unimplemented");
-    }
-    fn i64_atomic_load_8u(
-        &mut self,
-        _addr: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_load_8u unimplemented");
-    }
-    fn i64_atomic_load_16u(
-        &mut self,
-        _addr: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_load_16u unimplemented");
-    }
-    fn i64_atomic_load_32u(
-        &mut self,
-        _addr: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_load_32u unimplemented");
-    }
-    fn i64_save(
+    // i64 atomic Exchange with i64
+    fn i64_atomic_cmpxchg(
         &mut self,
-        target_value: Location,
+        new: Location,
+        cmp: Location,
+        target: Location,
         memarg: &MemoryImmediate,
-        target_addr: Location,
+        ret: Location,
         need_check: bool,
         imported_memories: bool,
         offset: i32,
         heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        self.memory_op(
-            target_addr,
+        self.emit_atomic_cmpxchg(
+            Size::S64,
+            Size::S64,
+            new,
+            cmp,
+            target,
             memarg,
-            false,
-            8,
+            ret,
             need_check,
             imported_memories,
             offset,
             heap_access_oob,
-            |this, addr| this.emit_relaxed_str64(target_value, Location::Memory(addr, 0)),
         )
     }
-    fn i64_save_8(
+    // i64 atomic Exchange with u8
+    fn i64_atomic_cmpxchg_8u(
         &mut self,
-        target_value: Location,
+        new: Location,
+        cmp: Location,
+        target: Location,
         memarg: &MemoryImmediate,
-        target_addr: Location,
+        ret: Location,
         need_check: bool,
         imported_memories: bool,
         offset: i32,
         heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        self.memory_op(
-            target_addr,
+        self.emit_atomic_cmpxchg(
+            Size::S64,
+            Size::S8,
+            new,
+            cmp,
+            target,
             memarg,
-            false,
-            1,
+            ret,
             need_check,
             imported_memories,
             offset,
             heap_access_oob,
-            |this, addr| this.emit_relaxed_str8(target_value, Location::Memory(addr, 0)),
         )
     }
-    fn i64_save_16(
+    // i64 atomic Exchange with u16
+    fn i64_atomic_cmpxchg_16u(
         &mut self,
-        target_value: Location,
+        new: Location,
+        cmp: Location,
+        target: Location,
         memarg: &MemoryImmediate,
-        target_addr: Location,
+        ret: Location,
         need_check: bool,
         imported_memories: bool,
         offset: i32,
         heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        self.memory_op(
-            target_addr,
+        self.emit_atomic_cmpxchg(
+            Size::S64,
+            Size::S16,
+            new,
+            cmp,
+            target,
             memarg,
-            false,
-            2,
+            ret,
             need_check,
             imported_memories,
             offset,
             heap_access_oob,
-            |this, addr| this.emit_relaxed_str16(target_value, Location::Memory(addr, 0)),
         )
     }
-    fn i64_save_32(
+    // i64 atomic Exchange with u32
+    fn i64_atomic_cmpxchg_32u(
         &mut self,
-        target_value: Location,
+        new: Location,
+        cmp: Location,
+        target: Location,
         memarg: &MemoryImmediate,
-        target_addr: Location,
+        ret: Location,
         need_check: bool,
         imported_memories: bool,
         offset: i32,
         heap_access_oob: Label,
     ) -> Result<(), CodegenError> {
-        self.memory_op(
-            target_addr,
+        self.emit_atomic_cmpxchg(
+            Size::S64,
+            Size::S32,
+            new,
+            cmp,
+            target,
             memarg,
-            false,
-            4,
+            ret,
             need_check,
             imported_memories,
             offset,
             heap_access_oob,
-            |this, addr| this.emit_relaxed_str32(target_value, Location::Memory(addr, 0)),
         )
     }
-    fn i64_atomic_save(
-        &mut self,
-        _value: Location,
-        _memarg: &MemoryImmediate,
-        _target_addr: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_save unimplemented");
-    }
-    fn i64_atomic_save_8(
-        &mut self,
-        _value: Location,
-        _memarg: &MemoryImmediate,
-        _target_addr: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_save_8 unimplemented");
-    }
-    fn i64_atomic_save_16(
-        &mut self,
-        _value: Location,
-        _memarg: &MemoryImmediate,
-        _target_addr: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_save_16 unimplemented");
-    }
-    fn i64_atomic_save_32(
-        &mut self,
-        _value: Location,
-        _memarg: &MemoryImmediate,
-        _target_addr: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_save_32 unimplemented");
-    }
-    // i64 atomic Add with i64
-    fn i64_atomic_add(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_add unimplemented");
-    }
-    // i64 atomic Add with u8
-    fn i64_atomic_add_8u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_add_8u unimplemented");
-    }
-    // i64 atomic Add with u16
-    fn i64_atomic_add_16u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_add_16u unimplemented");
-    }
-    // i64 atomic Add with u32
-    fn i64_atomic_add_32u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_add_32u unimplemented");
-    }
-    // i64 atomic Sub with i64
-    fn i64_atomic_sub(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_sub unimplemented");
-    }
-    // i64 atomic Sub with u8
-    fn i64_atomic_sub_8u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_sub_8u unimplemented");
-    }
-    // i64 atomic Sub with u16
-    fn i64_atomic_sub_16u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_sub_16u unimplemented");
-    }
-    // i64 atomic Sub with u32
-    fn i64_atomic_sub_32u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_sub_32u unimplemented");
-    }
-    // i64 atomic And with i64
-    fn i64_atomic_and(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_and unimplemented");
-    }
-    // i64 atomic And with u8
-    fn i64_atomic_and_8u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_and_8u unimplemented");
-    }
-    // i64 atomic And with u16
-    fn i64_atomic_and_16u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_and_16u unimplemented");
-    }
-    // i64 atomic And with u32
-    fn i64_atomic_and_32u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_and_32u unimplemented");
-    }
-    // i64 atomic Or with i64
-    fn i64_atomic_or(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_or unimplemented");
-    }
-    // i64 atomic Or with u8
-    fn i64_atomic_or_8u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_or_8u unimplemented");
-    }
-    // i64 atomic Or with u16
-    fn i64_atomic_or_16u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_or_16u unimplemented");
-    }
-    // i64 atomic Or with u32
-    fn i64_atomic_or_32u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_or_32u unimplemented");
-    }
-    // i64 atomic xor with i64
-    fn i64_atomic_xor(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_xor unimplemented");
-    }
-    // i64 atomic xor with u8
-    fn i64_atomic_xor_8u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_xor_8u unimplemented");
-    }
-    // i64 atomic xor with u16
-    fn i64_atomic_xor_16u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_xor_16u unimplemented");
-    }
-    // i64 atomic xor with u32
-    fn i64_atomic_xor_32u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_xor_32u unimplemented");
-    }
-    // i64 atomic Exchange with i64
-    fn i64_atomic_xchg(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_xchg unimplemented");
-    }
-    // i64 atomic Exchange with u8
-    fn i64_atomic_xchg_8u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_xchg_8u unimplemented");
-    }
-    // i64 atomic Exchange with u16
-    fn i64_atomic_xchg_16u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_xchg_16u unimplemented");
-    }
-    // i64 atomic Exchange with u32
-    fn i64_atomic_xchg_32u(
-        &mut self,
-        _loc: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_xchg_32u unimplemented");
-    }
-    // i64 atomic Exchange with i64
-    fn i64_atomic_cmpxchg(
-        &mut self,
-        _new: Location,
-        _cmp: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_cmpxchg unimplemented");
-    }
-    // i64 atomic Exchange with u8
-    fn i64_atomic_cmpxchg_8u(
-        &mut self,
-        _new: Location,
-        _cmp: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_cmpxchg_8u unimplemented");
-    }
-    // i64 atomic Exchange with u16
-    fn i64_atomic_cmpxchg_16u(
-        &mut self,
-        _new: Location,
-        _cmp: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_cmpxchg_16u unimplemented");
-    }
-    // i64 atomic Exchange with u32
-    fn i64_atomic_cmpxchg_32u(
-        &mut self,
-        _new: Location,
-        _cmp: Location,
-        _target: Location,
-        _memarg: &MemoryImmediate,
-        _ret: Location,
-        _need_check: bool,
-        _imported_memories: bool,
-        _offset: i32,
-        _heap_access_oob: Label,
-    ) -> Result<(), CodegenError> {
-        codegen_error!("singlepass i64_atomic_cmpxchg_32u unimplemented");
-    }
 
     fn f32_load(
         &mut self,
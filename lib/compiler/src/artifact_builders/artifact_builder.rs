@@ -6,14 +6,18 @@ use super::trampoline::{libcall_trampoline_len, make_libcall_trampolines};
 use crate::ArtifactCreate;
 use crate::EngineInner;
 use crate::Features;
+#[cfg(feature = "compiler")]
+use crate::ModuleTranslationState;
 use crate::{ModuleEnvironment, ModuleMiddlewareChain};
 use enumset::EnumSet;
 use std::mem;
-use wasmer_types::entity::PrimaryMap;
 #[cfg(feature = "compiler")]
-use wasmer_types::CompileModuleInfo;
+use std::ops::Range;
+use wasmer_types::entity::PrimaryMap;
 use wasmer_types::MetadataHeader;
 use wasmer_types::SerializeError;
+#[cfg(feature = "compiler")]
+use wasmer_types::{Compilation, CompileModuleInfo, DataInitializer, InterpretedFunction};
 use wasmer_types::{
     CompileError, CpuFeature, CustomSection, Dwarf, FunctionIndex, LocalFunctionIndex, MemoryIndex,
     MemoryStyle, ModuleInfo, OwnedDataInitializer, Relocation, SectionIndex, SignatureIndex,
@@ -65,21 +69,193 @@ impl ArtifactBuild {
             table_styles,
         };
 
+        // SAFETY: Calling `unwrap` is correct since
+        // `environ.translate()` above will write some data into
+        // `module_translation_state`.
+        let module_translation_state = translation.module_translation_state.as_ref().unwrap();
+        let function_body_inputs = translation.function_body_inputs;
+
         // Compile the Module
+        let compilation = inner_engine.in_compile_pool(|| {
+            compiler.compile_module(
+                target,
+                &compile_info,
+                module_translation_state,
+                function_body_inputs,
+            )
+        })?;
+
+        Ok(Self::from_compilation(
+            compile_info,
+            compilation,
+            &translation.data_initializers,
+            target,
+        ))
+    }
+
+    /// Translate a data buffer into an `ArtifactBuild` whose functions
+    /// are left to be compiled one by one later on.
+    ///
+    /// The functions get empty bodies, to be replaced with
+    /// [`ArtifactBuild::set_function_bodies`]. Returns the translation
+    /// state of the module and where the bodies of its functions are in
+    /// `data`, which are needed to compile them.
+    #[cfg(feature = "compiler")]
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn new_lazy(
+        inner_engine: &mut EngineInner,
+        data: &[u8],
+        target: &Target,
+        memory_styles: PrimaryMap<MemoryIndex, MemoryStyle>,
+        table_styles: PrimaryMap<TableIndex, TableStyle>,
+    ) -> Result<
+        (
+            Self,
+            ModuleTranslationState,
+            PrimaryMap<LocalFunctionIndex, Range<usize>>,
+        ),
+        CompileError,
+    > {
+        let environ = ModuleEnvironment::new();
+        let features = inner_engine.features().clone();
+
+        let translation = environ.translate(data).map_err(CompileError::Wasm)?;
+
+        let compiler = inner_engine.compiler()?;
+
+        let mut module = translation.module;
+        let middlewares = compiler.get_middlewares();
+        middlewares.apply_on_module_info(&mut module);
+
+        let compile_info = CompileModuleInfo {
+            module,
+            features,
+            memory_styles,
+            table_styles,
+        };
+
+        // Only the trampolines and the sections they need get compiled.
+        let module_translation_state = translation.module_translation_state.unwrap();
         let compilation = compiler.compile_module(
             target,
             &compile_info,
-            // SAFETY: Calling `unwrap` is correct since
-            // `environ.translate()` above will write some data into
-            // `module_translation_state`.
-            translation.module_translation_state.as_ref().unwrap(),
-            translation.function_body_inputs,
+            &module_translation_state,
+            PrimaryMap::new(),
         )?;
+
+        let bodies = translation
+            .function_body_inputs
+            .values()
+            .map(|input| input.module_offset..input.module_offset + input.data.len())
+            .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+
+        let mut artifact = Self::from_compilation(
+            compile_info,
+            compilation,
+            &translation.data_initializers,
+            target,
+        );
+        let functions = &mut artifact.serializable.compilation;
+        functions.function_bodies = bodies
+            .keys()
+            .map(|_| FunctionBody {
+                body: vec![],
+                unwind_info: None,
+            })
+            .collect();
+        functions.function_relocations = bodies.keys().map(|_| vec![]).collect();
+        functions.function_frame_info = bodies
+            .keys()
+            .map(|_| CompiledFunctionFrameInfo::default())
+            .collect();
+        Ok((artifact, module_translation_state, bodies))
+    }
+
+    /// Translate a data buffer for the interpreter of the VM, returning
+    /// the `ArtifactBuild` of the module along with its functions.
+    ///
+    /// Nothing is compiled to machine code: the functions get empty
+    /// bodies, and the module no trampolines.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn new_interpreted(
+        inner_engine: &mut EngineInner,
+        data: &[u8],
+        target: &Target,
+        memory_styles: PrimaryMap<MemoryIndex, MemoryStyle>,
+        table_styles: PrimaryMap<TableIndex, TableStyle>,
+    ) -> Result<(Self, PrimaryMap<LocalFunctionIndex, InterpretedFunction>), CompileError> {
+        let environ = ModuleEnvironment::new();
+        let features = inner_engine.features().clone();
+
+        let translation = environ.translate(data).map_err(CompileError::Wasm)?;
+
+        let compiler = inner_engine.compiler()?;
+
+        let mut module = translation.module;
+        let middlewares = compiler.get_middlewares();
+        middlewares.apply_on_module_info(&mut module);
+
+        let compile_info = CompileModuleInfo {
+            module,
+            features,
+            memory_styles,
+            table_styles,
+        };
+
+        let module_translation_state = translation.module_translation_state.as_ref().unwrap();
+        let function_body_inputs = translation.function_body_inputs;
+        let functions = inner_engine.in_compile_pool(|| {
+            compiler.interpret_module(
+                target,
+                &compile_info,
+                module_translation_state,
+                function_body_inputs,
+            )
+        })?;
+
+        let compilation = Compilation::new(
+            PrimaryMap::new(),
+            PrimaryMap::new(),
+            PrimaryMap::new(),
+            PrimaryMap::new(),
+            None,
+        );
+        let mut artifact = Self::from_compilation(
+            compile_info,
+            compilation,
+            &translation.data_initializers,
+            target,
+        );
+        let compilation = &mut artifact.serializable.compilation;
+        compilation.function_bodies = functions
+            .keys()
+            .map(|_| FunctionBody {
+                body: vec![],
+                unwind_info: None,
+            })
+            .collect();
+        compilation.function_relocations = functions.keys().map(|_| vec![]).collect();
+        compilation.function_frame_info = functions
+            .values()
+            .map(|function| CompiledFunctionFrameInfo {
+                traps: vec![],
+                address_map: function.address_map.clone(),
+            })
+            .collect();
+        Ok((artifact, functions))
+    }
+
+    #[cfg(feature = "compiler")]
+    fn from_compilation(
+        compile_info: CompileModuleInfo,
+        compilation: Compilation,
+        data_initializers: &[DataInitializer<'_>],
+        target: &Target,
+    ) -> Self {
         let function_call_trampolines = compilation.get_function_call_trampolines();
         let dynamic_function_trampolines = compilation.get_dynamic_function_trampolines();
 
-        let data_initializers = translation
-            .data_initializers
+        let data_initializers = data_initializers
             .iter()
             .map(OwnedDataInitializer::new)
             .collect::<Vec<_>>()
@@ -112,8 +288,9 @@ impl ArtifactBuild {
             compile_info,
             data_initializers,
             cpu_features: target.cpu_features().as_u64(),
+            target_triple: target.triple().to_string(),
         };
-        Ok(Self { serializable })
+        Self { serializable }
     }
 
     /// Compile a data buffer into a `ArtifactBuild`, which may then be instantiated.
@@ -136,6 +313,16 @@ impl ArtifactBuild {
         Self { serializable }
     }
 
+    /// Replace the bodies of the functions, which keep their
+    /// relocations and frame information.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn set_function_bodies(
+        &mut self,
+        function_bodies: PrimaryMap<LocalFunctionIndex, FunctionBody>,
+    ) {
+        self.serializable.compilation.function_bodies = function_bodies;
+    }
+
     /// Get Functions Bodies ref
     pub fn get_function_bodies_ref(&self) -> &PrimaryMap<LocalFunctionIndex, FunctionBody> {
         &self.serializable.compilation.function_bodies
@@ -185,6 +372,11 @@ impl ArtifactBuild {
     pub fn get_frame_info_ref(&self) -> &PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo> {
         &self.serializable.compilation.function_frame_info
     }
+
+    /// Get the target the code was compiled for, if its triple is valid
+    pub fn target(&self) -> Option<Target> {
+        self.serializable.target()
+    }
 }
 
 impl ArtifactCreate for ArtifactBuild {
@@ -3,17 +3,20 @@
 
 use crate::lib::std::boxed::Box;
 use crate::lib::std::sync::Arc;
+use crate::lib::std::vec::Vec;
 use crate::translator::ModuleMiddleware;
 use crate::FunctionBodyData;
 use crate::ModuleTranslationState;
-use wasmer_types::compilation::function::Compilation;
+use wasmer_types::compilation::function::{Compilation, CompiledFunction};
+use wasmer_types::compilation::interpreter::InterpretedFunction;
 use wasmer_types::compilation::module::CompileModuleInfo;
+use wasmer_types::compilation::section::CustomSection;
 use wasmer_types::compilation::symbols::SymbolRegistry;
 use wasmer_types::compilation::target::Target;
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::error::CompileError;
 use wasmer_types::{Features, LocalFunctionIndex};
-use wasmparser::{Validator, WasmFeatures};
+use wasmparser::{Chunk, Parser, Payload, ValidPayload, Validator, WasmFeatures};
 
 /// The compiler configuration options.
 pub trait CompilerConfig {
@@ -77,7 +80,7 @@ where
 }
 
 /// An implementation of a Compiler from parsed WebAssembly module to Compiled native code.
-pub trait Compiler: Send {
+pub trait Compiler: Send + Sync {
     /// Validates a module.
     ///
     /// It returns the a succesful Result in case is valid, `CompileError` in case is not.
@@ -87,25 +90,7 @@ pub trait Compiler: Send {
         data: &'data [u8],
     ) -> Result<(), CompileError> {
         let mut validator = Validator::new();
-        let wasm_features = WasmFeatures {
-            bulk_memory: features.bulk_memory,
-            threads: features.threads,
-            reference_types: features.reference_types,
-            multi_value: features.multi_value,
-            simd: features.simd,
-            tail_call: features.tail_call,
-            module_linking: features.module_linking,
-            multi_memory: features.multi_memory,
-            memory64: features.memory64,
-            exceptions: features.exceptions,
-            deterministic_only: false,
-            extended_const: features.extended_const,
-            relaxed_simd: features.relaxed_simd,
-            mutable_global: true,
-            saturating_float_to_int: true,
-            sign_extension: true,
-        };
-        validator.wasm_features(wasm_features);
+        validator.wasm_features(wasm_features(features));
         validator
             .validate_all(data)
             .map_err(|e| CompileError::Validate(format!("{}", e)))?;
@@ -124,6 +109,54 @@ pub trait Compiler: Send {
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
     ) -> Result<Compilation, CompileError>;
 
+    /// Whether the compiler implements [`Compiler::compile_function`],
+    /// allowing engines to compile functions lazily, on their first call.
+    fn supports_lazy_compilation(&self) -> bool {
+        false
+    }
+
+    /// Compiles a single function of a parsed module.
+    ///
+    /// Relocations to other local functions target their entry points
+    /// as for [`Compiler::compile_module`]. If the function is described
+    /// by DWARF unwind information, it comes with an `.eh_frame` section
+    /// holding it.
+    fn compile_function<'data, 'module>(
+        &self,
+        _target: &Target,
+        _module: &'module CompileModuleInfo,
+        _module_translation: &ModuleTranslationState,
+        _index: LocalFunctionIndex,
+        _input: &FunctionBodyData<'data>,
+    ) -> Result<(CompiledFunction, Option<CustomSection>), CompileError> {
+        Err(CompileError::UnsupportedFeature(
+            "compiling functions one by one".to_string(),
+        ))
+    }
+
+    /// Whether the compiler translates modules for the interpreter of the
+    /// VM, through [`Compiler::interpret_module`], instead of compiling
+    /// them to machine code.
+    fn is_interpreter(&self) -> bool {
+        false
+    }
+
+    /// Translates the functions of a parsed module into the instructions
+    /// of the interpreter of the VM, for platforms where memory can't be
+    /// made executable.
+    fn interpret_module<'data, 'module>(
+        &self,
+        _target: &Target,
+        _module: &'module CompileModuleInfo,
+        _module_translation: &ModuleTranslationState,
+        // The list of function bodies
+        _function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
+    ) -> Result<PrimaryMap<LocalFunctionIndex, InterpretedFunction>, CompileError> {
+        Err(CompileError::UnsupportedFeature(
+            "interpreting modules".to_string(),
+        ))
+    }
+
     /// Compiles a module into a native object file.
     ///
     /// It returns the bytes as a `&[u8]` or a [`CompileError`].
@@ -141,6 +174,113 @@ pub trait Compiler: Send {
         None
     }
 
+    /// A string identifying the compiler and the options changing the code
+    /// it generates, like its version and optimization level.
+    ///
+    /// Compilers with the same id compile modules to interchangeable
+    /// artifacts, which is why caches use it in their keys. Middlewares
+    /// aren't part of it, the engine adds their own ids.
+    ///
+    /// Defaults to the name of the compiler type, which compilers with
+    /// options should extend.
+    fn deterministic_id(&self) -> String {
+        std::any::type_name::<Self>().to_string()
+    }
+
     /// Get the middlewares for this compiler
     fn get_middlewares(&self) -> &[Arc<dyn ModuleMiddleware>];
 }
+
+/// The wasmparser features matching `features`.
+fn wasm_features(features: &Features) -> WasmFeatures {
+    WasmFeatures {
+        bulk_memory: features.bulk_memory,
+        threads: features.threads,
+        reference_types: features.reference_types,
+        multi_value: features.multi_value,
+        simd: features.simd,
+        tail_call: features.tail_call,
+        module_linking: features.module_linking,
+        multi_memory: features.multi_memory,
+        memory64: features.memory64,
+        exceptions: features.exceptions,
+        deterministic_only: false,
+        extended_const: features.extended_const,
+        relaxed_simd: features.relaxed_simd,
+        mutable_global: true,
+        saturating_float_to_int: true,
+        sign_extension: true,
+    }
+}
+
+/// Validates a WebAssembly binary given in chunks, as it is downloaded
+/// for example, so that the sections and function bodies are validated
+/// as soon as they are complete rather than once the whole binary is.
+///
+/// It validates like [`Compiler::validate_module`] does.
+pub struct StreamingValidator {
+    parser: Parser,
+    validator: Validator,
+    /// The binary received so far.
+    binary: Vec<u8>,
+    /// How many bytes of `binary` are validated.
+    validated: usize,
+    /// Whether the end of the module was reached.
+    complete: bool,
+}
+
+impl StreamingValidator {
+    /// Creates a validator for modules using the given features.
+    pub fn new(features: &Features) -> Self {
+        let mut validator = Validator::new();
+        validator.wasm_features(wasm_features(features));
+        Self {
+            parser: Parser::new(0),
+            validator,
+            binary: Vec::new(),
+            validated: 0,
+            complete: false,
+        }
+    }
+
+    /// Appends `data` to the binary, validating what it completes.
+    pub fn push(&mut self, data: &[u8]) -> Result<(), CompileError> {
+        self.binary.extend_from_slice(data);
+        self.validate(false)
+    }
+
+    /// Validates the rest of the binary, and returns it if it is a
+    /// complete and valid module.
+    pub fn finish(mut self) -> Result<Vec<u8>, CompileError> {
+        self.validate(true)?;
+        if !self.complete || self.validated != self.binary.len() {
+            return Err(CompileError::Validate(
+                "the module is incomplete or has trailing bytes".to_string(),
+            ));
+        }
+        Ok(self.binary)
+    }
+
+    fn validate(&mut self, eof: bool) -> Result<(), CompileError> {
+        while !self.complete {
+            let data = &self.binary[self.validated..];
+            let (consumed, payload) = match self.parser.parse(data, eof) {
+                Ok(Chunk::NeedMoreData(_)) => return Ok(()),
+                Ok(Chunk::Parsed { consumed, payload }) => (consumed, payload),
+                Err(e) => return Err(CompileError::Validate(format!("{}", e))),
+            };
+            if let Payload::End = payload {
+                self.complete = true;
+            }
+            match self.validator.payload(&payload) {
+                Ok(ValidPayload::Func(mut validator, body)) => validator
+                    .validate(&body)
+                    .map_err(|e| CompileError::Validate(format!("{}", e)))?,
+                Ok(_) => {}
+                Err(e) => return Err(CompileError::Validate(format!("{}", e))),
+            }
+            self.validated += consumed;
+        }
+        Ok(())
+    }
+}
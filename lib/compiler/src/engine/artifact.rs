@@ -1,14 +1,22 @@
 //! Define `Artifact`, based on `ArtifactBuild`
 //! to allow compiling and instantiating to be done as separate steps.
 
+#[cfg(feature = "debug-info")]
+use crate::engine::debug_info::{build_image, CompiledFunction};
+#[cfg(feature = "debug-info")]
+use crate::engine::gdb_jit::GdbJitImageRegistration;
+#[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+use crate::engine::lazy::{start_tier_up, LazyCompilation, LinkedModule};
 use crate::engine::link::link_module;
+use crate::engine::metrics::ModuleMetrics;
+use crate::engine::perf::{register_function, PerfStrategy};
 use crate::ArtifactBuild;
 use crate::ArtifactCreate;
 use crate::Features;
 use crate::ModuleEnvironment;
 use crate::{
     register_frame_info, resolve_imports, FunctionExtent, GlobalFrameInfoRegistration,
-    InstantiationError, RuntimeError, Tunables,
+    InstantiationError, LinkError, PooledTunables, RuntimeError, Tunables,
 };
 #[cfg(feature = "static-artifact-create")]
 use crate::{Compiler, FunctionBodyData, ModuleTranslationState};
@@ -18,23 +26,44 @@ use enumset::EnumSet;
 use std::mem;
 use std::sync::Arc;
 use std::sync::Mutex;
+#[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+use std::thread::JoinHandle;
+#[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+use std::time::Duration;
+#[cfg(feature = "compiler")]
+use std::time::Instant;
 #[cfg(feature = "static-artifact-create")]
 use wasmer_object::{emit_compilation, emit_data, get_object_for_target, Object};
 #[cfg(any(feature = "static-artifact-create", feature = "static-artifact-load"))]
 use wasmer_types::compilation::symbols::ModuleMetadata;
 use wasmer_types::entity::{BoxedSlice, PrimaryMap};
+#[cfg(any(
+    feature = "static-artifact-create",
+    all(feature = "compiler", target_arch = "x86_64", unix)
+))]
+use wasmer_types::CompileModuleInfo;
+#[cfg(feature = "compiler")]
+use wasmer_types::InterpretedFunction;
 use wasmer_types::MetadataHeader;
 #[cfg(feature = "static-artifact-load")]
 use wasmer_types::SerializableCompilation;
+#[cfg(feature = "static-artifact-create")]
+use wasmer_types::Target;
+use wasmer_types::Triple;
 use wasmer_types::{
     CompileError, CpuFeature, DataInitializer, DeserializeError, FunctionIndex, LocalFunctionIndex,
-    MemoryIndex, ModuleInfo, OwnedDataInitializer, SerializableModule, SerializeError,
-    SignatureIndex, TableIndex,
+    LocalMemoryIndex, MemoryIndex, ModuleInfo, OwnedDataInitializer, SectionIndex,
+    SerializableModule, SerializeError, SignatureIndex, TableIndex,
+};
+#[cfg(all(feature = "compiler", not(target_arch = "wasm32")))]
+use wasmer_vm::{wasmer_vm_interpreter_trampoline, VMFunctionBody};
+use wasmer_vm::{
+    FunctionBodyPtr, MemoryStyle, SectionBodyPtr, TableStyle, VMSharedSignatureIndex, VMTrampoline,
+};
+use wasmer_vm::{
+    InstanceAllocator, InstanceHandle, InstancePool, MemoryImage, StoreObjects, TrapHandlerFn,
+    VMExtern,
 };
-#[cfg(feature = "static-artifact-create")]
-use wasmer_types::{CompileModuleInfo, Target};
-use wasmer_vm::{FunctionBodyPtr, MemoryStyle, TableStyle, VMSharedSignatureIndex, VMTrampoline};
-use wasmer_vm::{InstanceAllocator, InstanceHandle, StoreObjects, TrapHandlerFn, VMExtern};
 
 /// A compiled wasm module, ready to be instantiated.
 pub struct Artifact {
@@ -46,8 +75,30 @@ pub struct Artifact {
     /// Some(_) only if this is not a deserialized static artifact
     frame_info_registration: Option<Mutex<Option<GlobalFrameInfoRegistration>>>,
     finished_function_lengths: BoxedSlice<LocalFunctionIndex, usize>,
+    /// Some(_) only if this is a deserialized artifact, whose memories are
+    /// initialized from copy-on-write images built on first instantiation
+    memory_images: Option<Mutex<Option<Arc<MemoryImages>>>>,
+    /// The compilation metrics, shared with the lazy compilation of the
+    /// functions
+    metrics: Arc<Mutex<ModuleMetrics>>,
+    /// Some(_) only if the functions are compiled on their first call
+    #[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+    lazy: Option<Arc<LazyCompilation>>,
+    /// The thread recompiling the functions with the optimized tier, if
+    /// it hasn't been waited for
+    #[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+    tier_up: Mutex<Option<JoinHandle<Result<(), CompileError>>>>,
+    /// Some(_) only if the functions are run by the interpreter of the
+    /// VM, which their function references point to
+    #[cfg(feature = "compiler")]
+    interpreted: Option<Box<[InterpretedFunction]>>,
+    /// The code of the functions as registered with native debuggers
+    #[cfg(feature = "debug-info")]
+    debug_registration: Option<GdbJitImageRegistration>,
 }
 
+type MemoryImages = PrimaryMap<LocalMemoryIndex, Option<MemoryImage>>;
+
 #[cfg(feature = "static-artifact-create")]
 pub type PrefixerFn = Box<dyn Fn(&[u8]) -> String + Send>;
 
@@ -61,6 +112,22 @@ impl Artifact {
         engine: &Engine,
         data: &[u8],
         tunables: &dyn Tunables,
+    ) -> Result<Self, CompileError> {
+        let start = Instant::now();
+        let artifact = Self::compile(engine, data, tunables)?;
+        artifact
+            .metrics
+            .lock()
+            .unwrap()
+            .add_compile_time(start.elapsed());
+        Ok(artifact)
+    }
+
+    #[cfg(feature = "compiler")]
+    fn compile(
+        engine: &Engine,
+        data: &[u8],
+        tunables: &dyn Tunables,
     ) -> Result<Self, CompileError> {
         let environ = ModuleEnvironment::new();
         let mut inner_engine = engine.inner_mut();
@@ -77,6 +144,36 @@ impl Artifact {
             .map(|table_type| tunables.table_style(table_type))
             .collect();
 
+        #[cfg(not(target_arch = "wasm32"))]
+        if inner_engine.compiler()?.is_interpreter() {
+            return Self::new_interpreted(
+                engine,
+                &mut inner_engine,
+                data,
+                memory_styles,
+                table_styles,
+            );
+        }
+
+        #[cfg(all(target_arch = "x86_64", unix))]
+        {
+            let tiered = engine
+                .optimized_tier()
+                .map_or(false, |compiler| compiler.supports_lazy_compilation());
+            if (engine.lazy_compilation() || tiered)
+                && engine.target().is_native()
+                && inner_engine.compiler()?.supports_lazy_compilation()
+            {
+                return Self::new_lazy(
+                    engine,
+                    &mut inner_engine,
+                    data,
+                    memory_styles,
+                    table_styles,
+                );
+            }
+        }
+
         let artifact = ArtifactBuild::new(
             &mut inner_engine,
             data,
@@ -85,7 +182,226 @@ impl Artifact {
             table_styles,
         )?;
 
-        Self::from_parts(&mut inner_engine, artifact)
+        if !engine.target().is_native() {
+            return Ok(Self::unlinked(artifact));
+        }
+        #[allow(unused_mut)]
+        let mut artifact = Self::from_parts(&mut inner_engine, artifact)?;
+        if let Some(strategy) = engine.perf() {
+            artifact.describe_to_perf(strategy);
+        }
+        #[cfg(feature = "debug-info")]
+        if engine.debug_info() {
+            artifact.register_debug_info(engine, data);
+        }
+        Ok(artifact)
+    }
+
+    /// Registers the code of the functions with native debuggers, along
+    /// with the DWARF describing them.
+    #[cfg(feature = "debug-info")]
+    fn register_debug_info(&mut self, engine: &Engine, data: &[u8]) {
+        let module_info = self.artifact.create_module_info();
+        let frame_infos = self.artifact.get_frame_info_ref();
+        let functions = self
+            .finished_functions
+            .iter()
+            .map(|(index, ptr)| CompiledFunction {
+                index: module_info.func_index(index),
+                address: **ptr as usize,
+                length: self.finished_function_lengths[index],
+                address_map: &frame_infos[index].address_map,
+            })
+            .collect::<Vec<_>>();
+        self.debug_registration =
+            build_image(engine.target().triple(), &module_info, data, &functions)
+                .map(GdbJitImageRegistration::register);
+    }
+
+    /// Wrap an `ArtifactBuild` compiled for another target. Its code is
+    /// not loaded nor linked, as the relocations can only be applied on
+    /// the target, so the artifact can be serialized but not instantiated.
+    #[cfg(feature = "compiler")]
+    fn unlinked(artifact: ArtifactBuild) -> Self {
+        Self {
+            artifact,
+            finished_functions: PrimaryMap::new().into_boxed_slice(),
+            finished_function_call_trampolines: PrimaryMap::new().into_boxed_slice(),
+            finished_dynamic_function_trampolines: PrimaryMap::new().into_boxed_slice(),
+            signatures: PrimaryMap::new().into_boxed_slice(),
+            frame_info_registration: None,
+            finished_function_lengths: PrimaryMap::new().into_boxed_slice(),
+            memory_images: None,
+            metrics: Default::default(),
+            #[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+            lazy: None,
+            #[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+            tier_up: Mutex::new(None),
+            #[cfg(feature = "compiler")]
+            interpreted: None,
+            #[cfg(feature = "debug-info")]
+            debug_registration: None,
+        }
+    }
+
+    /// Prepare a data buffer to be instantiated, leaving its functions to
+    /// be compiled on their first call, and to be recompiled in the
+    /// background if the engine has an optimized tier.
+    #[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+    fn new_lazy(
+        engine: &Engine,
+        engine_inner: &mut EngineInner,
+        data: &[u8],
+        memory_styles: PrimaryMap<MemoryIndex, MemoryStyle>,
+        table_styles: PrimaryMap<TableIndex, TableStyle>,
+    ) -> Result<Self, CompileError> {
+        let (mut artifact, module_translation, bodies) = ArtifactBuild::new_lazy(
+            engine_inner,
+            data,
+            engine.target(),
+            memory_styles,
+            table_styles,
+        )?;
+        let compile_info = CompileModuleInfo {
+            module: artifact.create_module_info(),
+            features: artifact.features().clone(),
+            memory_styles: artifact.memory_styles().clone(),
+            table_styles: artifact.table_styles().clone(),
+        };
+        let metrics = Arc::new(Mutex::new(ModuleMetrics::default()));
+        let (lazy, stubs) = LazyCompilation::new(
+            engine,
+            compile_info,
+            module_translation,
+            data,
+            bodies,
+            metrics.clone(),
+        );
+        artifact.set_function_bodies(stubs);
+
+        let libcall_trampolines = artifact.get_libcall_trampolines();
+        let libcall_trampoline_len = artifact.get_libcall_trampoline_len();
+        let mut artifact = Self::link(engine_inner, artifact, |functions, custom_sections| {
+            lazy.set_linked(LinkedModule {
+                functions: functions.clone(),
+                custom_sections: custom_sections.clone(),
+                libcall_trampolines,
+                libcall_trampoline_len,
+            })
+        })?;
+        if let Some(compiler) = engine
+            .optimized_tier_compiler()
+            .filter(|compiler| compiler.supports_lazy_compilation())
+        {
+            let tier_up = start_tier_up(&lazy, compiler)
+                .map_err(|error| CompileError::Resource(error.to_string()))?;
+            artifact.tier_up = Mutex::new(Some(tier_up));
+            metrics
+                .lock()
+                .unwrap()
+                .add_optimized_compile_time(Duration::ZERO);
+        }
+        artifact.metrics = metrics;
+        artifact.lazy = Some(lazy);
+        Ok(artifact)
+    }
+
+    /// Translate a data buffer for the interpreter of the VM. No code is
+    /// allocated: all the functions of the module are called through
+    /// the trampoline of the interpreter.
+    #[cfg(all(feature = "compiler", not(target_arch = "wasm32")))]
+    fn new_interpreted(
+        engine: &Engine,
+        engine_inner: &mut EngineInner,
+        data: &[u8],
+        memory_styles: PrimaryMap<MemoryIndex, MemoryStyle>,
+        table_styles: PrimaryMap<TableIndex, TableStyle>,
+    ) -> Result<Self, CompileError> {
+        let (artifact, functions) = ArtifactBuild::new_interpreted(
+            engine_inner,
+            data,
+            engine.target(),
+            memory_styles,
+            table_styles,
+        )?;
+        let module_info = artifact.create_module_info();
+        // The functions are moved into the artifact below, which doesn't
+        // move their boxed contents, and are dropped along with it.
+        let functions: Box<[InterpretedFunction]> = functions
+            .into_iter()
+            .map(|(_, function)| function)
+            .collect();
+
+        let finished_functions = functions
+            .iter()
+            .map(|function| {
+                FunctionBodyPtr(function as *const InterpretedFunction as *const VMFunctionBody)
+            })
+            .collect::<PrimaryMap<LocalFunctionIndex, _>>()
+            .into_boxed_slice();
+        let finished_function_lengths = finished_functions
+            .values()
+            .map(|_| 0)
+            .collect::<PrimaryMap<LocalFunctionIndex, usize>>()
+            .into_boxed_slice();
+        let finished_function_call_trampolines = module_info
+            .signatures
+            .values()
+            .map(|_| wasmer_vm_interpreter_trampoline as VMTrampoline)
+            .collect::<PrimaryMap<SignatureIndex, _>>()
+            .into_boxed_slice();
+        // Imported functions are always called through their own
+        // trampolines, never through a native call.
+        let finished_dynamic_function_trampolines = (0..module_info.num_imported_functions)
+            .map(|_| FunctionBodyPtr(std::ptr::null()))
+            .collect::<PrimaryMap<FunctionIndex, _>>()
+            .into_boxed_slice();
+        let signatures = {
+            let signature_registry = engine_inner.signatures();
+            module_info
+                .signatures
+                .values()
+                .map(|sig| signature_registry.register(sig))
+                .collect::<PrimaryMap<SignatureIndex, _>>()
+                .into_boxed_slice()
+        };
+
+        // The interpreter reports the instructions running when it traps
+        // by their addresses, so the frame info covers their code.
+        let code_extents = functions
+            .iter()
+            .map(|function| FunctionExtent {
+                ptr: FunctionBodyPtr(function.code.as_ptr() as *const VMFunctionBody),
+                length: std::mem::size_of_val(&*function.code),
+            })
+            .collect::<PrimaryMap<LocalFunctionIndex, _>>()
+            .into_boxed_slice();
+        let frame_info_registration = register_frame_info(
+            module_info,
+            &code_extents,
+            artifact.get_frame_info_ref().clone(),
+        );
+
+        Ok(Self {
+            artifact,
+            finished_functions,
+            finished_function_call_trampolines,
+            finished_dynamic_function_trampolines,
+            signatures,
+            frame_info_registration: frame_info_registration
+                .map(|registration| Mutex::new(Some(registration))),
+            finished_function_lengths,
+            memory_images: None,
+            metrics: Default::default(),
+            #[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+            lazy: None,
+            #[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+            tier_up: Mutex::new(None),
+            #[cfg(feature = "compiler")]
+            interpreted: Some(functions),
+            #[cfg(feature = "debug-info")]
+            debug_registration: None,
+        })
     }
 
     /// Compile a data buffer into a `ArtifactBuild`, which may then be instantiated.
@@ -124,15 +440,85 @@ impl Artifact {
         let metadata_slice = Self::get_byte_slice(metadata_slice, 0, metadata_len)?;
 
         let serializable = SerializableModule::deserialize(metadata_slice)?;
+        match serializable.target() {
+            Some(target) if target.is_native() => {}
+            Some(target) => {
+                return Err(DeserializeError::Incompatible(format!(
+                    "The provided bytes were compiled for `{}`, which can't run on `{}`",
+                    target.triple(),
+                    Triple::host()
+                )));
+            }
+            None => {
+                return Err(DeserializeError::CorruptedBinary(format!(
+                    "invalid target triple `{}`",
+                    serializable.target_triple
+                )));
+            }
+        }
         let artifact = ArtifactBuild::from_serializable(serializable);
+        Self::validate_for_engine(engine, &artifact)?;
         let mut inner_engine = engine.inner_mut();
-        Self::from_parts(&mut inner_engine, artifact).map_err(DeserializeError::Compiler)
+        let mut artifact =
+            Self::from_parts(&mut inner_engine, artifact).map_err(DeserializeError::Compiler)?;
+        artifact.memory_images = Some(Mutex::new(None));
+        if let Some(strategy) = engine.perf() {
+            artifact.describe_to_perf(strategy);
+        }
+        Ok(artifact)
+    }
+
+    /// Checks that a deserialized artifact can run on the host and was
+    /// compiled with the features of the engine, so that an artifact
+    /// compiled with other settings is rejected when it's loaded rather
+    /// than run.
+    fn validate_for_engine(
+        engine: &Engine,
+        artifact: &ArtifactBuild,
+    ) -> Result<(), DeserializeError> {
+        let host_cpu_features = CpuFeature::for_host();
+        if !host_cpu_features.is_superset(artifact.cpu_features()) {
+            return Err(DeserializeError::Incompatible(format!(
+                "The provided bytes were compiled for CPU features the host doesn't support: {}",
+                CpuFeature::format_set(artifact.cpu_features().difference(host_cpu_features))
+            )));
+        }
+        // Headless engines run artifacts compiled with any features
+        #[cfg(feature = "compiler")]
+        {
+            let inner = engine.inner();
+            if inner.compiler().is_ok() {
+                let missing = artifact.features().difference(inner.features());
+                if !missing.is_empty() {
+                    return Err(DeserializeError::Incompatible(format!(
+                        "The provided bytes were compiled with features the engine doesn't enable: {}",
+                        missing.join(", ")
+                    )));
+                }
+            }
+        }
+        #[cfg(not(feature = "compiler"))]
+        let _ = engine;
+        Ok(())
     }
 
     /// Construct a `ArtifactBuild` from component parts.
     pub fn from_parts(
         engine_inner: &mut EngineInner,
         artifact: ArtifactBuild,
+    ) -> Result<Self, CompileError> {
+        Self::link(engine_inner, artifact, |_, _| {})
+    }
+
+    /// Allocate and link the code of `artifact`, calling `on_linked` with
+    /// where its functions and custom sections were allocated.
+    fn link(
+        engine_inner: &mut EngineInner,
+        artifact: ArtifactBuild,
+        on_linked: impl FnOnce(
+            &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+            &PrimaryMap<SectionIndex, SectionBodyPtr>,
+        ),
     ) -> Result<Self, CompileError> {
         let module_info = artifact.create_module_info();
         let (
@@ -157,6 +543,7 @@ impl Artifact {
             artifact.get_libcall_trampolines(),
             artifact.get_libcall_trampoline_len(),
         );
+        on_linked(&finished_functions, &custom_sections);
 
         // Compute indices into the shared signature table.
         let signatures = {
@@ -210,9 +597,34 @@ impl Artifact {
             signatures,
             frame_info_registration: Some(Mutex::new(None)),
             finished_function_lengths,
+            memory_images: None,
+            metrics: Default::default(),
+            #[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+            lazy: None,
+            #[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+            tier_up: Mutex::new(None),
+            #[cfg(feature = "compiler")]
+            interpreted: None,
+            #[cfg(feature = "debug-info")]
+            debug_registration: None,
         })
     }
 
+    /// Describes the compiled functions to the `perf` profiler.
+    fn describe_to_perf(&self, strategy: PerfStrategy) {
+        let module_info = self.artifact.create_module_info();
+        for (index, ptr) in self.finished_functions.iter() {
+            let code = unsafe {
+                std::slice::from_raw_parts(
+                    **ptr as *const u8,
+                    self.finished_function_lengths[index],
+                )
+            };
+            let name = module_info.function_name(module_info.func_index(index));
+            register_function(strategy, &name, code);
+        }
+    }
+
     /// Check if the provided bytes look like a serialized `ArtifactBuild`.
     pub fn is_deserializable(bytes: &[u8]) -> bool {
         ArtifactBuild::is_deserializable(bytes)
@@ -245,6 +657,16 @@ impl ArtifactCreate for Artifact {
     }
 
     fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
+        #[cfg(feature = "compiler")]
+        if self.interpreted.is_some() {
+            return Err(SerializeError::Generic(
+                "modules run by the interpreter can't be serialized".to_string(),
+            ));
+        }
+        #[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+        if let Some(lazy) = self.lazy.as_ref() {
+            return lazy.serialize();
+        }
         self.artifact.serialize()
     }
 }
@@ -279,6 +701,31 @@ impl Artifact {
         }
     }
 
+    /// Blocks until the functions of this `Artifact` have been recompiled
+    /// by the optimized tier of the engine, if they are being.
+    ///
+    /// Returns the first error of the functions that failed to compile,
+    /// which keep running in the baseline tier.
+    pub fn wait_for_optimized_tier(&self) -> Result<(), CompileError> {
+        #[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+        if let Some(tier_up) = self.tier_up.lock().unwrap().take() {
+            return tier_up.join().unwrap_or_else(|_| {
+                Err(CompileError::Resource(
+                    "the optimized tier panicked".to_string(),
+                ))
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns how long this `Artifact` took to compile, and the size of
+    /// its code.
+    pub fn metrics(&self) -> ModuleMetrics {
+        let mut metrics = self.metrics.lock().unwrap().clone();
+        metrics.code_size += self.finished_function_lengths.values().sum::<usize>();
+        metrics
+    }
+
     /// Returns the functions allocated in memory or this `Artifact`
     /// ready to be run.
     pub fn finished_functions(&self) -> &BoxedSlice<LocalFunctionIndex, FunctionBodyPtr> {
@@ -320,13 +767,38 @@ impl Artifact {
         imports: &[VMExtern],
         context: &mut StoreObjects,
     ) -> Result<InstanceHandle, InstantiationError> {
+        self.instantiate_in_pool(None, tunables, imports, context)
+    }
+
+    /// Crate an `Instance` from this `Artifact`, claiming its `VMContext`,
+    /// memories and tables from the given pool if any.
+    ///
+    /// The pool takes over the allocation of VM-owned memories and tables
+    /// from `tunables`.
+    ///
+    /// # Safety
+    ///
+    /// See [`InstanceHandle::new`].
+    pub unsafe fn instantiate_in_pool(
+        &self,
+        pool: Option<&InstancePool>,
+        tunables: &dyn Tunables,
+        imports: &[VMExtern],
+        context: &mut StoreObjects,
+    ) -> Result<InstanceHandle, InstantiationError> {
+        // Code compiled for another target was never linked.
+        if let Some(target) = self.artifact.target().filter(|target| !target.is_native()) {
+            return Err(InstantiationError::DifferentArchOS(
+                target.triple().to_string(),
+            ));
+        }
+
         // Validate the CPU features this module was compiled with against the
         // host CPU features.
         let host_cpu_features = CpuFeature::for_host();
         if !host_cpu_features.is_superset(self.cpu_features()) {
-            return Err(InstantiationError::CpuFeature(format!(
-                "{:?}",
-                self.cpu_features().difference(host_cpu_features)
+            return Err(InstantiationError::CpuFeature(CpuFeature::format_set(
+                self.cpu_features().difference(host_cpu_features),
             )));
         }
 
@@ -346,8 +818,19 @@ impl Artifact {
         // Get pointers to where metadata about local memories should live in VM memory.
         // Get pointers to where metadata about local tables should live in VM memory.
 
-        let (allocator, memory_definition_locations, table_definition_locations) =
-            InstanceAllocator::new(&module);
+        let pooled_tunables;
+        let (tunables, (allocator, memory_definition_locations, table_definition_locations)) =
+            match pool {
+                Some(pool) => {
+                    pooled_tunables = PooledTunables { tunables, pool };
+                    (
+                        &pooled_tunables as &dyn Tunables,
+                        InstanceAllocator::new_pooled(&module, pool)
+                            .map_err(|e| InstantiationError::Link(LinkError::Resource(e)))?,
+                    )
+                }
+                None => (tunables, InstanceAllocator::new(&module)),
+            };
         let finished_memories = tunables
             .create_memories(
                 context,
@@ -389,7 +872,9 @@ impl Artifact {
         Ok(handle)
     }
 
-    /// Finishes the instantiation of a just created `InstanceHandle`.
+    /// Finishes the instantiation of a just created `InstanceHandle`,
+    /// running its start function on a stack of `stack_size` bytes unless
+    /// `invoke_start` is false.
     ///
     /// # Safety
     ///
@@ -397,7 +882,9 @@ impl Artifact {
     pub unsafe fn finish_instantiation(
         &self,
         trap_handler: Option<*const TrapHandlerFn<'static>>,
+        stack_size: usize,
         handle: &mut InstanceHandle,
+        invoke_start: bool,
     ) -> Result<(), InstantiationError> {
         let data_initializers = self
             .data_initializers()
@@ -407,9 +894,61 @@ impl Artifact {
                 data: &init.data,
             })
             .collect::<Vec<_>>();
-        handle
-            .finish_instantiation(trap_handler, &data_initializers)
-            .map_err(|trap| InstantiationError::Start(RuntimeError::from_trap(trap)))
+        match self.memory_images() {
+            Some(images) => handle.finish_instantiation_with_images(
+                trap_handler,
+                stack_size,
+                &images,
+                &data_initializers,
+                invoke_start,
+            ),
+            None => handle.finish_instantiation(
+                trap_handler,
+                stack_size,
+                &data_initializers,
+                invoke_start,
+            ),
+        }
+        .map_err(|trap| InstantiationError::Start(RuntimeError::from_trap(trap)))
+    }
+
+    /// Returns the copy-on-write images of the local memories, building
+    /// them on first use.
+    fn memory_images(&self) -> Option<Arc<MemoryImages>> {
+        let mut images = self.memory_images.as_ref()?.lock().unwrap();
+        Some(Arc::clone(
+            images.get_or_insert_with(|| Arc::new(self.build_memory_images())),
+        ))
+    }
+
+    /// Builds the images of the local memories, or no image at all if the
+    /// content of the memories depends on the imports or if the data
+    /// initializers trap.
+    fn build_memory_images(&self) -> MemoryImages {
+        let module = self.create_module_info();
+        let mut segments: PrimaryMap<LocalMemoryIndex, Vec<(usize, &[u8])>> = module
+            .memories
+            .values()
+            .skip(module.num_imported_memories)
+            .map(|_| Vec::new())
+            .collect();
+        for init in self.data_initializers() {
+            let index = init.location.memory_index;
+            let local_index = match (init.location.base, module.local_memory_index(index)) {
+                (None, Some(local_index)) => local_index,
+                _ => return PrimaryMap::new(),
+            };
+            let minimum = module.memories[index].minimum.bytes().0;
+            let end = init.location.offset.checked_add(init.data.len());
+            if !matches!(end, Some(end) if end <= minimum) {
+                return PrimaryMap::new();
+            }
+            segments[local_index].push((init.location.offset, &init.data[..]));
+        }
+        segments
+            .values()
+            .map(|segments| MemoryImage::new(segments.iter().copied()).unwrap_or(None))
+            .collect()
     }
 
     #[allow(clippy::type_complexity)]
@@ -684,6 +1223,8 @@ impl Artifact {
             compile_info: metadata.compile_info,
             data_initializers: metadata.data_initializers,
             cpu_features: metadata.cpu_features,
+            // Static objects are linked into the host executable
+            target_triple: Triple::host().to_string(),
         });
 
         let finished_function_lengths = finished_functions
@@ -702,6 +1243,16 @@ impl Artifact {
             signatures: signatures.into_boxed_slice(),
             finished_function_lengths,
             frame_info_registration: None,
+            memory_images: Some(Mutex::new(None)),
+            metrics: Default::default(),
+            #[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+            lazy: None,
+            #[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+            tier_up: Mutex::new(None),
+            #[cfg(feature = "compiler")]
+            interpreted: None,
+            #[cfg(feature = "debug-info")]
+            debug_registration: None,
         })
     }
 }
@@ -1,6 +1,10 @@
 use super::Engine;
+#[cfg(not(target_arch = "wasm32"))]
+use super::PerfStrategy;
 use crate::CompilerConfig;
 use wasmer_types::{Features, Target};
+#[cfg(not(target_arch = "wasm32"))]
+use wasmer_vm::InstancePool;
 
 /// The Builder contents of `Engine`
 pub struct EngineBuilder {
@@ -10,6 +14,26 @@ pub struct EngineBuilder {
     target: Option<Target>,
     /// The features to compile the Wasm module with
     features: Option<Features>,
+    /// The pool to allocate instances in
+    #[cfg(not(target_arch = "wasm32"))]
+    instance_pool: Option<InstancePool>,
+    /// Whether functions are compiled on their first call
+    lazy_compilation: bool,
+    /// The number of threads to compile functions on
+    compile_jobs: Option<usize>,
+    /// The compiler to recompile functions with in the background
+    optimized_tier: Option<Box<dyn CompilerConfig>>,
+    /// Whether native debuggers are told about the compiled code
+    debug_info: bool,
+    /// How the compiled functions are described to `perf`
+    #[cfg(not(target_arch = "wasm32"))]
+    perf: Option<PerfStrategy>,
+    /// The size of the stack Wasm code runs on
+    #[cfg(not(target_arch = "wasm32"))]
+    stack_size: Option<usize>,
+    /// Whether the trap handlers are only installed while the engine lives
+    #[cfg(not(target_arch = "wasm32"))]
+    scoped_trap_handlers: bool,
 }
 
 impl EngineBuilder {
@@ -22,6 +46,18 @@ impl EngineBuilder {
             compiler_config: Some(compiler_config.into()),
             target: None,
             features: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            instance_pool: None,
+            lazy_compilation: false,
+            compile_jobs: None,
+            optimized_tier: None,
+            debug_info: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            perf: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            stack_size: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            scoped_trap_handlers: false,
         }
     }
 
@@ -31,6 +67,18 @@ impl EngineBuilder {
             compiler_config: None,
             target: None,
             features: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            instance_pool: None,
+            lazy_compilation: false,
+            compile_jobs: None,
+            optimized_tier: None,
+            debug_info: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            perf: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            stack_size: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            scoped_trap_handlers: false,
         }
     }
 
@@ -46,24 +94,161 @@ impl EngineBuilder {
         self
     }
 
+    /// Set the pool to allocate instances in
+    ///
+    /// With a pool, instantiating a module claims pre-reserved slots for
+    /// its `VMContext`, memories and tables instead of allocating them,
+    /// and the slots are recycled once the instance is dropped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_instance_pool(mut self, instance_pool: Option<InstancePool>) -> Self {
+        self.instance_pool = instance_pool;
+        self
+    }
+
+    /// Set whether functions are compiled lazily
+    ///
+    /// Lazily compiled modules only get their functions compiled when
+    /// they are first called, which makes compiling large modules whose
+    /// functions mostly don't run much faster. This is only supported for
+    /// the Cranelift and Singlepass compilers on x86_64 Unix hosts; other
+    /// setups keep compiling all the functions upfront.
+    pub fn set_lazy_compilation(mut self, lazy_compilation: bool) -> Self {
+        self.lazy_compilation = lazy_compilation;
+        self
+    }
+
+    /// Set the number of threads to compile functions on
+    ///
+    /// The functions of a module are compiled in parallel, by default on
+    /// the global Rayon thread pool. Setting this gives the engine its own
+    /// pool of `compile_jobs` threads instead, so `Some(1)` compiles the
+    /// functions one after the other.
+    pub fn set_compile_jobs(mut self, compile_jobs: Option<usize>) -> Self {
+        self.compile_jobs = compile_jobs;
+        self
+    }
+
+    /// Set the compiler of the optimized tier
+    ///
+    /// Engines with an optimized tier compile modules in two tiers: the
+    /// functions are first compiled on their first call by the baseline
+    /// compiler of the engine, typically Singlepass, while the optimizing
+    /// compiler, typically Cranelift or LLVM, recompiles them on a
+    /// background thread. Each function switches to its optimized code
+    /// as soon as it is ready. Both compilers should be configured with
+    /// the same middlewares.
+    ///
+    /// This is only supported on x86_64 Unix hosts, by compilers that can
+    /// compile functions one by one; other setups only use the baseline
+    /// compiler.
+    pub fn set_optimized_tier(mut self, compiler_config: Option<Box<dyn CompilerConfig>>) -> Self {
+        self.optimized_tier = compiler_config;
+        self
+    }
+
+    /// Set whether native debuggers are told about the compiled code
+    ///
+    /// With debug info, the code of every module compiled by the engine
+    /// is registered through the GDB JIT interface, which GDB and LLDB
+    /// both support, along with DWARF naming its functions. When the
+    /// module carries DWARF of its own, its line tables are translated
+    /// to the compiled code, so breakpoints can be set on the lines of
+    /// the guest sources and backtraces show them. Guest variables can't
+    /// be inspected yet.
+    ///
+    /// This needs the `debug-info` feature, and only applies to modules
+    /// compiled upfront for the host: lazily compiled, interpreted and
+    /// deserialized modules aren't registered.
+    pub fn set_debug_info(mut self, debug_info: bool) -> Self {
+        self.debug_info = debug_info;
+        self
+    }
+
+    /// Set how the compiled functions are described to `perf`
+    ///
+    /// Samples the Linux `perf` profiler takes in compiled code are
+    /// attributed to anonymous memory, unless the names and addresses of
+    /// the functions are written to a perf map or a jitdump file as they
+    /// are compiled. See [`PerfStrategy`] for where the files go.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_perf(mut self, perf: Option<PerfStrategy>) -> Self {
+        self.perf = perf;
+        self
+    }
+
+    /// Set the size in bytes of the stack Wasm code runs on
+    ///
+    /// Every call into Wasm code runs on a stack of its own, 1MiB by
+    /// default, and traps with `TrapCode::StackOverflow` once it is
+    /// exhausted. Deeply recursive modules need a larger stack, while
+    /// hosts running many calls at once can make it smaller. The host
+    /// functions the module imports run on the stack of the thread that
+    /// called into Wasm instead.
+    ///
+    /// The stack bounds how deep calls can nest, but not by a set number
+    /// of calls since the frames of compiled functions differ in size;
+    /// the `CallDepthLimit` middleware of `wasmer-middlewares` limits the
+    /// number of nested calls itself.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_stack_size(mut self, stack_size: Option<usize>) -> Self {
+        self.stack_size = stack_size;
+        self
+    }
+
+    /// Set whether the trap handlers are only installed while the engine
+    /// lives
+    ///
+    /// Wasm traps are caught with signal handlers (an exception handler
+    /// on Windows), installed once the first `Store` is created and kept
+    /// until the process exits by default. An engine scoping them
+    /// installs them when it is built instead, and removes them once it
+    /// and all its clones are dropped, restoring the handlers of the
+    /// embedder, such as the ones of a crash reporter. See
+    /// `wasmer_vm::TrapHandlers`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_scoped_trap_handlers(mut self, scoped_trap_handlers: bool) -> Self {
+        self.scoped_trap_handlers = scoped_trap_handlers;
+        self
+    }
+
     /// Build the `Engine` for this configuration
     #[cfg(feature = "compiler")]
     pub fn engine(self) -> Engine {
         let target = self.target.unwrap_or_default();
-        if let Some(compiler_config) = self.compiler_config {
+        let engine = if let Some(compiler_config) = self.compiler_config {
             let features = self
                 .features
                 .unwrap_or_else(|| compiler_config.default_features_for_target(&target));
-            Engine::new(compiler_config, target, features)
+            let engine = Engine::new(compiler_config, target, features)
+                .with_lazy_compilation(self.lazy_compilation)
+                .with_optimized_tier(self.optimized_tier)
+                .with_debug_info(self.debug_info);
+            #[cfg(not(target_arch = "wasm32"))]
+            let engine = engine.with_compile_jobs(self.compile_jobs);
+            engine
         } else {
             Engine::headless()
-        }
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let engine = engine
+            .with_instance_pool(self.instance_pool)
+            .with_perf(self.perf)
+            .with_stack_size(self.stack_size)
+            .with_scoped_trap_handlers(self.scoped_trap_handlers);
+        engine
     }
 
     /// Build the `Engine` for this configuration
     #[cfg(not(feature = "compiler"))]
     pub fn engine(self) -> Engine {
-        Engine::headless()
+        let engine = Engine::headless();
+        #[cfg(not(target_arch = "wasm32"))]
+        let engine = engine
+            .with_instance_pool(self.instance_pool)
+            .with_perf(self.perf)
+            .with_stack_size(self.stack_size)
+            .with_scoped_trap_handlers(self.scoped_trap_handlers);
+        engine
     }
 
     /// The Wasm features
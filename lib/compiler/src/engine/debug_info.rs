@@ -0,0 +1,320 @@
+//! The native debug info of compiled modules.
+//!
+//! The image registered with the debuggers is an ELF object whose
+//! `.text` section covers the compiled code of a module, with a symbol
+//! and a DWARF subprogram for each of its functions. When the module has
+//! DWARF of its own, the rows of its line programs are translated to the
+//! compiled instructions, through the source locations recorded by the
+//! compilers.
+
+use gimli::write::{
+    Address, AttributeValue, DwarfUnit, EndianVec, FileId, LineProgram, LineString, Range,
+    RangeList, Sections,
+};
+use gimli::{Encoding, EndianSlice, Format, LineEncoding, LittleEndian, SectionId};
+use object::write::{Object, Symbol, SymbolSection};
+use object::{
+    elf, Architecture, BinaryFormat, Endianness, SectionFlags, SectionKind, SymbolFlags,
+    SymbolKind, SymbolScope,
+};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use wasmer_types::{FunctionAddressMap, FunctionIndex, ModuleInfo, Triple};
+use wasmparser::{Parser, Payload};
+
+/// A function of a module, as allocated in memory.
+pub(crate) struct CompiledFunction<'a> {
+    pub index: FunctionIndex,
+    pub address: usize,
+    pub length: usize,
+    pub address_map: &'a FunctionAddressMap,
+}
+
+/// A row of the line programs of the module.
+struct GuestRow {
+    /// The offset in the code section.
+    address: u64,
+    /// The file, line and column, or `None` at the end of a sequence.
+    location: Option<(String, u64, u64)>,
+}
+
+/// Builds the image describing the functions of `module` to the
+/// debuggers, or `None` if the host isn't supported.
+///
+/// `wasm` are the bytes the module was compiled from.
+pub(crate) fn build_image(
+    triple: &Triple,
+    module: &ModuleInfo,
+    wasm: &[u8],
+    functions: &[CompiledFunction],
+) -> Option<Vec<u8>> {
+    let architecture = match triple.architecture {
+        wasmer_types::Architecture::X86_64 => Architecture::X86_64,
+        wasmer_types::Architecture::Aarch64(_) => Architecture::Aarch64,
+        wasmer_types::Architecture::Riscv64(_) => Architecture::Riscv64,
+        _ => return None,
+    };
+    let start = functions.iter().map(|f| f.address).min()?;
+    let end = functions.iter().map(|f| f.address + f.length).max()?;
+
+    let mut obj = Object::new(BinaryFormat::Elf, architecture, Endianness::Little);
+    let text = obj.add_section(vec![], b".text".to_vec(), SectionKind::UninitializedData);
+    obj.section_mut(text).flags = SectionFlags::Elf {
+        sh_flags: (elf::SHF_ALLOC | elf::SHF_EXECINSTR) as u64,
+    };
+    obj.append_section_bss(text, (end - start) as u64, 1);
+    for function in functions {
+        obj.add_symbol(Symbol {
+            name: module.function_name(function.index).into_bytes(),
+            value: (function.address - start) as u64,
+            size: function.length as u64,
+            kind: SymbolKind::Text,
+            scope: SymbolScope::Compilation,
+            weak: false,
+            section: SymbolSection::Section(text),
+            flags: SymbolFlags::None,
+        });
+    }
+
+    let sections = write_dwarf(module, wasm, functions).ok()?;
+    sections
+        .for_each(|id, data| {
+            if !data.slice().is_empty() {
+                let section =
+                    obj.add_section(vec![], id.name().as_bytes().to_vec(), SectionKind::Debug);
+                obj.append_section_data(section, data.slice(), 1);
+            }
+            Ok::<(), ()>(())
+        })
+        .ok()?;
+
+    let mut image = obj.write().ok()?;
+    set_section_address(&mut image, b".text", start as u64)?;
+    Some(image)
+}
+
+fn write_dwarf(
+    module: &ModuleInfo,
+    wasm: &[u8],
+    functions: &[CompiledFunction],
+) -> gimli::write::Result<Sections<EndianVec<LittleEndian>>> {
+    let encoding = Encoding {
+        format: Format::Dwarf32,
+        version: 4,
+        address_size: 8,
+    };
+    let mut dwarf = DwarfUnit::new(encoding);
+    let module_name = module.name.clone().unwrap_or_else(|| "wasm".to_string());
+    dwarf.unit.line_program = LineProgram::new(
+        encoding,
+        LineEncoding::default(),
+        LineString::String(Vec::new()),
+        LineString::String(module_name.clone().into_bytes()),
+        None,
+    );
+
+    let root = dwarf.unit.root();
+    let ranges = RangeList(
+        functions
+            .iter()
+            .map(|function| Range::StartLength {
+                begin: Address::Constant(function.address as u64),
+                length: function.length as u64,
+            })
+            .collect(),
+    );
+    let ranges = dwarf.unit.ranges.add(ranges);
+    let entry = dwarf.unit.get_mut(root);
+    entry.set(
+        gimli::DW_AT_producer,
+        AttributeValue::String(b"wasmer".to_vec()),
+    );
+    entry.set(
+        gimli::DW_AT_name,
+        AttributeValue::String(module_name.into_bytes()),
+    );
+    entry.set(
+        gimli::DW_AT_low_pc,
+        AttributeValue::Address(Address::Constant(0)),
+    );
+    entry.set(gimli::DW_AT_ranges, AttributeValue::RangeListRef(ranges));
+
+    for function in functions {
+        let id = dwarf.unit.add(root, gimli::DW_TAG_subprogram);
+        let entry = dwarf.unit.get_mut(id);
+        entry.set(
+            gimli::DW_AT_name,
+            AttributeValue::String(module.function_name(function.index).into_bytes()),
+        );
+        entry.set(
+            gimli::DW_AT_low_pc,
+            AttributeValue::Address(Address::Constant(function.address as u64)),
+        );
+        entry.set(
+            gimli::DW_AT_high_pc,
+            AttributeValue::Udata(function.length as u64),
+        );
+    }
+
+    let guest_rows = match code_section_offset(wasm) {
+        Some(offset) => guest_rows(module).map(|rows| (offset, rows)),
+        None => None,
+    };
+    if let Some((code_section_offset, rows)) = guest_rows {
+        let mut files: HashMap<String, FileId> = HashMap::new();
+        let program = &mut dwarf.unit.line_program;
+        for function in functions {
+            program.begin_sequence(Some(Address::Constant(function.address as u64)));
+            for instruction in &function.address_map.instructions {
+                let srcloc = instruction.srcloc;
+                if srcloc.is_default() {
+                    continue;
+                }
+                let address = (srcloc.bits() as u64).wrapping_sub(code_section_offset);
+                let (path, line, column) = match find_row(&rows, address) {
+                    Some(location) => location,
+                    None => continue,
+                };
+                let file = *files.entry(path.clone()).or_insert_with(|| {
+                    let directory = program.default_directory();
+                    program.add_file(
+                        LineString::String(path.clone().into_bytes()),
+                        directory,
+                        None,
+                    )
+                });
+                let row = program.row();
+                row.address_offset = instruction.code_offset as u64;
+                row.file = file;
+                row.line = *line;
+                row.column = *column;
+                program.generate_row();
+            }
+            program.end_sequence(function.length as u64);
+        }
+    }
+
+    let mut sections = Sections::new(EndianVec::new(LittleEndian));
+    dwarf.write(&mut sections)?;
+    Ok(sections)
+}
+
+/// The offset of the contents of the code section, which the addresses
+/// of the DWARF of the module are relative to.
+fn code_section_offset(wasm: &[u8]) -> Option<u64> {
+    Parser::new(0)
+        .parse_all(wasm)
+        .find_map(|payload| match payload {
+            Ok(Payload::CodeSectionStart { range, .. }) => Some(range.start as u64),
+            _ => None,
+        })
+}
+
+/// Reads the rows of all the line programs of the module, sorted by
+/// address, or `None` if the module has no line programs.
+fn guest_rows(module: &ModuleInfo) -> Option<Vec<GuestRow>> {
+    let dwarf = gimli::Dwarf::load(|id| load_section(module, id)).ok()?;
+    let mut rows = Vec::new();
+    let mut units = dwarf.units();
+    while let Ok(Some(header)) = units.next() {
+        let unit = match dwarf.unit(header) {
+            Ok(unit) => unit,
+            Err(_) => continue,
+        };
+        let program = match unit.line_program.clone() {
+            Some(program) => program,
+            None => continue,
+        };
+        let mut program_rows = program.rows();
+        while let Ok(Some((header, row))) = program_rows.next_row() {
+            if row.end_sequence() {
+                rows.push(GuestRow {
+                    address: row.address(),
+                    location: None,
+                });
+                continue;
+            }
+            let path = match row.file(header) {
+                Some(file) => {
+                    let name = dwarf
+                        .attr_string(&unit, file.path_name())
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    let directory = file
+                        .directory(header)
+                        .and_then(|directory| dwarf.attr_string(&unit, directory).ok())
+                        .map(|directory| directory.to_string_lossy().into_owned());
+                    match directory {
+                        Some(directory) if !directory.is_empty() && !name.starts_with('/') => {
+                            format!("{}/{}", directory, name)
+                        }
+                        _ => name,
+                    }
+                }
+                None => continue,
+            };
+            let line = row.line().map_or(0, |line| line.get());
+            let column = match row.column() {
+                gimli::ColumnType::LeftEdge => 0,
+                gimli::ColumnType::Column(column) => column.get(),
+            };
+            rows.push(GuestRow {
+                address: row.address(),
+                location: Some((path, line, column)),
+            });
+        }
+    }
+    if rows.is_empty() {
+        return None;
+    }
+    // The end of a sequence goes before the rows of another one starting
+    // at the same address
+    rows.sort_by_key(|row| (row.address, row.location.is_some()));
+    Some(rows)
+}
+
+/// Reads a DWARF section from the custom sections of the module, which
+/// is empty if the module doesn't have it.
+fn load_section(
+    module: &ModuleInfo,
+    id: SectionId,
+) -> Result<EndianSlice<LittleEndian>, gimli::Error> {
+    let data = module.custom_sections(id.name()).next().unwrap_or(&[]);
+    Ok(EndianSlice::new(data, LittleEndian))
+}
+
+/// Finds the location of the last row at or before `address`.
+fn find_row(rows: &[GuestRow], address: u64) -> Option<&(String, u64, u64)> {
+    let index = rows.partition_point(|row| row.address <= address);
+    rows[..index].last()?.location.as_ref()
+}
+
+/// Sets the address of a section of a 64-bit little-endian ELF object,
+/// which `object` doesn't let us choose.
+fn set_section_address(image: &mut [u8], name: &[u8], address: u64) -> Option<()> {
+    let read_u16 = |image: &[u8], at: usize| -> Option<u16> {
+        Some(u16::from_le_bytes(image.get(at..at + 2)?.try_into().ok()?))
+    };
+    let read_u32 = |image: &[u8], at: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(image.get(at..at + 4)?.try_into().ok()?))
+    };
+    let read_u64 = |image: &[u8], at: usize| -> Option<u64> {
+        Some(u64::from_le_bytes(image.get(at..at + 8)?.try_into().ok()?))
+    };
+    let section_headers = read_u64(image, 0x28)? as usize;
+    let header_size = read_u16(image, 0x3a)? as usize;
+    let count = read_u16(image, 0x3c)? as usize;
+    let names_index = read_u16(image, 0x3e)? as usize;
+    let names = read_u64(image, section_headers + names_index * header_size + 0x18)? as usize;
+    for index in 0..count {
+        let header = section_headers + index * header_size;
+        let name_offset = names + read_u32(image, header)? as usize;
+        if image.get(name_offset..name_offset + name.len() + 1)? == [name, &[0]].concat() {
+            image
+                .get_mut(header + 0x10..header + 0x18)?
+                .copy_from_slice(&address.to_le_bytes());
+            return Some(());
+        }
+    }
+    None
+}
@@ -42,9 +42,14 @@ pub enum InstantiationError {
 
     /// The module was compiled with a CPU feature that is not available on
     /// the current host.
-    #[error("module compiled with CPU feature that is missing from host")]
+    #[error("module compiled with CPU features that are missing from host: {0}")]
     CpuFeature(String),
 
+    /// The module was compiled for another architecture or operating
+    /// system than the ones of the current host.
+    #[error("module compiled for `{0}`, which can't run on the host")]
+    DifferentArchOS(String),
+
     /// A runtime error occured while invoking the start function
     #[cfg(not(target_arch = "wasm32"))]
     #[error(transparent)]
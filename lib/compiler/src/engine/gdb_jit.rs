@@ -0,0 +1,130 @@
+//! The GDB JIT interface, through which native debuggers learn about
+//! code generated at runtime.
+//!
+//! Debuggers set a breakpoint on `__jit_debug_register_code`, and read
+//! the object file of the new code from `__jit_debug_descriptor` when it
+//! is hit. LLDB implements the same interface.
+
+use lazy_static::lazy_static;
+use std::ptr;
+use std::sync::Mutex;
+
+#[repr(C)]
+struct JitCodeEntry {
+    next_entry: *mut JitCodeEntry,
+    prev_entry: *mut JitCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+const JIT_NOACTION: u32 = 0;
+const JIT_REGISTER_FN: u32 = 1;
+const JIT_UNREGISTER_FN: u32 = 2;
+
+#[repr(C)]
+struct JitDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JitCodeEntry,
+    first_entry: *mut JitCodeEntry,
+}
+
+#[no_mangle]
+#[used]
+#[allow(non_upper_case_globals)]
+static mut __jit_debug_descriptor: JitDescriptor = JitDescriptor {
+    version: 1,
+    action_flag: JIT_NOACTION,
+    relevant_entry: ptr::null_mut(),
+    first_entry: ptr::null_mut(),
+};
+
+#[no_mangle]
+#[inline(never)]
+extern "C" fn __jit_debug_register_code() {
+    // The debuggers break on this function: the volatile read keeps it
+    // from being optimized out or merged with another one
+    unsafe {
+        ptr::read_volatile(&__jit_debug_descriptor.action_flag);
+    }
+}
+
+lazy_static! {
+    /// Serializes the changes to the list of entries.
+    static ref DESCRIPTOR_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// An object file registered with the debuggers until it is dropped.
+pub(crate) struct GdbJitImageRegistration {
+    entry: Box<JitCodeEntry>,
+    /// The object file the entry points to.
+    _image: Box<[u8]>,
+}
+
+// The entry is only accessed under `DESCRIPTOR_LOCK`.
+unsafe impl Send for GdbJitImageRegistration {}
+unsafe impl Sync for GdbJitImageRegistration {}
+
+impl GdbJitImageRegistration {
+    /// Registers an object file with the debuggers.
+    pub(crate) fn register(image: Vec<u8>) -> Self {
+        let image = image.into_boxed_slice();
+        let mut entry = Box::new(JitCodeEntry {
+            next_entry: ptr::null_mut(),
+            prev_entry: ptr::null_mut(),
+            symfile_addr: image.as_ptr(),
+            symfile_size: image.len() as u64,
+        });
+        let _lock = DESCRIPTOR_LOCK.lock().unwrap();
+        unsafe {
+            let entry_ptr: *mut JitCodeEntry = &mut *entry;
+            let first = __jit_debug_descriptor.first_entry;
+            (*entry_ptr).next_entry = first;
+            if !first.is_null() {
+                (*first).prev_entry = entry_ptr;
+            }
+            __jit_debug_descriptor.first_entry = entry_ptr;
+            notify(entry_ptr, JIT_REGISTER_FN);
+        }
+        Self {
+            entry,
+            _image: image,
+        }
+    }
+}
+
+impl Drop for GdbJitImageRegistration {
+    fn drop(&mut self) {
+        let _lock = DESCRIPTOR_LOCK.lock().unwrap();
+        unsafe {
+            let entry_ptr: *mut JitCodeEntry = &mut *self.entry;
+            let JitCodeEntry {
+                next_entry,
+                prev_entry,
+                ..
+            } = *entry_ptr;
+            if prev_entry.is_null() {
+                __jit_debug_descriptor.first_entry = next_entry;
+            } else {
+                (*prev_entry).next_entry = next_entry;
+            }
+            if !next_entry.is_null() {
+                (*next_entry).prev_entry = prev_entry;
+            }
+            notify(entry_ptr, JIT_UNREGISTER_FN);
+        }
+    }
+}
+
+/// Tells the debuggers about a change of the list of entries.
+///
+/// # Safety
+///
+/// `DESCRIPTOR_LOCK` must be held.
+unsafe fn notify(entry: *mut JitCodeEntry, action: u32) {
+    __jit_debug_descriptor.relevant_entry = entry;
+    __jit_debug_descriptor.action_flag = action;
+    __jit_debug_register_code();
+    __jit_debug_descriptor.action_flag = JIT_NOACTION;
+    __jit_debug_descriptor.relevant_entry = ptr::null_mut();
+}
@@ -1,16 +1,23 @@
 //! Universal compilation.
 
 use crate::engine::builder::EngineBuilder;
+use crate::engine::quarantine::{self, ModuleFailure, ModuleKey, Quarantine, QuarantinePolicy};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::engine::PerfStrategy;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::Artifact;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::CodeMemory;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::RuntimeError;
 #[cfg(feature = "compiler")]
-use crate::{Compiler, CompilerConfig};
+use crate::{Compiler, CompilerConfig, StreamingValidator};
 #[cfg(not(target_arch = "wasm32"))]
 use crate::{FunctionExtent, Tunables};
 #[cfg(not(target_arch = "wasm32"))]
 use memmap2::Mmap;
+#[cfg(all(feature = "compiler", not(target_arch = "wasm32")))]
+use rayon::{ThreadPool, ThreadPoolBuilder};
 #[cfg(not(target_arch = "wasm32"))]
 use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
@@ -18,15 +25,15 @@ use std::sync::{Arc, Mutex};
 #[cfg(not(target_arch = "wasm32"))]
 use wasmer_types::{
     entity::PrimaryMap, DeserializeError, FunctionBody, FunctionIndex, FunctionType,
-    LocalFunctionIndex, ModuleInfo, SignatureIndex,
+    LocalFunctionIndex, SignatureIndex,
 };
-use wasmer_types::{CompileError, Features, Target};
+use wasmer_types::{CompileError, Features, ModuleInfo, Target};
 #[cfg(not(target_arch = "wasm32"))]
 use wasmer_types::{CustomSection, CustomSectionProtection, SectionIndex};
 #[cfg(not(target_arch = "wasm32"))]
 use wasmer_vm::{
-    FunctionBodyPtr, SectionBodyPtr, SignatureRegistry, VMFunctionBody, VMSharedSignatureIndex,
-    VMTrampoline,
+    FunctionBodyPtr, InstancePool, SectionBodyPtr, SignatureRegistry, TrapHandlers, VMFunctionBody,
+    VMSharedSignatureIndex, VMTrampoline, DEFAULT_STACK_SIZE,
 };
 
 /// A WebAssembly `Universal` Engine.
@@ -36,6 +43,28 @@ pub struct Engine {
     /// The target for the compiler
     target: Arc<Target>,
     engine_id: EngineId,
+    /// The failure accounting used to quarantine misbehaving modules
+    quarantine: Arc<Mutex<Quarantine>>,
+    /// The pool instances are allocated in, if any
+    #[cfg(not(target_arch = "wasm32"))]
+    instance_pool: Option<InstancePool>,
+    /// Whether functions are compiled on their first call
+    lazy_compilation: bool,
+    /// Whether native debuggers are told about the compiled code
+    debug_info: bool,
+    /// How the compiled functions are described to `perf`
+    #[cfg(not(target_arch = "wasm32"))]
+    perf: Option<PerfStrategy>,
+    /// The size of the stack Wasm code runs on
+    #[cfg(not(target_arch = "wasm32"))]
+    stack_size: usize,
+    /// The trap handlers, when they are only installed while the engine
+    /// lives
+    #[cfg(not(target_arch = "wasm32"))]
+    trap_handlers: Option<Arc<TrapHandlers>>,
+    /// The compiler functions are recompiled with in the background
+    #[cfg(feature = "compiler")]
+    optimized_tier: Option<Arc<dyn Compiler>>,
 }
 
 impl Engine {
@@ -54,9 +83,24 @@ impl Engine {
                 code_memory: vec![],
                 #[cfg(not(target_arch = "wasm32"))]
                 signatures: SignatureRegistry::new(),
+                #[cfg(all(feature = "compiler", not(target_arch = "wasm32")))]
+                compile_pool: None,
             })),
             target: Arc::new(target),
             engine_id: EngineId::default(),
+            quarantine: Arc::new(Mutex::new(Quarantine::default())),
+            #[cfg(not(target_arch = "wasm32"))]
+            instance_pool: None,
+            lazy_compilation: false,
+            debug_info: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            perf: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            stack_size: DEFAULT_STACK_SIZE,
+            #[cfg(not(target_arch = "wasm32"))]
+            trap_handlers: None,
+            #[cfg(feature = "compiler")]
+            optimized_tier: None,
         }
     }
 
@@ -84,9 +128,24 @@ impl Engine {
                 code_memory: vec![],
                 #[cfg(not(target_arch = "wasm32"))]
                 signatures: SignatureRegistry::new(),
+                #[cfg(all(feature = "compiler", not(target_arch = "wasm32")))]
+                compile_pool: None,
             })),
             target: Arc::new(Target::default()),
             engine_id: EngineId::default(),
+            quarantine: Arc::new(Mutex::new(Quarantine::default())),
+            #[cfg(not(target_arch = "wasm32"))]
+            instance_pool: None,
+            lazy_compilation: false,
+            debug_info: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            perf: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            stack_size: DEFAULT_STACK_SIZE,
+            #[cfg(not(target_arch = "wasm32"))]
+            trap_handlers: None,
+            #[cfg(feature = "compiler")]
+            optimized_tier: None,
         }
     }
 
@@ -105,6 +164,189 @@ impl Engine {
         &self.target
     }
 
+    /// A string identifying the artifacts the engine compiles: its
+    /// compiler with its options and middlewares, the enabled WebAssembly
+    /// features, and the target with its CPU features.
+    ///
+    /// Engines with different ids compile modules differently, so caches
+    /// must not share artifacts between them.
+    pub fn deterministic_id(&self) -> String {
+        #[cfg(feature = "compiler")]
+        let (compiler, features) = {
+            let inner = self.inner();
+            let compiler = inner.compiler.as_ref().map_or_else(
+                || "headless".to_string(),
+                |c| {
+                    let middlewares = c
+                        .get_middlewares()
+                        .iter()
+                        .map(|m| m.deterministic_id())
+                        .collect::<Vec<_>>();
+                    format!("{}+[{}]", c.deterministic_id(), middlewares.join(","))
+                },
+            );
+            (
+                compiler,
+                inner.features.enabled().collect::<Vec<_>>().join(","),
+            )
+        };
+        #[cfg(not(feature = "compiler"))]
+        let (compiler, features) = ("headless".to_string(), String::new());
+        format!(
+            "{}-{}-{:x}-[{}]",
+            compiler,
+            self.target.triple(),
+            self.target.cpu_features().as_u64(),
+            features
+        )
+    }
+
+    /// Sets the pool the instances are allocated in.
+    ///
+    /// The engine keeps sharing the modules compiled by its clones, so
+    /// that a pool can be sized for a module once it is compiled.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_instance_pool(mut self, pool: Option<InstancePool>) -> Self {
+        self.instance_pool = pool;
+        self
+    }
+
+    /// Gets the pool the instances are allocated in, if any.
+    ///
+    /// See [`EngineBuilder::set_instance_pool`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn instance_pool(&self) -> Option<&InstancePool> {
+        self.instance_pool.as_ref()
+    }
+
+    /// Sets whether functions are compiled on their first call.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn with_lazy_compilation(mut self, lazy_compilation: bool) -> Self {
+        self.lazy_compilation = lazy_compilation;
+        self
+    }
+
+    /// Whether functions are compiled on their first call.
+    ///
+    /// See [`EngineBuilder::set_lazy_compilation`].
+    pub fn lazy_compilation(&self) -> bool {
+        self.lazy_compilation
+    }
+
+    /// Sets whether native debuggers are told about the compiled code.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn with_debug_info(mut self, debug_info: bool) -> Self {
+        self.debug_info = debug_info;
+        self
+    }
+
+    /// Whether native debuggers are told about the compiled code.
+    ///
+    /// See [`EngineBuilder::set_debug_info`].
+    pub fn debug_info(&self) -> bool {
+        self.debug_info
+    }
+
+    /// Sets how the compiled functions are described to `perf`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn with_perf(mut self, perf: Option<PerfStrategy>) -> Self {
+        self.perf = perf;
+        self
+    }
+
+    /// How the compiled functions are described to `perf`, if they are.
+    ///
+    /// See [`EngineBuilder::set_perf`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn perf(&self) -> Option<PerfStrategy> {
+        self.perf
+    }
+
+    /// Sets the size of the stack Wasm code runs on.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn with_stack_size(mut self, stack_size: Option<usize>) -> Self {
+        self.stack_size = stack_size.unwrap_or(DEFAULT_STACK_SIZE);
+        self
+    }
+
+    /// The size in bytes of the stack Wasm code runs on.
+    ///
+    /// See [`EngineBuilder::set_stack_size`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn stack_size(&self) -> usize {
+        self.stack_size
+    }
+
+    /// Installs the trap handlers while the engine lives, if `scoped`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn with_scoped_trap_handlers(mut self, scoped: bool) -> Self {
+        self.trap_handlers = if scoped {
+            Some(Arc::new(TrapHandlers::install()))
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Whether the trap handlers are only installed while the engine
+    /// lives, rather than from the creation of the first `Store`.
+    ///
+    /// See [`EngineBuilder::set_scoped_trap_handlers`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn scopes_trap_handlers(&self) -> bool {
+        self.trap_handlers.is_some()
+    }
+
+    /// Sets the compiler functions are recompiled with in the background.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn with_optimized_tier(
+        mut self,
+        compiler_config: Option<Box<dyn CompilerConfig>>,
+    ) -> Self {
+        self.optimized_tier = compiler_config.map(|config| Arc::from(config.compiler()));
+        self
+    }
+
+    /// The compiler functions are recompiled with in the background, if
+    /// the engine compiles in tiers.
+    ///
+    /// See [`EngineBuilder::set_optimized_tier`].
+    #[cfg(feature = "compiler")]
+    pub fn optimized_tier(&self) -> Option<&dyn Compiler> {
+        self.optimized_tier.as_deref()
+    }
+
+    #[cfg(feature = "compiler")]
+    pub(crate) fn optimized_tier_compiler(&self) -> Option<Arc<dyn Compiler>> {
+        self.optimized_tier.clone()
+    }
+
+    /// Sets the number of threads the functions of a module are compiled
+    /// on, `None` meaning the global Rayon thread pool.
+    #[cfg(all(feature = "compiler", not(target_arch = "wasm32")))]
+    pub(crate) fn with_compile_jobs(self, compile_jobs: Option<usize>) -> Self {
+        self.inner_mut().compile_pool = compile_jobs.map(|num_threads| {
+            ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .thread_name(|index| format!("wasmer-compile-{}", index))
+                .build()
+                .expect("failed to spawn the compilation threads")
+        });
+        self
+    }
+
+    /// The number of threads the functions of a module are compiled on,
+    /// if it has been set.
+    ///
+    /// See [`EngineBuilder::set_compile_jobs`].
+    #[cfg(all(feature = "compiler", not(target_arch = "wasm32")))]
+    pub fn compile_jobs(&self) -> Option<usize> {
+        self.inner()
+            .compile_pool
+            .as_ref()
+            .map(ThreadPool::current_num_threads)
+    }
+
     /// Register a signature
     #[cfg(not(target_arch = "wasm32"))]
     pub fn register_signature(&self, func_type: &FunctionType) -> VMSharedSignatureIndex {
@@ -125,6 +367,13 @@ impl Engine {
         self.inner().validate(binary)
     }
 
+    /// Creates a validator for WebAssembly modules given in chunks, with
+    /// the features of this engine.
+    #[cfg(feature = "compiler")]
+    pub fn streaming_validator(&self) -> StreamingValidator {
+        StreamingValidator::new(self.inner().features())
+    }
+
     /// Compile a WebAssembly binary
     #[cfg(feature = "compiler")]
     #[cfg(not(target_arch = "wasm32"))]
@@ -183,10 +432,87 @@ impl Engine {
         &self.engine_id
     }
 
+    /// Whether `other` is this engine or a clone of it, sharing its
+    /// compiled code and its signatures.
+    ///
+    /// Unlike their [`Engine::id`], the clones of an engine are the same.
+    pub fn is_same(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
     /// Clone the engine
     pub fn cloned(&self) -> Self {
         self.clone()
     }
+
+    /// Sets the policy deciding when modules get quarantined.
+    ///
+    /// The policy is shared by all the clones of this engine and
+    /// applies to failures reported from then on.
+    pub fn set_quarantine_policy(&self, policy: QuarantinePolicy) {
+        self.quarantine.lock().unwrap().set_policy(policy);
+    }
+
+    /// Gets the policy deciding when modules get quarantined.
+    pub fn quarantine_policy(&self) -> QuarantinePolicy {
+        self.quarantine.lock().unwrap().policy()
+    }
+
+    /// Reports an error raised while running WebAssembly code.
+    ///
+    /// WebAssembly traps are accounted to the module whose code raised
+    /// them, which gets quarantined once the policy limit is reached.
+    /// Errors raised by the host are ignored.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn report_trap(&self, error: &RuntimeError) {
+        let frame = match error.trace().first() {
+            Some(frame) => frame,
+            None => return,
+        };
+        if let Some(trap_code) = error.clone().to_trap() {
+            self.quarantine.lock().unwrap().report(
+                &ModuleKey::new(frame.module_hash(), frame.module_id()),
+                ModuleFailure::from_trap_code(trap_code),
+                trap_code.message(),
+            );
+        }
+    }
+
+    /// Reports that instantiating the module failed for lack of resources.
+    pub fn report_resource_exhaustion(&self, module: &ModuleInfo, cause: &str) {
+        self.quarantine.lock().unwrap().report(
+            &ModuleKey::from(module),
+            ModuleFailure::ResourceExhaustion,
+            cause,
+        );
+    }
+
+    /// Quarantines a module, so it can't be instantiated anymore.
+    pub fn quarantine_module(&self, module: &ModuleInfo, reason: &str) {
+        self.quarantine
+            .lock()
+            .unwrap()
+            .quarantine(&ModuleKey::from(module), reason.to_string());
+    }
+
+    /// Releases a module from quarantine and resets its failure counters.
+    pub fn release_module(&self, module: &ModuleInfo) {
+        self.quarantine
+            .lock()
+            .unwrap()
+            .release(&ModuleKey::from(module));
+    }
+
+    /// Returns a description of why the module is quarantined, or `None`
+    /// if it isn't.
+    pub fn quarantined(&self, module: &ModuleInfo) -> Option<String> {
+        let reason = self
+            .quarantine
+            .lock()
+            .unwrap()
+            .reason(&ModuleKey::from(module))?;
+        Some(quarantine::describe(module, &reason))
+    }
 }
 
 /// The inner contents of `Engine`
@@ -205,6 +531,9 @@ pub struct EngineInner {
     /// performantly.
     #[cfg(not(target_arch = "wasm32"))]
     signatures: SignatureRegistry,
+    /// The thread pool modules are compiled in, instead of the global one
+    #[cfg(all(feature = "compiler", not(target_arch = "wasm32")))]
+    compile_pool: Option<ThreadPool>,
 }
 
 impl EngineInner {
@@ -232,6 +561,17 @@ impl EngineInner {
         &self.features
     }
 
+    /// Runs `compile` in the thread pool of the engine, so that the
+    /// functions it compiles in parallel are spread over its threads.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn in_compile_pool<R: Send>(&self, compile: impl FnOnce() -> R + Send) -> R {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(pool) = self.compile_pool.as_ref() {
+            return pool.install(compile);
+        }
+        compile()
+    }
+
     /// Allocate compiled functions into memory
     #[cfg(not(target_arch = "wasm32"))]
     #[allow(clippy::type_complexity)]
@@ -346,6 +686,15 @@ impl EngineInner {
         Ok(())
     }
 
+    /// Takes the code memory the last functions were allocated and
+    /// published in, for them to be freed before the engine is.
+    #[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+    pub(crate) fn take_code_memory(&mut self) -> CodeMemory {
+        self.code_memory
+            .pop()
+            .expect("no code memory was allocated")
+    }
+
     /// Shared signature registry.
     #[cfg(not(target_arch = "wasm32"))]
     pub fn signatures(&self) -> &SignatureRegistry {
@@ -0,0 +1,490 @@
+//! Lazy compilation of the functions of a module.
+//!
+//! The functions of a lazily compiled module start out as small stubs that
+//! jump through the `target` slot of their [`LazyFunction`]. That slot
+//! first points to a trampoline which compiles the function on its first
+//! call, stores the address of the compiled code in the slot and jumps to
+//! it, so that later calls only go through the stub.
+//!
+//! The code of the functions compiled on demand belongs to the module
+//! rather than to the engine, and is freed along with it. Code replaced
+//! by the optimized tier is kept as long too, as calls that were already
+//! running it when it was replaced may not have returned yet.
+
+use crate::engine::link::{relocation_target, write_relocation};
+use crate::engine::metrics::ModuleMetrics;
+use crate::engine::perf::register_function;
+use crate::{register_function_frame_info, CodeMemory, Compiler, Engine, EngineInner};
+use crate::{ArtifactBuild, ArtifactCreate, GlobalFrameInfoRegistration, ModuleTranslationState};
+use crate::{FunctionBodyData, FunctionExtent};
+use std::arch::global_asm;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::JoinHandle;
+use std::time::Instant;
+use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::{
+    CompileError, CompileModuleInfo, CompiledFunction, CustomSection, FunctionBody,
+    LocalFunctionIndex, ModuleInfo, Relocation, RelocationKind, RelocationTarget, SectionIndex,
+    SerializeError,
+};
+use wasmer_vm::{on_host_stack, raise_user_trap, SectionBodyPtr};
+
+/// The body of a function that hasn't been compiled yet:
+/// `mov r11, <LazyFunction>; jmp [r11]`.
+fn stub(function: &LazyFunction) -> Vec<u8> {
+    let mut stub = vec![0x49, 0xbb];
+    stub.extend_from_slice(&(function as *const LazyFunction as u64).to_le_bytes());
+    stub.extend_from_slice(&[0x41, 0xff, 0x23]);
+    stub
+}
+
+/// Jumps to an absolute address: `mov r11, <address>; jmp r11`.
+///
+/// Lazily compiled functions live in their own allocation, which may be
+/// out of the range of 32-bit relative calls to the rest of the module.
+fn veneer(address: usize) -> [u8; VENEER_LEN] {
+    let mut veneer = [
+        0x49, 0xbb, 0, 0, 0, 0, 0, 0, 0, 0, 0x41, 0xff, 0xe3, 0x90, 0x90, 0x90,
+    ];
+    veneer[2..10].copy_from_slice(&(address as u64).to_le_bytes());
+    veneer
+}
+
+const VENEER_LEN: usize = 16;
+
+// Called from a stub with the `LazyFunction` in r11 and the arguments of
+// the function still in their registers, which are saved around the call
+// to the `compile` callback.
+macro_rules! compile_trampoline {
+    ($name:literal) => {
+        global_asm!(concat!(
+            ".text\n",
+            ".p2align 4\n",
+            ".globl ",
+            $name,
+            "\n",
+            $name,
+            ":\n",
+            "push rbp\n",
+            "mov rbp, rsp\n",
+            "push rdi\n",
+            "push rsi\n",
+            "push rdx\n",
+            "push rcx\n",
+            "push r8\n",
+            "push r9\n",
+            "sub rsp, 128\n",
+            "movdqu [rsp], xmm0\n",
+            "movdqu [rsp + 16], xmm1\n",
+            "movdqu [rsp + 32], xmm2\n",
+            "movdqu [rsp + 48], xmm3\n",
+            "movdqu [rsp + 64], xmm4\n",
+            "movdqu [rsp + 80], xmm5\n",
+            "movdqu [rsp + 96], xmm6\n",
+            "movdqu [rsp + 112], xmm7\n",
+            "mov rdi, r11\n",
+            "call qword ptr [r11 + 8]\n",
+            "mov r11, rax\n",
+            "movdqu xmm0, [rsp]\n",
+            "movdqu xmm1, [rsp + 16]\n",
+            "movdqu xmm2, [rsp + 32]\n",
+            "movdqu xmm3, [rsp + 48]\n",
+            "movdqu xmm4, [rsp + 64]\n",
+            "movdqu xmm5, [rsp + 80]\n",
+            "movdqu xmm6, [rsp + 96]\n",
+            "movdqu xmm7, [rsp + 112]\n",
+            "add rsp, 128\n",
+            "pop r9\n",
+            "pop r8\n",
+            "pop rcx\n",
+            "pop rdx\n",
+            "pop rsi\n",
+            "pop rdi\n",
+            "pop rbp\n",
+            "jmp r11\n",
+        ));
+    };
+}
+
+#[cfg(target_os = "macos")]
+compile_trampoline!("_wasmer_lazy_compile_trampoline");
+#[cfg(not(target_os = "macos"))]
+compile_trampoline!("wasmer_lazy_compile_trampoline");
+
+extern "C" {
+    fn wasmer_lazy_compile_trampoline();
+}
+
+/// The entry of a function of a lazily compiled module.
+///
+/// These are owned by the compilation of the module, which lives as long
+/// as its artifact.
+#[repr(C)]
+pub(crate) struct LazyFunction {
+    /// Where the stub of the function jumps to.
+    target: AtomicUsize,
+    /// Compiles the function, returning the address of its code.
+    compile: unsafe extern "C" fn(*const LazyFunction) -> usize,
+    compilation: Weak<LazyCompilation>,
+    index: LocalFunctionIndex,
+}
+
+impl LazyFunction {
+    fn new(compilation: Weak<LazyCompilation>, index: LocalFunctionIndex) -> Self {
+        Self {
+            target: AtomicUsize::new(wasmer_lazy_compile_trampoline as usize),
+            compile: compile_lazy_function,
+            compilation,
+            index,
+        }
+    }
+
+    fn is_compiled(&self) -> bool {
+        self.target.load(Ordering::Acquire) != wasmer_lazy_compile_trampoline as usize
+    }
+}
+
+unsafe extern "C" fn compile_lazy_function(function: *const LazyFunction) -> usize {
+    let function = &*function;
+    // Compiling can use a lot of stack, which must not come out of the
+    // stack of the Wasm code.
+    let result = on_host_stack(|| match function.compilation.upgrade() {
+        Some(compilation) => compilation.compile(function.index),
+        None => Err(CompileError::Resource(
+            "the module of the function has been dropped".to_string(),
+        )),
+    });
+    match result {
+        Ok(address) => address,
+        Err(error) => raise_user_trap(Box::new(error)),
+    }
+}
+
+/// Where the rest of a lazily compiled module has been allocated.
+pub(crate) struct LinkedModule {
+    pub(crate) functions: PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+    pub(crate) custom_sections: PrimaryMap<SectionIndex, SectionBodyPtr>,
+    pub(crate) libcall_trampolines: SectionIndex,
+    pub(crate) libcall_trampoline_len: usize,
+}
+
+#[derive(Default)]
+struct LazyState {
+    linked: Option<LinkedModule>,
+    frame_info_registrations: Vec<GlobalFrameInfoRegistration>,
+    /// The code of the functions compiled so far, including the code
+    /// replaced by the optimized tier.
+    code_memory: Vec<CodeMemory>,
+}
+
+/// What is needed to compile the functions of a module on demand.
+pub(crate) struct LazyCompilation {
+    engine: Engine,
+    compile_info: CompileModuleInfo,
+    module: Arc<ModuleInfo>,
+    module_translation: ModuleTranslationState,
+    wasm: Box<[u8]>,
+    bodies: PrimaryMap<LocalFunctionIndex, Range<usize>>,
+    /// Boxed so that the stubs can point to them.
+    functions: Box<[LazyFunction]>,
+    state: Mutex<LazyState>,
+    metrics: Arc<Mutex<ModuleMetrics>>,
+}
+
+// The code memory is only accessed with the state locked, and the
+// functions only through atomics.
+unsafe impl Send for LazyCompilation {}
+unsafe impl Sync for LazyCompilation {}
+
+impl LazyCompilation {
+    /// Sets up the lazy compilation of a module, whose function bodies in
+    /// the module `wasm` are found at `bodies`.
+    ///
+    /// Returns the compilation along with the stubs to use as the bodies
+    /// of the functions.
+    pub(crate) fn new(
+        engine: &Engine,
+        compile_info: CompileModuleInfo,
+        module_translation: ModuleTranslationState,
+        wasm: &[u8],
+        bodies: PrimaryMap<LocalFunctionIndex, Range<usize>>,
+        metrics: Arc<Mutex<ModuleMetrics>>,
+    ) -> (Arc<Self>, PrimaryMap<LocalFunctionIndex, FunctionBody>) {
+        let compilation = Arc::new_cyclic(|compilation: &Weak<Self>| {
+            let functions = bodies
+                .keys()
+                .map(|index| LazyFunction::new(compilation.clone(), index))
+                .collect::<Box<[_]>>();
+            Self {
+                engine: engine.cloned(),
+                module: Arc::new(compile_info.module.clone()),
+                compile_info,
+                module_translation,
+                wasm: wasm.into(),
+                bodies,
+                functions,
+                state: Mutex::new(LazyState::default()),
+                metrics,
+            }
+        });
+        let stubs = compilation
+            .functions()
+            .iter()
+            .map(|function| FunctionBody {
+                body: stub(function),
+                unwind_info: None,
+            })
+            .collect();
+        (compilation, stubs)
+    }
+
+    fn functions(&self) -> &[LazyFunction] {
+        &self.functions
+    }
+
+    /// Compiles the whole module and serializes it, as it would have been
+    /// without lazy compilation.
+    pub(crate) fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
+        let mut engine_inner = self.engine.inner_mut();
+        ArtifactBuild::new(
+            &mut engine_inner,
+            &self.wasm,
+            self.engine.target(),
+            self.compile_info.memory_styles.clone(),
+            self.compile_info.table_styles.clone(),
+        )
+        .map_err(|error| SerializeError::Generic(error.to_string()))?
+        .serialize()
+    }
+
+    /// Records where the rest of the module has been allocated, which
+    /// must be done before any function is compiled.
+    pub(crate) fn set_linked(&self, linked: LinkedModule) {
+        self.state.lock().unwrap().linked = Some(linked);
+    }
+
+    /// Compiles the function `index`, if it hasn't been already, and
+    /// returns the address of its code.
+    fn compile(&self, index: LocalFunctionIndex) -> Result<usize, CompileError> {
+        let mut state = self.state.lock().unwrap();
+        let function = &self.functions()[index.index()];
+        // Another thread may have compiled the function in the meantime.
+        if function.is_compiled() {
+            return Ok(function.target.load(Ordering::Acquire));
+        }
+
+        let mut engine_inner = self.engine.inner_mut();
+        let start = Instant::now();
+        let (compiled, eh_frame) = self.compile_with(engine_inner.compiler()?, index)?;
+        self.metrics
+            .lock()
+            .unwrap()
+            .add_compile_time(start.elapsed());
+        let address = self.install(&mut state, &mut engine_inner, index, compiled, eh_frame)?;
+        function.target.store(address, Ordering::Release);
+        Ok(address)
+    }
+
+    /// Recompiles the function `index` with `compiler`, and makes its
+    /// stub jump to the new code from then on.
+    ///
+    /// The engine isn't locked while the function is being compiled. The
+    /// code it replaces is kept until the module is dropped.
+    pub(crate) fn recompile(
+        &self,
+        compiler: &dyn Compiler,
+        index: LocalFunctionIndex,
+    ) -> Result<(), CompileError> {
+        let start = Instant::now();
+        let (compiled, eh_frame) = self.compile_with(compiler, index)?;
+        self.metrics
+            .lock()
+            .unwrap()
+            .add_optimized_compile_time(start.elapsed());
+        let mut state = self.state.lock().unwrap();
+        let mut engine_inner = self.engine.inner_mut();
+        let address = self.install(&mut state, &mut engine_inner, index, compiled, eh_frame)?;
+        self.functions()[index.index()]
+            .target
+            .store(address, Ordering::Release);
+        self.metrics.lock().unwrap().optimized_functions += 1;
+        Ok(())
+    }
+
+    /// The functions of the module, those already called first and then
+    /// the others, each in the order of their index.
+    pub(crate) fn functions_by_hotness(&self) -> Vec<LocalFunctionIndex> {
+        let (mut called, not_called): (Vec<_>, Vec<_>) = self
+            .bodies
+            .keys()
+            .partition(|index| self.functions()[index.index()].is_compiled());
+        called.extend(not_called);
+        called
+    }
+
+    fn compile_with(
+        &self,
+        compiler: &dyn Compiler,
+        index: LocalFunctionIndex,
+    ) -> Result<(CompiledFunction, Option<CustomSection>), CompileError> {
+        let range = self.bodies[index].clone();
+        let input = FunctionBodyData {
+            data: &self.wasm[range.clone()],
+            module_offset: range.start,
+        };
+        compiler.compile_function(
+            self.engine.target(),
+            &self.compile_info,
+            &self.module_translation,
+            index,
+            &input,
+        )
+    }
+
+    /// Allocates and links the code of the function `index`, returning
+    /// its address.
+    fn install(
+        &self,
+        state: &mut LazyState,
+        engine_inner: &mut EngineInner,
+        index: LocalFunctionIndex,
+        compiled: CompiledFunction,
+        eh_frame: Option<CustomSection>,
+    ) -> Result<usize, CompileError> {
+        // Calls out of the function go through veneers placed after it.
+        let mut body = compiled.body.body;
+        let veneers_start = (body.len() + VENEER_LEN - 1) & !(VENEER_LEN - 1);
+        let num_veneers = compiled
+            .relocations
+            .iter()
+            .filter(|r| needs_veneer(index, r.kind, r.reloc_target))
+            .count();
+        body.resize(veneers_start + num_veneers * VENEER_LEN, 0x90);
+        let functions = std::iter::once(FunctionBody {
+            body,
+            unwind_info: compiled.body.unwind_info,
+        })
+        .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+        let sections = eh_frame
+            .into_iter()
+            .collect::<PrimaryMap<SectionIndex, _>>();
+
+        let (allocated_functions, _, _, allocated_sections) = engine_inner.allocate(
+            &self.module,
+            &functions,
+            &PrimaryMap::new(),
+            &PrimaryMap::new(),
+            &sections,
+        )?;
+        let extent = &allocated_functions[LocalFunctionIndex::new(0)];
+        let address = *extent.ptr as usize;
+        self.metrics.lock().unwrap().code_size += extent.length;
+
+        let linked = state
+            .linked
+            .as_ref()
+            .expect("lazily compiled module was not linked");
+        let target_of = |r: &Relocation| match r.reloc_target {
+            RelocationTarget::LocalFunc(target) if target == index => address,
+            _ => relocation_target(
+                r,
+                &linked.functions,
+                &linked.custom_sections,
+                linked.libcall_trampolines,
+                linked.libcall_trampoline_len,
+            ),
+        };
+        let mut veneer_address = address + veneers_start;
+        for r in &compiled.relocations {
+            let mut target = target_of(r);
+            if needs_veneer(index, r.kind, r.reloc_target) {
+                unsafe {
+                    std::ptr::copy_nonoverlapping(
+                        veneer(target).as_ptr(),
+                        veneer_address as *mut u8,
+                        VENEER_LEN,
+                    );
+                }
+                target = veneer_address;
+                veneer_address += VENEER_LEN;
+            }
+            write_relocation(address, r, target);
+        }
+        for (i, section) in sections.iter() {
+            for r in &section.relocations {
+                write_relocation(*allocated_sections[i] as usize, r, target_of(r));
+            }
+        }
+
+        engine_inner.publish_compiled_code();
+        let eh_frame = sections.get(SectionIndex::new(0)).map(|section| unsafe {
+            std::slice::from_raw_parts(
+                *allocated_sections[SectionIndex::new(0)],
+                section.bytes.len(),
+            )
+        });
+        engine_inner.publish_eh_frame(eh_frame)?;
+        state.code_memory.push(engine_inner.take_code_memory());
+        if let Some(strategy) = self.engine.perf() {
+            let code = unsafe { std::slice::from_raw_parts(address as *const u8, extent.length) };
+            let name = self.module.function_name(self.module.func_index(index));
+            register_function(strategy, &name, code);
+        }
+
+        state
+            .frame_info_registrations
+            .push(register_function_frame_info(
+                self.module.clone(),
+                index,
+                extent,
+                compiled.frame_info,
+            ));
+        Ok(address)
+    }
+}
+
+/// Recompiles the functions of a lazily compiled module with the
+/// optimizing `compiler` on a background thread, the functions called
+/// by the time it starts first.
+///
+/// Functions that fail to compile keep running in the baseline tier, and
+/// the thread returns the first of these failures once it is done. It
+/// stops early once the module is dropped.
+pub(crate) fn start_tier_up(
+    compilation: &Arc<LazyCompilation>,
+    compiler: Arc<dyn Compiler>,
+) -> std::io::Result<JoinHandle<Result<(), CompileError>>> {
+    let compilation = Arc::downgrade(compilation);
+    std::thread::Builder::new()
+        .name("wasmer-tier-up".to_string())
+        .spawn(move || {
+            let functions = match compilation.upgrade() {
+                Some(compilation) => compilation.functions_by_hotness(),
+                None => return Ok(()),
+            };
+            let mut result = Ok(());
+            for index in functions {
+                let compilation = match compilation.upgrade() {
+                    Some(compilation) => compilation,
+                    None => break,
+                };
+                if let Err(error) = compilation.recompile(&*compiler, index) {
+                    if result.is_ok() {
+                        result = Err(error);
+                    }
+                }
+            }
+            result
+        })
+}
+
+/// Whether a relocation may not reach its target from a separately
+/// allocated function.
+fn needs_veneer(index: LocalFunctionIndex, kind: RelocationKind, target: RelocationTarget) -> bool {
+    matches!(
+        kind,
+        RelocationKind::X86PCRel4 | RelocationKind::X86CallPCRel4
+    ) && target != RelocationTarget::LocalFunc(index)
+}
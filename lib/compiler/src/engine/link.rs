@@ -17,7 +17,25 @@ fn apply_relocation(
     libcall_trampolines: SectionIndex,
     libcall_trampoline_len: usize,
 ) {
-    let target_func_address: usize = match r.reloc_target {
+    let target_func_address = relocation_target(
+        r,
+        allocated_functions,
+        allocated_sections,
+        libcall_trampolines,
+        libcall_trampoline_len,
+    );
+    write_relocation(body, r, target_func_address);
+}
+
+/// Returns the address the relocation `r` points to.
+pub(crate) fn relocation_target(
+    r: &Relocation,
+    allocated_functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+    allocated_sections: &PrimaryMap<SectionIndex, SectionBodyPtr>,
+    libcall_trampolines: SectionIndex,
+    libcall_trampoline_len: usize,
+) -> usize {
+    match r.reloc_target {
         RelocationTarget::LocalFunc(index) => *allocated_functions[index].ptr as usize,
         RelocationTarget::LibCall(libcall) => {
             // Use the direct target of the libcall if the relocation supports
@@ -35,8 +53,12 @@ fn apply_relocation(
         RelocationTarget::CustomSection(custom_section) => {
             *allocated_sections[custom_section] as usize
         }
-    };
+    }
+}
 
+/// Patches the code at `body` so that the relocation `r` points to
+/// `target_func_address`.
+pub(crate) fn write_relocation(body: usize, r: &Relocation, target_func_address: usize) {
     match r.kind {
         RelocationKind::Abs8 => unsafe {
             let (reloc_address, reloc_delta) = r.for_address(body, target_func_address as u64);
@@ -93,6 +115,33 @@ fn apply_relocation(
                 | read_unaligned(reloc_address as *mut u32);
             write_unaligned(reloc_address as *mut u32, reloc_delta);
         },
+        RelocationKind::RiscvCall => unsafe {
+            let (reloc_address, reloc_delta) = r.for_address(body, target_func_address as u64);
+            if reloc_delta as i64 != reloc_delta as i32 as i64 {
+                panic!(
+                    "Relocation to big for {:?} for {:?} with {:x}, current val {:x}",
+                    r.kind,
+                    r.reloc_target,
+                    reloc_delta,
+                    read_unaligned(reloc_address as *mut u32)
+                )
+            }
+            let auipc = riscv_hi20(reloc_delta) | read_unaligned(reloc_address as *mut u32);
+            write_unaligned(reloc_address as *mut u32, auipc);
+            let jalr_address = reloc_address + 4;
+            let jalr = riscv_lo12_i(reloc_delta) | read_unaligned(jalr_address as *mut u32);
+            write_unaligned(jalr_address as *mut u32, jalr);
+        },
+        RelocationKind::RiscvPCRelHi20 => unsafe {
+            let (reloc_address, reloc_delta) = r.for_address(body, target_func_address as u64);
+            let auipc = riscv_hi20(reloc_delta) | read_unaligned(reloc_address as *mut u32);
+            write_unaligned(reloc_address as *mut u32, auipc);
+        },
+        RelocationKind::RiscvPCRelLo12I => unsafe {
+            let (reloc_address, reloc_delta) = r.for_address(body, target_func_address as u64);
+            let insn = riscv_lo12_i(reloc_delta) | read_unaligned(reloc_address as *mut u32);
+            write_unaligned(reloc_address as *mut u32, insn);
+        },
         kind => panic!(
             "Relocation kind unsupported in the current architecture {}",
             kind
@@ -100,6 +149,19 @@ fn apply_relocation(
     }
 }
 
+/// The immediate of an `auipc` adding the high part of `delta` to the PC.
+///
+/// The low part is sign-extended by the instruction using it, so the high
+/// part is rounded to compensate.
+fn riscv_hi20(delta: u64) -> u32 {
+    (delta as u32).wrapping_add(0x800) & 0xffff_f000
+}
+
+/// The immediate of an I-type instruction adding the low part of `delta`.
+fn riscv_lo12_i(delta: u64) -> u32 {
+    (delta as u32 & 0xfff) << 20
+}
+
 /// Links a module, patching the allocated functions with the
 /// required relocations and jump tables.
 pub fn link_module(
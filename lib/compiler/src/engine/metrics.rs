@@ -0,0 +1,38 @@
+//! Measurements of the compilation of a module.
+
+use std::time::Duration;
+
+/// How long a module took to compile, and how much code it generated.
+///
+/// For lazily compiled modules, the numbers grow as functions are
+/// compiled on their first call, and recompiled by the optimized tier.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleMetrics {
+    /// The time spent compiling the module with the compiler of the
+    /// engine, or `None` if the module was deserialized.
+    pub compile_time: Option<Duration>,
+    /// The time spent recompiling the functions of the module with the
+    /// optimized tier so far, or `None` if it isn't tiered up.
+    pub optimized_compile_time: Option<Duration>,
+    /// The number of functions of the module swapped to the code of the
+    /// optimized tier so far.
+    pub optimized_functions: usize,
+    /// The size in bytes of the machine code generated for the functions
+    /// of the module, including the code of the functions replaced by
+    /// the optimized tier.
+    pub code_size: usize,
+}
+
+impl ModuleMetrics {
+    /// Adds to the time spent compiling with the compiler of the engine.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn add_compile_time(&mut self, time: Duration) {
+        *self.compile_time.get_or_insert(Duration::ZERO) += time;
+    }
+
+    /// Adds to the time spent compiling with the optimized tier.
+    #[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+    pub(crate) fn add_optimized_compile_time(&mut self, time: Duration) {
+        *self.optimized_compile_time.get_or_insert(Duration::ZERO) += time;
+    }
+}
@@ -16,13 +16,27 @@ mod builder;
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
 mod code_memory;
+#[cfg(all(feature = "debug-info", not(target_arch = "wasm32")))]
+mod debug_info;
+#[cfg(all(feature = "debug-info", not(target_arch = "wasm32")))]
+mod gdb_jit;
 #[cfg(feature = "translator")]
 mod inner;
+#[cfg(all(feature = "compiler", target_arch = "x86_64", unix))]
+mod lazy;
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
 mod link;
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
+mod metrics;
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+mod perf;
+#[cfg(feature = "translator")]
+mod quarantine;
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
 mod unwind;
 
 pub use self::error::{InstantiationError, LinkError};
@@ -31,6 +45,8 @@ pub use self::resolver::resolve_imports;
 #[cfg(not(target_arch = "wasm32"))]
 pub use self::trap::*;
 #[cfg(not(target_arch = "wasm32"))]
+pub(crate) use self::tunables::PooledTunables;
+#[cfg(not(target_arch = "wasm32"))]
 pub use self::tunables::Tunables;
 
 #[cfg(feature = "translator")]
@@ -46,3 +62,11 @@ pub use self::inner::{Engine, EngineInner};
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
 pub use self::link::link_module;
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::metrics::ModuleMetrics;
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::perf::PerfStrategy;
+#[cfg(feature = "translator")]
+pub use self::quarantine::QuarantinePolicy;
@@ -0,0 +1,168 @@
+//! Descriptions of the compiled functions for the Linux `perf` profiler.
+//!
+//! Without them, `perf report` attributes the samples taken in compiled
+//! code to anonymous memory. A perf map is a text file listing the name
+//! and address range of every function, that `perf report` reads as is.
+//! A jitdump also holds a copy of the code, so `perf inject --jit` can
+//! turn it into objects that `perf annotate` can disassemble.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+/// How the compiled functions are described to `perf`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerfStrategy {
+    /// Appends the functions to `/tmp/perf-<pid>.map`.
+    PerfMap,
+    /// Writes the functions and their code to `jit-<pid>.dump` in the
+    /// current directory, for `perf record -k mono` and `perf inject --jit`.
+    JitDump,
+}
+
+lazy_static::lazy_static! {
+    static ref PERF_MAP: Mutex<Option<File>> = Mutex::new(None);
+    #[cfg(target_os = "linux")]
+    static ref JIT_DUMP: Mutex<Option<JitDump>> = Mutex::new(None);
+}
+
+/// Describes a function whose code is at `code` to `perf`.
+///
+/// Failing to write the file only costs the names in the profiles, so
+/// errors are ignored.
+pub(crate) fn register_function(strategy: PerfStrategy, name: &str, code: &[u8]) {
+    let _ = match strategy {
+        PerfStrategy::PerfMap => write_perf_map(name, code),
+        PerfStrategy::JitDump => write_jit_dump(name, code),
+    };
+}
+
+fn write_perf_map(name: &str, code: &[u8]) -> io::Result<()> {
+    let mut file = PERF_MAP.lock().unwrap();
+    if file.is_none() {
+        let path = format!("/tmp/perf-{}.map", std::process::id());
+        *file = Some(OpenOptions::new().create(true).append(true).open(path)?);
+    }
+    // A single write, so that the lines of several processes sharing the
+    // file don't interleave
+    let line = format!("{:x} {:x} {}\n", code.as_ptr() as usize, code.len(), name);
+    file.as_mut().unwrap().write_all(line.as_bytes())
+}
+
+#[cfg(target_os = "linux")]
+fn write_jit_dump(name: &str, code: &[u8]) -> io::Result<()> {
+    let mut dump = JIT_DUMP.lock().unwrap();
+    if dump.is_none() {
+        *dump = Some(JitDump::create()?);
+    }
+    dump.as_mut().unwrap().code_load(name, code)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn write_jit_dump(_name: &str, _code: &[u8]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "jitdump is only supported on Linux",
+    ))
+}
+
+/// A jitdump file, in the format of the specification found in the
+/// `tools/perf/Documentation` directory of Linux.
+#[cfg(target_os = "linux")]
+struct JitDump {
+    file: File,
+    /// The header of the file, mapped as executable so that `perf record`
+    /// finds the file in the mmap events of the process.
+    _marker: memmap2::Mmap,
+    code_index: u64,
+}
+
+#[cfg(target_os = "linux")]
+impl JitDump {
+    const MAGIC: u32 = 0x4a69_5444;
+    const VERSION: u32 = 1;
+    const HEADER_SIZE: u32 = 40;
+    const JIT_CODE_LOAD: u32 = 0;
+
+    fn create() -> io::Result<Self> {
+        let pid = std::process::id();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(format!("jit-{}.dump", pid))?;
+
+        let machine: u32 = if cfg!(target_arch = "x86_64") {
+            62 // EM_X86_64
+        } else if cfg!(target_arch = "aarch64") {
+            183 // EM_AARCH64
+        } else if cfg!(target_arch = "riscv64") {
+            243 // EM_RISCV
+        } else {
+            0 // EM_NONE
+        };
+        let mut header = Vec::with_capacity(Self::HEADER_SIZE as usize);
+        header.extend_from_slice(&Self::MAGIC.to_ne_bytes());
+        header.extend_from_slice(&Self::VERSION.to_ne_bytes());
+        header.extend_from_slice(&Self::HEADER_SIZE.to_ne_bytes());
+        header.extend_from_slice(&machine.to_ne_bytes());
+        header.extend_from_slice(&0u32.to_ne_bytes());
+        header.extend_from_slice(&pid.to_ne_bytes());
+        header.extend_from_slice(&timestamp().to_ne_bytes());
+        header.extend_from_slice(&0u64.to_ne_bytes());
+        file.write_all(&header)?;
+
+        let marker = unsafe {
+            memmap2::MmapOptions::new()
+                .len(Self::HEADER_SIZE as usize)
+                .map_exec(&file)?
+        };
+        Ok(Self {
+            file,
+            _marker: marker,
+            code_index: 0,
+        })
+    }
+
+    fn code_load(&mut self, name: &str, code: &[u8]) -> io::Result<()> {
+        let address = code.as_ptr() as u64;
+        // The record header, the fields of the load, the name and its
+        // terminator, then the code
+        let size = 16 + 40 + name.len() + 1 + code.len();
+        let mut record = Vec::with_capacity(size);
+        record.extend_from_slice(&Self::JIT_CODE_LOAD.to_ne_bytes());
+        record.extend_from_slice(&(size as u32).to_ne_bytes());
+        record.extend_from_slice(&timestamp().to_ne_bytes());
+        record.extend_from_slice(&std::process::id().to_ne_bytes());
+        record.extend_from_slice(&thread_id().to_ne_bytes());
+        record.extend_from_slice(&address.to_ne_bytes());
+        record.extend_from_slice(&address.to_ne_bytes());
+        record.extend_from_slice(&(code.len() as u64).to_ne_bytes());
+        record.extend_from_slice(&self.code_index.to_ne_bytes());
+        record.extend_from_slice(name.as_bytes());
+        record.push(0);
+        record.extend_from_slice(code);
+        self.code_index += 1;
+        self.file.write_all(&record)
+    }
+}
+
+/// The `CLOCK_MONOTONIC` time in nanoseconds, which `perf record -k mono`
+/// timestamps its samples with.
+#[cfg(target_os = "linux")]
+fn timestamp() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+    }
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+#[cfg(target_os = "linux")]
+fn thread_id() -> u32 {
+    unsafe { libc::syscall(libc::SYS_gettid) as u32 }
+}
@@ -0,0 +1,268 @@
+//! Quarantining of misbehaving modules.
+//!
+//! An [`Engine`] can be shared by many tenants. When the instances of a
+//! module keep trapping or exhausting resources, the engine can be told
+//! to stop instantiating that module so a crash-looping guest doesn't keep
+//! consuming the host.
+//!
+//! [`Engine`]: crate::Engine
+
+use std::collections::HashMap;
+use std::fmt;
+use wasmer_types::{ModuleHash, ModuleId, ModuleInfo, TrapCode};
+
+/// The number of modules whose failures are counted at once. Past it,
+/// the counts of the modules that aren't quarantined are reset, so that
+/// an engine running many short-lived modules doesn't keep growing.
+const MAX_TRACKED_MODULES: usize = 1024;
+
+/// The policy deciding when an [`Engine`] quarantines a module.
+///
+/// A quarantined module can't be instantiated anymore (existing
+/// instances keep working) until it is released with
+/// [`Engine::release_module`].
+///
+/// By default no limit is set, so modules are only quarantined
+/// explicitly, through [`Engine::quarantine_module`].
+///
+/// [`Engine`]: crate::Engine
+/// [`Engine::release_module`]: crate::Engine::release_module
+/// [`Engine::quarantine_module`]: crate::Engine::quarantine_module
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuarantinePolicy {
+    /// Number of WebAssembly traps raised by a module's code after
+    /// which the module is quarantined.
+    pub max_traps: Option<u32>,
+    /// Number of resource exhaustions (call stack exhaustion, or failing
+    /// to allocate memories and tables on instantiation) after which
+    /// the module is quarantined.
+    pub max_resource_exhaustions: Option<u32>,
+}
+
+/// What the failures of a module are accounted by.
+///
+/// Modules are told apart by the hash of their bytes, so that compiling
+/// or deserializing a quarantined module again doesn't release it. Only
+/// the modules that weren't translated from bytes fall back to their id.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum ModuleKey {
+    Hash(ModuleHash),
+    Id(ModuleId),
+}
+
+impl ModuleKey {
+    pub(crate) fn new(hash: Option<&ModuleHash>, id: &ModuleId) -> Self {
+        match hash {
+            Some(hash) => Self::Hash(*hash),
+            None => Self::Id(id.clone()),
+        }
+    }
+}
+
+impl From<&ModuleInfo> for ModuleKey {
+    fn from(module: &ModuleInfo) -> Self {
+        Self::new(module.hash.as_ref(), &module.id)
+    }
+}
+
+/// The kind of failure reported for a module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ModuleFailure {
+    Trap,
+    ResourceExhaustion,
+}
+
+impl ModuleFailure {
+    pub(crate) fn from_trap_code(trap_code: TrapCode) -> Self {
+        match trap_code {
+            TrapCode::StackOverflow => Self::ResourceExhaustion,
+            _ => Self::Trap,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ModuleHealth {
+    traps: u32,
+    resource_exhaustions: u32,
+    quarantined: Option<String>,
+}
+
+/// The per-module failure accounting of an engine.
+#[derive(Debug, Default)]
+pub(crate) struct Quarantine {
+    policy: QuarantinePolicy,
+    modules: HashMap<ModuleKey, ModuleHealth>,
+}
+
+impl Quarantine {
+    pub(crate) fn policy(&self) -> QuarantinePolicy {
+        self.policy
+    }
+
+    pub(crate) fn set_policy(&mut self, policy: QuarantinePolicy) {
+        self.policy = policy;
+    }
+
+    /// Records a failure of the given module, quarantining it if the
+    /// policy limit is reached.
+    ///
+    /// Failures without a limit aren't recorded at all.
+    pub(crate) fn report(&mut self, module: &ModuleKey, failure: ModuleFailure, cause: &str) {
+        let limit = match failure {
+            ModuleFailure::Trap => self.policy.max_traps,
+            ModuleFailure::ResourceExhaustion => self.policy.max_resource_exhaustions,
+        };
+        let limit = match limit {
+            Some(limit) => limit,
+            None => return,
+        };
+        if self.modules.len() >= MAX_TRACKED_MODULES && !self.modules.contains_key(module) {
+            self.modules
+                .retain(|_, health| health.quarantined.is_some());
+        }
+
+        let health = self.modules.entry(module.clone()).or_default();
+        if health.quarantined.is_some() {
+            return;
+        }
+        let (count, what) = match failure {
+            ModuleFailure::Trap => {
+                health.traps += 1;
+                (health.traps, "trapped")
+            }
+            ModuleFailure::ResourceExhaustion => {
+                health.resource_exhaustions += 1;
+                (health.resource_exhaustions, "exhausted resources")
+            }
+        };
+        if count >= limit {
+            health.quarantined = Some(format!(
+                "{} {} (last failure: {})",
+                what,
+                Times(count),
+                cause
+            ));
+        }
+    }
+
+    pub(crate) fn quarantine(&mut self, module: &ModuleKey, reason: String) {
+        self.modules.entry(module.clone()).or_default().quarantined = Some(reason);
+    }
+
+    pub(crate) fn release(&mut self, module: &ModuleKey) {
+        self.modules.remove(module);
+    }
+
+    pub(crate) fn reason(&self, module: &ModuleKey) -> Option<String> {
+        self.modules.get(module)?.quarantined.clone()
+    }
+}
+
+/// Returns a description of a quarantined module suitable for errors.
+pub(crate) fn describe(module: &ModuleInfo, reason: &str) -> String {
+    match &module.name {
+        Some(name) => format!("module `{}` is quarantined: {}", name, reason),
+        None => format!("module is quarantined: {}", reason),
+    }
+}
+
+struct Times(u32);
+
+impl fmt::Display for Times {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            1 => write!(f, "once"),
+            n => write!(f, "{} times", n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quarantine_after_limit() {
+        let module = ModuleKey::Id(ModuleId::default());
+        let mut quarantine = Quarantine::default();
+        quarantine.set_policy(QuarantinePolicy {
+            max_traps: Some(2),
+            max_resource_exhaustions: Some(1),
+        });
+
+        quarantine.report(&module, ModuleFailure::Trap, "unreachable");
+        assert_eq!(quarantine.reason(&module), None);
+        quarantine.report(&module, ModuleFailure::Trap, "unreachable");
+        assert_eq!(
+            quarantine.reason(&module).as_deref(),
+            Some("trapped 2 times (last failure: unreachable)")
+        );
+
+        quarantine.release(&module);
+        assert_eq!(quarantine.reason(&module), None);
+        quarantine.report(
+            &module,
+            ModuleFailure::ResourceExhaustion,
+            "call stack exhausted",
+        );
+        assert_eq!(
+            quarantine.reason(&module).as_deref(),
+            Some("exhausted resources once (last failure: call stack exhausted)")
+        );
+    }
+
+    #[test]
+    fn no_quarantine_by_default() {
+        let module = ModuleKey::Id(ModuleId::default());
+        let mut quarantine = Quarantine::default();
+        for _ in 0..100 {
+            quarantine.report(&module, ModuleFailure::Trap, "unreachable");
+        }
+        assert_eq!(quarantine.reason(&module), None);
+        assert!(quarantine.modules.is_empty());
+    }
+
+    #[test]
+    fn tracked_modules_are_bounded() {
+        let mut quarantine = Quarantine::default();
+        quarantine.set_policy(QuarantinePolicy {
+            max_traps: Some(2),
+            max_resource_exhaustions: None,
+        });
+        let quarantined = ModuleKey::Id(ModuleId::default());
+        quarantine.report(&quarantined, ModuleFailure::Trap, "unreachable");
+        quarantine.report(&quarantined, ModuleFailure::Trap, "unreachable");
+
+        for _ in 0..MAX_TRACKED_MODULES * 3 {
+            quarantine.report(
+                &ModuleKey::Id(ModuleId::default()),
+                ModuleFailure::Trap,
+                "unreachable",
+            );
+        }
+        assert!(quarantine.modules.len() <= MAX_TRACKED_MODULES);
+        // Quarantined modules are kept.
+        assert!(quarantine.reason(&quarantined).is_some());
+    }
+
+    #[test]
+    fn same_bytes_share_the_quarantine() {
+        let mut quarantine = Quarantine::default();
+        let mut module = ModuleInfo::new();
+        module.hash = Some(ModuleHash::hash(b"\0asm\x01\0\0\0"));
+        quarantine.quarantine(&ModuleKey::from(&module), "crash loop".to_string());
+
+        // A new compilation of the same bytes gets a new id.
+        let mut recompiled = module.clone();
+        recompiled.id = ModuleId::default();
+        assert_eq!(
+            quarantine.reason(&ModuleKey::from(&recompiled)).as_deref(),
+            Some("crash loop")
+        );
+
+        let mut other = ModuleInfo::new();
+        other.hash = Some(ModuleHash::hash(b"\0asm\x01\0\0\0\0"));
+        assert_eq!(quarantine.reason(&ModuleKey::from(&other)), None);
+    }
+}
@@ -3,6 +3,7 @@ use backtrace::Backtrace;
 use std::error::Error;
 use std::fmt;
 use std::sync::Arc;
+use wasmer_types::METADATA_NAME;
 use wasmer_vm::{Trap, TrapCode};
 
 /// A struct representing an aborted instruction execution, with a message
@@ -46,6 +47,9 @@ struct RuntimeErrorInner {
     wasm_trace: Vec<FrameInfo>,
     /// The native backtrace
     native_trace: Backtrace,
+    /// Key/value tags describing where the error happened (tenant, request
+    /// id, ...), copied from the store the trap occurred in.
+    tags: Vec<(String, String)>,
 }
 
 fn _assert_trap_is_sync_and_send(t: &Trap) -> (&dyn Sync, &dyn Send) {
@@ -110,6 +114,24 @@ impl RuntimeError {
                 trap_code,
                 backtrace,
             } => Self::new_with_trace(&info, None, RuntimeErrorSource::Trap(trap_code), backtrace),
+            // A trap raised by the interpreter, whose frames aren't on the
+            // native stack
+            Trap::Interpreter {
+                trap_code,
+                pcs,
+                backtrace,
+            } => {
+                let mut error = Self::new_with_trace(
+                    &info,
+                    None,
+                    RuntimeErrorSource::Trap(trap_code),
+                    backtrace,
+                );
+                let inner = Arc::get_mut(&mut error.inner).unwrap();
+                let interpreted = pcs.iter().filter_map(|pc| info.lookup_frame_info(*pc));
+                inner.wasm_trace.splice(0..0, interpreted);
+                error
+            }
         }
     }
 
@@ -192,6 +214,7 @@ impl RuntimeError {
                 source,
                 wasm_trace,
                 native_trace,
+                tags: Vec::new(),
             }),
         }
     }
@@ -207,6 +230,39 @@ impl RuntimeError {
         &self.inner.wasm_trace
     }
 
+    /// Returns the key/value tags attached to this error.
+    ///
+    /// Errors raised while calling into an instance carry the tags of
+    /// its store (see `Store::set_tag` in the `wasmer` crate).
+    pub fn tags(&self) -> &[(String, String)] {
+        &self.inner.tags
+    }
+
+    /// Attaches the given key/value tags to this error, replacing any
+    /// previous tags.
+    ///
+    /// Errors that are already shared (cloned) are returned unchanged, so
+    /// the tags of the first embedder to see the error win.
+    pub fn with_tags<I, K, V>(self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        match Arc::try_unwrap(self.inner) {
+            Ok(mut inner) => {
+                inner.tags = tags
+                    .into_iter()
+                    .map(|(k, v)| (k.into(), v.into()))
+                    .collect();
+                Self {
+                    inner: Arc::new(inner),
+                }
+            }
+            Err(inner) => Self { inner },
+        }
+    }
+
     /// Attempts to downcast the `RuntimeError` to a concrete type.
     pub fn downcast<T: Error + 'static>(self) -> Result<T, Self> {
         match Arc::try_unwrap(self.inner) {
@@ -231,6 +287,22 @@ impl RuntimeError {
         }
     }
 
+    /// Returns the trap code, if it's a Trap, without consuming the error.
+    pub fn trap_code(&self) -> Option<TrapCode> {
+        if let RuntimeErrorSource::Trap(trap_code) = self.inner.source {
+            Some(trap_code)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the offset in the Wasm module of the instruction that
+    /// trapped, if it's a Trap and the faulting frame is known.
+    pub fn trap_offset(&self) -> Option<usize> {
+        self.trap_code()?;
+        self.trace().first().map(|frame| frame.module_offset())
+    }
+
     /// Returns true if the `RuntimeError` is the same as T
     pub fn is<T: Error + 'static>(&self) -> bool {
         match &self.inner.source {
@@ -246,6 +318,7 @@ impl fmt::Debug for RuntimeError {
             .field("source", &self.inner.source)
             .field("wasm_trace", &self.inner.wasm_trace)
             .field("native_trace", &self.inner.native_trace)
+            .field("tags", &self.inner.tags)
             .finish()
     }
 }
@@ -253,32 +326,73 @@ impl fmt::Debug for RuntimeError {
 impl fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "RuntimeError: {}", self.message())?;
+        if !self.inner.tags.is_empty() {
+            write!(f, " [")?;
+            for (i, (key, value)) in self.inner.tags.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}={}", key, value)?;
+            }
+            write!(f, "]")?;
+        }
         let trace = self.trace();
         if trace.is_empty() {
             return Ok(());
         }
         for frame in self.trace().iter() {
-            let name = frame.module_name();
-            let func_index = frame.func_index();
             writeln!(f)?;
-            write!(f, "    at ")?;
-            match frame.function_name() {
-                Some(name) => match rustc_demangle::try_demangle(name) {
-                    Ok(name) => write!(f, "{}", name)?,
-                    Err(_) => write!(f, "{}", name)?,
-                },
-                None => write!(f, "<unnamed>")?,
-            }
+            write_frame(f, frame)?;
+        }
+        write_module_metadata(f, trace)
+    }
+}
+
+/// Writes `frame` as a line of a trace.
+pub(super) fn write_frame(f: &mut fmt::Formatter<'_>, frame: &FrameInfo) -> fmt::Result {
+    write!(f, "    at ")?;
+    match frame.function_name() {
+        Some(name) => match rustc_demangle::try_demangle(name) {
+            Ok(name) => write!(f, "{}", name)?,
+            Err(_) => write!(f, "{}", name)?,
+        },
+        None => write!(f, "<unnamed>")?,
+    }
+    write!(
+        f,
+        " ({}[{}]:0x{:x})",
+        frame.module_name(),
+        frame.func_index(),
+        frame.module_offset()
+    )
+}
+
+/// Writes the metadata of the modules of `trace`, once per module, to tell
+/// which builds of them the trace comes from.
+fn write_module_metadata(f: &mut fmt::Formatter<'_>, trace: &[FrameInfo]) -> fmt::Result {
+    let mut written = Vec::new();
+    for frame in trace {
+        if written.contains(&frame.module_id()) {
+            continue;
+        }
+        written.push(frame.module_id());
+        // The name is already the one of the frames
+        let metadata = frame
+            .module_metadata()
+            .iter()
+            .filter(|(key, _)| key.as_str() != METADATA_NAME)
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>();
+        if !metadata.is_empty() {
             write!(
                 f,
-                " ({}[{}]:0x{:x})",
-                name,
-                func_index,
-                frame.module_offset()
+                "\n    module {}: {}",
+                frame.module_name(),
+                metadata.join(", ")
             )?;
         }
-        Ok(())
     }
+    Ok(())
 }
 
 impl std::error::Error for RuntimeError {
@@ -13,10 +13,10 @@
 //! ```
 use std::cmp;
 use std::collections::BTreeMap;
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 use wasmer_types::entity::{BoxedSlice, EntityRef, PrimaryMap};
 use wasmer_types::{CompiledFunctionFrameInfo, SourceLoc, TrapInformation};
-use wasmer_types::{LocalFunctionIndex, ModuleInfo};
+use wasmer_types::{LocalFunctionIndex, ModuleHash, ModuleId, ModuleInfo};
 use wasmer_vm::FunctionBodyPtr;
 
 lazy_static::lazy_static! {
@@ -54,15 +54,12 @@ pub struct GlobalFrameInfoRegistration {
 struct ModuleInfoFrameInfo {
     start: usize,
     functions: BTreeMap<usize, FunctionInfo>,
-    module: ModuleInfo,
-    frame_infos: PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo>,
+    module: Arc<ModuleInfo>,
+    /// The metadata of the module, shared by the frames of a trace.
+    metadata: Arc<BTreeMap<String, String>>,
 }
 
 impl ModuleInfoFrameInfo {
-    fn function_debug_info(&self, local_index: LocalFunctionIndex) -> &CompiledFunctionFrameInfo {
-        self.frame_infos.get(local_index).unwrap()
-    }
-
     /// Gets a function given a pc
     fn function_info(&self, pc: usize) -> Option<&FunctionInfo> {
         let (end, func) = self.functions.range(pc..).next()?;
@@ -78,6 +75,7 @@ impl ModuleInfoFrameInfo {
 struct FunctionInfo {
     start: usize,
     local_index: LocalFunctionIndex,
+    frame_info: CompiledFunctionFrameInfo,
 }
 
 impl GlobalFrameInfo {
@@ -93,7 +91,7 @@ impl GlobalFrameInfo {
         // machine instruction that corresponds to `pc`, which then allows us to
         // map that to a wasm original source location.
         let rel_pos = pc - func.start;
-        let instr_map = &module.function_debug_info(func.local_index).address_map;
+        let instr_map = &func.frame_info.address_map;
         let pos = match instr_map
             .instructions
             .binary_search_by_key(&rel_pos, |map| map.code_offset)
@@ -130,7 +128,10 @@ impl GlobalFrameInfo {
         };
         let func_index = module.module.func_index(func.local_index);
         Some(FrameInfo {
+            module_id: module.module.id.clone(),
+            module_hash: module.module.hash,
             module_name: module.module.name(),
+            module_metadata: module.metadata.clone(),
             func_index: func_index.index() as u32,
             function_name: module.module.function_names.get(&func_index).cloned(),
             instr,
@@ -142,7 +143,7 @@ impl GlobalFrameInfo {
     pub fn lookup_trap_info(&self, pc: usize) -> Option<&TrapInformation> {
         let module = self.module_info(pc)?;
         let func = module.function_info(pc)?;
-        let traps = &module.function_debug_info(func.local_index).traps;
+        let traps = &func.frame_info.traps;
         let idx = traps
             .binary_search_by_key(&((pc - func.start) as u32), |info| info.code_offset)
             .ok()?;
@@ -170,7 +171,7 @@ impl Drop for GlobalFrameInfoRegistration {
 
 /// Represents a continuous region of executable memory starting with a function
 /// entry point.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[repr(C)]
 pub struct FunctionExtent {
     /// Entry point for normal entry of the function. All addresses in the
@@ -195,12 +196,17 @@ pub fn register(
     let mut max = 0;
     let mut functions = BTreeMap::new();
     for (
-        i,
-        FunctionExtent {
-            ptr: start,
-            length: len,
-        },
-    ) in finished_functions.iter()
+        (
+            i,
+            FunctionExtent {
+                ptr: start,
+                length: len,
+            },
+        ),
+        frame_info,
+    ) in finished_functions
+        .iter()
+        .zip(frame_infos.into_iter().map(|(_, f)| f))
     {
         let start = **start as usize;
         // end is "last byte" of the function code
@@ -210,13 +216,46 @@ pub fn register(
         let func = FunctionInfo {
             start,
             local_index: i,
+            frame_info,
         };
         assert!(functions.insert(end, func).is_none());
     }
     if functions.is_empty() {
         return None;
     }
+    Some(insert(Arc::new(module), functions, min, max))
+}
+
+/// Registers the frame information of a single function of `module`,
+/// compiled and allocated separately from the rest of the module.
+///
+/// The returned object, when dropped, unregisters the function.
+pub fn register_function(
+    module: Arc<ModuleInfo>,
+    index: LocalFunctionIndex,
+    extent: &FunctionExtent,
+    frame_info: CompiledFunctionFrameInfo,
+) -> GlobalFrameInfoRegistration {
+    let start = *extent.ptr as usize;
+    let end = start + extent.length - 1;
+    let mut functions = BTreeMap::new();
+    functions.insert(
+        end,
+        FunctionInfo {
+            start,
+            local_index: index,
+            frame_info,
+        },
+    );
+    insert(module, functions, start, end)
+}
 
+fn insert(
+    module: Arc<ModuleInfo>,
+    functions: BTreeMap<usize, FunctionInfo>,
+    min: usize,
+    max: usize,
+) -> GlobalFrameInfoRegistration {
     let mut info = FRAME_INFO.write().unwrap();
     // First up assert that our chunk of jit functions doesn't collide with
     // any other known chunks of jit functions...
@@ -233,12 +272,12 @@ pub fn register(
         ModuleInfoFrameInfo {
             start: min,
             functions,
+            metadata: Arc::new(module.metadata.clone()),
             module,
-            frame_infos,
         },
     );
     assert!(prev.is_none());
-    Some(GlobalFrameInfoRegistration { key: max })
+    GlobalFrameInfoRegistration { key: max }
 }
 
 /// Description of a frame in a backtrace for a [`RuntimeError::trace`](crate::RuntimeError::trace).
@@ -251,7 +290,10 @@ pub fn register(
 /// [`RuntimeError`]: crate::RuntimeError
 #[derive(Debug, Clone)]
 pub struct FrameInfo {
+    module_id: ModuleId,
+    module_hash: Option<ModuleHash>,
     module_name: String,
+    module_metadata: Arc<BTreeMap<String, String>>,
     func_index: u32,
     function_name: Option<String>,
     func_start: SourceLoc,
@@ -267,6 +309,17 @@ impl FrameInfo {
         self.func_index
     }
 
+    /// Returns the process-unique id of the module that this frame is for.
+    pub fn module_id(&self) -> &ModuleId {
+        &self.module_id
+    }
+
+    /// Returns the hash of the bytes of the module that this frame is
+    /// for, if it was compiled from bytes.
+    pub fn module_hash(&self) -> Option<&ModuleHash> {
+        self.module_hash.as_ref()
+    }
+
     /// Returns the identifer of the module that this frame is for.
     ///
     /// ModuleInfo identifiers are present in the `name` section of a WebAssembly
@@ -280,6 +333,12 @@ impl FrameInfo {
         &self.module_name
     }
 
+    /// Returns the metadata attached to the module that this frame is for,
+    /// such as its version or build id.
+    pub fn module_metadata(&self) -> &BTreeMap<String, String> {
+        &self.module_metadata
+    }
+
     /// Returns a descriptive name of the function for this frame, if one is
     /// available.
     ///
@@ -1,7 +1,9 @@
 mod error;
 mod frame_info;
+mod stack_trace;
 pub use error::RuntimeError;
 pub use frame_info::{
-    register as register_frame_info, FrameInfo, FunctionExtent, GlobalFrameInfoRegistration,
-    FRAME_INFO,
+    register as register_frame_info, register_function as register_function_frame_info, FrameInfo,
+    FunctionExtent, GlobalFrameInfoRegistration, FRAME_INFO,
 };
+pub use stack_trace::RuntimeStackTrace;
@@ -0,0 +1,72 @@
+use super::error::write_frame;
+use super::frame_info::{FrameInfo, FRAME_INFO};
+use backtrace::Backtrace;
+use std::fmt;
+use wasmer_vm::{interpreted_host_callers, wasmer_vm_interpreter_trampoline};
+
+/// The WebAssembly frames running on the current thread, as captured by a
+/// host function to find out which guest code called it.
+#[derive(Debug, Clone)]
+pub struct RuntimeStackTrace {
+    frames: Vec<FrameInfo>,
+}
+
+impl RuntimeStackTrace {
+    /// Captures the WebAssembly frames running on the current thread.
+    ///
+    /// From a host function called by WebAssembly, the first frame is the
+    /// one of its caller, and the others the ones of the callers of that
+    /// frame, including the WebAssembly frames below host functions that
+    /// called back into WebAssembly. Outside of a call into WebAssembly,
+    /// the trace is empty.
+    pub fn capture() -> Self {
+        let info = FRAME_INFO.read().unwrap();
+        let mut interpreted = interpreted_host_callers().into_iter();
+        let mut frames = Vec::new();
+        for frame in Backtrace::new_unresolved().frames() {
+            let pc = frame.ip() as usize;
+            if pc == 0 {
+                continue;
+            }
+            if frame.symbol_address() as usize == wasmer_vm_interpreter_trampoline as usize {
+                // The frames of the interpreter aren't native frames
+                if let Some(pcs) = interpreted.next() {
+                    frames.extend(pcs.into_iter().filter_map(|pc| info.lookup_frame_info(pc)));
+                }
+                continue;
+            }
+            // The native frames are past their call, so the lookup is for
+            // the previous instruction
+            if let Some(frame) = info.lookup_frame_info(pc - 1) {
+                frames.push(frame);
+            }
+        }
+        // Unless the interpreter frames were found on the native stack
+        for pcs in interpreted {
+            frames.extend(pcs.into_iter().filter_map(|pc| info.lookup_frame_info(pc)));
+        }
+        Self { frames }
+    }
+
+    /// The frames of the trace, from the innermost one.
+    pub fn frames(&self) -> &[FrameInfo] {
+        &self.frames
+    }
+
+    /// Whether no WebAssembly code is running.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+impl fmt::Display for RuntimeStackTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, frame) in self.frames.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write_frame(f, frame)?;
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,14 @@
 use crate::engine::error::LinkError;
 use std::ptr::NonNull;
+use std::sync::Arc;
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
     GlobalType, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex, MemoryType,
     ModuleInfo, TableIndex, TableType,
 };
-use wasmer_vm::{InternalStoreHandle, MemoryError, StoreObjects};
+use wasmer_vm::{
+    InstancePool, InternalStoreHandle, LinearMemory, MemoryError, MemoryGrowObserver, StoreObjects,
+};
 use wasmer_vm::{MemoryStyle, TableStyle};
 use wasmer_vm::{VMGlobal, VMMemory, VMTable};
 use wasmer_vm::{VMMemoryDefinition, VMTableDefinition};
@@ -37,6 +40,12 @@ pub trait Tunables {
         vm_definition_location: NonNull<VMMemoryDefinition>,
     ) -> Result<VMMemory, MemoryError>;
 
+    /// The observer told about the growth of the memories of type `ty`,
+    /// host and VM owned alike, if any.
+    fn memory_grow_observer(&self, _ty: &MemoryType) -> Option<Arc<dyn MemoryGrowObserver>> {
+        None
+    }
+
     /// Create a table owned by the host given a [`TableType`] and a [`TableStyle`].
     fn create_host_table(&self, ty: &TableType, style: &TableStyle) -> Result<VMTable, String>;
 
@@ -79,11 +88,15 @@ pub trait Tunables {
             let mi = MemoryIndex::new(index);
             let ty = &module.memories[mi];
             let style = &memory_styles[mi];
-            memories.push(InternalStoreHandle::new(
-                context,
-                self.create_vm_memory(ty, style, *mdl)
-                    .map_err(|e| LinkError::Resource(format!("Failed to create memory: {}", e)))?,
-            ));
+            let mut memory = self
+                .create_vm_memory(ty, style, *mdl)
+                .map_err(|e| LinkError::Resource(format!("Failed to create memory: {}", e)))?;
+            if let Some(observer) = self.memory_grow_observer(ty) {
+                memory
+                    .set_grow_observer(observer)
+                    .map_err(|e| LinkError::Resource(format!("Failed to observe memory: {}", e)))?;
+            }
+            memories.push(InternalStoreHandle::new(context, memory));
         }
         Ok(memories)
     }
@@ -142,3 +155,58 @@ pub trait Tunables {
         Ok(vmctx_globals)
     }
 }
+
+/// Tunables that allocate the VM-owned memories and tables of instances
+/// in an [`InstancePool`], deferring everything else to other tunables.
+pub(crate) struct PooledTunables<'a> {
+    pub(crate) tunables: &'a dyn Tunables,
+    pub(crate) pool: &'a InstancePool,
+}
+
+impl Tunables for PooledTunables<'_> {
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        self.tunables.memory_style(memory)
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.tunables.table_style(table)
+    }
+
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<VMMemory, MemoryError> {
+        self.tunables.create_host_memory(ty, style)
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<VMMemory, MemoryError> {
+        VMMemory::from_pooled_definition(ty, style, vm_definition_location, self.pool)
+    }
+
+    fn memory_grow_observer(&self, ty: &MemoryType) -> Option<Arc<dyn MemoryGrowObserver>> {
+        self.tunables.memory_grow_observer(ty)
+    }
+
+    fn create_host_table(&self, ty: &TableType, style: &TableStyle) -> Result<VMTable, String> {
+        self.tunables.create_host_table(ty, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<VMTable, String> {
+        VMTable::from_pooled_definition(ty, style, vm_definition_location, self.pool)
+    }
+
+    fn create_global(&self, ty: GlobalType) -> Result<VMGlobal, String> {
+        self.tunables.create_global(ty)
+    }
+}
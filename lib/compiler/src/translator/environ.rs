@@ -9,11 +9,12 @@ use crate::wasmparser::{Operator, Range, Type};
 use std::convert::{TryFrom, TryInto};
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::FunctionType;
+use wasmer_types::{parse_metadata, ModuleHash, METADATA_NAME, METADATA_SECTION};
 use wasmer_types::{
     CustomSectionIndex, DataIndex, DataInitializer, DataInitializerLocation, ElemIndex,
     ExportIndex, FunctionIndex, GlobalIndex, GlobalInit, GlobalType, ImportIndex,
     LocalFunctionIndex, MemoryIndex, MemoryType, ModuleInfo, SignatureIndex, TableIndex,
-    TableInitializer, TableType,
+    TableInitializer, TableType, TrapCode,
 };
 use wasmer_types::{WasmError, WasmResult};
 
@@ -52,6 +53,13 @@ pub trait FunctionBinaryReader<'a> {
 
     /// Return the range (original offset, original offset + data length)
     fn range(&self) -> Range;
+
+    /// Returns the code the last operator read traps with, if it's an
+    /// `unreachable`. Middlewares may change it from the default
+    /// `TrapCode::UnreachableCodeReached`.
+    fn unreachable_trap_code(&self) -> TrapCode {
+        TrapCode::UnreachableCodeReached
+    }
 }
 
 /// The result of translating via `ModuleEnvironment`. Function bodies are not
@@ -89,6 +97,10 @@ impl<'data> ModuleEnvironment<'data> {
         assert!(self.module_translation_state.is_none());
         let module_translation_state = translate_module(data, &mut self)?;
         self.module_translation_state = Some(module_translation_state);
+        self.module.hash = Some(ModuleHash::hash(data));
+        if let Some(name) = self.module.metadata.get(METADATA_NAME) {
+            self.module.name = Some(name.clone());
+        }
         Ok(self)
     }
 
@@ -450,8 +462,16 @@ impl<'data> ModuleEnvironment<'data> {
         );
         self.module
             .custom_sections
-            .insert(String::from(name), custom_section);
+            .entry(String::from(name))
+            .or_default()
+            .push(custom_section);
         self.module.custom_sections_data.push(Box::from(data));
+        if name == METADATA_SECTION {
+            // Malformed metadata is only kept as a custom section
+            if let Some(metadata) = parse_metadata(data) {
+                self.module.metadata = metadata;
+            }
+        }
         Ok(())
     }
 }
@@ -5,7 +5,7 @@ use smallvec::SmallVec;
 use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::ops::Deref;
-use wasmer_types::{LocalFunctionIndex, MiddlewareError, ModuleInfo, WasmResult};
+use wasmer_types::{LocalFunctionIndex, MiddlewareError, ModuleInfo, TrapCode, WasmResult};
 use wasmparser::{BinaryReader, Operator, Range, Type};
 
 use super::error::from_binaryreadererror_wasmerror;
@@ -25,6 +25,16 @@ pub trait ModuleMiddleware: Debug + Send + Sync {
 
     /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
     fn transform_module_info(&self, _: &mut ModuleInfo) {}
+
+    /// A string identifying the middleware and the options changing the
+    /// code it generates, which caches use in their keys along with the
+    /// [`Compiler::deterministic_id`](crate::Compiler::deterministic_id).
+    ///
+    /// Defaults to the name of the middleware type, which is enough for
+    /// middlewares without options.
+    fn deterministic_id(&self) -> String {
+        std::any::type_name::<Self>().to_string()
+    }
 }
 
 /// A function middleware specialized for a single function.
@@ -48,6 +58,10 @@ pub struct MiddlewareBinaryReader<'a> {
 
     /// The backing middleware chain for this reader.
     chain: Vec<Box<dyn FunctionMiddleware>>,
+
+    /// The trap code of the last operator read, if it's an `unreachable`
+    /// pushed by `push_trap`.
+    trap_code: Option<TrapCode>,
 }
 
 /// The state of the binary reader. Exposed to middlewares to push their outputs.
@@ -56,8 +70,12 @@ pub struct MiddlewareReaderState<'a> {
     /// Raw binary reader.
     inner: BinaryReader<'a>,
 
-    /// The pending operations added by the middleware.
-    pending_operations: VecDeque<Operator<'a>>,
+    /// The pending operations added by the middleware, with the trap
+    /// code of the `unreachable`s pushed by `push_trap`.
+    pending_operations: VecDeque<(Operator<'a>, Option<TrapCode>)>,
+
+    /// The trap code of the operation being fed to a middleware.
+    feeding_trap_code: Option<TrapCode>,
 }
 
 /// Trait for generating middleware chains from "prototype" (generator) chains.
@@ -93,20 +111,36 @@ impl<T: Deref<Target = dyn ModuleMiddleware>> ModuleMiddlewareChain for [T] {
 
 impl<'a> MiddlewareReaderState<'a> {
     /// Push an operator.
+    ///
+    /// Pushing the `unreachable` being fed keeps the trap code it was
+    /// pushed with.
     pub fn push_operator(&mut self, operator: Operator<'a>) {
-        self.pending_operations.push_back(operator);
+        let trap_code = match operator {
+            Operator::Unreachable => self.feeding_trap_code,
+            _ => None,
+        };
+        self.pending_operations.push_back((operator, trap_code));
+    }
+
+    /// Push an `unreachable` trapping with `trap_code` instead of
+    /// `TrapCode::UnreachableCodeReached`.
+    pub fn push_trap(&mut self, trap_code: TrapCode) {
+        self.pending_operations
+            .push_back((Operator::Unreachable, Some(trap_code)));
     }
 }
 
 impl<'a> Extend<Operator<'a>> for MiddlewareReaderState<'a> {
     fn extend<I: IntoIterator<Item = Operator<'a>>>(&mut self, iter: I) {
-        self.pending_operations.extend(iter);
+        self.pending_operations
+            .extend(iter.into_iter().map(|operator| (operator, None)));
     }
 }
 
 impl<'a: 'b, 'b> Extend<&'b Operator<'a>> for MiddlewareReaderState<'a> {
     fn extend<I: IntoIterator<Item = &'b Operator<'a>>>(&mut self, iter: I) {
-        self.pending_operations.extend(iter.into_iter().cloned());
+        self.pending_operations
+            .extend(iter.into_iter().map(|operator| (operator.clone(), None)));
     }
 }
 
@@ -118,8 +152,10 @@ impl<'a> MiddlewareBinaryReader<'a> {
             state: MiddlewareReaderState {
                 inner,
                 pending_operations: VecDeque::new(),
+                feeding_trap_code: None,
             },
             chain: vec![],
+            trap_code: None,
         }
     }
 
@@ -170,22 +206,30 @@ impl<'a> FunctionBinaryReader<'a> for MiddlewareBinaryReader<'a> {
                 .map_err(from_binaryreadererror_wasmerror)?;
 
             // Fill the initial raw operator into pending buffer.
-            self.state.pending_operations.push_back(raw_op);
+            self.state.pending_operations.push_back((raw_op, None));
 
             // Run the operator through each stage.
             for stage in &mut self.chain {
                 // Take the outputs from the previous stage.
-                let pending: SmallVec<[Operator<'a>; 2]> =
+                let pending: SmallVec<[(Operator<'a>, Option<TrapCode>); 2]> =
                     self.state.pending_operations.drain(0..).collect();
 
                 // ...and feed them into the current stage.
-                for pending_op in pending {
+                for (pending_op, trap_code) in pending {
+                    self.state.feeding_trap_code = trap_code;
                     stage.feed(pending_op, &mut self.state)?;
                 }
+                self.state.feeding_trap_code = None;
             }
         }
 
-        Ok(self.state.pending_operations.pop_front().unwrap())
+        let (operator, trap_code) = self.state.pending_operations.pop_front().unwrap();
+        self.trap_code = trap_code;
+        Ok(operator)
+    }
+
+    fn unreachable_trap_code(&self) -> TrapCode {
+        self.trap_code.unwrap_or(TrapCode::UnreachableCodeReached)
     }
 
     fn current_position(&self) -> usize {
@@ -10,20 +10,37 @@ use std::path::PathBuf;
 use std::slice;
 use wasmer::{FunctionEnvMut, GlobalInit, MemoryView, Module, Pages, WasmPtr};
 
-/// We check if a provided module is an Emscripten generated one
+/// We check if a provided module is an Emscripten generated one, that is
+/// if it imports functions of the Emscripten runtime (`emscripten_*`) or
+/// its syscalls (`___syscall*`) from `env`.
+///
+/// Modules built with `-s STANDALONE_WASM` can import a few of these
+/// helpers too, but they run on WASI, so modules importing anything from
+/// WASI aren't Emscripten ones.
 pub fn is_emscripten_module(module: &Module) -> bool {
-    for import in module.imports().functions() {
-        let name = import.name();
-        let module = import.module();
-        if (name == "_emscripten_memcpy_big"
-            || name == "emscripten_memcpy_big"
-            || name == "__map_file")
-            && module == "env"
-        {
-            return true;
-        }
+    if module
+        .imports()
+        .any(|import| is_wasi_namespace(import.module()))
+    {
+        return false;
     }
-    false
+    module
+        .imports()
+        .functions()
+        .any(|import| import.module() == "env" && is_emscripten_import(import.name()))
+}
+
+fn is_wasi_namespace(namespace: &str) -> bool {
+    namespace == "wasi_unstable" || namespace == "wasi_snapshot_preview1"
+}
+
+fn is_emscripten_import(name: &str) -> bool {
+    let name = name.trim_start_matches('_');
+    name.starts_with("emscripten_")
+        || name == "map_file"
+        || name
+            .strip_prefix("syscall")
+            .map_or(false, |number| number.parse::<u32>().is_ok())
 }
 
 pub fn get_emscripten_table_size(module: &Module) -> Result<(u32, Option<u32>), String> {
@@ -304,3 +321,42 @@ pub fn get_current_directory(ctx: FunctionEnvMut<EmEnv>) -> Option<PathBuf> {
         })
         .ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::is_emscripten_module;
+    use wasmer::{Module, Store};
+
+    #[test]
+    fn classic_emscripten_modules() {
+        let store = Store::default();
+        let module = Module::new(
+            &store,
+            r#"(module
+                 (import "env" "memory" (memory 256 256))
+                 (import "env" "table" (table 10 10 funcref))
+                 (import "env" "___syscall5" (func (param i32 i32) (result i32)))
+                 (import "env" "_emscripten_memcpy_big" (func (param i32 i32 i32) (result i32))))"#,
+        )
+        .unwrap();
+        assert!(is_emscripten_module(&module));
+    }
+
+    #[test]
+    fn standalone_wasm_modules_are_not_emscripten_ones() {
+        let store = Store::default();
+        let module = Module::new(
+            &store,
+            r#"(module
+                 (import "wasi_snapshot_preview1" "fd_write"
+                   (func (param i32 i32 i32 i32) (result i32)))
+                 (import "env" "emscripten_notify_memory_growth" (func (param i32)))
+                 (memory (export "memory") 1))"#,
+        )
+        .unwrap();
+        assert!(!is_emscripten_module(&module));
+
+        let module = Module::new(&store, "(module)").unwrap();
+        assert!(!is_emscripten_module(&module));
+    }
+}
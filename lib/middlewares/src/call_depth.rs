@@ -0,0 +1,365 @@
+//! `call_depth` is a middleware putting a limit on how many calls of
+//! WebAssembly functions can be nested. The WebAssembly instance
+//! execution is stopped when a call would go past the limit.
+//!
+//! The size of the stack Wasm code runs on bounds the nesting of calls
+//! too, but by how large the frames of the compiled functions are,
+//! which depends on the compiler. This middleware counts the calls
+//! themselves, so the limit is the same with every compiler.
+
+use std::convert::TryInto;
+use std::fmt;
+use std::sync::Mutex;
+use wasmer::wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
+use wasmer::{
+    AsStoreMut, ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, Instance,
+    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+};
+use wasmer_types::{GlobalIndex, ModuleInfo, TrapCode};
+
+#[derive(Clone)]
+struct CallDepthGlobalIndexes(GlobalIndex, GlobalIndex);
+
+impl CallDepthGlobalIndexes {
+    /// The global index in the current module for the number of nested calls.
+    fn depth(&self) -> GlobalIndex {
+        self.0
+    }
+
+    /// The global index in the current module for a boolean indicating whether the limit
+    /// has been exceeded or not.
+    /// This boolean is represented as a i32 global:
+    ///   * 0: the calls stayed within the limit
+    ///   * 1: a call went past the limit
+    fn exceeded(&self) -> GlobalIndex {
+        self.1
+    }
+}
+
+impl fmt::Debug for CallDepthGlobalIndexes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallDepthGlobalIndexes")
+            .field("depth", &self.depth())
+            .field("exceeded", &self.exceeded())
+            .finish()
+    }
+}
+
+/// The module-level call depth middleware.
+///
+/// Every `call` and `call_indirect` counts as one more nested call
+/// until it returns, including the calls of imported functions. The
+/// calls made by the host into the instance aren't counted, so the
+/// limit applies to the calls nested within each of them.
+///
+/// A call going past the limit traps with `TrapCode::StackOverflow`.
+///
+/// A call that traps doesn't return, so the depth it reached is kept;
+/// reset it with [`reset_call_depth`] before calling into the instance
+/// again.
+///
+/// # Panic
+///
+/// An instance of `CallDepthLimit` should _not_ be shared among
+/// different modules, since it tracks module-specific information like
+/// the global index to store the call depth. Attempts to use a
+/// `CallDepthLimit` instance from multiple modules will result in a
+/// panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::CallDepthLimit;
+///
+/// fn create_call_depth_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     // Let's allow at most 1000 nested calls.
+///     let call_depth_limit = Arc::new(CallDepthLimit::new(1000));
+///
+///     // Finally, let's push the middleware.
+///     compiler_config.push_middleware(call_depth_limit);
+/// }
+/// ```
+pub struct CallDepthLimit {
+    /// The number of calls that can be nested.
+    max_depth: u32,
+
+    /// The global indexes for the call depth.
+    global_indexes: Mutex<Option<CallDepthGlobalIndexes>>,
+}
+
+/// The function-level call depth middleware.
+pub struct FunctionCallDepthLimit {
+    /// The number of calls that can be nested.
+    max_depth: u32,
+
+    /// The global indexes for the call depth.
+    global_indexes: CallDepthGlobalIndexes,
+}
+
+impl CallDepthLimit {
+    /// Creates a `CallDepthLimit` middleware allowing `max_depth`
+    /// nested calls.
+    pub fn new(max_depth: u32) -> Self {
+        Self {
+            max_depth,
+            global_indexes: Mutex::new(None),
+        }
+    }
+}
+
+impl fmt::Debug for CallDepthLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallDepthLimit")
+            .field("max_depth", &self.max_depth)
+            .field("global_indexes", &self.global_indexes)
+            .finish()
+    }
+}
+
+impl ModuleMiddleware for CallDepthLimit {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionCallDepthLimit {
+            max_depth: self.max_depth,
+            global_indexes: self.global_indexes.lock().unwrap().clone().unwrap(),
+        })
+    }
+
+    fn deterministic_id(&self) -> String {
+        format!("CallDepthLimit-{}", self.max_depth)
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut global_indexes = self.global_indexes.lock().unwrap();
+
+        if global_indexes.is_some() {
+            panic!("CallDepthLimit::transform_module_info: Attempting to use a `CallDepthLimit` middleware from multiple modules.");
+        }
+
+        // Append a global for the call depth and initialize it.
+        let depth_global_index = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+
+        module_info.exports.insert(
+            "wasmer_call_depth".to_string(),
+            ExportIndex::Global(depth_global_index),
+        );
+
+        // Append a global for the exceeded limit boolean and initialize it.
+        let exceeded_global_index = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+
+        module_info.exports.insert(
+            "wasmer_call_depth_exceeded".to_string(),
+            ExportIndex::Global(exceeded_global_index),
+        );
+
+        *global_indexes = Some(CallDepthGlobalIndexes(
+            depth_global_index,
+            exceeded_global_index,
+        ))
+    }
+}
+
+impl fmt::Debug for FunctionCallDepthLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionCallDepthLimit")
+            .field("max_depth", &self.max_depth)
+            .field("global_indexes", &self.global_indexes)
+            .finish()
+    }
+}
+
+impl FunctionMiddleware for FunctionCallDepthLimit {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        match operator {
+            Operator::Call { .. } | Operator::CallIndirect { .. } => {
+                let depth = self.global_indexes.depth().as_u32();
+                state.extend(&[
+                    // if unsigned(globals[depth_index]) >= unsigned(self.max_depth) { throw(); }
+                    Operator::GlobalGet {
+                        global_index: depth,
+                    },
+                    Operator::I32Const {
+                        value: self.max_depth as i32,
+                    },
+                    Operator::I32GeU,
+                    Operator::If {
+                        ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+                    },
+                    Operator::I32Const { value: 1 },
+                    Operator::GlobalSet {
+                        global_index: self.global_indexes.exceeded().as_u32(),
+                    },
+                ]);
+                state.push_trap(TrapCode::StackOverflow);
+                state.extend(&[
+                    Operator::End,
+                    // globals[depth_index] += 1;
+                    Operator::GlobalGet {
+                        global_index: depth,
+                    },
+                    Operator::I32Const { value: 1 },
+                    Operator::I32Add,
+                    Operator::GlobalSet {
+                        global_index: depth,
+                    },
+                ]);
+                state.push_operator(operator);
+                state.extend(&[
+                    // globals[depth_index] -= 1;
+                    Operator::GlobalGet {
+                        global_index: depth,
+                    },
+                    Operator::I32Const { value: 1 },
+                    Operator::I32Sub,
+                    Operator::GlobalSet {
+                        global_index: depth,
+                    },
+                ]);
+            }
+            _ => state.push_operator(operator),
+        }
+
+        Ok(())
+    }
+}
+
+/// Get the number of nested calls an [`Instance`][wasmer::Instance]
+/// is in.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`CallDepthLimit`] middleware at compile time, otherwise this
+/// will panic.
+pub fn get_call_depth(ctx: &mut impl AsStoreMut, instance: &Instance) -> u32 {
+    let depth: i32 = instance
+        .exports
+        .get_global("wasmer_call_depth")
+        .expect("Can't get `wasmer_call_depth` from Instance")
+        .get(ctx)
+        .try_into()
+        .expect("`wasmer_call_depth` from Instance has wrong type");
+    depth as u32
+}
+
+/// Whether a call of an [`Instance`][wasmer::Instance] was stopped
+/// because it went past the limit of the [`CallDepthLimit`]
+/// middleware.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`CallDepthLimit`] middleware at compile time, otherwise this
+/// will panic.
+pub fn call_depth_exceeded(ctx: &mut impl AsStoreMut, instance: &Instance) -> bool {
+    let exceeded: i32 = instance
+        .exports
+        .get_global("wasmer_call_depth_exceeded")
+        .expect("Can't get `wasmer_call_depth_exceeded` from Instance")
+        .get(ctx)
+        .try_into()
+        .expect("`wasmer_call_depth_exceeded` from Instance has wrong type");
+    exceeded > 0
+}
+
+/// Reset the call depth of an [`Instance`][wasmer::Instance] after a
+/// call trapped, so that it can be called again.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`CallDepthLimit`] middleware at compile time, otherwise this
+/// will panic.
+pub fn reset_call_depth(ctx: &mut impl AsStoreMut, instance: &Instance) {
+    instance
+        .exports
+        .get_global("wasmer_call_depth")
+        .expect("Can't get `wasmer_call_depth` from Instance")
+        .set(ctx, 0i32.into())
+        .expect("Can't set `wasmer_call_depth` in Instance");
+
+    instance
+        .exports
+        .get_global("wasmer_call_depth_exceeded")
+        .expect("Can't get `wasmer_call_depth_exceeded` from Instance")
+        .set(ctx, 0i32.into())
+        .expect("Can't set `wasmer_call_depth_exceeded` in Instance");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Module, Store, TypedFunction,
+    };
+
+    fn bytecode() -> Vec<u8> {
+        wat2wasm(
+            br#"
+            (module
+            (func $recurse (export "recurse") (param $n i32)
+                local.get $n
+                if
+                    local.get $n
+                    i32.const 1
+                    i32.sub
+                    call $recurse
+                end))
+            "#,
+        )
+        .unwrap()
+        .into()
+    }
+
+    #[test]
+    fn call_depth_limit_works() {
+        let call_depth_limit = Arc::new(CallDepthLimit::new(10));
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(call_depth_limit);
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode()).unwrap();
+
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        let recurse: TypedFunction<i32, ()> = instance
+            .exports
+            .get_function("recurse")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        // 10 nested calls are within the limit
+        recurse.call(&mut store, 10).unwrap();
+        assert_eq!(get_call_depth(&mut store, &instance), 0);
+        assert!(!call_depth_exceeded(&mut store, &instance));
+
+        // The 11th nested call fails
+        assert!(recurse.call(&mut store, 11).is_err());
+        assert_eq!(get_call_depth(&mut store, &instance), 10);
+        assert!(call_depth_exceeded(&mut store, &instance));
+
+        reset_call_depth(&mut store, &instance);
+        recurse.call(&mut store, 10).unwrap();
+        assert!(!call_depth_exceeded(&mut store, &instance));
+    }
+}
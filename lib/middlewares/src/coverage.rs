@@ -0,0 +1,478 @@
+//! `coverage` is a middleware recording which functions and basic
+//! blocks of a module are executed, to measure the coverage of guest
+//! test suites.
+//!
+//! Every basic block gets a counter, kept in a global of the instance,
+//! which is incremented each time the block starts. Once the guest has
+//! run, [`Coverage::report`] reads the counters back, and the report can
+//! be written in the LCOV format most coverage tools read. When the
+//! module has DWARF, the blocks are reported at the lines of the sources
+//! they were compiled from.
+
+use crate::line_table::LineTable;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use wasmer::wasmparser::{Operator, Parser, Payload};
+use wasmer::{
+    AsStoreMut, ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, Instance,
+    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+};
+use wasmer_types::entity::EntityRef;
+use wasmer_types::{FunctionIndex, ModuleInfo};
+
+/// The module-level coverage middleware.
+///
+/// As the counters are allocated before the functions are compiled, the
+/// middleware is created from the module it instruments, and must only
+/// be used to compile this module. It should be pushed before the other
+/// middlewares, so that it sees the original code of the functions.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::{CompilerConfig, MiddlewareError};
+/// use wasmer_middlewares::Coverage;
+///
+/// fn instrument(
+///     compiler_config: &mut dyn CompilerConfig,
+///     wasm: &[u8],
+/// ) -> Result<Arc<Coverage>, MiddlewareError> {
+///     let coverage = Arc::new(Coverage::new(wasm)?);
+///     compiler_config.push_middleware(coverage.clone());
+///     Ok(coverage)
+/// }
+/// ```
+pub struct Coverage {
+    /// The offsets of the basic blocks of each local function.
+    blocks: Vec<Vec<usize>>,
+    /// The offset of the contents of the code section.
+    code_section_offset: usize,
+    /// The DWARF sections of the module, by name.
+    debug_sections: HashMap<String, Vec<u8>>,
+    /// What the instrumented module looks like, once it is known.
+    state: Mutex<Option<CoverageState>>,
+}
+
+#[derive(Debug, Clone)]
+struct CoverageState {
+    /// The global holding the counter of the first block.
+    first_global: u32,
+    num_imported_functions: usize,
+    /// The names of the local functions.
+    function_names: Vec<String>,
+}
+
+/// The function-level coverage middleware.
+#[derive(Debug)]
+pub struct FunctionCoverage {
+    /// The global of the counter of the next block.
+    next_global: u32,
+    /// The number of blocks left to instrument.
+    remaining_blocks: usize,
+    blocks: BlockTracker,
+}
+
+impl Coverage {
+    /// Creates a `Coverage` middleware for the module `wasm`.
+    pub fn new(wasm: &[u8]) -> Result<Self, MiddlewareError> {
+        let error = |e: wasmer::wasmparser::BinaryReaderError| {
+            MiddlewareError::new("coverage", e.to_string())
+        };
+        let mut blocks = Vec::new();
+        let mut code_section_offset = 0;
+        let mut debug_sections = HashMap::new();
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload.map_err(error)? {
+                Payload::CodeSectionStart { range, .. } => code_section_offset = range.start,
+                Payload::CodeSectionEntry(body) => {
+                    let mut tracker = BlockTracker::new();
+                    let mut offsets = Vec::new();
+                    let mut reader = body.get_operators_reader().map_err(error)?;
+                    while !reader.eof() {
+                        let (operator, offset) = reader.read_with_offset().map_err(error)?;
+                        if tracker.starts_block(&operator) {
+                            offsets.push(offset);
+                        }
+                    }
+                    blocks.push(offsets);
+                }
+                Payload::CustomSection { name, data, .. } if name.starts_with(".debug_") => {
+                    debug_sections.insert(name.to_string(), data.to_vec());
+                }
+                _ => {}
+            }
+        }
+        Ok(Self {
+            blocks,
+            code_section_offset,
+            debug_sections,
+            state: Mutex::new(None),
+        })
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.blocks.iter().map(Vec::len).sum()
+    }
+
+    /// Reads the counters of an instance of the module.
+    ///
+    /// # Panic
+    ///
+    /// The module of the instance must have been compiled with this
+    /// middleware, otherwise this will panic.
+    pub fn report(&self, store: &mut impl AsStoreMut, instance: &Instance) -> CoverageReport {
+        let state = self
+            .state
+            .lock()
+            .unwrap()
+            .clone()
+            .expect("Coverage::report: the middleware hasn't instrumented any module");
+        let lines = LineTable::new(&self.debug_sections);
+        let mut counter = 0;
+        let functions = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(local_index, offsets)| {
+                let index = FunctionIndex::new(state.num_imported_functions + local_index);
+                let blocks = offsets
+                    .iter()
+                    .map(|offset| {
+                        let hits: i64 = instance
+                            .exports
+                            .get_global(&counter_name(counter))
+                            .expect("Can't get a coverage counter from Instance")
+                            .get(store)
+                            .try_into()
+                            .expect("A coverage counter from Instance has wrong type");
+                        counter += 1;
+                        let location = lines.as_ref().and_then(|lines| {
+                            lines.find(offset.wrapping_sub(self.code_section_offset) as u64)
+                        });
+                        BlockCoverage {
+                            offset: *offset,
+                            location,
+                            hits: hits as u64,
+                        }
+                    })
+                    .collect();
+                FunctionReport {
+                    index,
+                    name: state.function_names[local_index].clone(),
+                    blocks,
+                }
+            })
+            .collect();
+        CoverageReport { functions }
+    }
+}
+
+impl fmt::Debug for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Coverage")
+            .field("functions", &self.blocks.len())
+            .field("blocks", &self.num_blocks())
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl ModuleMiddleware for Coverage {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(
+        &self,
+        local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        let state = self.state.lock().unwrap().clone().unwrap();
+        let index = local_function_index.index();
+        let previous_blocks: usize = self.blocks[..index].iter().map(Vec::len).sum();
+        Box::new(FunctionCoverage {
+            next_global: state.first_global + previous_blocks as u32,
+            remaining_blocks: self.blocks[index].len(),
+            blocks: BlockTracker::new(),
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        assert_eq!(
+            module_info.functions.len() - module_info.num_imported_functions,
+            self.blocks.len(),
+            "Coverage::transform_module_info: the middleware was created for another module"
+        );
+        // A module can be compiled again, for instance to be serialized
+        // after being compiled lazily: as the counters are allocated the
+        // same way every time, the state is simply replaced
+        let mut first_global = None;
+        for counter in 0..self.num_blocks() {
+            let global_index = module_info
+                .globals
+                .push(GlobalType::new(Type::I64, Mutability::Var));
+            module_info
+                .global_initializers
+                .push(GlobalInit::I64Const(0));
+            module_info
+                .exports
+                .insert(counter_name(counter), ExportIndex::Global(global_index));
+            first_global.get_or_insert(global_index.as_u32());
+        }
+        let num_imported_functions = module_info.num_imported_functions;
+        *self.state.lock().unwrap() = Some(CoverageState {
+            first_global: first_global.unwrap_or(0),
+            num_imported_functions,
+            function_names: (0..self.blocks.len())
+                .map(|i| module_info.function_name(FunctionIndex::new(num_imported_functions + i)))
+                .collect(),
+        });
+    }
+}
+
+impl FunctionMiddleware for FunctionCoverage {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        if self.blocks.starts_block(&operator) && self.remaining_blocks > 0 {
+            state.extend(&[
+                // globals[counter] += 1;
+                Operator::GlobalGet {
+                    global_index: self.next_global,
+                },
+                Operator::I64Const { value: 1 },
+                Operator::I64Add,
+                Operator::GlobalSet {
+                    global_index: self.next_global,
+                },
+            ]);
+            self.next_global += 1;
+            self.remaining_blocks -= 1;
+        }
+        state.push_operator(operator);
+        Ok(())
+    }
+}
+
+fn counter_name(counter: usize) -> String {
+    format!("wasmer_coverage_counter_{}", counter)
+}
+
+/// Finds where the basic blocks of a function start, operator by
+/// operator.
+#[derive(Debug)]
+struct BlockTracker {
+    /// The number of blocks, loops and ifs the operator is nested in.
+    depth: usize,
+    /// Whether the next operator starts a basic block.
+    at_block_start: bool,
+}
+
+impl BlockTracker {
+    fn new() -> Self {
+        Self {
+            depth: 0,
+            at_block_start: true,
+        }
+    }
+
+    /// Whether a basic block starts with `operator`, the next operator of
+    /// the function.
+    fn starts_block(&mut self, operator: &Operator) -> bool {
+        let starts_block = self.at_block_start;
+        let ends_function = match operator {
+            Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } => {
+                self.depth += 1;
+                false
+            }
+            Operator::End if self.depth == 0 => true,
+            Operator::End => {
+                self.depth -= 1;
+                false
+            }
+            _ => false,
+        };
+        // Loop headers, the arms of ifs and the code following branches
+        // and the ends of blocks can be reached from elsewhere
+        self.at_block_start = !ends_function
+            && matches!(
+                operator,
+                Operator::Loop { .. }
+                    | Operator::If { .. }
+                    | Operator::Else
+                    | Operator::End
+                    | Operator::Br { .. }
+                    | Operator::BrIf { .. }
+                    | Operator::BrTable { .. }
+                    | Operator::Return
+                    | Operator::Unreachable
+            );
+        starts_block
+    }
+}
+
+/// The counters of an instance, as read by [`Coverage::report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageReport {
+    functions: Vec<FunctionReport>,
+}
+
+/// The coverage of a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionReport {
+    /// The index of the function in the module.
+    pub index: FunctionIndex,
+    /// The name of the function, from the name section if any.
+    pub name: String,
+    /// The basic blocks of the function, the first one starting with the
+    /// function.
+    pub blocks: Vec<BlockCoverage>,
+}
+
+/// The coverage of a basic block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockCoverage {
+    /// The offset of the first operator of the block in the module.
+    pub offset: usize,
+    /// The source file and line of the block, from the DWARF of the
+    /// module.
+    pub location: Option<(String, u64)>,
+    /// How many times the block was executed.
+    pub hits: u64,
+}
+
+impl FunctionReport {
+    /// How many times the function was called.
+    pub fn hits(&self) -> u64 {
+        self.blocks.first().map_or(0, |block| block.hits)
+    }
+}
+
+impl CoverageReport {
+    /// The coverage of the local functions of the module.
+    pub fn functions(&self) -> &[FunctionReport] {
+        &self.functions
+    }
+
+    /// Writes the report in the LCOV tracefile format.
+    ///
+    /// Blocks are reported at their source lines when the module has
+    /// DWARF. The others are reported in a file named `module_name`, at
+    /// their offset in the module as if it were a line number.
+    pub fn write_lcov(&self, module_name: &str, out: &mut impl Write) -> io::Result<()> {
+        // Per file, the functions with their first line, and the lines
+        // with their hits
+        let mut files: BTreeMap<&str, (Vec<(u64, &FunctionReport)>, BTreeMap<u64, u64>)> =
+            BTreeMap::new();
+        for function in &self.functions {
+            for (i, block) in function.blocks.iter().enumerate() {
+                let (file, line) = match &block.location {
+                    Some((file, line)) => (file.as_str(), *line),
+                    None => (module_name, block.offset as u64),
+                };
+                let (functions, lines) = files.entry(file).or_default();
+                if i == 0 {
+                    functions.push((line, function));
+                }
+                let hits = lines.entry(line).or_insert(0);
+                *hits = (*hits).max(block.hits);
+            }
+        }
+
+        writeln!(out, "TN:")?;
+        for (file, (functions, lines)) in &files {
+            writeln!(out, "SF:{}", file)?;
+            for (line, function) in functions {
+                writeln!(out, "FN:{},{}", line, function.name)?;
+            }
+            for (_, function) in functions {
+                writeln!(out, "FNDA:{},{}", function.hits(), function.name)?;
+            }
+            writeln!(out, "FNF:{}", functions.len())?;
+            let functions_hit = functions.iter().filter(|(_, f)| f.hits() > 0).count();
+            writeln!(out, "FNH:{}", functions_hit)?;
+            for (line, hits) in lines {
+                writeln!(out, "DA:{},{}", line, hits)?;
+            }
+            writeln!(out, "LF:{}", lines.len())?;
+            writeln!(
+                out,
+                "LH:{}",
+                lines.values().filter(|hits| **hits > 0).count()
+            )?;
+            writeln!(out, "end_of_record")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use wasmer::{imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Module, Store};
+
+    fn bytecode() -> Vec<u8> {
+        wat2wasm(
+            br#"
+            (module
+            (func $abs (export "abs") (param $value i32) (result i32)
+                local.get $value
+                i32.const 0
+                i32.lt_s
+                if (result i32)
+                    i32.const 0
+                    local.get $value
+                    i32.sub
+                else
+                    local.get $value
+                end)
+            (func $unused (export "unused")))
+            "#,
+        )
+        .unwrap()
+        .into()
+    }
+
+    #[test]
+    fn coverage_counts_blocks() {
+        let wasm = bytecode();
+        let coverage = Arc::new(Coverage::new(&wasm).unwrap());
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(coverage.clone());
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, &wasm).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+
+        let abs = instance.exports.get_function("abs").unwrap();
+        abs.call(&mut store, &[5.into()]).unwrap();
+        abs.call(&mut store, &[(-5).into()]).unwrap();
+        abs.call(&mut store, &[7.into()]).unwrap();
+
+        let report = coverage.report(&mut store, &instance);
+        let functions = report.functions();
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions[0].name, "abs");
+        assert_eq!(functions[0].hits(), 3);
+        // The entry, both arms of the `if`, and the code after it
+        let hits = functions[0]
+            .blocks
+            .iter()
+            .map(|block| block.hits)
+            .collect::<Vec<_>>();
+        assert_eq!(hits, vec![3, 1, 2, 3]);
+        assert_eq!(functions[1].hits(), 0);
+
+        let mut lcov = Vec::new();
+        report.write_lcov("test.wasm", &mut lcov).unwrap();
+        let lcov = String::from_utf8(lcov).unwrap();
+        assert!(lcov.contains("SF:test.wasm\n"));
+        assert!(lcov.contains("FNDA:3,abs\n"));
+        assert!(lcov.contains("FNDA:0,unused\n"));
+        assert!(lcov.contains("FNH:1\n"));
+    }
+}
@@ -0,0 +1,256 @@
+//! `instruction_counter` is a middleware counting how many operators
+//! are executed by an instance, without limiting them.
+//!
+//! Like [`Metering`](crate::Metering), the operators of a basic block
+//! are counted all at once, when the block is left, which keeps the
+//! instrumentation cheap. The operators of a block that traps aren't
+//! counted.
+
+use std::convert::TryInto;
+use std::fmt;
+use std::sync::Mutex;
+use wasmer::wasmparser::Operator;
+use wasmer::{
+    AsStoreMut, ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, Instance,
+    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+};
+use wasmer_types::{GlobalIndex, ModuleInfo};
+
+/// The name of the exported global holding the count.
+const INSTRUCTION_COUNT_EXPORT: &str = "wasmer_instruction_count";
+
+/// The module-level instruction counter middleware.
+///
+/// Every operator counts as one instruction, including the `end` of
+/// blocks and functions.
+///
+/// # Panic
+///
+/// An instance of `InstructionCounter` should _not_ be shared among
+/// different modules, since it tracks module-specific information like
+/// the global index to store the count. Attempts to use an
+/// `InstructionCounter` instance from multiple modules will result in a
+/// panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::InstructionCounter;
+///
+/// fn create_instruction_counter_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     compiler_config.push_middleware(Arc::new(InstructionCounter::new()));
+/// }
+/// ```
+#[derive(Default)]
+pub struct InstructionCounter {
+    /// The global index for the count.
+    global_index: Mutex<Option<GlobalIndex>>,
+}
+
+/// The function-level instruction counter middleware.
+pub struct FunctionInstructionCounter {
+    /// The global index for the count.
+    global_index: GlobalIndex,
+
+    /// The operators of the current basic block.
+    accumulated_count: u64,
+}
+
+impl InstructionCounter {
+    /// Creates an `InstructionCounter` middleware.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl fmt::Debug for InstructionCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstructionCounter")
+            .field("global_index", &self.global_index)
+            .finish()
+    }
+}
+
+impl ModuleMiddleware for InstructionCounter {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionInstructionCounter {
+            global_index: self.global_index.lock().unwrap().unwrap(),
+            accumulated_count: 0,
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut global_index = self.global_index.lock().unwrap();
+
+        if global_index.is_some() {
+            panic!("InstructionCounter::transform_module_info: Attempting to use an `InstructionCounter` middleware from multiple modules.");
+        }
+
+        // Append a global for the count and initialize it.
+        let count_global_index = module_info
+            .globals
+            .push(GlobalType::new(Type::I64, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I64Const(0));
+
+        module_info.exports.insert(
+            INSTRUCTION_COUNT_EXPORT.to_string(),
+            ExportIndex::Global(count_global_index),
+        );
+
+        *global_index = Some(count_global_index);
+    }
+}
+
+impl fmt::Debug for FunctionInstructionCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionInstructionCounter")
+            .field("global_index", &self.global_index)
+            .finish()
+    }
+}
+
+impl FunctionMiddleware for FunctionInstructionCounter {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        self.accumulated_count += 1;
+
+        // The same basic blocks as the ones of `Metering`.
+        match operator {
+            Operator::Loop { .. }
+            | Operator::End
+            | Operator::Else
+            | Operator::Br { .. }
+            | Operator::BrTable { .. }
+            | Operator::BrIf { .. }
+            | Operator::Call { .. }
+            | Operator::CallIndirect { .. }
+            | Operator::Return => {
+                // globals[count_index] += self.accumulated_count;
+                state.extend(&[
+                    Operator::GlobalGet {
+                        global_index: self.global_index.as_u32(),
+                    },
+                    Operator::I64Const {
+                        value: self.accumulated_count as i64,
+                    },
+                    Operator::I64Add,
+                    Operator::GlobalSet {
+                        global_index: self.global_index.as_u32(),
+                    },
+                ]);
+                self.accumulated_count = 0;
+            }
+            _ => {}
+        }
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+/// Get the number of instructions an [`Instance`][wasmer::Instance]
+/// has executed, or `None` if it wasn't processed with the
+/// [`InstructionCounter`] middleware at compile time.
+///
+/// # Example
+///
+/// ```rust
+/// use wasmer::{AsStoreMut, Instance};
+/// use wasmer_middlewares::instruction_counter::get_instruction_count;
+///
+/// fn print_instruction_count(store: &mut impl AsStoreMut, instance: &Instance) {
+///     if let Some(count) = get_instruction_count(store, instance) {
+///         println!("{} instructions executed", count);
+///     }
+/// }
+/// ```
+pub fn get_instruction_count(ctx: &mut impl AsStoreMut, instance: &Instance) -> Option<u64> {
+    let count: i64 = instance
+        .exports
+        .get_global(INSTRUCTION_COUNT_EXPORT)
+        .ok()?
+        .get(ctx)
+        .try_into()
+        .expect("`wasmer_instruction_count` from Instance has wrong type");
+    Some(count as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Module, Store, TypedFunction,
+    };
+
+    #[test]
+    fn get_instruction_count_works() {
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(InstructionCounter::new()));
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let bytecode = wat2wasm(
+            br#"
+            (module
+            (func (export "sum") (param $n i32) (result i32)
+                (local $sum i32)
+                (block $done
+                    (loop $next
+                        local.get $n
+                        i32.eqz
+                        br_if $done
+                        local.get $sum
+                        local.get $n
+                        i32.add
+                        local.set $sum
+                        local.get $n
+                        i32.const 1
+                        i32.sub
+                        local.set $n
+                        br $next))
+                local.get $sum))
+            "#,
+        )
+        .unwrap();
+        let module = Module::new(&store, bytecode).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        assert_eq!(get_instruction_count(&mut store, &instance), Some(0));
+
+        let sum: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("sum")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        assert_eq!(sum.call(&mut store, 0).unwrap(), 0);
+        // `block`, `loop`, `local.get`, `i32.eqz`, `br_if`, then
+        // `local.get` and the `end` of the function.
+        assert_eq!(get_instruction_count(&mut store, &instance), Some(7));
+
+        assert_eq!(sum.call(&mut store, 2).unwrap(), 3);
+        // Each of the 2 iterations adds 12 instructions to the ones of a
+        // call returning right away.
+        assert_eq!(
+            get_instruction_count(&mut store, &instance),
+            Some(7 + 7 + 2 * 12)
+        );
+    }
+
+    #[test]
+    fn get_instruction_count_without_the_middleware() {
+        let mut store = Store::default();
+        let module = Module::new(&store, "(module)").unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        assert_eq!(get_instruction_count(&mut store, &instance), None);
+    }
+}
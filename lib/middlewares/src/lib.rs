@@ -1,6 +1,17 @@
+pub mod call_depth;
+pub mod coverage;
+pub mod instruction_counter;
+pub mod line_table;
 pub mod metering;
+pub mod stats;
+pub mod watchpoint;
 
 // The most commonly used symbol are exported at top level of the
 // module. Others are available via modules,
 // e.g. `wasmer_middlewares::metering::get_remaining_points`
+pub use call_depth::CallDepthLimit;
+pub use coverage::Coverage;
+pub use instruction_counter::InstructionCounter;
 pub use metering::Metering;
+pub use stats::{CallMeter, CallStats};
+pub use watchpoint::Watchpoints;
@@ -0,0 +1,111 @@
+//! The line programs of the DWARF of a module, mapping the offsets of
+//! its code to the lines of the sources it was compiled from, and back.
+//!
+//! The addresses of WebAssembly DWARF are offsets in the code section.
+
+use std::collections::HashMap;
+
+/// The rows of the line programs of a module, sorted by address.
+pub struct LineTable {
+    /// The offset in the code section of each row, with its file and
+    /// line, or `None` at the end of a sequence.
+    rows: Vec<(u64, Option<(String, u64)>)>,
+}
+
+impl LineTable {
+    /// Reads the line programs of a module from its `.debug_*` custom
+    /// sections, keyed by name, or returns `None` if it has no DWARF.
+    pub fn new(sections: &HashMap<String, Vec<u8>>) -> Option<Self> {
+        let load = |id: gimli::SectionId| -> Result<_, gimli::Error> {
+            let data = sections.get(id.name()).map_or(&[][..], Vec::as_slice);
+            Ok(gimli::EndianSlice::new(data, gimli::LittleEndian))
+        };
+        let dwarf = gimli::Dwarf::load(load).ok()?;
+        let mut rows = Vec::new();
+        let mut units = dwarf.units();
+        while let Ok(Some(header)) = units.next() {
+            let unit = match dwarf.unit(header) {
+                Ok(unit) => unit,
+                Err(_) => continue,
+            };
+            let program = match unit.line_program.clone() {
+                Some(program) => program,
+                None => continue,
+            };
+            let mut program_rows = program.rows();
+            while let Ok(Some((header, row))) = program_rows.next_row() {
+                if row.end_sequence() {
+                    rows.push((row.address(), None));
+                    continue;
+                }
+                let file = match row.file(header) {
+                    Some(file) => file,
+                    None => continue,
+                };
+                let mut path = String::new();
+                if let Some(directory) = file.directory(header) {
+                    if let Ok(directory) = dwarf.attr_string(&unit, directory) {
+                        path.push_str(&directory.to_string_lossy());
+                    }
+                }
+                if let Ok(name) = dwarf.attr_string(&unit, file.path_name()) {
+                    let name = name.to_string_lossy();
+                    if path.is_empty() || name.starts_with('/') {
+                        path = name.into_owned();
+                    } else {
+                        path = format!("{}/{}", path, name);
+                    }
+                }
+                let line = row.line().map_or(0, |line| line.get());
+                rows.push((row.address(), Some((path, line))));
+            }
+        }
+        if rows.is_empty() {
+            return None;
+        }
+        // The end of a sequence goes before the rows of another one
+        // starting at the same address
+        rows.sort_by_key(|(address, location)| (*address, location.is_some()));
+        Some(Self { rows })
+    }
+
+    /// The file and line of the last row at or before `address`.
+    pub fn find(&self, address: u64) -> Option<(String, u64)> {
+        let index = self.rows.partition_point(|(row, _)| *row <= address);
+        self.rows[..index].last()?.1.clone()
+    }
+
+    /// The addresses where the code of the first line at or after `line`
+    /// of the file `path` starts, with that line.
+    ///
+    /// `path` matches the files of the rows whose paths end with it, or
+    /// which it ends with, as the directories the module was compiled in
+    /// are seldom the ones its sources are read from.
+    pub fn lines(&self, path: &str, line: u64) -> Option<(u64, Vec<u64>)> {
+        let same_file = |file: &str| {
+            file == path
+                || file.ends_with(&format!("/{}", path))
+                || path.ends_with(&format!("/{}", file))
+        };
+        let found = self
+            .rows
+            .iter()
+            .filter_map(|(_, location)| location.as_ref())
+            .filter(|(file, row_line)| *row_line >= line && same_file(file))
+            .map(|(_, row_line)| *row_line)
+            .min()?;
+        let mut addresses = Vec::new();
+        let mut previous = None;
+        for (address, location) in &self.rows {
+            let current = location
+                .as_ref()
+                .filter(|(file, row_line)| *row_line == found && same_file(file));
+            // Only the rows entering the line, not the ones continuing it
+            if current.is_some() && previous.is_none() {
+                addresses.push(*address);
+            }
+            previous = current;
+        }
+        Some((found, addresses))
+    }
+}
@@ -16,7 +16,7 @@ use wasmer::{
     AsStoreMut, ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, Instance,
     LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
 };
-use wasmer_types::{GlobalIndex, ModuleInfo};
+use wasmer_types::{GlobalIndex, ModuleInfo, TrapCode};
 
 #[derive(Clone)]
 struct MeteringGlobalIndexes(GlobalIndex, GlobalIndex);
@@ -48,6 +48,8 @@ impl fmt::Debug for MeteringGlobalIndexes {
 
 /// The module-level metering middleware.
 ///
+/// Running out of points traps with `TrapCode::OutOfGas`.
+///
 /// # Panic
 ///
 /// An instance of `Metering` should _not_ be shared among different
@@ -153,6 +155,12 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync + 'static> ModuleMiddleware for Meter
         })
     }
 
+    /// The initial limit and the type of the cost function, which tells
+    /// closures apart by where they are defined.
+    fn deterministic_id(&self) -> String {
+        format!("{}-{}", std::any::type_name::<Self>(), self.initial_limit)
+    }
+
     /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
     fn transform_module_info(&self, module_info: &mut ModuleInfo) {
         let mut global_indexes = self.global_indexes.lock().unwrap();
@@ -237,7 +245,9 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> FunctionMiddleware for FunctionMeter
                         Operator::If { ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType) },
                         Operator::I32Const { value: 1 },
                         Operator::GlobalSet { global_index: self.global_indexes.points_exhausted().as_u32() },
-                        Operator::Unreachable,
+                    ]);
+                    state.push_trap(TrapCode::OutOfGas);
+                    state.extend(&[
                         Operator::End,
 
                         // globals[remaining_points_index] -= self.accumulated_cost;
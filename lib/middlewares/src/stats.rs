@@ -0,0 +1,196 @@
+//! `stats` measures the resources used by calls into an instance: the
+//! wall and CPU time they take, and, when the module was instrumented
+//! with [`InstructionCounter`](crate::InstructionCounter) or
+//! [`Metering`](crate::Metering), the instructions they execute and the
+//! points they consume.
+//!
+//! # Example
+//!
+//! ```rust
+//! use wasmer::{AsStoreMut, Function, Instance};
+//! use wasmer_middlewares::stats::CallMeter;
+//!
+//! fn call_and_report(store: &mut impl AsStoreMut, instance: &Instance, function: &Function) {
+//!     let meter = CallMeter::start(store, instance);
+//!     let _ = function.call(store, &[]);
+//!     let stats = meter.finish(store, instance);
+//!     println!("{}", stats);
+//! }
+//! ```
+
+use crate::instruction_counter::get_instruction_count;
+use crate::metering::{get_remaining_points, MeteringPoints};
+use std::fmt;
+use std::time::{Duration, Instant};
+use wasmer::{AsStoreMut, Instance};
+
+/// The resources used by a call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallStats {
+    /// The time elapsed during the call.
+    pub wall_time: Duration,
+
+    /// The CPU time the calling thread spent in the call, or `None` if it
+    /// can't be measured on this platform.
+    pub cpu_time: Option<Duration>,
+
+    /// The instructions executed by the call, or `None` if the module
+    /// wasn't instrumented with
+    /// [`InstructionCounter`](crate::InstructionCounter).
+    pub instructions: Option<u64>,
+
+    /// The metering points consumed by the call, or `None` if the module
+    /// wasn't instrumented with [`Metering`](crate::Metering).
+    pub points: Option<u64>,
+}
+
+impl fmt::Display for CallStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "wall time {:?}", self.wall_time)?;
+        if let Some(cpu_time) = self.cpu_time {
+            write!(f, ", CPU time {:?}", cpu_time)?;
+        }
+        if let Some(instructions) = self.instructions {
+            write!(f, ", {} instructions", instructions)?;
+        }
+        if let Some(points) = self.points {
+            write!(f, ", {} points", points)?;
+        }
+        Ok(())
+    }
+}
+
+/// Measures a call into an instance, from [`CallMeter::start`] to
+/// [`CallMeter::finish`].
+///
+/// The CPU time is the one of the current thread, so the call must run
+/// on the thread the meter was started on.
+#[derive(Debug)]
+pub struct CallMeter {
+    wall_time: Instant,
+    cpu_time: Option<Duration>,
+    instructions: Option<u64>,
+    points: Option<u64>,
+}
+
+impl CallMeter {
+    /// Starts measuring a call into `instance`.
+    pub fn start(store: &mut impl AsStoreMut, instance: &Instance) -> Self {
+        Self {
+            cpu_time: thread_cpu_time(),
+            instructions: get_instruction_count(store, instance),
+            points: remaining_points(store, instance),
+            // Last, so that reading the counters isn't measured
+            wall_time: Instant::now(),
+        }
+    }
+
+    /// Stops measuring, and returns the resources used by the call.
+    pub fn finish(self, store: &mut impl AsStoreMut, instance: &Instance) -> CallStats {
+        let wall_time = self.wall_time.elapsed();
+        let cpu_time = match (self.cpu_time, thread_cpu_time()) {
+            (Some(start), Some(end)) => Some(end.saturating_sub(start)),
+            _ => None,
+        };
+        let instructions = match (self.instructions, get_instruction_count(store, instance)) {
+            (Some(start), Some(end)) => Some(end.wrapping_sub(start)),
+            _ => None,
+        };
+        // Exhausting the points consumes all the remaining ones
+        let points = self
+            .points
+            .map(|start| start.saturating_sub(remaining_points(store, instance).unwrap_or(0)));
+        CallStats {
+            wall_time,
+            cpu_time,
+            instructions,
+            points,
+        }
+    }
+}
+
+/// The remaining points of `instance`, if it's metered.
+fn remaining_points(store: &mut impl AsStoreMut, instance: &Instance) -> Option<u64> {
+    instance
+        .exports
+        .get_global("wasmer_metering_remaining_points")
+        .ok()?;
+    match get_remaining_points(store, instance) {
+        MeteringPoints::Remaining(points) => Some(points),
+        MeteringPoints::Exhausted => Some(0),
+    }
+}
+
+/// The CPU time used by the current thread.
+#[cfg(unix)]
+fn thread_cpu_time() -> Option<Duration> {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) } != 0 {
+        return None;
+    }
+    Some(Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+}
+
+/// The CPU time used by the current thread.
+#[cfg(not(unix))]
+fn thread_cpu_time() -> Option<Duration> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{InstructionCounter, Metering};
+    use std::sync::Arc;
+    use wasmer::wasmparser::Operator;
+    use wasmer::{imports, CompilerConfig, Cranelift, EngineBuilder, Module, Store, TypedFunction};
+
+    #[test]
+    fn call_stats() {
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(InstructionCounter::new()));
+        compiler_config.push_middleware(Arc::new(Metering::new(100, |_: &Operator| 2)));
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(
+            &store,
+            r#"(module
+                (func (export "add_one") (param i32) (result i32)
+                    local.get 0
+                    i32.const 1
+                    i32.add))"#,
+        )
+        .unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        let add_one: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("add_one")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        let meter = CallMeter::start(&mut store, &instance);
+        add_one.call(&mut store, 1).unwrap();
+        let stats = meter.finish(&mut store, &instance);
+        // `local.get`, `i32.const`, `i32.add` and `end`
+        assert_eq!(stats.instructions, Some(4));
+        // At least the same 4 operators, at 2 points each
+        assert!(stats.points.unwrap() >= 8);
+        if cfg!(unix) {
+            assert!(stats.cpu_time.is_some());
+        }
+    }
+
+    #[test]
+    fn call_stats_without_instrumentation() {
+        let mut store = Store::default();
+        let module = Module::new(&store, "(module)").unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        let stats = CallMeter::start(&mut store, &instance).finish(&mut store, &instance);
+        assert_eq!(stats.instructions, None);
+        assert_eq!(stats.points, None);
+    }
+}
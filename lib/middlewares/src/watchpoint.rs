@@ -0,0 +1,631 @@
+//! `watchpoint` is a middleware letting the host watch a range of the
+//! guest memory, to find out which code reads or writes it.
+//!
+//! Every load and store of the module checks the address it accesses
+//! against the range set with [`set_watchpoint`]. An access overlapping
+//! it is recorded, and, depending on the [`WatchAction`], either traps
+//! with `TrapCode::Watchpoint` before it happens, so that the backtrace
+//! of the error points at the faulty code, or is let through and counted.
+//! [`get_watchpoint_hit`] reads the last access back.
+//!
+//! Checking every access makes the code a lot slower, so the middleware
+//! is meant for debugging. The bulk memory operations, and the atomic
+//! and SIMD accesses aren't checked.
+
+use std::convert::TryInto;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Mutex;
+use wasmer::wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
+use wasmer::{
+    AsStoreMut, ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, Instance,
+    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+    Value,
+};
+use wasmer_types::{GlobalIndex, ModuleInfo, TrapCode};
+
+/// The accesses a watchpoint reacts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum WatchAccess {
+    /// Loads from the range.
+    Read = 1,
+    /// Stores to the range.
+    Write = 2,
+    /// Both loads and stores.
+    ReadWrite = 3,
+}
+
+/// What happens when an access hits a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum WatchAction {
+    /// The access traps with `TrapCode::Watchpoint` before it is made.
+    Trap = 0,
+    /// The access is made, and only recorded.
+    Record = 1,
+}
+
+/// The last access that hit the watchpoint of an instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchpointHit {
+    /// The first byte accessed.
+    pub address: u64,
+    /// How many bytes are accessed.
+    pub size: u32,
+    /// Either [`WatchAccess::Read`] or [`WatchAccess::Write`].
+    pub access: WatchAccess,
+    /// How many accesses hit the watchpoint since it was set.
+    pub hits: u64,
+}
+
+/// The globals an instrumented module gets, in the order they are
+/// appended to it.
+const GLOBALS: &[(&str, Type)] = &[
+    ("wasmer_watchpoint_start", Type::I64),
+    ("wasmer_watchpoint_end", Type::I64),
+    ("wasmer_watchpoint_access", Type::I32),
+    ("wasmer_watchpoint_action", Type::I32),
+    ("wasmer_watchpoint_hits", Type::I64),
+    ("wasmer_watchpoint_hit_address", Type::I64),
+    ("wasmer_watchpoint_hit_size", Type::I32),
+    ("wasmer_watchpoint_hit_access", Type::I32),
+];
+
+/// The globals keeping the operands of an access while it is checked,
+/// which aren't exported.
+const SCRATCH_GLOBALS: &[Type] = &[
+    // The address and the effective address.
+    Type::I32,
+    Type::I64,
+    // The value of a store, for each type.
+    Type::I32,
+    Type::I64,
+    Type::F32,
+    Type::F64,
+];
+
+#[derive(Debug, Clone, Copy)]
+struct WatchpointGlobalIndexes(GlobalIndex);
+
+impl WatchpointGlobalIndexes {
+    fn get(&self, offset: usize) -> u32 {
+        self.0.as_u32() + offset as u32
+    }
+
+    fn start(&self) -> u32 {
+        self.get(0)
+    }
+
+    fn end(&self) -> u32 {
+        self.get(1)
+    }
+
+    fn access(&self) -> u32 {
+        self.get(2)
+    }
+
+    fn action(&self) -> u32 {
+        self.get(3)
+    }
+
+    fn hits(&self) -> u32 {
+        self.get(4)
+    }
+
+    fn hit_address(&self) -> u32 {
+        self.get(5)
+    }
+
+    fn hit_size(&self) -> u32 {
+        self.get(6)
+    }
+
+    fn hit_access(&self) -> u32 {
+        self.get(7)
+    }
+
+    fn address(&self) -> u32 {
+        self.get(GLOBALS.len())
+    }
+
+    fn effective_address(&self) -> u32 {
+        self.get(GLOBALS.len() + 1)
+    }
+
+    /// The global keeping the value of a store of type `ty`.
+    fn value(&self, ty: Type) -> u32 {
+        let offset = match ty {
+            Type::I32 => 2,
+            Type::I64 => 3,
+            Type::F32 => 4,
+            _ => 5,
+        };
+        self.get(GLOBALS.len() + offset)
+    }
+}
+
+/// The module-level watchpoint middleware.
+///
+/// The watchpoint of an instance is disabled until [`set_watchpoint`]
+/// is called.
+///
+/// # Panic
+///
+/// An instance of `Watchpoints` should _not_ be shared among different
+/// modules, since it tracks module-specific information like the global
+/// indexes of the watchpoint. Attempts to use a `Watchpoints` instance
+/// from multiple modules will result in a panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::Watchpoints;
+///
+/// fn create_watchpoint_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     compiler_config.push_middleware(Arc::new(Watchpoints::new()));
+/// }
+/// ```
+#[derive(Default)]
+pub struct Watchpoints {
+    /// The global indexes of the watchpoint.
+    global_indexes: Mutex<Option<WatchpointGlobalIndexes>>,
+}
+
+/// The function-level watchpoint middleware.
+pub struct FunctionWatchpoints {
+    /// The global indexes of the watchpoint.
+    global_indexes: WatchpointGlobalIndexes,
+}
+
+impl Watchpoints {
+    /// Creates a `Watchpoints` middleware.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl fmt::Debug for Watchpoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watchpoints")
+            .field("global_indexes", &self.global_indexes)
+            .finish()
+    }
+}
+
+impl ModuleMiddleware for Watchpoints {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionWatchpoints {
+            global_indexes: self.global_indexes.lock().unwrap().unwrap(),
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut global_indexes = self.global_indexes.lock().unwrap();
+
+        if global_indexes.is_some() {
+            panic!("Watchpoints::transform_module_info: Attempting to use a `Watchpoints` middleware from multiple modules.");
+        }
+
+        let mut first = None;
+        let globals = GLOBALS
+            .iter()
+            .map(|(name, ty)| (Some(*name), *ty))
+            .chain(SCRATCH_GLOBALS.iter().map(|ty| (None, *ty)));
+        for (name, ty) in globals {
+            let index = module_info
+                .globals
+                .push(GlobalType::new(ty, Mutability::Var));
+            module_info.global_initializers.push(match ty {
+                Type::I32 => GlobalInit::I32Const(0),
+                Type::I64 => GlobalInit::I64Const(0),
+                Type::F32 => GlobalInit::F32Const(0.0),
+                _ => GlobalInit::F64Const(0.0),
+            });
+            if let Some(name) = name {
+                module_info
+                    .exports
+                    .insert(name.to_string(), ExportIndex::Global(index));
+            }
+            first.get_or_insert(index);
+        }
+
+        *global_indexes = first.map(WatchpointGlobalIndexes);
+    }
+}
+
+impl fmt::Debug for FunctionWatchpoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionWatchpoints")
+            .field("global_indexes", &self.global_indexes)
+            .finish()
+    }
+}
+
+impl FunctionWatchpoints {
+    /// Checks an access of `size` bytes at the address on top of the
+    /// stack plus `offset`, leaving the address on the stack.
+    fn check<'a>(
+        &self,
+        state: &mut MiddlewareReaderState<'a>,
+        offset: u64,
+        size: u32,
+        access: WatchAccess,
+    ) {
+        let globals = &self.global_indexes;
+        let access = access as i32;
+        state.extend(&[
+            // effective_address = address as u64 + offset
+            Operator::GlobalSet {
+                global_index: globals.address(),
+            },
+            Operator::GlobalGet {
+                global_index: globals.address(),
+            },
+            Operator::I64ExtendI32U,
+            Operator::I64Const {
+                value: offset as i64,
+            },
+            Operator::I64Add,
+            Operator::GlobalSet {
+                global_index: globals.effective_address(),
+            },
+            // if (watched_access & access) != 0
+            //     && effective_address < end
+            //     && effective_address + size > start
+            Operator::GlobalGet {
+                global_index: globals.access(),
+            },
+            Operator::I32Const { value: access },
+            Operator::I32And,
+            Operator::I32Const { value: 0 },
+            Operator::I32Ne,
+            Operator::GlobalGet {
+                global_index: globals.effective_address(),
+            },
+            Operator::GlobalGet {
+                global_index: globals.end(),
+            },
+            Operator::I64LtU,
+            Operator::I32And,
+            Operator::GlobalGet {
+                global_index: globals.effective_address(),
+            },
+            Operator::I64Const { value: size as i64 },
+            Operator::I64Add,
+            Operator::GlobalGet {
+                global_index: globals.start(),
+            },
+            Operator::I64GtU,
+            Operator::I32And,
+            Operator::If {
+                ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+            },
+            // Record the access
+            Operator::GlobalGet {
+                global_index: globals.effective_address(),
+            },
+            Operator::GlobalSet {
+                global_index: globals.hit_address(),
+            },
+            Operator::I32Const { value: size as i32 },
+            Operator::GlobalSet {
+                global_index: globals.hit_size(),
+            },
+            Operator::I32Const { value: access },
+            Operator::GlobalSet {
+                global_index: globals.hit_access(),
+            },
+            Operator::GlobalGet {
+                global_index: globals.hits(),
+            },
+            Operator::I64Const { value: 1 },
+            Operator::I64Add,
+            Operator::GlobalSet {
+                global_index: globals.hits(),
+            },
+            // if action == Trap { throw(); }
+            Operator::GlobalGet {
+                global_index: globals.action(),
+            },
+            Operator::I32Eqz,
+            Operator::If {
+                ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+            },
+        ]);
+        state.push_trap(TrapCode::Watchpoint);
+        state.extend(&[
+            Operator::End,
+            Operator::End,
+            Operator::GlobalGet {
+                global_index: globals.address(),
+            },
+        ]);
+    }
+}
+
+impl FunctionMiddleware for FunctionWatchpoints {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        let load = match &operator {
+            Operator::I32Load8S { memarg }
+            | Operator::I32Load8U { memarg }
+            | Operator::I64Load8S { memarg }
+            | Operator::I64Load8U { memarg } => Some((memarg, 1)),
+            Operator::I32Load16S { memarg }
+            | Operator::I32Load16U { memarg }
+            | Operator::I64Load16S { memarg }
+            | Operator::I64Load16U { memarg } => Some((memarg, 2)),
+            Operator::I32Load { memarg }
+            | Operator::F32Load { memarg }
+            | Operator::I64Load32S { memarg }
+            | Operator::I64Load32U { memarg } => Some((memarg, 4)),
+            Operator::I64Load { memarg } | Operator::F64Load { memarg } => Some((memarg, 8)),
+            _ => None,
+        };
+        let store = match &operator {
+            Operator::I32Store8 { memarg } => Some((memarg, 1, Type::I32)),
+            Operator::I64Store8 { memarg } => Some((memarg, 1, Type::I64)),
+            Operator::I32Store16 { memarg } => Some((memarg, 2, Type::I32)),
+            Operator::I64Store16 { memarg } => Some((memarg, 2, Type::I64)),
+            Operator::I32Store { memarg } => Some((memarg, 4, Type::I32)),
+            Operator::I64Store32 { memarg } => Some((memarg, 4, Type::I64)),
+            Operator::F32Store { memarg } => Some((memarg, 4, Type::F32)),
+            Operator::I64Store { memarg } => Some((memarg, 8, Type::I64)),
+            Operator::F64Store { memarg } => Some((memarg, 8, Type::F64)),
+            _ => None,
+        };
+
+        if let Some((memarg, size)) = load {
+            self.check(state, memarg.offset as u64, size, WatchAccess::Read);
+        } else if let Some((memarg, size, ty)) = store {
+            // The value is on top of the address, put it aside while the
+            // address is checked.
+            let value = self.global_indexes.value(ty);
+            state.push_operator(Operator::GlobalSet {
+                global_index: value,
+            });
+            self.check(state, memarg.offset as u64, size, WatchAccess::Write);
+            state.push_operator(Operator::GlobalGet {
+                global_index: value,
+            });
+        }
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+/// Watches the bytes of `range` in the memory of an
+/// [`Instance`][wasmer::Instance], for the accesses of `access`,
+/// replacing its previous watchpoint. The count of hits is reset.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Watchpoints`] middleware at compile time, otherwise this will
+/// panic.
+///
+/// # Example
+///
+/// ```rust
+/// use wasmer::{AsStoreMut, Instance};
+/// use wasmer_middlewares::watchpoint::{set_watchpoint, WatchAccess, WatchAction};
+///
+/// fn trap_on_writes_to_the_first_page(store: &mut impl AsStoreMut, instance: &Instance) {
+///     set_watchpoint(store, instance, 0..0x10000, WatchAccess::Write, WatchAction::Trap);
+/// }
+/// ```
+pub fn set_watchpoint(
+    ctx: &mut impl AsStoreMut,
+    instance: &Instance,
+    range: Range<u64>,
+    access: WatchAccess,
+    action: WatchAction,
+) {
+    set_global(
+        ctx,
+        instance,
+        "wasmer_watchpoint_start",
+        Value::I64(range.start as i64),
+    );
+    set_global(
+        ctx,
+        instance,
+        "wasmer_watchpoint_end",
+        Value::I64(range.end as i64),
+    );
+    set_global(
+        ctx,
+        instance,
+        "wasmer_watchpoint_access",
+        Value::I32(access as i32),
+    );
+    set_global(
+        ctx,
+        instance,
+        "wasmer_watchpoint_action",
+        Value::I32(action as i32),
+    );
+    set_global(ctx, instance, "wasmer_watchpoint_hits", Value::I64(0));
+}
+
+/// Stops watching the memory of an [`Instance`][wasmer::Instance].
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Watchpoints`] middleware at compile time, otherwise this will
+/// panic.
+pub fn clear_watchpoint(ctx: &mut impl AsStoreMut, instance: &Instance) {
+    set_global(ctx, instance, "wasmer_watchpoint_access", Value::I32(0));
+}
+
+/// Get the last access of an [`Instance`][wasmer::Instance] that hit
+/// its watchpoint, or `None` if no access did since it was set.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Watchpoints`] middleware at compile time, otherwise this will
+/// panic.
+pub fn get_watchpoint_hit(ctx: &mut impl AsStoreMut, instance: &Instance) -> Option<WatchpointHit> {
+    let hits = get_global::<i64>(ctx, instance, "wasmer_watchpoint_hits") as u64;
+    if hits == 0 {
+        return None;
+    }
+    let access = match get_global::<i32>(ctx, instance, "wasmer_watchpoint_hit_access") {
+        1 => WatchAccess::Read,
+        _ => WatchAccess::Write,
+    };
+    Some(WatchpointHit {
+        address: get_global::<i64>(ctx, instance, "wasmer_watchpoint_hit_address") as u64,
+        size: get_global::<i32>(ctx, instance, "wasmer_watchpoint_hit_size") as u32,
+        access,
+        hits,
+    })
+}
+
+fn get_global<T>(ctx: &mut impl AsStoreMut, instance: &Instance, name: &str) -> T
+where
+    Value: TryInto<T>,
+{
+    instance
+        .exports
+        .get_global(name)
+        .unwrap_or_else(|_| panic!("Can't get `{}` from Instance", name))
+        .get(ctx)
+        .try_into()
+        .unwrap_or_else(|_| panic!("`{}` from Instance has wrong type", name))
+}
+
+fn set_global(ctx: &mut impl AsStoreMut, instance: &Instance, name: &str, value: Value) {
+    instance
+        .exports
+        .get_global(name)
+        .unwrap_or_else(|_| panic!("Can't get `{}` from Instance", name))
+        .set(ctx, value)
+        .unwrap_or_else(|_| panic!("Can't set `{}` in Instance", name));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Module, Store, TypedFunction,
+    };
+
+    fn instance(store: &mut Store) -> Instance {
+        let bytecode = wat2wasm(
+            br#"
+            (module
+            (memory 1)
+            (func (export "write") (param $addr i32) (param $value i64)
+                local.get $addr
+                local.get $value
+                i64.store offset=4)
+            (func (export "read") (param $addr i32) (result i32)
+                local.get $addr
+                i32.load8_u))
+            "#,
+        )
+        .unwrap();
+        let module = Module::new(store, bytecode).unwrap();
+        Instance::new(store, &module, &imports! {}).unwrap()
+    }
+
+    fn store() -> Store {
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(Watchpoints::new()));
+        Store::new(EngineBuilder::new(compiler_config))
+    }
+
+    #[test]
+    fn watchpoints_trap() {
+        let mut store = store();
+        let instance = instance(&mut store);
+        let write: TypedFunction<(i32, i64), ()> = instance
+            .exports
+            .get_function("write")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        // Disabled until set
+        write.call(&mut store, 100, 1).unwrap();
+        assert_eq!(get_watchpoint_hit(&mut store, &instance), None);
+
+        set_watchpoint(
+            &mut store,
+            &instance,
+            200..204,
+            WatchAccess::Write,
+            WatchAction::Trap,
+        );
+        // Writes bytes 204 to 211, right after the range
+        write.call(&mut store, 200, 1).unwrap();
+        // Writes bytes 197 to 204, overlapping it
+        let error = write.call(&mut store, 193, 1).unwrap_err();
+        assert_eq!(error.to_trap(), Some(TrapCode::Watchpoint));
+        assert_eq!(
+            get_watchpoint_hit(&mut store, &instance),
+            Some(WatchpointHit {
+                address: 197,
+                size: 8,
+                access: WatchAccess::Write,
+                hits: 1,
+            })
+        );
+
+        clear_watchpoint(&mut store, &instance);
+        write.call(&mut store, 193, 1).unwrap();
+    }
+
+    #[test]
+    fn watchpoints_record() {
+        let mut store = store();
+        let instance = instance(&mut store);
+        let read: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("read")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        let write: TypedFunction<(i32, i64), ()> = instance
+            .exports
+            .get_function("write")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        set_watchpoint(
+            &mut store,
+            &instance,
+            8..16,
+            WatchAccess::ReadWrite,
+            WatchAction::Record,
+        );
+        // The writes are made, and only recorded
+        write.call(&mut store, 4, 0x2a).unwrap();
+        assert_eq!(read.call(&mut store, 8).unwrap(), 0x2a);
+        assert_eq!(read.call(&mut store, 16).unwrap(), 0);
+        assert_eq!(
+            get_watchpoint_hit(&mut store, &instance),
+            Some(WatchpointHit {
+                address: 8,
+                size: 1,
+                access: WatchAccess::Read,
+                hits: 2,
+            })
+        );
+    }
+}
@@ -0,0 +1,478 @@
+//! Code for the interpreter of the VM.
+//!
+//! Compilers that don't emit machine code, for platforms where memory
+//! can't be made executable, translate WebAssembly functions into
+//! [`InterpretedFunction`]s instead. Their [`Instruction`]s work on a
+//! stack of untyped 64-bit slots, and their branches have been resolved
+//! to the instruction they continue at.
+
+use crate::lib::std::boxed::Box;
+use crate::{DataIndex, ElemIndex, FunctionIndex, GlobalIndex, MemoryIndex, SignatureIndex};
+use crate::{FunctionAddressMap, TableIndex, TrapCode, Type};
+
+/// A function translated for the interpreter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterpretedFunction {
+    /// The types of the parameters of the function.
+    pub params: Box<[Type]>,
+    /// The types of the results of the function.
+    pub results: Box<[Type]>,
+    /// The types of the locals the function declares after its
+    /// parameters, which start zeroed.
+    pub locals: Box<[Type]>,
+    /// The instructions of the function, which run from the first one
+    /// until an [`Instruction::Return`].
+    pub code: Box<[Instruction]>,
+    /// The targets of the [`Instruction::BrTable`]s of the function, the
+    /// default target of each table coming last.
+    pub branch_tables: Box<[Box<[BranchTarget]>]>,
+    /// The offsets in the WebAssembly module the instructions were
+    /// translated from, their code offsets being their offsets in `code`
+    /// in bytes.
+    pub address_map: FunctionAddressMap,
+}
+
+/// Where a branch continues, and how it unwinds the value stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BranchTarget {
+    /// The index of the instruction the branch continues at.
+    pub pc: u32,
+    /// The number of values the branch drops, below the ones it keeps.
+    pub drop: u32,
+    /// The number of values on top of the stack the branch keeps.
+    pub keep: u32,
+}
+
+/// The immediate of an instruction accessing a linear memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemArg {
+    /// The memory accessed.
+    pub memory: MemoryIndex,
+    /// The offset added to the address popped from the stack.
+    pub offset: u32,
+}
+
+/// An instruction of the interpreter.
+///
+/// Instructions named after a WebAssembly instruction have its
+/// semantics. Structured control flow is lowered to branches, and
+/// instructions without any effect in the interpreter, like `nop` or the
+/// reinterpretations, aren't emitted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    /// `unreachable`, trapping with the given code, which is
+    /// `UnreachableCodeReached` unless a middleware changed it.
+    Unreachable(TrapCode),
+    /// Branches unconditionally.
+    Br(BranchTarget),
+    /// Branches if the `i32` popped from the stack isn't zero.
+    BrIf(BranchTarget),
+    /// Branches if the `i32` popped from the stack is zero, without
+    /// unwinding the stack.
+    BrIfEqz(u32),
+    /// Branches to the target of the given branch table selected by the
+    /// `i32` popped from the stack.
+    BrTable(u32),
+    /// Returns from the function, with the results on top of the stack.
+    Return,
+    /// `call`
+    Call(FunctionIndex),
+    /// `call_indirect`
+    CallIndirect {
+        /// The signature of the function called.
+        signature: SignatureIndex,
+        /// The table the function is called through.
+        table: TableIndex,
+    },
+    /// `drop`
+    Drop,
+    /// `select`, typed or not.
+    Select,
+    /// `local.get`, the parameters coming first.
+    LocalGet(u32),
+    /// `local.set`
+    LocalSet(u32),
+    /// `local.tee`
+    LocalTee(u32),
+    /// `global.get`
+    GlobalGet {
+        /// The global read.
+        global: GlobalIndex,
+        /// Its type.
+        ty: Type,
+    },
+    /// `global.set`
+    GlobalSet {
+        /// The global written.
+        global: GlobalIndex,
+        /// Its type.
+        ty: Type,
+    },
+    /// `i32.load`
+    I32Load(MemArg),
+    /// `i64.load`
+    I64Load(MemArg),
+    /// `f32.load`
+    F32Load(MemArg),
+    /// `f64.load`
+    F64Load(MemArg),
+    /// `i32.load8_s`
+    I32Load8S(MemArg),
+    /// `i32.load8_u`
+    I32Load8U(MemArg),
+    /// `i32.load16_s`
+    I32Load16S(MemArg),
+    /// `i32.load16_u`
+    I32Load16U(MemArg),
+    /// `i64.load8_s`
+    I64Load8S(MemArg),
+    /// `i64.load8_u`
+    I64Load8U(MemArg),
+    /// `i64.load16_s`
+    I64Load16S(MemArg),
+    /// `i64.load16_u`
+    I64Load16U(MemArg),
+    /// `i64.load32_s`
+    I64Load32S(MemArg),
+    /// `i64.load32_u`
+    I64Load32U(MemArg),
+    /// `i32.store`
+    I32Store(MemArg),
+    /// `i64.store`
+    I64Store(MemArg),
+    /// `f32.store`
+    F32Store(MemArg),
+    /// `f64.store`
+    F64Store(MemArg),
+    /// `i32.store8`
+    I32Store8(MemArg),
+    /// `i32.store16`
+    I32Store16(MemArg),
+    /// `i64.store8`
+    I64Store8(MemArg),
+    /// `i64.store16`
+    I64Store16(MemArg),
+    /// `i64.store32`
+    I64Store32(MemArg),
+    /// `memory.size`
+    MemorySize(MemoryIndex),
+    /// `memory.grow`
+    MemoryGrow(MemoryIndex),
+    /// `memory.init`
+    MemoryInit {
+        /// The memory initialized.
+        memory: MemoryIndex,
+        /// The passive data segment copied.
+        data: DataIndex,
+    },
+    /// `data.drop`
+    DataDrop(DataIndex),
+    /// `memory.copy`, within a single memory.
+    MemoryCopy(MemoryIndex),
+    /// `memory.fill`
+    MemoryFill(MemoryIndex),
+    /// `table.init`
+    TableInit {
+        /// The table initialized.
+        table: TableIndex,
+        /// The passive element segment copied.
+        elem: ElemIndex,
+    },
+    /// `elem.drop`
+    ElemDrop(ElemIndex),
+    /// `table.copy`
+    TableCopy {
+        /// The table copied to.
+        dst: TableIndex,
+        /// The table copied from.
+        src: TableIndex,
+    },
+    /// `table.get`
+    TableGet(TableIndex),
+    /// `table.set`
+    TableSet(TableIndex),
+    /// `table.grow`
+    TableGrow(TableIndex),
+    /// `table.size`
+    TableSize(TableIndex),
+    /// `table.fill`
+    TableFill(TableIndex),
+    /// `ref.null`
+    RefNull,
+    /// `ref.is_null`
+    RefIsNull,
+    /// `ref.func`
+    RefFunc(FunctionIndex),
+    /// `i32.const`
+    I32Const(i32),
+    /// `i64.const`
+    I64Const(i64),
+    /// `f32.const`, given by its bits.
+    F32Const(u32),
+    /// `f64.const`, given by its bits.
+    F64Const(u64),
+    /// `i32.eqz`
+    I32Eqz,
+    /// `i32.eq`
+    I32Eq,
+    /// `i32.ne`
+    I32Ne,
+    /// `i32.lt_s`
+    I32LtS,
+    /// `i32.lt_u`
+    I32LtU,
+    /// `i32.gt_s`
+    I32GtS,
+    /// `i32.gt_u`
+    I32GtU,
+    /// `i32.le_s`
+    I32LeS,
+    /// `i32.le_u`
+    I32LeU,
+    /// `i32.ge_s`
+    I32GeS,
+    /// `i32.ge_u`
+    I32GeU,
+    /// `i64.eqz`
+    I64Eqz,
+    /// `i64.eq`
+    I64Eq,
+    /// `i64.ne`
+    I64Ne,
+    /// `i64.lt_s`
+    I64LtS,
+    /// `i64.lt_u`
+    I64LtU,
+    /// `i64.gt_s`
+    I64GtS,
+    /// `i64.gt_u`
+    I64GtU,
+    /// `i64.le_s`
+    I64LeS,
+    /// `i64.le_u`
+    I64LeU,
+    /// `i64.ge_s`
+    I64GeS,
+    /// `i64.ge_u`
+    I64GeU,
+    /// `f32.eq`
+    F32Eq,
+    /// `f32.ne`
+    F32Ne,
+    /// `f32.lt`
+    F32Lt,
+    /// `f32.gt`
+    F32Gt,
+    /// `f32.le`
+    F32Le,
+    /// `f32.ge`
+    F32Ge,
+    /// `f64.eq`
+    F64Eq,
+    /// `f64.ne`
+    F64Ne,
+    /// `f64.lt`
+    F64Lt,
+    /// `f64.gt`
+    F64Gt,
+    /// `f64.le`
+    F64Le,
+    /// `f64.ge`
+    F64Ge,
+    /// `i32.clz`
+    I32Clz,
+    /// `i32.ctz`
+    I32Ctz,
+    /// `i32.popcnt`
+    I32Popcnt,
+    /// `i32.add`
+    I32Add,
+    /// `i32.sub`
+    I32Sub,
+    /// `i32.mul`
+    I32Mul,
+    /// `i32.div_s`
+    I32DivS,
+    /// `i32.div_u`
+    I32DivU,
+    /// `i32.rem_s`
+    I32RemS,
+    /// `i32.rem_u`
+    I32RemU,
+    /// `i32.and`
+    I32And,
+    /// `i32.or`
+    I32Or,
+    /// `i32.xor`
+    I32Xor,
+    /// `i32.shl`
+    I32Shl,
+    /// `i32.shr_s`
+    I32ShrS,
+    /// `i32.shr_u`
+    I32ShrU,
+    /// `i32.rotl`
+    I32Rotl,
+    /// `i32.rotr`
+    I32Rotr,
+    /// `i64.clz`
+    I64Clz,
+    /// `i64.ctz`
+    I64Ctz,
+    /// `i64.popcnt`
+    I64Popcnt,
+    /// `i64.add`
+    I64Add,
+    /// `i64.sub`
+    I64Sub,
+    /// `i64.mul`
+    I64Mul,
+    /// `i64.div_s`
+    I64DivS,
+    /// `i64.div_u`
+    I64DivU,
+    /// `i64.rem_s`
+    I64RemS,
+    /// `i64.rem_u`
+    I64RemU,
+    /// `i64.and`
+    I64And,
+    /// `i64.or`
+    I64Or,
+    /// `i64.xor`
+    I64Xor,
+    /// `i64.shl`
+    I64Shl,
+    /// `i64.shr_s`
+    I64ShrS,
+    /// `i64.shr_u`
+    I64ShrU,
+    /// `i64.rotl`
+    I64Rotl,
+    /// `i64.rotr`
+    I64Rotr,
+    /// `f32.abs`
+    F32Abs,
+    /// `f32.neg`
+    F32Neg,
+    /// `f32.ceil`
+    F32Ceil,
+    /// `f32.floor`
+    F32Floor,
+    /// `f32.trunc`
+    F32Trunc,
+    /// `f32.nearest`
+    F32Nearest,
+    /// `f32.sqrt`
+    F32Sqrt,
+    /// `f32.add`
+    F32Add,
+    /// `f32.sub`
+    F32Sub,
+    /// `f32.mul`
+    F32Mul,
+    /// `f32.div`
+    F32Div,
+    /// `f32.min`
+    F32Min,
+    /// `f32.max`
+    F32Max,
+    /// `f32.copysign`
+    F32Copysign,
+    /// `f64.abs`
+    F64Abs,
+    /// `f64.neg`
+    F64Neg,
+    /// `f64.ceil`
+    F64Ceil,
+    /// `f64.floor`
+    F64Floor,
+    /// `f64.trunc`
+    F64Trunc,
+    /// `f64.nearest`
+    F64Nearest,
+    /// `f64.sqrt`
+    F64Sqrt,
+    /// `f64.add`
+    F64Add,
+    /// `f64.sub`
+    F64Sub,
+    /// `f64.mul`
+    F64Mul,
+    /// `f64.div`
+    F64Div,
+    /// `f64.min`
+    F64Min,
+    /// `f64.max`
+    F64Max,
+    /// `f64.copysign`
+    F64Copysign,
+    /// `i32.wrap_i64`
+    I32WrapI64,
+    /// `i32.trunc_f32_s`
+    I32TruncF32S,
+    /// `i32.trunc_f32_u`
+    I32TruncF32U,
+    /// `i32.trunc_f64_s`
+    I32TruncF64S,
+    /// `i32.trunc_f64_u`
+    I32TruncF64U,
+    /// `i64.extend_i32_s`
+    I64ExtendI32S,
+    /// `i64.extend_i32_u`
+    I64ExtendI32U,
+    /// `i64.trunc_f32_s`
+    I64TruncF32S,
+    /// `i64.trunc_f32_u`
+    I64TruncF32U,
+    /// `i64.trunc_f64_s`
+    I64TruncF64S,
+    /// `i64.trunc_f64_u`
+    I64TruncF64U,
+    /// `f32.convert_i32_s`
+    F32ConvertI32S,
+    /// `f32.convert_i32_u`
+    F32ConvertI32U,
+    /// `f32.convert_i64_s`
+    F32ConvertI64S,
+    /// `f32.convert_i64_u`
+    F32ConvertI64U,
+    /// `f32.demote_f64`
+    F32DemoteF64,
+    /// `f64.convert_i32_s`
+    F64ConvertI32S,
+    /// `f64.convert_i32_u`
+    F64ConvertI32U,
+    /// `f64.convert_i64_s`
+    F64ConvertI64S,
+    /// `f64.convert_i64_u`
+    F64ConvertI64U,
+    /// `f64.promote_f32`
+    F64PromoteF32,
+    /// `i32.extend8_s`
+    I32Extend8S,
+    /// `i32.extend16_s`
+    I32Extend16S,
+    /// `i64.extend8_s`
+    I64Extend8S,
+    /// `i64.extend16_s`
+    I64Extend16S,
+    /// `i64.extend32_s`
+    I64Extend32S,
+    /// `i32.trunc_sat_f32_s`
+    I32TruncSatF32S,
+    /// `i32.trunc_sat_f32_u`
+    I32TruncSatF32U,
+    /// `i32.trunc_sat_f64_s`
+    I32TruncSatF64S,
+    /// `i32.trunc_sat_f64_u`
+    I32TruncSatF64U,
+    /// `i64.trunc_sat_f32_s`
+    I64TruncSatF32S,
+    /// `i64.trunc_sat_f32_u`
+    I64TruncSatF32U,
+    /// `i64.trunc_sat_f64_s`
+    I64TruncSatF64S,
+    /// `i64.trunc_sat_f64_u`
+    I64TruncSatF64U,
+}
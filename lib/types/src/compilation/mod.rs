@@ -2,6 +2,7 @@
 
 pub mod address_map;
 pub mod function;
+pub mod interpreter;
 pub mod module;
 pub mod relocation;
 pub mod section;
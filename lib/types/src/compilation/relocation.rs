@@ -50,12 +50,16 @@ pub enum RelocationKind {
     Arm64Movw2,
     /// Arm64 movk/z part 3
     Arm64Movw3,
-    // /// RISC-V call target
-    // RiscvCall,
     /// Elf x86_64 32 bit signed PC relative offset to two GOT entries for GD symbol.
     ElfX86_64TlsGd,
     // /// Mach-O x86_64 32 bit signed PC relative offset to a `__thread_vars` entry.
     // MachOX86_64Tlv,
+    /// RISC-V call target, an `auipc` followed by a `jalr`
+    RiscvCall,
+    /// RISC-V PC-relative high 20 bits, of an `auipc`
+    RiscvPCRelHi20,
+    /// RISC-V PC-relative low 12 bits, of an I-type instruction
+    RiscvPCRelLo12I,
 }
 
 impl fmt::Display for RelocationKind {
@@ -77,6 +81,9 @@ impl fmt::Display for RelocationKind {
             Self::Arm64Movw3 => write!(f, "Arm64MovwG3"),
             Self::ElfX86_64TlsGd => write!(f, "ElfX86_64TlsGd"),
             // Self::MachOX86_64Tlv => write!(f, "MachOX86_64Tlv"),
+            Self::RiscvCall => write!(f, "RiscvCall"),
+            Self::RiscvPCRelHi20 => write!(f, "RiscvPCRelHi20"),
+            Self::RiscvPCRelLo12I => write!(f, "RiscvPCRelLo12I"),
         }
     }
 }
@@ -153,7 +160,10 @@ impl Relocation {
                     .wrapping_add(reloc_addend as u32);
                 (reloc_address, reloc_delta_u32 as u64)
             }
-            RelocationKind::Arm64Call => {
+            RelocationKind::Arm64Call
+            | RelocationKind::RiscvCall
+            | RelocationKind::RiscvPCRelHi20
+            | RelocationKind::RiscvPCRelLo12I => {
                 let reloc_address = start + self.offset as usize;
                 let reloc_addend = self.addend as isize;
                 let reloc_delta_u32 = target_func_address
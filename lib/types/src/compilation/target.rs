@@ -9,6 +9,7 @@
 
 use crate::error::ParseCpuFeatureError;
 use enumset::{EnumSet, EnumSetType};
+use std::fmt;
 use std::str::FromStr;
 use std::string::{String, ToString};
 pub use target_lexicon::{
@@ -112,6 +113,92 @@ impl CpuFeature {
         // We default to an empty hash set
         EnumSet::new()
     }
+
+    /// Formats a set of `CpuFeature`s as a comma separated list, using
+    /// the same names accepted by `FromStr`.
+    pub fn format_set(features: EnumSet<Self>) -> String {
+        features
+            .iter()
+            .map(|feature| feature.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// A named set of CPU features that artifacts can be compiled for, so
+/// they are portable across every host that meets the profile instead of
+/// only running on the exact CPU that compiled them.
+///
+/// The x86-64 profiles follow the [x86-64 microarchitecture levels],
+/// restricted to the features known by [`CpuFeature`].
+///
+/// [x86-64 microarchitecture levels]: https://gitlab.com/x86-psABIs/x86-64-ABI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CpuBaseline {
+    /// `x86-64`: the original AMD64 feature set.
+    X86_64,
+    /// `x86-64-v2`: adds SSE3, SSSE3, SSE4.1, SSE4.2 and POPCNT.
+    X86_64V2,
+    /// `x86-64-v3`: adds AVX, AVX2, BMI1, BMI2 and LZCNT.
+    X86_64V3,
+    /// `x86-64-v4`: adds AVX512F, AVX512DQ and AVX512VL.
+    X86_64V4,
+}
+
+impl CpuBaseline {
+    /// The architecture the profile applies to.
+    pub fn architecture(&self) -> Architecture {
+        Architecture::X86_64
+    }
+
+    /// The CPU features required by the profile.
+    pub fn features(&self) -> EnumSet<CpuFeature> {
+        let v1 = EnumSet::only(CpuFeature::SSE2);
+        let v2 = v1
+            | CpuFeature::SSE3
+            | CpuFeature::SSSE3
+            | CpuFeature::SSE41
+            | CpuFeature::SSE42
+            | CpuFeature::POPCNT;
+        let v3 = v2
+            | CpuFeature::AVX
+            | CpuFeature::AVX2
+            | CpuFeature::BMI1
+            | CpuFeature::BMI2
+            | CpuFeature::LZCNT;
+        let v4 = v3 | CpuFeature::AVX512F | CpuFeature::AVX512DQ | CpuFeature::AVX512VL;
+        match self {
+            Self::X86_64 => v1,
+            Self::X86_64V2 => v2,
+            Self::X86_64V3 => v3,
+            Self::X86_64V4 => v4,
+        }
+    }
+}
+
+impl FromStr for CpuBaseline {
+    type Err = ParseCpuFeatureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "x86-64" | "x86-64-v1" => Ok(Self::X86_64),
+            "x86-64-v2" => Ok(Self::X86_64V2),
+            "x86-64-v3" => Ok(Self::X86_64V3),
+            "x86-64-v4" => Ok(Self::X86_64V4),
+            _ => Err(ParseCpuFeatureError::UnknownBaseline(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for CpuBaseline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::X86_64 => "x86-64",
+            Self::X86_64V2 => "x86-64-v2",
+            Self::X86_64V3 => "x86-64-v3",
+            Self::X86_64V4 => "x86-64-v4",
+        })
+    }
 }
 
 // This options should map exactly the GCC options indicated
@@ -192,6 +279,13 @@ impl Target {
     pub fn cpu_features(&self) -> &EnumSet<CpuFeature> {
         &self.cpu_features
     }
+
+    /// Check if target is a native (eq to host) or not
+    pub fn is_native(&self) -> bool {
+        let host = Triple::host();
+        host.architecture == self.triple.architecture
+            && host.operating_system == self.triple.operating_system
+    }
 }
 
 /// The default for the Target will use the HOST as the triple
@@ -203,3 +297,26 @@ impl Default for Target {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_baselines_are_layered() {
+        let levels = ["x86-64", "x86-64-v2", "x86-64-v3", "x86-64-v4"]
+            .iter()
+            .map(|name| CpuBaseline::from_str(name).unwrap())
+            .collect::<Vec<_>>();
+        for pair in levels.windows(2) {
+            assert!(pair[1].features().is_superset(pair[0].features()));
+            assert_ne!(pair[1].features(), pair[0].features());
+        }
+        assert_eq!(levels[2].to_string(), "x86-64-v3");
+        assert!(CpuBaseline::from_str("pentium").is_err());
+        assert_eq!(
+            CpuFeature::format_set(CpuFeature::SSE2 | CpuFeature::AVX2),
+            "sse2, avx2"
+        );
+    }
+}
@@ -242,6 +242,9 @@ pub enum ParseCpuFeatureError {
     /// The provided string feature doesn't exist
     #[cfg_attr(feature = "std", error("CpuFeature {0} not recognized"))]
     Missing(String),
+    /// The provided string is not a known CPU baseline
+    #[cfg_attr(feature = "std", error("CPU baseline {0} not recognized"))]
+    UnknownBaseline(String),
 }
 
 /// A convenient alias for a `Result` that uses `WasmError` as the error type.
@@ -232,6 +232,36 @@ impl Features {
         self.memory64 = enable;
         self
     }
+
+    /// The names of the enabled features.
+    pub fn enabled(&self) -> impl Iterator<Item = &'static str> {
+        let features = vec![
+            ("threads", self.threads),
+            ("reference_types", self.reference_types),
+            ("simd", self.simd),
+            ("bulk_memory", self.bulk_memory),
+            ("multi_value", self.multi_value),
+            ("tail_call", self.tail_call),
+            ("module_linking", self.module_linking),
+            ("multi_memory", self.multi_memory),
+            ("memory64", self.memory64),
+            ("exceptions", self.exceptions),
+            ("relaxed_simd", self.relaxed_simd),
+            ("extended_const", self.extended_const),
+        ];
+        features
+            .into_iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| name)
+    }
+
+    /// The names of the features enabled in `self` but not in `other`.
+    pub fn difference(&self, other: &Self) -> Vec<&'static str> {
+        let other = other.enabled().collect::<Vec<_>>();
+        self.enabled()
+            .filter(|name| !other.contains(name))
+            .collect()
+    }
 }
 
 impl Default for Features {
@@ -340,4 +370,17 @@ mod test_features {
         features.memory64(true);
         assert!(features.memory64);
     }
+
+    #[test]
+    fn features_difference() {
+        let default = Features::new();
+        let mut features = Features::new();
+        features.threads(true).simd(false);
+        assert_eq!(
+            default.enabled().collect::<Vec<_>>(),
+            vec!["reference_types", "simd", "bulk_memory", "multi_value"]
+        );
+        assert_eq!(features.difference(&default), vec!["threads"]);
+        assert_eq!(default.difference(&features), vec!["simd"]);
+    }
 }
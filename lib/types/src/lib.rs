@@ -71,8 +71,8 @@ mod value;
 mod vmoffsets;
 
 pub use crate::compilation::target::{
-    Aarch64Architecture, Architecture, BinaryFormat, CallingConvention, CpuFeature, Endianness,
-    Environment, OperatingSystem, PointerWidth, Target, Triple, Vendor,
+    Aarch64Architecture, Architecture, BinaryFormat, CallingConvention, CpuBaseline, CpuFeature,
+    Endianness, Environment, OperatingSystem, PointerWidth, Target, Triple, Vendor,
 };
 pub use crate::serialize::{MetadataHeader, SerializableCompilation, SerializableModule};
 pub use error::{
@@ -92,7 +92,9 @@ pub use crate::initializers::{
     DataInitializer, DataInitializerLocation, OwnedDataInitializer, TableInitializer,
 };
 pub use crate::memory::{Memory32, Memory64, MemorySize};
-pub use crate::module::{ExportsIterator, ImportKey, ImportsIterator, ModuleInfo};
+pub use crate::module::{
+    ExportsIterator, ImportKey, ImportsIterator, ModuleHash, ModuleId, ModuleInfo,
+};
 pub use crate::units::{
     Bytes, PageCountOutOfRange, Pages, WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
 };
@@ -108,7 +110,10 @@ pub use crate::table::TableStyle;
 pub use crate::trapcode::TrapCode;
 pub use crate::vmoffsets::{TargetSharedSignatureIndex, VMBuiltinFunctionIndex, VMOffsets};
 
-pub use crate::utils::is_wasm;
+pub use crate::utils::{
+    attach_metadata, is_wasm, parse_metadata, strip_custom_sections, METADATA_NAME,
+    METADATA_SECTION,
+};
 
 pub use crate::compilation::relocation::{
     Relocation, RelocationKind, RelocationTarget, Relocations,
@@ -122,6 +127,7 @@ pub use crate::compilation::function::{
     Compilation, CompiledFunction, CompiledFunctionFrameInfo, CustomSections, Dwarf, FunctionBody,
     Functions,
 };
+pub use crate::compilation::interpreter::{BranchTarget, Instruction, InterpretedFunction, MemArg};
 pub use crate::compilation::module::CompileModuleInfo;
 pub use crate::compilation::sourceloc::SourceLoc;
 pub use crate::compilation::symbols::{Symbol, SymbolRegistry};
@@ -19,18 +19,21 @@ use rkyv::{
 };
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::fmt;
 use std::iter::ExactSizeIterator;
 use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 
-#[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
+/// A process-unique identifier of a [`ModuleInfo`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, RkyvSerialize, RkyvDeserialize, Archive)]
 pub struct ModuleId {
     id: usize,
 }
 
 impl ModuleId {
+    /// Returns the identifier as a string.
     pub fn id(&self) -> String {
         format!("{}", &self.id)
     }
@@ -45,6 +48,35 @@ impl Default for ModuleId {
     }
 }
 
+/// The SHA-256 hash of the bytes a [`ModuleInfo`] was translated from.
+///
+/// Unlike a [`ModuleId`], it stays the same when the same bytes are
+/// compiled again, or when the module is serialized and deserialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, RkyvSerialize, RkyvDeserialize, Archive)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub struct ModuleHash([u8; 32]);
+
+impl ModuleHash {
+    /// Hashes the bytes of a WebAssembly module.
+    pub fn hash(wasm: &[u8]) -> Self {
+        Self(Sha256::digest(wasm).into())
+    }
+
+    /// Returns the bytes of the hash.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for ModuleHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
 /// Hash key of an import
 #[derive(Debug, Hash, Eq, PartialEq, Clone, Default, RkyvSerialize, RkyvDeserialize, Archive)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
@@ -81,9 +113,16 @@ pub struct ModuleInfo {
     #[cfg_attr(feature = "enable-serde", serde(skip_serializing, skip_deserializing))]
     pub id: ModuleId,
 
+    /// The hash of the bytes this module was translated from, if it was.
+    pub hash: Option<ModuleHash>,
+
     /// The name of this wasm module, often found in the wasm file.
     pub name: Option<String>,
 
+    /// Key/value metadata attached to the module, such as its version or
+    /// build id, see [`attach_metadata`](crate::attach_metadata).
+    pub metadata: BTreeMap<String, String>,
+
     /// Imported entities with the (module, field, index_of_the_import)
     ///
     /// Keeping the `index_of_the_import` is important, as there can be
@@ -127,8 +166,8 @@ pub struct ModuleInfo {
     /// WebAssembly global variables (imported and local).
     pub globals: PrimaryMap<GlobalIndex, GlobalType>,
 
-    /// Custom sections in the module.
-    pub custom_sections: IndexMap<String, CustomSectionIndex>,
+    /// Custom sections in the module, by name in the order they appear.
+    pub custom_sections: IndexMap<String, Vec<CustomSectionIndex>>,
 
     /// The data for each CustomSection in the module.
     pub custom_sections_data: PrimaryMap<CustomSectionIndex, Box<[u8]>>,
@@ -149,7 +188,9 @@ pub struct ModuleInfo {
 /// Mirror version of ModuleInfo that can derive rkyv traits
 #[derive(RkyvSerialize, RkyvDeserialize, Archive)]
 pub struct ArchivableModuleInfo {
+    hash: Option<ModuleHash>,
     name: Option<String>,
+    metadata: BTreeMap<String, String>,
     imports: IndexMap<ImportKey, ImportIndex>,
     exports: IndexMap<String, ExportIndex>,
     start_function: Option<FunctionIndex>,
@@ -163,7 +204,7 @@ pub struct ArchivableModuleInfo {
     tables: PrimaryMap<TableIndex, TableType>,
     memories: PrimaryMap<MemoryIndex, MemoryType>,
     globals: PrimaryMap<GlobalIndex, GlobalType>,
-    custom_sections: IndexMap<String, CustomSectionIndex>,
+    custom_sections: IndexMap<String, Vec<CustomSectionIndex>>,
     custom_sections_data: PrimaryMap<CustomSectionIndex, Box<[u8]>>,
     num_imported_functions: usize,
     num_imported_tables: usize,
@@ -174,7 +215,9 @@ pub struct ArchivableModuleInfo {
 impl From<ModuleInfo> for ArchivableModuleInfo {
     fn from(it: ModuleInfo) -> Self {
         Self {
+            hash: it.hash,
             name: it.name,
+            metadata: it.metadata,
             imports: it.imports,
             exports: it.exports,
             start_function: it.start_function,
@@ -202,7 +245,9 @@ impl From<ArchivableModuleInfo> for ModuleInfo {
     fn from(it: ArchivableModuleInfo) -> Self {
         Self {
             id: Default::default(),
+            hash: it.hash,
             name: it.name,
+            metadata: it.metadata,
             imports: it.imports,
             exports: it.exports,
             start_function: it.start_function,
@@ -262,7 +307,9 @@ impl<D: Fallible + ?Sized + SharedDeserializeRegistry> RkyvDeserialize<ModuleInf
 // For test serialization correctness, everything except module id should be same
 impl PartialEq for ModuleInfo {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name
+        self.hash == other.hash
+            && self.name == other.name
+            && self.metadata == other.metadata
             && self.imports == other.imports
             && self.exports == other.exports
             && self.start_function == other.start_function
@@ -371,15 +418,12 @@ impl ModuleInfo {
     }
 
     /// Get the custom sections of the module given a `name`.
-    pub fn custom_sections<'a>(&'a self, name: &'a str) -> impl Iterator<Item = Box<[u8]>> + 'a {
+    pub fn custom_sections<'a>(&'a self, name: &str) -> impl Iterator<Item = &'a [u8]> + 'a {
         self.custom_sections
+            .get(name)
+            .map_or(&[][..], |indices| &indices[..])
             .iter()
-            .filter_map(move |(section_name, section_index)| {
-                if name != section_name {
-                    return None;
-                }
-                Some(self.custom_sections_data[*section_index].clone())
-            })
+            .map(move |index| &self.custom_sections_data[*index][..])
     }
 
     /// Convert a `LocalFunctionIndex` into a `FunctionIndex`.
@@ -465,6 +509,35 @@ impl ModuleInfo {
         }
     }
 
+    /// Get the name of a function, from the name section, or else
+    /// `wasm-function[{index}]`.
+    pub fn function_name(&self, index: FunctionIndex) -> String {
+        match self.function_names.get(&index) {
+            Some(name) => name.clone(),
+            None => format!("wasm-function[{}]", index.index()),
+        }
+    }
+
+    /// Get the name the name section gives to the function exported as
+    /// `name`, if it gives it one.
+    pub fn exported_function_name(&self, name: &str) -> Option<&str> {
+        match self.exports.get(name)? {
+            ExportIndex::Function(index) => self.function_names.get(index).map(String::as_str),
+            _ => None,
+        }
+    }
+
+    /// Get the name the name section gives to the function imported as
+    /// `field` of `module`, if it gives it one.
+    pub fn imported_function_name(&self, module: &str, field: &str) -> Option<&str> {
+        self.imports.iter().find_map(|(key, index)| match index {
+            ImportIndex::Function(index) if key.module == module && key.field == field => {
+                self.function_names.get(index).map(String::as_str)
+            }
+            _ => None,
+        })
+    }
+
     /// Get the imported function types of the module.
     pub fn imported_function_types(&'_ self) -> impl Iterator<Item = FunctionType> + '_ {
         self.functions
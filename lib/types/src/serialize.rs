@@ -3,7 +3,7 @@ use crate::{
     compilation::target::CpuFeature, CompileModuleInfo, CompiledFunctionFrameInfo, CustomSection,
     DeserializeError, Dwarf, Features, FunctionBody, FunctionIndex, LocalFunctionIndex,
     MemoryIndex, MemoryStyle, ModuleInfo, OwnedDataInitializer, Relocation, SectionIndex,
-    SerializeError, SignatureIndex, TableIndex, TableStyle,
+    SerializeError, SignatureIndex, TableIndex, TableStyle, Target, Triple,
 };
 use enumset::EnumSet;
 use rkyv::{
@@ -13,6 +13,7 @@ use rkyv::{
 };
 use std::convert::TryInto;
 use std::path::Path;
+use std::str::FromStr;
 use std::{fs, mem};
 
 /// The compilation related data for a serialized modules
@@ -61,6 +62,8 @@ pub struct SerializableModule {
     pub data_initializers: Box<[OwnedDataInitializer]>,
     /// CPU Feature flags for this compilation
     pub cpu_features: u64,
+    /// Triple of the target this compilation is for
+    pub target_triple: String,
 }
 
 fn to_serialize_error(err: impl std::error::Error) -> SerializeError {
@@ -142,6 +145,13 @@ impl SerializableModule {
         EnumSet::from_u64(self.cpu_features)
     }
 
+    /// Returns the target this Artifact was compiled for, or `None` if its
+    /// triple can't be parsed
+    pub fn target(&self) -> Option<Target> {
+        let triple = Triple::from_str(&self.target_triple).ok()?;
+        Some(Target::new(triple, self.cpu_features()))
+    }
+
     /// Returns data initializers to pass to `InstanceHandle::initialize`
     pub fn data_initializers(&self) -> &[OwnedDataInitializer] {
         &self.data_initializers
@@ -178,7 +188,7 @@ pub struct MetadataHeader {
 impl MetadataHeader {
     /// Current ABI version. Increment this any time breaking changes are made
     /// to the format of the serialized data.
-    const CURRENT_VERSION: u32 = 1;
+    const CURRENT_VERSION: u32 = 6;
 
     /// Magic number to identify wasmer metadata.
     const MAGIC: [u8; 8] = *b"WASMER\0\0";
@@ -62,6 +62,16 @@ pub enum TrapCode {
 
     /// An atomic memory access was attempted with an unaligned pointer.
     UnalignedAtomic = 11,
+
+    /// The execution was interrupted through an interrupt handle of the
+    /// instance.
+    Interrupt = 12,
+
+    /// The execution ran out of the points given by a metering middleware.
+    OutOfGas = 13,
+
+    /// A memory access hit a watchpoint set by the host.
+    Watchpoint = 14,
 }
 
 impl TrapCode {
@@ -80,6 +90,9 @@ impl TrapCode {
             Self::BadConversionToInteger => "invalid conversion to integer",
             Self::UnreachableCodeReached => "unreachable",
             Self::UnalignedAtomic => "unaligned atomic access",
+            Self::Interrupt => "interrupted",
+            Self::OutOfGas => "out of gas",
+            Self::Watchpoint => "watchpoint hit",
         }
     }
 }
@@ -99,6 +112,9 @@ impl Display for TrapCode {
             Self::BadConversionToInteger => "bad_toint",
             Self::UnreachableCodeReached => "unreachable",
             Self::UnalignedAtomic => "unalign_atom",
+            Self::Interrupt => "interrupt",
+            Self::OutOfGas => "out_of_gas",
+            Self::Watchpoint => "watchpoint",
         };
         f.write_str(identifier)
     }
@@ -121,6 +137,9 @@ impl FromStr for TrapCode {
             "bad_toint" => Ok(Self::BadConversionToInteger),
             "unreachable" => Ok(Self::UnreachableCodeReached),
             "unalign_atom" => Ok(Self::UnalignedAtomic),
+            "interrupt" => Ok(Self::Interrupt),
+            "out_of_gas" => Ok(Self::OutOfGas),
+            "watchpoint" => Ok(Self::Watchpoint),
             _ => Err(()),
         }
     }
@@ -131,7 +150,7 @@ mod tests {
     use super::*;
 
     // Everything but user-defined codes.
-    const CODES: [TrapCode; 12] = [
+    const CODES: [TrapCode; 15] = [
         TrapCode::StackOverflow,
         TrapCode::HeapAccessOutOfBounds,
         TrapCode::HeapMisaligned,
@@ -144,6 +163,9 @@ mod tests {
         TrapCode::BadConversionToInteger,
         TrapCode::UnreachableCodeReached,
         TrapCode::UnalignedAtomic,
+        TrapCode::Interrupt,
+        TrapCode::OutOfGas,
+        TrapCode::Watchpoint,
     ];
 
     #[test]
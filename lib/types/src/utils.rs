@@ -1,4 +1,191 @@
+use crate::error::WasmError;
+use crate::lib::std::string::String;
+use crate::lib::std::string::ToString;
+use crate::lib::std::vec::Vec;
+use core::str;
+use std::collections::BTreeMap;
+
+/// The custom section [`attach_metadata`] stores the metadata of a module
+/// in.
+pub const METADATA_SECTION: &str = "wasmer.metadata";
+
+/// The metadata entry naming the module, in place of its `name` section.
+pub const METADATA_NAME: &str = "name";
+
 /// Check if the provided bytes are wasm-like
 pub fn is_wasm(bytes: impl AsRef<[u8]>) -> bool {
     bytes.as_ref().starts_with(b"\0asm")
 }
+
+/// Removes the custom sections (the name section, DWARF, producers, ...)
+/// of a wasm binary, except the ones whose name `keep` accepts.
+///
+/// Only the framing of the sections is checked; the rest of the binary is
+/// copied as is, to be validated when it's compiled.
+pub fn strip_custom_sections(
+    binary: &[u8],
+    keep: impl Fn(&str) -> bool,
+) -> Result<Vec<u8>, WasmError> {
+    let invalid = |message: &str, offset: usize| WasmError::InvalidWebAssembly {
+        message: message.to_string(),
+        offset,
+    };
+    if binary.len() < 8 || !is_wasm(binary) {
+        return Err(invalid("not a wasm binary", 0));
+    }
+    let mut stripped = binary[..8].to_vec();
+    let mut offset = 8;
+    while offset < binary.len() {
+        let id = binary[offset];
+        let (size, size_len) = read_u32(&binary[offset + 1..])
+            .ok_or_else(|| invalid("invalid section size", offset + 1))?;
+        let start = offset + 1 + size_len;
+        let end = start
+            .checked_add(size as usize)
+            .filter(|end| *end <= binary.len())
+            .ok_or_else(|| invalid("section out of bounds", offset))?;
+        let kept = id != 0 || {
+            let (name_len, name_len_len) = read_u32(&binary[start..end])
+                .ok_or_else(|| invalid("invalid custom section name", start))?;
+            let name_start = start + name_len_len;
+            let name = Some(name_start + name_len as usize)
+                .filter(|name_end| *name_end <= end)
+                .and_then(|name_end| str::from_utf8(&binary[name_start..name_end]).ok())
+                .ok_or_else(|| invalid("invalid custom section name", start))?;
+            keep(name)
+        };
+        if kept {
+            stripped.extend_from_slice(&binary[offset..end]);
+        }
+        offset = end;
+    }
+    Ok(stripped)
+}
+
+/// Attaches key/value metadata, such as the name, version or build id of
+/// the module, to a wasm binary, for the module compiled from it to report
+/// them in its [`ModuleInfo::metadata`](crate::ModuleInfo::metadata). The
+/// metadata replaces the one already attached, if any, and is removed when
+/// empty.
+///
+/// The metadata is stored in the [`METADATA_SECTION`] custom section, as
+/// a vector of key and value names.
+pub fn attach_metadata(
+    binary: &[u8],
+    metadata: &BTreeMap<String, String>,
+) -> Result<Vec<u8>, WasmError> {
+    let mut attached = strip_custom_sections(binary, |name| name != METADATA_SECTION)?;
+    if metadata.is_empty() {
+        return Ok(attached);
+    }
+    let mut contents = Vec::new();
+    write_name(&mut contents, METADATA_SECTION);
+    write_u32(&mut contents, metadata.len() as u32);
+    for (key, value) in metadata {
+        write_name(&mut contents, key);
+        write_name(&mut contents, value);
+    }
+    attached.push(0);
+    write_u32(&mut attached, contents.len() as u32);
+    attached.extend(contents);
+    Ok(attached)
+}
+
+/// Reads the contents of a [`METADATA_SECTION`] custom section, or `None`
+/// if they are malformed.
+pub fn parse_metadata(data: &[u8]) -> Option<BTreeMap<String, String>> {
+    let mut offset = 0;
+    let (len, len_len) = read_u32(data)?;
+    offset += len_len;
+    let mut metadata = BTreeMap::new();
+    for _ in 0..len {
+        let key = read_name(data, &mut offset)?;
+        let value = read_name(data, &mut offset)?;
+        metadata.insert(key, value);
+    }
+    Some(metadata).filter(|_| offset == data.len())
+}
+
+/// Reads a length-prefixed UTF-8 name at `offset`, and moves past it.
+fn read_name(bytes: &[u8], offset: &mut usize) -> Option<String> {
+    let (len, len_len) = read_u32(&bytes[*offset..])?;
+    let start = *offset + len_len;
+    let end = start
+        .checked_add(len as usize)
+        .filter(|end| *end <= bytes.len())?;
+    let name = str::from_utf8(&bytes[start..end]).ok()?;
+    *offset = end;
+    Some(name.to_string())
+}
+
+/// Writes a length-prefixed UTF-8 name.
+fn write_name(bytes: &mut Vec<u8>, name: &str) {
+    write_u32(bytes, name.len() as u32);
+    bytes.extend_from_slice(name.as_bytes());
+}
+
+/// Writes an unsigned LEB128 `u32`.
+fn write_u32(bytes: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Reads an unsigned LEB128 `u32`, returning it with its length.
+fn read_u32(bytes: &[u8]) -> Option<(u32, usize)> {
+    let mut value = 0u32;
+    for (i, byte) in bytes.iter().take(5).enumerate() {
+        value |= u32::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_custom_sections() {
+        // A type section, then the `name` and `version` custom sections
+        let binary = b"\0asm\x01\0\0\0\x01\x01\0\0\x05\x04name\0\x09\x07version1";
+        let stripped = strip_custom_sections(binary, |name| name == "version").unwrap();
+        assert_eq!(stripped, b"\0asm\x01\0\0\0\x01\x01\0\0\x09\x07version1");
+        let stripped = strip_custom_sections(binary, |_| false).unwrap();
+        assert_eq!(stripped, b"\0asm\x01\0\0\0\x01\x01\0");
+        assert!(strip_custom_sections(b"\0asm\x01\0\0\0\0\x09\x04name", |_| false).is_err());
+    }
+
+    #[test]
+    fn attaches_metadata() {
+        let binary = b"\0asm\x01\0\0\0\x01\x01\0";
+        let metadata = [("version", "1.2.0"), ("build-id", "abc")]
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<BTreeMap<_, _>>();
+        let attached = attach_metadata(binary, &metadata).unwrap();
+        assert_eq!(
+            attached,
+            b"\0asm\x01\0\0\0\x01\x01\0\0\x2c\x0fwasmer.metadata\x02\x08build-id\x03abc\x07version\x051.2.0"
+                .as_ref()
+        );
+        let contents = &attached[binary.len() + 2 + 16..];
+        assert_eq!(parse_metadata(contents), Some(metadata.clone()));
+        assert_eq!(parse_metadata(&contents[..contents.len() - 1]), None);
+
+        // Attaching replaces the previous metadata
+        assert_eq!(attach_metadata(&attached, &metadata).unwrap(), attached);
+        assert_eq!(
+            attach_metadata(&attached, &BTreeMap::new()).unwrap(),
+            binary
+        );
+    }
+}
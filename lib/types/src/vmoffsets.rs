@@ -527,8 +527,8 @@ impl VMOffsets {
             .unwrap()
     }
 
-    /// Return the size of the `VMContext` allocation.
-    pub fn size_of_vmctx(&self) -> u32 {
+    /// The offset of the pointer to the interrupt flag of the instance.
+    pub fn vmctx_interrupt(&self) -> u32 {
         self.vmctx_builtin_functions_begin()
             .checked_add(
                 VMBuiltinFunctionIndex::builtin_functions_total_number()
@@ -538,6 +538,13 @@ impl VMOffsets {
             .unwrap()
     }
 
+    /// Return the size of the `VMContext` allocation.
+    pub fn size_of_vmctx(&self) -> u32 {
+        self.vmctx_interrupt()
+            .checked_add(u32::from(self.pointer_size))
+            .unwrap()
+    }
+
     /// Return the offset to `VMSharedSignatureIndex` index `index`.
     pub fn vmctx_vmshared_signature_id(&self, index: SignatureIndex) -> u32 {
         assert_lt!(index.as_u32(), self.num_signature_ids);
@@ -1,6 +1,6 @@
 use crate::{
-    DirEntry, FileDescriptor, FileType, FsError, Metadata, OpenOptions, OpenOptionsConfig, ReadDir,
-    Result, VirtualFile,
+    DirEntry, FileDescriptor, FileOpener as _, FileSystem as _, FileType, FsError, Metadata,
+    OpenOptions, OpenOptionsConfig, ReadDir, Result, VirtualFile,
 };
 #[cfg(feature = "enable-serde")]
 use serde::{de, Deserialize, Serialize};
@@ -11,7 +11,7 @@ use std::io::{self, Read, Seek, Write};
 use std::os::unix::io::{AsRawFd, RawFd};
 #[cfg(windows)]
 use std::os::windows::io::{AsRawHandle, RawHandle};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::debug;
 
@@ -202,6 +202,87 @@ impl crate::FileOpener for FileOpener {
     }
 }
 
+/// A directory of the host as a file system whose root is `/`, such as
+/// the upper layer of an [`overlay_fs::FileSystem`].
+///
+/// [`overlay_fs::FileSystem`]: crate::overlay_fs::FileSystem
+#[derive(Debug, Clone)]
+pub struct DirFileSystem {
+    root: PathBuf,
+}
+
+impl DirFileSystem {
+    /// The file system of the host directory `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The path on the host of `path`, which can't lead out of the
+    /// directory.
+    fn host_path(&self, path: &Path) -> Result<PathBuf> {
+        let mut host_path = self.root.clone();
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => host_path.push(name),
+                Component::RootDir | Component::CurDir => {}
+                Component::ParentDir | Component::Prefix(_) => {
+                    return Err(FsError::PermissionDenied)
+                }
+            }
+        }
+        Ok(host_path)
+    }
+}
+
+impl crate::FileSystem for DirFileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        let data = FileSystem
+            .read_dir(&self.host_path(path)?)?
+            .map(|entry| {
+                entry.map(|entry| DirEntry {
+                    path: path.join(entry.file_name()),
+                    metadata: entry.metadata,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ReadDir::new(data))
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        FileSystem.create_dir(&self.host_path(path)?)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        FileSystem.remove_dir(&self.host_path(path)?)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        FileSystem.rename(&self.host_path(from)?, &self.host_path(to)?)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        FileSystem.remove_file(&self.host_path(path)?)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(self.clone()))
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        FileSystem.metadata(&self.host_path(path)?)
+    }
+}
+
+impl crate::FileOpener for DirFileSystem {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        FileOpener.open(&self.host_path(path)?, conf)
+    }
+}
+
 /// A thin wrapper around `std::fs::File`
 #[derive(Debug)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize))]
@@ -15,6 +15,7 @@ compile_error!("At least the `host-fs` or the `mem-fs` feature must be enabled.
 pub mod host_fs;
 #[cfg(feature = "mem-fs")]
 pub mod mem_fs;
+pub mod overlay_fs;
 
 pub type Result<T> = std::result::Result<T, FsError>;
 
@@ -0,0 +1,239 @@
+//! This module loads tar and zip archives into a [`FileSystem`].
+
+use super::FileSystem;
+use crate::{FileSystem as _, FsError, Result};
+use flate2::read::GzDecoder;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// The first bytes of a gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// The first bytes of a zip archive, and of an empty one.
+const ZIP_MAGICS: [[u8; 4]; 2] = [*b"PK\x03\x04", *b"PK\x05\x06"];
+
+/// The most memory reserved up front for the contents of a file, as the
+/// size an archive gives for it may be a lie.
+const MAX_PREALLOCATED_SIZE: u64 = 1 << 20;
+
+impl FileSystem {
+    /// Creates a file system holding the contents of an archive: a tar
+    /// archive, compressed with gzip or not, or a zip archive, told apart
+    /// by their first bytes.
+    ///
+    /// The files and directories of the archive are loaded in memory.
+    /// Its links and special files are left out, and so are the entries
+    /// whose path would lead out of the archive.
+    pub fn from_archive<R: Read + Seek>(mut archive: R) -> Result<Self> {
+        let start = archive.stream_position()?;
+        let mut magic = [0; 4];
+        let len = read_prefix(&mut archive, &mut magic)?;
+        archive.seek(SeekFrom::Start(start))?;
+
+        let fs = Self::default();
+        if magic[..len].starts_with(&GZIP_MAGIC) {
+            load_tar(&fs, GzDecoder::new(archive))?;
+        } else if ZIP_MAGICS
+            .iter()
+            .any(|zip_magic| magic[..len] == zip_magic[..])
+        {
+            load_zip(&fs, archive)?;
+        } else {
+            load_tar(&fs, archive)?;
+        }
+        Ok(fs)
+    }
+}
+
+/// Reads the start of `reader` into `buf`, up to its end, and returns the
+/// number of bytes read.
+fn read_prefix(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..])? {
+            0 => break,
+            read => len += read,
+        }
+    }
+    Ok(len)
+}
+
+fn load_tar(fs: &FileSystem, archive: impl Read) -> Result<()> {
+    let mut archive = tar::Archive::new(archive);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = match archive_path(&entry.path()?) {
+            Some(path) => path,
+            None => continue,
+        };
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_dir() {
+            create_dirs(fs, &path)?;
+        } else if entry_type.is_file() {
+            let contents = read_contents(&mut entry, entry.size())?;
+            add_file(fs, &path, &contents)?;
+        }
+    }
+    Ok(())
+}
+
+fn load_zip(fs: &FileSystem, archive: impl Read + Seek) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(archive).map_err(zip_error)?;
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index).map_err(zip_error)?;
+        let path = match file.enclosed_name().and_then(archive_path) {
+            Some(path) => path,
+            None => continue,
+        };
+        if file.is_dir() {
+            create_dirs(fs, &path)?;
+        } else if file.is_file() {
+            let size = file.size();
+            let contents = read_contents(&mut file, size)?;
+            add_file(fs, &path, &contents)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads the contents of a file of an archive, which the archive says
+/// are `size` bytes long.
+fn read_contents(file: &mut impl Read, size: u64) -> Result<Vec<u8>> {
+    let mut contents = Vec::with_capacity(size.min(MAX_PREALLOCATED_SIZE) as usize);
+    file.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+fn zip_error(error: zip::result::ZipError) -> FsError {
+    match error {
+        zip::result::ZipError::Io(error) => error.into(),
+        _ => FsError::InvalidData,
+    }
+}
+
+/// The absolute path of an entry of an archive, unless its path is
+/// absolute or goes up with `..`.
+fn archive_path(path: &Path) -> Option<PathBuf> {
+    let mut archive_path = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => archive_path.push(name),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(archive_path)
+}
+
+/// Creates the directory at `path` and its missing parents, as archives
+/// don't always have entries for the directories of their files.
+fn create_dirs(fs: &FileSystem, path: &Path) -> Result<()> {
+    let mut ancestors = path.ancestors().collect::<Vec<_>>();
+    // Without the root
+    ancestors.pop();
+    for dir in ancestors.into_iter().rev() {
+        match fs.metadata(dir) {
+            Ok(metadata) if metadata.is_dir() => {}
+            Ok(_) => return Err(FsError::AlreadyExists),
+            Err(_) => fs.create_dir(dir)?,
+        }
+    }
+    Ok(())
+}
+
+fn add_file(fs: &FileSystem, path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        create_dirs(fs, parent)?;
+    }
+    let mut file = fs
+        .new_open_options()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    file.write_all(contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_archive {
+    use crate::{mem_fs::*, FileSystem as FS};
+    use std::io::{Cursor, Read, Write};
+    use std::path::Path;
+
+    fn read(fs: &FileSystem, path: &str) -> String {
+        let mut contents = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open(path)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        contents
+    }
+
+    fn tar() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut append = |path: &str, contents: &[u8]| {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, contents).unwrap();
+        };
+        append("./assets/hello.txt", b"hello");
+        append("top.txt", b"top");
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_from_tar() {
+        let fs = FileSystem::from_archive(Cursor::new(tar())).unwrap();
+
+        assert!(fs.metadata(Path::new("/assets")).unwrap().is_dir());
+        assert_eq!(read(&fs, "/assets/hello.txt"), "hello");
+        assert_eq!(read(&fs, "/top.txt"), "top");
+    }
+
+    #[test]
+    fn test_from_tar_gz() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(&tar()).unwrap();
+        let tar_gz = encoder.finish().unwrap();
+
+        let fs = FileSystem::from_archive(Cursor::new(tar_gz)).unwrap();
+        assert_eq!(read(&fs, "/assets/hello.txt"), "hello");
+    }
+
+    #[test]
+    fn test_from_tar_lying_about_sizes() {
+        let mut header = tar::Header::new_gnu();
+        header.set_path("huge.txt").unwrap();
+        header.set_size(1 << 50);
+        header.set_mode(0o644);
+        header.set_cksum();
+        let mut tar = header.as_bytes().to_vec();
+        tar.extend_from_slice(&[b'a'; 512]);
+
+        // The archive ends long before the file does.
+        assert!(FileSystem::from_archive(Cursor::new(tar)).is_err());
+    }
+
+    #[test]
+    fn test_from_zip() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::FileOptions::default();
+        writer.add_directory("empty/", options).unwrap();
+        writer.start_file("assets/hello.txt", options).unwrap();
+        writer.write_all(b"hello").unwrap();
+        // Leading out of the archive
+        writer.start_file("../escape.txt", options).unwrap();
+        writer.write_all(b"escape").unwrap();
+        let zip = writer.finish().unwrap().into_inner();
+
+        let fs = FileSystem::from_archive(Cursor::new(zip)).unwrap();
+        assert!(fs.metadata(Path::new("/empty")).unwrap().is_dir());
+        assert_eq!(read(&fs, "/assets/hello.txt"), "hello");
+        assert!(fs.metadata(Path::new("/escape.txt")).is_err());
+    }
+}
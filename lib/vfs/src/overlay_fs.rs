@@ -0,0 +1,435 @@
+//! A union of two file systems: a read-only lower one, such as the
+//! contents of a package or an archive, and a writable upper one, which
+//! receives all the changes.
+//!
+//! The files of the lower file system are copied up to the upper one
+//! when they are opened for writing. Removing an entry of the lower file
+//! system writes a `.wh.<name>` whiteout file next to it in the upper
+//! one, and a directory created over a removed one is made opaque with a
+//! `.wh..wh..opq` file, the conventions of the layers of OCI images.
+//! Both file systems are addressed with the same absolute paths.
+
+use crate::{
+    DirEntry, FileOpener, FsError, Metadata, OpenOptions, OpenOptionsConfig, ReadDir, Result,
+    VirtualFile,
+};
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Starts the name of the files marking the entries removed from the
+/// lower file system.
+const WHITEOUT_PREFIX: &str = ".wh.";
+
+/// Marks the directories hiding the entries of the lower file system.
+const OPAQUE_MARKER: &str = ".wh..wh..opq";
+
+/// The union of a read-only lower file system and a writable upper one.
+#[derive(Debug, Clone)]
+pub struct FileSystem {
+    layers: Arc<Layers>,
+}
+
+#[derive(Debug)]
+struct Layers {
+    lower: Box<dyn crate::FileSystem>,
+    upper: Box<dyn crate::FileSystem>,
+}
+
+impl FileSystem {
+    /// Stacks `upper` over `lower`, which is never modified.
+    pub fn new(lower: Box<dyn crate::FileSystem>, upper: Box<dyn crate::FileSystem>) -> Self {
+        Self {
+            layers: Arc::new(Layers { lower, upper }),
+        }
+    }
+}
+
+/// Whether `name` is the one of a whiteout or opaque marker.
+fn is_marker(name: &OsStr) -> bool {
+    name.to_string_lossy().starts_with(WHITEOUT_PREFIX)
+}
+
+/// The path of the whiteout file of `path`.
+fn whiteout_path(path: &Path) -> Option<PathBuf> {
+    let mut name = OsString::from(WHITEOUT_PREFIX);
+    name.push(path.file_name()?);
+    Some(path.with_file_name(name))
+}
+
+/// Fails for the paths of markers, which guests can't address.
+fn check_name(path: &Path) -> Result<()> {
+    match path.file_name() {
+        Some(name) if is_marker(name) => Err(FsError::PermissionDenied),
+        _ => Ok(()),
+    }
+}
+
+impl Layers {
+    fn in_upper(&self, path: &Path) -> bool {
+        self.upper.symlink_metadata(path).is_ok()
+    }
+
+    /// Whether the lower file system shows through at `path`, which
+    /// neither has a whiteout nor is in an opaque directory.
+    fn lower_visible(&self, path: &Path) -> bool {
+        path.ancestors().all(|ancestor| {
+            let whiteout =
+                whiteout_path(ancestor).map_or(false, |whiteout| self.in_upper(&whiteout));
+            let opaque = ancestor != path && self.in_upper(&ancestor.join(OPAQUE_MARKER));
+            !whiteout && !opaque
+        })
+    }
+
+    fn in_lower(&self, path: &Path) -> bool {
+        self.lower_visible(path) && self.lower.symlink_metadata(path).is_ok()
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        check_name(path).map_err(|_| FsError::EntityNotFound)?;
+        match self.upper.metadata(path) {
+            Ok(metadata) => Ok(metadata),
+            Err(_) if self.lower_visible(path) => self.lower.metadata(path),
+            Err(_) => Err(FsError::EntityNotFound),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        if !self.metadata(path)?.is_dir() {
+            return Err(FsError::BaseNotDirectory);
+        }
+        let mut upper = Vec::new();
+        if self.in_upper(path) {
+            for entry in self.upper.read_dir(path)? {
+                upper.push(entry?);
+            }
+        }
+        let names = upper
+            .iter()
+            .map(|entry| entry.file_name())
+            .collect::<HashSet<_>>();
+
+        let mut entries = BTreeMap::new();
+        if self.in_lower(path) && !names.contains(OsStr::new(OPAQUE_MARKER)) {
+            for entry in self.lower.read_dir(path)? {
+                let entry = entry?;
+                let mut whiteout = OsString::from(WHITEOUT_PREFIX);
+                whiteout.push(entry.file_name());
+                if !names.contains(&whiteout) {
+                    entries.insert(entry.file_name(), entry.metadata);
+                }
+            }
+        }
+        for entry in upper {
+            entries.insert(entry.file_name(), entry.metadata);
+        }
+
+        Ok(ReadDir::new(
+            entries
+                .into_iter()
+                .filter(|(name, _)| !is_marker(name))
+                .map(|(name, metadata)| DirEntry {
+                    path: path.join(name),
+                    metadata,
+                })
+                .collect(),
+        ))
+    }
+
+    /// Creates the directories of the upper file system leading to `dir`,
+    /// and `dir` itself, where they are only in the lower one.
+    fn copy_up_dirs(&self, dir: &Path) -> Result<()> {
+        let missing = dir
+            .ancestors()
+            .take_while(|ancestor| !self.in_upper(ancestor))
+            .collect::<Vec<_>>();
+        for dir in missing.into_iter().rev() {
+            if !self.metadata(dir)?.is_dir() {
+                return Err(FsError::BaseNotDirectory);
+            }
+            self.upper.create_dir(dir)?;
+        }
+        Ok(())
+    }
+
+    /// Copies the file at `path` from the lower file system to the upper
+    /// one.
+    fn copy_up_file(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            self.copy_up_dirs(parent)?;
+        }
+        let mut source = self.lower.new_open_options().read(true).open(path)?;
+        let mut target = self
+            .upper
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        io::copy(&mut source, &mut target)?;
+        Ok(())
+    }
+
+    /// Copies the entries of the directory at `path` that are only in the
+    /// lower file system to the upper one, recursively.
+    fn copy_up_tree(&self, path: &Path) -> Result<()> {
+        self.copy_up_dirs(path)?;
+        for entry in self.read_dir(path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                self.copy_up_tree(&entry.path)?;
+            } else if !self.in_upper(&entry.path) {
+                self.copy_up_file(&entry.path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Hides the entry of the lower file system at `path`, if any.
+    fn whiteout(&self, path: &Path) -> Result<()> {
+        if !self.in_lower(path) {
+            return Ok(());
+        }
+        if let (Some(parent), Some(whiteout)) = (path.parent(), whiteout_path(path)) {
+            self.copy_up_dirs(parent)?;
+            self.upper
+                .new_open_options()
+                .write(true)
+                .create(true)
+                .open(whiteout)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the whiteout of `path`, and returns whether there was one.
+    fn remove_whiteout(&self, path: &Path) -> bool {
+        whiteout_path(path).map_or(false, |whiteout| self.upper.remove_file(&whiteout).is_ok())
+    }
+
+    /// Makes the directory at `path` of the upper file system hide the
+    /// one of the lower file system.
+    fn make_opaque(&self, path: &Path) -> Result<()> {
+        self.upper
+            .new_open_options()
+            .write(true)
+            .create(true)
+            .open(path.join(OPAQUE_MARKER))?;
+        Ok(())
+    }
+}
+
+impl crate::FileSystem for FileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        self.layers.read_dir(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        check_name(path)?;
+        if self.layers.metadata(path).is_ok() {
+            return Err(FsError::AlreadyExists);
+        }
+        if let Some(parent) = path.parent() {
+            self.layers.copy_up_dirs(parent)?;
+        }
+        let removed = self.layers.remove_whiteout(path);
+        self.layers.upper.create_dir(path)?;
+        if removed {
+            self.layers.make_opaque(path)?;
+        }
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        if !self.layers.metadata(path)?.is_dir() {
+            return Err(FsError::BaseNotDirectory);
+        }
+        if self.layers.read_dir(path)?.next().is_some() {
+            return Err(FsError::DirectoryNotEmpty);
+        }
+        if self.layers.in_upper(path) {
+            // Only markers are left in it
+            for entry in self.layers.upper.read_dir(path)? {
+                self.layers.upper.remove_file(&entry?.path)?;
+            }
+            self.layers.upper.remove_dir(path)?;
+        }
+        self.layers.whiteout(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        check_name(to)?;
+        let is_dir = self.layers.metadata(from)?.is_dir();
+        if is_dir {
+            self.layers.copy_up_tree(from)?;
+        } else if !self.layers.in_upper(from) {
+            self.layers.copy_up_file(from)?;
+        }
+        if let Some(parent) = to.parent() {
+            self.layers.copy_up_dirs(parent)?;
+        }
+        self.layers.remove_whiteout(to);
+        self.layers.upper.rename(from, to)?;
+        if is_dir && self.layers.in_lower(to) {
+            self.layers.make_opaque(to)?;
+        }
+        self.layers.whiteout(from)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.layers.metadata(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        if self.layers.metadata(path)?.is_dir() {
+            return Err(FsError::NotAFile);
+        }
+        if self.layers.in_upper(path) {
+            self.layers.upper.remove_file(path)?;
+        }
+        self.layers.whiteout(path)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(OverlayFileOpener {
+            layers: self.layers.clone(),
+        }))
+    }
+}
+
+/// Opens the files of an overlay [`FileSystem`].
+struct OverlayFileOpener {
+    layers: Arc<Layers>,
+}
+
+impl FileOpener for OverlayFileOpener {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        check_name(path)?;
+        let layers = &self.layers;
+        let writes =
+            conf.write() || conf.append() || conf.truncate() || conf.create() || conf.create_new();
+        if !writes {
+            let layer = if layers.in_upper(path) {
+                &layers.upper
+            } else if layers.in_lower(path) {
+                &layers.lower
+            } else {
+                return Err(FsError::EntityNotFound);
+            };
+            return layer.new_open_options().options(conf.clone()).open(path);
+        }
+
+        if layers.in_lower(path) && !layers.in_upper(path) {
+            if conf.create_new() {
+                return Err(FsError::AlreadyExists);
+            }
+            layers.copy_up_file(path)?;
+        } else if !layers.in_upper(path) {
+            if let Some(parent) = path.parent() {
+                layers.copy_up_dirs(parent)?;
+            }
+            layers.remove_whiteout(path);
+        }
+        layers
+            .upper
+            .new_open_options()
+            .options(conf.clone())
+            .open(path)
+    }
+}
+
+#[cfg(all(test, feature = "mem-fs"))]
+mod test_overlay_fs {
+    use super::*;
+    use crate::{mem_fs, FileSystem as FS};
+    use std::io::{Read, Write};
+
+    fn write(fs: &dyn FS, path: &str, contents: &str) {
+        fs.new_open_options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .unwrap()
+            .write_all(contents.as_bytes())
+            .unwrap();
+    }
+
+    fn read(fs: &dyn FS, path: &str) -> Result<String> {
+        let mut contents = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open(path)?
+            .read_to_string(&mut contents)
+            .unwrap();
+        Ok(contents)
+    }
+
+    fn names(fs: &dyn FS, path: &str) -> Vec<String> {
+        fs.read_dir(Path::new(path))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn layers() -> (mem_fs::FileSystem, mem_fs::FileSystem, FileSystem) {
+        let lower = mem_fs::FileSystem::default();
+        lower.create_dir(Path::new("/etc")).unwrap();
+        write(&lower, "/etc/config", "base");
+        write(&lower, "/etc/hosts", "localhost");
+        let upper = mem_fs::FileSystem::default();
+        let overlay = FileSystem::new(Box::new(lower.clone()), Box::new(upper.clone()));
+        (lower, upper, overlay)
+    }
+
+    #[test]
+    fn test_writes_go_to_the_upper_layer() {
+        let (lower, upper, overlay) = layers();
+
+        write(&overlay, "/etc/config", "changed");
+        write(&overlay, "/etc/new", "new");
+        assert_eq!(read(&overlay, "/etc/config").unwrap(), "changed");
+        assert_eq!(read(&lower, "/etc/config").unwrap(), "base");
+        assert_eq!(read(&upper, "/etc/new").unwrap(), "new");
+        assert_eq!(names(&overlay, "/etc"), ["config", "hosts", "new"]);
+    }
+
+    #[test]
+    fn test_removals_are_whiteouts() {
+        let (lower, upper, overlay) = layers();
+
+        overlay.remove_file(Path::new("/etc/hosts")).unwrap();
+        assert_eq!(read(&overlay, "/etc/hosts"), Err(FsError::EntityNotFound));
+        assert_eq!(read(&lower, "/etc/hosts").unwrap(), "localhost");
+        assert!(upper.metadata(Path::new("/etc/.wh.hosts")).is_ok());
+        assert_eq!(names(&overlay, "/etc"), ["config"]);
+
+        // Recreating a removed directory doesn't bring its entries back
+        overlay.remove_file(Path::new("/etc/config")).unwrap();
+        overlay.remove_dir(Path::new("/etc")).unwrap();
+        assert!(overlay.metadata(Path::new("/etc")).is_err());
+        overlay.create_dir(Path::new("/etc")).unwrap();
+        assert!(names(&overlay, "/etc").is_empty());
+        assert_eq!(
+            overlay.create_dir(Path::new("/.wh.etc")),
+            Err(FsError::PermissionDenied)
+        );
+    }
+
+    #[test]
+    fn test_renames_copy_up() {
+        let (lower, _, overlay) = layers();
+
+        overlay
+            .rename(Path::new("/etc"), Path::new("/config"))
+            .unwrap();
+        assert_eq!(read(&overlay, "/config/hosts").unwrap(), "localhost");
+        assert!(overlay.metadata(Path::new("/etc")).is_err());
+        assert!(lower.metadata(Path::new("/etc/hosts")).is_ok());
+    }
+}
@@ -0,0 +1,156 @@
+//! Breakpoints and single-stepping in the interpreted functions of an
+//! instance.
+//!
+//! The interpreter checks, before every instruction of an instance that
+//! has an [`InstanceDebugger`], whether it has to stop there. Stopping
+//! calls the handler of the debugger with a [`DebugStop`], from which it
+//! reads the locals and the operand stack of the function, and the
+//! execution resumes once the handler returns. The handler can change
+//! the breakpoints, but the instance doesn't stop while it runs.
+
+use crate::interpreter::to_raw;
+use std::collections::HashSet;
+use wasmer_types::{InterpretedFunction, RawValue, Type};
+
+/// Why the interpreter stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The instruction about to run has a breakpoint.
+    Breakpoint,
+    /// The previous stop asked for a single step.
+    Step,
+}
+
+/// How the execution resumes after a stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugAction {
+    /// Run until the next breakpoint.
+    Continue,
+    /// Stop again before the next instruction of the instance.
+    Step,
+}
+
+/// The handler called on every stop of an [`InstanceDebugger`].
+pub type DebugHandler = dyn FnMut(&DebugStop<'_>) -> DebugAction + Send;
+
+/// The breakpoints of an instance, and what to do when they are hit.
+pub struct InstanceDebugger {
+    /// The addresses of the instructions to stop before.
+    breakpoints: HashSet<usize>,
+    /// Whether to stop before the next instruction.
+    stepping: bool,
+    /// The handler, taken out while it runs.
+    handler: Option<Box<DebugHandler>>,
+}
+
+impl InstanceDebugger {
+    /// Creates a debugger without breakpoints, calling `handler` on
+    /// every stop.
+    pub fn new(handler: Box<DebugHandler>) -> Self {
+        Self {
+            breakpoints: HashSet::new(),
+            stepping: false,
+            handler: Some(handler),
+        }
+    }
+
+    /// Stops before the instruction at `address`, as returned by
+    /// [`InstanceHandle::instruction_address`]. Returns whether the
+    /// breakpoint is new.
+    ///
+    /// [`InstanceHandle::instruction_address`]: crate::InstanceHandle::instruction_address
+    pub fn add_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.insert(address)
+    }
+
+    /// Removes the breakpoint at `address`, returning whether there was
+    /// one.
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// Stops before the next instruction of the instance that runs.
+    pub fn step(&mut self) {
+        self.stepping = true;
+    }
+
+    /// Whether the interpreter stops before the instruction at `address`,
+    /// which it doesn't while the handler runs.
+    pub(crate) fn stop_reason(&self, address: usize) -> Option<StopReason> {
+        if self.is_stopped() {
+            None
+        } else if self.stepping {
+            Some(StopReason::Step)
+        } else if self.breakpoints.contains(&address) {
+            Some(StopReason::Breakpoint)
+        } else {
+            None
+        }
+    }
+
+    /// Takes the handler out for a stop, so that the debugger stays in
+    /// the instance while it runs.
+    pub(crate) fn take_handler(&mut self) -> Box<DebugHandler> {
+        self.stepping = false;
+        self.handler.take().unwrap()
+    }
+
+    /// Whether the handler is out for a stop.
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.handler.is_none()
+    }
+
+    /// Puts the handler back after a stop, and prepares the next one.
+    pub(crate) fn resume(&mut self, handler: Box<DebugHandler>, action: DebugAction) {
+        self.handler = Some(handler);
+        self.stepping = action == DebugAction::Step;
+    }
+}
+
+/// An interpreted function stopped before one of its instructions.
+pub struct DebugStop<'a> {
+    pub(crate) reason: StopReason,
+    pub(crate) function: &'a InterpretedFunction,
+    pub(crate) pc: usize,
+    /// The parameters and locals of the function, then its operands.
+    pub(crate) values: &'a [u64],
+}
+
+impl DebugStop<'_> {
+    /// Why the interpreter stopped.
+    pub fn reason(&self) -> StopReason {
+        self.reason
+    }
+
+    /// The address of the instruction about to run, which the frame
+    /// information of the module maps back to the function and offset
+    /// it was translated from.
+    pub fn address(&self) -> usize {
+        self.function.code[self.pc..].as_ptr() as usize
+    }
+
+    /// The types of the parameters of the function, followed by the ones
+    /// of its locals.
+    pub fn local_types(&self) -> impl Iterator<Item = Type> + '_ {
+        self.function
+            .params
+            .iter()
+            .chain(self.function.locals.iter())
+            .copied()
+    }
+
+    /// The value of the local `index`, the parameters coming first.
+    pub fn local(&self, index: u32) -> Option<(Type, RawValue)> {
+        let ty = self.local_types().nth(index as usize)?;
+        Some((ty, to_raw(self.values[index as usize], ty)))
+    }
+
+    /// The operand stack of the function, from the bottom up.
+    ///
+    /// The operands are untyped: each one is a 64-bit slot, holding the
+    /// bits of a number zero-extended, or the address of a reference.
+    pub fn operands(&self) -> &[u64] {
+        let num_locals = self.function.params.len() + self.function.locals.len();
+        &self.values[num_locals..]
+    }
+}
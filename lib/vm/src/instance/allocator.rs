@@ -1,4 +1,6 @@
 use super::{Instance, InstanceHandle};
+use crate::mmap::Mmap;
+use crate::pool::InstancePool;
 use crate::vmcontext::{VMMemoryDefinition, VMTableDefinition};
 use std::alloc::{self, Layout};
 use std::convert::TryFrom;
@@ -41,6 +43,10 @@ pub struct InstanceAllocator {
     /// `instance_ptr` buffer. If it has not when being dropped,
     /// the buffer should be freed.
     consumed: bool,
+
+    /// The pool slot backing the `instance_ptr` buffer, if it has been
+    /// claimed from an [`InstancePool`] rather than allocated.
+    vmctx_slot: Option<(InstancePool, Mmap)>,
 }
 
 impl Drop for InstanceAllocator {
@@ -50,8 +56,11 @@ impl Drop for InstanceAllocator {
             // over the buffer and must free it.
             let instance_ptr = self.instance_ptr.as_ptr();
 
-            unsafe {
-                std::alloc::dealloc(instance_ptr as *mut u8, self.instance_layout);
+            match self.vmctx_slot.take() {
+                Some((pool, slot)) => pool.release_vmctx(slot, self.instance_layout.size()),
+                None => unsafe {
+                    std::alloc::dealloc(instance_ptr as *mut u8, self.instance_layout);
+                },
             }
         }
     }
@@ -86,16 +95,72 @@ impl InstanceAllocator {
             alloc::handle_alloc_error(instance_layout);
         };
 
+        Self::from_buffer(instance_ptr, instance_layout, offsets, None)
+    }
+
+    /// Claims instance data for use with [`InstanceHandle::new`] from the
+    /// given pool.
+    ///
+    /// This is like [`InstanceAllocator::new`], except that the buffer is
+    /// a slot of the pool, which is recycled when the instance is dropped.
+    /// The buffer is allocated outside of the pool if the instance doesn't
+    /// fit in its slots.
+    ///
+    /// [`InstanceHandle::new`]: super::InstanceHandle::new
+    #[allow(clippy::type_complexity)]
+    pub fn new_pooled(
+        module: &ModuleInfo,
+        pool: &InstancePool,
+    ) -> Result<
+        (
+            Self,
+            Vec<NonNull<VMMemoryDefinition>>,
+            Vec<NonNull<VMTableDefinition>>,
+        ),
+        String,
+    > {
+        let offsets = VMOffsets::new(mem::size_of::<usize>() as u8, module);
+        let instance_layout = Self::instance_layout(&offsets);
+        if instance_layout.align() > region::page::size() {
+            return Ok(Self::new(module));
+        }
+
+        match pool.claim_vmctx(instance_layout.size())? {
+            Some(mut slot) => {
+                #[allow(clippy::cast_ptr_alignment)]
+                let instance_ptr = NonNull::new(slot.as_mut_ptr() as *mut Instance).unwrap();
+                Ok(Self::from_buffer(
+                    instance_ptr,
+                    instance_layout,
+                    offsets,
+                    Some((pool.clone(), slot)),
+                ))
+            }
+            None => Ok(Self::new(module)),
+        }
+    }
+
+    fn from_buffer(
+        instance_ptr: NonNull<Instance>,
+        instance_layout: Layout,
+        offsets: VMOffsets,
+        vmctx_slot: Option<(InstancePool, Mmap)>,
+    ) -> (
+        Self,
+        Vec<NonNull<VMMemoryDefinition>>,
+        Vec<NonNull<VMTableDefinition>>,
+    ) {
         let allocator = Self {
             instance_ptr,
             instance_layout,
             offsets,
             consumed: false,
+            vmctx_slot,
         };
 
         // # Safety
-        // Both of these calls are safe because we allocate the pointer
-        // above with the same `offsets` that these functions use.
+        // Both of these calls are safe because the buffer has been
+        // allocated with the same `offsets` that these functions use.
         // Thus there will be enough valid memory for both of them.
         let memories = unsafe { allocator.memory_definition_locations() };
         let tables = unsafe { allocator.table_definition_locations() };
@@ -201,12 +266,14 @@ impl InstanceAllocator {
         }
         let instance = self.instance_ptr;
         let instance_layout = self.instance_layout;
+        let vmctx_slot = self.vmctx_slot.take();
 
         // This is correct because of the invariants of `Self` and
         // because we write `Instance` to the pointer in this function.
         InstanceHandle {
             instance,
             instance_layout,
+            vmctx_slot,
         }
     }
 
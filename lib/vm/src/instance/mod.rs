@@ -8,8 +8,14 @@
 
 mod allocator;
 
+use crate::debugger::InstanceDebugger;
 use crate::export::VMExtern;
 use crate::imports::Imports;
+use crate::interpreter::is_interpreted;
+use crate::interrupt::InterruptHandle;
+use crate::memory_image::MemoryImage;
+use crate::mmap::Mmap;
+use crate::pool::InstancePool;
 use crate::store::{InternalStoreHandle, StoreObjects};
 use crate::table::TableElement;
 use crate::trap::{catch_traps, Trap, TrapCode};
@@ -32,12 +38,14 @@ use std::fmt;
 use std::mem;
 use std::ptr::{self, NonNull};
 use std::slice;
+use std::sync::atomic::AtomicU32;
 use std::sync::Arc;
 use wasmer_types::entity::{packed_option::ReservedValue, BoxedSlice, EntityRef, PrimaryMap};
 use wasmer_types::{
     DataIndex, DataInitializer, ElemIndex, ExportIndex, FunctionIndex, GlobalIndex, GlobalInit,
-    LocalFunctionIndex, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryError,
-    MemoryIndex, ModuleInfo, Pages, SignatureIndex, TableIndex, TableInitializer, VMOffsets,
+    InterpretedFunction, LocalFunctionIndex, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex,
+    MemoryError, MemoryIndex, ModuleInfo, Pages, SignatureIndex, TableIndex, TableInitializer,
+    VMOffsets,
 };
 
 /// A WebAssembly instance.
@@ -88,6 +96,17 @@ pub(crate) struct Instance {
     /// will point to elements here for functions imported by this instance.
     imported_funcrefs: BoxedSlice<FunctionIndex, NonNull<VMCallerCheckedAnyfunc>>,
 
+    /// The flag the compiled code checks to stop running, pointed to
+    /// from the `vmctx`.
+    interrupt: InterruptHandle,
+
+    /// The debugger stopping the interpreted functions of the instance.
+    pub(crate) debugger: Option<Box<InstanceDebugger>>,
+
+    /// Whether the start function was invoked, so that it runs at most
+    /// once even when its invocation is deferred.
+    started: bool,
+
     /// Additional context used by compiled WebAssembly code. This
     /// field is last, and represents a dynamically-sized array that
     /// extends beyond the nominal end of the struct (similar to a
@@ -206,8 +225,13 @@ impl Instance {
         unsafe { self.vmctx_plus_offset(self.offsets.vmctx_tables_begin()) }
     }
 
+    /// Return the shared signature index of a signature of the module.
+    pub(crate) fn signature_id(&self, index: SignatureIndex) -> VMSharedSignatureIndex {
+        unsafe { *self.signature_ids_ptr().add(index.index()) }
+    }
+
     /// Get a locally defined or imported memory.
-    fn get_memory(&self, index: MemoryIndex) -> VMMemoryDefinition {
+    pub(crate) fn get_memory(&self, index: MemoryIndex) -> VMMemoryDefinition {
         if let Some(local_index) = self.module.local_memory_index(index) {
             self.memory(local_index)
         } else {
@@ -260,6 +284,15 @@ impl Instance {
         NonNull::new(unsafe { *self.globals_ptr().add(index) }).unwrap()
     }
 
+    /// Return a pointer to a locally defined or imported global.
+    pub(crate) fn get_global_ptr(&self, index: GlobalIndex) -> NonNull<VMGlobalDefinition> {
+        if let Some(local_index) = self.module.local_global_index(index) {
+            self.global_ptr(local_index)
+        } else {
+            self.imported_global(index).definition
+        }
+    }
+
     /// Return a pointer to the `VMGlobalDefinition`s.
     fn globals_ptr(&self) -> *mut *mut VMGlobalDefinition {
         unsafe { self.vmctx_plus_offset(self.offsets.vmctx_globals_begin()) }
@@ -270,6 +303,17 @@ impl Instance {
         unsafe { self.vmctx_plus_offset(self.offsets.vmctx_builtin_functions_begin()) }
     }
 
+    /// Return a pointer to the pointer to the interrupt flag.
+    fn interrupt_ptr(&self) -> *mut *const AtomicU32 {
+        unsafe { self.vmctx_plus_offset(self.offsets.vmctx_interrupt()) }
+    }
+
+    /// Returns `true` if the Wasm code of this instance has been
+    /// interrupted.
+    pub(crate) fn interrupted(&self) -> bool {
+        self.interrupt.is_interrupted()
+    }
+
     /// Return a reference to the vmctx used by compiled wasm code.
     fn vmctx(&self) -> &VMContext {
         &self.vmctx
@@ -280,16 +324,36 @@ impl Instance {
         self.vmctx() as *const VMContext as *mut VMContext
     }
 
-    /// Invoke the WebAssembly start function of the instance, if one is present.
+    /// Invoke the WebAssembly start function of the instance, if one is
+    /// present and wasn't invoked yet.
     fn invoke_start_function(
-        &self,
+        &mut self,
         trap_handler: Option<*const TrapHandlerFn<'static>>,
+        stack_size: usize,
     ) -> Result<(), Trap> {
+        if mem::replace(&mut self.started, true) {
+            return Ok(());
+        }
         let start_index = match self.module.start_function {
             Some(idx) => idx,
             None => return Ok(()),
         };
 
+        // Interpreted functions aren't machine code: they are run by their
+        // call trampoline.
+        let anyfunc = unsafe { self.func_ref(start_index).unwrap().0.as_ref() };
+        if is_interpreted(anyfunc) {
+            return unsafe {
+                catch_traps(trap_handler, stack_size, || {
+                    (anyfunc.call_trampoline)(
+                        anyfunc.vmctx.vmctx,
+                        anyfunc.func_ptr,
+                        ptr::null_mut(),
+                    )
+                })
+            };
+        }
+
         let (callee_address, callee_vmctx) = match self.module.local_func_index(start_index) {
             Some(local_index) => {
                 let body = self
@@ -313,7 +377,7 @@ impl Instance {
 
         // Make the call.
         unsafe {
-            catch_traps(trap_handler, || {
+            catch_traps(trap_handler, stack_size, || {
                 mem::transmute::<*const VMFunctionBody, unsafe extern "C" fn(VMFunctionContext)>(
                     callee_address,
                 )(callee_vmctx)
@@ -367,7 +431,7 @@ impl Instance {
             .memories
             .get(memory_index)
             .unwrap_or_else(|| panic!("no memory for index {}", memory_index.index()));
-        mem.get_mut(self.context_mut()).grow(delta.into())
+        self.context_mut().grow_memory(mem, delta.into())
     }
 
     /// Grow imported memory by the specified amount of pages.
@@ -388,7 +452,7 @@ impl Instance {
     {
         let import = self.imported_memory(memory_index);
         let mem = import.handle;
-        mem.get_mut(self.context_mut()).grow(delta.into())
+        self.context_mut().grow_memory(mem, delta.into())
     }
 
     /// Returns the number of allocated wasm pages.
@@ -444,7 +508,7 @@ impl Instance {
             .tables
             .get(table_index)
             .unwrap_or_else(|| panic!("no table for index {}", table_index.index()));
-        table.get_mut(self.context_mut()).grow(delta, init_value)
+        self.context_mut().grow_table(table, delta, init_value)
     }
 
     /// Grow table by the specified amount of elements.
@@ -459,7 +523,7 @@ impl Instance {
     ) -> Option<u32> {
         let import = self.imported_table(table_index);
         let table = import.handle;
-        table.get_mut(self.context_mut()).grow(delta, init_value)
+        self.context_mut().grow_table(table, delta, init_value)
     }
 
     /// Get table element by index.
@@ -773,7 +837,16 @@ impl Instance {
 ///
 /// This is more or less a public facade of the private `Instance`,
 /// providing useful higher-level API.
-#[derive(Debug, Clone, Eq, PartialEq)]
+///
+/// The handle owns the `Instance`: dropping it drops the `Instance` and
+/// frees its `VMContext`, or hands its slot back to the [`InstancePool`]
+/// it was claimed from, where the next instance reuses it. The handle
+/// isn't `Clone` for that reason, and it must outlive every pointer to
+/// its `VMContext`, such as the functions it exports, which can be
+/// imported by other instances or stored in their tables. The
+/// `StoreObjects` the instance is created in keep the handles of all its
+/// instances until they are dropped together, so that those pointers
+/// never outlive them.
 pub struct InstanceHandle {
     /// The layout of `Instance` (which can vary).
     instance_layout: Layout,
@@ -788,6 +861,24 @@ pub struct InstanceHandle {
     /// No one in the code has a copy of the `Instance`'s
     /// pointer. `Self` is the only one.
     instance: NonNull<Instance>,
+
+    /// The pool slot backing the `Instance`, if it was claimed from
+    /// an [`InstancePool`] rather than allocated.
+    vmctx_slot: Option<(InstancePool, Mmap)>,
+}
+
+impl Drop for InstanceHandle {
+    fn drop(&mut self) {
+        let instance_ptr = self.instance.as_ptr();
+
+        unsafe {
+            instance_ptr.drop_in_place();
+            match self.vmctx_slot.take() {
+                Some((pool, slot)) => pool.release_vmctx(slot, self.instance_layout.size()),
+                None => std::alloc::dealloc(instance_ptr as *mut u8, self.instance_layout),
+            }
+        }
+    }
 }
 
 impl InstanceHandle {
@@ -858,6 +949,9 @@ impl InstanceHandle {
                 passive_data,
                 funcrefs,
                 imported_funcrefs,
+                interrupt: InterruptHandle::default(),
+                debugger: None,
+                started: false,
                 vmctx: VMContext {},
             };
 
@@ -919,6 +1013,7 @@ impl InstanceHandle {
             instance.builtin_functions_ptr() as *mut VMBuiltinFunctionsArray,
             VMBuiltinFunctionsArray::initialized(),
         );
+        ptr::write(instance.interrupt_ptr(), instance.interrupt.flag_ptr());
 
         // Perform infallible initialization in this constructor, while fallible
         // initialization is deferred to the `initialize` method.
@@ -928,6 +1023,45 @@ impl InstanceHandle {
         Ok(handle)
     }
 
+    /// Return a handle interrupting the Wasm code of this instance.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.instance().interrupt.clone()
+    }
+
+    /// Set the debugger stopping the interpreted functions of this
+    /// instance, replacing the previous one.
+    pub fn set_debugger(&mut self, debugger: Option<InstanceDebugger>) {
+        self.instance_mut().debugger = debugger.map(Box::new);
+    }
+
+    /// Return the debugger of this instance, if any.
+    pub fn debugger_mut(&mut self) -> Option<&mut InstanceDebugger> {
+        self.instance_mut().debugger.as_deref_mut()
+    }
+
+    /// Return the address of the instruction of the local function
+    /// `index` translated from the operator `offset` bytes into its body,
+    /// or from the first operator after it.
+    ///
+    /// Returns `None` if the function is imported or isn't interpreted,
+    /// or if `offset` is past its end.
+    pub fn instruction_address(&self, index: FunctionIndex, offset: u32) -> Option<usize> {
+        let instance = self.instance();
+        let local_index = instance.module.local_func_index(index)?;
+        let anyfunc = &instance.funcrefs[local_index];
+        if !is_interpreted(anyfunc) {
+            return None;
+        }
+        let function = unsafe { &*(anyfunc.func_ptr as *const InterpretedFunction) };
+        let srcloc = u64::from(function.address_map.start_srcloc.bits()) + u64::from(offset);
+        let pc = function
+            .address_map
+            .instructions
+            .iter()
+            .position(|instruction| u64::from(instruction.srcloc.bits()) >= srcloc)?;
+        Some(function.code[pc..].as_ptr() as usize)
+    }
+
     /// Return a reference to the contained `Instance`.
     pub(crate) fn instance(&self) -> &Instance {
         unsafe { self.instance.as_ref() }
@@ -940,26 +1074,78 @@ impl InstanceHandle {
 
     /// Finishes the instantiation process started by `Instance::new`.
     ///
+    /// The start function runs on a stack of `stack_size` bytes, unless
+    /// `invoke_start` is false, in which case it is left for
+    /// [`InstanceHandle::invoke_start_function`].
+    ///
     /// # Safety
     ///
     /// Only safe to call immediately after instantiation.
     pub unsafe fn finish_instantiation(
         &mut self,
         trap_handler: Option<*const TrapHandlerFn<'static>>,
+        stack_size: usize,
+        data_initializers: &[DataInitializer<'_>],
+        invoke_start: bool,
+    ) -> Result<(), Trap> {
+        self.finish_instantiation_with_images(
+            trap_handler,
+            stack_size,
+            &PrimaryMap::new(),
+            data_initializers,
+            invoke_start,
+        )
+    }
+
+    /// Finishes the instantiation process started by `Instance::new`,
+    /// mapping `memory_images` over the local memories they are given for
+    /// instead of copying their data initializers.
+    ///
+    /// The data initializers of a memory that gets an image are skipped, so
+    /// the image must hold exactly their content. Memories that can't map
+    /// images are initialized by copying as usual.
+    ///
+    /// # Safety
+    ///
+    /// Only safe to call immediately after instantiation.
+    pub unsafe fn finish_instantiation_with_images(
+        &mut self,
+        trap_handler: Option<*const TrapHandlerFn<'static>>,
+        stack_size: usize,
+        memory_images: &PrimaryMap<LocalMemoryIndex, Option<MemoryImage>>,
         data_initializers: &[DataInitializer<'_>],
+        invoke_start: bool,
     ) -> Result<(), Trap> {
         let instance = self.instance_mut();
 
         // Apply the initializers.
         initialize_tables(instance)?;
-        initialize_memories(instance, data_initializers)?;
+        initialize_memories(instance, memory_images, data_initializers)?;
 
         // The WebAssembly spec specifies that the start function is
         // invoked automatically at instantiation time.
-        instance.invoke_start_function(trap_handler)?;
+        if invoke_start {
+            instance.invoke_start_function(trap_handler, stack_size)?;
+        }
         Ok(())
     }
 
+    /// Invokes the start function of the instance on a stack of
+    /// `stack_size` bytes, if it has one that wasn't invoked yet.
+    ///
+    /// # Safety
+    ///
+    /// The instantiation must have been finished by
+    /// [`InstanceHandle::finish_instantiation`].
+    pub unsafe fn invoke_start_function(
+        &mut self,
+        trap_handler: Option<*const TrapHandlerFn<'static>>,
+        stack_size: usize,
+    ) -> Result<(), Trap> {
+        self.instance_mut()
+            .invoke_start_function(trap_handler, stack_size)
+    }
+
     /// Return a reference to the vmctx used by compiled wasm code.
     pub fn vmctx(&self) -> &VMContext {
         self.instance().vmctx()
@@ -987,6 +1173,16 @@ impl InstanceHandle {
         self.instance().module_ref()
     }
 
+    /// Return pointers to the `VMMemoryDefinition`s of the memories
+    /// defined by this instance, which stay valid as long as the
+    /// instance is alive.
+    pub fn local_memory_definitions(&self) -> Vec<NonNull<VMMemoryDefinition>> {
+        let instance = self.instance();
+        (0..instance.memories.len())
+            .map(|index| instance.memory_ptr(LocalMemoryIndex::new(index)))
+            .collect()
+    }
+
     /// Lookup an export with the given name.
     pub fn lookup(&mut self, field: &str) -> Option<VMExtern> {
         let export = *self.module_ref().exports.get(field)?;
@@ -1126,6 +1322,26 @@ impl InstanceHandle {
     pub fn get_local_table(&mut self, index: LocalTableIndex) -> &mut VMTable {
         self.instance_mut().get_local_table(index)
     }
+
+    /// Get a `VMFuncRef` for the given `FunctionIndex`.
+    pub fn func_ref(&self, function_index: FunctionIndex) -> Option<VMFuncRef> {
+        self.instance().func_ref(function_index)
+    }
+
+    /// Maps the `VMFuncRef`s of the functions of this instance to their
+    /// index, to find out which function a table element points to.
+    ///
+    /// A function imported several times maps to its first index.
+    pub fn func_indices(&self) -> HashMap<VMFuncRef, FunctionIndex> {
+        let instance = self.instance();
+        let mut indices = HashMap::with_capacity(instance.module.functions.len());
+        for index in instance.module.functions.keys() {
+            if let Some(func_ref) = instance.func_ref(index) {
+                indices.entry(func_ref).or_insert(index);
+            }
+        }
+        indices
+    }
 }
 
 /// Compute the offset for a memory data initializer.
@@ -1239,9 +1455,27 @@ fn initialize_passive_elements(instance: &Instance) {
 /// Initialize the table memory from the provided initializers.
 fn initialize_memories(
     instance: &mut Instance,
+    memory_images: &PrimaryMap<LocalMemoryIndex, Option<MemoryImage>>,
     data_initializers: &[DataInitializer<'_>],
 ) -> Result<(), Trap> {
+    let mut imaged = Vec::new();
+    for (index, image) in memory_images.iter() {
+        if let Some(image) = image {
+            let memory = instance.memories[index];
+            let memory = unsafe { memory.get_mut(&mut *instance.context) };
+            if memory
+                .map_image(image)
+                .map_err(|e| Trap::User(Box::new(e)))?
+            {
+                imaged.push(instance.module.memory_index(index));
+            }
+        }
+    }
+
     for init in data_initializers {
+        if imaged.contains(&init.location.memory_index) {
+            continue;
+        }
         let memory = instance.get_memory(init.location.memory_index);
 
         let start = get_memory_init_start(init, instance);
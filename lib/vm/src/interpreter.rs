@@ -0,0 +1,1049 @@
+//! The interpreter running the [`InterpretedFunction`]s of modules
+//! compiled for platforms where memory can't be made executable.
+//!
+//! All the functions of such modules are called through
+//! [`wasmer_vm_interpreter_trampoline`], their `func_ptr` pointing to the
+//! function to interpret. Calls between interpreted functions, even of
+//! different instances, push a frame on the stacks of the running
+//! interpreter instead of going through the trampoline again, while host
+//! functions are called through their own trampoline.
+//!
+//! Traps are raised once the interpreter has unwound its stacks, except
+//! for the ones raised by host functions: they unwind over the
+//! interpreter, whose stacks are then leaked.
+
+use crate::debugger::DebugStop;
+use crate::libcalls::{wasmer_vm_f32_nearest, wasmer_vm_f64_nearest};
+use crate::table::{TableElement, VMTable};
+use crate::trap::{raise_lib_trap, Trap, TrapCode};
+use crate::vmcontext::{VMCallerCheckedAnyfunc, VMContext};
+use crate::{on_host_stack, VMExternRef, VMFuncRef, VMFunctionBody};
+use std::cell::RefCell;
+use std::{mem, ptr};
+use wasmer_types::{
+    BranchTarget, FunctionType, Instruction, InterpretedFunction, MemArg, RawValue, SignatureIndex,
+    TableIndex, Type,
+};
+
+/// The maximum number of nested calls in an interpreter.
+const MAX_FRAMES: usize = 64 * 1024;
+
+/// The maximum number of locals of all the functions running in an
+/// interpreter.
+const MAX_LOCALS: usize = 1024 * 1024;
+
+/// Calls the interpreted function `body` points to, reading its
+/// parameters from `values` and writing its results back to them.
+///
+/// This is the call trampoline of all the interpreted functions, whatever
+/// their signature.
+///
+/// # Safety
+///
+/// `body` must point to an `InterpretedFunction` of the module of the
+/// instance `vmctx` belongs to, and `values` must have room for its
+/// parameters and results.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_vm_interpreter_trampoline(
+    vmctx: *mut VMContext,
+    body: *const VMFunctionBody,
+    values: *mut RawValue,
+) {
+    let result = {
+        let function = &*(body as *const InterpretedFunction);
+        let mut interpreter = Interpreter::default();
+        for (i, ty) in function.params.iter().enumerate() {
+            interpreter.push(from_raw(*values.add(i), *ty));
+        }
+        interpreter.run(vmctx, function).map(|()| {
+            for (i, ty) in function.results.iter().enumerate() {
+                *values.add(i) = to_raw(interpreter.values[i], *ty);
+            }
+        })
+    };
+    if let Err(trap) = result {
+        raise_lib_trap(trap);
+    }
+}
+
+thread_local! {
+    /// The interpreters of the current thread calling host functions, the
+    /// innermost last.
+    static HOST_CALLERS: RefCell<Vec<HostCaller>> = RefCell::new(Vec::new());
+}
+
+/// An interpreter calling a host function from `function`, past the call
+/// at `pc`, while its `callers` wait.
+pub(crate) struct HostCaller {
+    callers: *const Vec<Frame>,
+    function: *const InterpretedFunction,
+    pc: usize,
+}
+
+/// Returns how many interpreters are calling host functions on the
+/// current thread.
+pub(crate) fn num_host_callers() -> usize {
+    HOST_CALLERS.with(|callers| callers.borrow().len())
+}
+
+/// Takes the interpreters calling host functions after the `len` first
+/// ones, as they stop running on the current thread when the Wasm code
+/// they run on unwinds or is suspended.
+pub(crate) fn take_host_callers(len: usize) -> Vec<HostCaller> {
+    HOST_CALLERS.with(|callers| {
+        let mut callers = callers.borrow_mut();
+        let len = len.min(callers.len());
+        callers.split_off(len)
+    })
+}
+
+/// Puts back interpreters taken with [`take_host_callers`], when the Wasm
+/// code they run on is resumed.
+pub(crate) fn restore_host_callers(host_callers: Vec<HostCaller>) {
+    HOST_CALLERS.with(|callers| callers.borrow_mut().extend(host_callers));
+}
+
+/// Returns the addresses of the instructions running in the interpreters
+/// that are calling host functions on the current thread: the frames of
+/// each interpreter, from the innermost interpreter and frame.
+///
+/// An interpreter runs in a native frame of
+/// [`wasmer_vm_interpreter_trampoline`], which its frames replace in a
+/// backtrace of the native stack.
+pub fn interpreted_host_callers() -> Vec<Vec<usize>> {
+    HOST_CALLERS.with(|callers| {
+        callers
+            .borrow()
+            .iter()
+            .rev()
+            .map(|caller| unsafe { trace(caller.function, caller.pc, &*caller.callers) })
+            .collect()
+    })
+}
+
+/// Returns the addresses of the instructions running in the frames of an
+/// interpreter, from the innermost one, which runs `function` and is past
+/// the instruction before `pc`, to the outermost of its `callers`, which
+/// are past their calls.
+unsafe fn trace(function: *const InterpretedFunction, pc: usize, callers: &[Frame]) -> Vec<usize> {
+    let callers = callers.iter().rev().map(|frame| (frame.function, frame.pc));
+    Some((function, pc))
+        .into_iter()
+        .chain(callers)
+        .map(|(function, pc)| (*function).code.as_ptr().add(pc.saturating_sub(1)) as usize)
+        .collect()
+}
+
+/// Whether `anyfunc` is run by the interpreter.
+pub(crate) fn is_interpreted(anyfunc: &VMCallerCheckedAnyfunc) -> bool {
+    anyfunc.call_trampoline as usize == wasmer_vm_interpreter_trampoline as usize
+}
+
+/// The stacks of an interpreter.
+#[derive(Default)]
+struct Interpreter {
+    /// The locals and operands of all the running functions, as untyped
+    /// 64-bit slots.
+    values: Vec<u64>,
+    /// The callers of the function running.
+    frames: Vec<Frame>,
+}
+
+/// A function waiting for the function it called to return.
+struct Frame {
+    function: *const InterpretedFunction,
+    vmctx: *mut VMContext,
+    locals: usize,
+    pc: usize,
+}
+
+macro_rules! unop {
+    ($self:ident, $from:ident, $into:ident, $op:expr) => {{
+        let x = $from($self.pop());
+        $self.push($into($op(x)));
+    }};
+}
+
+macro_rules! binop {
+    ($self:ident, $from:ident, $into:ident, $op:expr) => {{
+        let y = $from($self.pop());
+        let x = $from($self.pop());
+        $self.push($into($op(x, y)));
+    }};
+}
+
+macro_rules! load {
+    ($self:ident, $vmctx:ident, $arg:expr, $ty:ty, $into:expr) => {{
+        let address = as_u32($self.pop());
+        let pointer = memory_address($vmctx, $arg, address, mem::size_of::<$ty>())?;
+        let value = <$ty>::from_le_bytes(ptr::read_unaligned(pointer as *const _));
+        $self.push($into(value));
+    }};
+}
+
+macro_rules! store {
+    ($self:ident, $vmctx:ident, $arg:expr, $ty:ty, $from:expr) => {{
+        let value: $ty = $from($self.pop());
+        let address = as_u32($self.pop());
+        let pointer = memory_address($vmctx, $arg, address, mem::size_of::<$ty>())?;
+        ptr::write_unaligned(pointer as *mut _, value.to_le_bytes());
+    }};
+}
+
+impl Interpreter {
+    fn push(&mut self, value: u64) {
+        self.values.push(value);
+    }
+
+    fn pop(&mut self) -> u64 {
+        self.values.pop().unwrap()
+    }
+
+    /// Runs `function` until it returns, with its parameters on top of
+    /// the stack, leaving its results in their place.
+    ///
+    /// The traps it raises are traced back to the instructions running in
+    /// the interpreted frames.
+    unsafe fn run(
+        &mut self,
+        vmctx: *mut VMContext,
+        function: &InterpretedFunction,
+    ) -> Result<(), Trap> {
+        let mut function: *const InterpretedFunction = function;
+        let mut pc = 0;
+        self.execute(vmctx, &mut function, &mut pc)
+            .map_err(|trap| match trap {
+                Trap::Lib {
+                    trap_code,
+                    backtrace,
+                } => {
+                    // `pc` is past the instruction that trapped, and the
+                    // callers past their call.
+                    let pcs = trace(function, pc, &self.frames);
+                    Trap::Interpreter {
+                        trap_code,
+                        pcs,
+                        backtrace,
+                    }
+                }
+                trap => trap,
+            })
+    }
+
+    /// Runs the instructions of `function` from `pc`, keeping both up to
+    /// date with the function running and its next instruction.
+    #[allow(clippy::cognitive_complexity)]
+    unsafe fn execute(
+        &mut self,
+        mut vmctx: *mut VMContext,
+        function: &mut *const InterpretedFunction,
+        pc: &mut usize,
+    ) -> Result<(), Trap> {
+        let mut locals = self.enter(&**function)?;
+        check_interrupt(vmctx)?;
+
+        macro_rules! call {
+            ($anyfunc:expr, $signature:expr) => {{
+                let anyfunc: *const VMCallerCheckedAnyfunc = $anyfunc;
+                if is_interpreted(&*anyfunc) {
+                    self.frames.push(Frame {
+                        function: *function,
+                        vmctx,
+                        locals,
+                        pc: *pc,
+                    });
+                    *function = (*anyfunc).func_ptr as *const InterpretedFunction;
+                    vmctx = (*anyfunc).vmctx.vmctx;
+                    *pc = 0;
+                    locals = self.enter(&**function)?;
+                    check_interrupt(vmctx)?;
+                } else {
+                    let module = (*vmctx).instance().module_ref();
+                    self.call_host(&*anyfunc, &module.signatures[$signature], *function, *pc);
+                }
+            }};
+        }
+
+        loop {
+            if (*vmctx).instance().debugger.is_some() {
+                self.debug(vmctx, &**function, *pc, locals);
+            }
+            let instruction = (**function).code[*pc];
+            *pc += 1;
+            match instruction {
+                Instruction::Unreachable(trap_code) => return Err(Trap::lib(trap_code)),
+                Instruction::Br(target) => {
+                    *pc = self.branch(target);
+                    check_interrupt(vmctx)?;
+                }
+                Instruction::BrIf(target) => {
+                    if as_u32(self.pop()) != 0 {
+                        *pc = self.branch(target);
+                        check_interrupt(vmctx)?;
+                    }
+                }
+                Instruction::BrIfEqz(target) => {
+                    if as_u32(self.pop()) == 0 {
+                        *pc = target as usize;
+                    }
+                }
+                Instruction::BrTable(table) => {
+                    let targets = &(**function).branch_tables[table as usize];
+                    let index = as_u32(self.pop()) as usize;
+                    *pc = self.branch(targets[index.min(targets.len() - 1)]);
+                    check_interrupt(vmctx)?;
+                }
+                Instruction::Return => {
+                    let results = (**function).results.len();
+                    let top = self.values.len() - results;
+                    self.values.copy_within(top.., locals);
+                    self.values.truncate(locals + results);
+                    match self.frames.pop() {
+                        Some(frame) => {
+                            *function = frame.function;
+                            vmctx = frame.vmctx;
+                            locals = frame.locals;
+                            *pc = frame.pc;
+                        }
+                        None => return Ok(()),
+                    }
+                }
+                Instruction::Call(index) => {
+                    let instance = (*vmctx).instance();
+                    let anyfunc = instance.func_ref(index).unwrap().0.as_ptr();
+                    call!(anyfunc, instance.module_ref().functions[index])
+                }
+                Instruction::CallIndirect { signature, table } => {
+                    let anyfunc = self.indirect_callee(vmctx, table, signature)?;
+                    call!(anyfunc, signature)
+                }
+
+                Instruction::Drop => {
+                    self.pop();
+                }
+                Instruction::Select => {
+                    let condition = as_u32(self.pop());
+                    let y = self.pop();
+                    let x = self.pop();
+                    self.push(if condition != 0 { x } else { y });
+                }
+                Instruction::LocalGet(index) => {
+                    let value = self.values[locals + index as usize];
+                    self.push(value);
+                }
+                Instruction::LocalSet(index) => {
+                    let value = self.pop();
+                    self.values[locals + index as usize] = value;
+                }
+                Instruction::LocalTee(index) => {
+                    let value = *self.values.last().unwrap();
+                    self.values[locals + index as usize] = value;
+                }
+                Instruction::GlobalGet { global, ty } => {
+                    let definition = (*vmctx).instance().get_global_ptr(global);
+                    self.push(from_raw(definition.as_ref().val, ty));
+                }
+                Instruction::GlobalSet { global, ty } => {
+                    let value = self.pop();
+                    let mut definition = (*vmctx).instance().get_global_ptr(global);
+                    definition.as_mut().val = to_raw(value, ty);
+                }
+
+                Instruction::I32Load(arg) => load!(self, vmctx, arg, u32, from_u32),
+                Instruction::I64Load(arg) => load!(self, vmctx, arg, u64, from_u64),
+                Instruction::F32Load(arg) => load!(self, vmctx, arg, u32, from_u32),
+                Instruction::F64Load(arg) => load!(self, vmctx, arg, u64, from_u64),
+                Instruction::I32Load8S(arg) => {
+                    load!(self, vmctx, arg, i8, |x: i8| from_i32(i32::from(x)))
+                }
+                Instruction::I32Load8U(arg) => {
+                    load!(self, vmctx, arg, u8, |x: u8| from_u32(u32::from(x)))
+                }
+                Instruction::I32Load16S(arg) => {
+                    load!(self, vmctx, arg, i16, |x: i16| from_i32(i32::from(x)))
+                }
+                Instruction::I32Load16U(arg) => {
+                    load!(self, vmctx, arg, u16, |x: u16| from_u32(u32::from(x)))
+                }
+                Instruction::I64Load8S(arg) => {
+                    load!(self, vmctx, arg, i8, |x: i8| from_i64(i64::from(x)))
+                }
+                Instruction::I64Load8U(arg) => load!(self, vmctx, arg, u8, u64::from),
+                Instruction::I64Load16S(arg) => {
+                    load!(self, vmctx, arg, i16, |x: i16| from_i64(i64::from(x)))
+                }
+                Instruction::I64Load16U(arg) => load!(self, vmctx, arg, u16, u64::from),
+                Instruction::I64Load32S(arg) => {
+                    load!(self, vmctx, arg, i32, |x: i32| from_i64(i64::from(x)))
+                }
+                Instruction::I64Load32U(arg) => load!(self, vmctx, arg, u32, u64::from),
+                Instruction::I32Store(arg) => store!(self, vmctx, arg, u32, as_u32),
+                Instruction::I64Store(arg) => store!(self, vmctx, arg, u64, as_u64),
+                Instruction::F32Store(arg) => store!(self, vmctx, arg, u32, as_u32),
+                Instruction::F64Store(arg) => store!(self, vmctx, arg, u64, as_u64),
+                Instruction::I32Store8(arg) | Instruction::I64Store8(arg) => {
+                    store!(self, vmctx, arg, u8, |x: u64| x as u8)
+                }
+                Instruction::I32Store16(arg) | Instruction::I64Store16(arg) => {
+                    store!(self, vmctx, arg, u16, |x: u64| x as u16)
+                }
+                Instruction::I64Store32(arg) => store!(self, vmctx, arg, u32, as_u32),
+
+                Instruction::MemorySize(memory) => {
+                    let instance = (*vmctx).instance();
+                    let pages = match instance.module_ref().local_memory_index(memory) {
+                        Some(local) => instance.memory_size(local),
+                        None => instance.imported_memory_size(memory),
+                    };
+                    self.push(from_u32(pages.0));
+                }
+                Instruction::MemoryGrow(memory) => {
+                    let delta = as_u32(self.pop());
+                    let instance = (*vmctx).instance_mut();
+                    let previous =
+                        on_host_stack(|| match instance.module_ref().local_memory_index(memory) {
+                            Some(local) => instance.memory_grow(local, delta),
+                            None => instance.imported_memory_grow(memory, delta),
+                        });
+                    self.push(from_u32(previous.map_or(u32::MAX, |pages| pages.0)));
+                }
+                Instruction::MemoryInit { memory, data } => {
+                    let len = as_u32(self.pop());
+                    let src = as_u32(self.pop());
+                    let dst = as_u32(self.pop());
+                    (*vmctx)
+                        .instance()
+                        .memory_init(memory, data, dst, src, len)?;
+                }
+                Instruction::DataDrop(data) => (*vmctx).instance().data_drop(data),
+                Instruction::MemoryCopy(memory) => {
+                    let len = as_u32(self.pop());
+                    let src = as_u32(self.pop());
+                    let dst = as_u32(self.pop());
+                    let instance = (*vmctx).instance();
+                    match instance.module_ref().local_memory_index(memory) {
+                        Some(local) => instance.local_memory_copy(local, dst, src, len)?,
+                        None => instance.imported_memory_copy(memory, dst, src, len)?,
+                    }
+                }
+                Instruction::MemoryFill(memory) => {
+                    let len = as_u32(self.pop());
+                    let val = as_u32(self.pop());
+                    let dst = as_u32(self.pop());
+                    let instance = (*vmctx).instance();
+                    match instance.module_ref().local_memory_index(memory) {
+                        Some(local) => instance.local_memory_fill(local, dst, val, len)?,
+                        None => instance.imported_memory_fill(memory, dst, val, len)?,
+                    }
+                }
+
+                Instruction::TableInit { table, elem } => {
+                    let len = as_u32(self.pop());
+                    let src = as_u32(self.pop());
+                    let dst = as_u32(self.pop());
+                    (*vmctx)
+                        .instance_mut()
+                        .table_init(table, elem, dst, src, len)?;
+                }
+                Instruction::ElemDrop(elem) => (*vmctx).instance().elem_drop(elem),
+                Instruction::TableCopy { dst, src } => {
+                    let len = as_u32(self.pop());
+                    let src_index = as_u32(self.pop());
+                    let dst_index = as_u32(self.pop());
+                    let dst_table: *mut VMTable = (*vmctx).instance_mut().get_table(dst);
+                    if dst == src {
+                        (*dst_table).copy_within(dst_index, src_index, len)?;
+                    } else {
+                        let src_table = (*vmctx).instance_mut().get_table(src);
+                        (*dst_table).copy(src_table, dst_index, src_index, len)?;
+                    }
+                }
+                Instruction::TableGet(table) => {
+                    let index = as_u32(self.pop());
+                    match (*vmctx).instance_mut().get_table(table).get(index) {
+                        Some(element) => self.push(from_element(element)),
+                        None => return Err(Trap::lib(TrapCode::TableAccessOutOfBounds)),
+                    }
+                }
+                Instruction::TableSet(table) => {
+                    let value = self.pop();
+                    let index = as_u32(self.pop());
+                    let table = (*vmctx).instance_mut().get_table(table);
+                    let element = to_element(table, value);
+                    table.set(index, element)?;
+                }
+                Instruction::TableGrow(table) => {
+                    let delta = as_u32(self.pop());
+                    let value = self.pop();
+                    let table = (*vmctx).instance_mut().get_table(table);
+                    let element = to_element(table, value);
+                    let previous = on_host_stack(|| table.grow(delta, element));
+                    self.push(from_u32(previous.unwrap_or(u32::MAX)));
+                }
+                Instruction::TableSize(table) => {
+                    let size = (*vmctx).instance_mut().get_table(table).size();
+                    self.push(from_u32(size));
+                }
+                Instruction::TableFill(table) => {
+                    let len = as_u32(self.pop());
+                    let value = self.pop();
+                    let start = as_u32(self.pop());
+                    let instance = (*vmctx).instance_mut();
+                    let element = to_element(instance.get_table(table), value);
+                    instance.table_fill(table, start, element, len)?;
+                }
+                Instruction::RefNull => self.push(0),
+                Instruction::RefIsNull => unop!(self, as_u64, from_bool, |x| x == 0),
+                Instruction::RefFunc(index) => {
+                    let func_ref = (*vmctx).instance().func_ref(index);
+                    self.push(from_element(TableElement::FuncRef(func_ref)));
+                }
+
+                Instruction::I32Const(value) => self.push(from_i32(value)),
+                Instruction::I64Const(value) => self.push(from_i64(value)),
+                Instruction::F32Const(bits) => self.push(from_u32(bits)),
+                Instruction::F64Const(bits) => self.push(bits),
+
+                Instruction::I32Eqz => unop!(self, as_u32, from_bool, |x| x == 0),
+                Instruction::I32Eq => binop!(self, as_u32, from_bool, |x, y| x == y),
+                Instruction::I32Ne => binop!(self, as_u32, from_bool, |x, y| x != y),
+                Instruction::I32LtS => binop!(self, as_i32, from_bool, |x, y| x < y),
+                Instruction::I32LtU => binop!(self, as_u32, from_bool, |x, y| x < y),
+                Instruction::I32GtS => binop!(self, as_i32, from_bool, |x, y| x > y),
+                Instruction::I32GtU => binop!(self, as_u32, from_bool, |x, y| x > y),
+                Instruction::I32LeS => binop!(self, as_i32, from_bool, |x, y| x <= y),
+                Instruction::I32LeU => binop!(self, as_u32, from_bool, |x, y| x <= y),
+                Instruction::I32GeS => binop!(self, as_i32, from_bool, |x, y| x >= y),
+                Instruction::I32GeU => binop!(self, as_u32, from_bool, |x, y| x >= y),
+                Instruction::I64Eqz => unop!(self, as_u64, from_bool, |x| x == 0),
+                Instruction::I64Eq => binop!(self, as_u64, from_bool, |x, y| x == y),
+                Instruction::I64Ne => binop!(self, as_u64, from_bool, |x, y| x != y),
+                Instruction::I64LtS => binop!(self, as_i64, from_bool, |x, y| x < y),
+                Instruction::I64LtU => binop!(self, as_u64, from_bool, |x, y| x < y),
+                Instruction::I64GtS => binop!(self, as_i64, from_bool, |x, y| x > y),
+                Instruction::I64GtU => binop!(self, as_u64, from_bool, |x, y| x > y),
+                Instruction::I64LeS => binop!(self, as_i64, from_bool, |x, y| x <= y),
+                Instruction::I64LeU => binop!(self, as_u64, from_bool, |x, y| x <= y),
+                Instruction::I64GeS => binop!(self, as_i64, from_bool, |x, y| x >= y),
+                Instruction::I64GeU => binop!(self, as_u64, from_bool, |x, y| x >= y),
+                Instruction::F32Eq => binop!(self, as_f32, from_bool, |x, y| x == y),
+                Instruction::F32Ne => binop!(self, as_f32, from_bool, |x, y| x != y),
+                Instruction::F32Lt => binop!(self, as_f32, from_bool, |x, y| x < y),
+                Instruction::F32Gt => binop!(self, as_f32, from_bool, |x, y| x > y),
+                Instruction::F32Le => binop!(self, as_f32, from_bool, |x, y| x <= y),
+                Instruction::F32Ge => binop!(self, as_f32, from_bool, |x, y| x >= y),
+                Instruction::F64Eq => binop!(self, as_f64, from_bool, |x, y| x == y),
+                Instruction::F64Ne => binop!(self, as_f64, from_bool, |x, y| x != y),
+                Instruction::F64Lt => binop!(self, as_f64, from_bool, |x, y| x < y),
+                Instruction::F64Gt => binop!(self, as_f64, from_bool, |x, y| x > y),
+                Instruction::F64Le => binop!(self, as_f64, from_bool, |x, y| x <= y),
+                Instruction::F64Ge => binop!(self, as_f64, from_bool, |x, y| x >= y),
+
+                Instruction::I32Clz => unop!(self, as_u32, from_u32, u32::leading_zeros),
+                Instruction::I32Ctz => unop!(self, as_u32, from_u32, u32::trailing_zeros),
+                Instruction::I32Popcnt => unop!(self, as_u32, from_u32, u32::count_ones),
+                Instruction::I32Add => binop!(self, as_u32, from_u32, u32::wrapping_add),
+                Instruction::I32Sub => binop!(self, as_u32, from_u32, u32::wrapping_sub),
+                Instruction::I32Mul => binop!(self, as_u32, from_u32, u32::wrapping_mul),
+                Instruction::I32DivS => {
+                    let y = as_i32(self.pop());
+                    let x = as_i32(self.pop());
+                    self.push(from_i32(divide(y, || x.checked_div(y))?));
+                }
+                Instruction::I32DivU => {
+                    let y = as_u32(self.pop());
+                    let x = as_u32(self.pop());
+                    self.push(from_u32(divide(y, || x.checked_div(y))?));
+                }
+                Instruction::I32RemS => {
+                    let y = as_i32(self.pop());
+                    let x = as_i32(self.pop());
+                    self.push(from_i32(divide(y, || Some(x.wrapping_rem(y)))?));
+                }
+                Instruction::I32RemU => {
+                    let y = as_u32(self.pop());
+                    let x = as_u32(self.pop());
+                    self.push(from_u32(divide(y, || x.checked_rem(y))?));
+                }
+                Instruction::I32And => binop!(self, as_u32, from_u32, |x, y| x & y),
+                Instruction::I32Or => binop!(self, as_u32, from_u32, |x, y| x | y),
+                Instruction::I32Xor => binop!(self, as_u32, from_u32, |x, y| x ^ y),
+                Instruction::I32Shl => binop!(self, as_u32, from_u32, u32::wrapping_shl),
+                Instruction::I32ShrS => {
+                    binop!(self, as_i32, from_i32, |x: i32, y: i32| x
+                        .wrapping_shr(y as u32))
+                }
+                Instruction::I32ShrU => binop!(self, as_u32, from_u32, u32::wrapping_shr),
+                Instruction::I32Rotl => binop!(self, as_u32, from_u32, u32::rotate_left),
+                Instruction::I32Rotr => binop!(self, as_u32, from_u32, u32::rotate_right),
+                Instruction::I64Clz => {
+                    unop!(self, as_u64, from_u64, |x: u64| u64::from(
+                        x.leading_zeros()
+                    ))
+                }
+                Instruction::I64Ctz => {
+                    unop!(self, as_u64, from_u64, |x: u64| u64::from(
+                        x.trailing_zeros()
+                    ))
+                }
+                Instruction::I64Popcnt => {
+                    unop!(self, as_u64, from_u64, |x: u64| u64::from(x.count_ones()))
+                }
+                Instruction::I64Add => binop!(self, as_u64, from_u64, u64::wrapping_add),
+                Instruction::I64Sub => binop!(self, as_u64, from_u64, u64::wrapping_sub),
+                Instruction::I64Mul => binop!(self, as_u64, from_u64, u64::wrapping_mul),
+                Instruction::I64DivS => {
+                    let y = as_i64(self.pop());
+                    let x = as_i64(self.pop());
+                    self.push(from_i64(divide(y, || x.checked_div(y))?));
+                }
+                Instruction::I64DivU => {
+                    let y = self.pop();
+                    let x = self.pop();
+                    self.push(divide(y, || x.checked_div(y))?);
+                }
+                Instruction::I64RemS => {
+                    let y = as_i64(self.pop());
+                    let x = as_i64(self.pop());
+                    self.push(from_i64(divide(y, || Some(x.wrapping_rem(y)))?));
+                }
+                Instruction::I64RemU => {
+                    let y = self.pop();
+                    let x = self.pop();
+                    self.push(divide(y, || x.checked_rem(y))?);
+                }
+                Instruction::I64And => binop!(self, as_u64, from_u64, |x, y| x & y),
+                Instruction::I64Or => binop!(self, as_u64, from_u64, |x, y| x | y),
+                Instruction::I64Xor => binop!(self, as_u64, from_u64, |x, y| x ^ y),
+                Instruction::I64Shl => {
+                    binop!(self, as_u64, from_u64, |x: u64, y: u64| x
+                        .wrapping_shl(y as u32))
+                }
+                Instruction::I64ShrS => {
+                    binop!(self, as_i64, from_i64, |x: i64, y: i64| x
+                        .wrapping_shr(y as u32))
+                }
+                Instruction::I64ShrU => {
+                    binop!(self, as_u64, from_u64, |x: u64, y: u64| x
+                        .wrapping_shr(y as u32))
+                }
+                Instruction::I64Rotl => {
+                    binop!(self, as_u64, from_u64, |x: u64, y: u64| x
+                        .rotate_left(y as u32))
+                }
+                Instruction::I64Rotr => {
+                    binop!(self, as_u64, from_u64, |x: u64, y: u64| x
+                        .rotate_right(y as u32))
+                }
+
+                Instruction::F32Abs => unop!(self, as_f32, from_f32, f32::abs),
+                Instruction::F32Neg => unop!(self, as_f32, from_f32, |x: f32| -x),
+                Instruction::F32Ceil => unop!(self, as_f32, from_f32, f32::ceil),
+                Instruction::F32Floor => unop!(self, as_f32, from_f32, f32::floor),
+                Instruction::F32Trunc => unop!(self, as_f32, from_f32, f32::trunc),
+                Instruction::F32Nearest => unop!(self, as_f32, from_f32, wasmer_vm_f32_nearest),
+                Instruction::F32Sqrt => unop!(self, as_f32, from_f32, f32::sqrt),
+                Instruction::F32Add => binop!(self, as_f32, from_f32, |x, y| x + y),
+                Instruction::F32Sub => binop!(self, as_f32, from_f32, |x, y| x - y),
+                Instruction::F32Mul => binop!(self, as_f32, from_f32, |x, y| x * y),
+                Instruction::F32Div => binop!(self, as_f32, from_f32, |x, y| x / y),
+                Instruction::F32Min => binop!(self, as_f32, from_f32, f32_min),
+                Instruction::F32Max => binop!(self, as_f32, from_f32, f32_max),
+                Instruction::F32Copysign => binop!(self, as_f32, from_f32, f32::copysign),
+                Instruction::F64Abs => unop!(self, as_f64, from_f64, f64::abs),
+                Instruction::F64Neg => unop!(self, as_f64, from_f64, |x: f64| -x),
+                Instruction::F64Ceil => unop!(self, as_f64, from_f64, f64::ceil),
+                Instruction::F64Floor => unop!(self, as_f64, from_f64, f64::floor),
+                Instruction::F64Trunc => unop!(self, as_f64, from_f64, f64::trunc),
+                Instruction::F64Nearest => unop!(self, as_f64, from_f64, wasmer_vm_f64_nearest),
+                Instruction::F64Sqrt => unop!(self, as_f64, from_f64, f64::sqrt),
+                Instruction::F64Add => binop!(self, as_f64, from_f64, |x, y| x + y),
+                Instruction::F64Sub => binop!(self, as_f64, from_f64, |x, y| x - y),
+                Instruction::F64Mul => binop!(self, as_f64, from_f64, |x, y| x * y),
+                Instruction::F64Div => binop!(self, as_f64, from_f64, |x, y| x / y),
+                Instruction::F64Min => binop!(self, as_f64, from_f64, f64_min),
+                Instruction::F64Max => binop!(self, as_f64, from_f64, f64_max),
+                Instruction::F64Copysign => binop!(self, as_f64, from_f64, f64::copysign),
+
+                Instruction::I32WrapI64 => unop!(self, as_u64, from_u32, |x: u64| x as u32),
+                Instruction::I32TruncF32S => {
+                    let x = f64::from(as_f32(self.pop()));
+                    self.push(from_i32(truncate(x, -2147483648.0, 2147483648.0)? as i32));
+                }
+                Instruction::I32TruncF32U => {
+                    let x = f64::from(as_f32(self.pop()));
+                    self.push(from_u32(truncate(x, 0.0, 4294967296.0)? as u32));
+                }
+                Instruction::I32TruncF64S => {
+                    let x = as_f64(self.pop());
+                    self.push(from_i32(truncate(x, -2147483648.0, 2147483648.0)? as i32));
+                }
+                Instruction::I32TruncF64U => {
+                    let x = as_f64(self.pop());
+                    self.push(from_u32(truncate(x, 0.0, 4294967296.0)? as u32));
+                }
+                Instruction::I64ExtendI32S => unop!(self, as_i32, from_i64, i64::from),
+                Instruction::I64ExtendI32U => unop!(self, as_u32, from_u64, u64::from),
+                Instruction::I64TruncF32S => {
+                    let x = f64::from(as_f32(self.pop()));
+                    self.push(from_i64(truncate(x, I64_MIN, I64_END)? as i64));
+                }
+                Instruction::I64TruncF32U => {
+                    let x = f64::from(as_f32(self.pop()));
+                    self.push(truncate(x, 0.0, U64_END)? as u64);
+                }
+                Instruction::I64TruncF64S => {
+                    let x = as_f64(self.pop());
+                    self.push(from_i64(truncate(x, I64_MIN, I64_END)? as i64));
+                }
+                Instruction::I64TruncF64U => {
+                    let x = as_f64(self.pop());
+                    self.push(truncate(x, 0.0, U64_END)? as u64);
+                }
+                Instruction::F32ConvertI32S => unop!(self, as_i32, from_f32, |x: i32| x as f32),
+                Instruction::F32ConvertI32U => unop!(self, as_u32, from_f32, |x: u32| x as f32),
+                Instruction::F32ConvertI64S => unop!(self, as_i64, from_f32, |x: i64| x as f32),
+                Instruction::F32ConvertI64U => unop!(self, as_u64, from_f32, |x: u64| x as f32),
+                Instruction::F32DemoteF64 => unop!(self, as_f64, from_f32, |x: f64| x as f32),
+                Instruction::F64ConvertI32S => unop!(self, as_i32, from_f64, f64::from),
+                Instruction::F64ConvertI32U => unop!(self, as_u32, from_f64, f64::from),
+                Instruction::F64ConvertI64S => unop!(self, as_i64, from_f64, |x: i64| x as f64),
+                Instruction::F64ConvertI64U => unop!(self, as_u64, from_f64, |x: u64| x as f64),
+                Instruction::F64PromoteF32 => unop!(self, as_f32, from_f64, f64::from),
+                Instruction::I32Extend8S => {
+                    unop!(self, as_i32, from_i32, |x: i32| i32::from(x as i8))
+                }
+                Instruction::I32Extend16S => {
+                    unop!(self, as_i32, from_i32, |x: i32| i32::from(x as i16))
+                }
+                Instruction::I64Extend8S => {
+                    unop!(self, as_i64, from_i64, |x: i64| i64::from(x as i8))
+                }
+                Instruction::I64Extend16S => {
+                    unop!(self, as_i64, from_i64, |x: i64| i64::from(x as i16))
+                }
+                Instruction::I64Extend32S => {
+                    unop!(self, as_i64, from_i64, |x: i64| i64::from(x as i32))
+                }
+                Instruction::I32TruncSatF32S => unop!(self, as_f32, from_i32, |x: f32| x as i32),
+                Instruction::I32TruncSatF32U => unop!(self, as_f32, from_u32, |x: f32| x as u32),
+                Instruction::I32TruncSatF64S => unop!(self, as_f64, from_i32, |x: f64| x as i32),
+                Instruction::I32TruncSatF64U => unop!(self, as_f64, from_u32, |x: f64| x as u32),
+                Instruction::I64TruncSatF32S => unop!(self, as_f32, from_i64, |x: f32| x as i64),
+                Instruction::I64TruncSatF32U => unop!(self, as_f32, from_u64, |x: f32| x as u64),
+                Instruction::I64TruncSatF64S => unop!(self, as_f64, from_i64, |x: f64| x as i64),
+                Instruction::I64TruncSatF64U => unop!(self, as_f64, from_u64, |x: f64| x as u64),
+            }
+        }
+    }
+
+    /// Calls the debugger of the instance `vmctx` belongs to if it stops
+    /// before the instruction `pc` of `function`.
+    unsafe fn debug(
+        &mut self,
+        vmctx: *mut VMContext,
+        function: &InterpretedFunction,
+        pc: usize,
+        locals: usize,
+    ) {
+        let debugger = (*vmctx).instance_mut().debugger.as_mut().unwrap();
+        let address = function.code[pc..].as_ptr() as usize;
+        let reason = match debugger.stop_reason(address) {
+            Some(reason) => reason,
+            None => return,
+        };
+        let mut handler = debugger.take_handler();
+        let stop = DebugStop {
+            reason,
+            function,
+            pc,
+            values: &self.values[locals..],
+        };
+        let action = on_host_stack(|| handler(&stop));
+        // Unless the handler removed or replaced the debugger
+        if let Some(debugger) = (*vmctx).instance_mut().debugger.as_mut() {
+            if debugger.is_stopped() {
+                debugger.resume(handler, action);
+            }
+        }
+    }
+
+    /// Makes room for the locals of `function`, whose parameters are on
+    /// top of the stack, returning the slot of its first local.
+    fn enter(&mut self, function: &InterpretedFunction) -> Result<usize, Trap> {
+        let num_locals = function.locals.len();
+        if self.frames.len() >= MAX_FRAMES || self.values.len() + num_locals > MAX_LOCALS {
+            return Err(Trap::lib(TrapCode::StackOverflow));
+        }
+        let locals = self.values.len() - function.params.len();
+        self.values.resize(self.values.len() + num_locals, 0);
+        Ok(locals)
+    }
+
+    /// Unwinds the stack to `target`, returning the instruction it
+    /// continues at.
+    fn branch(&mut self, target: BranchTarget) -> usize {
+        if target.drop != 0 {
+            let keep = target.keep as usize;
+            let len = self.values.len();
+            let top = len - keep - target.drop as usize;
+            self.values.copy_within(len - keep.., top);
+            self.values.truncate(top + keep);
+        }
+        target.pc as usize
+    }
+
+    /// Pops the index of the function called by a `call_indirect`,
+    /// returning the function if its signature matches.
+    unsafe fn indirect_callee(
+        &mut self,
+        vmctx: *mut VMContext,
+        table: TableIndex,
+        signature: SignatureIndex,
+    ) -> Result<*const VMCallerCheckedAnyfunc, Trap> {
+        let index = as_u32(self.pop());
+        let instance = (*vmctx).instance_mut();
+        let anyfunc = match instance.get_table(table).get(index) {
+            Some(TableElement::FuncRef(Some(func_ref))) => func_ref.0.as_ptr(),
+            Some(_) => return Err(Trap::lib(TrapCode::IndirectCallToNull)),
+            None => return Err(Trap::lib(TrapCode::TableAccessOutOfBounds)),
+        };
+        if (*anyfunc).type_index != instance.signature_id(signature) {
+            return Err(Trap::lib(TrapCode::BadSignature));
+        }
+        Ok(anyfunc)
+    }
+
+    /// Calls the host function `anyfunc` from `function`, past the call at
+    /// `pc`, with the parameters on top of the stack, replacing them with
+    /// its results.
+    unsafe fn call_host(
+        &mut self,
+        anyfunc: &VMCallerCheckedAnyfunc,
+        ty: &FunctionType,
+        function: *const InterpretedFunction,
+        pc: usize,
+    ) {
+        let params = ty.params();
+        let results = ty.results();
+        let mut values = vec![RawValue::default(); params.len().max(results.len())];
+        let first = self.values.len() - params.len();
+        for (i, ty) in params.iter().enumerate() {
+            values[i] = to_raw(self.values[first + i], *ty);
+        }
+        self.values.truncate(first);
+        HOST_CALLERS.with(|callers| {
+            callers.borrow_mut().push(HostCaller {
+                callers: &self.frames,
+                function,
+                pc,
+            })
+        });
+        (anyfunc.call_trampoline)(anyfunc.vmctx.vmctx, anyfunc.func_ptr, values.as_mut_ptr());
+        // A trap unwinds past this, and `on_wasm_stack` drops the caller
+        HOST_CALLERS.with(|callers| callers.borrow_mut().pop());
+        for (value, ty) in values.iter().zip(results) {
+            self.push(from_raw(*value, *ty));
+        }
+    }
+}
+
+/// Traps with `Interrupt` if the instance `vmctx` belongs to has been
+/// interrupted, on function entry and on branches as compiled code does
+/// on loop headers.
+unsafe fn check_interrupt(vmctx: *mut VMContext) -> Result<(), Trap> {
+    if (*vmctx).instance().interrupted() {
+        return Err(Trap::lib(TrapCode::Interrupt));
+    }
+    Ok(())
+}
+
+/// Returns the address of the `size` bytes `arg` accesses at `address`,
+/// if they are in bounds of its memory.
+unsafe fn memory_address(
+    vmctx: *mut VMContext,
+    arg: MemArg,
+    address: u32,
+    size: usize,
+) -> Result<*mut u8, Trap> {
+    let memory = (*vmctx).instance().get_memory(arg.memory);
+    let start = u64::from(address) + u64::from(arg.offset);
+    if start + size as u64 > memory.current_length as u64 {
+        return Err(Trap::lib(TrapCode::HeapAccessOutOfBounds));
+    }
+    Ok(memory.base.add(start as usize))
+}
+
+/// Converts a `RawValue` of type `ty` into a slot of the stack.
+unsafe fn from_raw(value: RawValue, ty: Type) -> u64 {
+    match ty {
+        Type::I32 | Type::F32 => u64::from(value.u32),
+        Type::FuncRef => value.funcref as u64,
+        Type::ExternRef => value.externref as u64,
+        Type::I64 | Type::F64 | Type::V128 => value.u64,
+    }
+}
+
+/// Converts a slot of the stack holding a value of type `ty` into a
+/// `RawValue`.
+pub(crate) fn to_raw(value: u64, ty: Type) -> RawValue {
+    match ty {
+        Type::I32 | Type::F32 => RawValue { u32: value as u32 },
+        Type::FuncRef => RawValue {
+            funcref: value as usize,
+        },
+        Type::ExternRef => RawValue {
+            externref: value as usize,
+        },
+        Type::I64 | Type::F64 | Type::V128 => RawValue { u64: value },
+    }
+}
+
+/// Converts a slot of the stack into an element of `table`.
+unsafe fn to_element(table: &VMTable, value: u64) -> TableElement {
+    match table.ty().ty {
+        Type::ExternRef => TableElement::ExternRef(VMExternRef::from_raw(RawValue {
+            externref: value as usize,
+        })),
+        _ => TableElement::FuncRef(VMFuncRef::from_raw(RawValue {
+            funcref: value as usize,
+        })),
+    }
+}
+
+/// Converts an element of a table into a slot of the stack.
+unsafe fn from_element(element: TableElement) -> u64 {
+    match element {
+        TableElement::FuncRef(func_ref) => func_ref.map_or(0, |f| f.into_raw().funcref as u64),
+        TableElement::ExternRef(extern_ref) => {
+            extern_ref.map_or(0, |e| e.into_raw().externref as u64)
+        }
+    }
+}
+
+fn as_i32(value: u64) -> i32 {
+    value as i32
+}
+
+fn as_u32(value: u64) -> u32 {
+    value as u32
+}
+
+fn as_i64(value: u64) -> i64 {
+    value as i64
+}
+
+fn as_u64(value: u64) -> u64 {
+    value
+}
+
+fn as_f32(value: u64) -> f32 {
+    f32::from_bits(value as u32)
+}
+
+fn as_f64(value: u64) -> f64 {
+    f64::from_bits(value)
+}
+
+fn from_i32(value: i32) -> u64 {
+    u64::from(value as u32)
+}
+
+fn from_u32(value: u32) -> u64 {
+    u64::from(value)
+}
+
+fn from_i64(value: i64) -> u64 {
+    value as u64
+}
+
+fn from_u64(value: u64) -> u64 {
+    value
+}
+
+fn from_f32(value: f32) -> u64 {
+    u64::from(value.to_bits())
+}
+
+fn from_f64(value: f64) -> u64 {
+    value.to_bits()
+}
+
+fn from_bool(value: bool) -> u64 {
+    u64::from(value)
+}
+
+/// Runs the division `op` by `y`, trapping if `y` is zero or if it
+/// overflows.
+fn divide<T: Default + PartialEq>(y: T, op: impl FnOnce() -> Option<T>) -> Result<T, Trap> {
+    if y == T::default() {
+        return Err(Trap::lib(TrapCode::IntegerDivisionByZero));
+    }
+    op().ok_or_else(|| Trap::lib(TrapCode::IntegerOverflow))
+}
+
+/// The lowest `i64`, as a float.
+const I64_MIN: f64 = -9223372036854775808.0;
+/// The first float above the highest `i64`.
+const I64_END: f64 = 9223372036854775808.0;
+/// The first float above the highest `u64`.
+const U64_END: f64 = 18446744073709551616.0;
+
+/// Truncates `x` towards zero, trapping if it isn't a number or if the
+/// result isn't in `min..end`.
+fn truncate(x: f64, min: f64, end: f64) -> Result<f64, Trap> {
+    if x.is_nan() {
+        return Err(Trap::lib(TrapCode::BadConversionToInteger));
+    }
+    let x = x.trunc();
+    if x < min || x >= end {
+        return Err(Trap::lib(TrapCode::IntegerOverflow));
+    }
+    Ok(x)
+}
+
+#[allow(clippy::float_cmp)]
+fn f32_min(x: f32, y: f32) -> f32 {
+    if x.is_nan() || y.is_nan() {
+        f32::NAN
+    } else if x == y {
+        // Makes -0 the lowest of the zeros.
+        f32::from_bits(x.to_bits() | y.to_bits())
+    } else {
+        x.min(y)
+    }
+}
+
+#[allow(clippy::float_cmp)]
+fn f32_max(x: f32, y: f32) -> f32 {
+    if x.is_nan() || y.is_nan() {
+        f32::NAN
+    } else if x == y {
+        // Makes +0 the highest of the zeros.
+        f32::from_bits(x.to_bits() & y.to_bits())
+    } else {
+        x.max(y)
+    }
+}
+
+#[allow(clippy::float_cmp)]
+fn f64_min(x: f64, y: f64) -> f64 {
+    if x.is_nan() || y.is_nan() {
+        f64::NAN
+    } else if x == y {
+        f64::from_bits(x.to_bits() | y.to_bits())
+    } else {
+        x.min(y)
+    }
+}
+
+#[allow(clippy::float_cmp)]
+fn f64_max(x: f64, y: f64) -> f64 {
+    if x.is_nan() || y.is_nan() {
+        f64::NAN
+    } else if x == y {
+        f64::from_bits(x.to_bits() & y.to_bits())
+    } else {
+        x.max(y)
+    }
+}
@@ -0,0 +1,43 @@
+//! Interrupting the Wasm code of an instance from another thread.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+/// A handle that makes the Wasm code of an instance trap with
+/// [`TrapCode::Interrupt`](wasmer_types::TrapCode::Interrupt).
+///
+/// The compiled code checks the interrupt flag when entering a function
+/// and at the start of each loop iteration, so a running guest stops at
+/// the next of these safepoints rather than immediately. Calls made
+/// while the flag is set trap the same way, until it is
+/// [`reset`](Self::reset).
+///
+/// The handle is cheap to clone and can be moved to another thread.
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle {
+    flag: Arc<AtomicU32>,
+}
+
+impl InterruptHandle {
+    /// Interrupts the Wasm code of the instance.
+    pub fn interrupt(&self) {
+        self.flag.store(1, Ordering::SeqCst);
+    }
+
+    /// Clears a previous interruption so the instance can be called
+    /// again.
+    pub fn reset(&self) {
+        self.flag.store(0, Ordering::SeqCst);
+    }
+
+    /// Returns `true` if the instance has been interrupted.
+    pub fn is_interrupted(&self) -> bool {
+        self.flag.load(Ordering::SeqCst) != 0
+    }
+
+    /// The address of the flag, which the compiled code reads through
+    /// the `VMContext`.
+    pub(crate) fn flag_ptr(&self) -> *const AtomicU32 {
+        Arc::as_ptr(&self.flag)
+    }
+}
@@ -20,14 +20,19 @@
     )
 )]
 
+mod debugger;
 mod export;
 mod extern_ref;
 mod function_env;
 mod global;
 mod imports;
 mod instance;
+mod interpreter;
+mod interrupt;
 mod memory;
+mod memory_image;
 mod mmap;
+mod pool;
 mod probestack;
 mod sig_registry;
 mod store;
@@ -36,21 +41,28 @@ mod trap;
 mod vmcontext;
 
 pub mod libcalls;
+pub mod memory_wait;
 
 use std::ptr::NonNull;
 
+pub use crate::debugger::{DebugAction, DebugHandler, DebugStop, InstanceDebugger, StopReason};
 pub use crate::export::*;
 pub use crate::extern_ref::{VMExternObj, VMExternRef};
 pub use crate::function_env::VMFunctionEnvironment;
 pub use crate::global::*;
 pub use crate::imports::Imports;
 pub use crate::instance::{InstanceAllocator, InstanceHandle};
-pub use crate::memory::{LinearMemory, VMMemory};
+pub use crate::interpreter::{interpreted_host_callers, wasmer_vm_interpreter_trampoline};
+pub use crate::interrupt::InterruptHandle;
+pub use crate::memory::{LinearMemory, MemoryGrowObserver, VMMemory};
+pub use crate::memory_image::MemoryImage;
+pub use crate::memory_wait::WaitResult;
 pub use crate::mmap::Mmap;
+pub use crate::pool::{InstancePool, PoolingConfig};
 pub use crate::probestack::PROBESTACK;
 pub use crate::sig_registry::SignatureRegistry;
 pub use crate::store::{
-    InternalStoreHandle, MaybeInstanceOwned, StoreHandle, StoreId, StoreObjects,
+    InternalStoreHandle, MaybeInstanceOwned, ResourceLimiter, StoreHandle, StoreId, StoreObjects,
 };
 pub use crate::table::{TableElement, VMTable};
 pub use crate::trap::*;
@@ -5,11 +5,17 @@
 //!
 //! `Memory` is to WebAssembly linear memories what `Table` is to WebAssembly tables.
 
-use crate::{mmap::Mmap, store::MaybeInstanceOwned, vmcontext::VMMemoryDefinition};
+use crate::{
+    memory_image::MemoryImage, mmap::Mmap, pool::InstancePool, store::MaybeInstanceOwned,
+    vmcontext::VMMemoryDefinition,
+};
 use more_asserts::assert_ge;
 use std::cell::UnsafeCell;
 use std::convert::TryInto;
+use std::fmt;
+use std::mem;
 use std::ptr::NonNull;
+use std::sync::Arc;
 use wasmer_types::{Bytes, MemoryError, MemoryStyle, MemoryType, Pages};
 
 // The memory mapped area
@@ -21,6 +27,8 @@ struct WasmMmap {
     size: Pages,
     /// The owned memory definition used by the generated code
     vm_memory_definition: MaybeInstanceOwned<VMMemoryDefinition>,
+    // The number of bytes at the start of `alloc` mapped from a memory image.
+    image_len: usize,
 }
 
 impl WasmMmap {
@@ -36,7 +44,12 @@ impl WasmMmap {
         }
     }
 
-    fn grow(&mut self, delta: Pages, conf: VMMemoryConfig) -> Result<Pages, MemoryError> {
+    fn grow(
+        &mut self,
+        delta: Pages,
+        conf: VMMemoryConfig,
+        observer: Option<&dyn MemoryGrowObserver>,
+    ) -> Result<Pages, MemoryError> {
         // Optimization of memory.grow 0 calls.
         if delta.0 == 0 {
             return Ok(self.size);
@@ -71,6 +84,15 @@ impl WasmMmap {
             });
         }
 
+        if let Some(observer) = observer {
+            if !observer.will_grow(prev_pages, new_pages) {
+                return Err(MemoryError::CouldNotGrow {
+                    current: self.size,
+                    attempted_delta: delta,
+                });
+            }
+        }
+
         let delta_bytes = delta.bytes().0;
         let prev_bytes = prev_pages.bytes().0;
         let new_bytes = new_pages.bytes().0;
@@ -94,6 +116,7 @@ impl WasmMmap {
             new_mmap.as_mut_slice()[..copy_len].copy_from_slice(&self.alloc.as_slice()[..copy_len]);
 
             self.alloc = new_mmap;
+            self.image_len = 0;
         } else if delta_bytes > 0 {
             // Make the newly allocated pages accessible.
             self.alloc
@@ -111,6 +134,10 @@ impl WasmMmap {
             md.base = self.alloc.as_mut_ptr() as _;
         }
 
+        if let Some(observer) = observer {
+            observer.did_grow(prev_pages, new_pages, self.alloc.as_mut_ptr());
+        }
+
         Ok(prev_pages)
     }
 }
@@ -149,6 +176,10 @@ pub struct VMOwnedMemory {
     mmap: WasmMmap,
     // Configuration of this memory
     config: VMMemoryConfig,
+    // The pool the allocation is returned to when dropped, if any.
+    pool: Option<InstancePool>,
+    // Told about the growth of this memory, if any.
+    observer: Option<Arc<dyn MemoryGrowObserver>>,
 }
 
 unsafe impl Send for VMOwnedMemory {}
@@ -160,7 +191,7 @@ impl VMOwnedMemory {
     /// This creates a `Memory` with owned metadata: this can be used to create a memory
     /// that will be imported into Wasm modules.
     pub fn new(memory: &MemoryType, style: &MemoryStyle) -> Result<Self, MemoryError> {
-        unsafe { Self::new_internal(memory, style, None) }
+        unsafe { Self::new_internal(memory, style, None, None) }
     }
 
     /// Create a new linear memory instance with specified minimum and maximum number of wasm pages.
@@ -175,7 +206,26 @@ impl VMOwnedMemory {
         style: &MemoryStyle,
         vm_memory_location: NonNull<VMMemoryDefinition>,
     ) -> Result<Self, MemoryError> {
-        Self::new_internal(memory, style, Some(vm_memory_location))
+        Self::new_internal(memory, style, Some(vm_memory_location), None)
+    }
+
+    /// Create a new linear memory instance with specified minimum and maximum number of wasm pages,
+    /// in a slot of the given pool.
+    ///
+    /// This creates a `Memory` with metadata owned by a VM, pointed to by
+    /// `vm_memory_location`: this can be used to create a local memory.
+    /// The memory is allocated outside of the pool if it doesn't fit in
+    /// its slots.
+    ///
+    /// # Safety
+    /// - `vm_memory_location` must point to a valid location in VM memory.
+    pub unsafe fn from_pooled_definition(
+        memory: &MemoryType,
+        style: &MemoryStyle,
+        vm_memory_location: NonNull<VMMemoryDefinition>,
+        pool: &InstancePool,
+    ) -> Result<Self, MemoryError> {
+        Self::new_internal(memory, style, Some(vm_memory_location), Some(pool))
     }
 
     /// Build a `Memory` with either self-owned or VM owned metadata.
@@ -183,6 +233,7 @@ impl VMOwnedMemory {
         memory: &MemoryType,
         style: &MemoryStyle,
         vm_memory_location: Option<NonNull<VMMemoryDefinition>>,
+        pool: Option<&InstancePool>,
     ) -> Result<Self, MemoryError> {
         if memory.minimum > Pages::max_value() {
             return Err(MemoryError::MinimumMemoryTooLarge {
@@ -222,8 +273,20 @@ impl VMOwnedMemory {
         let mapped_pages = memory.minimum;
         let mapped_bytes = mapped_pages.bytes();
 
-        let mut alloc = Mmap::accessible_reserved(mapped_bytes.0, request_bytes)
-            .map_err(MemoryError::Region)?;
+        let pooled = match pool {
+            Some(pool) => pool
+                .claim_memory(mapped_bytes.0, request_bytes)?
+                .map(|alloc| (alloc, pool.clone())),
+            None => None,
+        };
+        let (mut alloc, pool) = match pooled {
+            Some((alloc, pool)) => (alloc, Some(pool)),
+            None => (
+                Mmap::accessible_reserved(mapped_bytes.0, request_bytes)
+                    .map_err(MemoryError::Region)?,
+                None,
+            ),
+        };
         let base_ptr = alloc.as_mut_ptr();
         let mem_length = memory.minimum.bytes().0;
         let mmap = WasmMmap {
@@ -243,6 +306,7 @@ impl VMOwnedMemory {
             },
             alloc,
             size: memory.minimum,
+            image_len: 0,
         };
 
         Ok(Self {
@@ -253,10 +317,29 @@ impl VMOwnedMemory {
                 memory: *memory,
                 style: *style,
             },
+            pool,
+            observer: None,
         })
     }
 }
 
+impl Drop for VMOwnedMemory {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            if self.mmap.image_len > 0 {
+                // Decommitting a file mapping brings the file content back,
+                // so the image must be unmapped before the slot is reused.
+                let base = self.mmap.alloc.as_mut_ptr();
+                if unsafe { MemoryImage::unmap_at(base, self.mmap.image_len) }.is_err() {
+                    return;
+                }
+            }
+            let alloc = mem::replace(&mut self.mmap.alloc, Mmap::new());
+            pool.release_memory(alloc, self.mmap.size.bytes().0);
+        }
+    }
+}
+
 impl LinearMemory for VMOwnedMemory {
     /// Returns the type for this memory.
     fn ty(&self) -> MemoryType {
@@ -279,7 +362,8 @@ impl LinearMemory for VMOwnedMemory {
     /// Returns `None` if memory can't be grown by the specified amount
     /// of wasm pages.
     fn grow(&mut self, delta: Pages) -> Result<Pages, MemoryError> {
-        self.mmap.grow(delta, self.config.clone())
+        self.mmap
+            .grow(delta, self.config.clone(), self.observer.as_deref())
     }
 
     /// Return a `VMMemoryDefinition` for exposing the memory to compiled wasm code.
@@ -291,6 +375,25 @@ impl LinearMemory for VMOwnedMemory {
     fn try_clone(&self) -> Option<Box<dyn LinearMemory + 'static>> {
         None
     }
+
+    /// Maps `image` copy-on-write over the start of the memory.
+    fn map_image(&mut self, image: &MemoryImage) -> Result<bool, MemoryError> {
+        if self.mmap.image_len > 0 || image.len() > self.mmap.size.bytes().0 {
+            return Ok(false);
+        }
+        unsafe { image.map_at(self.mmap.alloc.as_mut_ptr()) }.map_err(MemoryError::Region)?;
+        self.mmap.image_len = image.len();
+        Ok(true)
+    }
+
+    /// Tells `observer` about the growth of this memory.
+    fn set_grow_observer(
+        &mut self,
+        observer: Arc<dyn MemoryGrowObserver>,
+    ) -> Result<(), MemoryError> {
+        self.observer = Some(observer);
+        Ok(())
+    }
 }
 
 impl From<VMOwnedMemory> for VMMemory {
@@ -342,6 +445,19 @@ impl LinearMemory for VMMemory {
     fn try_clone(&self) -> Option<Box<dyn LinearMemory + 'static>> {
         self.0.try_clone()
     }
+
+    /// Maps `image` copy-on-write over the start of the memory.
+    fn map_image(&mut self, image: &MemoryImage) -> Result<bool, MemoryError> {
+        self.0.map_image(image)
+    }
+
+    /// Tells `observer` about the growth of this memory.
+    fn set_grow_observer(
+        &mut self,
+        observer: Arc<dyn MemoryGrowObserver>,
+    ) -> Result<(), MemoryError> {
+        self.0.set_grow_observer(observer)
+    }
 }
 
 impl VMMemory {
@@ -373,6 +489,28 @@ impl VMMemory {
         )?)))
     }
 
+    /// Create a new linear memory instance with specified minimum and maximum number of wasm pages,
+    /// in a slot of the given pool.
+    ///
+    /// This creates a `Memory` with metadata owned by a VM, pointed to by
+    /// `vm_memory_location`: this can be used to create a local memory.
+    ///
+    /// # Safety
+    /// - `vm_memory_location` must point to a valid location in VM memory.
+    pub unsafe fn from_pooled_definition(
+        memory: &MemoryType,
+        style: &MemoryStyle,
+        vm_memory_location: NonNull<VMMemoryDefinition>,
+        pool: &InstancePool,
+    ) -> Result<VMMemory, MemoryError> {
+        Ok(Self(Box::new(VMOwnedMemory::from_pooled_definition(
+            memory,
+            style,
+            vm_memory_location,
+            pool,
+        )?)))
+    }
+
     /// Creates VMMemory from a custom implementation - the following into implementations
     /// are natively supported
     /// - VMOwnedMemory -> VMMemory
@@ -410,4 +548,48 @@ where
 
     /// Attempts to clone this memory (if its clonable)
     fn try_clone(&self) -> Option<Box<dyn LinearMemory + 'static>>;
+
+    /// Maps `image` copy-on-write over the start of the memory, replacing
+    /// the content of its first `image.len()` bytes.
+    ///
+    /// Returns `Ok(false)` if the memory can't map images, in which case it
+    /// is left untouched and the caller must initialize it by copying.
+    fn map_image(&mut self, _image: &MemoryImage) -> Result<bool, MemoryError> {
+        Ok(false)
+    }
+
+    /// Tells `observer` about the growth of this memory, replacing the
+    /// previous observer if any.
+    ///
+    /// Returns an error if the memory can't be observed.
+    fn set_grow_observer(
+        &mut self,
+        _observer: Arc<dyn MemoryGrowObserver>,
+    ) -> Result<(), MemoryError> {
+        Err(MemoryError::Generic(
+            "the growth of this memory can't be observed".to_string(),
+        ))
+    }
+}
+
+/// Observes the growth of a linear memory, be it requested by the host or
+/// by `memory.grow`, see [`LinearMemory::set_grow_observer`].
+///
+/// The same observer can be given to several memories, to keep a budget
+/// across them for example.
+pub trait MemoryGrowObserver: fmt::Debug + Send + Sync {
+    /// Called before the memory grows from `current` to `requested` pages,
+    /// once the growth is known to be within the limits of the memory.
+    ///
+    /// Returning `false` vetoes the growth, which then fails as if the
+    /// maximum of the memory was reached.
+    fn will_grow(&self, _current: Pages, _requested: Pages) -> bool {
+        true
+    }
+
+    /// Called after the memory grew from `previous` to `current` pages.
+    ///
+    /// The memory may have moved to `base`, so pointers into it held
+    /// outside of the memory must be updated.
+    fn did_grow(&self, _previous: Pages, _current: Pages, _base: *mut u8) {}
 }
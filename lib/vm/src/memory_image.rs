@@ -0,0 +1,239 @@
+//! Copy-on-write images of the initial content of linear memories.
+//!
+//! A [`MemoryImage`] holds the bytes a linear memory starts with once its
+//! data segments are applied, in an anonymous file. Instead of copying the
+//! segments on every instantiation, the file is mapped privately over the
+//! start of the memory: pages are only read in when touched, they are
+//! shared between all the instances of a module until written to, and a
+//! write only copies the page it hits.
+//!
+//! Images are only supported on Linux, where they are backed by a memfd.
+
+use std::fmt;
+
+/// The initial content of a linear memory, ready to be mapped
+/// copy-on-write into instances.
+pub struct MemoryImage {
+    #[cfg(target_os = "linux")]
+    file: std::fs::File,
+    len: usize,
+}
+
+impl MemoryImage {
+    /// Builds the image of a memory initialized with `segments`, given as
+    /// offsets into the memory along with the bytes to write there.
+    ///
+    /// Returns `Ok(None)` if there is nothing to write or if the platform
+    /// doesn't support memory images.
+    #[cfg(target_os = "linux")]
+    pub fn new<'a>(
+        segments: impl IntoIterator<Item = (usize, &'a [u8])>,
+    ) -> Result<Option<Self>, String> {
+        use std::ffi::CStr;
+        use std::io;
+        use std::os::unix::fs::FileExt;
+        use std::os::unix::io::FromRawFd;
+
+        let name = CStr::from_bytes_with_nul(b"wasmer-memory-image\0").unwrap();
+        let fd = unsafe { libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        let file = unsafe { std::fs::File::from_raw_fd(fd) };
+
+        let mut end = 0;
+        for (offset, data) in segments {
+            if data.is_empty() {
+                continue;
+            }
+            file.write_all_at(data, offset as u64)
+                .map_err(|e| e.to_string())?;
+            end = end.max(offset + data.len());
+        }
+        if end == 0 {
+            return Ok(None);
+        }
+
+        let page_size = region::page::size();
+        let len = (end + (page_size - 1)) & !(page_size - 1);
+        file.set_len(len as u64).map_err(|e| e.to_string())?;
+        Ok(Some(Self { file, len }))
+    }
+
+    /// Builds the image of a memory initialized with `segments`, given as
+    /// offsets into the memory along with the bytes to write there.
+    ///
+    /// Returns `Ok(None)` if there is nothing to write or if the platform
+    /// doesn't support memory images.
+    #[cfg(not(target_os = "linux"))]
+    pub fn new<'a>(
+        _segments: impl IntoIterator<Item = (usize, &'a [u8])>,
+    ) -> Result<Option<Self>, String> {
+        Ok(None)
+    }
+
+    /// The size in bytes of the image, a multiple of the page size.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the image is empty, which never happens for a built image.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Maps the image privately over the `len()` bytes starting at `base`.
+    ///
+    /// # Safety
+    /// `base` must be page-aligned and point to `len()` bytes of memory
+    /// owned by the caller, which are replaced by the mapping.
+    #[cfg(target_os = "linux")]
+    pub(crate) unsafe fn map_at(&self, base: *mut u8) -> Result<(), String> {
+        use std::os::unix::io::AsRawFd;
+
+        let ptr = libc::mmap(
+            base as *mut libc::c_void,
+            self.len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_FIXED,
+            self.file.as_raw_fd(),
+            0,
+        );
+        if ptr as isize == -1_isize {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(crate) unsafe fn map_at(&self, _base: *mut u8) -> Result<(), String> {
+        Err("memory images are not supported on this platform".to_string())
+    }
+
+    /// Replaces a mapping made by [`MemoryImage::map_at`] with inaccessible
+    /// anonymous memory, so the range reads as zeroes once made accessible
+    /// again.
+    ///
+    /// # Safety
+    /// `base` and `len` must describe an image mapping made by `map_at`.
+    pub(crate) unsafe fn unmap_at(base: *mut u8, len: usize) -> Result<(), String> {
+        #[cfg(target_os = "linux")]
+        {
+            let ptr = libc::mmap(
+                base as *mut libc::c_void,
+                len,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_FIXED,
+                -1,
+                0,
+            );
+            if ptr as isize == -1_isize {
+                return Err(std::io::Error::last_os_error().to_string());
+            }
+            Ok(())
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = (base, len);
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Debug for MemoryImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryImage")
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_holds_the_segments() {
+        let image = MemoryImage::new(vec![(4, &b"abc"[..]), (0x1_0000, &b"de"[..])])
+            .unwrap()
+            .unwrap();
+        assert_eq!(image.len() % region::page::size(), 0);
+        assert!(image.len() >= 0x1_0002);
+
+        let mut file = vec![0; image.len()];
+        std::os::unix::fs::FileExt::read_exact_at(&image.file, &mut file, 0).unwrap();
+        assert_eq!(&file[..8], b"\0\0\0\0abc\0");
+        assert_eq!(&file[0x1_0000..0x1_0002], b"de");
+    }
+
+    #[test]
+    fn mapped_images_are_copy_on_write() {
+        use crate::memory::VMOwnedMemory;
+        use crate::{InstancePool, LinearMemory, PoolingConfig, VMMemoryDefinition};
+        use std::ptr::NonNull;
+        use wasmer_types::{MemoryStyle, MemoryType, Pages};
+
+        let image = MemoryImage::new(vec![(8, &b"hello"[..])]).unwrap().unwrap();
+        let pool = InstancePool::new(PoolingConfig {
+            max_instances: 0,
+            max_memories: 1,
+            memory_slot_size: 0x4_0000,
+            max_tables: 0,
+            ..PoolingConfig::default()
+        })
+        .unwrap();
+        let ty = MemoryType::new(Pages(1), None, false);
+        let style = MemoryStyle::Dynamic {
+            offset_guard_size: 0,
+        };
+        let mut definition = Box::new(VMMemoryDefinition {
+            base: std::ptr::null_mut(),
+            current_length: 0,
+        });
+        fn contents(memory: &mut VMOwnedMemory) -> &mut [u8] {
+            unsafe {
+                let definition = memory.vmmemory().as_ref();
+                std::slice::from_raw_parts_mut(definition.base, definition.current_length)
+            }
+        }
+
+        let mut first = VMOwnedMemory::new(&ty, &style).unwrap();
+        let mut second = unsafe {
+            VMOwnedMemory::from_pooled_definition(
+                &ty,
+                &style,
+                NonNull::from(&mut *definition),
+                &pool,
+            )
+        }
+        .unwrap();
+        assert!(first.map_image(&image).unwrap());
+        assert!(second.map_image(&image).unwrap());
+        assert!(!second.map_image(&image).unwrap());
+
+        contents(&mut first)[8] = b'j';
+        assert_eq!(&contents(&mut first)[8..13], b"jello");
+        assert_eq!(&contents(&mut second)[8..13], b"hello");
+        assert_eq!(contents(&mut second)[0xffff], 0);
+
+        // A recycled slot doesn't keep the image.
+        drop(second);
+        let mut second = unsafe {
+            VMOwnedMemory::from_pooled_definition(
+                &ty,
+                &style,
+                NonNull::from(&mut *definition),
+                &pool,
+            )
+        }
+        .unwrap();
+        assert_eq!(&contents(&mut second)[8..13], b"\0\0\0\0\0");
+    }
+
+    #[test]
+    fn empty_segments_build_no_image() {
+        assert!(MemoryImage::new(vec![(0x1000, &b""[..])])
+            .unwrap()
+            .is_none());
+    }
+}
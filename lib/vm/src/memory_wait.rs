@@ -0,0 +1,139 @@
+//! Waiting on and notifying addresses of linear memories, with the
+//! semantics of `memory.atomic.wait32`, `memory.atomic.wait64` and
+//! `memory.atomic.notify` of the threads proposal.
+//!
+//! The waiters are kept in a process-wide table keyed by the host address
+//! they wait on, so that threads using different views of the same shared
+//! memory find each other.
+
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// The outcome of a wait, with the values `memory.atomic.wait` returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum WaitResult {
+    /// The thread was woken by a notification.
+    Woken = 0,
+    /// The value at the address wasn't the expected one.
+    NotEqual = 1,
+    /// The timeout elapsed without a notification.
+    TimedOut = 2,
+}
+
+/// The threads waiting on an address.
+#[derive(Default)]
+struct Waiters {
+    /// How many threads are waiting.
+    waiting: u32,
+    /// How many of them were notified and didn't wake up yet.
+    notified: u32,
+}
+
+lazy_static! {
+    static ref WAITERS: Mutex<HashMap<usize, Waiters>> = Mutex::new(HashMap::new());
+    static ref WOKEN: Condvar = Condvar::new();
+}
+
+/// Waits on `atomic` until it is notified or `timeout` elapses, if its
+/// value is `expected`.
+pub fn wait32(atomic: &AtomicU32, expected: u32, timeout: Option<Duration>) -> WaitResult {
+    wait(atomic as *const _ as usize, timeout, || {
+        atomic.load(Ordering::SeqCst) == expected
+    })
+}
+
+/// Waits on `atomic` until it is notified or `timeout` elapses, if its
+/// value is `expected`.
+pub fn wait64(atomic: &AtomicU64, expected: u64, timeout: Option<Duration>) -> WaitResult {
+    wait(atomic as *const _ as usize, timeout, || {
+        atomic.load(Ordering::SeqCst) == expected
+    })
+}
+
+fn wait(address: usize, timeout: Option<Duration>, is_expected: impl Fn() -> bool) -> WaitResult {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    // The value is checked with the table locked, so that a notification
+    // sent after changing it can't be missed.
+    let mut waiters = WAITERS.lock().unwrap();
+    if !is_expected() {
+        return WaitResult::NotEqual;
+    }
+    waiters.entry(address).or_default().waiting += 1;
+    loop {
+        let entry = waiters.get_mut(&address).unwrap();
+        if entry.notified > 0 {
+            entry.notified -= 1;
+            remove_waiter(&mut waiters, address);
+            return WaitResult::Woken;
+        }
+        waiters = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    remove_waiter(&mut waiters, address);
+                    return WaitResult::TimedOut;
+                }
+                WOKEN.wait_timeout(waiters, deadline - now).unwrap().0
+            }
+            None => WOKEN.wait(waiters).unwrap(),
+        };
+    }
+}
+
+fn remove_waiter(waiters: &mut HashMap<usize, Waiters>, address: usize) {
+    let entry = waiters.get_mut(&address).unwrap();
+    entry.waiting -= 1;
+    if entry.waiting == 0 {
+        waiters.remove(&address);
+    }
+}
+
+/// Wakes up to `count` of the threads waiting on the address `atomic`
+/// points to, returning how many were woken.
+pub fn notify(atomic: *const u8, count: u32) -> u32 {
+    let mut waiters = WAITERS.lock().unwrap();
+    let entry = match waiters.get_mut(&(atomic as usize)) {
+        Some(entry) => entry,
+        None => return 0,
+    };
+    let woken = count.min(entry.waiting - entry.notified);
+    entry.notified += woken;
+    if woken > 0 {
+        WOKEN.notify_all();
+    }
+    woken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn wait_and_notify() {
+        let atomic = Arc::new(AtomicU32::new(0));
+        let address = &*atomic as *const AtomicU32 as *const u8;
+
+        assert_eq!(wait32(&atomic, 1, None), WaitResult::NotEqual);
+        assert_eq!(
+            wait32(&atomic, 0, Some(Duration::from_millis(10))),
+            WaitResult::TimedOut
+        );
+        assert_eq!(notify(address, 1), 0);
+
+        let waiter = {
+            let atomic = atomic.clone();
+            thread::spawn(move || wait32(&atomic, 0, None))
+        };
+        // Notify until the waiter is registered and woken.
+        while notify(address, u32::MAX) == 0 {
+            thread::yield_now();
+        }
+        assert_eq!(waiter.join().unwrap(), WaitResult::Woken);
+    }
+}
@@ -227,6 +227,73 @@ impl Mmap {
         Ok(())
     }
 
+    /// Make the memory starting at `start` and extending for `len` bytes
+    /// inaccessible again, releasing its backing pages so that it reads as
+    /// zeroes once made accessible again. `start` and `len` must be native
+    /// page-size multiples and describe a range within `self`'s reserved memory.
+    #[cfg(not(target_os = "windows"))]
+    pub fn decommit(&mut self, start: usize, len: usize) -> Result<(), String> {
+        let page_size = region::page::size();
+        assert_eq!(start & (page_size - 1), 0);
+        assert_eq!(len & (page_size - 1), 0);
+        assert_le!(start + len, self.len);
+        if len == 0 {
+            return Ok(());
+        }
+
+        let ptr = (self.ptr + start) as *mut libc::c_void;
+        #[cfg(target_os = "linux")]
+        unsafe {
+            if libc::madvise(ptr, len, libc::MADV_DONTNEED) != 0 {
+                return Err(io::Error::last_os_error().to_string());
+            }
+            region::protect(ptr as *const u8, len, region::Protection::NONE)
+                .map_err(|e| e.to_string())
+        }
+        #[cfg(not(target_os = "linux"))]
+        unsafe {
+            // `MADV_DONTNEED` doesn't zero the pages on every platform, so
+            // map fresh ones over the range instead.
+            let r = libc::mmap(
+                ptr,
+                len,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_FIXED,
+                -1,
+                0,
+            );
+            if r as isize == -1_isize {
+                return Err(io::Error::last_os_error().to_string());
+            }
+            Ok(())
+        }
+    }
+
+    /// Make the memory starting at `start` and extending for `len` bytes
+    /// inaccessible again, releasing its backing pages so that it reads as
+    /// zeroes once made accessible again. `start` and `len` must be native
+    /// page-size multiples and describe a range within `self`'s reserved memory.
+    #[cfg(target_os = "windows")]
+    pub fn decommit(&mut self, start: usize, len: usize) -> Result<(), String> {
+        use winapi::ctypes::c_void;
+        use winapi::um::memoryapi::VirtualFree;
+        use winapi::um::winnt::MEM_DECOMMIT;
+        let page_size = region::page::size();
+        assert_eq!(start & (page_size - 1), 0);
+        assert_eq!(len & (page_size - 1), 0);
+        assert_le!(start + len, self.len);
+        if len == 0 {
+            return Ok(());
+        }
+
+        let ptr = self.ptr as *const u8;
+        if unsafe { VirtualFree(ptr.add(start) as *mut c_void, len, MEM_DECOMMIT) } == 0 {
+            return Err(io::Error::last_os_error().to_string());
+        }
+
+        Ok(())
+    }
+
     /// Return the allocated memory as a slice of u8.
     pub fn as_slice(&self) -> &[u8] {
         unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
@@ -295,4 +362,14 @@ mod tests {
         assert_eq!(round_up_to_page_size(4096, 4096), 4096);
         assert_eq!(round_up_to_page_size(4097, 4096), 8192);
     }
+
+    #[test]
+    fn test_decommit_zeroes_pages() {
+        let page_size = region::page::size();
+        let mut mmap = Mmap::accessible_reserved(page_size, 2 * page_size).unwrap();
+        mmap.as_mut_slice()[0] = 42;
+        mmap.decommit(0, page_size).unwrap();
+        mmap.make_accessible(0, page_size).unwrap();
+        assert_eq!(mmap.as_slice()[0], 0);
+    }
 }
@@ -0,0 +1,290 @@
+//! Pooling allocation of instance resources.
+//!
+//! An [`InstancePool`] reserves, once and for all, a fixed number of slots
+//! for linear memories, table elements and `VMContext`s. Instantiating a
+//! module then just claims free slots instead of mapping fresh memory, and
+//! dropping the instance decommits the slots and hands them back to the pool.
+
+use crate::mmap::Mmap;
+use crate::table::RawTableElement;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use wasmer_types::MemoryError;
+
+/// The configuration of an [`InstancePool`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolingConfig {
+    /// The maximum number of instances alive at the same time, which is
+    /// the number of `VMContext` slots.
+    pub max_instances: usize,
+    /// The size in bytes of a `VMContext` slot. Instances of modules that
+    /// need a bigger `VMContext` are allocated outside of the pool.
+    pub vmctx_slot_size: usize,
+    /// The number of linear memory slots.
+    pub max_memories: usize,
+    /// The size in bytes reserved for a linear memory slot, guard pages
+    /// included. Memories whose style needs a bigger reservation are
+    /// allocated outside of the pool.
+    pub memory_slot_size: usize,
+    /// The number of table slots.
+    pub max_tables: usize,
+    /// The number of elements reserved for every table slot. Tables can
+    /// still grow past it, but then they reallocate.
+    pub table_elements: u32,
+}
+
+impl Default for PoolingConfig {
+    fn default() -> Self {
+        Self {
+            max_instances: 100,
+            vmctx_slot_size: 0x10_0000,
+            max_memories: 100,
+            // Enough for the static heaps of `BaseTunables`: a 4 GiB
+            // bound followed by a 2 GiB guard.
+            #[cfg(target_pointer_width = "64")]
+            memory_slot_size: 0x1_8001_0000,
+            #[cfg(not(target_pointer_width = "64"))]
+            memory_slot_size: 0x400_0000 + 0x2_0000,
+            max_tables: 100,
+            table_elements: 10_000,
+        }
+    }
+}
+
+/// A pool of pre-reserved memory for instances.
+///
+/// The pool is cheap to clone: clones share the same slots.
+#[derive(Clone)]
+pub struct InstancePool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    config: PoolingConfig,
+    vmctxs: Mutex<Vec<Mmap>>,
+    memories: Mutex<Vec<Mmap>>,
+    tables: Mutex<Vec<Vec<RawTableElement>>>,
+}
+
+// The table slots held by the pool are always empty, so they don't share
+// any reference.
+unsafe impl Send for PoolInner {}
+unsafe impl Sync for PoolInner {}
+
+impl InstancePool {
+    /// Reserves all the slots described by `config`.
+    pub fn new(mut config: PoolingConfig) -> Result<Self, String> {
+        let page_size = region::page::size();
+        config.vmctx_slot_size = round_up(config.vmctx_slot_size, page_size);
+        config.memory_slot_size = round_up(config.memory_slot_size, page_size);
+
+        let vmctxs = (0..config.max_instances)
+            .map(|_| Mmap::accessible_reserved(0, config.vmctx_slot_size))
+            .collect::<Result<Vec<_>, _>>()?;
+        let memories = (0..config.max_memories)
+            .map(|_| Mmap::accessible_reserved(0, config.memory_slot_size))
+            .collect::<Result<Vec<_>, _>>()?;
+        let tables = (0..config.max_tables)
+            .map(|_| Vec::with_capacity(config.table_elements as usize))
+            .collect();
+
+        Ok(Self {
+            inner: Arc::new(PoolInner {
+                config,
+                vmctxs: Mutex::new(vmctxs),
+                memories: Mutex::new(memories),
+                tables: Mutex::new(tables),
+            }),
+        })
+    }
+
+    /// The configuration of this pool, with slot sizes rounded up to
+    /// the page size.
+    pub fn config(&self) -> &PoolingConfig {
+        &self.inner.config
+    }
+
+    /// The number of free `VMContext` slots.
+    pub fn available_instances(&self) -> usize {
+        self.inner.vmctxs.lock().unwrap().len()
+    }
+
+    /// The number of free linear memory slots.
+    pub fn available_memories(&self) -> usize {
+        self.inner.memories.lock().unwrap().len()
+    }
+
+    /// The number of free table slots.
+    pub fn available_tables(&self) -> usize {
+        self.inner.tables.lock().unwrap().len()
+    }
+
+    /// Claims a `VMContext` slot with `size` accessible bytes.
+    ///
+    /// Returns `Ok(None)` if `size` doesn't fit in a slot.
+    pub(crate) fn claim_vmctx(&self, size: usize) -> Result<Option<Mmap>, String> {
+        let size = round_up(size, region::page::size());
+        if size > self.inner.config.vmctx_slot_size {
+            return Ok(None);
+        }
+        let mut slot = self
+            .inner
+            .vmctxs
+            .lock()
+            .unwrap()
+            .pop()
+            .ok_or_else(|| "the instance pool has no instance slot left".to_string())?;
+        if let Err(e) = slot.make_accessible(0, size) {
+            self.inner.vmctxs.lock().unwrap().push(slot);
+            return Err(e);
+        }
+        Ok(Some(slot))
+    }
+
+    /// Decommits the first `size` bytes of a `VMContext` slot and returns it
+    /// to the pool.
+    pub(crate) fn release_vmctx(&self, slot: Mmap, size: usize) {
+        Self::release(&self.inner.vmctxs, slot, size);
+    }
+
+    /// Claims a linear memory slot, reserving `reserved` bytes of which
+    /// `accessible` bytes are accessible.
+    ///
+    /// Returns `Ok(None)` if the reservation doesn't fit in a slot.
+    pub(crate) fn claim_memory(
+        &self,
+        accessible: usize,
+        reserved: usize,
+    ) -> Result<Option<Mmap>, MemoryError> {
+        if reserved > self.inner.config.memory_slot_size {
+            return Ok(None);
+        }
+        let mut slot = self.inner.memories.lock().unwrap().pop().ok_or_else(|| {
+            MemoryError::Region("the instance pool has no memory slot left".to_string())
+        })?;
+        if accessible > 0 {
+            if let Err(e) = slot.make_accessible(0, accessible) {
+                self.inner.memories.lock().unwrap().push(slot);
+                return Err(MemoryError::Region(e));
+            }
+        }
+        Ok(Some(slot))
+    }
+
+    /// Decommits the first `size` bytes of a linear memory slot and returns
+    /// it to the pool.
+    pub(crate) fn release_memory(&self, slot: Mmap, size: usize) {
+        if slot.len() == self.inner.config.memory_slot_size {
+            Self::release(&self.inner.memories, slot, size);
+        } else if let Ok(slot) = Mmap::accessible_reserved(0, self.inner.config.memory_slot_size) {
+            // The memory outgrew its slot and moved, so replace the slot.
+            self.inner.memories.lock().unwrap().push(slot);
+        }
+    }
+
+    /// Claims a table slot with `len` elements.
+    pub(crate) fn claim_table(&self, len: usize) -> Result<Vec<RawTableElement>, String> {
+        let mut vec = self
+            .inner
+            .tables
+            .lock()
+            .unwrap()
+            .pop()
+            .ok_or_else(|| "the instance pool has no table slot left".to_string())?;
+        vec.resize(len, RawTableElement::default());
+        Ok(vec)
+    }
+
+    /// Clears a table slot and returns it to the pool.
+    pub(crate) fn release_table(&self, mut vec: Vec<RawTableElement>) {
+        vec.clear();
+        if vec.capacity() > self.inner.config.table_elements as usize {
+            vec.shrink_to(self.inner.config.table_elements as usize);
+        }
+        self.inner.tables.lock().unwrap().push(vec);
+    }
+
+    fn release(slots: &Mutex<Vec<Mmap>>, mut slot: Mmap, size: usize) {
+        let size = round_up(size, region::page::size()).min(slot.len());
+        // A slot that can't be decommitted can't be handed out safely
+        // again, so it is dropped instead.
+        if slot.decommit(0, size).is_ok() {
+            slots.lock().unwrap().push(slot);
+        }
+    }
+}
+
+impl fmt::Debug for InstancePool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstancePool")
+            .field("config", &self.inner.config)
+            .field("available_instances", &self.available_instances())
+            .field("available_memories", &self.available_memories())
+            .field("available_tables", &self.available_tables())
+            .finish()
+    }
+}
+
+fn round_up(size: usize, page_size: usize) -> usize {
+    (size + (page_size - 1)) & !(page_size - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_config() -> PoolingConfig {
+        PoolingConfig {
+            max_instances: 2,
+            vmctx_slot_size: 0x1_0000,
+            max_memories: 2,
+            memory_slot_size: 0x4_0000,
+            max_tables: 1,
+            table_elements: 16,
+        }
+    }
+
+    #[test]
+    fn slots_are_recycled() {
+        let pool = InstancePool::new(small_config()).unwrap();
+        let mut memory = pool.claim_memory(0x1_0000, 0x2_0000).unwrap().unwrap();
+        assert_eq!(pool.available_memories(), 1);
+        memory.as_mut_slice()[0] = 1;
+        pool.release_memory(memory, 0x1_0000);
+        assert_eq!(pool.available_memories(), 2);
+
+        let memory = pool.claim_memory(0x1_0000, 0x2_0000).unwrap().unwrap();
+        assert_eq!(memory.as_slice()[0], 0);
+        pool.release_memory(memory, 0x1_0000);
+    }
+
+    #[test]
+    fn requests_filling_a_slot_are_pooled() {
+        let pool = InstancePool::new(small_config()).unwrap();
+        let memory = pool.claim_memory(0, 0x4_0000).unwrap().unwrap();
+        let vmctx = pool.claim_vmctx(0x1_0000).unwrap().unwrap();
+        assert_eq!(pool.available_memories(), 1);
+        assert_eq!(pool.available_instances(), 1);
+        pool.release_memory(memory, 0);
+        pool.release_vmctx(vmctx, 0x1_0000);
+    }
+
+    #[test]
+    fn oversized_requests_are_not_pooled() {
+        let pool = InstancePool::new(small_config()).unwrap();
+        assert!(pool.claim_memory(0, 0x4_0001).unwrap().is_none());
+        assert!(pool.claim_vmctx(0x1_0001).unwrap().is_none());
+        assert_eq!(pool.available_memories(), 2);
+        assert_eq!(pool.available_instances(), 2);
+    }
+
+    #[test]
+    fn exhaustion_is_an_error() {
+        let pool = InstancePool::new(small_config()).unwrap();
+        let table = pool.claim_table(4).unwrap();
+        assert_eq!(table.len(), 4);
+        assert!(pool.claim_table(4).is_err());
+        pool.release_table(table);
+        assert_eq!(pool.available_tables(), 1);
+    }
+}
@@ -9,7 +9,11 @@ use std::{
 
 use crate::VMExternObj;
 
-use crate::{InstanceHandle, VMFunction, VMFunctionEnvironment, VMGlobal, VMMemory, VMTable};
+use crate::{
+    InstanceHandle, LinearMemory, TableElement, VMFunction, VMFunctionEnvironment, VMGlobal,
+    VMMemory, VMTable,
+};
+use wasmer_types::{MemoryError, MemoryType, Pages, TableType};
 
 /// Unique ID to identify a context.
 ///
@@ -59,6 +63,51 @@ impl_context_object! {
     function_environments => VMFunctionEnvironment,
 }
 
+/// Limits the resources used by the objects of a context, to enforce
+/// quotas on the instances of a store.
+///
+/// It is consulted before the memories and the tables of the context
+/// grow, be it requested by the host or by the instances, and before
+/// instances, memories and tables are created in it. The implementor holds
+/// whatever state it needs to decide, like the resources used so far.
+pub trait ResourceLimiter: Send {
+    /// Whether an instance can be created.
+    fn instance_creating(&mut self) -> bool {
+        true
+    }
+
+    /// Whether a memory of type `ty` can be created.
+    fn memory_creating(&mut self, _ty: &MemoryType) -> bool {
+        true
+    }
+
+    /// Whether a table of type `ty` can be created.
+    fn table_creating(&mut self, _ty: &TableType) -> bool {
+        true
+    }
+
+    /// Whether a memory can grow from `current` to `desired` pages, within
+    /// its own `maximum`.
+    ///
+    /// A denied growth fails as if the maximum was reached.
+    fn memory_growing(
+        &mut self,
+        _current: Pages,
+        _desired: Pages,
+        _maximum: Option<Pages>,
+    ) -> bool {
+        true
+    }
+
+    /// Whether a table can grow from `current` to `desired` elements,
+    /// within its own `maximum`.
+    ///
+    /// A denied growth fails as if the maximum was reached.
+    fn table_growing(&mut self, _current: u32, _desired: u32, _maximum: Option<u32>) -> bool {
+        true
+    }
+}
+
 /// Set of objects managed by a context.
 #[derive(Default)]
 pub struct StoreObjects {
@@ -70,6 +119,7 @@ pub struct StoreObjects {
     instances: Vec<InstanceHandle>,
     extern_objs: Vec<VMExternObj>,
     function_environments: Vec<VMFunctionEnvironment>,
+    limiter: Option<Box<dyn ResourceLimiter>>,
 }
 
 impl StoreObjects {
@@ -78,6 +128,63 @@ impl StoreObjects {
         self.id
     }
 
+    /// Sets the limiter consulted before the objects of this context are
+    /// created or grow, replacing the previous one if any.
+    pub fn set_limiter(&mut self, limiter: Option<Box<dyn ResourceLimiter>>) {
+        self.limiter = limiter;
+    }
+
+    /// Returns the limiter of this context, if any.
+    pub fn limiter_mut(&mut self) -> Option<&mut (dyn ResourceLimiter + 'static)> {
+        self.limiter.as_deref_mut()
+    }
+
+    /// Grows a memory of this context by `delta` pages, if the limiter
+    /// allows it, returning its previous size.
+    pub fn grow_memory(
+        &mut self,
+        memory: InternalStoreHandle<VMMemory>,
+        delta: Pages,
+    ) -> Result<Pages, MemoryError> {
+        if delta.0 > 0 {
+            let vm_memory = memory.get(self);
+            let current = vm_memory.size();
+            let maximum = vm_memory.ty().maximum;
+            if let (Some(limiter), Some(desired)) =
+                (self.limiter.as_mut(), current.checked_add(delta))
+            {
+                if !limiter.memory_growing(current, desired, maximum) {
+                    return Err(MemoryError::CouldNotGrow {
+                        current,
+                        attempted_delta: delta,
+                    });
+                }
+            }
+        }
+        memory.get_mut(self).grow(delta)
+    }
+
+    /// Grows a table of this context by `delta` elements set to `init`, if
+    /// the limiter allows it, returning its previous size.
+    pub fn grow_table(
+        &mut self,
+        table: InternalStoreHandle<VMTable>,
+        delta: u32,
+        init: TableElement,
+    ) -> Option<u32> {
+        if delta > 0 {
+            let vm_table = table.get(self);
+            let current = vm_table.size();
+            let maximum = vm_table.ty().maximum;
+            if let Some(limiter) = self.limiter.as_mut() {
+                if !limiter.table_growing(current, current.checked_add(delta)?, maximum) {
+                    return None;
+                }
+            }
+        }
+        table.get_mut(self).grow(delta, init)
+    }
+
     /// Returns a pair of mutable references from two handles.
     ///
     /// Panics if both handles point to the same object.
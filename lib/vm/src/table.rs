@@ -5,6 +5,7 @@
 //!
 //! `Table` is to WebAssembly tables what `Memory` is to WebAssembly linear memories.
 
+use crate::pool::InstancePool;
 use crate::store::MaybeInstanceOwned;
 use crate::vmcontext::VMTableDefinition;
 use crate::Trap;
@@ -13,6 +14,7 @@ use crate::VMFuncRef;
 use std::cell::UnsafeCell;
 use std::convert::TryFrom;
 use std::fmt;
+use std::mem;
 use std::ptr::NonNull;
 use wasmer_types::TableStyle;
 use wasmer_types::{TableType, TrapCode, Type as ValType};
@@ -77,6 +79,8 @@ pub struct VMTable {
     /// Our chosen implementation style.
     style: TableStyle,
     vm_table_definition: MaybeInstanceOwned<VMTableDefinition>,
+    /// The pool the elements are returned to when dropped, if any.
+    pool: Option<InstancePool>,
 }
 
 impl VMTable {
@@ -85,7 +89,7 @@ impl VMTable {
     /// This creates a `Table` with metadata owned by a VM, pointed to by
     /// `vm_table_location`: this can be used to create a local table.
     pub fn new(table: &TableType, style: &TableStyle) -> Result<Self, String> {
-        unsafe { Self::new_inner(table, style, None) }
+        unsafe { Self::new_inner(table, style, None, None) }
     }
 
     /// Create a new linear table instance with specified minimum and maximum number of elements.
@@ -100,7 +104,24 @@ impl VMTable {
         style: &TableStyle,
         vm_table_location: NonNull<VMTableDefinition>,
     ) -> Result<Self, String> {
-        Self::new_inner(table, style, Some(vm_table_location))
+        Self::new_inner(table, style, Some(vm_table_location), None)
+    }
+
+    /// Create a new linear table instance with specified minimum and maximum number of elements,
+    /// in a slot of the given pool.
+    ///
+    /// This creates a `Table` with metadata owned by a VM, pointed to by
+    /// `vm_table_location`: this can be used to create a local table.
+    ///
+    /// # Safety
+    /// - `vm_table_location` must point to a valid location in VM memory.
+    pub unsafe fn from_pooled_definition(
+        table: &TableType,
+        style: &TableStyle,
+        vm_table_location: NonNull<VMTableDefinition>,
+        pool: &InstancePool,
+    ) -> Result<Self, String> {
+        Self::new_inner(table, style, Some(vm_table_location), Some(pool))
     }
 
     /// Create a new `Table` with either self-owned or VM owned metadata.
@@ -108,6 +129,7 @@ impl VMTable {
         table: &TableType,
         style: &TableStyle,
         vm_table_location: Option<NonNull<VMTableDefinition>>,
+        pool: Option<&InstancePool>,
     ) -> Result<Self, String> {
         match table.ty {
             ValType::FuncRef | ValType::ExternRef => (),
@@ -128,7 +150,10 @@ impl VMTable {
         }
         let table_minimum = usize::try_from(table.minimum)
             .map_err(|_| "Table minimum is bigger than usize".to_string())?;
-        let mut vec = vec![RawTableElement::default(); table_minimum];
+        let mut vec = match pool {
+            Some(pool) => pool.claim_table(table_minimum)?,
+            None => vec![RawTableElement::default(); table_minimum],
+        };
         let base = vec.as_mut_ptr();
         match style {
             TableStyle::CallerChecksSignature => Ok(Self {
@@ -150,6 +175,7 @@ impl VMTable {
                         current_elements: table_minimum as _,
                     })))
                 },
+                pool: pool.cloned(),
             }),
         }
     }
@@ -338,3 +364,11 @@ impl VMTable {
         Ok(())
     }
 }
+
+impl Drop for VMTable {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.release_table(mem::take(&mut self.vec));
+        }
+    }
+}
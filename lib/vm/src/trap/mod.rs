@@ -10,8 +10,9 @@ mod traphandlers;
 
 pub use trap::Trap;
 pub use traphandlers::{
-    catch_traps, on_host_stack, raise_lib_trap, raise_user_trap, wasmer_call_trampoline,
-    TrapHandler, TrapHandlerFn,
+    block_on_future, catch_traps, handle_wasm_trap, is_wasm_trap, on_host_stack, raise_lib_trap,
+    raise_user_trap, wasmer_call_trampoline, AsyncCall, TrapHandler, TrapHandlerFn, TrapHandlers,
+    DEFAULT_STACK_SIZE,
 };
 pub use traphandlers::{init_traps, resume_panic};
 pub use wasmer_types::TrapCode;
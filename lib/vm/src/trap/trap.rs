@@ -30,6 +30,19 @@ pub enum Trap {
         backtrace: Backtrace,
     },
 
+    /// A trap raised by the interpreter of the VM
+    ///
+    /// Note: this trap is deterministic (assuming a deterministic host implementation)
+    Interpreter {
+        /// Code of the trap.
+        trap_code: TrapCode,
+        /// The addresses of the instructions running in the interpreted
+        /// frames, from the one that trapped to the outermost caller.
+        pcs: Vec<usize>,
+        /// Native stack backtrace at the time the trap occurred
+        backtrace: Backtrace,
+    },
+
     /// A trap indicating that the runtime was unable to allocate sufficient memory.
     ///
     /// Note: this trap is nondeterministic, since it depends on the host system.
@@ -4,24 +4,29 @@
 //! WebAssembly trap handling, which is built on top of the lower-level
 //! signalhandling mechanisms.
 
+use crate::interpreter::{num_host_callers, restore_host_callers, take_host_callers, HostCaller};
 use crate::vmcontext::{VMFunctionContext, VMTrampoline};
 use crate::{Trap, VMFunctionBody};
 use backtrace::Backtrace;
 use core::ptr::{read, read_unaligned};
 use corosensei::stack::DefaultStack;
 use corosensei::trap::{CoroutineTrapHandler, TrapHandlerRegs};
-use corosensei::{CoroutineResult, ScopedCoroutine, Yielder};
+use corosensei::{Coroutine, CoroutineResult, ScopedCoroutine, Yielder};
 use scopeguard::defer;
 use std::any::Any;
 use std::cell::Cell;
+use std::collections::HashMap;
 use std::error::Error;
+use std::future::Future;
 use std::io;
 use std::mem;
 #[cfg(unix)]
 use std::mem::MaybeUninit;
+use std::panic::{self, AssertUnwindSafe};
 use std::ptr::{self, NonNull};
 use std::sync::atomic::{compiler_fence, AtomicPtr, Ordering};
 use std::sync::{Mutex, Once};
+use std::task::{Context, Poll};
 use wasmer_types::TrapCode;
 
 // TrapInformation can be stored in the "Undefined Instruction" itself.
@@ -82,6 +87,9 @@ unsafe fn process_illegal_op(addr: usize) -> Option<TrapCode> {
             9 => Some(TrapCode::BadConversionToInteger),
             10 => Some(TrapCode::UnreachableCodeReached),
             11 => Some(TrapCode::UnalignedAtomic),
+            12 => Some(TrapCode::Interrupt),
+            13 => Some(TrapCode::OutOfGas),
+            14 => Some(TrapCode::Watchpoint),
             _ => None,
         },
     }
@@ -109,8 +117,31 @@ cfg_if::cfg_if! {
         static mut PREV_SIGILL: MaybeUninit<libc::sigaction> = MaybeUninit::uninit();
         static mut PREV_SIGFPE: MaybeUninit<libc::sigaction> = MaybeUninit::uninit();
 
+        /// The signals the trap handler is registered for, with the slots
+        /// of the handlers it forwards the other signals to.
+        unsafe fn trap_signals() -> Vec<(libc::c_int, *mut MaybeUninit<libc::sigaction>)> {
+            // Allow handling OOB with signals on all architectures
+            let mut signals = vec![(libc::SIGSEGV, &mut PREV_SIGSEGV as *mut _)];
+
+            // Handle `unreachable` instructions which execute `ud2` right now
+            signals.push((libc::SIGILL, &mut PREV_SIGILL as *mut _));
+
+            // x86 uses SIGFPE to report division by zero
+            if cfg!(target_arch = "x86") || cfg!(target_arch = "x86_64") {
+                signals.push((libc::SIGFPE, &mut PREV_SIGFPE as *mut _));
+            }
+
+            // On ARM, handle Unaligned Accesses.
+            // On Darwin, guard page accesses are raised as SIGBUS.
+            if cfg!(target_arch = "arm") || cfg!(target_vendor = "apple") {
+                signals.push((libc::SIGBUS, &mut PREV_SIGBUS as *mut _));
+            }
+
+            signals
+        }
+
         unsafe fn platform_init() {
-            let register = |slot: &mut MaybeUninit<libc::sigaction>, signal: i32| {
+            let register = |slot: *mut MaybeUninit<libc::sigaction>, signal: i32| {
                 let mut handler: libc::sigaction = mem::zeroed();
                 // The flags here are relatively careful, and they are...
                 //
@@ -128,7 +159,7 @@ cfg_if::cfg_if! {
                 handler.sa_flags = libc::SA_SIGINFO | libc::SA_NODEFER | libc::SA_ONSTACK;
                 handler.sa_sigaction = trap_handler as usize;
                 libc::sigemptyset(&mut handler.sa_mask);
-                if libc::sigaction(signal, &handler, slot.as_mut_ptr()) != 0 {
+                if libc::sigaction(signal, &handler, (*slot).as_mut_ptr()) != 0 {
                     panic!(
                         "unable to install signal handler: {}",
                         io::Error::last_os_error(),
@@ -136,21 +167,8 @@ cfg_if::cfg_if! {
                 }
             };
 
-            // Allow handling OOB with signals on all architectures
-            register(&mut PREV_SIGSEGV, libc::SIGSEGV);
-
-            // Handle `unreachable` instructions which execute `ud2` right now
-            register(&mut PREV_SIGILL, libc::SIGILL);
-
-            // x86 uses SIGFPE to report division by zero
-            if cfg!(target_arch = "x86") || cfg!(target_arch = "x86_64") {
-                register(&mut PREV_SIGFPE, libc::SIGFPE);
-            }
-
-            // On ARM, handle Unaligned Accesses.
-            // On Darwin, guard page accesses are raised as SIGBUS.
-            if cfg!(target_arch = "arm") || cfg!(target_vendor = "apple") {
-                register(&mut PREV_SIGBUS, libc::SIGBUS);
+            for (signal, slot) in trap_signals() {
+                register(slot, signal);
             }
 
             // This is necessary to support debugging under LLDB on Darwin.
@@ -191,6 +209,26 @@ cfg_if::cfg_if! {
             }
         }
 
+        /// Restores the handlers the trap handler was registered over, and
+        /// returns whether it could: handlers registered over it since may
+        /// forward signals to it, so it stays registered for all the
+        /// signals if it isn't the current handler of one of them.
+        unsafe fn platform_uninit() -> bool {
+            let signals = trap_signals();
+            for &(signal, _) in &signals {
+                let mut current: libc::sigaction = mem::zeroed();
+                if libc::sigaction(signal, ptr::null(), &mut current) != 0
+                    || current.sa_sigaction != trap_handler as usize
+                {
+                    return false;
+                }
+            }
+            for (signal, slot) in signals {
+                libc::sigaction(signal, (*slot).as_ptr(), ptr::null_mut());
+            }
+            true
+        }
+
         unsafe extern "C" fn trap_handler(
             signum: libc::c_int,
             siginfo: *mut libc::siginfo_t,
@@ -203,33 +241,7 @@ cfg_if::cfg_if! {
                 libc::SIGILL => &PREV_SIGILL,
                 _ => panic!("unknown signal: {}", signum),
             };
-            // We try to get the fault address associated to this signal
-            let maybe_fault_address = match signum {
-                libc::SIGSEGV | libc::SIGBUS => {
-                    Some((*siginfo).si_addr() as usize)
-                }
-                _ => None,
-            };
-            let trap_code = match signum {
-                // check if it was cased by a UD and if the Trap info is a payload to it
-                libc::SIGILL => {
-                    let addr = (*siginfo).si_addr() as usize;
-                    process_illegal_op(addr)
-                }
-                _ => None,
-            };
-            let ucontext = &mut *(context as *mut libc::ucontext_t);
-            let (pc, sp) = get_pc_sp(ucontext);
-            let handled = TrapHandlerContext::handle_trap(
-                pc,
-                sp,
-                maybe_fault_address,
-                trap_code,
-                |regs| update_context(ucontext, regs),
-                |handler| handler(signum, siginfo, context),
-            );
-
-            if handled {
+            if handle_wasm_trap(signum, siginfo, context) {
                 return;
             }
 
@@ -258,6 +270,66 @@ cfg_if::cfg_if! {
             }
         }
 
+        /// Handles a signal raised by Wasm code running on this thread,
+        /// for embedders whose own signal handler runs before the one of
+        /// wasmer, and returns whether it did.
+        ///
+        /// When it returns `true`, the context was updated to unwind the
+        /// Wasm code, and the signal handler must return right away.
+        /// Otherwise the signal isn't a Wasm trap, and the embedder can
+        /// handle it as any other signal.
+        ///
+        /// # Safety
+        ///
+        /// Must only be called from a `SA_SIGINFO` signal handler, with
+        /// its arguments.
+        pub unsafe fn handle_wasm_trap(
+            signum: libc::c_int,
+            siginfo: *mut libc::siginfo_t,
+            context: *mut libc::c_void,
+        ) -> bool {
+            match signum {
+                libc::SIGSEGV | libc::SIGBUS | libc::SIGFPE | libc::SIGILL => {}
+                _ => return false,
+            }
+            // We try to get the fault address associated to this signal
+            let maybe_fault_address = match signum {
+                libc::SIGSEGV | libc::SIGBUS => {
+                    Some((*siginfo).si_addr() as usize)
+                }
+                _ => None,
+            };
+            let trap_code = match signum {
+                // check if it was cased by a UD and if the Trap info is a payload to it
+                libc::SIGILL => {
+                    let addr = (*siginfo).si_addr() as usize;
+                    process_illegal_op(addr)
+                }
+                _ => None,
+            };
+            let ucontext = &mut *(context as *mut libc::ucontext_t);
+            let (pc, sp) = get_pc_sp(ucontext);
+            TrapHandlerContext::handle_trap(
+                pc,
+                sp,
+                maybe_fault_address,
+                trap_code,
+                |regs| update_context(ucontext, regs),
+                |handler| handler(signum, siginfo, context),
+            )
+        }
+
+        /// Whether a signal was raised by Wasm code running on this thread,
+        /// from the context passed to a `SA_SIGINFO` signal handler.
+        ///
+        /// # Safety
+        ///
+        /// `context` must be the `ucontext_t` of the signal being handled.
+        pub unsafe fn is_wasm_trap(context: *const libc::c_void) -> bool {
+            let (_, sp) = get_pc_sp(&*(context as *const libc::ucontext_t));
+            TrapHandlerContext::is_wasm_stack(sp)
+        }
+
         unsafe fn get_pc_sp(context: &libc::ucontext_t) -> (usize, usize) {
             let (pc, sp);
             cfg_if::cfg_if! {
@@ -426,18 +498,47 @@ cfg_if::cfg_if! {
         use winapi::um::minwinbase::*;
         use winapi::vc::excpt::*;
 
+        /// The handle of the registered exception handler.
+        static mut EXCEPTION_HANDLER: PVOID = ptr::null_mut();
+
         unsafe fn platform_init() {
             // our trap handler needs to go first, so that we can recover from
             // wasm faults and continue execution, so pass `1` as a true value
             // here.
-            if AddVectoredExceptionHandler(1, Some(exception_handler)).is_null() {
+            EXCEPTION_HANDLER = AddVectoredExceptionHandler(1, Some(exception_handler));
+            if EXCEPTION_HANDLER.is_null() {
                 panic!("failed to add exception handler: {}", io::Error::last_os_error());
             }
         }
 
+        /// Removes the exception handler, and returns whether it could.
+        unsafe fn platform_uninit() -> bool {
+            RemoveVectoredExceptionHandler(EXCEPTION_HANDLER) != 0
+        }
+
         unsafe extern "system" fn exception_handler(
             exception_info: PEXCEPTION_POINTERS
         ) -> LONG {
+            if handle_wasm_trap(exception_info) {
+                EXCEPTION_CONTINUE_EXECUTION
+            } else {
+                EXCEPTION_CONTINUE_SEARCH
+            }
+        }
+
+        /// Handles an exception raised by Wasm code running on this thread,
+        /// for embedders whose own exception handler runs before the one of
+        /// wasmer, and returns whether it did.
+        ///
+        /// When it returns `true`, the context was updated to unwind the
+        /// Wasm code, and the handler must return
+        /// `EXCEPTION_CONTINUE_EXECUTION` right away.
+        ///
+        /// # Safety
+        ///
+        /// Must only be called from a vectored exception handler, with its
+        /// argument.
+        pub unsafe fn handle_wasm_trap(exception_info: PEXCEPTION_POINTERS) -> bool {
             // Check the kind of exception, since we only handle a subset within
             // wasm code. If anything else happens we want to defer to whatever
             // the rest of the system wants to do for this exception.
@@ -448,7 +549,7 @@ cfg_if::cfg_if! {
                 record.ExceptionCode != EXCEPTION_INT_DIVIDE_BY_ZERO &&
                 record.ExceptionCode != EXCEPTION_INT_OVERFLOW
             {
-                return EXCEPTION_CONTINUE_SEARCH;
+                return false;
             }
 
             // FIXME: this is what the previous C++ did to make sure that TLS
@@ -481,20 +582,25 @@ cfg_if::cfg_if! {
             };
             // This is basically the same as the unix version above, only with a
             // few parameters tweaked here and there.
-            let handled = TrapHandlerContext::handle_trap(
+            TrapHandlerContext::handle_trap(
                 pc,
                 sp,
                 maybe_fault_address,
                 trap_code,
                 |regs| update_context(context, regs),
                 |handler| handler(exception_info),
-            );
+            )
+        }
 
-            if handled {
-                EXCEPTION_CONTINUE_EXECUTION
-            } else {
-                EXCEPTION_CONTINUE_SEARCH
-            }
+        /// Whether an exception was raised by Wasm code running on this
+        /// thread.
+        ///
+        /// # Safety
+        ///
+        /// `exception_info` must be the one of the exception being handled.
+        pub unsafe fn is_wasm_trap(exception_info: PEXCEPTION_POINTERS) -> bool {
+            let (_, sp) = get_pc_sp(&*(*exception_info).ContextRecord);
+            TrapHandlerContext::is_wasm_stack(sp)
         }
 
         unsafe fn get_pc_sp(context: &CONTEXT) -> (usize, usize) {
@@ -544,14 +650,70 @@ cfg_if::cfg_if! {
 /// This function must not only be called globally once before entering
 /// WebAssembly but it must also be called once-per-thread that enters
 /// WebAssembly. Currently in wasmer's integration this function is called on
-/// creation of a `Store`.
+/// creation of a `Store`, unless its engine scopes the handlers to its
+/// lifetime with [`TrapHandlers`].
+///
+/// The handlers stay installed until the process exits.
 pub fn init_traps() {
     static INIT: Once = Once::new();
-    INIT.call_once(|| unsafe {
-        platform_init();
+    INIT.call_once(|| mem::forget(TrapHandlers::install()));
+}
+
+lazy_static::lazy_static! {
+    static ref TRAP_HANDLERS: Mutex<TrapHandlersState> = Mutex::new(TrapHandlersState {
+        users: 0,
+        installed: false,
     });
 }
 
+struct TrapHandlersState {
+    /// The number of [`TrapHandlers`] alive
+    users: usize,
+    /// Whether the handlers are registered
+    installed: bool,
+}
+
+/// Keeps the signal handlers (or the exception handler on Windows) of
+/// wasmer installed while it is alive.
+///
+/// The handlers are installed by the first `TrapHandlers` and removed
+/// when the last one is dropped, restoring the handlers they were
+/// installed over, so that an embedder with handlers of its own only has
+/// the ones of wasmer in the way while it runs Wasm code. Signals that
+/// aren't Wasm traps are forwarded to the previous handlers meanwhile.
+///
+/// Handlers installed over the ones of wasmer must call
+/// [`handle_wasm_trap`] first, or forward the signals to the handlers they
+/// replaced. The handlers of wasmer then stay installed after the last
+/// `TrapHandlers` is dropped, as removing them would break that chain.
+#[derive(Debug)]
+pub struct TrapHandlers {
+    _private: (),
+}
+
+impl TrapHandlers {
+    /// Installs the handlers, unless they already are.
+    pub fn install() -> Self {
+        let mut state = TRAP_HANDLERS.lock().unwrap();
+        if !state.installed {
+            unsafe { platform_init() };
+            state.installed = true;
+        }
+        state.users += 1;
+        Self { _private: () }
+    }
+}
+
+impl Drop for TrapHandlers {
+    fn drop(&mut self) {
+        let mut state = TRAP_HANDLERS.lock().unwrap();
+        state.users -= 1;
+        if state.users == 0 && unsafe { platform_uninit() } {
+            state.installed = false;
+        }
+    }
+}
+
 /// Raises a user-defined trap immediately.
 ///
 /// This function performs as-if a wasm trap was just executed, only the trap
@@ -605,6 +767,7 @@ pub unsafe fn resume_panic(payload: Box<dyn Any + Send>) -> ! {
 /// * `callee` - the third argument to the `trampoline` function
 /// * `values_vec` - points to a buffer which holds the incoming arguments, and to
 ///   which the outgoing return values will be written.
+/// * `stack_size` - the size of the stack the function runs on
 ///
 /// # Safety
 ///
@@ -616,8 +779,9 @@ pub unsafe fn wasmer_call_trampoline(
     trampoline: VMTrampoline,
     callee: *const VMFunctionBody,
     values_vec: *mut u8,
+    stack_size: usize,
 ) -> Result<(), Trap> {
-    catch_traps(trap_handler, || {
+    catch_traps(trap_handler, stack_size, || {
         mem::transmute::<_, extern "C" fn(VMFunctionContext, *const VMFunctionBody, *mut u8)>(
             trampoline,
         )(vmctx, callee, values_vec);
@@ -627,11 +791,15 @@ pub unsafe fn wasmer_call_trampoline(
 /// Catches any wasm traps that happen within the execution of `closure`,
 /// returning them as a `Result`.
 ///
+/// The closure runs on a stack of `stack_size` bytes, overflowing it traps
+/// with [`TrapCode::StackOverflow`].
+///
 /// # Safety
 ///
 /// Highly unsafe since `closure` won't have any dtors run.
 pub unsafe fn catch_traps<F, R>(
     trap_handler: Option<*const TrapHandlerFn<'static>>,
+    stack_size: usize,
     closure: F,
 ) -> Result<R, Trap>
 where
@@ -640,20 +808,23 @@ where
     // Ensure that per-thread initialization is done.
     lazy_per_thread_init()?;
 
-    on_wasm_stack(trap_handler, closure).map_err(UnwindReason::into_trap)
+    on_wasm_stack(trap_handler, stack_size, closure).map_err(UnwindReason::into_trap)
 }
 
-// We need two separate thread-local variables here:
+// We need three separate thread-local variables here:
 // - YIELDER is set within the new stack and is used to unwind back to the root
 //   of the stack from inside it.
 // - TRAP_HANDLER is set from outside the new stack and is solely used from
 //   signal handlers. It must be atomic since it is used by signal handlers.
+// - ASYNC_CONTEXT is set from outside the new stack while an `AsyncCall` is
+//   polled, and is used by host functions to poll their futures.
 //
 // We also do per-thread signal stack initialization on the first time
 // TRAP_HANDLER is accessed.
 thread_local! {
-    static YIELDER: Cell<Option<NonNull<Yielder<(), UnwindReason>>>> = Cell::new(None);
+    static YIELDER: Cell<Option<NonNull<Yielder<(), Suspension>>>> = Cell::new(None);
     static TRAP_HANDLER: AtomicPtr<TrapHandlerContext> = AtomicPtr::new(ptr::null_mut());
+    static ASYNC_CONTEXT: Cell<*mut Context<'static>> = Cell::new(ptr::null_mut());
 }
 
 /// Read-only information that is used by signal handlers to handle and recover
@@ -670,6 +841,7 @@ struct TrapHandlerContext {
         &mut dyn FnMut(TrapHandlerRegs),
     ) -> bool,
     custom_trap: Option<*const TrapHandlerFn<'static>>,
+    stack_ptr_in_bounds: fn(*const u8, usize) -> bool,
 }
 struct TrapHandlerContextInner<T> {
     /// Information about the currently running coroutine. This is used to
@@ -704,11 +876,19 @@ impl TrapHandlerContext {
                 )
             }
         }
+        fn stack_ptr_in_bounds<T>(ptr: *const u8, sp: usize) -> bool {
+            unsafe {
+                (*(ptr as *const TrapHandlerContextInner<T>))
+                    .coro_trap_handler
+                    .stack_ptr_in_bounds(sp)
+            }
+        }
         let inner = TrapHandlerContextInner { coro_trap_handler };
         let ctx = Self {
             inner: &inner as *const _ as *const u8,
             handle_trap: func::<T>,
             custom_trap,
+            stack_ptr_in_bounds: stack_ptr_in_bounds::<T>,
         };
 
         compiler_fence(Ordering::Release);
@@ -726,6 +906,17 @@ impl TrapHandlerContext {
         f()
     }
 
+    /// Whether `sp` is in the stack Wasm code runs on in this thread.
+    unsafe fn is_wasm_stack(sp: usize) -> bool {
+        let ptr = TRAP_HANDLER.with(|ptr| ptr.load(Ordering::Relaxed));
+        if ptr.is_null() {
+            return false;
+        }
+
+        let ctx = &*ptr;
+        (ctx.stack_ptr_in_bounds)(ctx.inner, sp)
+    }
+
     /// Attempts to handle the trap if it's a wasm trap.
     unsafe fn handle_trap(
         pc: usize,
@@ -812,6 +1003,14 @@ impl<T> TrapHandlerContextInner<T> {
     }
 }
 
+/// Why the coroutine running Wasm code yielded.
+enum Suspension {
+    /// The coroutine is unwinding and must be reset
+    Unwind(UnwindReason),
+    /// A host function is waiting for a pending future
+    Pending,
+}
+
 enum UnwindReason {
     /// A panic caused by the host
     Panic(Box<dyn Any + Send>),
@@ -847,28 +1046,51 @@ unsafe fn unwind_with(reason: UnwindReason) -> ! {
         .with(|cell| cell.replace(None))
         .expect("not running on Wasm stack");
 
-    yielder.as_ref().suspend(reason);
+    yielder.as_ref().suspend(Suspension::Unwind(reason));
 
     // on_wasm_stack will forcibly reset the coroutine stack after yielding.
     unreachable!();
 }
 
+/// The size of the stack Wasm code runs on, unless the engine sets
+/// another one.
+pub const DEFAULT_STACK_SIZE: usize = 1024 * 1024;
+
+// Allocating a new stack is pretty expensive since it involves several
+// system calls. We therefore keep a cache of pre-allocated stacks which
+// allows them to be reused multiple times.
+// FIXME(Amanieu): We should refactor this to avoid the lock.
+lazy_static::lazy_static! {
+    static ref STACK_POOL: Mutex<HashMap<usize, Vec<DefaultStack>>> = Mutex::new(HashMap::new());
+}
+
+/// Takes a stack of `size` bytes from the pool, or allocates one.
+fn take_stack(size: usize) -> DefaultStack {
+    let stack = STACK_POOL.lock().unwrap().get_mut(&size).and_then(Vec::pop);
+    stack.unwrap_or_else(|| DefaultStack::new(size).expect("failed to allocate the Wasm stack"))
+}
+
+/// Returns a stack taken with [`take_stack`] to the pool.
+fn return_stack(size: usize, stack: DefaultStack) {
+    STACK_POOL
+        .lock()
+        .unwrap()
+        .entry(size)
+        .or_default()
+        .push(stack);
+}
+
 /// Runs the given function on a separate stack so that its stack usage can be
 /// bounded. Stack overflows and other traps can be caught and execution
 /// returned to the root of the stack.
 fn on_wasm_stack<F: FnOnce() -> T, T>(
     trap_handler: Option<*const TrapHandlerFn<'static>>,
+    stack_size: usize,
     f: F,
 ) -> Result<T, UnwindReason> {
-    // Allocating a new stack is pretty expensive since it involves several
-    // system calls. We therefore keep a cache of pre-allocated stacks which
-    // allows them to be reused multiple times.
-    // FIXME(Amanieu): We should refactor this to avoid the lock.
-    lazy_static::lazy_static! {
-        static ref STACK_POOL: Mutex<Vec<DefaultStack>> = Mutex::new(vec![]);
-    }
-    let stack = STACK_POOL.lock().unwrap().pop().unwrap_or_default();
-    let mut stack = scopeguard::guard(stack, |stack| STACK_POOL.lock().unwrap().push(stack));
+    let stack = take_stack(stack_size);
+    let mut stack = scopeguard::guard(stack, |stack| return_stack(stack_size, stack));
+    let host_callers = num_host_callers();
 
     // Create a coroutine with a new stack to run the function on.
     let mut coro = ScopedCoroutine::with_stack(&mut *stack, move |yielder, ()| {
@@ -883,23 +1105,206 @@ fn on_wasm_stack<F: FnOnce() -> T, T>(
         YIELDER.with(|cell| cell.set(None));
     }
 
+    // Host functions called by this code can't suspend an outer async call.
+    let async_context = ASYNC_CONTEXT.with(|cell| cell.replace(ptr::null_mut()));
+    defer! {
+        ASYNC_CONTEXT.with(|cell| cell.set(async_context));
+    }
+
     // Set up metadata for the trap handler for the duration of the coroutine
     // execution. This is restored to its previous value afterwards.
     TrapHandlerContext::install(trap_handler, coro.trap_handler(), || {
         match coro.resume(()) {
-            CoroutineResult::Yield(trap) => {
+            CoroutineResult::Yield(Suspension::Unwind(trap)) => {
                 // This came from unwind_with which requires that there be only
                 // Wasm code on the stack.
                 unsafe {
                     coro.force_reset();
                 }
+                take_host_callers(host_callers);
                 Err(trap)
             }
+            CoroutineResult::Yield(Suspension::Pending) => {
+                unreachable!("host futures are only awaited in async calls")
+            }
             CoroutineResult::Return(result) => result,
         }
     })
 }
 
+/// A call into Wasm code which can be suspended while host functions wait
+/// for futures, with [`block_on_future`].
+///
+/// Like [`wasmer_call_trampoline`], the call runs on a separate stack. It
+/// is driven by [`AsyncCall::poll`], which returns `Poll::Pending` when a
+/// host function waits for a pending future, and resumes the Wasm code
+/// where it was suspended the next time it's called.
+///
+/// Dropping a call that hasn't completed cancels it: the host function it
+/// is suspended in drops its future, and the Wasm frames are discarded as
+/// if the call trapped.
+pub struct AsyncCall {
+    trap_handler: Option<*const TrapHandlerFn<'static>>,
+    stack_size: usize,
+    coro: Option<Coroutine<(), Suspension, Result<(), UnwindReason>>>,
+    /// The interpreters calling the host functions the call is suspended
+    /// in.
+    host_callers: Vec<HostCaller>,
+}
+
+impl AsyncCall {
+    /// Prepares a call to `callee` through `trampoline` on a stack of
+    /// `stack_size` bytes, like [`wasmer_call_trampoline`], without
+    /// starting it.
+    ///
+    /// # Safety
+    ///
+    /// Wildly unsafe because it calls raw function pointers and
+    /// reads/writes raw function pointers. `values_vec` must stay valid
+    /// until the call completes.
+    pub unsafe fn new(
+        trap_handler: Option<*const TrapHandlerFn<'static>>,
+        vmctx: VMFunctionContext,
+        trampoline: VMTrampoline,
+        callee: *const VMFunctionBody,
+        values_vec: *mut u8,
+        stack_size: usize,
+    ) -> Self {
+        let stack = take_stack(stack_size);
+        let coro = Coroutine::with_stack(stack, move |yielder, ()| {
+            YIELDER.with(|cell| cell.set(Some(yielder.into())));
+            mem::transmute::<_, extern "C" fn(VMFunctionContext, *const VMFunctionBody, *mut u8)>(
+                trampoline,
+            )(vmctx, callee, values_vec);
+            Ok(())
+        });
+        Self {
+            trap_handler,
+            stack_size,
+            coro: Some(coro),
+            host_callers: Vec::new(),
+        }
+    }
+
+    /// Runs the call until it completes, or until a host function waits
+    /// for a pending future, in which case the waker of `cx` is woken
+    /// when the call can make progress again.
+    ///
+    /// # Safety
+    ///
+    /// The memory and functions the call uses must still be valid.
+    pub unsafe fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Trap>> {
+        if let Err(trap) = lazy_per_thread_init() {
+            return Poll::Ready(Err(trap));
+        }
+        let coro = self.coro.as_mut().expect("polled a completed call");
+
+        let yielder = YIELDER.with(|cell| cell.get());
+        let async_context = ASYNC_CONTEXT
+            .with(|cell| cell.replace(cx as *mut Context<'_> as *mut Context<'static>));
+        defer! {
+            YIELDER.with(|cell| cell.set(yielder));
+            ASYNC_CONTEXT.with(|cell| cell.set(async_context));
+        }
+
+        let host_callers = num_host_callers();
+        restore_host_callers(mem::take(&mut self.host_callers));
+        let trap_handler = self.trap_handler;
+        let result =
+            TrapHandlerContext::install(trap_handler, coro.trap_handler(), || coro.resume(()));
+        let result = match result {
+            CoroutineResult::Yield(Suspension::Pending) => {
+                self.host_callers = take_host_callers(host_callers);
+                return Poll::Pending;
+            }
+            CoroutineResult::Yield(Suspension::Unwind(trap)) => {
+                // Only Wasm code is left on the stack, as in on_wasm_stack
+                coro.force_reset();
+                take_host_callers(host_callers);
+                Err(trap)
+            }
+            CoroutineResult::Return(result) => result,
+        };
+        if let Some(coro) = self.coro.take() {
+            return_stack(self.stack_size, coro.into_stack());
+        }
+        Poll::Ready(result.map_err(UnwindReason::into_trap))
+    }
+}
+
+impl AsyncCall {
+    /// Resumes a suspended call without an async context, for the host
+    /// function it is suspended in to drop its future and unwind.
+    unsafe fn cancel(&mut self, coro: &mut Coroutine<(), Suspension, Result<(), UnwindReason>>) {
+        let yielder = YIELDER.with(|cell| cell.get());
+        let async_context = ASYNC_CONTEXT.with(|cell| cell.replace(ptr::null_mut()));
+        defer! {
+            YIELDER.with(|cell| cell.set(yielder));
+            ASYNC_CONTEXT.with(|cell| cell.set(async_context));
+        }
+
+        let host_callers = num_host_callers();
+        restore_host_callers(mem::take(&mut self.host_callers));
+        let trap_handler = self.trap_handler;
+        let _ = TrapHandlerContext::install(trap_handler, coro.trap_handler(), || coro.resume(()));
+        take_host_callers(host_callers);
+        // Only Wasm code is left on the stack, as in on_wasm_stack
+        if !coro.done() {
+            coro.force_reset();
+        }
+    }
+}
+
+impl Drop for AsyncCall {
+    fn drop(&mut self) {
+        if let Some(mut coro) = self.coro.take() {
+            if coro.started() && !coro.done() {
+                unsafe {
+                    self.cancel(&mut coro);
+                }
+            }
+            return_stack(self.stack_size, coro.into_stack());
+        }
+    }
+}
+
+/// Waits for `future` in a host function called by Wasm code running in
+/// an [`AsyncCall`], suspending the call while the future is pending.
+///
+/// Returns `None`, without polling the future, when the Wasm code isn't
+/// running in an async call. If the call is dropped while the future is
+/// pending, the future is dropped, and the call unwinds from here.
+///
+/// # Safety
+///
+/// Must be called on the Wasm stack, outside of [`on_host_stack`].
+pub unsafe fn block_on_future<F: Future>(future: F) -> Option<F::Output> {
+    let yielder = YIELDER.with(|cell| cell.get())?;
+    if ASYNC_CONTEXT.with(|cell| cell.get()).is_null() {
+        return None;
+    }
+    let mut future = Box::pin(future);
+    loop {
+        // The context changes every time the call is polled
+        let cx = &mut *ASYNC_CONTEXT.with(|cell| cell.get());
+        match panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+            Ok(Poll::Ready(output)) => return Some(output),
+            Ok(Poll::Pending) => {}
+            Err(panic) => resume_panic(panic),
+        }
+        yielder.as_ref().suspend(Suspension::Pending);
+        // The poller restored its own YIELDER when the call was suspended
+        YIELDER.with(|cell| cell.set(Some(yielder)));
+        // The call was resumed by `AsyncCall::cancel`
+        if ASYNC_CONTEXT.with(|cell| cell.get()).is_null() {
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(future)));
+            unwind_with(UnwindReason::UserTrap(
+                "the async call was cancelled".into(),
+            ));
+        }
+    }
+}
+
 /// When executing on the Wasm stack, temporarily switch back to the host stack
 /// to perform an operation that should not be constrainted by the Wasm stack
 /// limits.
@@ -908,6 +1313,10 @@ fn on_wasm_stack<F: FnOnce() -> T, T>(
 /// the control of untrusted code. Malicious code could artificially induce a
 /// stack overflow in the middle of a sensitive host operations (e.g. growing
 /// a memory) which would be hard to recover from.
+///
+/// A panic raised by the operation must not unwind through the Wasm frames
+/// below it, so it is caught on the host stack and carried across them with
+/// [`resume_panic`].
 pub fn on_host_stack<F: FnOnce() -> T, T>(f: F) -> T {
     // Reset YIEDER to None for the duration of this call to indicate that we
     // are no longer on the Wasm stack.
@@ -920,18 +1329,26 @@ pub fn on_host_stack<F: FnOnce() -> T, T>(f: F) -> T {
         None => return f(),
     };
 
-    // Restore YIELDER upon exiting normally or unwinding.
-    defer! {
-        YIELDER.with(|cell| cell.set(yielder_ptr));
-    }
+    let result = {
+        // Restore YIELDER upon exiting normally or unwinding.
+        defer! {
+            YIELDER.with(|cell| cell.set(yielder_ptr));
+        }
 
-    // on_parent_stack requires the closure to be Send so that the Yielder
-    // cannot be called from the parent stack. This is not a problem for us
-    // since we don't expose the Yielder.
-    struct SendWrapper<T>(T);
-    unsafe impl<T> Send for SendWrapper<T> {}
-    let wrapped = SendWrapper(f);
-    yielder.on_parent_stack(move || (wrapped.0)())
+        // on_parent_stack requires the closure to be Send so that the Yielder
+        // cannot be called from the parent stack. This is not a problem for us
+        // since we don't expose the Yielder.
+        struct SendWrapper<T>(T);
+        unsafe impl<T> Send for SendWrapper<T> {}
+        let wrapped = SendWrapper(f);
+        yielder.on_parent_stack(move || panic::catch_unwind(AssertUnwindSafe(wrapped.0)))
+    };
+
+    match result {
+        Ok(result) => result,
+        // YIELDER is restored, so the panic can unwind the Wasm stack
+        Err(panic) => unsafe { resume_panic(panic) },
+    }
 }
 
 #[cfg(windows)]
@@ -0,0 +1,868 @@
+//! The host side of the [wasi-crypto] proposal.
+//!
+//! Guests get the `wasi_ephemeral_crypto_*` imports of [`WasiCrypto`],
+//! and do their cryptography with the primitives of [RustCrypto] rather
+//! than with their own. The handles and options of the common module are
+//! implemented, as well as Ed25519 signatures and X25519 key exchanges
+//! (with keys in the `raw` encoding), the `SHA-256`, `SHA-512`,
+//! `SHA-512/256`, `HMAC/SHA-256` and `HMAC/SHA-512` symmetric algorithms,
+//! and the `AES-128-GCM`, `AES-256-GCM` and `CHACHA20-POLY1305` AEAD
+//! ciphers, whose nonce is the `nonce` option. The other functions of the
+//! proposal (key encapsulation, managed secrets) aren't provided, or fail
+//! with `unsupported_feature`.
+//!
+//! An instance holds at most 4096 handles at once, and its signature and
+//! AEAD states buffer at most 16 MiB of input together. The secret keys
+//! are wiped from the memory of the host once closed.
+//!
+//! [wasi-crypto]: https://github.com/WebAssembly/wasi-crypto
+//! [RustCrypto]: https://github.com/RustCrypto
+
+mod state;
+
+use state::{CryptoState, Handle, Secret};
+use std::convert::TryFrom;
+use std::sync::Mutex;
+use tracing::debug;
+use wasmer::{
+    imports, AsStoreMut, ExportError, Function, FunctionEnv, FunctionEnvMut, Imports, Instance,
+    Memory, Memory32, MemoryView, WasmPtr,
+};
+
+/// The errors of the wasi-crypto functions, the `crypto_errno` of the
+/// proposal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+#[allow(missing_docs)]
+pub enum CryptoErrno {
+    Success = 0,
+    GuestError = 1,
+    NotImplemented = 2,
+    UnsupportedFeature = 3,
+    ProhibitedOperation = 4,
+    UnsupportedEncoding = 5,
+    UnsupportedAlgorithm = 6,
+    UnsupportedOption = 7,
+    InvalidKey = 8,
+    InvalidLength = 9,
+    VerificationFailed = 10,
+    RngError = 11,
+    AlgorithmFailure = 12,
+    InvalidSignature = 13,
+    Closed = 14,
+    InvalidHandle = 15,
+    Overflow = 16,
+    InternalError = 17,
+    TooManyHandles = 18,
+    KeyNotSupported = 19,
+    KeyRequired = 20,
+    InvalidTag = 21,
+    InvalidOperation = 22,
+    NonceRequired = 23,
+    InvalidNonce = 24,
+    OptionNotSet = 25,
+    NotFound = 26,
+    ParametersMissing = 27,
+    InProgress = 28,
+    IncompatibleKeys = 29,
+    Expired = 30,
+}
+
+type Result<T> = std::result::Result<T, CryptoErrno>;
+type Ptr<T> = WasmPtr<T, Memory32>;
+
+/// The wasi-crypto objects of an instance, and the memory their
+/// functions read their arguments from.
+#[derive(Debug, Default)]
+struct CryptoEnv {
+    memory: Option<Memory>,
+    state: Mutex<CryptoState>,
+}
+
+/// The host side of wasi-crypto for an instance.
+///
+/// Its imports are given to the instance, which must then be passed to
+/// [`WasiCrypto::initialize`] for them to access its memory.
+#[derive(Debug, Clone)]
+pub struct WasiCrypto {
+    env: FunctionEnv<CryptoEnv>,
+}
+
+impl WasiCrypto {
+    /// Creates the state of wasi-crypto for an instance of `store`.
+    pub fn new(store: &mut impl AsStoreMut) -> Self {
+        Self {
+            env: FunctionEnv::new(store, CryptoEnv::default()),
+        }
+    }
+
+    /// Gives the functions access to the `memory` export of `instance`.
+    pub fn initialize(
+        &self,
+        store: &mut impl AsStoreMut,
+        instance: &Instance,
+    ) -> std::result::Result<(), ExportError> {
+        let memory = instance.exports.get_memory("memory")?.clone();
+        self.env.as_mut(store).memory = Some(memory);
+        Ok(())
+    }
+
+    /// The `wasi_ephemeral_crypto_*` imports.
+    pub fn import_object(&self, mut store: &mut impl AsStoreMut) -> Imports {
+        let env = &self.env;
+        imports! {
+            "wasi_ephemeral_crypto_common" => {
+                "options_open" => Function::new_typed_with_env(&mut store, env, options_open),
+                "options_close" => Function::new_typed_with_env(&mut store, env, options_close),
+                "options_set" => Function::new_typed_with_env(&mut store, env, options_set),
+                "options_set_u64" => Function::new_typed_with_env(&mut store, env, options_set_u64),
+                "options_set_guest_buffer" => Function::new_typed_with_env(&mut store, env, options_set_guest_buffer),
+                "array_output_len" => Function::new_typed_with_env(&mut store, env, array_output_len),
+                "array_output_pull" => Function::new_typed_with_env(&mut store, env, array_output_pull),
+                "secrets_manager_open" => Function::new_typed_with_env(&mut store, env, secrets_manager_open),
+                "secrets_manager_close" => Function::new_typed_with_env(&mut store, env, secrets_manager_close),
+                "secrets_manager_invalidate" => Function::new_typed_with_env(&mut store, env, secrets_manager_invalidate),
+            },
+            "wasi_ephemeral_crypto_asymmetric_common" => {
+                "keypair_generate" => Function::new_typed_with_env(&mut store, env, keypair_generate),
+                "keypair_import" => Function::new_typed_with_env(&mut store, env, keypair_import),
+                "keypair_publickey" => Function::new_typed_with_env(&mut store, env, keypair_publickey),
+                "keypair_export" => Function::new_typed_with_env(&mut store, env, keypair_export),
+                "keypair_secretkey" => Function::new_typed_with_env(&mut store, env, keypair_secretkey),
+                "keypair_close" => Function::new_typed_with_env(&mut store, env, keypair_close),
+                "publickey_import" => Function::new_typed_with_env(&mut store, env, publickey_import),
+                "publickey_export" => Function::new_typed_with_env(&mut store, env, publickey_export),
+                "publickey_from_secretkey" => Function::new_typed_with_env(&mut store, env, publickey_from_secretkey),
+                "publickey_close" => Function::new_typed_with_env(&mut store, env, publickey_close),
+                "secretkey_import" => Function::new_typed_with_env(&mut store, env, secretkey_import),
+                "secretkey_export" => Function::new_typed_with_env(&mut store, env, secretkey_export),
+                "secretkey_close" => Function::new_typed_with_env(&mut store, env, secretkey_close),
+            },
+            "wasi_ephemeral_crypto_kx" => {
+                "kx_dh" => Function::new_typed_with_env(&mut store, env, kx_dh),
+                "kx_encapsulate" => Function::new_typed_with_env(&mut store, env, kx_encapsulate),
+                "kx_decapsulate" => Function::new_typed_with_env(&mut store, env, kx_decapsulate),
+            },
+            "wasi_ephemeral_crypto_signatures" => {
+                "signature_export" => Function::new_typed_with_env(&mut store, env, signature_export),
+                "signature_import" => Function::new_typed_with_env(&mut store, env, signature_import),
+                "signature_state_open" => Function::new_typed_with_env(&mut store, env, signature_state_open),
+                "signature_state_update" => Function::new_typed_with_env(&mut store, env, signature_state_update),
+                "signature_state_sign" => Function::new_typed_with_env(&mut store, env, signature_state_sign),
+                "signature_state_close" => Function::new_typed_with_env(&mut store, env, signature_state_close),
+                "signature_verification_state_open" => Function::new_typed_with_env(&mut store, env, signature_verification_state_open),
+                "signature_verification_state_update" => Function::new_typed_with_env(&mut store, env, signature_verification_state_update),
+                "signature_verification_state_verify" => Function::new_typed_with_env(&mut store, env, signature_verification_state_verify),
+                "signature_verification_state_close" => Function::new_typed_with_env(&mut store, env, signature_verification_state_close),
+                "signature_close" => Function::new_typed_with_env(&mut store, env, signature_close),
+            },
+            "wasi_ephemeral_crypto_symmetric" => {
+                "symmetric_key_generate" => Function::new_typed_with_env(&mut store, env, symmetric_key_generate),
+                "symmetric_key_import" => Function::new_typed_with_env(&mut store, env, symmetric_key_import),
+                "symmetric_key_export" => Function::new_typed_with_env(&mut store, env, symmetric_key_export),
+                "symmetric_key_close" => Function::new_typed_with_env(&mut store, env, symmetric_key_close),
+                "symmetric_state_open" => Function::new_typed_with_env(&mut store, env, symmetric_state_open),
+                "symmetric_state_absorb" => Function::new_typed_with_env(&mut store, env, symmetric_state_absorb),
+                "symmetric_state_squeeze" => Function::new_typed_with_env(&mut store, env, symmetric_state_squeeze),
+                "symmetric_state_squeeze_tag" => Function::new_typed_with_env(&mut store, env, symmetric_state_squeeze_tag),
+                "symmetric_state_max_tag_len" => Function::new_typed_with_env(&mut store, env, symmetric_state_max_tag_len),
+                "symmetric_state_encrypt" => Function::new_typed_with_env(&mut store, env, symmetric_state_encrypt),
+                "symmetric_state_encrypt_detached" => Function::new_typed_with_env(&mut store, env, symmetric_state_encrypt_detached),
+                "symmetric_state_decrypt" => Function::new_typed_with_env(&mut store, env, symmetric_state_decrypt),
+                "symmetric_state_decrypt_detached" => Function::new_typed_with_env(&mut store, env, symmetric_state_decrypt_detached),
+                "symmetric_state_close" => Function::new_typed_with_env(&mut store, env, symmetric_state_close),
+                "symmetric_tag_len" => Function::new_typed_with_env(&mut store, env, symmetric_tag_len),
+                "symmetric_tag_pull" => Function::new_typed_with_env(&mut store, env, symmetric_tag_pull),
+                "symmetric_tag_verify" => Function::new_typed_with_env(&mut store, env, symmetric_tag_verify),
+                "symmetric_tag_close" => Function::new_typed_with_env(&mut store, env, symmetric_tag_close),
+            },
+        }
+    }
+}
+
+/// The memory of the guest, as the functions see it.
+struct Guest<'a> {
+    view: MemoryView<'a>,
+}
+
+impl Guest<'_> {
+    fn bytes(&self, ptr: Ptr<u8>, len: u32) -> Result<Vec<u8>> {
+        ptr.slice(&self.view, len)
+            .and_then(|slice| slice.read_to_vec())
+            .map_err(|_| CryptoErrno::GuestError)
+    }
+
+    /// Reads bytes which are wiped from the memory of the host once
+    /// dropped.
+    fn secret(&self, ptr: Ptr<u8>, len: u32) -> Result<Secret> {
+        self.bytes(ptr, len).map(Secret::new)
+    }
+
+    fn string(&self, ptr: Ptr<u8>, len: u32) -> Result<String> {
+        String::from_utf8(self.bytes(ptr, len)?).map_err(|_| CryptoErrno::GuestError)
+    }
+
+    /// Reads an optional handle, a variant with an `u8` tag which is `0`
+    /// when the handle, 4 bytes further, is set.
+    fn optional_handle(&self, ptr: Ptr<u8>) -> Result<Option<Handle>> {
+        let tag = ptr.read(&self.view).map_err(|_| CryptoErrno::GuestError)?;
+        if tag != 0 {
+            return Ok(None);
+        }
+        let handle = ptr.offset().checked_add(4).ok_or(CryptoErrno::GuestError)?;
+        Ptr::<Handle>::new(handle)
+            .read(&self.view)
+            .map(Some)
+            .map_err(|_| CryptoErrno::GuestError)
+    }
+
+    fn write_bytes(&self, ptr: Ptr<u8>, data: &[u8]) -> Result<()> {
+        self.view
+            .write(u64::from(ptr.offset()), data)
+            .map_err(|_| CryptoErrno::GuestError)
+    }
+
+    fn write_u32(&self, ptr: Ptr<u32>, value: u32) -> Result<()> {
+        ptr.write(&self.view, value)
+            .map_err(|_| CryptoErrno::GuestError)
+    }
+
+    fn write_len(&self, ptr: Ptr<u32>, len: usize) -> Result<()> {
+        self.write_u32(ptr, u32::try_from(len).map_err(|_| CryptoErrno::Overflow)?)
+    }
+}
+
+/// Runs `f` with the memory of the guest and the objects of the
+/// instance, and returns its error code.
+fn with_guest(
+    ctx: &FunctionEnvMut<CryptoEnv>,
+    f: impl FnOnce(&Guest, &mut CryptoState) -> Result<()>,
+) -> u32 {
+    let env = ctx.data();
+    let memory = match &env.memory {
+        Some(memory) => memory,
+        None => return CryptoErrno::InternalError as u32,
+    };
+    let guest = Guest {
+        view: memory.view(ctx),
+    };
+    let mut state = env.state.lock().unwrap();
+    match f(&guest, &mut state) {
+        Ok(()) => CryptoErrno::Success as u32,
+        Err(errno) => {
+            debug!("wasi-crypto failed with {:?}", errno);
+            errno as u32
+        }
+    }
+}
+
+fn options_open(ctx: FunctionEnvMut<CryptoEnv>, algorithm_type: u32, result: Ptr<u32>) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_u32(result, state.options_open(algorithm_type)?)
+    })
+}
+
+fn options_close(ctx: FunctionEnvMut<CryptoEnv>, options: Handle) -> u32 {
+    with_guest(&ctx, |_, state| state.options_close(options))
+}
+
+fn options_set(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    options: Handle,
+    name: Ptr<u8>,
+    name_len: u32,
+    value: Ptr<u8>,
+    value_len: u32,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        state.options_set(
+            options,
+            &guest.string(name, name_len)?,
+            &guest.bytes(value, value_len)?,
+        )
+    })
+}
+
+fn options_set_u64(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    options: Handle,
+    name: Ptr<u8>,
+    name_len: u32,
+    _value: u64,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.string(name, name_len)?;
+        state.options_set_unsupported(options)
+    })
+}
+
+fn options_set_guest_buffer(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    options: Handle,
+    name: Ptr<u8>,
+    name_len: u32,
+    _buffer: Ptr<u8>,
+    _buffer_len: u32,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.string(name, name_len)?;
+        state.options_set_unsupported(options)
+    })
+}
+
+fn array_output_len(ctx: FunctionEnvMut<CryptoEnv>, output: Handle, result: Ptr<u32>) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_len(result, state.array_output_len(output)?)
+    })
+}
+
+fn array_output_pull(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    output: Handle,
+    buf: Ptr<u8>,
+    buf_len: u32,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        let data = state.array_output_pull(output, buf_len as usize)?;
+        guest.write_bytes(buf, &data)?;
+        guest.write_len(result, data.len())
+    })
+}
+
+fn secrets_manager_open(
+    _ctx: FunctionEnvMut<CryptoEnv>,
+    _options: Ptr<u8>,
+    _result: Ptr<u32>,
+) -> u32 {
+    CryptoErrno::UnsupportedFeature as u32
+}
+
+fn secrets_manager_close(_ctx: FunctionEnvMut<CryptoEnv>, _secrets_manager: Handle) -> u32 {
+    CryptoErrno::UnsupportedFeature as u32
+}
+
+fn secrets_manager_invalidate(
+    _ctx: FunctionEnvMut<CryptoEnv>,
+    _secrets_manager: Handle,
+    _key_id: Ptr<u8>,
+    _key_id_len: u32,
+    _key_version: u64,
+) -> u32 {
+    CryptoErrno::UnsupportedFeature as u32
+}
+
+fn keypair_generate(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    algorithm_type: u32,
+    algorithm: Ptr<u8>,
+    algorithm_len: u32,
+    options: Ptr<u8>,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        let keypair = state.keypair_generate(
+            algorithm_type,
+            &guest.string(algorithm, algorithm_len)?,
+            guest.optional_handle(options)?,
+        )?;
+        guest.write_u32(result, keypair)
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn keypair_import(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    algorithm_type: u32,
+    algorithm: Ptr<u8>,
+    algorithm_len: u32,
+    encoded: Ptr<u8>,
+    encoded_len: u32,
+    encoding: u32,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        let keypair = state.keypair_import(
+            algorithm_type,
+            &guest.string(algorithm, algorithm_len)?,
+            &guest.secret(encoded, encoded_len)?,
+            encoding,
+        )?;
+        guest.write_u32(result, keypair)
+    })
+}
+
+fn keypair_publickey(ctx: FunctionEnvMut<CryptoEnv>, keypair: Handle, result: Ptr<u32>) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_u32(result, state.keypair_publickey(keypair)?)
+    })
+}
+
+fn keypair_export(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    keypair: Handle,
+    encoding: u32,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_u32(result, state.keypair_export(keypair, encoding)?)
+    })
+}
+
+fn keypair_secretkey(ctx: FunctionEnvMut<CryptoEnv>, keypair: Handle, result: Ptr<u32>) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_u32(result, state.keypair_secretkey(keypair)?)
+    })
+}
+
+fn keypair_close(ctx: FunctionEnvMut<CryptoEnv>, keypair: Handle) -> u32 {
+    with_guest(&ctx, |_, state| state.keypair_close(keypair))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn publickey_import(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    algorithm_type: u32,
+    algorithm: Ptr<u8>,
+    algorithm_len: u32,
+    encoded: Ptr<u8>,
+    encoded_len: u32,
+    encoding: u32,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        let public = state.publickey_import(
+            algorithm_type,
+            &guest.string(algorithm, algorithm_len)?,
+            &guest.bytes(encoded, encoded_len)?,
+            encoding,
+        )?;
+        guest.write_u32(result, public)
+    })
+}
+
+fn publickey_export(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    public: Handle,
+    encoding: u32,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_u32(result, state.publickey_export(public, encoding)?)
+    })
+}
+
+fn publickey_from_secretkey(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    secret: Handle,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_u32(result, state.publickey_from_secretkey(secret)?)
+    })
+}
+
+fn publickey_close(ctx: FunctionEnvMut<CryptoEnv>, public: Handle) -> u32 {
+    with_guest(&ctx, |_, state| state.publickey_close(public))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn secretkey_import(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    algorithm_type: u32,
+    algorithm: Ptr<u8>,
+    algorithm_len: u32,
+    encoded: Ptr<u8>,
+    encoded_len: u32,
+    encoding: u32,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        let secret = state.secretkey_import(
+            algorithm_type,
+            &guest.string(algorithm, algorithm_len)?,
+            &guest.secret(encoded, encoded_len)?,
+            encoding,
+        )?;
+        guest.write_u32(result, secret)
+    })
+}
+
+fn secretkey_export(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    secret: Handle,
+    encoding: u32,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_u32(result, state.secretkey_export(secret, encoding)?)
+    })
+}
+
+fn secretkey_close(ctx: FunctionEnvMut<CryptoEnv>, secret: Handle) -> u32 {
+    with_guest(&ctx, |_, state| state.secretkey_close(secret))
+}
+
+fn kx_dh(ctx: FunctionEnvMut<CryptoEnv>, public: Handle, secret: Handle, result: Ptr<u32>) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_u32(result, state.kx_dh(public, secret)?)
+    })
+}
+
+fn kx_encapsulate(
+    _ctx: FunctionEnvMut<CryptoEnv>,
+    _public: Handle,
+    _secret: Ptr<u32>,
+    _encapsulated_secret: Ptr<u32>,
+) -> u32 {
+    CryptoErrno::UnsupportedFeature as u32
+}
+
+fn kx_decapsulate(
+    _ctx: FunctionEnvMut<CryptoEnv>,
+    _secret: Handle,
+    _encapsulated_secret: Ptr<u8>,
+    _encapsulated_secret_len: u32,
+    _result: Ptr<u32>,
+) -> u32 {
+    CryptoErrno::UnsupportedFeature as u32
+}
+
+fn signature_export(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    signature: Handle,
+    encoding: u32,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_u32(result, state.signature_export(signature, encoding)?)
+    })
+}
+
+fn signature_import(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    algorithm: Ptr<u8>,
+    algorithm_len: u32,
+    encoded: Ptr<u8>,
+    encoded_len: u32,
+    encoding: u32,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        let signature = state.signature_import(
+            &guest.string(algorithm, algorithm_len)?,
+            &guest.bytes(encoded, encoded_len)?,
+            encoding,
+        )?;
+        guest.write_u32(result, signature)
+    })
+}
+
+fn signature_state_open(ctx: FunctionEnvMut<CryptoEnv>, keypair: Handle, result: Ptr<u32>) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_u32(result, state.signature_state_open(keypair)?)
+    })
+}
+
+fn signature_state_update(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    signature_state: Handle,
+    input: Ptr<u8>,
+    input_len: u32,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        state.signature_state_update(signature_state, &guest.bytes(input, input_len)?)
+    })
+}
+
+fn signature_state_sign(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    signature_state: Handle,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_u32(result, state.signature_state_sign(signature_state)?)
+    })
+}
+
+fn signature_state_close(ctx: FunctionEnvMut<CryptoEnv>, signature_state: Handle) -> u32 {
+    with_guest(&ctx, |_, state| {
+        state.signature_state_close(signature_state)
+    })
+}
+
+fn signature_verification_state_open(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    public: Handle,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_u32(result, state.signature_verification_state_open(public)?)
+    })
+}
+
+fn signature_verification_state_update(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    verification_state: Handle,
+    input: Ptr<u8>,
+    input_len: u32,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        state.signature_verification_state_update(
+            verification_state,
+            &guest.bytes(input, input_len)?,
+        )
+    })
+}
+
+fn signature_verification_state_verify(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    verification_state: Handle,
+    signature: Handle,
+) -> u32 {
+    with_guest(&ctx, |_, state| {
+        state.signature_verification_state_verify(verification_state, signature)
+    })
+}
+
+fn signature_verification_state_close(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    verification_state: Handle,
+) -> u32 {
+    with_guest(&ctx, |_, state| {
+        state.signature_verification_state_close(verification_state)
+    })
+}
+
+fn signature_close(ctx: FunctionEnvMut<CryptoEnv>, signature: Handle) -> u32 {
+    with_guest(&ctx, |_, state| state.signature_close(signature))
+}
+
+fn symmetric_key_generate(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    algorithm: Ptr<u8>,
+    algorithm_len: u32,
+    options: Ptr<u8>,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        let key = state.symmetric_key_generate(
+            &guest.string(algorithm, algorithm_len)?,
+            guest.optional_handle(options)?,
+        )?;
+        guest.write_u32(result, key)
+    })
+}
+
+fn symmetric_key_import(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    algorithm: Ptr<u8>,
+    algorithm_len: u32,
+    raw: Ptr<u8>,
+    raw_len: u32,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        let key = state.symmetric_key_import(
+            &guest.string(algorithm, algorithm_len)?,
+            &guest.secret(raw, raw_len)?,
+        )?;
+        guest.write_u32(result, key)
+    })
+}
+
+fn symmetric_key_export(ctx: FunctionEnvMut<CryptoEnv>, key: Handle, result: Ptr<u32>) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_u32(result, state.symmetric_key_export(key)?)
+    })
+}
+
+fn symmetric_key_close(ctx: FunctionEnvMut<CryptoEnv>, key: Handle) -> u32 {
+    with_guest(&ctx, |_, state| state.symmetric_key_close(key))
+}
+
+fn symmetric_state_open(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    algorithm: Ptr<u8>,
+    algorithm_len: u32,
+    key: Ptr<u8>,
+    options: Ptr<u8>,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        let symmetric_state = state.symmetric_state_open(
+            &guest.string(algorithm, algorithm_len)?,
+            guest.optional_handle(key)?,
+            guest.optional_handle(options)?,
+        )?;
+        guest.write_u32(result, symmetric_state)
+    })
+}
+
+fn symmetric_state_absorb(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    symmetric_state: Handle,
+    data: Ptr<u8>,
+    data_len: u32,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        state.symmetric_state_absorb(symmetric_state, &guest.bytes(data, data_len)?)
+    })
+}
+
+fn symmetric_state_squeeze(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    symmetric_state: Handle,
+    out: Ptr<u8>,
+    out_len: u32,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        let data = state.symmetric_state_squeeze(symmetric_state, out_len as usize)?;
+        guest.write_bytes(out, &data)
+    })
+}
+
+fn symmetric_state_squeeze_tag(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    symmetric_state: Handle,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_u32(result, state.symmetric_state_squeeze_tag(symmetric_state)?)
+    })
+}
+
+fn symmetric_state_max_tag_len(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    symmetric_state: Handle,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_len(result, state.symmetric_state_max_tag_len(symmetric_state)?)
+    })
+}
+
+fn symmetric_state_encrypt(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    symmetric_state: Handle,
+    out: Ptr<u8>,
+    out_len: u32,
+    data: Ptr<u8>,
+    data_len: u32,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        let ciphertext = state.symmetric_state_encrypt(
+            symmetric_state,
+            &guest.bytes(data, data_len)?,
+            out_len as usize,
+        )?;
+        guest.write_bytes(out, &ciphertext)?;
+        guest.write_len(result, ciphertext.len())
+    })
+}
+
+fn symmetric_state_encrypt_detached(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    symmetric_state: Handle,
+    out: Ptr<u8>,
+    out_len: u32,
+    data: Ptr<u8>,
+    data_len: u32,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        let (ciphertext, tag) = state.symmetric_state_encrypt_detached(
+            symmetric_state,
+            &guest.bytes(data, data_len)?,
+            out_len as usize,
+        )?;
+        guest.write_bytes(out, &ciphertext)?;
+        guest.write_u32(result, tag)
+    })
+}
+
+fn symmetric_state_decrypt(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    symmetric_state: Handle,
+    out: Ptr<u8>,
+    out_len: u32,
+    data: Ptr<u8>,
+    data_len: u32,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        let plaintext = state.symmetric_state_decrypt(
+            symmetric_state,
+            &guest.bytes(data, data_len)?,
+            out_len as usize,
+        )?;
+        guest.write_bytes(out, &plaintext)?;
+        guest.write_len(result, plaintext.len())
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn symmetric_state_decrypt_detached(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    symmetric_state: Handle,
+    out: Ptr<u8>,
+    out_len: u32,
+    data: Ptr<u8>,
+    data_len: u32,
+    raw_tag: Ptr<u8>,
+    raw_tag_len: u32,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        let plaintext = state.symmetric_state_decrypt_detached(
+            symmetric_state,
+            &guest.bytes(data, data_len)?,
+            &guest.bytes(raw_tag, raw_tag_len)?,
+            out_len as usize,
+        )?;
+        guest.write_bytes(out, &plaintext)?;
+        guest.write_len(result, plaintext.len())
+    })
+}
+
+fn symmetric_state_close(ctx: FunctionEnvMut<CryptoEnv>, symmetric_state: Handle) -> u32 {
+    with_guest(&ctx, |_, state| {
+        state.symmetric_state_close(symmetric_state)
+    })
+}
+
+fn symmetric_tag_len(ctx: FunctionEnvMut<CryptoEnv>, tag: Handle, result: Ptr<u32>) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        guest.write_len(result, state.symmetric_tag_len(tag)?)
+    })
+}
+
+fn symmetric_tag_pull(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    tag: Handle,
+    buf: Ptr<u8>,
+    buf_len: u32,
+    result: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        let data = state.symmetric_tag_pull(tag, buf_len as usize)?;
+        guest.write_bytes(buf, &data)?;
+        guest.write_len(result, data.len())
+    })
+}
+
+fn symmetric_tag_verify(
+    ctx: FunctionEnvMut<CryptoEnv>,
+    tag: Handle,
+    expected: Ptr<u8>,
+    expected_len: u32,
+) -> u32 {
+    with_guest(&ctx, |guest, state| {
+        state.symmetric_tag_verify(tag, &guest.bytes(expected, expected_len)?)
+    })
+}
+
+fn symmetric_tag_close(ctx: FunctionEnvMut<CryptoEnv>, tag: Handle) -> u32 {
+    with_guest(&ctx, |_, state| state.symmetric_tag_close(tag))
+}
@@ -0,0 +1,1353 @@
+//! The objects the guest holds handles to, and the operations on them.
+//!
+//! Nothing here touches the memory of the guest: the functions of
+//! `lib.rs` read their arguments from it, call these operations, and
+//! write the results back.
+
+use crate::CryptoErrno;
+use aes_gcm::aead::generic_array::GenericArray;
+use aes_gcm::aead::{AeadInPlace, NewAead};
+use aes_gcm::{Aes128Gcm, Aes256Gcm};
+use chacha20poly1305::ChaCha20Poly1305;
+use ed25519_dalek::{self as ed25519, Signer, Verifier};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512, Sha512_256};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use subtle::ConstantTimeEq;
+use x25519_dalek as x25519;
+use zeroize::Zeroizing;
+
+/// A handle to an object of the guest.
+pub type Handle = u32;
+
+/// Secret bytes, wiped from memory once dropped.
+pub type Secret = Zeroizing<Vec<u8>>;
+
+/// The `algorithm_type` of signatures.
+pub const ALGORITHM_TYPE_SIGNATURES: u32 = 0;
+/// The `algorithm_type` of symmetric operations.
+pub const ALGORITHM_TYPE_SYMMETRIC: u32 = 1;
+/// The `algorithm_type` of key exchanges.
+pub const ALGORITHM_TYPE_KEY_EXCHANGE: u32 = 2;
+
+/// The `raw` encoding, of keypairs, keys and signatures.
+pub const ENCODING_RAW: u32 = 0;
+
+/// The signature algorithm.
+const ED25519: &str = "Ed25519";
+/// The key exchange algorithm.
+const X25519: &str = "X25519";
+
+/// The most objects an instance holds handles to at once.
+const MAX_HANDLES: usize = 4096;
+
+/// The most bytes the states of an instance buffer together: Ed25519
+/// signs whole messages, and AEAD ciphers take all their associated data
+/// at once.
+const MAX_BUFFERED_INPUT: usize = 16 << 20;
+
+/// The length of the nonces of the AEAD algorithms.
+const AEAD_NONCE_LEN: usize = 12;
+/// The length of the tags of the AEAD algorithms.
+const AEAD_TAG_LEN: usize = 16;
+
+type Result<T> = std::result::Result<T, CryptoErrno>;
+
+/// Appends `data` to the `input` a state buffers, unless the states would
+/// buffer more than [`MAX_BUFFERED_INPUT`] bytes together.
+fn buffer_input(buffered: &mut usize, input: &mut Vec<u8>, data: &[u8]) -> Result<()> {
+    let total = buffered
+        .checked_add(data.len())
+        .filter(|total| *total <= MAX_BUFFERED_INPUT)
+        .ok_or(CryptoErrno::Overflow)?;
+    input.extend_from_slice(data);
+    *buffered = total;
+    Ok(())
+}
+
+/// The asymmetric algorithms that can be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AsymmetricAlgorithm {
+    Ed25519,
+    X25519,
+}
+
+impl AsymmetricAlgorithm {
+    fn from_name(algorithm_type: u32, name: &str) -> Result<Self> {
+        match (algorithm_type, name) {
+            (ALGORITHM_TYPE_SIGNATURES, ED25519) => Ok(Self::Ed25519),
+            (ALGORITHM_TYPE_KEY_EXCHANGE, X25519) => Ok(Self::X25519),
+            (ALGORITHM_TYPE_SIGNATURES, _) | (ALGORITHM_TYPE_KEY_EXCHANGE, _) => {
+                Err(CryptoErrno::UnsupportedAlgorithm)
+            }
+            _ => Err(CryptoErrno::UnsupportedFeature),
+        }
+    }
+}
+
+/// Copies an Ed25519 secret key, which can't be cloned.
+fn copy_ed25519_secret(secret: &ed25519::SecretKey) -> ed25519::SecretKey {
+    ed25519::SecretKey::from_bytes(secret.as_bytes()).expect("secret keys have a valid length")
+}
+
+/// Imports an X25519 secret key in the `raw` encoding.
+fn import_x25519_secret(encoded: &[u8]) -> Result<x25519::StaticSecret> {
+    let mut bytes = Zeroizing::new([0; 32]);
+    if encoded.len() != bytes.len() {
+        return Err(CryptoErrno::InvalidKey);
+    }
+    bytes.copy_from_slice(encoded);
+    Ok(x25519::StaticSecret::from(*bytes))
+}
+
+/// A keypair. Its secret key is wiped from memory once dropped.
+enum Keypair {
+    Ed25519(ed25519::Keypair),
+    X25519(x25519::StaticSecret),
+}
+
+impl Keypair {
+    fn generate(algorithm: AsymmetricAlgorithm) -> Result<Self> {
+        let mut seed = Zeroizing::new([0; 32]);
+        getrandom::getrandom(&mut *seed).map_err(|_| CryptoErrno::RngError)?;
+        Ok(match algorithm {
+            AsymmetricAlgorithm::Ed25519 => {
+                let secret = ed25519::SecretKey::from_bytes(&*seed)
+                    .map_err(|_| CryptoErrno::AlgorithmFailure)?;
+                let public = ed25519::PublicKey::from(&secret);
+                Self::Ed25519(ed25519::Keypair { secret, public })
+            }
+            AsymmetricAlgorithm::X25519 => Self::X25519(x25519::StaticSecret::from(*seed)),
+        })
+    }
+
+    /// Imports a keypair in the `raw` encoding: the secret key followed by
+    /// the public key for Ed25519, and the secret key alone for X25519.
+    fn import(algorithm: AsymmetricAlgorithm, encoded: &[u8]) -> Result<Self> {
+        Ok(match algorithm {
+            AsymmetricAlgorithm::Ed25519 => Self::Ed25519(
+                ed25519::Keypair::from_bytes(encoded).map_err(|_| CryptoErrno::InvalidKey)?,
+            ),
+            AsymmetricAlgorithm::X25519 => Self::X25519(import_x25519_secret(encoded)?),
+        })
+    }
+
+    fn export(&self) -> Secret {
+        match self {
+            Self::Ed25519(keypair) => {
+                let mut bytes = Secret::new(Vec::with_capacity(ed25519::KEYPAIR_LENGTH));
+                bytes.extend_from_slice(keypair.secret.as_bytes());
+                bytes.extend_from_slice(keypair.public.as_bytes());
+                bytes
+            }
+            Self::X25519(_) => self.secret_key().export(),
+        }
+    }
+
+    fn public_key(&self) -> PublicKey {
+        match self {
+            Self::Ed25519(keypair) => PublicKey::Ed25519(keypair.public),
+            Self::X25519(secret) => PublicKey::X25519(x25519::PublicKey::from(secret)),
+        }
+    }
+
+    fn secret_key(&self) -> SecretKey {
+        match self {
+            Self::Ed25519(keypair) => SecretKey::Ed25519(copy_ed25519_secret(&keypair.secret)),
+            Self::X25519(secret) => SecretKey::X25519(secret.clone()),
+        }
+    }
+
+    /// A copy of the keypair, for a signature state.
+    fn signing_keypair(&self) -> Result<ed25519::Keypair> {
+        match self {
+            Self::Ed25519(keypair) => Ok(ed25519::Keypair {
+                secret: copy_ed25519_secret(&keypair.secret),
+                public: keypair.public,
+            }),
+            Self::X25519(_) => Err(CryptoErrno::InvalidKey),
+        }
+    }
+}
+
+/// A public key.
+#[derive(Clone, Copy)]
+enum PublicKey {
+    Ed25519(ed25519::PublicKey),
+    X25519(x25519::PublicKey),
+}
+
+impl PublicKey {
+    fn import(algorithm: AsymmetricAlgorithm, encoded: &[u8]) -> Result<Self> {
+        Ok(match algorithm {
+            AsymmetricAlgorithm::Ed25519 => Self::Ed25519(
+                ed25519::PublicKey::from_bytes(encoded).map_err(|_| CryptoErrno::InvalidKey)?,
+            ),
+            AsymmetricAlgorithm::X25519 => {
+                let bytes = <[u8; 32]>::try_from(encoded).map_err(|_| CryptoErrno::InvalidKey)?;
+                Self::X25519(x25519::PublicKey::from(bytes))
+            }
+        })
+    }
+
+    fn export(&self) -> Vec<u8> {
+        match self {
+            Self::Ed25519(public) => public.as_bytes().to_vec(),
+            Self::X25519(public) => public.as_bytes().to_vec(),
+        }
+    }
+}
+
+/// A secret key, wiped from memory once dropped.
+enum SecretKey {
+    Ed25519(ed25519::SecretKey),
+    X25519(x25519::StaticSecret),
+}
+
+impl SecretKey {
+    fn import(algorithm: AsymmetricAlgorithm, encoded: &[u8]) -> Result<Self> {
+        Ok(match algorithm {
+            AsymmetricAlgorithm::Ed25519 => Self::Ed25519(
+                ed25519::SecretKey::from_bytes(encoded).map_err(|_| CryptoErrno::InvalidKey)?,
+            ),
+            AsymmetricAlgorithm::X25519 => Self::X25519(import_x25519_secret(encoded)?),
+        })
+    }
+
+    fn export(&self) -> Secret {
+        match self {
+            Self::Ed25519(secret) => Secret::new(secret.as_bytes().to_vec()),
+            Self::X25519(secret) => Secret::new(Zeroizing::new(secret.to_bytes()).to_vec()),
+        }
+    }
+
+    fn public_key(&self) -> PublicKey {
+        match self {
+            Self::Ed25519(secret) => PublicKey::Ed25519(ed25519::PublicKey::from(secret)),
+            Self::X25519(secret) => PublicKey::X25519(x25519::PublicKey::from(secret)),
+        }
+    }
+
+    /// The secret shared through an X25519 key exchange with `public`.
+    fn diffie_hellman(&self, public: &PublicKey) -> Result<Secret> {
+        let (secret, public) = match (self, public) {
+            (Self::X25519(secret), PublicKey::X25519(public)) => (secret, public),
+            (Self::X25519(_), PublicKey::Ed25519(_)) => return Err(CryptoErrno::IncompatibleKeys),
+            (Self::Ed25519(_), _) => return Err(CryptoErrno::InvalidOperation),
+        };
+        let shared = secret.diffie_hellman(public);
+        // The public keys of a small order share the same all-zero secret
+        // with every secret key.
+        if shared.as_bytes().iter().fold(0, |bits, byte| bits | byte) == 0 {
+            return Err(CryptoErrno::InvalidKey);
+        }
+        Ok(Secret::new(shared.as_bytes().to_vec()))
+    }
+}
+
+/// The symmetric algorithms that can be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SymmetricAlgorithm {
+    Sha256,
+    Sha512,
+    Sha512_256,
+    HmacSha256,
+    HmacSha512,
+    Aes128Gcm,
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl SymmetricAlgorithm {
+    fn from_name(name: &str) -> Result<Self> {
+        Ok(match name {
+            "SHA-256" => Self::Sha256,
+            "SHA-512" => Self::Sha512,
+            "SHA-512/256" => Self::Sha512_256,
+            "HMAC/SHA-256" => Self::HmacSha256,
+            "HMAC/SHA-512" => Self::HmacSha512,
+            "AES-128-GCM" => Self::Aes128Gcm,
+            "AES-256-GCM" => Self::Aes256Gcm,
+            "CHACHA20-POLY1305" => Self::ChaCha20Poly1305,
+            _ => return Err(CryptoErrno::UnsupportedAlgorithm),
+        })
+    }
+
+    /// The length of the generated keys, for the algorithms with keys.
+    fn key_len(self) -> Option<usize> {
+        match self {
+            Self::Aes128Gcm => Some(16),
+            Self::HmacSha256 | Self::Aes256Gcm | Self::ChaCha20Poly1305 => Some(32),
+            Self::HmacSha512 => Some(64),
+            Self::Sha256 | Self::Sha512 | Self::Sha512_256 => None,
+        }
+    }
+
+    fn is_aead(self) -> bool {
+        matches!(
+            self,
+            Self::Aes128Gcm | Self::Aes256Gcm | Self::ChaCha20Poly1305
+        )
+    }
+}
+
+/// The running state of a hash, of a MAC, or of an AEAD cipher.
+#[derive(Clone)]
+enum SymmetricState {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Sha512_256(Sha512_256),
+    HmacSha256(Hmac<Sha256>),
+    HmacSha512(Hmac<Sha512>),
+    Aead(AeadState),
+}
+
+impl SymmetricState {
+    fn open(
+        algorithm: SymmetricAlgorithm,
+        key: Option<&[u8]>,
+        nonce: Option<&[u8]>,
+    ) -> Result<Self> {
+        Ok(match (algorithm, key) {
+            (SymmetricAlgorithm::Sha256, None) => Self::Sha256(Sha256::new()),
+            (SymmetricAlgorithm::Sha512, None) => Self::Sha512(Sha512::new()),
+            (SymmetricAlgorithm::Sha512_256, None) => Self::Sha512_256(Sha512_256::new()),
+            (SymmetricAlgorithm::HmacSha256, Some(key)) => {
+                Self::HmacSha256(Hmac::new_from_slice(key).map_err(|_| CryptoErrno::InvalidKey)?)
+            }
+            (SymmetricAlgorithm::HmacSha512, Some(key)) => {
+                Self::HmacSha512(Hmac::new_from_slice(key).map_err(|_| CryptoErrno::InvalidKey)?)
+            }
+            (algorithm, Some(key)) if algorithm.is_aead() => {
+                let nonce = nonce.ok_or(CryptoErrno::NonceRequired)?;
+                if nonce.len() != AEAD_NONCE_LEN {
+                    return Err(CryptoErrno::InvalidNonce);
+                }
+                Self::Aead(AeadState {
+                    algorithm,
+                    key: Secret::new(key.to_vec()),
+                    nonce: nonce.to_vec(),
+                    associated_data: Vec::new(),
+                    encrypted: false,
+                })
+            }
+            (_, Some(_)) => return Err(CryptoErrno::KeyNotSupported),
+            (_, None) => return Err(CryptoErrno::KeyRequired),
+        })
+    }
+
+    /// Absorbs `data`, which is the associated data of AEAD ciphers.
+    fn absorb(&mut self, buffered: &mut usize, data: &[u8]) -> Result<()> {
+        match self {
+            Self::Sha256(state) => Digest::update(state, data),
+            Self::Sha512(state) => Digest::update(state, data),
+            Self::Sha512_256(state) => Digest::update(state, data),
+            Self::HmacSha256(state) => Mac::update(state, data),
+            Self::HmacSha512(state) => Mac::update(state, data),
+            Self::Aead(state) => return buffer_input(buffered, &mut state.associated_data, data),
+        }
+        Ok(())
+    }
+
+    /// The first `len` bytes of the digest of what was absorbed so far.
+    fn squeeze(&self, len: usize) -> Result<Vec<u8>> {
+        let digest = match self.clone() {
+            Self::Sha256(state) => state.finalize().to_vec(),
+            Self::Sha512(state) => state.finalize().to_vec(),
+            Self::Sha512_256(state) => state.finalize().to_vec(),
+            Self::HmacSha256(_) | Self::HmacSha512(_) | Self::Aead(_) => {
+                return Err(CryptoErrno::InvalidOperation)
+            }
+        };
+        if len > digest.len() {
+            return Err(CryptoErrno::InvalidLength);
+        }
+        Ok(digest[..len].to_vec())
+    }
+
+    /// The authentication tag of what was absorbed so far.
+    fn squeeze_tag(&self) -> Result<Vec<u8>> {
+        Ok(match self.clone() {
+            Self::HmacSha256(state) => state.finalize().into_bytes().to_vec(),
+            Self::HmacSha512(state) => state.finalize().into_bytes().to_vec(),
+            Self::Sha256(_) | Self::Sha512(_) | Self::Sha512_256(_) | Self::Aead(_) => {
+                return Err(CryptoErrno::InvalidOperation)
+            }
+        })
+    }
+
+    fn aead(&mut self) -> Result<&mut AeadState> {
+        match self {
+            Self::Aead(state) => Ok(state),
+            _ => Err(CryptoErrno::InvalidOperation),
+        }
+    }
+}
+
+/// The state of an AEAD cipher: its key, its nonce, and the associated
+/// data absorbed so far.
+#[derive(Clone)]
+struct AeadState {
+    algorithm: SymmetricAlgorithm,
+    key: Secret,
+    nonce: Vec<u8>,
+    associated_data: Vec<u8>,
+    /// Whether a message was encrypted already, as encrypting another one
+    /// would reuse the nonce.
+    encrypted: bool,
+}
+
+impl AeadState {
+    /// Encrypts `data` in place, and returns its tag.
+    fn encrypt(&mut self, data: &mut [u8]) -> Result<Vec<u8>> {
+        if self.encrypted {
+            return Err(CryptoErrno::ProhibitedOperation);
+        }
+        let tag = match self.algorithm {
+            SymmetricAlgorithm::Aes128Gcm => aead_seal::<Aes128Gcm>(self, data),
+            SymmetricAlgorithm::Aes256Gcm => aead_seal::<Aes256Gcm>(self, data),
+            SymmetricAlgorithm::ChaCha20Poly1305 => aead_seal::<ChaCha20Poly1305>(self, data),
+            _ => unreachable!("not an AEAD algorithm"),
+        }?;
+        self.encrypted = true;
+        Ok(tag)
+    }
+
+    /// Decrypts `data` in place, if `tag` authenticates it.
+    fn decrypt(&self, data: &mut [u8], tag: &[u8]) -> Result<()> {
+        if tag.len() != AEAD_TAG_LEN {
+            return Err(CryptoErrno::InvalidTag);
+        }
+        match self.algorithm {
+            SymmetricAlgorithm::Aes128Gcm => aead_open::<Aes128Gcm>(self, data, tag),
+            SymmetricAlgorithm::Aes256Gcm => aead_open::<Aes256Gcm>(self, data, tag),
+            SymmetricAlgorithm::ChaCha20Poly1305 => aead_open::<ChaCha20Poly1305>(self, data, tag),
+            _ => unreachable!("not an AEAD algorithm"),
+        }
+    }
+}
+
+fn aead_seal<C: NewAead + AeadInPlace>(state: &AeadState, data: &mut [u8]) -> Result<Vec<u8>> {
+    let cipher = C::new_from_slice(&state.key).map_err(|_| CryptoErrno::InvalidKey)?;
+    let tag = cipher
+        .encrypt_in_place_detached(
+            GenericArray::from_slice(&state.nonce),
+            &state.associated_data,
+            data,
+        )
+        .map_err(|_| CryptoErrno::AlgorithmFailure)?;
+    Ok(tag.to_vec())
+}
+
+fn aead_open<C: NewAead + AeadInPlace>(
+    state: &AeadState,
+    data: &mut [u8],
+    tag: &[u8],
+) -> Result<()> {
+    let cipher = C::new_from_slice(&state.key).map_err(|_| CryptoErrno::InvalidKey)?;
+    cipher
+        .decrypt_in_place_detached(
+            GenericArray::from_slice(&state.nonce),
+            &state.associated_data,
+            data,
+            GenericArray::from_slice(tag),
+        )
+        .map_err(|_| CryptoErrno::InvalidTag)
+}
+
+/// The options of an operation.
+struct Options {
+    algorithm_type: u32,
+    /// The nonce of AEAD ciphers.
+    nonce: Option<Vec<u8>>,
+}
+
+/// An object the guest holds a handle to.
+enum Object {
+    Options(Options),
+    ArrayOutput(Secret),
+    Keypair(Keypair),
+    PublicKey(PublicKey),
+    SecretKey(SecretKey),
+    Signature(ed25519::Signature),
+    SignatureState {
+        keypair: ed25519::Keypair,
+        input: Vec<u8>,
+    },
+    VerificationState {
+        public: ed25519::PublicKey,
+        input: Vec<u8>,
+    },
+    SymmetricKey(SymmetricAlgorithm, Secret),
+    SymmetricState(SymmetricState),
+    SymmetricTag(Vec<u8>),
+}
+
+impl Object {
+    /// The bytes of input the object buffers.
+    fn buffered_len(&self) -> usize {
+        match self {
+            Self::SignatureState { input, .. } | Self::VerificationState { input, .. } => {
+                input.len()
+            }
+            Self::SymmetricState(SymmetricState::Aead(state)) => state.associated_data.len(),
+            _ => 0,
+        }
+    }
+}
+
+/// The objects of an instance.
+#[derive(Default)]
+pub struct CryptoState {
+    objects: HashMap<Handle, Object>,
+    next_handle: Handle,
+    /// The bytes of input the states buffer together.
+    buffered: usize,
+}
+
+impl fmt::Debug for CryptoState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CryptoState")
+            .field("handles", &self.objects.len())
+            .field("buffered", &self.buffered)
+            .finish()
+    }
+}
+
+/// Gets the object of a handle, if it is of the given kind.
+macro_rules! get {
+    ($state:expr, $handle:expr, $pattern:pat => $value:expr) => {
+        match $state.objects.get_mut(&$handle) {
+            Some($pattern) => $value,
+            Some(_) | None => return Err(CryptoErrno::InvalidHandle),
+        }
+    };
+}
+
+impl CryptoState {
+    fn check_handles(&self) -> Result<()> {
+        if self.objects.len() >= MAX_HANDLES {
+            return Err(CryptoErrno::TooManyHandles);
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, object: Object) -> Result<Handle> {
+        self.check_handles()?;
+        // The handles of the closed objects are reused once the others
+        // ran out.
+        let mut handle = self.next_handle;
+        while self.objects.contains_key(&handle) {
+            handle = handle.wrapping_add(1);
+        }
+        self.next_handle = handle.wrapping_add(1);
+        self.objects.insert(handle, object);
+        Ok(handle)
+    }
+
+    /// Closes `handle`, if its object matches `is_kind`.
+    fn close(&mut self, handle: Handle, is_kind: fn(&Object) -> bool) -> Result<()> {
+        match self.objects.get(&handle) {
+            Some(object) if is_kind(object) => {
+                if let Some(object) = self.objects.remove(&handle) {
+                    self.buffered -= object.buffered_len();
+                }
+                Ok(())
+            }
+            _ => Err(CryptoErrno::InvalidHandle),
+        }
+    }
+
+    fn check_options(&mut self, options: Option<Handle>) -> Result<()> {
+        if let Some(options) = options {
+            get!(self, options, Object::Options(_) => ());
+        }
+        Ok(())
+    }
+
+    /// The nonce set in `options`, if any.
+    fn options_nonce(&mut self, options: Option<Handle>) -> Result<Option<Vec<u8>>> {
+        Ok(match options {
+            Some(options) => get!(self, options, Object::Options(options) => options.nonce.clone()),
+            None => None,
+        })
+    }
+
+    pub fn options_open(&mut self, algorithm_type: u32) -> Result<Handle> {
+        match algorithm_type {
+            ALGORITHM_TYPE_SIGNATURES | ALGORITHM_TYPE_SYMMETRIC | ALGORITHM_TYPE_KEY_EXCHANGE => {
+                self.insert(Object::Options(Options {
+                    algorithm_type,
+                    nonce: None,
+                }))
+            }
+            _ => Err(CryptoErrno::UnsupportedAlgorithm),
+        }
+    }
+
+    pub fn options_close(&mut self, options: Handle) -> Result<()> {
+        self.close(options, |object| matches!(object, Object::Options(_)))
+    }
+
+    /// The only option is the `nonce` of symmetric operations.
+    pub fn options_set(&mut self, options: Handle, name: &str, value: &[u8]) -> Result<()> {
+        let options = get!(self, options, Object::Options(options) => options);
+        match name {
+            "nonce" if options.algorithm_type == ALGORITHM_TYPE_SYMMETRIC => {
+                options.nonce = Some(value.to_vec());
+                Ok(())
+            }
+            _ => Err(CryptoErrno::UnsupportedOption),
+        }
+    }
+
+    /// None of the options are integers or guest buffers.
+    pub fn options_set_unsupported(&mut self, options: Handle) -> Result<()> {
+        get!(self, options, Object::Options(_) => ());
+        Err(CryptoErrno::UnsupportedOption)
+    }
+
+    pub fn array_output_len(&mut self, output: Handle) -> Result<usize> {
+        Ok(get!(self, output, Object::ArrayOutput(data) => data.len()))
+    }
+
+    /// Takes the content of `output`, which is closed, if it fits in
+    /// `buf_len` bytes.
+    pub fn array_output_pull(&mut self, output: Handle, buf_len: usize) -> Result<Secret> {
+        let len = self.array_output_len(output)?;
+        if len > buf_len {
+            return Err(CryptoErrno::Overflow);
+        }
+        match self.objects.remove(&output) {
+            Some(Object::ArrayOutput(data)) => Ok(data),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn keypair_generate(
+        &mut self,
+        algorithm_type: u32,
+        algorithm: &str,
+        options: Option<Handle>,
+    ) -> Result<Handle> {
+        let algorithm = AsymmetricAlgorithm::from_name(algorithm_type, algorithm)?;
+        self.check_options(options)?;
+        let keypair = Keypair::generate(algorithm)?;
+        self.insert(Object::Keypair(keypair))
+    }
+
+    pub fn keypair_import(
+        &mut self,
+        algorithm_type: u32,
+        algorithm: &str,
+        encoded: &[u8],
+        encoding: u32,
+    ) -> Result<Handle> {
+        let algorithm = AsymmetricAlgorithm::from_name(algorithm_type, algorithm)?;
+        if encoding != ENCODING_RAW {
+            return Err(CryptoErrno::UnsupportedEncoding);
+        }
+        let keypair = Keypair::import(algorithm, encoded)?;
+        self.insert(Object::Keypair(keypair))
+    }
+
+    pub fn keypair_export(&mut self, keypair: Handle, encoding: u32) -> Result<Handle> {
+        let bytes = get!(self, keypair, Object::Keypair(keypair) => keypair.export());
+        if encoding != ENCODING_RAW {
+            return Err(CryptoErrno::UnsupportedEncoding);
+        }
+        self.insert(Object::ArrayOutput(bytes))
+    }
+
+    pub fn keypair_publickey(&mut self, keypair: Handle) -> Result<Handle> {
+        let public = get!(self, keypair, Object::Keypair(keypair) => keypair.public_key());
+        self.insert(Object::PublicKey(public))
+    }
+
+    pub fn keypair_secretkey(&mut self, keypair: Handle) -> Result<Handle> {
+        let secret = get!(self, keypair, Object::Keypair(keypair) => keypair.secret_key());
+        self.insert(Object::SecretKey(secret))
+    }
+
+    pub fn keypair_close(&mut self, keypair: Handle) -> Result<()> {
+        self.close(keypair, |object| matches!(object, Object::Keypair(_)))
+    }
+
+    pub fn publickey_import(
+        &mut self,
+        algorithm_type: u32,
+        algorithm: &str,
+        encoded: &[u8],
+        encoding: u32,
+    ) -> Result<Handle> {
+        let algorithm = AsymmetricAlgorithm::from_name(algorithm_type, algorithm)?;
+        if encoding != ENCODING_RAW {
+            return Err(CryptoErrno::UnsupportedEncoding);
+        }
+        let public = PublicKey::import(algorithm, encoded)?;
+        self.insert(Object::PublicKey(public))
+    }
+
+    pub fn publickey_export(&mut self, public: Handle, encoding: u32) -> Result<Handle> {
+        let bytes = get!(self, public, Object::PublicKey(public) => public.export());
+        if encoding != ENCODING_RAW {
+            return Err(CryptoErrno::UnsupportedEncoding);
+        }
+        self.insert(Object::ArrayOutput(Secret::new(bytes)))
+    }
+
+    pub fn publickey_from_secretkey(&mut self, secret: Handle) -> Result<Handle> {
+        let public = get!(self, secret, Object::SecretKey(secret) => secret.public_key());
+        self.insert(Object::PublicKey(public))
+    }
+
+    pub fn publickey_close(&mut self, public: Handle) -> Result<()> {
+        self.close(public, |object| matches!(object, Object::PublicKey(_)))
+    }
+
+    pub fn secretkey_import(
+        &mut self,
+        algorithm_type: u32,
+        algorithm: &str,
+        encoded: &[u8],
+        encoding: u32,
+    ) -> Result<Handle> {
+        let algorithm = AsymmetricAlgorithm::from_name(algorithm_type, algorithm)?;
+        if encoding != ENCODING_RAW {
+            return Err(CryptoErrno::UnsupportedEncoding);
+        }
+        let secret = SecretKey::import(algorithm, encoded)?;
+        self.insert(Object::SecretKey(secret))
+    }
+
+    pub fn secretkey_export(&mut self, secret: Handle, encoding: u32) -> Result<Handle> {
+        let bytes = get!(self, secret, Object::SecretKey(secret) => secret.export());
+        if encoding != ENCODING_RAW {
+            return Err(CryptoErrno::UnsupportedEncoding);
+        }
+        self.insert(Object::ArrayOutput(bytes))
+    }
+
+    pub fn secretkey_close(&mut self, secret: Handle) -> Result<()> {
+        self.close(secret, |object| matches!(object, Object::SecretKey(_)))
+    }
+
+    /// The secret shared by `public` and `secret`, as an array output.
+    pub fn kx_dh(&mut self, public: Handle, secret: Handle) -> Result<Handle> {
+        let public = get!(self, public, Object::PublicKey(public) => *public);
+        let shared =
+            get!(self, secret, Object::SecretKey(secret) => secret.diffie_hellman(&public))?;
+        self.insert(Object::ArrayOutput(shared))
+    }
+
+    pub fn signature_import(
+        &mut self,
+        algorithm: &str,
+        encoded: &[u8],
+        encoding: u32,
+    ) -> Result<Handle> {
+        AsymmetricAlgorithm::from_name(ALGORITHM_TYPE_SIGNATURES, algorithm)?;
+        if encoding != ENCODING_RAW {
+            return Err(CryptoErrno::UnsupportedEncoding);
+        }
+        let signature =
+            ed25519::Signature::try_from(encoded).map_err(|_| CryptoErrno::InvalidSignature)?;
+        self.insert(Object::Signature(signature))
+    }
+
+    pub fn signature_export(&mut self, signature: Handle, encoding: u32) -> Result<Handle> {
+        let bytes = get!(self, signature, Object::Signature(signature) => signature.to_bytes());
+        if encoding != ENCODING_RAW {
+            return Err(CryptoErrno::UnsupportedEncoding);
+        }
+        self.insert(Object::ArrayOutput(Secret::new(bytes.to_vec())))
+    }
+
+    pub fn signature_close(&mut self, signature: Handle) -> Result<()> {
+        self.close(signature, |object| matches!(object, Object::Signature(_)))
+    }
+
+    pub fn signature_state_open(&mut self, keypair: Handle) -> Result<Handle> {
+        let keypair = get!(self, keypair, Object::Keypair(keypair) => keypair.signing_keypair())?;
+        self.insert(Object::SignatureState {
+            keypair,
+            input: Vec::new(),
+        })
+    }
+
+    pub fn signature_state_update(&mut self, state: Handle, data: &[u8]) -> Result<()> {
+        get!(
+            self,
+            state,
+            Object::SignatureState { input, .. } => buffer_input(&mut self.buffered, input, data)
+        )
+    }
+
+    pub fn signature_state_sign(&mut self, state: Handle) -> Result<Handle> {
+        let signature = get!(
+            self,
+            state,
+            Object::SignatureState { keypair, input } => keypair.sign(input)
+        );
+        self.insert(Object::Signature(signature))
+    }
+
+    pub fn signature_state_close(&mut self, state: Handle) -> Result<()> {
+        self.close(state, |object| {
+            matches!(object, Object::SignatureState { .. })
+        })
+    }
+
+    pub fn signature_verification_state_open(&mut self, public: Handle) -> Result<Handle> {
+        let public = match get!(self, public, Object::PublicKey(public) => *public) {
+            PublicKey::Ed25519(public) => public,
+            PublicKey::X25519(_) => return Err(CryptoErrno::InvalidKey),
+        };
+        self.insert(Object::VerificationState {
+            public,
+            input: Vec::new(),
+        })
+    }
+
+    pub fn signature_verification_state_update(
+        &mut self,
+        state: Handle,
+        data: &[u8],
+    ) -> Result<()> {
+        get!(
+            self,
+            state,
+            Object::VerificationState { input, .. } => buffer_input(&mut self.buffered, input, data)
+        )
+    }
+
+    pub fn signature_verification_state_verify(
+        &mut self,
+        state: Handle,
+        signature: Handle,
+    ) -> Result<()> {
+        let signature = get!(self, signature, Object::Signature(signature) => *signature);
+        let verified = get!(
+            self,
+            state,
+            Object::VerificationState { public, input } => public.verify(input, &signature)
+        );
+        verified.map_err(|_| CryptoErrno::VerificationFailed)
+    }
+
+    pub fn signature_verification_state_close(&mut self, state: Handle) -> Result<()> {
+        self.close(state, |object| {
+            matches!(object, Object::VerificationState { .. })
+        })
+    }
+
+    pub fn symmetric_key_generate(
+        &mut self,
+        algorithm: &str,
+        options: Option<Handle>,
+    ) -> Result<Handle> {
+        let algorithm = SymmetricAlgorithm::from_name(algorithm)?;
+        self.check_options(options)?;
+        let key_len = algorithm.key_len().ok_or(CryptoErrno::KeyNotSupported)?;
+        let mut key = Secret::new(vec![0; key_len]);
+        getrandom::getrandom(&mut key).map_err(|_| CryptoErrno::RngError)?;
+        self.insert(Object::SymmetricKey(algorithm, key))
+    }
+
+    /// Imports a raw key, which can be of any length for HMAC.
+    pub fn symmetric_key_import(&mut self, algorithm: &str, raw: &[u8]) -> Result<Handle> {
+        let algorithm = SymmetricAlgorithm::from_name(algorithm)?;
+        let key_len = algorithm.key_len().ok_or(CryptoErrno::KeyNotSupported)?;
+        if algorithm.is_aead() && raw.len() != key_len {
+            return Err(CryptoErrno::InvalidKey);
+        }
+        self.insert(Object::SymmetricKey(algorithm, Secret::new(raw.to_vec())))
+    }
+
+    pub fn symmetric_key_export(&mut self, key: Handle) -> Result<Handle> {
+        let raw = get!(self, key, Object::SymmetricKey(_, raw) => raw.clone());
+        self.insert(Object::ArrayOutput(raw))
+    }
+
+    pub fn symmetric_key_close(&mut self, key: Handle) -> Result<()> {
+        self.close(key, |object| matches!(object, Object::SymmetricKey(..)))
+    }
+
+    /// Opens the state of an algorithm, with the nonce of the options for
+    /// the AEAD ciphers.
+    pub fn symmetric_state_open(
+        &mut self,
+        algorithm: &str,
+        key: Option<Handle>,
+        options: Option<Handle>,
+    ) -> Result<Handle> {
+        let algorithm = SymmetricAlgorithm::from_name(algorithm)?;
+        let nonce = self.options_nonce(options)?;
+        let state = match key {
+            Some(key) => {
+                let (key_algorithm, raw) = get!(
+                    self,
+                    key,
+                    Object::SymmetricKey(key_algorithm, raw) => (*key_algorithm, raw)
+                );
+                if key_algorithm != algorithm {
+                    return Err(CryptoErrno::IncompatibleKeys);
+                }
+                SymmetricState::open(algorithm, Some(raw.as_slice()), nonce.as_deref())?
+            }
+            None => SymmetricState::open(algorithm, None, nonce.as_deref())?,
+        };
+        self.insert(Object::SymmetricState(state))
+    }
+
+    pub fn symmetric_state_absorb(&mut self, state: Handle, data: &[u8]) -> Result<()> {
+        get!(
+            self,
+            state,
+            Object::SymmetricState(state) => state.absorb(&mut self.buffered, data)
+        )
+    }
+
+    pub fn symmetric_state_squeeze(&mut self, state: Handle, len: usize) -> Result<Vec<u8>> {
+        get!(self, state, Object::SymmetricState(state) => state.squeeze(len))
+    }
+
+    pub fn symmetric_state_squeeze_tag(&mut self, state: Handle) -> Result<Handle> {
+        let tag = get!(self, state, Object::SymmetricState(state) => state.squeeze_tag())?;
+        self.insert(Object::SymmetricTag(tag))
+    }
+
+    pub fn symmetric_state_max_tag_len(&mut self, state: Handle) -> Result<usize> {
+        get!(self, state, Object::SymmetricState(state) => state.aead().map(|_| AEAD_TAG_LEN))
+    }
+
+    /// Encrypts `data` with an AEAD cipher, and returns the ciphertext and
+    /// its tag, if the ciphertext fits in `out_len` bytes, along with the
+    /// tag when `with_tag`.
+    fn seal(
+        &mut self,
+        state: Handle,
+        data: &[u8],
+        out_len: usize,
+        with_tag: bool,
+    ) -> Result<(Vec<u8>, Vec<u8>)> {
+        let state = get!(self, state, Object::SymmetricState(state) => state.aead()?);
+        let tag_len = if with_tag { AEAD_TAG_LEN } else { 0 };
+        if data.len().saturating_add(tag_len) > out_len {
+            return Err(CryptoErrno::Overflow);
+        }
+        let mut ciphertext = data.to_vec();
+        let tag = state.encrypt(&mut ciphertext)?;
+        Ok((ciphertext, tag))
+    }
+
+    /// Encrypts `data`, and returns the ciphertext followed by its tag, if
+    /// they fit in `out_len` bytes.
+    pub fn symmetric_state_encrypt(
+        &mut self,
+        state: Handle,
+        data: &[u8],
+        out_len: usize,
+    ) -> Result<Vec<u8>> {
+        let (mut ciphertext, tag) = self.seal(state, data, out_len, true)?;
+        ciphertext.extend_from_slice(&tag);
+        Ok(ciphertext)
+    }
+
+    /// Encrypts `data`, and returns the ciphertext, if it fits in `out_len`
+    /// bytes, and a handle to its tag.
+    pub fn symmetric_state_encrypt_detached(
+        &mut self,
+        state: Handle,
+        data: &[u8],
+        out_len: usize,
+    ) -> Result<(Vec<u8>, Handle)> {
+        // The message can't be encrypted again, so the tag must have a
+        // handle to go to.
+        self.check_handles()?;
+        let (ciphertext, tag) = self.seal(state, data, out_len, false)?;
+        let tag = self.insert(Object::SymmetricTag(tag))?;
+        Ok((ciphertext, tag))
+    }
+
+    /// Decrypts `data`, a ciphertext followed by its tag, if the plaintext
+    /// fits in `out_len` bytes.
+    pub fn symmetric_state_decrypt(
+        &mut self,
+        state: Handle,
+        data: &[u8],
+        out_len: usize,
+    ) -> Result<Vec<u8>> {
+        let ciphertext_len = data
+            .len()
+            .checked_sub(AEAD_TAG_LEN)
+            .ok_or(CryptoErrno::InvalidTag)?;
+        let (ciphertext, tag) = data.split_at(ciphertext_len);
+        self.symmetric_state_decrypt_detached(state, ciphertext, tag, out_len)
+    }
+
+    /// Decrypts `data`, if `tag` authenticates it and the plaintext fits
+    /// in `out_len` bytes.
+    pub fn symmetric_state_decrypt_detached(
+        &mut self,
+        state: Handle,
+        data: &[u8],
+        tag: &[u8],
+        out_len: usize,
+    ) -> Result<Vec<u8>> {
+        let state = get!(self, state, Object::SymmetricState(state) => state.aead()?);
+        if data.len() > out_len {
+            return Err(CryptoErrno::Overflow);
+        }
+        let mut plaintext = data.to_vec();
+        state.decrypt(&mut plaintext, tag)?;
+        Ok(plaintext)
+    }
+
+    pub fn symmetric_state_close(&mut self, state: Handle) -> Result<()> {
+        self.close(state, |object| matches!(object, Object::SymmetricState(_)))
+    }
+
+    pub fn symmetric_tag_len(&mut self, tag: Handle) -> Result<usize> {
+        Ok(get!(self, tag, Object::SymmetricTag(tag) => tag.len()))
+    }
+
+    /// Takes the tag, which is closed, if it fits in `buf_len` bytes.
+    pub fn symmetric_tag_pull(&mut self, tag: Handle, buf_len: usize) -> Result<Vec<u8>> {
+        let len = self.symmetric_tag_len(tag)?;
+        if len > buf_len {
+            return Err(CryptoErrno::Overflow);
+        }
+        match self.objects.remove(&tag) {
+            Some(Object::SymmetricTag(tag)) => Ok(tag),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Compares the tag with `expected`, in constant time.
+    pub fn symmetric_tag_verify(&mut self, tag: Handle, expected: &[u8]) -> Result<()> {
+        let tag = get!(self, tag, Object::SymmetricTag(tag) => tag);
+        // Slices of different lengths compare unequal right away, which
+        // only leaks the length of the tag
+        if bool::from(tag.as_slice().ct_eq(expected)) {
+            Ok(())
+        } else {
+            Err(CryptoErrno::InvalidTag)
+        }
+    }
+
+    pub fn symmetric_tag_close(&mut self, tag: Handle) -> Result<()> {
+        self.close(tag, |object| matches!(object, Object::SymmetricTag(_)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pull(state: &mut CryptoState, output: Handle) -> Vec<u8> {
+        let len = state.array_output_len(output).unwrap();
+        state.array_output_pull(output, len).unwrap().to_vec()
+    }
+
+    #[test]
+    fn hashes() {
+        let mut state = CryptoState::default();
+        let hash = state.symmetric_state_open("SHA-256", None, None).unwrap();
+        state.symmetric_state_absorb(hash, b"ab").unwrap();
+        state.symmetric_state_absorb(hash, b"c").unwrap();
+        assert_eq!(
+            state.symmetric_state_squeeze(hash, 4).unwrap(),
+            [0xba, 0x78, 0x16, 0xbf]
+        );
+        assert_eq!(
+            state.symmetric_state_squeeze(hash, 33),
+            Err(CryptoErrno::InvalidLength)
+        );
+        assert_eq!(
+            state.symmetric_state_squeeze_tag(hash),
+            Err(CryptoErrno::InvalidOperation)
+        );
+        state.symmetric_state_close(hash).unwrap();
+        assert_eq!(
+            state.symmetric_state_absorb(hash, b""),
+            Err(CryptoErrno::InvalidHandle)
+        );
+        assert_eq!(
+            state.symmetric_state_open("MD5", None, None),
+            Err(CryptoErrno::UnsupportedAlgorithm)
+        );
+    }
+
+    #[test]
+    fn macs() {
+        let mut state = CryptoState::default();
+        assert_eq!(
+            state.symmetric_state_open("HMAC/SHA-256", None, None),
+            Err(CryptoErrno::KeyRequired)
+        );
+        let key = state.symmetric_key_import("HMAC/SHA-256", b"key").unwrap();
+        let mac = state
+            .symmetric_state_open("HMAC/SHA-256", Some(key), None)
+            .unwrap();
+        state
+            .symmetric_state_absorb(mac, b"The quick brown fox jumps over the lazy dog")
+            .unwrap();
+        let tag = state.symmetric_state_squeeze_tag(mac).unwrap();
+        let expected = [
+            0xf7, 0xbc, 0x83, 0xf4, 0x30, 0x53, 0x84, 0x24, 0xb1, 0x32, 0x98, 0xe6, 0xaa, 0x6f,
+            0xb1, 0x43, 0xef, 0x4d, 0x59, 0xa1, 0x49, 0x46, 0x17, 0x59, 0x97, 0x47, 0x9d, 0xbc,
+            0x2d, 0x1a, 0x3c, 0xd8,
+        ];
+        state.symmetric_tag_verify(tag, &expected).unwrap();
+        assert_eq!(
+            state.symmetric_tag_verify(tag, &expected[1..]),
+            Err(CryptoErrno::InvalidTag)
+        );
+        assert_eq!(
+            state.symmetric_tag_verify(tag, &[&expected[..], &[0]].concat()),
+            Err(CryptoErrno::InvalidTag)
+        );
+        let mut tampered = expected;
+        tampered[31] ^= 1;
+        assert_eq!(
+            state.symmetric_tag_verify(tag, &tampered),
+            Err(CryptoErrno::InvalidTag)
+        );
+        assert_eq!(state.symmetric_tag_pull(tag, 32).unwrap(), expected);
+
+        let key = state.symmetric_key_generate("HMAC/SHA-512", None).unwrap();
+        let exported = state.symmetric_key_export(key).unwrap();
+        assert_eq!(pull(&mut state, exported).len(), 64);
+        assert_eq!(
+            state.symmetric_state_open("HMAC/SHA-256", Some(key), None),
+            Err(CryptoErrno::IncompatibleKeys)
+        );
+    }
+
+    #[test]
+    fn signatures() {
+        let mut state = CryptoState::default();
+        let keypair = state
+            .keypair_generate(ALGORITHM_TYPE_SIGNATURES, "Ed25519", None)
+            .unwrap();
+        let signing = state.signature_state_open(keypair).unwrap();
+        state.signature_state_update(signing, b"hello").unwrap();
+        let signature = state.signature_state_sign(signing).unwrap();
+
+        // Verify with the exported and reimported public key and signature
+        let public = state.keypair_publickey(keypair).unwrap();
+        let exported = state.publickey_export(public, ENCODING_RAW).unwrap();
+        let public = pull(&mut state, exported);
+        let public = state
+            .publickey_import(ALGORITHM_TYPE_SIGNATURES, "Ed25519", &public, ENCODING_RAW)
+            .unwrap();
+        let exported = state.signature_export(signature, ENCODING_RAW).unwrap();
+        let signature = pull(&mut state, exported);
+        let signature = state
+            .signature_import("Ed25519", &signature, ENCODING_RAW)
+            .unwrap();
+
+        let verification = state.signature_verification_state_open(public).unwrap();
+        state
+            .signature_verification_state_update(verification, b"hello")
+            .unwrap();
+        state
+            .signature_verification_state_verify(verification, signature)
+            .unwrap();
+        state
+            .signature_verification_state_update(verification, b"!")
+            .unwrap();
+        assert_eq!(
+            state.signature_verification_state_verify(verification, signature),
+            Err(CryptoErrno::VerificationFailed)
+        );
+
+        assert_eq!(
+            state.keypair_generate(ALGORITHM_TYPE_SIGNATURES, "ECDSA_P256_SHA256", None),
+            Err(CryptoErrno::UnsupportedAlgorithm)
+        );
+        assert_eq!(
+            state.keypair_generate(ALGORITHM_TYPE_SYMMETRIC, "Ed25519", None),
+            Err(CryptoErrno::UnsupportedFeature)
+        );
+    }
+
+    #[test]
+    fn key_exchanges() {
+        let mut state = CryptoState::default();
+        let alice = state
+            .keypair_generate(ALGORITHM_TYPE_KEY_EXCHANGE, "X25519", None)
+            .unwrap();
+        let bob = state
+            .keypair_generate(ALGORITHM_TYPE_KEY_EXCHANGE, "X25519", None)
+            .unwrap();
+        let alice_public = state.keypair_publickey(alice).unwrap();
+        let alice_secret = state.keypair_secretkey(alice).unwrap();
+        let bob_public = state.keypair_publickey(bob).unwrap();
+        let bob_secret = state.keypair_secretkey(bob).unwrap();
+
+        let shared = state.kx_dh(bob_public, alice_secret).unwrap();
+        let shared = pull(&mut state, shared);
+        let other = state.kx_dh(alice_public, bob_secret).unwrap();
+        assert_eq!(pull(&mut state, other), shared);
+
+        // Through the exported and reimported secret key
+        let exported = state.secretkey_export(alice_secret, ENCODING_RAW).unwrap();
+        let exported = pull(&mut state, exported);
+        let secret = state
+            .secretkey_import(
+                ALGORITHM_TYPE_KEY_EXCHANGE,
+                "X25519",
+                &exported,
+                ENCODING_RAW,
+            )
+            .unwrap();
+        let other = state.kx_dh(bob_public, secret).unwrap();
+        assert_eq!(pull(&mut state, other), shared);
+
+        // A public key of a small order
+        let public = state
+            .publickey_import(
+                ALGORITHM_TYPE_KEY_EXCHANGE,
+                "X25519",
+                &[0; 32],
+                ENCODING_RAW,
+            )
+            .unwrap();
+        assert_eq!(
+            state.kx_dh(public, alice_secret),
+            Err(CryptoErrno::InvalidKey)
+        );
+
+        let signing = state
+            .keypair_generate(ALGORITHM_TYPE_SIGNATURES, "Ed25519", None)
+            .unwrap();
+        let public = state.keypair_publickey(signing).unwrap();
+        assert_eq!(
+            state.kx_dh(public, alice_secret),
+            Err(CryptoErrno::IncompatibleKeys)
+        );
+        assert_eq!(
+            state.signature_state_open(alice),
+            Err(CryptoErrno::InvalidKey)
+        );
+    }
+
+    #[test]
+    fn aead() {
+        let mut state = CryptoState::default();
+        let key = state.symmetric_key_import("AES-128-GCM", &[0; 16]).unwrap();
+        assert_eq!(
+            state.symmetric_state_open("AES-128-GCM", Some(key), None),
+            Err(CryptoErrno::NonceRequired)
+        );
+        let options = state.options_open(ALGORITHM_TYPE_SYMMETRIC).unwrap();
+        state.options_set(options, "nonce", &[0; 12]).unwrap();
+
+        let cipher = state
+            .symmetric_state_open("AES-128-GCM", Some(key), Some(options))
+            .unwrap();
+        assert_eq!(state.symmetric_state_max_tag_len(cipher).unwrap(), 16);
+        assert_eq!(
+            state.symmetric_state_encrypt(cipher, &[0; 16], 31),
+            Err(CryptoErrno::Overflow)
+        );
+        let sealed = state.symmetric_state_encrypt(cipher, &[0; 16], 32).unwrap();
+        let expected = [
+            0x03, 0x88, 0xda, 0xce, 0x60, 0xb6, 0xa3, 0x92, 0xf3, 0x28, 0xc2, 0xb9, 0x71, 0xb2,
+            0xfe, 0x78, 0xab, 0x6e, 0x47, 0xd4, 0x2c, 0xec, 0x13, 0xbd, 0xf5, 0x3a, 0x67, 0xb2,
+            0x12, 0x57, 0xbd, 0xdf,
+        ];
+        assert_eq!(sealed, expected);
+        // The nonce can't be reused
+        assert_eq!(
+            state.symmetric_state_encrypt(cipher, &[0; 16], 32),
+            Err(CryptoErrno::ProhibitedOperation)
+        );
+
+        let decipher = state
+            .symmetric_state_open("AES-128-GCM", Some(key), Some(options))
+            .unwrap();
+        assert_eq!(
+            state
+                .symmetric_state_decrypt(decipher, &sealed, 16)
+                .unwrap(),
+            [0; 16]
+        );
+        let mut tampered = sealed.clone();
+        tampered[0] ^= 1;
+        assert_eq!(
+            state.symmetric_state_decrypt(decipher, &tampered, 16),
+            Err(CryptoErrno::InvalidTag)
+        );
+
+        // The associated data is authenticated as well
+        let key = state
+            .symmetric_key_generate("CHACHA20-POLY1305", None)
+            .unwrap();
+        let cipher = state
+            .symmetric_state_open("CHACHA20-POLY1305", Some(key), Some(options))
+            .unwrap();
+        state.symmetric_state_absorb(cipher, b"header").unwrap();
+        let (ciphertext, tag) = state
+            .symmetric_state_encrypt_detached(cipher, b"hello", 5)
+            .unwrap();
+        let tag = state.symmetric_tag_pull(tag, 16).unwrap();
+        let decipher = state
+            .symmetric_state_open("CHACHA20-POLY1305", Some(key), Some(options))
+            .unwrap();
+        assert_eq!(
+            state.symmetric_state_decrypt_detached(decipher, &ciphertext, &tag, 5),
+            Err(CryptoErrno::InvalidTag)
+        );
+        state.symmetric_state_absorb(decipher, b"header").unwrap();
+        assert_eq!(
+            state
+                .symmetric_state_decrypt_detached(decipher, &ciphertext, &tag, 5)
+                .unwrap(),
+            b"hello"
+        );
+
+        assert_eq!(
+            state.symmetric_key_import("AES-256-GCM", &[0; 16]),
+            Err(CryptoErrno::InvalidKey)
+        );
+        assert_eq!(
+            state.symmetric_state_squeeze_tag(decipher),
+            Err(CryptoErrno::InvalidOperation)
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let mut state = CryptoState::default();
+        let handles = (0..MAX_HANDLES)
+            .map(|_| state.options_open(ALGORITHM_TYPE_SYMMETRIC).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            state.options_open(ALGORITHM_TYPE_SYMMETRIC),
+            Err(CryptoErrno::TooManyHandles)
+        );
+        for handle in handles {
+            state.options_close(handle).unwrap();
+        }
+
+        let keypair = state
+            .keypair_generate(ALGORITHM_TYPE_SIGNATURES, "Ed25519", None)
+            .unwrap();
+        let first = state.signature_state_open(keypair).unwrap();
+        let second = state.signature_state_open(keypair).unwrap();
+        state
+            .signature_state_update(first, &vec![0; MAX_BUFFERED_INPUT])
+            .unwrap();
+        assert_eq!(
+            state.signature_state_update(second, b"!"),
+            Err(CryptoErrno::Overflow)
+        );
+        state.signature_state_close(first).unwrap();
+        state.signature_state_update(second, b"!").unwrap();
+    }
+}
@@ -0,0 +1,407 @@
+//! Outbound HTTP(S) requests for WASI guests.
+//!
+//! Guests get the `wasi_experimental_http` imports of [`WasiHttp`], the
+//! ABI of the [wasi-experimental-http] guest library, and send their
+//! requests through the host. Only the requests allowed by the
+//! [`HttpPolicy`] of the host are sent, any other one fails with
+//! `DestinationNotAllowed` before reaching the network.
+//!
+//! The responses are read whole by the host, up to the size limit of the
+//! policy, and kept until the guest closes them. Redirections aren't
+//! followed, and the `Host` header is always the one of the URL, so that
+//! guests can't be sent to a host they aren't allowed to reach.
+//!
+//! [wasi-experimental-http]: https://github.com/deislabs/wasi-experimental-http
+
+mod policy;
+
+pub use policy::{HttpPolicy, HttpRule, HttpRuleError, DEFAULT_MAX_BODY_SIZE, METHODS};
+
+use http_req::request::{Method, Request};
+use http_req::uri::Uri;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::debug;
+use wasmer::{
+    imports, AsStoreMut, ExportError, Function, FunctionEnv, FunctionEnvMut, Imports, Instance,
+    Memory, Memory32, MemoryView, WasmPtr,
+};
+
+/// The most responses a guest can keep open at once.
+const MAX_SESSIONS: usize = 64;
+
+/// How long a request can take before it fails.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The errors of the `wasi_experimental_http` functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+#[allow(missing_docs)]
+pub enum HttpErrno {
+    Success = 0,
+    InvalidHandle = 1,
+    MemoryNotFound = 2,
+    MemoryAccessError = 3,
+    BufferTooSmall = 4,
+    HeaderNotFound = 5,
+    Utf8Error = 6,
+    DestinationNotAllowed = 7,
+    InvalidMethod = 8,
+    InvalidEncoding = 9,
+    InvalidUrl = 10,
+    RequestError = 11,
+    RuntimeError = 12,
+    TooManySessions = 13,
+}
+
+type Result<T> = std::result::Result<T, HttpErrno>;
+type Ptr<T> = WasmPtr<T, Memory32>;
+
+/// A response, until the guest closes it.
+#[derive(Debug)]
+struct Session {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    /// How much of the body the guest has read.
+    read: usize,
+}
+
+#[derive(Debug, Default)]
+struct Sessions {
+    next: u32,
+    open: HashMap<u32, Session>,
+}
+
+/// The open responses of an instance, the requests it may send and the
+/// memory the functions read their arguments from.
+#[derive(Debug)]
+struct HttpEnv {
+    memory: Option<Memory>,
+    policy: HttpPolicy,
+    sessions: Mutex<Sessions>,
+}
+
+/// Outbound HTTP for an instance.
+///
+/// Its imports are given to the instance, which must then be passed to
+/// [`WasiHttp::initialize`] for them to access its memory.
+#[derive(Debug, Clone)]
+pub struct WasiHttp {
+    env: FunctionEnv<HttpEnv>,
+}
+
+impl WasiHttp {
+    /// Creates the state of outbound HTTP for an instance of `store`,
+    /// which can send the requests allowed by `policy`.
+    pub fn new(store: &mut impl AsStoreMut, policy: HttpPolicy) -> Self {
+        Self {
+            env: FunctionEnv::new(
+                store,
+                HttpEnv {
+                    memory: None,
+                    policy,
+                    sessions: Mutex::default(),
+                },
+            ),
+        }
+    }
+
+    /// Gives the functions access to the `memory` export of `instance`.
+    pub fn initialize(
+        &self,
+        store: &mut impl AsStoreMut,
+        instance: &Instance,
+    ) -> std::result::Result<(), ExportError> {
+        let memory = instance.exports.get_memory("memory")?.clone();
+        self.env.as_mut(store).memory = Some(memory);
+        Ok(())
+    }
+
+    /// The `wasi_experimental_http` imports.
+    pub fn import_object(&self, mut store: &mut impl AsStoreMut) -> Imports {
+        let env = &self.env;
+        imports! {
+            "wasi_experimental_http" => {
+                "req" => Function::new_typed_with_env(&mut store, env, req),
+                "close" => Function::new_typed_with_env(&mut store, env, close),
+                "body_read" => Function::new_typed_with_env(&mut store, env, body_read),
+                "header_get" => Function::new_typed_with_env(&mut store, env, header_get),
+                "headers_get_all" => Function::new_typed_with_env(&mut store, env, headers_get_all),
+            },
+        }
+    }
+}
+
+/// The memory of the guest, as the functions see it.
+struct Guest<'a> {
+    view: MemoryView<'a>,
+}
+
+impl Guest<'_> {
+    fn bytes(&self, ptr: Ptr<u8>, len: u32) -> Result<Vec<u8>> {
+        ptr.slice(&self.view, len)
+            .and_then(|slice| slice.read_to_vec())
+            .map_err(|_| HttpErrno::MemoryAccessError)
+    }
+
+    fn string(&self, ptr: Ptr<u8>, len: u32) -> Result<String> {
+        String::from_utf8(self.bytes(ptr, len)?).map_err(|_| HttpErrno::Utf8Error)
+    }
+
+    /// Writes `data` to a buffer of the guest of `len` bytes.
+    fn write_bytes(&self, ptr: Ptr<u8>, len: u32, data: &[u8]) -> Result<()> {
+        if data.len() > len as usize {
+            return Err(HttpErrno::BufferTooSmall);
+        }
+        self.view
+            .write(u64::from(ptr.offset()), data)
+            .map_err(|_| HttpErrno::MemoryAccessError)
+    }
+
+    fn write<T: wasmer::ValueType>(&self, ptr: Ptr<T>, value: T) -> Result<()> {
+        ptr.write(&self.view, value)
+            .map_err(|_| HttpErrno::MemoryAccessError)
+    }
+}
+
+/// Runs `f` with the memory of the guest and the state of the instance,
+/// and returns its error code.
+fn with_guest(
+    ctx: &FunctionEnvMut<HttpEnv>,
+    f: impl FnOnce(&Guest, &HttpEnv) -> Result<()>,
+) -> u32 {
+    let env = ctx.data();
+    let memory = match &env.memory {
+        Some(memory) => memory,
+        None => return HttpErrno::MemoryNotFound as u32,
+    };
+    let guest = Guest {
+        view: memory.view(ctx),
+    };
+    match f(&guest, env) {
+        Ok(()) => HttpErrno::Success as u32,
+        Err(errno) => {
+            debug!("wasi-http failed with {:?}", errno);
+            errno as u32
+        }
+    }
+}
+
+/// Runs `f` with the open response `handle`.
+fn with_session<T>(
+    env: &HttpEnv,
+    handle: u32,
+    f: impl FnOnce(&mut Session) -> Result<T>,
+) -> Result<T> {
+    let mut sessions = env.sessions.lock().unwrap();
+    let session = sessions
+        .open
+        .get_mut(&handle)
+        .ok_or(HttpErrno::InvalidHandle)?;
+    f(session)
+}
+
+/// A response body, which can't grow past a limit.
+struct Body {
+    bytes: Vec<u8>,
+    limit: usize,
+}
+
+impl Write for Body {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.bytes.len() + buf.len() > self.limit {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("the response body is larger than {} bytes", self.limit),
+            ));
+        }
+        self.bytes.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Sends a request, if `policy` allows it. The headers are written one
+/// per line, as `name:value`, and the ones describing the connection
+/// rather than the request are left out.
+fn send(
+    policy: &HttpPolicy,
+    url: &str,
+    method: &str,
+    headers: &str,
+    body: &[u8],
+) -> Result<Session> {
+    let uri = Uri::try_from(url).map_err(|_| HttpErrno::InvalidUrl)?;
+    if !matches!(uri.scheme(), "http" | "https") {
+        return Err(HttpErrno::InvalidUrl);
+    }
+    let host = uri.host().ok_or(HttpErrno::InvalidUrl)?;
+    let method = method.to_ascii_uppercase();
+    let http_method = match method.as_str() {
+        "GET" => Method::GET,
+        "HEAD" => Method::HEAD,
+        "POST" => Method::POST,
+        "PUT" => Method::PUT,
+        "DELETE" => Method::DELETE,
+        "OPTIONS" => Method::OPTIONS,
+        "PATCH" => Method::PATCH,
+        _ => return Err(HttpErrno::InvalidMethod),
+    };
+    if !policy.allows(&method, host) {
+        debug!("wasi-http: `{} {}` is not allowed", method, url);
+        return Err(HttpErrno::DestinationNotAllowed);
+    }
+
+    let mut request = Request::new(&uri);
+    request
+        .method(http_method)
+        .header("User-Agent", "wasmer")
+        .timeout(Some(TIMEOUT));
+    for line in headers.lines().filter(|line| !line.trim().is_empty()) {
+        let (name, value) = line.split_once(':').ok_or(HttpErrno::InvalidEncoding)?;
+        let name = name.trim();
+        // The host the policy allowed, and the body that is sent
+        if name.eq_ignore_ascii_case("Host") || name.eq_ignore_ascii_case("Content-Length") {
+            debug!("wasi-http: ignoring the `{}` header of the guest", name);
+            continue;
+        }
+        request.header(name, value.trim());
+    }
+    if !body.is_empty() {
+        request.header("Content-Length", &body.len()).body(body);
+    }
+    let mut response_body = Body {
+        bytes: Vec::new(),
+        limit: policy.max_body_size(),
+    };
+    let response = request.send(&mut response_body).map_err(|e| {
+        debug!("wasi-http: `{} {}` failed: {}", method, url, e);
+        HttpErrno::RequestError
+    })?;
+    Ok(Session {
+        status: u16::from(response.status_code()),
+        headers: response
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+        body: response_body.bytes,
+        read: 0,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn req(
+    ctx: FunctionEnvMut<HttpEnv>,
+    url: Ptr<u8>,
+    url_len: u32,
+    method: Ptr<u8>,
+    method_len: u32,
+    headers: Ptr<u8>,
+    headers_len: u32,
+    body: Ptr<u8>,
+    body_len: u32,
+    status_code: Ptr<u16>,
+    handle: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, env| {
+        if env.sessions.lock().unwrap().open.len() >= MAX_SESSIONS {
+            return Err(HttpErrno::TooManySessions);
+        }
+        let session = send(
+            &env.policy,
+            &guest.string(url, url_len)?,
+            &guest.string(method, method_len)?,
+            &guest.string(headers, headers_len)?,
+            &guest.bytes(body, body_len)?,
+        )?;
+        guest.write(status_code, session.status)?;
+
+        let mut sessions = env.sessions.lock().unwrap();
+        let id = sessions.next;
+        sessions.next = sessions.next.wrapping_add(1);
+        sessions.open.insert(id, session);
+        guest.write(handle, id)
+    })
+}
+
+fn close(ctx: FunctionEnvMut<HttpEnv>, handle: u32) -> u32 {
+    with_guest(&ctx, |_, env| {
+        env.sessions
+            .lock()
+            .unwrap()
+            .open
+            .remove(&handle)
+            .map(drop)
+            .ok_or(HttpErrno::InvalidHandle)
+    })
+}
+
+fn body_read(
+    ctx: FunctionEnvMut<HttpEnv>,
+    handle: u32,
+    buf: Ptr<u8>,
+    buf_len: u32,
+    read: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, env| {
+        with_session(env, handle, |session| {
+            let rest = &session.body[session.read..];
+            let len = rest.len().min(buf_len as usize);
+            guest.write_bytes(buf, buf_len, &rest[..len])?;
+            guest.write(read, len as u32)?;
+            session.read += len;
+            Ok(())
+        })
+    })
+}
+
+fn header_get(
+    ctx: FunctionEnvMut<HttpEnv>,
+    handle: u32,
+    name: Ptr<u8>,
+    name_len: u32,
+    value: Ptr<u8>,
+    value_len: u32,
+    written: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, env| {
+        let name = guest.string(name, name_len)?;
+        with_session(env, handle, |session| {
+            let (_, header) = session
+                .headers
+                .iter()
+                .find(|(header, _)| header.eq_ignore_ascii_case(&name))
+                .ok_or(HttpErrno::HeaderNotFound)?;
+            guest.write_bytes(value, value_len, header.as_bytes())?;
+            guest.write(written, header.len() as u32)
+        })
+    })
+}
+
+fn headers_get_all(
+    ctx: FunctionEnvMut<HttpEnv>,
+    handle: u32,
+    buf: Ptr<u8>,
+    buf_len: u32,
+    written: Ptr<u32>,
+) -> u32 {
+    with_guest(&ctx, |guest, env| {
+        with_session(env, handle, |session| {
+            let headers = session
+                .headers
+                .iter()
+                .map(|(name, value)| format!("{}:{}\n", name, value))
+                .collect::<String>();
+            guest.write_bytes(buf, buf_len, headers.as_bytes())?;
+            guest.write(written, headers.len() as u32)
+        })
+    })
+}
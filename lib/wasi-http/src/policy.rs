@@ -0,0 +1,178 @@
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The methods guests can send requests with.
+pub const METHODS: &[&str] = &["GET", "HEAD", "POST", "PUT", "DELETE", "OPTIONS", "PATCH"];
+
+/// An invalid [`HttpRule`].
+#[derive(Debug, Error)]
+pub enum HttpRuleError {
+    /// The rule has no host.
+    #[error("the rule `{0}` has no host")]
+    MissingHost(String),
+    /// The rule allows a method that can't be sent.
+    #[error("unknown HTTP method `{0}`, expected one of {}", METHODS.join(", "))]
+    UnknownMethod(String),
+}
+
+/// Allows requests to a host, with all or some of the methods.
+///
+/// Rules are written `[METHODS=]HOST`, where `METHODS` is a
+/// comma-separated list such as `GET,HEAD`. `HOST` is either a domain
+/// name or address, `*.` followed by a domain name, allowing all of its
+/// subdomains, or `*`, allowing any host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRule {
+    host: String,
+    methods: Option<Vec<String>>,
+}
+
+impl HttpRule {
+    /// Whether the rule allows a request to `host` with `method`.
+    pub fn allows(&self, method: &str, host: &str) -> bool {
+        let method_allowed = match &self.methods {
+            Some(methods) => methods.iter().any(|m| m.eq_ignore_ascii_case(method)),
+            None => true,
+        };
+        method_allowed && self.matches_host(host)
+    }
+
+    fn matches_host(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.');
+        if self.host == "*" {
+            true
+        } else if let Some(domain) = self.host.strip_prefix("*.") {
+            host.len() > domain.len() + 1
+                && host.to_ascii_lowercase().ends_with(domain)
+                && host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+        } else {
+            host.eq_ignore_ascii_case(&self.host)
+        }
+    }
+}
+
+impl FromStr for HttpRule {
+    type Err = HttpRuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (methods, host) = match s.split_once('=') {
+            Some((methods, host)) => {
+                let methods = methods
+                    .split(',')
+                    .map(|method| {
+                        let method = method.trim().to_ascii_uppercase();
+                        if METHODS.contains(&method.as_str()) {
+                            Ok(method)
+                        } else {
+                            Err(HttpRuleError::UnknownMethod(method))
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                (Some(methods), host)
+            }
+            None => (None, s),
+        };
+        let host = host.trim().trim_end_matches('.').to_ascii_lowercase();
+        if host.is_empty() || host == "*." {
+            return Err(HttpRuleError::MissingHost(s.to_string()));
+        }
+        Ok(Self { host, methods })
+    }
+}
+
+impl fmt::Display for HttpRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(methods) = &self.methods {
+            write!(f, "{}=", methods.join(","))?;
+        }
+        write!(f, "{}", self.host)
+    }
+}
+
+/// The size of the largest response body guests can receive by default.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// The requests guests are allowed to send, none by default, and the
+/// size of the responses they can receive.
+#[derive(Debug, Clone)]
+pub struct HttpPolicy {
+    rules: Vec<HttpRule>,
+    max_body_size: usize,
+}
+
+impl Default for HttpPolicy {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+}
+
+impl HttpPolicy {
+    /// Allows the requests of `rule`.
+    pub fn allow(&mut self, rule: HttpRule) -> &mut Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Sets the size of the largest response body, in bytes, past which
+    /// the request fails with `RequestError`. Defaults to
+    /// [`DEFAULT_MAX_BODY_SIZE`].
+    pub fn set_max_body_size(&mut self, bytes: usize) -> &mut Self {
+        self.max_body_size = bytes;
+        self
+    }
+
+    /// The size of the largest response body, in bytes.
+    pub fn max_body_size(&self) -> usize {
+        self.max_body_size
+    }
+
+    /// Whether a request to `host` with `method` is allowed by any rule.
+    pub fn allows(&self, method: &str, host: &str) -> bool {
+        self.rules.iter().any(|rule| rule.allows(method, host))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rules() {
+        let rule: HttpRule = "api.example.com".parse().unwrap();
+        assert_eq!(rule.to_string(), "api.example.com");
+        let rule: HttpRule = "get, Head=*.Example.org".parse().unwrap();
+        assert_eq!(rule.to_string(), "GET,HEAD=*.example.org");
+        assert!(matches!(
+            "FETCH=example.com".parse::<HttpRule>(),
+            Err(HttpRuleError::UnknownMethod(_))
+        ));
+        assert!(matches!(
+            "GET=".parse::<HttpRule>(),
+            Err(HttpRuleError::MissingHost(_))
+        ));
+    }
+
+    #[test]
+    fn policy_allows() {
+        let mut policy = HttpPolicy::default();
+        assert!(!policy.allows("GET", "example.com"));
+
+        policy
+            .allow("api.example.com".parse().unwrap())
+            .allow("GET=*.example.org".parse().unwrap());
+        assert!(policy.allows("POST", "API.example.com"));
+        assert!(!policy.allows("GET", "example.com"));
+        assert!(!policy.allows("GET", "evil-api.example.com"));
+        assert!(policy.allows("GET", "cdn.example.org"));
+        assert!(!policy.allows("GET", "example.org"));
+        assert!(!policy.allows("GET", "badexample.org"));
+        assert!(!policy.allows("PUT", "cdn.example.org"));
+
+        policy.allow("*".parse().unwrap());
+        assert!(policy.allows("DELETE", "anything.test"));
+    }
+}
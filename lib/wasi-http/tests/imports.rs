@@ -0,0 +1,152 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread::{self, JoinHandle};
+use wasmer::{Instance, Module, Singlepass, Store, TypedFunction};
+use wasmer_wasi_http::{HttpPolicy, WasiHttp};
+
+/// Sends a `GET` request to the URL at 1024 with the headers at 2048,
+/// then reads the body of the response at 4096.
+const GUEST: &str = r#"
+(module
+  (import "wasi_experimental_http" "req"
+    (func $req (param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32) (result i32)))
+  (import "wasi_experimental_http" "body_read"
+    (func $body_read (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "GET")
+  (func (export "get") (param $url_len i32) (param $headers_len i32) (result i32)
+    (call $req
+      (i32.const 1024) (local.get $url_len)
+      (i32.const 0) (i32.const 3)
+      (i32.const 2048) (local.get $headers_len)
+      (i32.const 0) (i32.const 0)
+      (i32.const 100) (i32.const 104)))
+  (func (export "read") (result i32)
+    (call $body_read
+      (i32.load (i32.const 104)) (i32.const 4096) (i32.const 60000) (i32.const 108))))
+"#;
+
+const EINVALIDHANDLE: u32 = 1;
+const EDESTINATIONNOTALLOWED: u32 = 7;
+const EREQUESTERROR: u32 = 11;
+
+struct Guest {
+    store: Store,
+    instance: Instance,
+}
+
+impl Guest {
+    fn new(policy: HttpPolicy) -> Self {
+        let mut store = Store::new(Singlepass::default());
+        let module = Module::new(&store, GUEST).unwrap();
+        let http = WasiHttp::new(&mut store, policy);
+        let imports = http.import_object(&mut store);
+        let instance = Instance::new(&mut store, &module, &imports).unwrap();
+        http.initialize(&mut store, &instance).unwrap();
+        Self { store, instance }
+    }
+
+    fn get(&mut self, url: &str, headers: &str) -> u32 {
+        let memory = self.instance.exports.get_memory("memory").unwrap();
+        let view = memory.view(&self.store);
+        view.write(1024, url.as_bytes()).unwrap();
+        view.write(2048, headers.as_bytes()).unwrap();
+        let get: TypedFunction<(u32, u32), u32> = self
+            .instance
+            .exports
+            .get_typed_function(&self.store, "get")
+            .unwrap();
+        get.call(&mut self.store, url.len() as u32, headers.len() as u32)
+            .unwrap()
+    }
+
+    fn status(&self) -> u16 {
+        let memory = self.instance.exports.get_memory("memory").unwrap();
+        let mut status = [0; 2];
+        memory.view(&self.store).read(100, &mut status).unwrap();
+        u16::from_le_bytes(status)
+    }
+
+    fn read_body(&mut self) -> Result<Vec<u8>, u32> {
+        let read: TypedFunction<(), u32> = self
+            .instance
+            .exports
+            .get_typed_function(&self.store, "read")
+            .unwrap();
+        match read.call(&mut self.store).unwrap() {
+            0 => {}
+            errno => return Err(errno),
+        }
+        let memory = self.instance.exports.get_memory("memory").unwrap();
+        let view = memory.view(&self.store);
+        let mut len = [0; 4];
+        view.read(108, &mut len).unwrap();
+        let mut body = vec![0; u32::from_le_bytes(len) as usize];
+        view.read(4096, &mut body).unwrap();
+        Ok(body)
+    }
+}
+
+/// Answers a single request with `body`, returning the head of the
+/// request.
+fn serve_once(body: &'static [u8]) -> (String, JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut head = String::new();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" || line.is_empty() {
+                break;
+            }
+            head.push_str(&line);
+        }
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        )
+        .unwrap();
+        let _ = stream.write_all(body);
+        head
+    });
+    (url, server)
+}
+
+#[test]
+fn requests_must_be_allowed() {
+    let mut guest = Guest::new(HttpPolicy::default());
+    assert_eq!(guest.get("http://127.0.0.1:1/", ""), EDESTINATIONNOTALLOWED);
+    assert_eq!(guest.read_body(), Err(EINVALIDHANDLE));
+}
+
+#[test]
+fn guest_host_header_is_ignored() {
+    let (url, server) = serve_once(b"hello");
+    let mut policy = HttpPolicy::default();
+    policy.allow("GET=127.0.0.1".parse().unwrap());
+    let mut guest = Guest::new(policy);
+    assert_eq!(guest.get(&url, "Host: evil.example\nX-Trace: 42\n"), 0);
+    assert_eq!(guest.status(), 200);
+    assert_eq!(guest.read_body().unwrap(), b"hello");
+
+    let head = server.join().unwrap().to_ascii_lowercase();
+    assert!(head.contains("host: 127.0.0.1"), "{}", head);
+    assert!(!head.contains("evil.example"), "{}", head);
+    assert!(head.contains("x-trace: 42"), "{}", head);
+}
+
+#[test]
+fn responses_are_size_limited() {
+    let (url, server) = serve_once(&[b'a'; 64]);
+    let mut policy = HttpPolicy::default();
+    policy
+        .allow("127.0.0.1".parse().unwrap())
+        .set_max_body_size(16);
+    let mut guest = Guest::new(policy);
+    assert_eq!(guest.get(&url, ""), EREQUESTERROR);
+    server.join().unwrap();
+}
@@ -0,0 +1,435 @@
+//! Hardening the process running an untrusted module.
+//!
+//! WASI confines a module to the capabilities it was given, but a bug in
+//! the runtime or in a compiler could still let it run code with all the
+//! privileges of the process. [`WasiHardening`] is a second line of
+//! defense: on Linux, it installs a seccomp filter only allowing the
+//! system calls the runtime needs for the capabilities of the module, so
+//! that such code couldn't start programs, nor open files when the module
+//! has no preopened directories, or sockets when it isn't a WASIX module.
+//!
+//! A seccomp filter can't read the paths the system calls are given, so
+//! the filesystem is allowed or denied as a whole: once a module has a
+//! preopened directory, the process can still open any file it has access
+//! to.
+//!
+//! The filter is installed once the module is compiled and its WASI
+//! environment built, before the module runs, and applies to all the
+//! threads of the process until it exits: the host can't open files or
+//! sockets anymore either, unless they are allowed.
+
+use crate::WasiState;
+use std::io;
+use std::sync::atomic::Ordering;
+use thiserror::Error;
+
+/// An error hardening the process.
+#[derive(Error, Debug)]
+pub enum WasiHardeningError {
+    /// The platform has no supported way to restrict the system calls of
+    /// the process.
+    #[error("hardening the process isn't supported on this platform")]
+    Unsupported,
+
+    /// The kernel refused the filter, e.g. because it was built without
+    /// seccomp.
+    #[error("failed to install the seccomp filter: {0}")]
+    Install(io::Error),
+}
+
+/// The system calls the process keeps once hardened.
+///
+/// The computations, memory, clocks and random of the module are always
+/// allowed, with reading and writing the files that are already open,
+/// such as its standard streams, and starting threads. Everything else,
+/// like starting processes, fails with `EPERM`.
+#[derive(Debug, Clone, Default)]
+pub struct WasiHardening {
+    filesystem: bool,
+    network: bool,
+}
+
+impl WasiHardening {
+    /// Allows nothing but the system calls every module needs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows the system calls `state` needs: the filesystem when it has
+    /// preopened directories, and the network for WASIX modules.
+    pub fn for_state(state: &WasiState) -> Self {
+        let mut hardening = Self::new();
+        hardening
+            .filesystem(!state.fs.preopen_fds.read().unwrap().is_empty())
+            .network(state.fs.is_wasix.load(Ordering::Acquire));
+        hardening
+    }
+
+    /// Allows opening, creating and removing files and directories,
+    /// anywhere the process has access to.
+    pub fn filesystem(&mut self, allow: bool) -> &mut Self {
+        self.filesystem = allow;
+
+        self
+    }
+
+    /// Allows creating sockets and using them.
+    pub fn network(&mut self, allow: bool) -> &mut Self {
+        self.network = allow;
+
+        self
+    }
+
+    /// Restricts the system calls of all the threads of the process to
+    /// the allowed ones, for the rest of its life.
+    pub fn install(&self) -> Result<(), WasiHardeningError> {
+        cfg_if::cfg_if! {
+            if #[cfg(all(
+                target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            ))] {
+                seccomp::install(self)
+            } else {
+                Err(WasiHardeningError::Unsupported)
+            }
+        }
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod seccomp {
+    use super::{WasiHardening, WasiHardeningError};
+    use libc::c_long;
+    use std::io;
+
+    // From <linux/filter.h>, <linux/seccomp.h> and <linux/audit.h>
+    const BPF_LD_W_ABS: u16 = 0x20;
+    const BPF_JMP_JEQ_K: u16 = 0x15;
+    const BPF_JMP_JSET_K: u16 = 0x45;
+    const BPF_RET_K: u16 = 0x06;
+    const SECCOMP_SET_MODE_FILTER: libc::c_uint = 1;
+    const SECCOMP_FILTER_FLAG_TSYNC: libc::c_uint = 1;
+    const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+    const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+    const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+    /// The offsets of the fields of `struct seccomp_data` the filter
+    /// reads, the first argument being little-endian.
+    const NR_OFFSET: u32 = 0;
+    const ARCH_OFFSET: u32 = 4;
+    const ARG0_OFFSET: u32 = 16;
+
+    /// Computing, memory, clocks, random, signals and the open files.
+    const BASE: &[c_long] = &[
+        libc::SYS_read,
+        libc::SYS_write,
+        libc::SYS_readv,
+        libc::SYS_writev,
+        libc::SYS_pread64,
+        libc::SYS_pwrite64,
+        libc::SYS_preadv,
+        libc::SYS_pwritev,
+        libc::SYS_lseek,
+        libc::SYS_close,
+        libc::SYS_fstat,
+        libc::SYS_newfstatat,
+        libc::SYS_statx,
+        libc::SYS_fcntl,
+        libc::SYS_dup,
+        libc::SYS_dup3,
+        libc::SYS_ioctl,
+        libc::SYS_ppoll,
+        libc::SYS_pselect6,
+        libc::SYS_mmap,
+        libc::SYS_munmap,
+        libc::SYS_mprotect,
+        libc::SYS_mremap,
+        libc::SYS_madvise,
+        libc::SYS_brk,
+        libc::SYS_futex,
+        libc::SYS_sched_yield,
+        libc::SYS_sched_getaffinity,
+        libc::SYS_set_robust_list,
+        libc::SYS_rseq,
+        libc::SYS_rt_sigaction,
+        libc::SYS_rt_sigprocmask,
+        libc::SYS_rt_sigreturn,
+        libc::SYS_sigaltstack,
+        libc::SYS_tgkill,
+        libc::SYS_restart_syscall,
+        libc::SYS_clock_gettime,
+        libc::SYS_clock_getres,
+        libc::SYS_clock_nanosleep,
+        libc::SYS_nanosleep,
+        libc::SYS_gettimeofday,
+        libc::SYS_getrandom,
+        libc::SYS_getpid,
+        libc::SYS_gettid,
+        libc::SYS_prlimit64,
+        libc::SYS_uname,
+        libc::SYS_exit,
+        libc::SYS_exit_group,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_poll,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_dup2,
+    ];
+
+    /// Opening, creating and removing files and directories.
+    const FILESYSTEM: &[c_long] = &[
+        libc::SYS_openat,
+        libc::SYS_mkdirat,
+        libc::SYS_unlinkat,
+        libc::SYS_renameat,
+        libc::SYS_renameat2,
+        libc::SYS_linkat,
+        libc::SYS_symlinkat,
+        libc::SYS_readlinkat,
+        libc::SYS_faccessat,
+        libc::SYS_getdents64,
+        libc::SYS_getcwd,
+        libc::SYS_fstatfs,
+        libc::SYS_statfs,
+        libc::SYS_ftruncate,
+        libc::SYS_fallocate,
+        libc::SYS_fsync,
+        libc::SYS_fdatasync,
+        libc::SYS_fchmod,
+        libc::SYS_fchmodat,
+        libc::SYS_utimensat,
+        libc::SYS_flock,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_open,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_stat,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_lstat,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_access,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_mkdir,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_rmdir,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_unlink,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_rename,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_link,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_symlink,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_readlink,
+    ];
+
+    /// Creating sockets, using them, and waiting for them.
+    const NETWORK: &[c_long] = &[
+        libc::SYS_socket,
+        libc::SYS_socketpair,
+        libc::SYS_connect,
+        libc::SYS_bind,
+        libc::SYS_listen,
+        libc::SYS_accept,
+        libc::SYS_accept4,
+        libc::SYS_sendto,
+        libc::SYS_recvfrom,
+        libc::SYS_sendmsg,
+        libc::SYS_recvmsg,
+        libc::SYS_shutdown,
+        libc::SYS_getsockname,
+        libc::SYS_getpeername,
+        libc::SYS_setsockopt,
+        libc::SYS_getsockopt,
+        libc::SYS_epoll_create1,
+        libc::SYS_epoll_ctl,
+        libc::SYS_epoll_pwait,
+        libc::SYS_eventfd2,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_epoll_wait,
+    ];
+
+    #[repr(C)]
+    struct SockFilter {
+        code: u16,
+        jt: u8,
+        jf: u8,
+        k: u32,
+    }
+
+    #[repr(C)]
+    struct SockFprog {
+        len: libc::c_ushort,
+        filter: *const SockFilter,
+    }
+
+    fn stmt(code: u16, k: u32) -> SockFilter {
+        SockFilter {
+            code,
+            jt: 0,
+            jf: 0,
+            k,
+        }
+    }
+
+    fn jump(code: u16, k: u32, jt: u8, jf: u8) -> SockFilter {
+        SockFilter { code, jt, jf, k }
+    }
+
+    /// The BPF program allowing the system calls of `hardening`.
+    fn filter(hardening: &WasiHardening) -> Vec<SockFilter> {
+        let deny = SECCOMP_RET_ERRNO | libc::EPERM as u32;
+        let mut filter = vec![
+            // The numbers of the system calls are the ones of this
+            // architecture
+            stmt(BPF_LD_W_ABS, ARCH_OFFSET),
+            jump(BPF_JMP_JEQ_K, AUDIT_ARCH, 1, 0),
+            stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+            stmt(BPF_LD_W_ABS, NR_OFFSET),
+            // Threads, but not processes
+            jump(BPF_JMP_JEQ_K, libc::SYS_clone as u32, 0, 4),
+            stmt(BPF_LD_W_ABS, ARG0_OFFSET),
+            jump(BPF_JMP_JSET_K, libc::CLONE_THREAD as u32, 0, 1),
+            stmt(BPF_RET_K, SECCOMP_RET_ALLOW),
+            stmt(BPF_RET_K, deny),
+            // The flags of `clone3` can't be checked, so the C library
+            // falls back to `clone`
+            jump(BPF_JMP_JEQ_K, libc::SYS_clone3 as u32, 0, 1),
+            stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::ENOSYS as u32),
+        ];
+        let allowed = BASE
+            .iter()
+            .chain(if hardening.filesystem {
+                FILESYSTEM
+            } else {
+                &[][..]
+            })
+            .chain(if hardening.network { NETWORK } else { &[][..] });
+        for &nr in allowed {
+            filter.push(jump(BPF_JMP_JEQ_K, nr as u32, 0, 1));
+            filter.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+        }
+        filter.push(stmt(BPF_RET_K, deny));
+        filter
+    }
+
+    /// Installs the filter of `hardening` on all the threads of the
+    /// process.
+    pub(super) fn install(hardening: &WasiHardening) -> Result<(), WasiHardeningError> {
+        let filter = filter(hardening);
+        let prog = SockFprog {
+            len: filter.len() as libc::c_ushort,
+            filter: filter.as_ptr(),
+        };
+        // Without `CAP_SYS_ADMIN`, a filter can only be installed by a
+        // process that can't gain privileges with `execve`
+        let result = unsafe {
+            libc::prctl(
+                libc::PR_SET_NO_NEW_PRIVS,
+                1 as libc::c_ulong,
+                0 as libc::c_ulong,
+                0 as libc::c_ulong,
+                0 as libc::c_ulong,
+            )
+        };
+        if result != 0 {
+            return Err(WasiHardeningError::Install(io::Error::last_os_error()));
+        }
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_seccomp,
+                SECCOMP_SET_MODE_FILTER,
+                SECCOMP_FILTER_FLAG_TSYNC,
+                &prog as *const SockFprog,
+            )
+        };
+        match result {
+            0 => Ok(()),
+            // The id of a thread that couldn't be synchronized
+            tid if tid > 0 => Err(WasiHardeningError::Install(io::Error::new(
+                io::ErrorKind::Other,
+                format!("the thread {} can't be restricted", tid),
+            ))),
+            _ => Err(WasiHardeningError::Install(io::Error::last_os_error())),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Whether the program of `filter` allows the system call `nr`.
+        fn allows(filter: &[SockFilter], nr: c_long) -> bool {
+            filter.windows(2).any(|instructions| {
+                let (check, ret) = (&instructions[0], &instructions[1]);
+                check.code == BPF_JMP_JEQ_K
+                    && check.k == nr as u32
+                    && ret.code == BPF_RET_K
+                    && ret.k == SECCOMP_RET_ALLOW
+            })
+        }
+
+        #[test]
+        fn filter_allows_the_capabilities() {
+            let filter = super::filter(&WasiHardening::new());
+            assert!(allows(&filter, libc::SYS_write));
+            assert!(!allows(&filter, libc::SYS_openat));
+            assert!(!allows(&filter, libc::SYS_socket));
+            assert!(!allows(&filter, libc::SYS_execve));
+            let last = filter.last().unwrap();
+            assert_eq!(
+                (last.code, last.k),
+                (BPF_RET_K, SECCOMP_RET_ERRNO | libc::EPERM as u32)
+            );
+
+            let filter = super::filter(WasiHardening::new().filesystem(true));
+            assert!(allows(&filter, libc::SYS_openat));
+            assert!(!allows(&filter, libc::SYS_socket));
+
+            let filter = super::filter(WasiHardening::new().network(true));
+            assert!(!allows(&filter, libc::SYS_openat));
+            assert!(allows(&filter, libc::SYS_socket));
+        }
+
+        #[test]
+        fn hardened_process_cant_open_files() {
+            // The filter applies to the process for the rest of its life,
+            // so it is installed in a child
+            match unsafe { libc::fork() } {
+                -1 => panic!("fork failed: {}", io::Error::last_os_error()),
+                0 => {
+                    let code = match install(&WasiHardening::new()) {
+                        Ok(()) => {
+                            let path = b"/\0";
+                            let fd = unsafe {
+                                libc::openat(libc::AT_FDCWD, path.as_ptr() as *const _, 0)
+                            };
+                            let denied = fd == -1
+                                && io::Error::last_os_error().raw_os_error() == Some(libc::EPERM);
+                            if denied {
+                                0
+                            } else {
+                                1
+                            }
+                        }
+                        // Kernels without seccomp
+                        Err(_) => 2,
+                    };
+                    unsafe { libc::_exit(code) }
+                }
+                child => {
+                    let mut status = 0;
+                    assert_eq!(unsafe { libc::waitpid(child, &mut status, 0) }, child);
+                    assert!(libc::WIFEXITED(status));
+                    assert_ne!(libc::WEXITSTATUS(status), 1, "`openat` wasn't denied");
+                }
+            }
+        }
+    }
+}
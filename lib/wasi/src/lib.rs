@@ -35,6 +35,7 @@ compile_error!(
 
 #[macro_use]
 mod macros;
+mod hardening;
 mod runtime;
 mod state;
 mod syscalls;
@@ -42,9 +43,11 @@ mod utils;
 
 use crate::syscalls::*;
 
+pub use crate::hardening::{WasiHardening, WasiHardeningError};
 pub use crate::state::{
-    Fd, Pipe, Stderr, Stdin, Stdout, WasiFs, WasiInodes, WasiState, WasiStateBuilder,
-    WasiStateCreationError, ALL_RIGHTS, VIRTUAL_ROOT_FD,
+    Fd, Pipe, Stderr, Stdin, Stdout, WasiFs, WasiInodes, WasiInterruptHandle, WasiQuotaExceeded,
+    WasiQuotaFile, WasiQuotaFileSystem, WasiState, WasiStateBuilder, WasiStateCreationError,
+    WasiVirtualClock, WasiWriteQuota, ALL_RIGHTS, VIRTUAL_ROOT_FD,
 };
 pub use crate::syscalls::types;
 #[cfg(feature = "wasix")]
@@ -83,6 +86,8 @@ pub enum WasiError {
     Exit(syscalls::types::__wasi_exitcode_t),
     #[error("The WASI version could not be determined")]
     UnknownWasiVersion,
+    #[error("WASI was interrupted by the host")]
+    Interrupted,
 }
 
 /// Represents the ID of a WASI thread
@@ -157,9 +162,10 @@ impl WasiFunctionEnv {
         module: &Module,
     ) -> Result<Imports, WasiError> {
         let wasi_version = get_wasi_version(module, false).ok_or(WasiError::UnknownWasiVersion)?;
-        Ok(generate_import_object_from_env(
+        Ok(generate_import_object_for_module(
             store,
             &self.env,
+            module,
             wasi_version,
         ))
     }
@@ -207,7 +213,8 @@ impl WasiFunctionEnv {
 
         let mut resolver = Imports::new();
         for version in wasi_versions.iter() {
-            let new_import_object = generate_import_object_from_env(store, &self.env, *version);
+            let new_import_object =
+                generate_import_object_for_module(store, &self.env, module, *version);
             for ((n, m), e) in new_import_object.into_iter() {
                 resolver.define(&n, &m, e);
             }
@@ -312,19 +319,51 @@ impl WasiEnv {
         self.memory.clone()
     }
 
+    /// Returns a handle that can interrupt this environment while it is
+    /// blocked in a sleeping or polling WASI call.
+    pub fn interrupt_handle(&self) -> WasiInterruptHandle {
+        self.state.interrupt.clone()
+    }
+
+    /// Returns the virtual clock driving this environment, if any.
+    pub fn virtual_clock(&self) -> Option<WasiVirtualClock> {
+        self.state.clock.clone()
+    }
+
+    /// Reads a clock, virtual if this environment is driven by one
+    pub(crate) fn clock_time_get(
+        &self,
+        clock_id: types::__wasi_clockid_t,
+        precision: types::__wasi_timestamp_t,
+    ) -> Result<i64, types::__wasi_errno_t> {
+        match self.state.clock.as_ref() {
+            Some(clock) => clock.time_get(clock_id),
+            None => platform_clock_time_get(clock_id, precision),
+        }
+    }
+
     // Yields execution
     pub fn yield_now(&self) -> Result<(), WasiError> {
+        if self.state.interrupt.is_interrupted() {
+            return Err(WasiError::Interrupted);
+        }
         self.runtime.yield_now(self.id)?;
         Ok(())
     }
 
-    // Sleeps for a period of time
+    // Sleeps for a period of time, waking up early if the environment
+    // is interrupted
     pub fn sleep(&self, duration: Duration) -> Result<(), WasiError> {
+        if let Some(clock) = self.state.clock.as_ref() {
+            self.yield_now()?;
+            clock.sleep(duration, &self.state.interrupt);
+            return self.yield_now();
+        }
         let duration = duration.as_nanos();
-        let start = platform_clock_time_get(__WASI_CLOCK_MONOTONIC, 1_000_000).unwrap() as u128;
+        let start = platform_clock_time_get(__WASI_CLOCK_MONOTONIC, 1).unwrap() as u128;
         self.yield_now()?;
         loop {
-            let now = platform_clock_time_get(__WASI_CLOCK_MONOTONIC, 1_000_000).unwrap() as u128;
+            let now = platform_clock_time_get(__WASI_CLOCK_MONOTONIC, 1).unwrap() as u128;
             let delta = match now.checked_sub(start) {
                 Some(a) => a,
                 None => {
@@ -340,7 +379,11 @@ impl WasiEnv {
                     break;
                 }
             };
-            std::thread::sleep(remaining.min(Duration::from_millis(10)));
+            // Wake up regularly so runtimes that rely on `yield_now` keep
+            // making progress.
+            self.state
+                .interrupt
+                .wait_timeout(remaining.min(Duration::from_millis(10)));
             self.yield_now()?;
         }
         Ok(())
@@ -433,6 +476,25 @@ pub fn generate_import_object_from_env(
     }
 }
 
+/// Like [`generate_import_object_from_env`], but detecting from `module`
+/// whether its `wasi_snapshot_preview1` imports take 32-bit or 64-bit
+/// pointers and sizes.
+#[cfg_attr(not(feature = "wasix"), allow(unused_variables))]
+pub fn generate_import_object_for_module(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<WasiEnv>,
+    module: &Module,
+    version: WasiVersion,
+) -> Imports {
+    match version {
+        #[cfg(feature = "wasix")]
+        WasiVersion::Snapshot1 | WasiVersion::Latest => {
+            generate_import_object_snapshot1_for_module(store, env, module)
+        }
+        _ => generate_import_object_from_env(store, env, version),
+    }
+}
+
 fn wasi_unstable_exports(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>) -> Exports {
     let namespace = namespace! {
         "args_get" => Function::new_typed_with_env(&mut store, env, args_get::<Memory32>),
@@ -537,6 +599,64 @@ fn wasi_snapshot_preview1_exports(
     };
     namespace
 }
+/// The `wasi_snapshot_preview1` namespace for modules built for wasm64,
+/// which pass pointers and sizes as 64-bit integers.
+#[cfg(feature = "wasix")]
+fn wasi_snapshot_preview1_exports_64(
+    mut store: &mut impl AsStoreMut,
+    env: &FunctionEnv<WasiEnv>,
+) -> Exports {
+    use self::wasix64::*;
+    let namespace = namespace! {
+        "args_get" => Function::new_typed_with_env(&mut store, env, args_get),
+        "args_sizes_get" => Function::new_typed_with_env(&mut store, env, args_sizes_get),
+        "clock_res_get" => Function::new_typed_with_env(&mut store, env, clock_res_get),
+        "clock_time_get" => Function::new_typed_with_env(&mut store, env, clock_time_get),
+        "environ_get" => Function::new_typed_with_env(&mut store, env, environ_get),
+        "environ_sizes_get" => Function::new_typed_with_env(&mut store, env, environ_sizes_get),
+        "fd_advise" => Function::new_typed_with_env(&mut store, env, fd_advise),
+        "fd_allocate" => Function::new_typed_with_env(&mut store, env, fd_allocate),
+        "fd_close" => Function::new_typed_with_env(&mut store, env, fd_close),
+        "fd_datasync" => Function::new_typed_with_env(&mut store, env, fd_datasync),
+        "fd_fdstat_get" => Function::new_typed_with_env(&mut store, env, fd_fdstat_get),
+        "fd_fdstat_set_flags" => Function::new_typed_with_env(&mut store, env, fd_fdstat_set_flags),
+        "fd_fdstat_set_rights" => Function::new_typed_with_env(&mut store, env, fd_fdstat_set_rights),
+        "fd_filestat_get" => Function::new_typed_with_env(&mut store, env, fd_filestat_get),
+        "fd_filestat_set_size" => Function::new_typed_with_env(&mut store, env, fd_filestat_set_size),
+        "fd_filestat_set_times" => Function::new_typed_with_env(&mut store, env, fd_filestat_set_times),
+        "fd_pread" => Function::new_typed_with_env(&mut store, env, fd_pread),
+        "fd_prestat_get" => Function::new_typed_with_env(&mut store, env, fd_prestat_get),
+        "fd_prestat_dir_name" => Function::new_typed_with_env(&mut store, env, fd_prestat_dir_name),
+        "fd_pwrite" => Function::new_typed_with_env(&mut store, env, fd_pwrite),
+        "fd_read" => Function::new_typed_with_env(&mut store, env, fd_read),
+        "fd_readdir" => Function::new_typed_with_env(&mut store, env, fd_readdir),
+        "fd_renumber" => Function::new_typed_with_env(&mut store, env, fd_renumber),
+        "fd_seek" => Function::new_typed_with_env(&mut store, env, fd_seek),
+        "fd_sync" => Function::new_typed_with_env(&mut store, env, fd_sync),
+        "fd_tell" => Function::new_typed_with_env(&mut store, env, fd_tell),
+        "fd_write" => Function::new_typed_with_env(&mut store, env, fd_write),
+        "path_create_directory" => Function::new_typed_with_env(&mut store, env, path_create_directory),
+        "path_filestat_get" => Function::new_typed_with_env(&mut store, env, path_filestat_get),
+        "path_filestat_set_times" => Function::new_typed_with_env(&mut store, env, path_filestat_set_times),
+        "path_link" => Function::new_typed_with_env(&mut store, env, path_link),
+        "path_open" => Function::new_typed_with_env(&mut store, env, path_open),
+        "path_readlink" => Function::new_typed_with_env(&mut store, env, path_readlink),
+        "path_remove_directory" => Function::new_typed_with_env(&mut store, env, path_remove_directory),
+        "path_rename" => Function::new_typed_with_env(&mut store, env, path_rename),
+        "path_symlink" => Function::new_typed_with_env(&mut store, env, path_symlink),
+        "path_unlink_file" => Function::new_typed_with_env(&mut store, env, path_unlink_file),
+        "poll_oneoff" => Function::new_typed_with_env(&mut store, env, poll_oneoff),
+        "proc_exit" => Function::new_typed_with_env(&mut store, env, proc_exit),
+        "proc_raise" => Function::new_typed_with_env(&mut store, env, proc_raise),
+        "random_get" => Function::new_typed_with_env(&mut store, env, random_get),
+        "sched_yield" => Function::new_typed_with_env(&mut store, env, sched_yield),
+        "sock_recv" => Function::new_typed_with_env(&mut store, env, sock_recv),
+        "sock_send" => Function::new_typed_with_env(&mut store, env, sock_send),
+        "sock_shutdown" => Function::new_typed_with_env(&mut store, env, sock_shutdown),
+    };
+    namespace
+}
+
 pub fn import_object_for_all_wasi_versions(
     store: &mut impl AsStoreMut,
     env: &FunctionEnv<WasiEnv>,
@@ -570,6 +690,51 @@ fn generate_import_object_snapshot1(
     }
 }
 
+/// Like `generate_import_object_snapshot1`, but passing pointers and sizes
+/// as 64-bit integers if the module was built for wasm64.
+///
+/// The ABI is detected from the signatures of the module imports: the
+/// 64-bit exports are used if they match the imports while the 32-bit
+/// ones don't.
+#[cfg(feature = "wasix")]
+fn generate_import_object_snapshot1_for_module(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<WasiEnv>,
+    module: &Module,
+) -> Imports {
+    let namespace = WasiVersion::Snapshot1.get_namespace_str();
+    let mut exports = wasi_snapshot_preview1_exports(store, env);
+    if !exports_match_imports(store, module, namespace, &exports) {
+        let exports64 = wasi_snapshot_preview1_exports_64(store, env);
+        if exports_match_imports(store, module, namespace, &exports64) {
+            exports = exports64;
+        }
+    }
+    let mut imports = Imports::new();
+    imports.register_namespace(namespace, exports);
+    imports
+}
+
+/// Checks that the functions of `exports` have the signatures `module`
+/// expects for the imports of `namespace`.
+#[cfg(feature = "wasix")]
+fn exports_match_imports(
+    store: &impl AsStoreRef,
+    module: &Module,
+    namespace: &str,
+    exports: &Exports,
+) -> bool {
+    module
+        .imports()
+        .functions()
+        .filter(|import| import.module() == namespace)
+        .all(|import| match exports.get_function(import.name()) {
+            Ok(function) => function.ty(store) == *import.ty(),
+            // Unknown functions fail to link whatever the ABI.
+            Err(_) => true,
+        })
+}
+
 /// Combines a state generating function with the import list for snapshot 1
 #[cfg(feature = "wasix")]
 fn generate_import_object_wasix32_v1(
@@ -1,6 +1,9 @@
 //! Builder system for configuring a [`WasiState`] and creating it.
 
-use crate::state::{default_fs_backing, WasiFs, WasiState};
+use crate::state::{
+    default_fs_backing, mount_path, Mount, MountFileSystem, WasiFs, WasiQuotaFile,
+    WasiQuotaFileSystem, WasiState, WasiVirtualClock, WasiWriteQuota,
+};
 use crate::syscalls::types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO};
 use crate::{WasiEnv, WasiFunctionEnv, WasiInodes};
 use generational_arena::Arena;
@@ -51,7 +54,11 @@ pub struct WasiStateBuilder {
     stderr_override: Option<Box<dyn VirtualFile + Send + Sync + 'static>>,
     stdin_override: Option<Box<dyn VirtualFile + Send + Sync + 'static>>,
     fs_override: Option<Box<dyn wasmer_vfs::FileSystem>>,
+    mounts: Vec<Mount>,
     runtime_override: Option<Arc<dyn crate::WasiRuntimeImplementation + Send + Sync + 'static>>,
+    virtual_clock: Option<WasiVirtualClock>,
+    max_output: Option<u64>,
+    fs_quota: Option<u64>,
 }
 
 impl std::fmt::Debug for WasiStateBuilder {
@@ -61,11 +68,15 @@ impl std::fmt::Debug for WasiStateBuilder {
             .field("args", &self.args)
             .field("envs", &self.envs)
             .field("preopens", &self.preopens)
+            .field("mounts", &self.mounts)
             .field("setup_fs_fn exists", &self.setup_fs_fn.is_some())
             .field("stdout_override exists", &self.stdout_override.is_some())
             .field("stderr_override exists", &self.stderr_override.is_some())
             .field("stdin_override exists", &self.stdin_override.is_some())
             .field("runtime_override_exists", &self.runtime_override.is_some())
+            .field("virtual_clock", &self.virtual_clock)
+            .field("max_output", &self.max_output)
+            .field("fs_quota", &self.fs_quota)
             .finish()
     }
 }
@@ -285,6 +296,53 @@ impl WasiStateBuilder {
         Ok(self)
     }
 
+    /// Mounts a filesystem read-only at `/alias`, like a preopened
+    /// directory.
+    ///
+    /// The filesystem can be an archive loaded in memory, such as a
+    /// `wasmer_vfs::mem_fs::FileSystem::from_archive`, to give the module
+    /// its assets without extracting them.
+    pub fn mount(
+        &mut self,
+        alias: &str,
+        fs: Box<dyn wasmer_vfs::FileSystem>,
+    ) -> Result<&mut Self, WasiStateCreationError> {
+        self.add_mount(alias, fs, false)
+    }
+
+    /// Mounts a filesystem at `/alias`, like a preopened directory the
+    /// module can write to.
+    ///
+    /// The filesystem can be a `wasmer_vfs::overlay_fs::FileSystem`, to
+    /// give the module an apparently writable copy of a read-only tree,
+    /// such as the contents of a package.
+    pub fn mount_writable(
+        &mut self,
+        alias: &str,
+        fs: Box<dyn wasmer_vfs::FileSystem>,
+    ) -> Result<&mut Self, WasiStateCreationError> {
+        self.add_mount(alias, fs, true)
+    }
+
+    fn add_mount(
+        &mut self,
+        alias: &str,
+        fs: Box<dyn wasmer_vfs::FileSystem>,
+        writable: bool,
+    ) -> Result<&mut Self, WasiStateCreationError> {
+        let mut pdb = PreopenDirBuilder::new();
+        pdb.directory(mount_path(self.mounts.len()))
+            .alias(alias)
+            .read(true)
+            .create(writable);
+        let preopen = pdb.build()?;
+
+        self.preopens.push(preopen);
+        self.mounts.push(Mount { fs, writable });
+
+        Ok(self)
+    }
+
     /// Overwrite the default WASI `stdout`, if you want to hold on to the
     /// original `stdout` use [`WasiFs::swap_file`] after building.
     pub fn stdout(&mut self, new_file: Box<dyn VirtualFile + Send + Sync + 'static>) -> &mut Self {
@@ -336,6 +394,28 @@ impl WasiStateBuilder {
         self
     }
 
+    /// Drives the clocks, sleeps and poll timeouts of the guest with a
+    /// host-controlled [`WasiVirtualClock`] instead of the system clocks.
+    pub fn virtual_clock(&mut self, clock: WasiVirtualClock) -> &mut Self {
+        self.virtual_clock = Some(clock);
+        self
+    }
+
+    /// Limits the number of bytes the guest can write to its standard
+    /// output and error together, see [`WasiWriteQuota`].
+    pub fn max_output(&mut self, bytes: u64) -> &mut Self {
+        self.max_output = Some(bytes);
+        self
+    }
+
+    /// Limits the number of bytes the guest can write to the files of the
+    /// filesystem, such as the ones of the preopened directories, see
+    /// [`WasiWriteQuota`].
+    pub fn fs_quota(&mut self, bytes: u64) -> &mut Self {
+        self.fs_quota = Some(bytes);
+        self
+    }
+
     /// Consumes the [`WasiStateBuilder`] and produces a [`WasiState`]
     ///
     /// Returns the error from `WasiFs::new` if there's an error
@@ -348,6 +428,8 @@ impl WasiStateBuilder {
     /// reset to their defaults:
     ///
     /// * [Self::set_fs],
+    /// * [Self::mount],
+    /// * [Self::mount_writable],
     /// * [Self::stdin],
     /// * [Self::stdout],
     /// * [Self::stderr].
@@ -418,7 +500,19 @@ impl WasiStateBuilder {
             }
         }
 
-        let fs_backing = self.fs_override.take().unwrap_or_else(default_fs_backing);
+        let mut fs_backing = self.fs_override.take().unwrap_or_else(default_fs_backing);
+        if !self.mounts.is_empty() {
+            fs_backing = Box::new(MountFileSystem::new(
+                fs_backing,
+                std::mem::take(&mut self.mounts),
+            ));
+        }
+        if let Some(limit) = self.fs_quota {
+            fs_backing = Box::new(WasiQuotaFileSystem::new(
+                fs_backing,
+                WasiWriteQuota::new(limit),
+            ));
+        }
 
         // self.preopens are checked in [`PreopenDirBuilder::build`]
         let inodes = RwLock::new(crate::state::WasiInodes {
@@ -456,6 +550,18 @@ impl WasiStateBuilder {
                     .map_err(WasiStateCreationError::FileSystemError)?;
             }
 
+            if let Some(limit) = self.max_output {
+                let quota = WasiWriteQuota::new(limit);
+                for fd in [__WASI_STDOUT_FILENO, __WASI_STDERR_FILENO] {
+                    let mut stdio = inodes
+                        .std_dev_get_mut(&wasi_fs.fd_map, fd)
+                        .map_err(WasiStateCreationError::FileSystemError)?;
+                    if let Some(file) = stdio.take() {
+                        *stdio = Some(Box::new(WasiQuotaFile::new(file, quota.clone())));
+                    }
+                }
+            }
+
             if let Some(f) = &self.setup_fs_fn {
                 f(inodes.deref_mut(), &mut wasi_fs)
                     .map_err(WasiStateCreationError::WasiFsSetupError)?;
@@ -468,6 +574,8 @@ impl WasiStateBuilder {
             inodes: Arc::new(inodes),
             args: self.args.clone(),
             threading: Default::default(),
+            interrupt: Default::default(),
+            clock: self.virtual_clock.clone(),
             envs: self
                 .envs
                 .iter()
@@ -671,4 +779,31 @@ mod test {
             _ => assert!(false),
         }
     }
+
+    #[test]
+    fn output_quota() {
+        use crate::syscalls::types::__WASI_ENOSPC;
+        use crate::utils::map_io_err;
+        use std::io::Write;
+
+        let state = create_wasi_state("test_prog")
+            .stdout(Box::new(crate::Pipe::new()))
+            .stderr(Box::new(crate::Pipe::new()))
+            .max_output(8)
+            .build()
+            .unwrap();
+        let inodes = state.inodes.read().unwrap();
+        let mut stdout = inodes.stdout_mut(&state.fs.fd_map).unwrap();
+        let stdout = stdout.as_mut().unwrap();
+        assert_eq!(stdout.write(b"hello").unwrap(), 5);
+
+        // The quota is shared with stderr, and a write going over it fails
+        // as a whole
+        let mut stderr = inodes.stderr_mut(&state.fs.fd_map).unwrap();
+        let stderr = stderr.as_mut().unwrap();
+        let err = stderr.write(b"world").unwrap_err();
+        assert_eq!(map_io_err(err), __WASI_ENOSPC);
+        assert_eq!(stderr.write(b"!!!").unwrap(), 3);
+        assert!(stdout.write(b"!").is_err());
+    }
 }
@@ -0,0 +1,208 @@
+use super::WasiInterruptHandle;
+use crate::syscalls::types::*;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// A host-controlled clock replacing the system clocks of a WASI
+/// environment, for reproducible simulations and tests.
+///
+/// Guests attached to a virtual clock with
+/// [`WasiStateBuilder::virtual_clock`](crate::WasiStateBuilder::virtual_clock)
+/// read its time from `clock_time_get`, and their sleeps and `poll_oneoff`
+/// timeouts are measured on it instead of the wall clock. The clock can
+/// be shared by several environments, which then observe the same time.
+///
+/// A clock made with [`WasiVirtualClock::new`] advances by itself:
+/// sleeping guests jump straight to their deadline. One made with
+/// [`WasiVirtualClock::stepped`] only moves when the host advances it,
+/// and guests block in their sleeps until then. Running the guests on
+/// their own threads, the host can then schedule them deterministically
+/// with [`wait_for_sleepers`](Self::wait_for_sleepers) and
+/// [`step`](Self::step), which wakes them in the order of their deadlines.
+#[derive(Debug, Clone)]
+pub struct WasiVirtualClock {
+    inner: Arc<WasiVirtualClockInner>,
+}
+
+#[derive(Debug)]
+struct WasiVirtualClockInner {
+    state: Mutex<ClockState>,
+    condvar: Condvar,
+}
+
+#[derive(Debug)]
+struct ClockState {
+    stepped: bool,
+    /// Nanoseconds since the clock was created.
+    monotonic: u64,
+    /// Nanoseconds since the Unix epoch.
+    realtime: u64,
+    /// The deadlines of the guests blocked in a sleep, on the monotonic
+    /// clock.
+    sleepers: Vec<u64>,
+}
+
+impl WasiVirtualClock {
+    /// Creates a clock that starts at zero and jumps forward whenever a
+    /// guest sleeps.
+    pub fn new() -> Self {
+        Self::with_mode(false)
+    }
+
+    /// Creates a clock that starts at zero and only moves when the host
+    /// advances it.
+    pub fn stepped() -> Self {
+        Self::with_mode(true)
+    }
+
+    fn with_mode(stepped: bool) -> Self {
+        Self {
+            inner: Arc::new(WasiVirtualClockInner {
+                state: Mutex::new(ClockState {
+                    stepped,
+                    monotonic: 0,
+                    realtime: 0,
+                    sleepers: Vec::new(),
+                }),
+                condvar: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Sets the wall-clock time guests read, as a duration since the Unix
+    /// epoch. The monotonic clock is left as it is.
+    pub fn set_realtime(&self, since_epoch: Duration) {
+        self.inner.state.lock().unwrap().realtime = nanos(since_epoch);
+    }
+
+    /// The time elapsed on the monotonic clock since it was created.
+    pub fn monotonic(&self) -> Duration {
+        Duration::from_nanos(self.inner.state.lock().unwrap().monotonic)
+    }
+
+    /// The wall-clock time, as a duration since the Unix epoch.
+    pub fn realtime(&self) -> Duration {
+        Duration::from_nanos(self.inner.state.lock().unwrap().realtime)
+    }
+
+    /// Moves both clocks forward, waking up the guests whose sleep ends in
+    /// the meantime.
+    pub fn advance(&self, by: Duration) {
+        let mut state = self.inner.state.lock().unwrap();
+        let to = state.monotonic.saturating_add(nanos(by));
+        self.advance_to(&mut state, to);
+    }
+
+    /// Advances the clocks to the earliest deadline of the sleeping
+    /// guests, waking up those sleeping until then.
+    ///
+    /// Returns the new monotonic time, or `None` if no guest is sleeping.
+    pub fn step(&self) -> Option<Duration> {
+        let mut state = self.inner.state.lock().unwrap();
+        let to = state.sleepers.iter().copied().min()?;
+        self.advance_to(&mut state, to);
+        Some(Duration::from_nanos(state.monotonic))
+    }
+
+    /// The number of guests blocked in a sleep on this clock.
+    pub fn sleepers(&self) -> usize {
+        self.inner.state.lock().unwrap().sleepers.len()
+    }
+
+    /// Blocks for at most `timeout` until at least `count` guests are
+    /// sleeping on this clock, returning whether they are.
+    ///
+    /// Waiting for every guest to block before calling
+    /// [`step`](Self::step) makes their interleaving independent of how
+    /// the threads they run on are scheduled.
+    pub fn wait_for_sleepers(&self, count: usize, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.inner.state.lock().unwrap();
+        while state.sleepers.len() < count {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            state = self
+                .inner
+                .condvar
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+        true
+    }
+
+    fn advance_to(&self, state: &mut ClockState, to: u64) {
+        if to <= state.monotonic {
+            return;
+        }
+        state.realtime = state.realtime.saturating_add(to - state.monotonic);
+        state.monotonic = to;
+        // Woken up guests stop counting as sleepers right away, so that
+        // the host can wait for them to block again
+        state.sleepers.retain(|deadline| *deadline > to);
+        self.inner.condvar.notify_all();
+    }
+
+    /// Reads one of the clocks, the CPU-time clocks following the
+    /// monotonic one.
+    pub(crate) fn time_get(&self, clock_id: __wasi_clockid_t) -> Result<i64, __wasi_errno_t> {
+        let state = self.inner.state.lock().unwrap();
+        let time = match clock_id {
+            __WASI_CLOCK_REALTIME => state.realtime,
+            __WASI_CLOCK_MONOTONIC
+            | __WASI_CLOCK_PROCESS_CPUTIME_ID
+            | __WASI_CLOCK_THREAD_CPUTIME_ID => state.monotonic,
+            _ => return Err(__WASI_EINVAL),
+        };
+        Ok(time as i64)
+    }
+
+    /// The resolution of the clocks, which is always a nanosecond.
+    pub(crate) fn res_get(&self, clock_id: __wasi_clockid_t) -> Result<i64, __wasi_errno_t> {
+        self.time_get(clock_id).map(|_| 1)
+    }
+
+    /// Sleeps for `duration` of virtual time, waking up early if the
+    /// environment gets interrupted.
+    pub(crate) fn sleep(&self, duration: Duration, interrupt: &WasiInterruptHandle) {
+        let mut state = self.inner.state.lock().unwrap();
+        let deadline = state.monotonic.saturating_add(nanos(duration));
+        if !state.stepped {
+            self.advance_to(&mut state, deadline);
+            return;
+        }
+        if deadline <= state.monotonic {
+            return;
+        }
+        state.sleepers.push(deadline);
+        self.inner.condvar.notify_all();
+        while state.monotonic < deadline {
+            if interrupt.is_interrupted() {
+                if let Some(index) = state.sleepers.iter().position(|d| *d == deadline) {
+                    state.sleepers.swap_remove(index);
+                }
+                return;
+            }
+            // Interruptions don't notify this condition variable, check
+            // for them regularly
+            state = self
+                .inner
+                .condvar
+                .wait_timeout(state, Duration::from_millis(10))
+                .unwrap()
+                .0;
+        }
+    }
+}
+
+impl Default for WasiVirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos().min(u64::MAX as u128) as u64
+}
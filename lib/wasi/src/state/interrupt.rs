@@ -0,0 +1,50 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// A handle that lets the host abort a WASI guest that is blocked in a
+/// host call, for example sleeping in `poll_oneoff` or `thread_sleep`.
+///
+/// Once interrupted, any blocking WASI call returns
+/// [`WasiError::Interrupted`](crate::WasiError::Interrupted), which
+/// unwinds the guest with a `RuntimeError`. The handle is cheap to
+/// clone and can be moved to another thread.
+#[derive(Debug, Clone, Default)]
+pub struct WasiInterruptHandle {
+    inner: Arc<WasiInterruptInner>,
+}
+
+#[derive(Debug, Default)]
+struct WasiInterruptInner {
+    interrupted: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl WasiInterruptHandle {
+    /// Interrupts the guest and wakes up any sleeping WASI call.
+    pub fn interrupt(&self) {
+        *self.inner.interrupted.lock().unwrap() = true;
+        self.inner.condvar.notify_all();
+    }
+
+    /// Clears a previous interruption so the environment can be reused.
+    pub fn reset(&self) {
+        *self.inner.interrupted.lock().unwrap() = false;
+    }
+
+    /// Returns `true` if the guest has been interrupted.
+    pub fn is_interrupted(&self) -> bool {
+        *self.inner.interrupted.lock().unwrap()
+    }
+
+    /// Blocks for at most `timeout`, returning early with `true` if the
+    /// guest is interrupted in the meantime.
+    pub(crate) fn wait_timeout(&self, timeout: Duration) -> bool {
+        let guard = self.inner.interrupted.lock().unwrap();
+        let (guard, _) = self
+            .inner
+            .condvar
+            .wait_timeout_while(guard, timeout, |interrupted| !*interrupted)
+            .unwrap();
+        *guard
+    }
+}
@@ -16,14 +16,22 @@
 #![allow(clippy::cognitive_complexity, clippy::too_many_arguments)]
 
 mod builder;
+mod clock;
 mod guard;
+mod interrupt;
+mod mount;
 mod pipe;
+mod quota;
 mod socket;
 mod types;
 
 pub use self::builder::*;
+pub use self::clock::*;
 pub use self::guard::*;
+pub use self::interrupt::*;
+pub(crate) use self::mount::*;
 pub use self::pipe::*;
+pub use self::quota::*;
 pub use self::socket::*;
 pub use self::types::*;
 use crate::syscalls::types::*;
@@ -1865,6 +1873,10 @@ pub struct WasiState {
     pub fs: WasiFs,
     pub inodes: Arc<RwLock<WasiInodes>>,
     pub(crate) threading: Mutex<WasiStateThreading>,
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    pub(crate) interrupt: WasiInterruptHandle,
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    pub(crate) clock: Option<WasiVirtualClock>,
     pub args: Vec<Vec<u8>>,
     pub envs: Vec<Vec<u8>>,
 }
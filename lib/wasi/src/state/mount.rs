@@ -0,0 +1,171 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use wasmer_vfs::{
+    DirEntry, FileOpener, FileSystem, FsError, Metadata, OpenOptions, OpenOptionsConfig, ReadDir,
+    VirtualFile,
+};
+
+/// The host path the filesystems mounted with
+/// [`WasiStateBuilder::mount`](crate::WasiStateBuilder::mount) are
+/// preopened at, each in the directory named after its index.
+const MOUNT_ROOT: &str = "/.wasmer-mounts";
+
+/// The host path of the `index`th mounted filesystem.
+pub(crate) fn mount_path(index: usize) -> PathBuf {
+    Path::new(MOUNT_ROOT).join(index.to_string())
+}
+
+/// A filesystem routing the paths under [`MOUNT_ROOT`] to mounted
+/// filesystems, and the other ones to the filesystem of the host.
+#[derive(Debug)]
+pub(crate) struct MountFileSystem {
+    inner: Arc<Mounts>,
+}
+
+#[derive(Debug)]
+struct Mounts {
+    host: Box<dyn FileSystem>,
+    mounts: Vec<Mount>,
+}
+
+/// A filesystem mounted with
+/// [`WasiStateBuilder::mount`](crate::WasiStateBuilder::mount) or
+/// [`WasiStateBuilder::mount_writable`](crate::WasiStateBuilder::mount_writable).
+#[derive(Debug)]
+pub(crate) struct Mount {
+    pub(crate) fs: Box<dyn FileSystem>,
+    pub(crate) writable: bool,
+}
+
+impl Mounts {
+    /// The index of the mount `path` belongs to, and its path in it, or
+    /// `None` for the paths of the host.
+    fn route(&self, path: &Path) -> Result<Option<(usize, PathBuf)>, FsError> {
+        let mut components = match path.strip_prefix(MOUNT_ROOT) {
+            Ok(relative) => relative.components(),
+            Err(_) => return Ok(None),
+        };
+        let index = match components.next() {
+            Some(Component::Normal(index)) => index
+                .to_str()
+                .and_then(|index| index.parse::<usize>().ok())
+                .filter(|&index| index < self.mounts.len())
+                .ok_or(FsError::EntityNotFound)?,
+            _ => return Err(FsError::EntityNotFound),
+        };
+        Ok(Some((index, Path::new("/").join(components.as_path()))))
+    }
+
+    /// The filesystem `path` belongs to, and its path in it.
+    fn resolve(&self, path: &Path) -> Result<(&dyn FileSystem, PathBuf), FsError> {
+        Ok(match self.route(path)? {
+            Some((index, mounted_path)) => (self.mounts[index].fs.as_ref(), mounted_path),
+            None => (self.host.as_ref(), path.to_path_buf()),
+        })
+    }
+
+    /// Like [`Self::resolve`], but fails for the paths of the read-only
+    /// mounts.
+    fn resolve_writable(&self, path: &Path) -> Result<(&dyn FileSystem, PathBuf), FsError> {
+        match self.route(path)? {
+            Some((index, _)) if !self.mounts[index].writable => Err(FsError::PermissionDenied),
+            _ => self.resolve(path),
+        }
+    }
+}
+
+impl MountFileSystem {
+    /// Mounts each of `mounts` at the [`mount_path`] of its index over
+    /// `host`.
+    pub(crate) fn new(host: Box<dyn FileSystem>, mounts: Vec<Mount>) -> Self {
+        Self {
+            inner: Arc::new(Mounts { host, mounts }),
+        }
+    }
+}
+
+impl FileSystem for MountFileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir, FsError> {
+        let (index, mounted_path) = match self.inner.route(path)? {
+            Some(route) => route,
+            None => return self.inner.host.read_dir(path),
+        };
+        let entries = self.inner.mounts[index]
+            .fs
+            .read_dir(&mounted_path)?
+            .map(|entry| {
+                entry.map(|entry| DirEntry {
+                    path: path.join(entry.file_name()),
+                    metadata: entry.metadata,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ReadDir::new(entries))
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<(), FsError> {
+        let (fs, path) = self.inner.resolve_writable(path)?;
+        fs.create_dir(&path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<(), FsError> {
+        let (fs, path) = self.inner.resolve_writable(path)?;
+        fs.remove_dir(&path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), FsError> {
+        // Moving entries between filesystems would be a copy
+        let mount_of = |path: &Path| -> Result<Option<usize>, FsError> {
+            Ok(self.inner.route(path)?.map(|(index, _)| index))
+        };
+        if mount_of(from)? != mount_of(to)? {
+            return Err(FsError::PermissionDenied);
+        }
+        let (fs, from) = self.inner.resolve_writable(from)?;
+        let (_, to) = self.inner.resolve_writable(to)?;
+        fs.rename(&from, &to)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, FsError> {
+        let (fs, path) = self.inner.resolve(path)?;
+        fs.metadata(&path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata, FsError> {
+        let (fs, path) = self.inner.resolve(path)?;
+        fs.symlink_metadata(&path)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), FsError> {
+        let (fs, path) = self.inner.resolve_writable(path)?;
+        fs.remove_file(&path)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(MountFileOpener {
+            mounts: self.inner.clone(),
+        }))
+    }
+}
+
+/// Opens the files of a [`MountFileSystem`].
+struct MountFileOpener {
+    mounts: Arc<Mounts>,
+}
+
+impl FileOpener for MountFileOpener {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>, FsError> {
+        let writes =
+            conf.write() || conf.append() || conf.truncate() || conf.create() || conf.create_new();
+        let (fs, path) = if writes {
+            self.mounts.resolve_writable(path)?
+        } else {
+            self.mounts.resolve(path)?
+        };
+        fs.new_open_options().options(conf.clone()).open(path)
+    }
+}
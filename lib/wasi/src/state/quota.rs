@@ -0,0 +1,250 @@
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use thiserror::Error;
+use wasmer_vfs::{
+    FileDescriptor, FileOpener, FileSystem, FsError, Metadata, OpenOptions, OpenOptionsConfig,
+    ReadDir, VirtualFile,
+};
+
+/// A limit on the number of bytes a guest can write, shared by the files
+/// it applies to.
+///
+/// A write that would go over the limit fails as a whole with `ENOSPC`,
+/// as if the disk were full, and so does growing a file with
+/// `fd_filestat_set_size` or `fd_allocate`. Set with
+/// [`WasiStateBuilder::max_output`](crate::WasiStateBuilder::max_output)
+/// for the standard output and error, and with
+/// [`WasiStateBuilder::fs_quota`](crate::WasiStateBuilder::fs_quota) for
+/// the files of the filesystem.
+#[derive(Debug, Clone)]
+pub struct WasiWriteQuota {
+    remaining: Arc<AtomicU64>,
+}
+
+/// The error of a write going over a [`WasiWriteQuota`].
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("the write quota of the guest is exhausted")]
+pub struct WasiQuotaExceeded;
+
+impl WasiWriteQuota {
+    /// Creates a quota of `limit` bytes.
+    pub fn new(limit: u64) -> Self {
+        Self {
+            remaining: Arc::new(AtomicU64::new(limit)),
+        }
+    }
+
+    /// The number of bytes that can still be written.
+    pub fn remaining(&self) -> u64 {
+        self.remaining.load(Ordering::Acquire)
+    }
+
+    /// Takes `bytes` from the quota, if there are enough left.
+    fn consume(&self, bytes: u64) -> Result<(), WasiQuotaExceeded> {
+        self.remaining
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |remaining| {
+                remaining.checked_sub(bytes)
+            })
+            .map(drop)
+            .map_err(|_| WasiQuotaExceeded)
+    }
+}
+
+/// Whether `err` is the error of a write going over a [`WasiWriteQuota`].
+pub(crate) fn is_quota_exceeded(err: &io::Error) -> bool {
+    err.get_ref()
+        .map_or(false, |inner| inner.is::<WasiQuotaExceeded>())
+}
+
+/// A file whose writes are counted against a [`WasiWriteQuota`].
+#[derive(Debug)]
+pub struct WasiQuotaFile {
+    inner: Box<dyn VirtualFile + Send + Sync + 'static>,
+    quota: WasiWriteQuota,
+}
+
+impl WasiQuotaFile {
+    /// Counts the writes to `inner` against `quota`.
+    pub fn new(inner: Box<dyn VirtualFile + Send + Sync + 'static>, quota: WasiWriteQuota) -> Self {
+        Self { inner, quota }
+    }
+
+    /// The file this one writes to.
+    pub fn into_inner(self) -> Box<dyn VirtualFile + Send + Sync + 'static> {
+        self.inner
+    }
+}
+
+impl Read for WasiQuotaFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Write for WasiQuotaFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.quota
+            .consume(buf.len() as u64)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let result = self.inner.write(buf);
+        // Only the bytes actually written count
+        let written = *result.as_ref().unwrap_or(&0);
+        self.quota
+            .remaining
+            .fetch_add((buf.len() - written) as u64, Ordering::AcqRel);
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Seek for WasiQuotaFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl VirtualFile for WasiQuotaFile {
+    fn last_accessed(&self) -> u64 {
+        self.inner.last_accessed()
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.inner.last_modified()
+    }
+
+    fn created_time(&self) -> u64 {
+        self.inner.created_time()
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn set_len(&mut self, new_size: u64) -> Result<(), FsError> {
+        let growth = new_size.saturating_sub(self.inner.size());
+        self.quota.consume(growth).map_err(|_| FsError::WriteZero)?;
+        let result = self.inner.set_len(new_size);
+        if result.is_err() {
+            self.quota.remaining.fetch_add(growth, Ordering::AcqRel);
+        }
+        result
+    }
+
+    fn unlink(&mut self) -> Result<(), FsError> {
+        self.inner.unlink()
+    }
+
+    fn sync_to_disk(&self) -> Result<(), FsError> {
+        self.inner.sync_to_disk()
+    }
+
+    fn bytes_available(&self) -> Result<usize, FsError> {
+        self.inner.bytes_available()
+    }
+
+    fn bytes_available_read(&self) -> Result<Option<usize>, FsError> {
+        self.inner.bytes_available_read()
+    }
+
+    fn bytes_available_write(&self) -> Result<Option<usize>, FsError> {
+        self.inner.bytes_available_write()
+    }
+
+    fn is_open(&self) -> bool {
+        self.inner.is_open()
+    }
+
+    fn get_fd(&self) -> Option<FileDescriptor> {
+        self.inner.get_fd()
+    }
+}
+
+/// A filesystem whose files count their writes against a
+/// [`WasiWriteQuota`].
+pub struct WasiQuotaFileSystem {
+    inner: Arc<dyn FileSystem>,
+    quota: WasiWriteQuota,
+}
+
+impl WasiQuotaFileSystem {
+    /// Counts the writes to the files of `inner` against `quota`.
+    pub fn new(inner: Box<dyn FileSystem>, quota: WasiWriteQuota) -> Self {
+        Self {
+            inner: inner.into(),
+            quota,
+        }
+    }
+}
+
+impl fmt::Debug for WasiQuotaFileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WasiQuotaFileSystem")
+            .field("inner", &self.inner)
+            .field("remaining", &self.quota.remaining())
+            .finish()
+    }
+}
+
+impl FileSystem for WasiQuotaFileSystem {
+    fn read_dir(&self, path: &Path) -> Result<ReadDir, FsError> {
+        self.inner.read_dir(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<(), FsError> {
+        self.inner.create_dir(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<(), FsError> {
+        self.inner.remove_dir(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), FsError> {
+        self.inner.rename(from, to)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, FsError> {
+        self.inner.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata, FsError> {
+        self.inner.symlink_metadata(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), FsError> {
+        self.inner.remove_file(path)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(Box::new(QuotaFileOpener {
+            fs: self.inner.clone(),
+            quota: self.quota.clone(),
+        }))
+    }
+}
+
+/// Opens the files of a [`WasiQuotaFileSystem`].
+struct QuotaFileOpener {
+    fs: Arc<dyn FileSystem>,
+    quota: WasiWriteQuota,
+}
+
+impl FileOpener for QuotaFileOpener {
+    fn open(
+        &mut self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>, FsError> {
+        let file = self
+            .fs
+            .new_open_options()
+            .options(conf.clone())
+            .open(path)?;
+        Ok(Box::new(WasiQuotaFile::new(file, self.quota.clone())))
+    }
+}
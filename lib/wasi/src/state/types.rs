@@ -376,6 +376,17 @@ pub(crate) fn poll(
 pub trait WasiPath {}
 
 /// For piping stdio. Stores all output / input in a byte-vector.
+///
+/// A `Pipe` is cheap to clone and all clones share the same buffer, so the
+/// host can keep one end while handing a boxed clone to
+/// [`WasiStateBuilder::stdin`], [`WasiStateBuilder::stdout`] or
+/// [`WasiStateBuilder::stderr`]. Whatever the guest writes can then be read
+/// back from the host end once the guest function returns, and whatever the
+/// host writes is served to the guest on its next read.
+///
+/// [`WasiStateBuilder::stdin`]: crate::WasiStateBuilder::stdin
+/// [`WasiStateBuilder::stdout`]: crate::WasiStateBuilder::stdout
+/// [`WasiStateBuilder::stderr`]: crate::WasiStateBuilder::stderr
 #[derive(Debug, Clone, Default)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub struct Pipe {
@@ -386,6 +397,36 @@ impl Pipe {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Creates a pipe that already holds `data`, ready to be read.
+    ///
+    /// This is mostly useful to feed a guest's `stdin` before it starts.
+    pub fn with_data(data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            buffer: Arc::new(Mutex::new(data.into().into())),
+        }
+    }
+
+    /// Returns the number of bytes currently buffered in the pipe.
+    pub fn len(&self) -> usize {
+        self.buffer.lock().unwrap().len()
+    }
+
+    /// Returns `true` if there is nothing buffered in the pipe.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drains everything buffered in the pipe and returns it.
+    pub fn take(&self) -> Vec<u8> {
+        self.buffer.lock().unwrap().drain(..).collect()
+    }
+
+    /// Drains everything buffered in the pipe and returns it as a `String`,
+    /// replacing invalid UTF-8 sequences.
+    pub fn take_string_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.take()).into_owned()
+    }
 }
 
 impl Read for Pipe {
@@ -374,7 +374,10 @@ pub fn clock_res_get<M: MemorySize>(
     let memory = env.memory_view(&ctx);
 
     let out_addr = resolution.deref(&memory);
-    let t_out = wasi_try!(platform_clock_res_get(clock_id, out_addr));
+    let t_out = match env.state.clock.as_ref() {
+        Some(clock) => wasi_try!(clock.res_get(clock_id)),
+        None => wasi_try!(platform_clock_res_get(clock_id, out_addr)),
+    };
     wasi_try_mem!(resolution.write(&memory, t_out as __wasi_timestamp_t));
     __WASI_ESUCCESS
 }
@@ -402,7 +405,7 @@ pub fn clock_time_get<M: MemorySize>(
     let env = ctx.data();
     let memory = env.memory_view(&ctx);
 
-    let t_out = wasi_try!(platform_clock_time_get(clock_id, precision));
+    let t_out = wasi_try!(env.clock_time_get(clock_id, precision));
     wasi_try_mem!(time.write(&memory, t_out as __wasi_timestamp_t));
 
     let result = __WASI_ESUCCESS;
@@ -3075,10 +3078,22 @@ pub fn poll_oneoff<M: MemorySize>(
                 if clock_info.clock_id == __WASI_CLOCK_REALTIME
                     || clock_info.clock_id == __WASI_CLOCK_MONOTONIC
                 {
-                    // this is a hack
-                    // TODO: do this properly
-                    time_to_sleep = Duration::from_nanos(clock_info.timeout);
-                    clock_subs.push((clock_info, s.user_data));
+                    // Absolute timeouts are expressed on the subscribed clock,
+                    // convert them to a delay from now
+                    let timeout = if clock_info.flags & __WASI_SUBSCRIPTION_CLOCK_ABSTIME != 0 {
+                        let now = wasi_try_ok!(env.clock_time_get(clock_info.clock_id, 1));
+                        clock_info.timeout.saturating_sub(now as u64)
+                    } else {
+                        clock_info.timeout
+                    };
+                    let timeout = Duration::from_nanos(timeout);
+                    // The earliest clock subscription determines how long we wait
+                    time_to_sleep = if clock_subs.is_empty() {
+                        timeout
+                    } else {
+                        time_to_sleep.min(timeout)
+                    };
+                    clock_subs.push((timeout, s.user_data));
                     None
                 } else {
                     unimplemented!("Polling not implemented for clocks yet");
@@ -3160,20 +3175,32 @@ pub fn poll_oneoff<M: MemorySize>(
 
     let mut seen_events = vec![Default::default(); in_events.len()];
 
-    let start = platform_clock_time_get(__WASI_CLOCK_MONOTONIC, 1_000_000).unwrap() as u128;
-    let mut triggered = 0;
-    while triggered == 0 {
-        let now = platform_clock_time_get(__WASI_CLOCK_MONOTONIC, 1_000_000).unwrap() as u128;
-        let delta = match now.checked_sub(start) {
+    let start = env.clock_time_get(__WASI_CLOCK_MONOTONIC, 1).unwrap() as u128;
+    let elapsed = || {
+        let now = env.clock_time_get(__WASI_CLOCK_MONOTONIC, 1).unwrap() as u128;
+        match now.checked_sub(start) {
             Some(a) => Duration::from_nanos(a as u64),
             None => Duration::ZERO,
-        };
+        }
+    };
+    let mut triggered = 0;
+    if fds.is_empty() {
+        // Only timers: sleep until the earliest one fires (the sleep is
+        // aborted if the environment gets interrupted)
+        env.sleep(time_to_sleep)?;
+    }
+    while triggered == 0 && !fds.is_empty() {
+        let remaining = time_to_sleep.saturating_sub(elapsed());
         match poll(
             fds.as_slice(),
             in_events.as_slice(),
             seen_events.as_mut_slice(),
-            Duration::from_millis(1),
+            remaining.min(Duration::from_millis(1)),
         ) {
+            Ok(0) if env.state.clock.is_some() => {
+                // Virtual time only passes while the guest sleeps
+                env.sleep(remaining.min(Duration::from_millis(1)))?;
+            }
             Ok(0) => {
                 env.yield_now()?;
             }
@@ -3181,16 +3208,17 @@ pub fn poll_oneoff<M: MemorySize>(
                 triggered = a;
             }
             Err(FsError::WouldBlock) => {
-                env.sleep(Duration::from_millis(1))?;
+                env.sleep(remaining.min(Duration::from_millis(1)))?;
             }
             Err(err) => {
                 return Ok(fs_error_into_wasi_err(err));
             }
         };
-        if delta > time_to_sleep {
+        if elapsed() >= time_to_sleep {
             break;
         }
     }
+    let elapsed = elapsed();
 
     for (i, seen_event) in seen_events.into_iter().enumerate() {
         let mut flags = 0;
@@ -3240,8 +3268,8 @@ pub fn poll_oneoff<M: MemorySize>(
         wasi_try_mem_ok!(event_array.index(events_seen as u64).write(event));
         events_seen += 1;
     }
-    if triggered == 0 {
-        for (clock_info, userdata) in clock_subs {
+    for (timeout, userdata) in clock_subs {
+        if timeout <= elapsed {
             let event = __wasi_event_t {
                 userdata,
                 error: __WASI_ESUCCESS,
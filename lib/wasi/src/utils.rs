@@ -1,4 +1,5 @@
 use super::types::*;
+use crate::state::is_quota_exceeded;
 use std::collections::BTreeSet;
 use wasmer::Module;
 
@@ -41,6 +42,7 @@ pub fn map_io_err(err: std::io::Error) -> __wasi_errno_t {
         ErrorKind::TimedOut => __WASI_ETIMEDOUT,
         ErrorKind::WriteZero => __WASI_EIO,
         ErrorKind::Interrupted => __WASI_EINTR,
+        ErrorKind::Other if is_quota_exceeded(&err) => __WASI_ENOSPC,
         ErrorKind::Other => __WASI_EIO,
         ErrorKind::UnexpectedEof => __WASI_EIO,
         ErrorKind::Unsupported => __WASI_ENOTSUP,
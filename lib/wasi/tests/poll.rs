@@ -0,0 +1,176 @@
+#![cfg(feature = "sys")]
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use wasmer::{Instance, Module, Store, TypedFunction};
+use wasmer_wasi::{WasiError, WasiFunctionEnv, WasiState, WasiStateBuilder, WasiVirtualClock};
+
+/// A module exporting `sleep(nanos) -> errno`, which blocks on a single
+/// relative clock subscription through `poll_oneoff`, and `now()`, which
+/// reads the monotonic clock.
+const SLEEP_WAT: &[u8] = br#"
+(module
+    (import "wasi_snapshot_preview1" "poll_oneoff" (func $poll_oneoff (param i32 i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "clock_time_get" (func $clock_time_get (param i32 i64 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func (export "sleep") (param $timeout i64) (result i32)
+        ;; subscription_t at 0: userdata, tag = clock, clock = monotonic
+        (i64.store (i32.const 0) (i64.const 0x1234))
+        (i32.store8 (i32.const 8) (i32.const 0))
+        (i32.store (i32.const 16) (i32.const 1))
+        (i64.store (i32.const 24) (local.get $timeout))
+        (i64.store (i32.const 32) (i64.const 0))
+        (i32.store16 (i32.const 40) (i32.const 0))
+
+        (call $poll_oneoff
+            (i32.const 0)   ;; in
+            (i32.const 64)  ;; out
+            (i32.const 1)   ;; nsubscriptions
+            (i32.const 128) ;; nevents
+        )
+    )
+
+    (func (export "now") (result i64)
+        (drop (call $clock_time_get (i32.const 1) (i64.const 1) (i32.const 256)))
+        (i64.load (i32.const 256))
+    )
+)
+"#;
+
+fn instantiate(store: &mut Store) -> (WasiFunctionEnv, Instance) {
+    instantiate_with(store, &mut WasiState::new("sleeper"))
+}
+
+fn instantiate_with(
+    store: &mut Store,
+    builder: &mut WasiStateBuilder,
+) -> (WasiFunctionEnv, Instance) {
+    let module = Module::new(&*store, SLEEP_WAT).unwrap();
+    let wasi_env = builder.finalize(store).unwrap();
+    let import_object = wasi_env.import_object(store, &module).unwrap();
+    let instance = Instance::new(store, &module, &import_object).unwrap();
+    let memory = instance.exports.get_memory("memory").unwrap();
+    wasi_env.data_mut(store).set_memory(memory.clone());
+    (wasi_env, instance)
+}
+
+#[test]
+fn test_poll_oneoff_clock() {
+    let mut store = Store::default();
+    let (_wasi_env, instance) = instantiate(&mut store);
+    let sleep: TypedFunction<i64, i32> = instance
+        .exports
+        .get_typed_function(&mut store, "sleep")
+        .unwrap();
+
+    let start = Instant::now();
+    assert_eq!(sleep.call(&mut store, 2_000_000).unwrap(), 0);
+    assert!(start.elapsed() >= Duration::from_millis(2));
+
+    let memory = instance.exports.get_memory("memory").unwrap();
+    let view = memory.view(&store);
+    let mut nevents = [0u8; 4];
+    view.read(128, &mut nevents).unwrap();
+    assert_eq!(u32::from_le_bytes(nevents), 1);
+    let mut userdata = [0u8; 8];
+    view.read(64, &mut userdata).unwrap();
+    assert_eq!(u64::from_le_bytes(userdata), 0x1234);
+}
+
+#[test]
+fn test_poll_oneoff_interrupted() {
+    let mut store = Store::default();
+    let (wasi_env, instance) = instantiate(&mut store);
+    let sleep: TypedFunction<i64, i32> = instance
+        .exports
+        .get_typed_function(&mut store, "sleep")
+        .unwrap();
+
+    let handle = wasi_env.env.as_ref(&store).interrupt_handle();
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        handle.interrupt();
+    });
+
+    let start = Instant::now();
+    let err = sleep.call(&mut store, 60_000_000_000).unwrap_err();
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert!(matches!(
+        err.downcast::<WasiError>(),
+        Ok(WasiError::Interrupted)
+    ));
+    interrupter.join().unwrap();
+
+    // Once reset, the guest can sleep again.
+    wasi_env.env.as_ref(&store).interrupt_handle().reset();
+    assert_eq!(sleep.call(&mut store, 1_000).unwrap(), 0);
+}
+
+#[test]
+fn test_poll_oneoff_virtual_clock() {
+    let clock = WasiVirtualClock::new();
+    let mut store = Store::default();
+    let (_wasi_env, instance) = instantiate_with(
+        &mut store,
+        WasiState::new("sleeper").virtual_clock(clock.clone()),
+    );
+    let sleep: TypedFunction<i64, i32> = instance
+        .exports
+        .get_typed_function(&mut store, "sleep")
+        .unwrap();
+    let now: TypedFunction<(), i64> = instance
+        .exports
+        .get_typed_function(&mut store, "now")
+        .unwrap();
+
+    // An hour long sleep returns at once, the clock jumping to its end.
+    let start = Instant::now();
+    assert_eq!(now.call(&mut store).unwrap(), 0);
+    assert_eq!(sleep.call(&mut store, 3_600_000_000_000).unwrap(), 0);
+    assert!(start.elapsed() < Duration::from_secs(60));
+    assert_eq!(now.call(&mut store).unwrap(), 3_600_000_000_000);
+
+    clock.advance(Duration::from_nanos(5));
+    assert_eq!(now.call(&mut store).unwrap(), 3_600_000_000_005);
+}
+
+#[test]
+fn test_poll_oneoff_stepped_clock() {
+    let clock = WasiVirtualClock::stepped();
+    let mut guests = [3_000_000_000i64, 1_000_000_000]
+        .iter()
+        .map(|&timeout| {
+            let clock = clock.clone();
+            thread::spawn(move || {
+                let mut store = Store::default();
+                let (_wasi_env, instance) =
+                    instantiate_with(&mut store, WasiState::new("sleeper").virtual_clock(clock));
+                let sleep: TypedFunction<i64, i32> = instance
+                    .exports
+                    .get_typed_function(&mut store, "sleep")
+                    .unwrap();
+                let now: TypedFunction<(), i64> = instance
+                    .exports
+                    .get_typed_function(&mut store, "now")
+                    .unwrap();
+                assert_eq!(sleep.call(&mut store, timeout).unwrap(), 0);
+                now.call(&mut store).unwrap()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // The guests only wake up when the host steps the clock, in the order
+    // of their deadlines.
+    assert!(clock.wait_for_sleepers(2, Duration::from_secs(60)));
+    assert_eq!(clock.step(), Some(Duration::from_secs(1)));
+    assert_eq!(clock.sleepers(), 1);
+    assert_eq!(guests.pop().unwrap().join().unwrap(), 1_000_000_000);
+
+    assert_eq!(clock.step(), Some(Duration::from_secs(3)));
+    assert_eq!(guests.pop().unwrap().join().unwrap(), 3_000_000_000);
+    assert_eq!(clock.step(), None);
+}
@@ -1,6 +1,6 @@
 use std::io::{Read, Write};
 
-use wasmer::{Instance, Module, Store};
+use wasmer::{Cranelift, EngineBuilder, Features, Instance, Module, Store};
 use wasmer_wasi::{Pipe, WasiState};
 
 mod sys {
@@ -9,11 +9,22 @@ mod sys {
         super::test_stdout()
     }
 
+    #[test]
+    #[ignore = "the compilers don't translate 64-bit memories yet"]
+    fn test_stdout_wasm64() {
+        super::test_stdout_wasm64()
+    }
+
     #[test]
     fn test_stdin() {
         super::test_stdin()
     }
 
+    #[test]
+    fn test_stderr() {
+        super::test_stderr()
+    }
+
     #[test]
     fn test_env() {
         super::test_env()
@@ -34,6 +45,11 @@ mod js {
         super::test_stdin()
     }
 
+    #[wasm_bindgen_test]
+    fn test_stderr() {
+        super::test_stderr()
+    }
+
     #[wasm_bindgen_test]
     fn test_env() {
         super::test_env()
@@ -98,6 +114,117 @@ fn test_stdout() {
     assert_eq!(stdout_as_str, "hello world\n");
 }
 
+fn test_stdout_wasm64() {
+    let mut features = Features::new();
+    features.memory64(true);
+    let engine = EngineBuilder::new(Cranelift::default())
+        .set_features(Some(features))
+        .engine();
+    let mut store = Store::new(engine);
+    let module = Module::new(&store, br#"
+    (module
+        ;; Modules built for wasm64 pass pointers and sizes as i64:
+        ;; (File Descriptor, *iovs, iovs_len, nwritten) -> Returns number of bytes written
+        (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i64 i64 i64) (result i32)))
+
+        (memory i64 1)
+        (export "memory" (memory 0))
+
+        (data (i64.const 32) "hello world\n")
+
+        (func $main (export "_start")
+            ;; The io vector holds a 64-bit pointer and a 64-bit length
+            (i64.store (i64.const 0) (i64.const 32))
+            (i64.store (i64.const 8) (i64.const 12))
+
+            (call $fd_write
+                (i32.const 1)
+                (i64.const 0)
+                (i64.const 1)
+                (i64.const 16)
+            )
+            drop
+        )
+    )
+    "#).unwrap();
+
+    let mut stdout = Pipe::default();
+    let wasi_env = WasiState::new("command-name")
+        .stdout(Box::new(stdout.clone()))
+        .finalize(&mut store)
+        .unwrap();
+
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    let memory = instance.exports.get_memory("memory").unwrap();
+    wasi_env.data_mut(&mut store).set_memory(memory.clone());
+
+    let start = instance.exports.get_function("_start").unwrap();
+    start.call(&mut store, &[]).unwrap();
+
+    let mut stdout_str = String::new();
+    stdout.read_to_string(&mut stdout_str).unwrap();
+    assert_eq!(stdout_str, "hello world\n");
+
+    // The number of bytes written is stored as a 64-bit integer.
+    let mut nwritten = [0; 8];
+    memory.view(&store).read(16, &mut nwritten).unwrap();
+    assert_eq!(u64::from_le_bytes(nwritten), 12);
+}
+
+fn test_stderr() {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        br#"
+    (module
+        (import "wasi_unstable" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+
+        (memory 1)
+        (export "memory" (memory 0))
+
+        (data (i32.const 8) "oh no\n")
+
+        (func $main (export "_start")
+            (i32.store (i32.const 0) (i32.const 8))
+            (i32.store (i32.const 4) (i32.const 6))
+
+            (call $fd_write
+                (i32.const 2) ;; file_descriptor - 2 for stderr
+                (i32.const 0)
+                (i32.const 1)
+                (i32.const 20)
+            )
+            drop
+        )
+    )
+    "#,
+    )
+    .unwrap();
+
+    // Create the `WasiEnv`, capturing both stdout and stderr.
+    let stdout = Pipe::new();
+    let stderr = Pipe::new();
+    let wasi_env = WasiState::new("command-name")
+        .stdout(Box::new(stdout.clone()))
+        .stderr(Box::new(stderr.clone()))
+        .finalize(&mut store)
+        .unwrap();
+
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    let memory = instance.exports.get_memory("memory").unwrap();
+    wasi_env.data_mut(&mut store).set_memory(memory.clone());
+
+    let start = instance.exports.get_function("_start").unwrap();
+    start.call(&mut store, &[]).unwrap();
+
+    assert!(stdout.is_empty());
+    assert_eq!(stderr.len(), 6);
+    assert_eq!(stderr.take_string_lossy(), "oh no\n");
+    assert!(stderr.is_empty());
+}
+
 fn test_env() {
     let mut store = Store::default();
     let module = Module::new(&store, include_bytes!("envvar.wasm")).unwrap();
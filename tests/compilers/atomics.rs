@@ -0,0 +1,73 @@
+//! Atomic memory accesses of the threads proposal, which every compiler
+//! lowers on its own.
+use anyhow::Result;
+use wasmer::*;
+
+#[compiler_test(atomics)]
+fn atomic_accesses(mut config: crate::Config) -> Result<()> {
+    let mut features = Features::new();
+    features.threads(true);
+    config.set_features(features);
+    let mut store = config.store();
+    let wat = r#"
+    (module
+      (memory 1)
+      (func (export "add8") (param i32 i32) (result i32)
+        (i32.atomic.rmw8.add_u (local.get 0) (local.get 1)))
+      (func (export "sub") (param i32 i32) (result i32)
+        (i32.atomic.rmw.sub (local.get 0) (local.get 1)))
+      (func (export "xchg16") (param i32 i64) (result i64)
+        (i64.atomic.rmw16.xchg_u (local.get 0) (local.get 1)))
+      (func (export "cmpxchg8") (param i32 i32 i32) (result i32)
+        (i32.atomic.rmw8.cmpxchg_u (local.get 0) (local.get 1) (local.get 2)))
+      (func (export "cmpxchg") (param i32 i64 i64) (result i64)
+        (i64.atomic.rmw.cmpxchg (local.get 0) (local.get 1) (local.get 2)))
+      (func (export "load16") (param i32) (result i32)
+        (i32.atomic.load16_u (local.get 0)))
+      (func (export "store") (param i32 i64)
+        (i64.atomic.store (local.get 0) (local.get 1))))
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let add8: TypedFunction<(i32, i32), i32> =
+        instance.exports.get_typed_function(&mut store, "add8")?;
+    let sub: TypedFunction<(i32, i32), i32> =
+        instance.exports.get_typed_function(&mut store, "sub")?;
+    let xchg16: TypedFunction<(i32, i64), i64> =
+        instance.exports.get_typed_function(&mut store, "xchg16")?;
+    let cmpxchg8: TypedFunction<(i32, i32, i32), i32> = instance
+        .exports
+        .get_typed_function(&mut store, "cmpxchg8")?;
+    let cmpxchg: TypedFunction<(i32, i64, i64), i64> =
+        instance.exports.get_typed_function(&mut store, "cmpxchg")?;
+    let load16: TypedFunction<i32, i32> =
+        instance.exports.get_typed_function(&mut store, "load16")?;
+    let store64: TypedFunction<(i32, i64), ()> =
+        instance.exports.get_typed_function(&mut store, "store")?;
+
+    // Sub-word operations wrap within their bytes.
+    assert_eq!(add8.call(&mut store, 0, 0x1ff)?, 0);
+    assert_eq!(add8.call(&mut store, 0, 1)?, 0xff);
+    assert_eq!(load16.call(&mut store, 0)?, 0);
+    assert_eq!(sub.call(&mut store, 4, 1)?, 0);
+    assert_eq!(load16.call(&mut store, 4)?, 0xffff);
+    assert_eq!(xchg16.call(&mut store, 8, 0xabcdef)?, 0);
+    assert_eq!(load16.call(&mut store, 8)?, 0xcdef);
+
+    // Only the expected value is replaced, compared within the bytes of
+    // the access.
+    store64.call(&mut store, 16, 0x1122334455667788)?;
+    assert_eq!(cmpxchg.call(&mut store, 16, 0, 1)?, 0x1122334455667788);
+    assert_eq!(
+        cmpxchg.call(&mut store, 16, 0x1122334455667788, 5)?,
+        0x1122334455667788
+    );
+    assert_eq!(load16.call(&mut store, 16)?, 5);
+    assert_eq!(cmpxchg8.call(&mut store, 24, 0x100, 7)?, 0);
+    assert_eq!(load16.call(&mut store, 24)?, 7);
+
+    // Atomic accesses must be aligned.
+    assert!(load16.call(&mut store, 1).is_err());
+
+    Ok(())
+}
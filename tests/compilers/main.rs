@@ -5,6 +5,7 @@
 #[macro_use]
 extern crate compiler_test_derive;
 
+mod atomics;
 mod config;
 mod deterministic;
 mod imports;
@@ -165,8 +165,7 @@ fn complex_loop(mut config: crate::Config) -> Result<()> {
     let f: TypedFunction<(i32, i32), i32> =
         instance.exports.get_typed_function(&mut store, "add_to")?;
 
-    // FIXME: Since now a metering error is signaled with an `unreachable`, it is impossible to verify
-    // the error type. Fix this later.
-    f.call(&mut store, 10_000_000, 4).unwrap_err();
+    let err = f.call(&mut store, 10_000_000, 4).unwrap_err();
+    assert_eq!(err.trap_code(), Some(TrapCode::OutOfGas));
     Ok(())
 }
@@ -277,14 +277,10 @@ fn trap_start_function_import(config: crate::Config) -> Result<()> {
     .err()
     .unwrap();
     match err {
-        InstantiationError::Link(_)
-        | InstantiationError::DifferentStores
-        | InstantiationError::CpuFeature(_) => {
-            panic!("It should be a start error")
-        }
         InstantiationError::Start(err) => {
             assert_eq!(err.message(), "user trap");
         }
+        _ => panic!("It should be a start error"),
     }
 
     Ok(())
@@ -323,17 +319,55 @@ fn rust_panic_import(config: crate::Config) -> Result<()> {
     .unwrap_err();
     assert_eq!(err.downcast_ref::<&'static str>(), Some(&"this is a panic"));
 
-    // TODO: Reenable this (disabled as it was not working with llvm/singlepass)
-    // It doesn't work either with cranelift and `--test-threads=1`.
-    // let func = instance.exports.get_function("bar")?.clone();
-    // let err = panic::catch_unwind(AssertUnwindSafe(|| {
-    //     drop(func.call(&[]));
-    // }))
-    // .unwrap_err();
-    // assert_eq!(
-    //     err.downcast_ref::<&'static str>(),
-    //     Some(&"this is another panic")
-    // );
+    let func = instance.exports.get_function("bar")?.clone();
+    let err = panic::catch_unwind(AssertUnwindSafe(|| {
+        drop(func.call(&mut store, &[]));
+    }))
+    .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<&'static str>(),
+        Some(&"this is another panic")
+    );
+    Ok(())
+}
+
+#[compiler_test(traps)]
+fn rust_panic_in_libcall(config: crate::Config) -> Result<()> {
+    struct PanickingLimiter;
+
+    impl ResourceLimiter for PanickingLimiter {
+        fn memory_growing(
+            &mut self,
+            _current: Pages,
+            _desired: Pages,
+            _maximum: Option<Pages>,
+        ) -> bool {
+            panic!("this is a panic")
+        }
+    }
+
+    let mut store = config.store();
+    store.set_limiter(Some(Box::new(PanickingLimiter)));
+    let binary = r#"
+        (module $a
+            (memory 1)
+            (func (export "grow") (result i32)
+                (memory.grow (i32.const 1)))
+        )
+    "#;
+
+    let module = Module::new(&store, &binary)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let grow: TypedFunction<(), i32> = instance.exports.get_typed_function(&mut store, "grow")?;
+    let err = panic::catch_unwind(AssertUnwindSafe(|| {
+        drop(grow.call(&mut store));
+    }))
+    .unwrap_err();
+    assert_eq!(err.downcast_ref::<&'static str>(), Some(&"this is a panic"));
+
+    // The store is still usable after the panic
+    store.set_limiter(None);
+    assert_eq!(grow.call(&mut store)?, 1);
     Ok(())
 }
 
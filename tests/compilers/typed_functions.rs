@@ -34,6 +34,43 @@ fn long_f_dynamic(values: &[Value]) -> Result<Vec<Value>, RuntimeError> {
     )])
 }
 
+#[compiler_test(typed_functions)]
+fn typed_function_works_for_mixed_result_pairs(config: crate::Config) -> anyhow::Result<()> {
+    let mut store = config.store();
+    let wat = r#"(module
+        (func $split (import "env" "split") (param f64) (result i64 f64))
+        (func (export "split") (param f64) (result i64 f64)
+           (call $split (local.get 0)))
+        (func (export "swap") (param i32 f32) (result f32 i32)
+           (local.get 1)
+           (local.get 0))
+        (func (export "floor") (param f32 f64) (result f32 f64)
+           (f32.floor (local.get 0))
+           (f64.floor (local.get 1)))
+)"#;
+    let module = Module::new(&store, wat).unwrap();
+    let import_object = imports! {
+        "env" => {
+            "split" => Function::new_typed(&mut store, |x: f64| (x.trunc() as i64, x.fract())),
+        },
+    };
+    let instance = Instance::new(&mut store, &module, &import_object)?;
+
+    let split: TypedFunction<f64, (i64, f64)> =
+        instance.exports.get_typed_function(&mut store, "split")?;
+    assert_eq!(split.call(&mut store, 2.5)?, (2, 0.5));
+
+    let swap: TypedFunction<(i32, f32), (f32, i32)> =
+        instance.exports.get_typed_function(&mut store, "swap")?;
+    assert_eq!(swap.call(&mut store, 1, 2.5)?, (2.5, 1));
+
+    let floor: TypedFunction<(f32, f64), (f32, f64)> =
+        instance.exports.get_typed_function(&mut store, "floor")?;
+    assert_eq!(floor.call(&mut store, -1.5, 2.75)?, (-2.0, 2.0));
+
+    Ok(())
+}
+
 #[compiler_test(typed_functions)]
 fn typed_function_works_for_wasm(config: crate::Config) -> anyhow::Result<()> {
     let mut store = config.store();
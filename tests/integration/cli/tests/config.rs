@@ -0,0 +1,39 @@
+//! Tests for the `config` subcommand
+
+#![cfg(unix)]
+
+use std::process::Command;
+use wasmer_integration_tests_cli::get_wasmer_path;
+
+#[test]
+fn config_prints_the_build_info_without_wasmer_dir() -> anyhow::Result<()> {
+    let output = Command::new(get_wasmer_path())
+        .arg("config")
+        .arg("--cache-dir")
+        .arg("--features")
+        .arg("--compilers")
+        .env_remove("WASMER_DIR")
+        .env("WASMER_CACHE_DIR", "/tmp/wasmer-cache")
+        .output()?;
+    assert!(
+        output.status.success(),
+        "wasmer config failed with: {}",
+        std::str::from_utf8(&output.stderr).unwrap()
+    );
+    let stdout = std::str::from_utf8(&output.stdout).unwrap();
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3, "unexpected output: {}", stdout);
+    assert!(lines[0].starts_with("/tmp/wasmer-cache"));
+    assert!(lines[1].split(' ').any(|feature| feature == "compiler"));
+    assert!(!lines[2].is_empty());
+
+    let output = Command::new(get_wasmer_path())
+        .arg("config")
+        .arg("--pkg-config")
+        .env("WASMER_DIR", "/opt/wasmer")
+        .output()?;
+    let stdout = std::str::from_utf8(&output.stdout).unwrap();
+    assert!(stdout.contains("Cflags: -I/opt/wasmer/include"));
+    assert!(stdout.contains("Libs: -L/opt/wasmer/lib -lwasmer"));
+    Ok(())
+}
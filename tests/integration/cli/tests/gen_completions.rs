@@ -0,0 +1,38 @@
+//! Tests for the `gen-completions` subcommand
+
+use anyhow::bail;
+use std::process::Command;
+use wasmer_integration_tests_cli::get_wasmer_path;
+
+#[test]
+fn gen_completions_covers_the_subcommands() -> anyhow::Result<()> {
+    for shell in &["bash", "zsh", "fish", "powershell"] {
+        let output = Command::new(get_wasmer_path())
+            .arg("gen-completions")
+            .arg(shell)
+            .output()?;
+        if !output.status.success() {
+            bail!(
+                "gen-completions {} failed with: {}",
+                shell,
+                std::str::from_utf8(&output.stderr).unwrap()
+            );
+        }
+        let completions = std::str::from_utf8(&output.stdout).unwrap();
+        for needle in &["run", "compile", "--invoke"] {
+            assert!(
+                completions.contains(needle),
+                "`{}` is missing from the {} completions",
+                needle,
+                shell
+            );
+        }
+    }
+
+    let output = Command::new(get_wasmer_path())
+        .arg("gen-completions")
+        .arg("tcsh")
+        .output()?;
+    assert!(!output.status.success());
+    Ok(())
+}
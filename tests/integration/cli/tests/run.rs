@@ -1,7 +1,9 @@
 //! Basic tests for the `run` subcommand
 
 use anyhow::bail;
-use std::process::Command;
+use std::fs::File;
+use std::io::Write;
+use std::process::{Command, Stdio};
 use wasmer_integration_tests_cli::{get_wasmer_path, ASSET_PATH, C_ASSET_PATH};
 
 fn wasi_test_wasm_path() -> String {
@@ -42,6 +44,33 @@ fn run_wasi_works() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn run_wasi_from_stdin_works() -> anyhow::Result<()> {
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg("-")
+        .arg("--")
+        .arg("-e")
+        .arg("print(3 * (4 + 5))")
+        .stdin(Stdio::from(File::open(wasi_test_wasm_path())?))
+        .output()?;
+
+    if !output.status.success() {
+        bail!(
+            "running from stdin failed with: stdout: {}\n\nstderr: {}",
+            std::str::from_utf8(&output.stdout)
+                .expect("stdout is not utf8! need to handle arbitrary bytes"),
+            std::str::from_utf8(&output.stderr)
+                .expect("stderr is not utf8! need to handle arbitrary bytes")
+        );
+    }
+
+    let stdout_output = std::str::from_utf8(&output.stdout).unwrap();
+    assert_eq!(stdout_output, "27\n");
+
+    Ok(())
+}
+
 #[test]
 fn run_no_imports_wasm_works() -> anyhow::Result<()> {
     let output = Command::new(get_wasmer_path())
@@ -112,6 +141,346 @@ fn run_invoke_works_with_nomain_wasi() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn run_exits_with_the_guest_exit_code() -> anyhow::Result<()> {
+    let wasi_wat = r#"
+    (module
+        (import "wasi_unstable" "proc_exit" (func $proc_exit (param i32)))
+        (func $_start (call $proc_exit (i32.const 42)))
+        (func $trap unreachable)
+        (memory 1)
+        (export "memory" (memory 0))
+        (export "_start" (func $_start))
+        (export "trap" (func $trap))
+      )
+    "#;
+
+    let random = rand::random::<u64>();
+    let module_file = std::env::temp_dir().join(&format!("{random}.wat"));
+    std::fs::write(&module_file, wasi_wat.as_bytes()).unwrap();
+
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(&module_file)
+        .output()?;
+    assert_eq!(output.status.code(), Some(42));
+    assert!(output.stderr.is_empty());
+
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg("--invoke")
+        .arg("trap")
+        .arg(&module_file)
+        .output()?;
+    assert_eq!(output.status.code(), Some(134));
+
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg("--invoke")
+        .arg("missing")
+        .arg(&module_file)
+        .output()?;
+    assert_eq!(output.status.code(), Some(1));
+
+    std::fs::remove_file(&module_file).unwrap();
+    Ok(())
+}
+
+#[test]
+fn run_writes_a_coredump_on_trap() -> anyhow::Result<()> {
+    let wat = r#"
+    (module
+        (memory 1)
+        (global $g (mut i32) (i32.const 7))
+        (data (i32.const 16) "coredumped")
+        (func (export "crash") (param i32) (result i32)
+            (global.set $g (local.get 0))
+            (i32.div_u (i32.const 1) (i32.const 0)))
+      )
+    "#;
+
+    let random = rand::random::<u64>();
+    let module_file = std::env::temp_dir().join(&format!("{random}.wat"));
+    let coredump_file = std::env::temp_dir().join(&format!("{random}.core.wasm"));
+    std::fs::write(&module_file, wat.as_bytes()).unwrap();
+
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(&module_file)
+        .arg("--coredump-on-trap")
+        .arg(&coredump_file)
+        .arg("--invoke")
+        .arg("crash")
+        .arg("42")
+        .output()?;
+    assert_eq!(output.status.code(), Some(134));
+
+    let coredump = std::fs::read(&coredump_file)?;
+    assert!(coredump.starts_with(b"\0asm"));
+    for needle in [&b"corestack"[..], b"coreinstances", b"coredumped"] {
+        assert!(coredump
+            .windows(needle.len())
+            .any(|window| window == needle));
+    }
+    // The mutable global, `(global (mut i32) (i32.const 42))`
+    assert!(coredump
+        .windows(5)
+        .any(|window| window == [0x7f, 0x01, 0x41, 42, 0x0b]));
+
+    std::fs::remove_file(&module_file).unwrap();
+    std::fs::remove_file(&coredump_file).unwrap();
+    Ok(())
+}
+
+#[test]
+fn run_invoke_converts_arguments_and_prints_json() -> anyhow::Result<()> {
+    let wat = r#"
+    (module
+        (memory (export "memory") 1)
+        (global $next (mut i32) (i32.const 1024))
+        (func (export "malloc") (param $size i32) (result i32)
+          global.get $next
+          global.get $next
+          local.get $size
+          i32.add
+          global.set $next)
+        (func (export "negate") (param i32 i64) (result i32 i64)
+          i32.const 0
+          local.get 0
+          i32.sub
+          i64.const 0
+          local.get 1
+          i64.sub)
+        ;; Returns the length of the string and its first byte
+        (func (export "first") (param $ptr i32) (param $len i32) (result i32 i32)
+          local.get $len
+          local.get $ptr
+          i32.load8_u))
+    "#;
+
+    let random = rand::random::<u64>();
+    let module_file = std::env::temp_dir().join(&format!("{random}.wat"));
+    std::fs::write(&module_file, wat.as_bytes()).unwrap();
+
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(&module_file)
+        .arg("--output")
+        .arg("json")
+        .arg("--invoke")
+        .arg("negate")
+        .arg("-5")
+        .arg("0x10")
+        .output()?;
+    let stdout = std::str::from_utf8(&output.stdout).unwrap();
+    if !output.status.success() {
+        bail!(
+            "wasmer run --invoke negate failed: {}",
+            std::str::from_utf8(&output.stderr).unwrap()
+        );
+    }
+    assert_eq!(stdout.trim(), "[5,-16]");
+
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(&module_file)
+        .arg("--invoke")
+        .arg("first")
+        .arg("hello")
+        .output()?;
+    let stdout = std::str::from_utf8(&output.stdout).unwrap();
+    if !output.status.success() {
+        bail!(
+            "wasmer run --invoke first failed: {}",
+            std::str::from_utf8(&output.stderr).unwrap()
+        );
+    }
+    assert_eq!(stdout.trim(), "5 104");
+
+    std::fs::remove_file(&module_file).unwrap();
+    Ok(())
+}
+
+#[test]
+fn run_interactive_keeps_the_instance_state() -> anyhow::Result<()> {
+    let wat = r#"
+    (module
+        (global $count (export "count") (mut i32) (i32.const 0))
+        (func (export "incr") (param $by i32) (result i32)
+          global.get $count
+          local.get $by
+          i32.add
+          global.set $count
+          global.get $count))
+    "#;
+
+    let random = rand::random::<u64>();
+    let module_file = std::env::temp_dir().join(&format!("{random}.wat"));
+    std::fs::write(&module_file, wat.as_bytes()).unwrap();
+
+    let mut child = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg("--interactive")
+        .arg(&module_file)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"incr 2\nincr -5\nmissing\n:global count\n")?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "wasmer run --interactive failed: {}",
+            std::str::from_utf8(&output.stderr).unwrap()
+        );
+    }
+    assert_eq!(std::str::from_utf8(&output.stdout).unwrap(), "2\n-3\n-3\n");
+    assert!(std::str::from_utf8(&output.stderr)
+        .unwrap()
+        .contains("no exported function `missing`"));
+
+    std::fs::remove_file(&module_file).unwrap();
+    Ok(())
+}
+
+#[test]
+fn invoke_and_inspect_use_the_name_section() -> anyhow::Result<()> {
+    let wat = r#"
+    (module
+        (import "env" "log" (func $log_message (param i32)))
+        (func $add_i32 (export "add") (param i32 i32) (result i32)
+          local.get 0
+          local.get 1
+          i32.add))
+    "#;
+
+    let random = rand::random::<u64>();
+    let module_file = std::env::temp_dir().join(&format!("{random}.wat"));
+    std::fs::write(&module_file, wat.as_bytes()).unwrap();
+
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(&module_file)
+        .arg("--stub-missing-imports")
+        .arg("--invoke")
+        .arg("add")
+        .arg("1")
+        .output()?;
+    assert!(!output.status.success());
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(
+        stderr.contains("`add` (`add_i32` in the name section): [I32, I32] -> [I32]"),
+        "unexpected error: {}",
+        stderr
+    );
+
+    let output = Command::new(get_wasmer_path())
+        .arg("inspect")
+        .arg(&module_file)
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "wasmer inspect failed: {}",
+            std::str::from_utf8(&output.stderr).unwrap()
+        );
+    }
+    let stdout = std::str::from_utf8(&output.stdout).unwrap();
+    assert!(stdout.contains("\"env\".\"log\": [I32] -> [] (`log_message` in the name section)"));
+    assert!(stdout.contains("\"add\": [I32, I32] -> [I32] (`add_i32` in the name section)"));
+
+    std::fs::remove_file(&module_file).unwrap();
+    Ok(())
+}
+
+#[test]
+fn run_links_imports_from_other_modules() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let helper = dir.path().join("helper.wat");
+    std::fs::write(
+        &helper,
+        r#"(module (func (export "double") (param i32) (result i32)
+          local.get 0
+          i32.const 2
+          i32.mul))"#,
+    )?;
+    // Linked modules can import from the ones linked before them
+    let math = dir.path().join("math.wat");
+    std::fs::write(
+        &math,
+        r#"(module
+          (import "helper" "double" (func $double (param i32) (result i32)))
+          (func (export "quadruple") (param i32) (result i32)
+            local.get 0
+            call $double
+            call $double))"#,
+    )?;
+    let main = dir.path().join("main.wat");
+    std::fs::write(
+        &main,
+        r#"(module
+          (import "helper" "double" (func $double (param i32) (result i32)))
+          (import "m" "quadruple" (func $quadruple (param i32) (result i32)))
+          (func (export "octuple") (param i32) (result i32)
+            local.get 0
+            call $quadruple
+            call $double))"#,
+    )?;
+
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(&main)
+        .arg("--link")
+        .arg(&helper)
+        .arg("--link")
+        .arg(format!("m={}", math.display()))
+        .arg("--invoke")
+        .arg("octuple")
+        .arg("3")
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "wasmer run --link failed: {}",
+            std::str::from_utf8(&output.stderr).unwrap()
+        );
+    }
+    assert_eq!(std::str::from_utf8(&output.stdout).unwrap().trim(), "24");
+    Ok(())
+}
+
+#[test]
+fn run_print_stats() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let module = dir.path().join("add.wat");
+    std::fs::write(
+        &module,
+        r#"(module (func (export "add_one") (param i32) (result i32)
+          local.get 0
+          i32.const 1
+          i32.add))"#,
+    )?;
+
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(&module)
+        .arg("--print-stats")
+        .arg("--invoke")
+        .arg("add_one")
+        .arg("1")
+        .output()?;
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    if !output.status.success() {
+        bail!("wasmer run --print-stats failed: {}", stderr);
+    }
+    assert_eq!(std::str::from_utf8(&output.stdout).unwrap().trim(), "2");
+    assert!(stderr.contains("`add_one`: wall time"), "{}", stderr);
+    assert!(stderr.contains(", 4 instructions"), "{}", stderr);
+    Ok(())
+}
+
 #[test]
 fn run_no_start_wasm_report_error() -> anyhow::Result<()> {
     let output = Command::new(get_wasmer_path())
@@ -0,0 +1,51 @@
+//! Tests for the `strip` subcommand
+
+use anyhow::bail;
+use std::process::Command;
+use wasmer_integration_tests_cli::get_wasmer_path;
+
+#[test]
+fn strip_removes_custom_sections() -> anyhow::Result<()> {
+    // A module with a `name` section naming the module `m`, and a
+    // `version` custom section holding "1.2.0"
+    let wasm = b"\0asm\x01\0\0\0\0\x0d\x07version1.2.0\0\x09\x04name\0\x02\x01m";
+    let dir = tempfile::tempdir()?;
+    let module_file = dir.path().join("module.wasm");
+    let stripped_file = dir.path().join("stripped.wasm");
+    std::fs::write(&module_file, &wasm[..])?;
+
+    let output = Command::new(get_wasmer_path())
+        .arg("strip")
+        .arg(&module_file)
+        .arg("-o")
+        .arg(&stripped_file)
+        .arg("--keep")
+        .arg("version")
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "wasmer strip failed with: {}",
+            std::str::from_utf8(&output.stderr).unwrap()
+        );
+    }
+    assert_eq!(
+        std::fs::read(&stripped_file)?,
+        &b"\0asm\x01\0\0\0\0\x0d\x07version1.2.0"[..]
+    );
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(
+        stderr.contains("Stripped 11 B"),
+        "unexpected output: {}",
+        stderr
+    );
+
+    // Without an output file, the module is stripped in place
+    let output = Command::new(get_wasmer_path())
+        .arg("strip")
+        .arg(&module_file)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(std::fs::read(&module_file)?, &b"\0asm\x01\0\0\0"[..]);
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+//! Basic tests for the `wast` subcommand
+
+use anyhow::bail;
+use std::process::Command;
+use wasmer_integration_tests_cli::get_wasmer_path;
+
+const PASSING_WAST: &str = r#"
+(module $math
+  (func (export "add") (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    i32.add))
+(register "math" $math)
+(module
+  (import "math" "add" (func $add (param i32 i32) (result i32)))
+  (func (export "double") (param i32) (result i32)
+    local.get 0
+    local.get 0
+    call $add)
+  (func (export "trap")
+    unreachable))
+(assert_return (invoke "double" (i32.const 21)) (i32.const 42))
+(assert_trap (invoke "trap") "unreachable")
+"#;
+
+const FAILING_WAST: &str = r#"
+(module
+  (func (export "one") (result i32)
+    i32.const 1))
+(assert_return (invoke "one") (i32.const 2))
+"#;
+
+#[test]
+fn wast_runs_the_files_of_directories() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("linking.wast"), PASSING_WAST)?;
+    std::fs::create_dir(dir.path().join("nested"))?;
+    std::fs::write(dir.path().join("nested/again.wast"), PASSING_WAST)?;
+    std::fs::write(dir.path().join("notes.txt"), "not a wast file")?;
+
+    let output = Command::new(get_wasmer_path())
+        .arg("wast")
+        .arg(dir.path())
+        .output()?;
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    if !output.status.success() {
+        bail!("wasmer wast failed: {}", stderr);
+    }
+    assert!(stderr.contains("succeeded for 2 files"));
+
+    Ok(())
+}
+
+#[test]
+fn wast_reports_the_failing_files() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let passing = dir.path().join("passing.wast");
+    let failing = dir.path().join("failing.wast");
+    std::fs::write(&passing, PASSING_WAST)?;
+    std::fs::write(&failing, FAILING_WAST)?;
+
+    let output = Command::new(get_wasmer_path())
+        .arg("wast")
+        .arg(&failing)
+        .arg(&passing)
+        .output()?;
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(stderr.contains("1 of 2 wast files failed"), "{}", stderr);
+
+    Ok(())
+}